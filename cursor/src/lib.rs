@@ -1,6 +1,5 @@
 mod tests;
 
-use std::cmp::{min};
 use std::str::Chars;
 use std::slice::Iter;
 
@@ -55,7 +54,8 @@ impl<'a> Cursor<'a> {
     pub fn bump_n(&mut self, n: usize) -> char {
         match self.chars.nth(n) {
             Some(c) => {
-                self.eaten_len = min(self.eaten_len + n + 1, self.input.len());
+                // in bytes, so that `input[..eaten_len]` is on a char boundary
+                self.eaten_len = self.input.len() - self.chars.as_str().len();
                 #[cfg(debug_assertions)]
                 {
                     self.prev = c;
@@ -77,6 +77,7 @@ impl<'a> Cursor<'a> {
         self.bump_n(0)
     }
 
+    /// in bytes
     pub fn eaten_len(&self) -> usize {
         self.eaten_len
     }
@@ -128,7 +129,7 @@ impl<'a> Cursor<'a> {
         let mut len = 0usize;
         let mut has_digit: bool = false;
         while self.next().is_digit(radix) || self.next() == '_' {
-            has_digit = self.next() != '_';
+            has_digit |= self.next() != '_';
             self.bump();
             len += 1;
         }
@@ -150,14 +151,25 @@ impl<'a> Cursor<'a> {
         len
     }
 
-    /// return true if the next character is ascii character
+    /// return true if the next character is a character or a valid escape sequence:
+    /// `\n`, `\x41`, `\u{1F600}` etc. The value of `\u{...}` is not checked.
     pub fn eat_ascii_character(&mut self) -> bool {
         if self.next() == '\\' {
-            if "nrt\\0'\"".contains(self.nth(1)) {
-                self.bump_n(1);
-                true
-            } else {
-                false
+            match self.nth(1) {
+                c if "nrt\\0'\"".contains(c) => {
+                    self.bump_n(1);
+                    true
+                }
+                'x' if self.nth(2).is_digit(8) && self.nth(3).is_ascii_hexdigit() => {
+                    self.bump_n(3);
+                    true
+                }
+                'u' if self.nth(2) == '{' => {
+                    self.bump_n(2);
+                    let len = self.eat_digits(16);
+                    (1..=6).contains(&len) && self.bump() == '}'
+                }
+                _ => false,
             }
         } else {
             self.bump();
@@ -226,6 +238,6 @@ pub fn is_id_continue(c: char) -> bool {
     // We also add fast-path for ascii idents
     match c {
         'a'..='z' | 'A'..='Z' | '0'..='9' | '_' => true,
-        c => (c > '\x7f' && unicode_xid::UnicodeXID::is_xid_start(c)),
+        c => (c > '\x7f' && unicode_xid::UnicodeXID::is_xid_continue(c)),
     }
 }
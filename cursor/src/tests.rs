@@ -19,5 +19,20 @@ mod cursor_tests {
         assert_eq!(cursor.eat_id(), 5);
         assert_eq!(cursor.bump(), '+');
         assert_eq!(cursor.eat_id(), 3);
+
+        // XID_Continue, ARABIC-INDIC DIGIT THREE is not XID_Start
+        let mut cursor = Cursor::new("café变量\u{0663} x");
+        assert_eq!(cursor.eat_id(), 7);
+        assert_eq!(cursor.eaten_len(), 13);
+    }
+
+    #[test]
+    fn eat_digits_with_underscore_test() {
+        // `0x1_i32`, the underscores may also come after the digits
+        let mut cursor = Cursor::new("1_i32");
+        assert!(cursor.eat_digits_with_underscore(16));
+        assert_eq!(cursor.eaten_len(), 2);
+        let mut cursor = Cursor::new("__ ");
+        assert!(!cursor.eat_digits_with_underscore(10));
     }
 }
@@ -0,0 +1,206 @@
+//! Call graph of a module, built from the CFGs of its functions.
+//!
+//! An indirect call, e.g. a call of a fn pointer or a method of a trait object, may call any
+//! function whose address is taken, so it is an edge to each of them. The address of a
+//! function is taken when its label is used other than as the callee of a call, or when a
+//! vtable which contains it is used.
+//!
+//! Functions reused from the incremental cache only have their direct callees.
+use crate::ir::cfg::CFGIR;
+use crate::ir::{IRInst, Operand};
+use indexmap::{IndexMap, IndexSet};
+use std::fmt::{Display, Formatter};
+
+pub struct CallGraph {
+    /// function -> functions it may call, in order of first call
+    calls: IndexMap<String, IndexSet<String>>,
+    /// function -> functions whose address it takes
+    refs: IndexMap<String, IndexSet<String>>,
+    /// `main`, `pub` functions and functions from the cache, which may be called from outside
+    roots: IndexSet<String>,
+}
+
+impl CallGraph {
+    pub fn new(cfg_ir: &CFGIR) -> CallGraph {
+        let mut calls: IndexMap<String, IndexSet<String>> = IndexMap::new();
+        let mut refs: IndexMap<String, IndexSet<String>> = IndexMap::new();
+        let mut roots = IndexSet::new();
+        let mut indirect_callers = vec![];
+        for cfg in cfg_ir.cfgs.iter() {
+            let name = &cfg.func_name;
+            let fn_calls = calls.entry(name.clone()).or_default();
+            let fn_refs = refs.entry(name.clone()).or_default();
+            if cfg.func_is_global || name == "main" {
+                roots.insert(name.clone());
+            }
+            for inst in cfg.iter_inst() {
+                let mut operands = inst.src_operands();
+                if let IRInst::Call { callee, .. } = inst {
+                    match callee {
+                        Operand::FnLabel(f) => {
+                            fn_calls.insert(f.clone());
+                        }
+                        _ => indirect_callers.push(name.clone()),
+                    }
+                    operands.remove(0);
+                }
+                for operand in operands {
+                    match operand {
+                        Operand::FnLabel(f) => {
+                            fn_refs.insert(f.clone());
+                        }
+                        Operand::Place(place) => {
+                            if let Some(methods) = cfg_ir.vtables.get(place.label.as_str()) {
+                                fn_refs.extend(methods.iter().cloned());
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+            }
+        }
+        for f in cfg_ir.precompiled_fns.iter() {
+            calls.insert(f.name.clone(), f.callees.iter().cloned().collect());
+            refs.insert(f.name.clone(), IndexSet::new());
+            roots.insert(f.name.clone());
+        }
+        let address_taken: IndexSet<String> = refs.values().flatten().cloned().collect();
+        for caller in indirect_callers {
+            calls[&caller].extend(address_taken.iter().cloned());
+        }
+        CallGraph { calls, refs, roots }
+    }
+
+    /// Functions which `f` may call
+    pub fn callees(&self, f: &str) -> impl Iterator<Item = &str> {
+        self.calls.get(f).into_iter().flatten().map(String::as_str)
+    }
+
+    /// Functions which may call `f`, `f` may be a function of the runtime
+    pub fn callers<'a>(&'a self, f: &'a str) -> impl Iterator<Item = &'a str> {
+        self.calls
+            .iter()
+            .filter(move |(_, callees)| callees.contains(f))
+            .map(|(caller, _)| caller.as_str())
+    }
+
+    /// Functions which are neither roots nor called or referenced by a reachable function,
+    /// they can be removed.
+    pub fn unreachable_fns(&self) -> Vec<&str> {
+        let mut reachable: IndexSet<&str> = self.roots.iter().map(String::as_str).collect();
+        let mut i = 0;
+        while let Some(&f) = reachable.get_index(i) {
+            let used = self.calls.get(f).into_iter().chain(self.refs.get(f)).flatten();
+            reachable.extend(used.map(String::as_str));
+            i += 1;
+        }
+        self.calls
+            .keys()
+            .map(String::as_str)
+            .filter(|f| !reachable.contains(f))
+            .collect()
+    }
+
+    /// Groups of functions which call each other, including single functions which call
+    /// themselves. Functions of a group are in the order of the graph.
+    pub fn recursion_cycles(&self) -> Vec<Vec<&str>> {
+        let mut tarjan = Tarjan {
+            graph: self,
+            index: IndexMap::new(),
+            low_link: vec![],
+            stack: vec![],
+            on_stack: vec![],
+            components: vec![],
+        };
+        for f in self.calls.keys() {
+            if !tarjan.index.contains_key(f.as_str()) {
+                tarjan.visit(f);
+            }
+        }
+        let mut cycles: Vec<Vec<&str>> = tarjan
+            .components
+            .into_iter()
+            .filter(|c| c.len() > 1 || self.callees(c[0]).any(|callee| callee == c[0]))
+            .map(|mut c| {
+                c.sort_by_key(|f| self.calls.get_index_of(*f));
+                c
+            })
+            .collect();
+        cycles.sort_by_key(|c| self.calls.get_index_of(c[0]));
+        cycles
+    }
+}
+
+/// Tarjan's algorithm of strongly connected components
+struct Tarjan<'g> {
+    graph: &'g CallGraph,
+    /// function -> order of visit
+    index: IndexMap<&'g str, usize>,
+    low_link: Vec<usize>,
+    stack: Vec<&'g str>,
+    on_stack: Vec<bool>,
+    components: Vec<Vec<&'g str>>,
+}
+
+impl<'g> Tarjan<'g> {
+    fn visit(&mut self, f: &'g str) -> usize {
+        let index = self.index.len();
+        self.index.insert(f, index);
+        self.low_link.push(index);
+        self.on_stack.push(true);
+        self.stack.push(f);
+        let graph = self.graph;
+        // callees which are not defined in the module, e.g. runtime functions, are leaves
+        for callee in graph.callees(f).filter(|c| graph.calls.contains_key(*c)) {
+            match self.index.get(callee) {
+                None => {
+                    let low_link = self.visit(callee);
+                    self.low_link[index] = self.low_link[index].min(low_link);
+                }
+                Some(&i) if self.on_stack[i] => {
+                    self.low_link[index] = self.low_link[index].min(i);
+                }
+                Some(_) => {}
+            }
+        }
+        if self.low_link[index] == index {
+            let mut component = vec![];
+            loop {
+                let g = self.stack.pop().unwrap();
+                self.on_stack[self.index[g]] = false;
+                component.push(g);
+                if g == f {
+                    break;
+                }
+            }
+            self.components.push(component);
+        }
+        self.low_link[index]
+    }
+}
+
+/// ```text
+/// main -> fib, println
+/// fib -> fib
+/// recursive: fib
+/// unreachable: unused
+/// ```
+impl Display for CallGraph {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for (caller, callees) in self.calls.iter() {
+            write!(f, "{} ->", caller)?;
+            for (i, callee) in callees.iter().enumerate() {
+                write!(f, "{}{}", if i == 0 { " " } else { ", " }, callee)?;
+            }
+            writeln!(f)?;
+        }
+        for cycle in self.recursion_cycles() {
+            writeln!(f, "recursive: {}", cycle.join(", "))?;
+        }
+        let unreachable = self.unreachable_fns();
+        if !unreachable.is_empty() {
+            writeln!(f, "unreachable: {}", unreachable.join(", "))?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,197 @@
+//! Conditional compilation. An item with `#[cfg(P)]` is compiled only if the predicate `P`
+//! holds for the options of the compilation, otherwise it is dropped before the symbols are
+//! resolved. Compiled for riscv32:
+//!
+//! ```text
+//! #[cfg(target = "riscv64")]
+//! const WORD: usize = 8;
+//! #[cfg(not(target = "riscv64"))]  ->  const WORD: usize = 4;
+//! const WORD: usize = 4;
+//! ```
+//!
+//! A predicate is an option, `name` or `name = "value"`, or `all(P, ...)`, `any(P, ...)` or
+//! `not(P)` of predicates. The options are set by `--cfg name` or `--cfg name="value"`,
+//! e.g. `--cfg feature="float"`, and `target` is the target platform.
+use crate::analyser::scope::{ScopeArena, ScopeID};
+use crate::ast::expr::BlockExpr;
+use crate::ast::file::File;
+use crate::ast::item::{Attribute, Item};
+use crate::ast::stmt::Stmt;
+use crate::ast::visit::VisitorMut;
+use crate::ast::NodeId;
+use crate::rcc::{CompileError, ErrorKind, RccError};
+use std::collections::{HashMap, HashSet};
+
+/// Options which the predicates of `#[cfg(...)]` are evaluated against
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CfgOptions {
+    options: HashSet<(String, Option<String>)>,
+}
+
+impl CfgOptions {
+    pub fn new() -> CfgOptions {
+        CfgOptions::default()
+    }
+
+    pub fn insert(&mut self, name: &str, value: Option<&str>) {
+        self.options.insert((name.to_string(), value.map(|v| v.to_string())));
+    }
+
+    /// Add the option of `--cfg name` or `--cfg name="value"`. `target` is set by the
+    /// compiler.
+    pub fn insert_flag(&mut self, flag: &str) -> Result<(), RccError> {
+        let (name, value) = match flag.split_once('=') {
+            Some((name, value)) => {
+                let value = value.trim();
+                match value.strip_prefix('"').and_then(|v| v.strip_suffix('"')) {
+                    Some(value) => (name.trim(), Some(value)),
+                    None => return Err(invalid_flag(flag)),
+                }
+            }
+            None => (flag.trim(), None),
+        };
+        let is_ident = name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+            && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+        if !is_ident {
+            return Err(invalid_flag(flag));
+        }
+        if name == "target" {
+            return Err("`--cfg target` is not allowed, it is the target platform".into());
+        }
+        self.insert(name, value);
+        Ok(())
+    }
+
+    /// Whether `predicate` holds. All the nested predicates are checked, even if the result
+    /// is known earlier.
+    pub fn eval(&self, predicate: &Attribute) -> Result<bool, RccError> {
+        let args: Vec<bool> = predicate
+            .args
+            .iter()
+            .map(|arg| self.eval(arg))
+            .collect::<Result<_, _>>()?;
+        match (predicate.name.as_str(), &predicate.value) {
+            ("all", None) => Ok(args.into_iter().all(|arg| arg)),
+            ("any", None) => Ok(args.into_iter().any(|arg| arg)),
+            ("not", None) if args.len() == 1 => Ok(!args[0]),
+            ("not", None) => Err("`not` takes exactly one predicate".into()),
+            ("all" | "any" | "not", Some(_)) => {
+                Err(format!("invalid predicate `{}`", predicate).into())
+            }
+            (name, value) if predicate.args.is_empty() => {
+                Ok(self.options.contains(&(name.to_string(), value.clone())))
+            }
+            _ => Err(format!("invalid predicate `{}`", predicate).into()),
+        }
+    }
+}
+
+fn invalid_flag(flag: &str) -> RccError {
+    format!("invalid `--cfg` option {}, expected `name` or `name=\"value\"`", flag).into()
+}
+
+/// Replace the items with `#[cfg(...)]` by the items if their predicates hold, or drop them,
+/// in the file and in all the blocks.
+pub fn eval_cfgs(file: &mut File, options: &CfgOptions) -> Result<(), RccError> {
+    let mut evaluator = CfgEvaluator {
+        options,
+        scopes: &mut file.scopes,
+        stmt_lines: &mut file.stmt_lines,
+        error: None,
+    };
+    let mut i = 0;
+    while i < file.items.len() {
+        if evaluator.eval_item(&mut file.items[i], file.scope_id)? {
+            i += 1;
+        } else {
+            file.items.remove(i);
+            if i < file.item_tokens.len() {
+                file.item_tokens.remove(i);
+            }
+        }
+    }
+    defined_once(file.items.iter())?;
+    for item in file.items.iter_mut() {
+        evaluator.visit_item(item);
+    }
+    evaluator.error.map_or(Ok(()), Err)
+}
+
+/// An item can be defined under different predicates, but only one of them may hold
+fn defined_once<'a>(items: impl Iterator<Item = &'a Item>) -> Result<(), RccError> {
+    let mut names = HashSet::new();
+    for name in items.flat_map(|item| item.defined_names()) {
+        if !names.insert(name) {
+            let msg = format!("the name `{}` is defined multiple times", name);
+            return Err(CompileError::new(ErrorKind::Resolve, msg)
+                .code("E0428")
+                .note(format!("`{}` must be defined only once in this scope", name))
+                .into());
+        }
+    }
+    Ok(())
+}
+
+struct CfgEvaluator<'a> {
+    options: &'a CfgOptions,
+    scopes: &'a mut ScopeArena,
+    /// lines of the statements of the blocks, the lines of the dropped items are removed
+    stmt_lines: &'a mut HashMap<NodeId, Vec<u32>>,
+    /// the first error
+    error: Option<RccError>,
+}
+
+impl CfgEvaluator<'_> {
+    /// Unwrap `item` if it has `#[cfg(...)]` and its predicates hold, and add it to the
+    /// scope. Return `false` if the item is dropped.
+    fn eval_item(&mut self, item: &mut Item, scope_id: ScopeID) -> Result<bool, RccError> {
+        if !matches!(item, Item::Cfg(_)) {
+            return Ok(true);
+        }
+        while let Item::Cfg(item_cfg) = item {
+            if !self.options.eval(item_cfg.predicate())? {
+                return Ok(false);
+            }
+            *item = std::mem::replace(item_cfg.item.as_mut(), Item::Static);
+        }
+        self.scopes.get_mut(scope_id).add_typedef(item);
+        Ok(true)
+    }
+
+    fn eval_stmts(&mut self, block_expr: &mut BlockExpr) -> Result<(), RccError> {
+        let mut i = 0;
+        while i < block_expr.stmts.len() {
+            let keep = match &mut block_expr.stmts[i] {
+                Stmt::Item(item) => self.eval_item(item, block_expr.scope_id)?,
+                _ => true,
+            };
+            if keep {
+                i += 1;
+                continue;
+            }
+            block_expr.stmts.remove(i);
+            if let Some(lines) = self.stmt_lines.get_mut(&block_expr.node_id) {
+                if i < lines.len() {
+                    lines.remove(i);
+                }
+            }
+        }
+        defined_once(block_expr.stmts.iter().filter_map(|stmt| match stmt {
+            Stmt::Item(item) => Some(item),
+            _ => None,
+        }))
+    }
+}
+
+impl VisitorMut for CfgEvaluator<'_> {
+    fn visit_block_expr(&mut self, block_expr: &mut BlockExpr) {
+        if self.error.is_some() {
+            return;
+        }
+        if let Err(e) = self.eval_stmts(block_expr) {
+            self.error = Some(e);
+            return;
+        }
+        self.walk_block_expr(block_expr);
+    }
+}
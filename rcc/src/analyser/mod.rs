@@ -3,8 +3,24 @@
 //! - Type check
 //! - Flow control check
 //!
+use crate::analyser::sym_resolver::SymbolResolver;
+use crate::ast::AST;
+use crate::parser::parse_source;
+use crate::rcc::RccError;
 
+pub mod cfg;
+pub mod callgraph;
 pub mod sym_resolver;
+pub mod mono;
 pub mod scope;
+pub mod type_table;
 #[cfg(test)]
 mod tests;
+
+/// Lex, parse and resolve symbols of a whole source file. The language server
+/// calls it again every time a document changes.
+pub fn analyse(input: &str) -> Result<AST, Vec<RccError>> {
+    let mut ast = parse_source(input)?;
+    SymbolResolver::new().visit_file(&mut ast.file).map_err(|e| vec![e])?;
+    Ok(ast)
+}
@@ -0,0 +1,134 @@
+//! Monomorphization: a generic function is compiled as one copy for each list of generic
+//! arguments it is called with, e.g. `add::<i32>` is the function `_RCG3add3i32E`.
+//!
+//! A copy gets new node ids and new scopes, so the types and variables of the instances of a
+//! function never mix.
+use crate::analyser::scope::ScopeArena;
+use crate::analyser::sym_resolver::TypeInfo;
+use crate::ast::expr::{
+    ArrayIndexExpr, BinOpExpr, BlockExpr, CallExpr, FieldAccessExpr, IfExpr, LitNumExpr,
+    LoopExpr, PathExpr, StructExpr, UnAryExpr,
+};
+use crate::ast::item::ItemFn;
+use crate::ast::types::{TypePtr, TypeAnnotation};
+use crate::ast::visit::VisitorMut;
+use crate::ast::NodeId;
+use crate::intern::Symbol;
+
+/// A generic argument, `T = i32`
+#[derive(Debug, Clone, PartialEq)]
+pub struct GenericArg {
+    pub name: String,
+    pub type_info: TypeInfo,
+    pub type_anno: TypeAnnotation,
+}
+
+/// Copy of the generic function `item_fn` named `name`, `T` in the signature is replaced
+/// with the annotation of its argument and is defined in the scope of the function block.
+pub fn instantiate(
+    item_fn: &ItemFn,
+    name: String,
+    args: &[GenericArg],
+    scopes: &mut ScopeArena,
+    node_count: &mut u32,
+) -> ItemFn {
+    let mut instance = item_fn.clone();
+    instance.name = name;
+    instance.generics.clear();
+    for param in instance.fn_params.params.iter_mut() {
+        subst_type_anno(&mut param._type, args);
+    }
+    subst_type_anno(&mut instance.ret_type, args);
+
+    let mut renewer = Renewer { scopes, node_count };
+    renewer.visit_block_expr(&mut instance.fn_block);
+    let scope = renewer.scopes.get_mut(instance.fn_block.scope_id);
+    for arg in args.iter() {
+        scope.types.insert(Symbol::intern(&arg.name), arg.type_info.clone());
+    }
+    instance
+}
+
+/// Replace `T` in `type_anno` with the annotation of its argument.
+pub fn subst_type_anno(type_anno: &mut TypeAnnotation, args: &[GenericArg]) {
+    match type_anno {
+        TypeAnnotation::Identifier(ident) => {
+            if let Some(arg) = args.iter().find(|arg| &arg.name == ident) {
+                *type_anno = arg.type_anno.clone();
+            }
+        }
+        TypeAnnotation::Ptr(TypePtr { type_anno, .. }) | TypeAnnotation::Vec(type_anno) => {
+            subst_type_anno(type_anno, args)
+        }
+        _ => {}
+    }
+}
+
+/// Gives new node ids and scopes to a copied function
+struct Renewer<'a> {
+    scopes: &'a mut ScopeArena,
+    node_count: &'a mut u32,
+}
+
+impl Renewer<'_> {
+    fn node_id(&mut self) -> NodeId {
+        *self.node_count += 1;
+        NodeId(*self.node_count - 1)
+    }
+}
+
+impl VisitorMut for Renewer<'_> {
+    fn visit_block_expr(&mut self, block_expr: &mut BlockExpr) {
+        block_expr.scope_id = self.scopes.clone_scope(block_expr.scope_id);
+        block_expr.node_id = self.node_id();
+        self.walk_block_expr(block_expr);
+    }
+
+    fn visit_path_expr(&mut self, path_expr: &mut PathExpr) {
+        path_expr.node_id = self.node_id();
+    }
+
+    fn visit_lit_num_expr(&mut self, lit_num_expr: &mut LitNumExpr) {
+        lit_num_expr.node_id = self.node_id();
+    }
+
+    fn visit_unary_expr(&mut self, unary_expr: &mut UnAryExpr) {
+        unary_expr.node_id = self.node_id();
+        self.walk_unary_expr(unary_expr);
+    }
+
+    fn visit_bin_op_expr(&mut self, bin_op_expr: &mut BinOpExpr) {
+        bin_op_expr.node_id = self.node_id();
+        self.walk_bin_op_expr(bin_op_expr);
+    }
+
+    fn visit_array_index_expr(&mut self, array_index_expr: &mut ArrayIndexExpr) {
+        array_index_expr.node_id = self.node_id();
+        self.walk_array_index_expr(array_index_expr);
+    }
+
+    fn visit_struct_expr(&mut self, struct_expr: &mut StructExpr) {
+        struct_expr.node_id = self.node_id();
+        self.walk_struct_expr(struct_expr);
+    }
+
+    fn visit_call_expr(&mut self, call_expr: &mut CallExpr) {
+        call_expr.node_id = self.node_id();
+        self.walk_call_expr(call_expr);
+    }
+
+    fn visit_field_access_expr(&mut self, field_access_expr: &mut FieldAccessExpr) {
+        field_access_expr.node_id = self.node_id();
+        self.walk_field_access_expr(field_access_expr);
+    }
+
+    fn visit_loop_expr(&mut self, loop_expr: &mut LoopExpr) {
+        loop_expr.node_id = self.node_id();
+        self.walk_loop_expr(loop_expr);
+    }
+
+    fn visit_if_expr(&mut self, if_expr: &mut IfExpr) {
+        if_expr.node_id = self.node_id();
+        self.walk_if_expr(if_expr);
+    }
+}
@@ -1,56 +1,28 @@
-use crate::analyser::sym_resolver::TypeInfo::*;
+use crate::analyser::sym_resolver::TypeInfo::{Bool, Char, LitNum, Str, Unknown};
 use crate::analyser::sym_resolver::{TypeInfo, VarInfo, VarKind};
-use crate::ast::expr::BlockExpr;
+use crate::analyser::type_table::{TypeSlot, TypeTable};
 use crate::ast::file::File;
 use crate::ast::item::{ExternalItem, FnSignature, Item, ItemStruct};
 use crate::ast::types::TypeLitNum::*;
-use crate::ir::var_name::temp_local_var;
-use crate::rcc::RccError;
-use lazy_static::lazy_static;
-use std::cell::RefCell;
-use std::cmp::Ordering;
+use crate::intern::Symbol;
+use crate::ir::var_name::{is_temp_var, temp_local_var};
 use std::collections::HashMap;
-use std::ops::Deref;
-use std::ptr::NonNull;
-use std::rc::Rc;
-
-lazy_static! {
-    pub static ref BULITIN_SCOPE: Scope = {
-        let mut s = Scope::new(0);
-        s.types.insert("bool".into(), Bool);
-        s.types.insert("char".into(), Char);
-        s.types.insert("str".into(), Str);
-        s.types.insert("f32".into(), LitNum(F32));
-        s.types.insert("f64".into(), LitNum(F64));
-        s.types.insert("i8".into(), LitNum(I8));
-        s.types.insert("i16".into(), LitNum(I16));
-        s.types.insert("i32".into(), LitNum(I32));
-        s.types.insert("i64".into(), LitNum(I64));
-        s.types.insert("i128".into(), LitNum(I128));
-        s.types.insert("isize".into(), LitNum(Isize));
-        s.types.insert("u8".into(), LitNum(U8));
-        s.types.insert("u16".into(), LitNum(U16));
-        s.types.insert("u32".into(), LitNum(U32));
-        s.types.insert("u64".into(), LitNum(U64));
-        s.types.insert("u128".into(), LitNum(U128));
-        s.types.insert("usize".into(), LitNum(Usize));
-        s
-    };
-}
 
 pub type ScopeID = u64;
 
+/// Id of the scope of builtin types, which is the father of the file scope
+pub const BUILTIN_SCOPE_ID: ScopeID = 0;
+
+#[derive(Clone)]
 pub struct Scope {
     pub scope_id: ScopeID,
-    father: Option<NonNull<Scope>>,
-    pub(crate) types: HashMap<String, TypeInfo>,
-    variables: HashMap<String, Vec<VarInfo>>,
+    father: Option<ScopeID>,
+    pub(crate) types: HashMap<Symbol, TypeInfo>,
+    variables: HashMap<Symbol, Vec<VarInfo>>,
     pub cur_stmt_id: u64,
     temp_count: u64,
 }
 
-unsafe impl std::marker::Sync for Scope {}
-
 impl Scope {
     pub fn new(scope_id: ScopeID) -> Scope {
         Scope {
@@ -63,147 +35,77 @@ impl Scope {
         }
     }
 
-    pub fn gen_temp_variable(&mut self, type_info: Rc<RefCell<TypeInfo>>) -> String {
-        let kind = VarKind::Local;
+    fn builtin() -> Scope {
+        let mut s = Scope::new(BUILTIN_SCOPE_ID);
+        s.types.insert(Symbol::intern("bool"), Bool);
+        s.types.insert(Symbol::intern("char"), Char);
+        s.types.insert(Symbol::intern("str"), Str);
+        s.types.insert(Symbol::intern("f32"), LitNum(F32));
+        s.types.insert(Symbol::intern("f64"), LitNum(F64));
+        s.types.insert(Symbol::intern("i8"), LitNum(I8));
+        s.types.insert(Symbol::intern("i16"), LitNum(I16));
+        s.types.insert(Symbol::intern("i32"), LitNum(I32));
+        s.types.insert(Symbol::intern("i64"), LitNum(I64));
+        s.types.insert(Symbol::intern("i128"), LitNum(I128));
+        s.types.insert(Symbol::intern("isize"), LitNum(Isize));
+        s.types.insert(Symbol::intern("u8"), LitNum(U8));
+        s.types.insert(Symbol::intern("u16"), LitNum(U16));
+        s.types.insert(Symbol::intern("u32"), LitNum(U32));
+        s.types.insert(Symbol::intern("u64"), LitNum(U64));
+        s.types.insert(Symbol::intern("u128"), LitNum(U128));
+        s.types.insert(Symbol::intern("usize"), LitNum(Usize));
+        s.types.insert(Symbol::intern("String"), TypeInfo::String);
+        s
+    }
+
+    pub fn gen_temp_variable(&mut self, type_info: TypeSlot) -> Symbol {
         let ident = temp_local_var(self.temp_count, self.scope_id);
         self.temp_count += 1;
-        self.add_variable(&ident, kind, type_info);
+        self.add_symbol(ident, VarKind::Local, type_info);
         ident
     }
 
-    pub fn add_variable(&mut self, ident: &str, kind: VarKind, type_info: Rc<RefCell<TypeInfo>>) {
-        let var_info = VarInfo::new(self.cur_stmt_id, kind, type_info);
-        if let Some(v) = self.variables.get_mut(ident) {
-            v.push(var_info);
-        } else {
-            self.variables.insert(ident.to_string(), vec![var_info]);
-        }
-    }
-
-    /// ```
-    /// let mut a;
-    /// ...
-    /// a = 32i32;
-    /// ```
-    pub fn update_variable_type(
-        &self,
-        ident: &str,
-        new_type_info: Rc<RefCell<TypeInfo>>,
-    ) -> Result<(), RccError> {
-        match self.find_variable_mut(ident) {
-            Some((var_info, _)) => match var_info.type_info.partial_cmp(&new_type_info) {
-                Some(o) => match o {
-                    Ordering::Greater | Ordering::Equal => {
-                        if !new_type_info.borrow().is_never() {
-                            var_info.type_info = new_type_info;
-                        }
-                        Ok(())
-                    }
-                    Ordering::Less => Err(RccError::from("invalid type")),
-                },
-                None => Err(RccError::from("invalid type")),
-            },
-            None => Err(RccError::from(format!("variable `{}` not found", ident))),
-        }
-    }
-
-    // Return (var info, scope id)
-    fn find_variable_mut(&self, ident: &str) -> Option<(&mut VarInfo, ScopeID)> {
-        let mut cur_scope: *mut Scope = self as *const Scope as *mut _;
-        loop {
-            let s = unsafe { &mut *cur_scope };
-            if let Some(v) = s.variables.get_mut(ident) {
-                let mut left = 0;
-                let mut right = v.len();
-                if right == 1 {
-                    return Some((unsafe { v.get_unchecked_mut(0) }, s.scope_id));
-                }
-                while left < right {
-                    let mid = (left + right + 1) / 2;
-                    let stmt_id = unsafe { (*v.get_unchecked(mid)).stmt_id() };
-                    // Let stmt and variable using stmt is impossible to be the same.
-                    debug_assert_ne!(stmt_id, self.cur_stmt_id);
-                    if self.cur_stmt_id < stmt_id {
-                        right = mid - 1;
-                    } else {
-                        left = mid;
-                    }
-                }
-                return Some((unsafe { v.get_unchecked_mut(left) }, s.scope_id));
-            } else if let Some(f) = s.father {
-                cur_scope = f.as_ptr();
-            } else {
-                return None;
-            }
-        }
+    /// Declare a new version of `ident`, visible from the current statement. The variable of
+    /// a `let` stmt is declared in the next statement, so that `a` in `let a = a + 1;` is the
+    /// version declared before.
+    pub fn add_variable(&mut self, ident: &str, kind: VarKind, type_info: TypeSlot) {
+        self.add_symbol(Symbol::intern(ident), kind, type_info);
     }
 
-    /// Return (var info, scope id)
-    pub fn find_variable(&self, ident: &str) -> Option<(&VarInfo, ScopeID)> {
-        let mut cur_scope: *const Scope = self;
-        loop {
-            let s = unsafe { &*cur_scope };
-            if let Some(v) = s.variables.get(ident) {
-                let mut left = 0;
-                let mut right = v.len() - 1;
-                if right == 0 {
-                    return Some((unsafe { v.get_unchecked(0) }, s.scope_id));
-                }
-                while left < right {
-                    let mid = (left + right + 1) / 2;
-                    let stmt_id = unsafe { (*v.get_unchecked(mid)).stmt_id() };
-                    // `Let stmt` and `variable use stmt` is impossible to be the same.
-                    match self.cur_stmt_id.cmp(&stmt_id) {
-                        Ordering::Less => {
-                            right = mid - 1;
-                        }
-                        Ordering::Equal => unreachable!(),
-                        Ordering::Greater => {left = mid;}
-                    }
-                    debug_assert_ne!(stmt_id, self.cur_stmt_id);
-                }
-                return Some((unsafe { v.get_unchecked(left) }, s.scope_id));
-            } else if let Some(f) = s.father {
-                cur_scope = f.as_ptr();
-            } else {
-                return None;
-            }
-        }
+    fn add_symbol(&mut self, ident: Symbol, kind: VarKind, type_info: TypeSlot) {
+        let versions = self.variables.entry(ident).or_default();
+        let version = versions.len() as u32;
+        versions.push(VarInfo::new(self.cur_stmt_id, version, kind, type_info));
     }
 
-    pub fn find_def_except_fn(&self, ident: &str) -> TypeInfo {
-        let mut cur_scope: *const Scope = self;
-        loop {
-            let s = unsafe { &*cur_scope };
-            if let Some(ti) = s.types.get(ident) {
-                match ti {
-                    TypeInfo::Fn { .. } => {}
-                    _ => return ti.clone(),
-                }
-            }
-            if let Some(f) = s.father {
-                cur_scope = f.as_ptr();
-            } else {
-                return Unknown;
-            }
-        }
+    /// Variables owning heap memory (`Box<T>`, `Vec<T>` and `String`) declared in this scope,
+    /// including the shadowed ones, in the reverse order of declaration. Temporary variables
+    /// are not included.
+    pub fn heap_owners(&self, types: &TypeTable) -> Vec<(&str, &VarInfo)> {
+        let mut variables: Vec<(&str, &VarInfo)> = self
+            .variables
+            .iter()
+            .map(|(ident, versions)| (ident.as_str(), versions))
+            .filter(|(ident, _)| !is_temp_var(ident))
+            .flat_map(|(ident, versions)| versions.iter().map(move |v| (ident, v)))
+            .filter(|(_, var_info)| types.get(var_info.type_info).owns_heap())
+            .collect();
+        variables.sort_by(|(a, var_a), (b, var_b)| {
+            (var_b.stmt_id(), var_b.version())
+                .cmp(&(var_a.stmt_id(), var_a.version()))
+                .then(a.cmp(b))
+        });
+        variables
     }
 
-    pub fn find_fn(&self, ident: &str) -> TypeInfo {
-        let mut cur_scope: *const Scope = self;
-        loop {
-            let s = unsafe { &*cur_scope };
-            if let Some(ti) = s.types.get(ident) {
-                if let TypeInfo::Fn { .. } = ti {
-                    return ti.clone();
-                }
-            }
-            if let Some(f) = s.father {
-                cur_scope = f.as_ptr();
-            } else {
-                return Unknown;
-            }
-        }
+    /// Index of the version of `ident` declared in this scope which is visible at statement
+    /// `cur_stmt_id`, the last one declared at or before it
+    fn variable_index(&self, ident: Symbol, cur_stmt_id: u64) -> Option<usize> {
+        let versions = self.variables.get(&ident)?;
+        // versions are declared in order, so their statements are ascending
+        versions
+            .partition_point(|v| v.stmt_id() <= cur_stmt_id)
+            .checked_sub(1)
     }
 
     /// Add type definitions (functions, structs, etc.) to current scope.
@@ -220,77 +122,268 @@ impl Scope {
                     }
                 }
             }
+            // methods are called by their symbols after being resolved
+            Item::Impl(item_impl) => {
+                for item_fn in item_impl.fns.iter() {
+                    self.types.insert(
+                        Symbol::intern(&item_impl.fn_symbol(item_fn)),
+                        TypeInfo::from_fn_signature(item_fn),
+                    );
+                }
+            }
+            // constants are variables of the file scope, added by the symbol resolver
+            Item::Trait(_) | Item::Const(_) => {}
+            // the target of an alias may be declared later, it is added by the symbol resolver
+            Item::Type(_) => {}
+            // the item is added if its predicate holds, see `analyser::cfg`
+            Item::Cfg(_) => {}
             _ => todo!(),
         }
     }
 
     fn add_type_fn(&mut self, fn_sig: &impl FnSignature) {
         let type_info = TypeInfo::from_fn_signature(fn_sig);
-        self.types.insert(fn_sig.name(), type_info);
+        self.types.insert(Symbol::intern(&fn_sig.name()), type_info);
+    }
+
+    /// `type Meters = i32;` is looked up as its target
+    pub fn add_type_alias(&mut self, name: &str, type_info: TypeInfo) {
+        self.types.insert(Symbol::intern(name), type_info);
     }
 
     fn add_type_struct(&mut self, item_struct: &ItemStruct) {
         let type_info = TypeInfo::from_item_struct(item_struct);
-        self.types.insert(item_struct.name().to_string(), type_info);
+        self.types.insert(Symbol::intern(item_struct.name()), type_info);
+    }
+}
+
+/// Owns all the scopes of a file. The id of a scope is its index in the arena, and a scope
+/// refers to its father by id. The scope of builtin types is created with the arena.
+#[derive(Clone)]
+pub struct ScopeArena {
+    scopes: Vec<Scope>,
+}
+
+impl Default for ScopeArena {
+    fn default() -> ScopeArena {
+        ScopeArena::new()
+    }
+}
+
+impl ScopeArena {
+    pub fn new() -> ScopeArena {
+        ScopeArena {
+            scopes: vec![Scope::builtin()],
+        }
+    }
+
+    pub fn new_scope(&mut self, father: Option<ScopeID>) -> ScopeID {
+        let scope_id = self.scopes.len() as ScopeID;
+        let mut scope = Scope::new(scope_id);
+        scope.father = father;
+        self.scopes.push(scope);
+        scope_id
+    }
+
+    pub fn get(&self, scope_id: ScopeID) -> &Scope {
+        &self.scopes[scope_id as usize]
+    }
+
+    pub fn get_mut(&mut self, scope_id: ScopeID) -> &mut Scope {
+        &mut self.scopes[scope_id as usize]
+    }
+
+    /// A new scope with the type definitions of `scope_id` but none of its variables
+    pub fn clone_scope(&mut self, scope_id: ScopeID) -> ScopeID {
+        let new_id = self.new_scope(self.get(scope_id).father);
+        self.get_mut(new_id).types = self.get(scope_id).types.clone();
+        new_id
+    }
+
+    pub fn set_father(&mut self, scope_id: ScopeID, father: ScopeID) {
+        self.get_mut(scope_id).father = Some(father);
+    }
+
+    /// `scope_id` and its fathers, from inner to outer
+    fn ancestors(&self, scope_id: ScopeID) -> impl Iterator<Item = &Scope> {
+        std::iter::successors(Some(self.get(scope_id)), move |s| {
+            s.father.map(|f| self.get(f))
+        })
+    }
+
+    /// Return (var info, scope id). A variable of a father is looked up at the statement of
+    /// the father being visited, which contains the inner scope.
+    pub fn find_variable(&self, scope_id: ScopeID, ident: &str) -> Option<(&VarInfo, ScopeID)> {
+        let ident = Symbol::find(ident)?;
+        self.ancestors(scope_id).find_map(|s| {
+            let idx = s.variable_index(ident, s.cur_stmt_id)?;
+            Some((&s.variables[&ident][idx], s.scope_id))
+        })
     }
 
-    pub fn set_father(&mut self, father: *mut Scope) {
-        self.father = Some(unsafe { NonNull::new_unchecked(father) });
+    /// Names of the variables and functions visible in `scope_id`
+    pub fn value_names(&self, scope_id: ScopeID) -> Vec<&'static str> {
+        let mut names = vec![];
+        for s in self.ancestors(scope_id) {
+            for &ident in s.variables.keys() {
+                if s.variable_index(ident, s.cur_stmt_id).is_some() {
+                    names.push(ident.as_str());
+                }
+            }
+            for (ident, type_info) in s.types.iter() {
+                if matches!(type_info, TypeInfo::Fn { .. }) {
+                    names.push(ident.as_str());
+                }
+            }
+        }
+        names.sort_unstable();
+        names.dedup();
+        names
+    }
+
+    pub fn find_def_except_fn(&self, scope_id: ScopeID, ident: &str) -> TypeInfo {
+        let ident = match Symbol::find(ident) {
+            Some(ident) => ident,
+            None => return Unknown,
+        };
+        self.ancestors(scope_id)
+            .filter_map(|s| s.types.get(&ident))
+            .find(|ti| !matches!(ti, TypeInfo::Fn { .. }))
+            .cloned()
+            .unwrap_or(Unknown)
     }
 
-    pub fn set_father_as_builtin_scope(&mut self) {
-        self.father = Some(NonNull::from(BULITIN_SCOPE.deref()));
+    pub fn find_fn(&self, scope_id: ScopeID, ident: &str) -> TypeInfo {
+        self.find_fn_scope(scope_id, ident)
+            .map_or(Unknown, |(type_info, _)| type_info.clone())
+    }
+
+    /// Return (fn type, id of the scope declaring it)
+    pub fn find_fn_scope(&self, scope_id: ScopeID, ident: &str) -> Option<(&TypeInfo, ScopeID)> {
+        let ident = Symbol::find(ident)?;
+        self.ancestors(scope_id).find_map(|s| match s.types.get(&ident) {
+            Some(type_info @ TypeInfo::Fn { .. }) => Some((type_info, s.scope_id)),
+            _ => None,
+        })
     }
 }
 
+/// Scopes entered while walking the AST of a file. The scopes are borrowed from the
+/// file between `enter_file` and `exit_file`.
 pub struct ScopeStack {
-    cur_scope: *mut Scope,
-    file_scope: Option<NonNull<Scope>>,
-    scope_stack: Vec<*mut Scope>,
+    scopes: ScopeArena,
+    cur_scope: ScopeID,
+    file_scope: Option<ScopeID>,
+    scope_stack: Vec<ScopeID>,
 }
 
 impl ScopeStack {
     pub fn new() -> ScopeStack {
         ScopeStack {
-            cur_scope: std::ptr::null_mut(),
+            scopes: ScopeArena::new(),
+            cur_scope: BUILTIN_SCOPE_ID,
             file_scope: None,
             scope_stack: vec![],
         }
     }
 
-    pub fn enter_scope(&mut self, block_expr: &mut BlockExpr) {
-        block_expr.scope.set_father(self.cur_scope);
+    /// Enter `scope_id` at its first statement
+    pub fn enter_scope(&mut self, scope_id: ScopeID) {
+        self.scopes.set_father(scope_id, self.cur_scope);
         self.scope_stack.push(self.cur_scope);
-        self.cur_scope = &mut block_expr.scope;
+        self.cur_scope = scope_id;
+        self.cur_scope_mut().cur_stmt_id = 0;
     }
 
+    /// Go back to the statement of the outer scope being visited
     pub fn exit_scope(&mut self) {
         if let Some(s) = self.scope_stack.pop() {
             self.cur_scope = s;
-            unsafe { &mut *self.cur_scope }.cur_stmt_id = 0;
         } else {
             debug_assert!(false, "scope_stack is empty!");
         }
     }
 
-    pub fn cur_scope_is_global(&mut self) -> bool {
-        if let Some(f) = &mut self.file_scope {
-            self.cur_scope == f.as_ptr()
-        } else {
-            false
-        }
+    pub fn cur_scope_is_global(&self) -> bool {
+        self.file_scope == Some(self.cur_scope)
     }
 
     pub fn cur_scope(&self) -> &Scope {
-        unsafe { &*self.cur_scope }
+        self.scopes.get(self.cur_scope)
     }
 
     pub fn cur_scope_mut(&mut self) -> &mut Scope {
-        unsafe { &mut *self.cur_scope }
+        self.scopes.get_mut(self.cur_scope)
+    }
+
+    pub fn scope_mut(&mut self, scope_id: ScopeID) -> &mut Scope {
+        self.scopes.get_mut(scope_id)
+    }
+
+    pub fn scopes(&self) -> &ScopeArena {
+        &self.scopes
+    }
+
+    pub fn scopes_mut(&mut self) -> &mut ScopeArena {
+        &mut self.scopes
+    }
+
+    /// Define `ident` in the scope of the file, e.g. an instance of a generic function.
+    pub fn add_file_typedef(&mut self, ident: String, type_info: TypeInfo) {
+        let file_scope = self.file_scope.expect("not in a file");
+        self.scopes
+            .get_mut(file_scope)
+            .types
+            .insert(Symbol::intern(&ident), type_info);
+    }
+
+    /// Return (var info, scope id) of the variable visible in current scope
+    pub fn find_variable(&self, ident: &str) -> Option<(&VarInfo, ScopeID)> {
+        self.scopes.find_variable(self.cur_scope, ident)
+    }
+
+    pub fn value_names(&self) -> Vec<&'static str> {
+        self.scopes.value_names(self.cur_scope)
+    }
+
+    pub fn find_def_except_fn(&self, ident: &str) -> TypeInfo {
+        self.scopes.find_def_except_fn(self.cur_scope, ident)
+    }
+
+    pub fn find_fn(&self, ident: &str) -> TypeInfo {
+        self.scopes.find_fn(self.cur_scope, ident)
+    }
+
+    /// Id of the scope declaring the function `ident` visible in current scope
+    pub fn find_fn_scope(&self, ident: &str) -> Option<ScopeID> {
+        self.scopes
+            .find_fn_scope(self.cur_scope, ident)
+            .map(|(_, scope_id)| scope_id)
+    }
+
+    /// Whether `scope_id` is `outer` or a scope entered after it, which are not left yet
+    pub fn entered_since(&self, scope_id: ScopeID, outer: ScopeID) -> bool {
+        for &s in std::iter::once(&self.cur_scope).chain(self.scope_stack.iter().rev()) {
+            if s == scope_id {
+                return true;
+            }
+            if s == outer {
+                return false;
+            }
+        }
+        false
     }
 
     pub fn enter_file(&mut self, file: &mut File) {
-        self.cur_scope = &mut file.scope;
-        self.file_scope = Some(NonNull::new(&mut file.scope).unwrap());
+        self.scopes = std::mem::take(&mut file.scopes);
+        self.cur_scope = file.scope_id;
+        self.file_scope = Some(file.scope_id);
+    }
+
+    /// Give the scopes back to `file`.
+    pub fn exit_file(&mut self, file: &mut File) {
+        file.scopes = std::mem::take(&mut self.scopes);
+        self.cur_scope = BUILTIN_SCOPE_ID;
+        self.file_scope = None;
     }
 }
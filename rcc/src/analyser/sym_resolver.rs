@@ -1,28 +1,38 @@
-use crate::analyser::scope::{Scope, ScopeStack};
+use crate::analyser::cfg::{self, CfgOptions};
+use crate::analyser::mono::{self, GenericArg};
+use crate::analyser::scope::{ScopeArena, ScopeID, ScopeStack};
+use crate::analyser::type_table::{TypeSlot, TypeTable};
 use crate::analyser::sym_resolver::LoopKind::NotIn;
 use crate::analyser::sym_resolver::TypeInfo::Unknown;
 use crate::ast::expr::{
-    ArrayExpr, ArrayIndexExpr, AssignExpr, AssignOp, BinOpExpr, BinOperator, BlockExpr, BreakExpr,
-    CallExpr, Expr, ExprKind, FieldAccessExpr, GroupedExpr, IfExpr, LhsExpr, LoopExpr, PathExpr,
-    RangeExpr, ReturnExpr, StructExpr, TupleExpr, TupleIndexExpr, UnAryExpr, UnOp, WhileExpr,
+    ArrayExpr, ArrayIndexExpr, AsmExpr, AssertExpr, AssignExpr, AssignOp, BinOpExpr, BinOperator, BlockExpr, BreakExpr,
+    CallExpr, CastExpr, Expr, ExprKind, FieldAccessExpr, ForExpr, GroupedExpr, IfExpr, LhsExpr, LoopExpr, PathExpr,
+    PrintExpr, RangeExpr, RangeOp, ReturnExpr, StructExpr, TupleExpr, TupleIndexExpr, UnAryExpr, UnOp, WhileExpr,
 };
-use crate::ast::expr::{ExprVisit, TypeInfoSetter};
+use crate::ast::expr::{ExprVisit, LitNumExpr, TypeInfoSetter};
 use crate::ast::file::File;
 use crate::ast::item::{
-    ExternalItem, ExternalItemFn, Fields, FnSignature, Item, ItemExternalBlock, ItemFn, ItemStruct,
-    TypeEnum,
+    ExternalItem, ExternalItemFn, Fields, FnSignature, Item, ItemConst,
+    ItemExternalBlock, ItemFn, ItemImpl, ItemStruct, ItemTrait, ItemTypeAlias, TypeEnum,
 };
 use crate::ast::pattern::{IdentPattern, Pattern};
+use crate::ast::printer::{expr_str, type_anno};
 use crate::ast::stmt::{LetStmt, Stmt};
-use crate::ast::types::{PtrKind, TypeAnnotation, TypeFnPtr, TypeLitNum};
-use crate::ast::Visibility;
-use crate::rcc::RccError;
-use std::cell::RefCell;
+use crate::ast::types::{PtrKind, TypeAnnotation, TypeFnPtr, TypeLitNum, TypePtr};
+use crate::ast::visit::{Visitor, VisitorMut};
+use crate::ast::{NodeId, Visibility};
+use crate::diagnostic::Span;
+use crate::ir::mangle::{
+    demangle, generic_fn_name, impl_fn_name, inherent_item_name, vtable_name,
+};
+use crate::ir::ir_build::IRBuilder;
+use crate::ir::var_name::{array_len_const, end_var, len_var, param_var, vtable_var};
+use crate::ir::Operand;
+use crate::rcc::{CompileError, ErrorKind, RccError};
 use std::cmp::Ordering;
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
+use std::fmt::Display;
 use std::ops::Deref;
-use std::ptr::NonNull;
-use std::rc::Rc;
 
 #[derive(Debug, PartialEq, Clone, Copy)]
 pub enum VarKind {
@@ -33,17 +43,22 @@ pub enum VarKind {
     Local,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone)]
 pub struct VarInfo {
+    /// the first statement of the scope in which the variable is visible
     stmt_id: u64,
+    /// the variables of the same name in a scope are numbered from 0 in the order of
+    /// declaration, see `versioned_var`
+    version: u32,
     kind: VarKind,
-    pub type_info: Rc<RefCell<TypeInfo>>,
+    pub type_info: TypeSlot,
 }
 
 impl VarInfo {
-    pub fn new(stmt_id: u64, kind: VarKind, type_info: Rc<RefCell<TypeInfo>>) -> VarInfo {
+    pub fn new(stmt_id: u64, version: u32, kind: VarKind, type_info: TypeSlot) -> VarInfo {
         VarInfo {
             stmt_id,
+            version,
             kind,
             type_info,
         }
@@ -53,6 +68,10 @@ impl VarInfo {
         self.stmt_id
     }
 
+    pub fn version(&self) -> u32 {
+        self.version
+    }
+
     pub fn kind(&self) -> VarKind {
         self.kind
     }
@@ -67,9 +86,10 @@ pub enum TypeInfo {
 
     FnPtr(TypeFnPtr),
 
+    /// The fields are kept in the `TypeTable` by the name of the struct
     Struct {
         vis: Visibility,
-        fields: NonNull<Fields>,
+        name: String,
     },
 
     Enum(TypeEnum),
@@ -79,6 +99,31 @@ pub enum TypeInfo {
         type_info: Box<TypeInfo>,
     },
 
+    /// `dyn Trait`, a trait object is a pair of (data ptr, vtable ptr) behind a reference
+    Dyn(String),
+
+    /// `Vec<T>`, the element type is unknown until the first `push`
+    Vec(Box<TypeInfo>),
+
+    /// `[T; N]`, the elements are stored in a heap allocation of `N` elements
+    Array(Box<TypeInfo>, usize),
+
+    /// `[T]`, only used behind a reference. `&[T]` and `&str` are pairs of (data ptr, len)
+    Slice(Box<TypeInfo>),
+
+    /// Bytes of a growable string are stored like `Vec<char>`
+    String,
+
+    /// `a..b` or `a..=b` of integers, a pair of (start, end)
+    Range {
+        type_info: Box<TypeInfo>,
+        inclusive: bool,
+    },
+
+    /// `(T, U)`, only used by params and arguments, whose elements are flattened like the
+    /// fields of a struct
+    Tuple(Vec<TypeInfo>),
+
     /// primitive type
     /// !
     Never,
@@ -94,9 +139,10 @@ pub enum TypeInfo {
 }
 
 impl TypeInfo {
-    pub(crate) fn from_type_anno(type_anno: &TypeAnnotation, cur_scope: &Scope) -> TypeInfo {
+    /// Types in `type_anno` are looked up from the current scope of `scopes`.
+    pub(crate) fn from_type_anno(type_anno: &TypeAnnotation, scopes: &ScopeStack) -> TypeInfo {
         match type_anno {
-            TypeAnnotation::Identifier(s) => cur_scope.find_def_except_fn(s),
+            TypeAnnotation::Identifier(s) => scopes.find_def_except_fn(s),
             TypeAnnotation::Never => TypeInfo::Never,
             TypeAnnotation::Unit => TypeInfo::Unit,
             TypeAnnotation::Bool => TypeInfo::Bool,
@@ -104,8 +150,24 @@ impl TypeInfo {
             TypeAnnotation::Char => TypeInfo::Char,
             TypeAnnotation::Ptr(tp) => TypeInfo::Ptr {
                 kind: tp.ptr_kind,
-                type_info: Box::new(TypeInfo::from_type_anno(&tp.type_anno, cur_scope)),
+                type_info: Box::new(TypeInfo::from_type_anno(&tp.type_anno, scopes)),
             },
+            TypeAnnotation::Vec(elem) => {
+                TypeInfo::Vec(Box::new(TypeInfo::from_type_anno(elem, scopes)))
+            }
+            TypeAnnotation::Array(array) => match array_len(&array.len) {
+                Some(len) => {
+                    TypeInfo::Array(Box::new(TypeInfo::from_type_anno(&array._type, scopes)), len)
+                }
+                None => TypeInfo::Unknown,
+            },
+            TypeAnnotation::Slice(elem) => {
+                TypeInfo::Slice(Box::new(TypeInfo::from_type_anno(elem, scopes)))
+            }
+            TypeAnnotation::Dyn(trait_name) => TypeInfo::Dyn(trait_name.clone()),
+            TypeAnnotation::Tuple(types) => {
+                TypeInfo::Tuple(types.iter().map(|t| TypeInfo::from_type_anno(t, scopes)).collect())
+            }
             TypeAnnotation::Unknown => TypeInfo::Unknown,
             _ => todo!(),
         }
@@ -122,7 +184,7 @@ impl TypeInfo {
     pub(crate) fn from_item_struct(item: &ItemStruct) -> Self {
         Self::Struct {
             vis: item.vis(),
-            fields: NonNull::from(item.fields()),
+            name: item.name().to_string(),
         }
     }
 
@@ -133,6 +195,14 @@ impl TypeInfo {
         }
     }
 
+    /// `&[u8]`
+    pub fn ref_bytes() -> TypeInfo {
+        TypeInfo::Ptr {
+            kind: PtrKind::Ref,
+            type_info: Box::new(TypeInfo::Slice(Box::new(TypeInfo::LitNum(TypeLitNum::U8)))),
+        }
+    }
+
     pub fn is_integer(&self) -> bool {
         if let TypeInfo::LitNum(ln) = &self {
             matches!(
@@ -158,7 +228,7 @@ impl TypeInfo {
 
     pub fn is_float(&self) -> bool {
         if let TypeInfo::LitNum(ln) = &self {
-            matches!(ln, TypeLitNum::F | TypeLitNum::F32 | TypeLitNum::F64)
+            ln.is_float()
         } else {
             false
         }
@@ -197,9 +267,133 @@ impl TypeInfo {
         self == &TypeInfo::Unknown
     }
 
+    pub fn is_box(&self) -> bool {
+        matches!(
+            self,
+            TypeInfo::Ptr {
+                kind: PtrKind::Box,
+                ..
+            }
+        )
+    }
+
+    /// `Box`, `Vec` and `String` free their heap memory at the end of their scopes. Arrays are
+    /// in the stack frames.
+    pub fn owns_heap(&self) -> bool {
+        self.is_box() || matches!(self, TypeInfo::Vec(_) | TypeInfo::String)
+    }
+
     pub fn is_never(&self) -> bool {
         self == &TypeInfo::Never
     }
+
+    pub fn is_struct(&self) -> bool {
+        matches!(self, TypeInfo::Struct { .. })
+    }
+
+    /// A struct or a tuple, whose values are passed as their scalar fields
+    pub fn is_aggregate(&self) -> bool {
+        matches!(self, TypeInfo::Struct { .. } | TypeInfo::Tuple(_))
+    }
+
+    /// `&[T]`, `&mut [T]` or `&str`
+    /// `&str` or `&mut str`
+    pub fn is_str_ref(&self) -> bool {
+        match self {
+            TypeInfo::Ptr {
+                kind: PtrKind::Ref | PtrKind::MutRef,
+                type_info,
+            } => type_info.as_ref() == &TypeInfo::Str,
+            _ => false,
+        }
+    }
+
+    /// `*const T` or `*mut T`
+    pub fn is_raw_ptr(&self) -> bool {
+        matches!(
+            self,
+            TypeInfo::Ptr {
+                kind: PtrKind::ConstRawPtr | PtrKind::MutRawPtr,
+                ..
+            }
+        )
+    }
+
+    pub fn is_slice_ref(&self) -> bool {
+        match self {
+            TypeInfo::Ptr {
+                kind: PtrKind::Ref | PtrKind::MutRef,
+                type_info,
+            } => matches!(type_info.as_ref(), TypeInfo::Slice(_) | TypeInfo::Str),
+            _ => false,
+        }
+    }
+
+    /// `Trait` of `&dyn Trait` and `&mut dyn Trait`
+    pub fn dyn_trait(&self) -> Option<&str> {
+        match self {
+            TypeInfo::Ptr {
+                kind: PtrKind::Ref | PtrKind::MutRef,
+                type_info,
+            } => match type_info.as_ref() {
+                TypeInfo::Dyn(trait_name) => Some(trait_name),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+}
+
+/// Types are displayed in Rust syntax for diagnostics, e.g. `&str` or `[i32; 3]`. Literals
+/// whose types are not inferred yet are `{integer}` and `{float}` like rustc.
+impl Display for TypeInfo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TypeInfo::Fn { inner, .. } | TypeInfo::FnPtr(inner) => {
+                let params: Vec<String> = inner.params.iter().map(type_anno).collect();
+                match inner.ret_type.as_ref() {
+                    TypeAnnotation::Unit => write!(f, "fn({})", params.join(", ")),
+                    t => write!(f, "fn({}) -> {}", params.join(", "), type_anno(t)),
+                }
+            }
+            TypeInfo::Struct { name, .. } => write!(f, "{}", name),
+            TypeInfo::Enum(type_enum) => write!(f, "{}", type_enum.name()),
+            TypeInfo::Ptr { kind, type_info } => match kind {
+                PtrKind::Ref => write!(f, "&{}", type_info),
+                PtrKind::MutRef => write!(f, "&mut {}", type_info),
+                PtrKind::MutRawPtr => write!(f, "*mut {}", type_info),
+                PtrKind::ConstRawPtr => write!(f, "*const {}", type_info),
+                PtrKind::Box => write!(f, "Box<{}>", type_info),
+            },
+            TypeInfo::Dyn(trait_name) => write!(f, "dyn {}", trait_name),
+            TypeInfo::Vec(elem) => write!(f, "Vec<{}>", elem),
+            TypeInfo::Array(elem, len) => write!(f, "[{}; {}]", elem, len),
+            TypeInfo::Slice(elem) => write!(f, "[{}]", elem),
+            TypeInfo::String => write!(f, "String"),
+            TypeInfo::Range {
+                type_info,
+                inclusive: false,
+            } => write!(f, "Range<{}>", type_info),
+            TypeInfo::Range {
+                type_info,
+                inclusive: true,
+            } => write!(f, "RangeInclusive<{}>", type_info),
+            TypeInfo::Tuple(types) if types.len() == 1 => write!(f, "({},)", types[0]),
+            TypeInfo::Tuple(types) => {
+                let types: Vec<String> = types.iter().map(|t| t.to_string()).collect();
+                write!(f, "({})", types.join(", "))
+            }
+            TypeInfo::Never => write!(f, "!"),
+            TypeInfo::Str => write!(f, "str"),
+            TypeInfo::Unit => write!(f, "()"),
+            TypeInfo::Bool => write!(f, "bool"),
+            TypeInfo::Char => write!(f, "char"),
+            TypeInfo::LitNum(TypeLitNum::I) => write!(f, "{{integer}}"),
+            TypeInfo::LitNum(TypeLitNum::F) => write!(f, "{{float}}"),
+            TypeInfo::LitNum(lit_num) => write!(f, "{}", lit_num),
+            TypeInfo::Unknown => write!(f, "_"),
+        }
+    }
 }
 
 impl PartialOrd for TypeInfo {
@@ -258,7 +452,8 @@ impl PartialOrd for TypeInfo {
 enum LoopKind {
     NotIn,
     While,
-    Loop(*mut LoopExpr),
+    For,
+    Loop(NodeId),
 }
 
 impl LoopKind {
@@ -267,9 +462,11 @@ impl LoopKind {
     }
 }
 
-/// Fill the `type information` and `expr kind` attributes of the expr nodes on AST
+/// Fill the `expr kind` attributes of the expr nodes on AST, and the types of the nodes in
+/// the `TypeTable` of the file
 pub struct SymbolResolver {
     scope_stack: ScopeStack,
+    types: TypeTable,
 
     loop_kind: LoopKind,
     loop_kind_stack: Vec<LoopKind>,
@@ -277,356 +474,1420 @@ pub struct SymbolResolver {
     cur_fn_ret_type: TypeInfo,
     cur_fn_ret_type_stack: Vec<TypeInfo>,
 
-    // TODO: Operator override tables
-    pub override_bin_ops: HashSet<(BinOperator, TypeInfo, TypeInfo)>,
+    traits: HashMap<String, ItemTrait>,
+    /// (trait, type) of the `impl Trait for Type` items -> name of the type
+    impls: HashMap<(String, TypeInfo), String>,
+    /// (type, method) -> symbol of the method, which takes a `self` receiver
+    methods: HashMap<(TypeInfo, String), String>,
+    /// (struct, name) -> symbol of the function or constant in `impl Struct`, used by
+    /// `Struct::name`
+    assoc_items: HashMap<(TypeInfo, String), String>,
+    generic_fns: HashMap<String, ItemFn>,
+    /// (generic function, generic arguments) of the instances to be resolved, in the order
+    /// they are called first
+    instances: Vec<(String, Vec<GenericArg>)>,
+    instance_names: HashSet<String>,
+    /// node count of the file, for the exprs added by the resolver
+    node_count: u32,
+    /// spans of the nodes of the file, for diagnostics
+    spans: HashMap<NodeId, Span>,
+    /// body of a function -> span of its signature
+    sig_spans: HashMap<NodeId, Span>,
+    /// call -> spans of its arguments
+    arg_spans: HashMap<NodeId, Vec<Span>>,
+    /// (scope, name) of the items -> span of the name
+    def_spans: HashMap<(ScopeID, String), Span>,
+    /// path -> span of the name of the item it refers to
+    references: HashMap<NodeId, Span>,
+    const_fns: HashSet<String>,
+    /// `Some("constants")` or `Some("constant functions")` when visiting the initializer of
+    /// a constant or the body of a `const fn`, only `const fn`s can be called in them.
+    const_context: Option<&'static str>,
+    unsafe_fns: HashSet<String>,
+    /// in an `unsafe` block or the body of an `unsafe fn`, where raw pointers can be
+    /// dereferenced, `asm!` can be used and `unsafe fn`s can be called
+    in_unsafe: bool,
+    /// The file is an executable whose entry is `main`
+    entry: bool,
+    /// options of `#[cfg(...)]`
+    cfg_options: CfgOptions,
+    /// scope of the body of the function being visited, the local variables outside of it
+    /// can not be used
+    fn_scope: Option<ScopeID>,
+    /// names of the functions of the file and of the `#[no_mangle]` functions declared in
+    /// blocks, which are their symbols
+    plain_symbols: HashSet<String>,
+    /// integer literals with their values, checked against their types at the end
+    lit_nums: Vec<(NodeId, String)>,
+    /// literals which are the operand of `-`, e.g. `-128i8`
+    negated_lits: HashSet<NodeId>,
+    /// in bits, the size of `isize` and `usize`
+    pointer_width: u32,
+    /// whether the file is compiled to assembly, whose numbers must be integers which fit in
+    /// a register
+    codegen: bool,
+    /// false in the resolver of the constants which computes the lengths of arrays, see
+    /// `eval_array_lens`
+    eval_array_lens: bool,
 }
 
 impl SymbolResolver {
     pub fn new() -> SymbolResolver {
         SymbolResolver {
             scope_stack: ScopeStack::new(),
+            types: TypeTable::new(),
             loop_kind: NotIn,
             loop_kind_stack: vec![],
             cur_fn_ret_type: TypeInfo::Unknown,
             cur_fn_ret_type_stack: vec![],
-            override_bin_ops: HashSet::new(),
+            traits: HashMap::new(),
+            impls: HashMap::new(),
+            methods: HashMap::new(),
+            assoc_items: HashMap::new(),
+            generic_fns: HashMap::new(),
+            instances: vec![],
+            instance_names: HashSet::new(),
+            node_count: 0,
+            spans: HashMap::new(),
+            sig_spans: HashMap::new(),
+            arg_spans: HashMap::new(),
+            def_spans: HashMap::new(),
+            references: HashMap::new(),
+            const_fns: HashSet::new(),
+            const_context: None,
+            unsafe_fns: HashSet::new(),
+            in_unsafe: false,
+            entry: false,
+            cfg_options: CfgOptions::new(),
+            fn_scope: None,
+            plain_symbols: HashSet::new(),
+            lit_nums: vec![],
+            negated_lits: HashSet::new(),
+            pointer_width: 64,
+            codegen: false,
+            eval_array_lens: true,
         }
     }
 
-    fn may_update_variable_type(
-        &self,
-        place_expr: &Expr,
-        new_type: Rc<RefCell<TypeInfo>>,
-    ) -> Result<(), RccError> {
-        match place_expr {
-            Expr::Path(path_expr) => {
-                let ident = path_expr.segments.last().unwrap();
-                self.scope_stack
-                    .cur_scope()
-                    .update_variable_type(ident, new_type)?;
-            }
-            Expr::ArrayIndex(_) | Expr::TupleIndex(_) | Expr::FieldAccess(_) => todo!(),
-            _ => {}
-        }
-        Ok(())
+    pub fn set_entry(&mut self, entry: bool) {
+        self.entry = entry;
+    }
+
+    /// Items whose `#[cfg(...)]` does not hold for `cfg_options` are dropped.
+    pub fn set_cfg_options(&mut self, cfg_options: CfgOptions) {
+        self.cfg_options = cfg_options;
+    }
+
+    /// `pointer_width` of the target, 64 by default
+    pub fn set_pointer_width(&mut self, pointer_width: u32) {
+        self.pointer_width = pointer_width;
+    }
+
+    /// The backend keeps every number in one integer register of `pointer_width` bits, so
+    /// floats and wider integers like `u64` on riscv32 are rejected if the file is compiled to
+    /// assembly.
+    pub fn set_codegen(&mut self, codegen: bool) {
+        self.codegen = codegen;
     }
 
     /// return `TypeInfo::Unknown` if bin_op expr is invalid
     fn primitive_bin_ops(
-        &self,
+        &mut self,
+        node_id: NodeId,
         lhs: &mut Expr,
         bin_op: BinOperator,
         rhs: &mut Expr,
-    ) -> Result<Rc<RefCell<TypeInfo>>, RccError> {
-        let l_type: Rc<RefCell<TypeInfo>> = lhs.type_info();
-        let r_type: Rc<RefCell<TypeInfo>> = rhs.type_info();
+    ) -> Result<TypeSlot, RccError> {
+        let l_type = lhs.type_slot(&mut self.types);
+        let r_type = rhs.type_slot(&mut self.types);
+        let l_type_info = self.types.get(l_type).clone();
+        let r_type_info = self.types.get(r_type).clone();
         match bin_op {
             // 3i64 << 2i32
             BinOperator::Shl | BinOperator::Shr => Ok(
-                if l_type.borrow().deref().is_integer() && r_type.borrow().deref().is_integer() {
+                if l_type_info.is_integer() && r_type_info.is_integer() {
                     l_type
                 } else {
-                    Rc::new(RefCell::new(Unknown))
+                    self.types.new_slot(Unknown)
                 },
             ),
             BinOperator::Plus | BinOperator::Minus | BinOperator::Star | BinOperator::Slash => {
-                match l_type.partial_cmp(&r_type) {
-                    Some(o) => match o {
-                        Ordering::Equal => Ok(l_type),
-                        Ordering::Greater => {
-                            self.may_update_variable_type(lhs, r_type.clone())?;
-                            lhs.set_type_info_ref(r_type.clone());
-                            Ok(r_type)
-                        }
-                        Ordering::Less => {
-                            self.may_update_variable_type(rhs, l_type.clone())?;
-                            rhs.set_type_info_ref(l_type.clone());
-                            Ok(l_type)
-                        }
-                    },
-                    None => Err(format!(
-                        "invalid operand type `{:?}` and `{:?}` for `{:?}`",
-                        l_type.borrow().deref(),
-                        r_type.borrow().deref(),
-                        bin_op
-                    )
-                    .into()),
+                // `String + String` is valid only if it is overloaded
+                if [&l_type_info, &r_type_info]
+                    .iter()
+                    .any(|t| !t.is_number() && !t.is_unknown() && !t.is_never())
+                {
+                    return Ok(self.types.new_slot(Unknown));
+                }
+                // `a + 1u8` makes `a` and all the exprs unified with it `u8`
+                match self.types.unify(l_type, r_type) {
+                    Ok(()) => Ok(if r_type_info.is_never() { r_type } else { l_type }),
+                    Err(_) => {
+                        let error = mismatched_num_types(bin_op, &l_type_info, &r_type_info);
+                        Err(self.error_at(error, node_id))
+                    }
                 }
             }
-            BinOperator::Percent => match (l_type.borrow().deref(), r_type.borrow().deref()) {
-                (TypeInfo::LitNum(l_lit), TypeInfo::LitNum(r_lit)) => {
-                    if l_lit == &TypeLitNum::I && r_lit.is_integer() {
-                        lhs.set_type_info_ref(r_type.clone());
-                    } else if r_lit == &TypeLitNum::I && l_lit.is_integer() {
-                        rhs.set_type_info_ref(l_type.clone())
-                    } else if l_lit != r_lit || !l_lit.is_integer() {
-                        return Ok(Rc::new(RefCell::new(Unknown)));
+            BinOperator::Percent => match (&l_type_info, &r_type_info) {
+                (TypeInfo::LitNum(l_lit), TypeInfo::LitNum(r_lit))
+                    if l_lit.is_integer() && r_lit.is_integer() =>
+                {
+                    match self.types.unify(l_type, r_type) {
+                        Ok(()) => Ok(l_type),
+                        Err(_) => {
+                            let error = mismatched_num_types(bin_op, &l_type_info, &r_type_info);
+                            Err(self.error_at(error, node_id))
+                        }
                     }
-                    Ok(lhs.type_info())
                 }
-                _ => Ok(Rc::new(RefCell::new(Unknown))),
+                _ => Ok(self.types.new_slot(Unknown)),
             },
             BinOperator::Lt
             | BinOperator::Gt
             | BinOperator::Le
             | BinOperator::Ge
             | BinOperator::EqEq
-            | BinOperator::Ne => {
-                if let TypeInfo::LitNum(l_lit) = l_type.borrow().deref() {
-                    if let TypeInfo::LitNum(r_lit) = r_type.borrow().deref() {
-                        return Ok(if l_lit == r_lit {
-                            Rc::new(RefCell::new(TypeInfo::Bool))
-                        } else if l_lit == &TypeLitNum::I && r_lit.is_integer()
-                            || l_lit == &TypeLitNum::F && r_lit.is_float()
-                        {
-                            if let Expr::LitNum(expr) = lhs {
-                                expr.set_type_info_ref(r_type.clone());
-                            }
-                            Rc::new(RefCell::new(TypeInfo::Bool))
-                        } else if r_lit == &TypeLitNum::I && l_lit.is_integer()
-                            || r_lit == &TypeLitNum::F && l_lit.is_float()
-                        {
-                            if let Expr::LitNum(expr) = rhs {
-                                expr.set_type_info_ref(l_type.clone());
-                            }
-                            Rc::new(RefCell::new(TypeInfo::Bool))
-                        } else {
-                            Rc::new(RefCell::new(Unknown))
-                        });
+            | BinOperator::Ne => match (&l_type_info, &r_type_info) {
+                (TypeInfo::LitNum(_), TypeInfo::LitNum(_)) => {
+                    match self.types.unify(l_type, r_type) {
+                        Ok(()) => Ok(self.types.new_slot(TypeInfo::Bool)),
+                        Err(_) => {
+                            let error = mismatched_num_types(bin_op, &l_type_info, &r_type_info);
+                            Err(self.error_at(error, node_id))
+                        }
                     }
                 }
-                Ok(Rc::new(RefCell::new(Unknown)))
-            }
+                _ => Ok(self.types.new_slot(Unknown)),
+            },
             BinOperator::And | BinOperator::Or | BinOperator::Caret => {
-                if let TypeInfo::LitNum(l_lit) = l_type.borrow().deref() {
-                    if let TypeInfo::LitNum(r_lit) = r_type.borrow().deref() {
-                        return Ok(if l_lit == r_lit {
-                            l_type.clone()
-                        } else if l_lit == &TypeLitNum::I && r_lit.is_integer() {
-                            if let Expr::LitNum(expr) = lhs {
-                                expr.set_type_info_ref(l_type.clone());
-                            }
-                            r_type.clone()
-                        } else if r_lit == &TypeLitNum::I && l_lit.is_integer() {
-                            if let Expr::LitNum(expr) = rhs {
-                                expr.set_type_info_ref(l_type.clone());
+                if let TypeInfo::LitNum(l_lit) = &l_type_info {
+                    if let TypeInfo::LitNum(r_lit) = &r_type_info {
+                        if l_lit != r_lit && !(l_lit.is_integer() && r_lit.is_integer()) {
+                            return Ok(self.types.new_slot(Unknown));
+                        }
+                        return match self.types.unify(l_type, r_type) {
+                            Ok(()) => Ok(l_type),
+                            Err(_) => {
+                                let error =
+                                    mismatched_num_types(bin_op, &l_type_info, &r_type_info);
+                                Err(self.error_at(error, node_id))
                             }
-                            l_type.clone()
-                        } else {
-                            Rc::new(RefCell::new(Unknown))
-                        });
+                        };
                     }
-                } else if l_type.borrow().deref() == &TypeInfo::Bool
-                    && r_type.borrow().deref() == &TypeInfo::Bool
-                {
-                    return Ok(Rc::new(RefCell::new(TypeInfo::Bool)));
+                } else if l_type_info == TypeInfo::Bool && r_type_info == TypeInfo::Bool {
+                    return Ok(self.types.new_slot(TypeInfo::Bool));
                 }
-                Ok(Rc::new(RefCell::new(Unknown)))
+                Ok(self.types.new_slot(Unknown))
             }
             BinOperator::AndAnd | BinOperator::OrOr => {
                 // if loop {} && true {}
-                if l_type.borrow().deref().is(&TypeInfo::Bool)
-                    && r_type.borrow().deref().is(&TypeInfo::Bool)
-                {
-                    return Ok(Rc::new(RefCell::new(TypeInfo::Bool)));
+                if l_type_info.is(&TypeInfo::Bool) && r_type_info.is(&TypeInfo::Bool) {
+                    return Ok(self.types.new_slot(TypeInfo::Bool));
+                }
+                Ok(self.types.new_slot(Unknown))
+            }
+        }
+    }
+
+    /// Why the place `lhs` can not be assigned to
+    fn immutable_place_error(&self, lhs: &LhsExpr) -> CompileError {
+        const LET_MUT: &str = "a variable is mutable only if it is declared by `let mut`";
+        match lhs {
+            LhsExpr::Path(path_expr) => {
+                let name = path_expr.segments.join("::");
+                let is_local = path_expr.segments.len() == 1
+                    && matches!(
+                        self.scope_stack.find_variable(&name),
+                        Some((VarInfo { kind: VarKind::Local, .. }, _))
+                    );
+                if is_local {
+                    let msg = format!("cannot assign twice to immutable variable `{}`", name);
+                    CompileError::new(ErrorKind::Type, msg).code("E0384").note(LET_MUT)
+                } else {
+                    // associated constants are resolved to their symbols
+                    let name = demangle(&name).unwrap_or(name);
+                    let msg = format!("cannot assign to constant `{}`", name);
+                    CompileError::new(ErrorKind::Type, msg)
+                        .code("E0070")
+                        .help("consider declaring a `static mut` instead")
                 }
-                Ok(Rc::new(RefCell::new(Unknown)))
             }
-            BinOperator::As => {
-                todo!()
+            LhsExpr::FieldAccess(_) | LhsExpr::TupleIndex(_) => {
+                let msg = "cannot assign to a field of an immutable value";
+                CompileError::new(ErrorKind::Type, msg).code("E0594").note(LET_MUT)
+            }
+            LhsExpr::ArrayIndex(_) => {
+                CompileError::new(ErrorKind::Type, "cannot assign to an immutable element")
+                    .code("E0594")
+                    .note(LET_MUT)
+                    .note("the elements of a slice are mutable only if it is `&mut [T]`")
             }
+            LhsExpr::Deref(expr) => match expr.type_info(&self.types) {
+                TypeInfo::Ptr { kind: PtrKind::Ref, .. } => {
+                    let msg = "cannot assign to immutable borrowed content";
+                    CompileError::new(ErrorKind::Type, msg)
+                        .code("E0594")
+                        .help("consider borrowing it by `&mut` instead")
+                }
+                TypeInfo::Ptr { kind: PtrKind::ConstRawPtr, .. } => {
+                    let msg = "cannot assign through a `*const` pointer";
+                    CompileError::new(ErrorKind::Type, msg)
+                        .code("E0594")
+                        .help("consider using a `*mut` pointer instead")
+                }
+                _ => {
+                    let msg = "cannot assign to the content of an immutable `Box`";
+                    CompileError::new(ErrorKind::Type, msg).code("E0594").note(LET_MUT)
+                }
+            },
         }
     }
 
+    /// Span of the `n`th argument of `call`, starting from 1
+    fn arg_span(&self, call: NodeId, n: usize) -> Option<Span> {
+        self.arg_spans.get(&call)?.get(n.checked_sub(1)?).copied()
+    }
+
+    /// `error` at the span of `node_id`, if it is known
+    fn error_at(&self, error: CompileError, node_id: NodeId) -> RccError {
+        with_span(error, self.spans.get(&node_id).copied())
+    }
+
     fn exit_loop(&mut self) {
         self.loop_kind = self.loop_kind_stack.pop().expect("empty loop kind stack!");
     }
 
+    /// Give `{integer}` and `{float}` exprs the default type `expected_num_type`.
     fn try_determine_number_type(
+        types: &mut TypeTable,
         expected_num_type: &TypeInfo,
-        expr: &mut (impl ExprVisit + TypeInfoSetter),
+        expr: &(impl ExprVisit + TypeInfoSetter),
     ) {
-        let type_info = expr.type_info();
-        let type_info = type_info.borrow();
+        let type_info = expr.type_info(types);
 
         if expected_num_type.is_integer() && type_info.is_i()
             || expected_num_type.is_float() && type_info.is_f()
         {
-            std::mem::drop(type_info);
-            expr.set_type_info(expected_num_type.clone());
+            expr.set_type_info(types, expected_num_type.clone());
         }
     }
 
-    fn validate_ret_type(&self, type_info: &TypeInfo) -> Result<(), RccError> {
-        if type_info.is(&self.cur_fn_ret_type) {
-            Ok(())
-        } else {
-            Err(format!(
-                "invalid return type: excepted `{:?}`, found `{:?}`",
-                self.cur_fn_ret_type, type_info
-            )
-            .into())
-        }
+    /// Unify the type of `expr` with the `expected` one, e.g. `let v: Vec<i32> = Vec::new();`.
+    /// A mismatch is reported by the caller.
+    fn try_unify(types: &mut TypeTable, expected: &TypeInfo, expr: &impl ExprVisit) {
+        let found = expr.type_slot(types);
+        let expected = types.new_slot(expected.clone());
+        types.unify(expected, found).ok();
     }
-}
 
-impl SymbolResolver {
-    pub(crate) fn visit_file(&mut self, file: &mut File) -> Result<(), RccError> {
-        self.scope_stack.enter_file(file);
-        for item in file.items.iter_mut() {
-            self.visit_item(item)?;
+    /// Unify the elements of the tuple `expr` with the `expected` ones, e.g. `f((1, 2))` of
+    /// `fn f(p: (u8, u8))`.
+    fn try_unify_elems(types: &mut TypeTable, expected: &TypeInfo, expr: &Expr) {
+        match (expected, expr) {
+            (_, Expr::Grouped(e)) => Self::try_unify_elems(types, expected, e),
+            (TypeInfo::Tuple(elems), Expr::Tuple(tuple_expr))
+                if elems.len() == tuple_expr.elems.len() =>
+            {
+                for (elem, e) in elems.iter().zip(tuple_expr.elems.iter()) {
+                    Self::try_unify_elems(types, elem, e);
+                    Self::try_unify(types, elem, e);
+                }
+            }
+            _ => {}
         }
-        Ok(())
     }
 
-    fn visit_item(&mut self, item: &mut Item) -> Result<(), RccError> {
-        match item {
-            Item::Fn(item_fn) => self.visit_item_fn(item_fn),
-            Item::Struct(item_struct) => self.visit_item_struct(item_struct),
-            Item::ExternalBlock(external_block) => self.visit_item_external_block(external_block),
-            _ => unimplemented!(),
+    /// `dyn Trait` and `[T]` are only supported behind a reference, and `Trait` must be
+    /// object safe.
+    fn check_unsized(&self, type_info: &TypeInfo) -> Result<(), RccError> {
+        if let Some(trait_name) = type_info.dyn_trait() {
+            return self.check_object_safe(trait_name);
+        }
+        match type_info {
+            TypeInfo::Ptr { type_info, .. } => match type_info.as_ref() {
+                TypeInfo::Slice(elem) => self.check_unsized(elem),
+                t => self.check_unsized(t),
+            },
+            // the elements are scalars, an array is not copied into the vector
+            TypeInfo::Vec(elem) if matches!(elem.as_ref(), TypeInfo::Array(..)) => {
+                Err(format!("`Vec<{:?}>` is not supported", elem).into())
+            }
+            TypeInfo::Vec(type_info) => self.check_unsized(type_info),
+            TypeInfo::Dyn(trait_name) => {
+                Err(format!("`dyn {}` is only supported behind a reference", trait_name).into())
+            }
+            TypeInfo::Slice(elem) => {
+                let msg = format!(
+                    "the size for values of type `[{:?}]` cannot be known at compilation time",
+                    elem
+                );
+                Err(CompileError::new(ErrorKind::Type, msg).code("E0277").into())
+            }
+            _ => Ok(()),
         }
     }
 
-    fn visit_expr(&mut self, expr: &mut Expr) -> Result<(), RccError> {
-        let result = match expr {
-            Expr::Path(path_expr) => self.visit_path_expr(path_expr),
-            Expr::LitNum(lit_num_expr) => Ok(()),
-            Expr::LitBool(lit_bool) => Ok(()),
-            Expr::LitChar(lig_char) => Ok(()),
-            Expr::LitStr(s) => self.visit_lit_str(s),
-            Expr::Unary(unary_expr) => self.visit_unary_expr(unary_expr),
-            Expr::Block(block_expr) => self.visit_block_expr(block_expr),
-            Expr::Assign(assign_expr) => self.visit_assign_expr(assign_expr),
-            // Expr::Range(range_expr) => self.visit_range_expr(range_expr),
-            Expr::BinOp(bin_op_expr) => self.visit_bin_op_expr(bin_op_expr),
-            Expr::Grouped(grouped_expr) => self.visit_grouped_expr(grouped_expr),
-            // Expr::Array(array_expr) => self.visit_array_expr(array_expr),
-            // Expr::ArrayIndex(array_index_expr) => self.visit_array_index_expr(array_index_expr),
-            // Expr::Tuple(tuple_expr) => self.visit_tuple_expr(tuple_expr),
-            // Expr::TupleIndex(tuple_index_expr) => self.visit_tuple_index_expr(tuple_index_expr),
-            // Expr::Struct(struct_expr) => self.visit_struct_expr(struct_expr),
-            Expr::Call(call_expr) => self.visit_call_expr(call_expr),
-            // Expr::FieldAccess(field_access_expr) => self.visit_field_access_expr(field_access_expr),
-            Expr::While(while_expr) => self.visit_while_expr(while_expr),
-            Expr::Loop(loop_expr) => self.visit_loop_expr(loop_expr),
-            Expr::If(if_expr) => self.visit_if_expr(if_expr),
-            Expr::Return(return_expr) => self.visit_return_expr(return_expr),
-            Expr::Break(break_expr) => self.visit_break_expr(break_expr),
-            _ => unimplemented!(),
+    /// Every method of an object safe trait takes `&self` or `&mut self`, and `Self` is
+    /// not used elsewhere in its signature.
+    fn check_object_safe(&self, trait_name: &str) -> Result<(), RccError> {
+        let item_trait = match self.traits.get(trait_name) {
+            Some(item_trait) => item_trait,
+            None => return Err(format!("cannot find trait `{}`", trait_name).into()),
         };
-        debug_assert_ne!(
-            ExprKind::Unknown,
-            expr.kind(),
-            "unknown expr kind: {:?}",
-            expr
-        );
-        result
+        let self_type = TypeAnnotation::Identifier("Self".to_string());
+        for trait_fn in item_trait.fns.iter() {
+            let has_ref_self = match trait_fn.fn_params.params.first() {
+                Some(param) => match &param._type {
+                    TypeAnnotation::Ptr(tp) => {
+                        matches!(tp.ptr_kind, PtrKind::Ref | PtrKind::MutRef)
+                            && tp.type_anno.as_ref() == &self_type
+                    }
+                    _ => false,
+                },
+                None => false,
+            };
+            if !has_ref_self {
+                return Err(format!(
+                    "the trait `{}` cannot be made into an object: method `{}` has no `&self` receiver",
+                    trait_name, trait_fn.name
+                )
+                .into());
+            }
+            let uses_self = trait_fn.fn_params.params[1..]
+                .iter()
+                .map(|param| &param._type)
+                .chain(std::iter::once(&trait_fn.ret_type))
+                .any(|type_anno| mentions_type(type_anno, "Self"));
+            if uses_self {
+                return Err(format!(
+                    "the trait `{}` cannot be made into an object: method `{}` references the `Self` type",
+                    trait_name, trait_fn.name
+                )
+                .into());
+            }
+        }
+        Ok(())
     }
 
-    fn visit_lhs_expr(&mut self, lhs_expr: &mut LhsExpr) -> Result<(), RccError> {
-        let r = match lhs_expr {
-            LhsExpr::Path(expr) => self.visit_path_expr(expr)?,
-            _ => todo!("visit lhs expr"),
-        };
-        Ok(r)
+    /// Unsized coercions of `expr` into `expected`. Return the slot of the coerced value, `None`
+    /// if `expr` is not coerced.
+    fn try_coerce(
+        &mut self,
+        expected: &TypeInfo,
+        expr: &Expr,
+    ) -> Result<Option<TypeSlot>, RccError> {
+        match self.try_coerce_dyn(expected, expr)? {
+            Some(slot) => Ok(Some(slot)),
+            None => Ok(self.try_coerce_slice(expected, expr)),
+        }
     }
 
-    fn visit_grouped_expr(&mut self, grouped_expr: &mut GroupedExpr) -> Result<(), RccError> {
-        self.visit_expr(grouped_expr)
+    /// `&[T; N]` is coerced into `&[T]`, and `&mut [T; N]` into `&[T]` or `&mut [T]`. The
+    /// elements of the borrowed array are typed by the slice, e.g. `u8` of `[1, 2]` in
+    /// `let a = [1, 2]; let s: &[u8] = &a;`.
+    fn try_coerce_slice(&mut self, expected: &TypeInfo, expr: &Expr) -> Option<TypeSlot> {
+        let (expected_kind, elem) = match expected {
+            TypeInfo::Ptr { kind, type_info } => match type_info.as_ref() {
+                TypeInfo::Slice(elem) => (*kind, elem.as_ref().clone()),
+                _ => return None,
+            },
+            _ => return None,
+        };
+        let (kind, len) = match expr.type_info(&self.types) {
+            TypeInfo::Ptr {
+                kind: kind @ (PtrKind::Ref | PtrKind::MutRef),
+                type_info,
+            } => match *type_info {
+                TypeInfo::Array(_, len) => (kind, len),
+                _ => return None,
+            },
+            _ => return None,
+        };
+        // the mismatch of `&mut [T]` and `&[T; N]` is reported by the caller
+        if expected_kind == PtrKind::MutRef && kind != PtrKind::MutRef {
+            return None;
+        }
+        let array = TypeInfo::Array(Box::new(elem), len);
+        if let Expr::Unary(unary_expr) = expr {
+            Self::try_unify(&mut self.types, &array, unary_expr.expr.as_ref());
+        }
+        let ptr = TypeInfo::Ptr {
+            kind,
+            type_info: Box::new(array),
+        };
+        Self::try_unify(&mut self.types, &ptr, expr);
+        if expr.type_info(&self.types) != ptr {
+            return None;
+        }
+        Some(self.types.new_slot(expected.clone()))
     }
 
-    fn visit_item_fn(&mut self, item_fn: &mut ItemFn) -> Result<(), RccError> {
-        // enter
-        let mut temp_ret_type = Unknown;
-        std::mem::swap(&mut self.cur_fn_ret_type, &mut temp_ret_type);
-        self.cur_fn_ret_type_stack.push(temp_ret_type);
-        self.cur_fn_ret_type =
-            TypeInfo::from_type_anno(&item_fn.ret_type, self.scope_stack.cur_scope());
-
-        // visit params of function
-        for param in item_fn.fn_params.params.iter() {
-            match &param.pattern {
-                Pattern::Identifier(ident_pattern) => item_fn.fn_block.scope.add_variable(
-                    ident_pattern.ident(),
-                    if ident_pattern.is_mut() {
-                        VarKind::LocalMut
-                    } else {
-                        VarKind::Local
-                    },
-                    Rc::new(RefCell::new(TypeInfo::from_type_anno(
-                        &param._type,
-                        self.scope_stack.cur_scope(),
-                    ))),
-                ),
-            }
+    /// `&T` is coerced into `&dyn Trait` if `T` implements `Trait`, and the vtable of the impl
+    /// is recorded in the type table. Return the slot of the trait object, `None` if `expr`
+    /// is not coerced.
+    fn try_coerce_dyn(
+        &mut self,
+        expected: &TypeInfo,
+        expr: &impl ExprVisit,
+    ) -> Result<Option<TypeSlot>, RccError> {
+        let (expected_kind, trait_name) = match (expected, expected.dyn_trait()) {
+            (TypeInfo::Ptr { kind, .. }, Some(trait_name)) => (*kind, trait_name.to_string()),
+            _ => return Ok(None),
+        };
+        let (kind, mut type_info) = match expr.type_info(&self.types) {
+            TypeInfo::Ptr { kind, type_info } => (kind, *type_info),
+            _ => return Ok(None),
+        };
+        // same as the generic arguments, `&{integer}` is `&i32`
+        if type_info.is_i() || type_info.is_f() {
+            type_info = TypeInfo::LitNum(if type_info.is_i() {
+                TypeLitNum::I32
+            } else {
+                TypeLitNum::F64
+            });
+            let slot = expr.type_slot(&mut self.types);
+            let ptr = TypeInfo::Ptr {
+                kind,
+                type_info: Box::new(type_info.clone()),
+            };
+            self.types.set(slot, ptr);
         }
-        self.visit_block_expr(&mut item_fn.fn_block)?;
-        if item_fn.fn_block.last_expr.is_some() {
-            Self::try_determine_number_type(&self.cur_fn_ret_type, &mut item_fn.fn_block);
-            let type_info = item_fn.fn_block.type_info();
-            let t = type_info.borrow();
-            let tp = t.deref();
-            self.validate_ret_type(tp)?;
-        } else if item_fn.fn_block.stmts.is_empty() {
-            if item_fn.ret_type != TypeAnnotation::Unit {
+        // `&mut T` can not be coerced from `&T`, the mismatch is reported by the caller
+        if matches!(type_info, TypeInfo::Dyn(_) | TypeInfo::Unknown)
+            || !matches!(kind, PtrKind::Ref | PtrKind::MutRef)
+            || expected_kind == PtrKind::MutRef && kind != PtrKind::MutRef
+        {
+            return Ok(None);
+        }
+        let type_name = match self.impls.get(&(trait_name.clone(), type_info.clone())) {
+            Some(type_name) => type_name.clone(),
+            None => {
                 return Err(format!(
-                    "invalid return type: expected `{:?}`, found `()`",
-                    item_fn.ret_type
+                    "the trait bound `{:?}: {}` is not satisfied",
+                    type_info, trait_name
                 )
-                .into());
+                .into())
             }
-        } else {
-            let last_stmt = item_fn.fn_block.stmts.last().unwrap();
-            let type_info = last_stmt.type_info();
-            self.validate_ret_type(&type_info)?;
-        }
-
-        // restore
-        self.cur_fn_ret_type = self
-            .cur_fn_ret_type_stack
-            .pop()
-            .expect("empty cur_fn_ret_type_stack!");
-        Ok(())
+        };
+        let methods = self.traits[&trait_name]
+            .fns
+            .iter()
+            .map(|f| impl_fn_name(&type_name, &trait_name, &f.name))
+            .collect();
+        let symbol = vtable_name(&type_name, &trait_name);
+        self.types.add_vtable(&trait_name, type_info, symbol, methods);
+        Ok(Some(self.types.new_slot(expected.clone())))
     }
 
-    fn visit_item_struct(&mut self, item_struct: &mut ItemStruct) -> Result<(), RccError> {
-        Ok(())
+    /// A trait object variable `d` is followed by the variable `d$vt` of its vtable pointer,
+    /// a slice variable `s` by the variable `s$len` of its length, and a range variable `r`
+    /// by the variable `r$end` of its end.
+    fn add_variable(&mut self, scope_id: ScopeID, ident: &str, kind: VarKind, slot: TypeSlot) {
+        let is_dyn = self.types.get(slot).dyn_trait().is_some();
+        let len_slot = self
+            .types
+            .get(slot)
+            .is_slice_ref()
+            .then(|| self.types.new_slot(TypeInfo::LitNum(TypeLitNum::Usize)));
+        let end_slot = match self.types.get(slot) {
+            TypeInfo::Range { type_info, .. } => Some(self.types.new_slot(*type_info.clone())),
+            _ => None,
+        };
+        let scope = self.scope_stack.scope_mut(scope_id);
+        scope.add_variable(ident, kind, slot);
+        if is_dyn {
+            scope.add_variable(vtable_var(ident).as_str(), kind, slot);
+        }
+        if let Some(len_slot) = len_slot {
+            scope.add_variable(len_var(ident).as_str(), kind, len_slot);
+        }
+        if let Some(end_slot) = end_slot {
+            scope.add_variable(end_var(ident).as_str(), kind, end_slot);
+        }
     }
 
-    fn visit_item_external_block(
+    /// Add the variables of the param pattern `pattern` matching a value of type `slot`. The
+    /// names of the variables of the params before are `bound`.
+    fn bind_param_pattern(
         &mut self,
-        external_block: &mut ItemExternalBlock,
+        scope_id: ScopeID,
+        pattern: &Pattern,
+        slot: TypeSlot,
+        bound: &mut Vec<String>,
     ) -> Result<(), RccError> {
-        for item in &mut external_block.external_items {
-            match item {
-                ExternalItem::Fn(f) => {
-                    self.visit_external_item_fn(f)?;
+        match pattern {
+            Pattern::Identifier(ident_pattern) => {
+                let ident = ident_pattern.ident();
+                if bound.iter().any(|b| b == ident) {
+                    let msg = format!(
+                        "identifier `{}` is bound more than once in this parameter list",
+                        ident
+                    );
+                    return Err(CompileError::new(ErrorKind::Resolve, msg).code("E0415").into());
+                }
+                bound.push(ident.to_string());
+                let kind = if ident_pattern.is_mut() {
+                    VarKind::LocalMut
+                } else {
+                    VarKind::Local
+                };
+                self.add_variable(scope_id, ident, kind, slot);
+            }
+            Pattern::Wild => {}
+            Pattern::Tuple(patterns) => {
+                let elems = match self.types.get(slot) {
+                    TypeInfo::Tuple(elems) if elems.len() == patterns.len() => elems.clone(),
+                    t => {
+                        let msg = format!(
+                            "mismatched types: expected `{:?}`, found a tuple with {} elements",
+                            t,
+                            patterns.len()
+                        );
+                        return Err(CompileError::new(ErrorKind::Type, msg).code("E0308").into());
+                    }
+                };
+                for (pattern, elem) in patterns.iter().zip(elems) {
+                    let slot = self.types.new_slot(elem);
+                    self.bind_param_pattern(scope_id, pattern, slot, bound)?;
                 }
             }
         }
         Ok(())
     }
 
-    fn visit_external_item_fn(
-        &mut self,
-        _external_item_fn: &mut ExternalItemFn,
-    ) -> Result<(), RccError> {
-        // do nothing
-        Ok(())
-    }
-
-    fn visit_stmt(&mut self, stmt: &mut Stmt) -> Result<(), RccError> {
-        match stmt {
-            Stmt::Semi => Ok(()),
-            Stmt::Item(item) => self.visit_item(item),
-            Stmt::Let(let_stmt) => self.visit_let_stmt(let_stmt),
-            Stmt::ExprStmt(expr) => {
+    fn validate_ret_type(&self, type_info: &TypeInfo) -> Result<(), RccError> {
+        if type_info.is(&self.cur_fn_ret_type) {
+            Ok(())
+        } else {
+            Err(format!(
+                "invalid return type: excepted `{:?}`, found `{:?}`",
+                self.cur_fn_ret_type, type_info
+            )
+            .into())
+        }
+    }
+}
+
+impl SymbolResolver {
+    pub(crate) fn visit_file(&mut self, file: &mut File) -> Result<(), RccError> {
+        cfg::eval_cfgs(file, &self.cfg_options)?;
+        if self.eval_array_lens {
+            eval_array_lens(file)?;
+        }
+        self.scope_stack.enter_file(file);
+        self.types = std::mem::take(&mut file.type_table);
+        self.node_count = file.node_count;
+        self.spans = std::mem::take(&mut file.spans);
+        self.sig_spans = std::mem::take(&mut file.sig_spans);
+        self.arg_spans = std::mem::take(&mut file.arg_spans);
+        self.def_spans = std::mem::take(&mut file.def_spans);
+        for item in file.items.iter() {
+            if let Item::Fn(item_fn) = item {
+                self.plain_symbols.insert(item_fn.name.clone());
+            }
+        }
+        let result = self
+            .declare_assoc_consts(file)
+            .and_then(|_| self.declare_items(&file.items))
+            .and_then(|_| self.check_entry(&file.items))
+            .and_then(|_| file.items.iter_mut().try_for_each(|item| self.visit_item(item)))
+            .and_then(|_| self.visit_instances(file))
+            .and_then(|_| self.check_lit_ranges())
+            .and_then(|_| self.check_backend_nums());
+        file.type_table = std::mem::take(&mut self.types);
+        file.node_count = self.node_count;
+        file.spans = std::mem::take(&mut self.spans);
+        file.sig_spans = std::mem::take(&mut self.sig_spans);
+        file.arg_spans = std::mem::take(&mut self.arg_spans);
+        file.def_spans = std::mem::take(&mut self.def_spans);
+        file.references = std::mem::take(&mut self.references);
+        self.scope_stack.exit_file(file);
+        result
+    }
+
+    /// Integer literals must fit in their types, which are only known after all the items
+    /// are visited, e.g. `let a: i8 = -128;` is valid but `let a: i8 = 128;` is not.
+    fn check_lit_ranges(&self) -> Result<(), RccError> {
+        for (node_id, value) in self.lit_nums.iter() {
+            let lit_type = match self.types.node_type(*node_id) {
+                Some(TypeInfo::LitNum(TypeLitNum::I)) => TypeLitNum::I32,
+                Some(TypeInfo::LitNum(t)) if t.is_integer() => *t,
+                _ => continue,
+            };
+            let (min, max) = int_range(lit_type, self.pointer_width);
+            let negated = self.negated_lits.contains(node_id);
+            let fits = match value.parse::<u128>() {
+                Ok(n) if negated => n <= min.unsigned_abs(),
+                Ok(n) => n <= max,
+                Err(_) => false,
+            };
+            if !fits {
+                let sign = if negated { "-" } else { "" };
+                let msg = format!("literal out of range for `{}`", lit_type);
+                let note = format!(
+                    "the literal `{}{}` does not fit into the type `{}` whose range is `{}..={}`",
+                    sign, value, lit_type, min, max
+                );
+                let error = CompileError::new(ErrorKind::Type, msg).note(note);
+                return Err(self.error_at(error, *node_id));
+            }
+        }
+        Ok(())
+    }
+
+    /// The first expr whose type has a number the backend can not compile, the ones with a
+    /// span are preferred
+    fn check_backend_nums(&self) -> Result<(), RccError> {
+        let mut first = None;
+        for node_id in self.types.node_ids() {
+            let type_info = self.types.node_type(node_id).unwrap();
+            if let Some(error) = self.backend_num_error(type_info) {
+                if self.spans.contains_key(&node_id) {
+                    return Err(self.error_at(error, node_id));
+                }
+                first.get_or_insert(error);
+            }
+        }
+        match first {
+            Some(error) => Err(error.into()),
+            None => Ok(()),
+        }
+    }
+
+    /// `type_info`, or a type in it, is a float or an integer wider than a register
+    fn backend_num_error(&self, type_info: &TypeInfo) -> Option<CompileError> {
+        if !self.codegen {
+            return None;
+        }
+        let lit_type = backend_num(type_info, self.pointer_width)?;
+        if lit_type.is_float() {
+            let msg = format!("`{}` is not supported by the backend", lit_type);
+            let note = "floating-point numbers are only evaluated by the IR interpreter yet";
+            return Some(CompileError::new(ErrorKind::Type, msg).note(note));
+        }
+        let msg = format!(
+            "`{}` is not supported by the {}-bit target",
+            lit_type, self.pointer_width
+        );
+        let note = "numbers wider than a register of the target are not supported yet";
+        Some(CompileError::new(ErrorKind::Type, msg).note(note))
+    }
+
+    /// The constants of `impl Point` become constants of the file named by their symbols,
+    /// e.g. `Point::N` is the constant `_RCI5Point1N`.
+    fn declare_assoc_consts(&mut self, file: &mut File) -> Result<(), RccError> {
+        let mut consts = vec![];
+        for item in file.items.iter_mut() {
+            let item_impl = match item {
+                Item::Impl(item_impl) if !item_impl.consts.is_empty() => item_impl,
+                _ => continue,
+            };
+            if let Some(trait_name) = &item_impl.trait_name {
+                let msg = format!(
+                    "const `{}` is not a member of trait `{}`",
+                    item_impl.consts[0].name, trait_name
+                );
+                return Err(CompileError::new(ErrorKind::Resolve, msg).code("E0438").into());
+            }
+            let self_type = TypeInfo::from_type_anno(&item_impl.self_type, &self.scope_stack);
+            for mut item_const in std::mem::take(&mut item_impl.consts) {
+                let symbol = inherent_item_name(&item_impl.self_type_name(), &item_const.name);
+                let key = (self_type.clone(), item_const.name.clone());
+                if self.assoc_items.insert(key, symbol.clone()).is_some() {
+                    let msg = format!("duplicate definitions with name `{}`", item_const.name);
+                    return Err(CompileError::new(ErrorKind::Resolve, msg).code("E0592").into());
+                }
+                item_const.name = symbol;
+                consts.push(Item::Const(item_const));
+            }
+        }
+        file.items.extend(consts);
+        Ok(())
+    }
+
+    /// Traits, impls and generic functions may be used before they are declared.
+    fn declare_items(&mut self, items: &[Item]) -> Result<(), RccError> {
+        self.declare_type_aliases(items.iter())?;
+        for item in items.iter() {
+            if let Item::Trait(item_trait) = item {
+                if self.traits.contains_key(&item_trait.name) {
+                    return Err(
+                        format!("the trait `{}` is defined multiple times", item_trait.name).into(),
+                    );
+                }
+                self.traits.insert(item_trait.name.clone(), item_trait.clone());
+            }
+        }
+        for item in items.iter() {
+            match item {
+                Item::Impl(item_impl) => self.declare_impl(item_impl)?,
+                Item::Const(item_const) => self.declare_const(item_const)?,
+                Item::Struct(item_struct) => self.declare_struct(item_struct)?,
+                Item::Fn(item_fn) if item_fn.is_generic() => {
+                    for bound in item_fn.generics.iter().flat_map(|g| g.bounds.iter()) {
+                        if !self.traits.contains_key(bound) {
+                            return Err(format!("cannot find trait `{}`", bound).into());
+                        }
+                    }
+                    self.generic_fns.insert(item_fn.name.clone(), item_fn.clone());
+                }
+                _ => {}
+            }
+            if let Item::Fn(item_fn) = item {
+                if item_fn.is_const {
+                    self.const_fns.insert(item_fn.name.clone());
+                }
+                if item_fn.is_unsafe {
+                    self.unsafe_fns.insert(item_fn.name.clone());
+                }
+            }
+        }
+        for item in items.iter() {
+            if let Item::Struct(item_struct) = item {
+                self.check_recursive_struct(item_struct)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// `main` of an executable is called by `_start`, which exits with its return value, so it
+    /// takes nothing and returns `()`, `i32` or `!`.
+    fn check_entry(&self, items: &[Item]) -> Result<(), RccError> {
+        if !self.entry {
+            return Ok(());
+        }
+        let main = items.iter().find_map(|item| match item {
+            Item::Fn(item_fn) if item_fn.name == "main" => Some(item_fn),
+            _ => None,
+        });
+        let main = match main {
+            Some(main) => main,
+            None => {
+                return Err(CompileError::new(ErrorKind::Resolve, "`main` function not found")
+                    .code("E0601")
+                    .help("consider adding a `main` function")
+                    .into())
+            }
+        };
+        let error = if !main.generics.is_empty() {
+            CompileError::new(
+                ErrorKind::Type,
+                "`main` function is not allowed to have generic parameters",
+            )
+            .code("E0131")
+        } else if !main.fn_params.params.is_empty() {
+            let params: Vec<String> = main.params().iter().map(type_anno).collect();
+            let msg = format!(
+                "`main` function has wrong type: expected `fn()`, found `fn({})`",
+                params.join(", ")
+            );
+            CompileError::new(ErrorKind::Type, msg).code("E0580")
+        } else if !matches!(
+            TypeInfo::from_type_anno(&main.ret_type, &self.scope_stack),
+            TypeInfo::Unit | TypeInfo::Never | TypeInfo::LitNum(TypeLitNum::I32)
+        ) {
+            let msg = format!("`main` has invalid return type `{}`", type_anno(&main.ret_type));
+            CompileError::new(ErrorKind::Type, msg)
+                .code("E0277")
+                .note("the return value of `main` is the exit status of the program")
+                .help("consider using `()` or `i32` as the return type of `main`")
+        } else {
+            return Ok(());
+        };
+        match self.sig_spans.get(&main.fn_block.node_id) {
+            Some(span) => Err(error.span(*span).into()),
+            None => Err(error.into()),
+        }
+    }
+
+    /// A constant is a variable of the file scope, so it can be used before it is declared.
+    /// Only constants of primitive types and structs are supported.
+    fn declare_const(&mut self, item_const: &ItemConst) -> Result<(), RccError> {
+        let type_info = TypeInfo::from_type_anno(&item_const.type_anno, &self.scope_stack);
+        if !matches!(
+            type_info,
+            TypeInfo::Bool | TypeInfo::Char | TypeInfo::LitNum(_) | TypeInfo::Struct { .. }
+        ) {
+            return Err(format!(
+                "the type of constant `{}` must be a primitive type or a struct, found `{:?}`",
+                demangle(&item_const.name).unwrap_or_else(|| item_const.name.clone()),
+                item_const.type_anno
+            )
+            .into());
+        }
+        let slot = self.types.new_slot(type_info);
+        let scope_id = self.scope_stack.cur_scope().scope_id;
+        self.add_variable(scope_id, &item_const.name, VarKind::Const, slot);
+        Ok(())
+    }
+
+    /// The fields of a struct are resolved before the struct is used, its type is added to
+    /// the scope by the parser. Fields owning heap memory or holding trait objects are not
+    /// supported.
+    fn declare_struct(&mut self, item_struct: &ItemStruct) -> Result<(), RccError> {
+        let struct_fields = match item_struct.fields() {
+            Fields::Struct(struct_fields) => struct_fields.as_slice(),
+            Fields::None => &[],
+            Fields::Tuple(_) => {
+                return Err(
+                    format!("tuple struct `{}` is not supported", item_struct.name()).into(),
+                )
+            }
+        };
+        let mut fields: Vec<(String, TypeInfo)> = vec![];
+        for field in struct_fields.iter() {
+            if fields.iter().any(|(name, _)| name == &field.name) {
+                return Err(format!("field `{}` is already declared", field.name).into());
+            }
+            let type_info = TypeInfo::from_type_anno(&field._type, &self.scope_stack);
+            if type_info.is_unknown() {
+                return Err(format!("cannot find type `{:?}`", field._type).into());
+            }
+            if type_info.owns_heap()
+                || type_info.dyn_trait().is_some()
+                || type_info.is_slice_ref()
+                || matches!(
+                    type_info,
+                    TypeInfo::Unit | TypeInfo::Never | TypeInfo::Tuple(_) | TypeInfo::Array(..)
+                )
+            {
+                return Err(format!(
+                    "field `{}` of type `{:?}` is not supported",
+                    field.name, field._type
+                )
+                .into());
+            }
+            fields.push((field.name.clone(), type_info));
+        }
+        self.types.add_struct(item_struct.name(), fields);
+        Ok(())
+    }
+
+    /// Adds the aliases of a scope to it. An alias may refer to the structs and aliases declared
+    /// later, so each one is added once its target is found.
+    fn declare_type_aliases<'a>(
+        &mut self,
+        items: impl Iterator<Item = &'a Item>,
+    ) -> Result<(), RccError> {
+        let mut aliases: Vec<&ItemTypeAlias> = items
+            .filter_map(|item| match item {
+                Item::Type(item_type_alias) => Some(item_type_alias),
+                _ => None,
+            })
+            .collect();
+        while !aliases.is_empty() {
+            let count = aliases.len();
+            aliases.retain(|alias| {
+                if self.unknown_type_name(&alias.type_anno).is_some() {
+                    return true;
+                }
+                let type_info = TypeInfo::from_type_anno(&alias.type_anno, &self.scope_stack);
+                self.scope_stack.cur_scope_mut().add_type_alias(&alias.name, type_info);
+                false
+            });
+            if aliases.len() == count {
+                let alias = aliases[0];
+                let name = self.unknown_type_name(&alias.type_anno).unwrap();
+                let error = if aliases.iter().any(|alias| alias.name == name) {
+                    let msg = format!("cycle detected when expanding type alias `{}`", alias.name);
+                    CompileError::new(ErrorKind::Type, msg).code("E0391")
+                } else {
+                    let msg = format!("cannot find type `{}` in this scope", name);
+                    CompileError::new(ErrorKind::Resolve, msg).code("E0412")
+                };
+                return Err(error.into());
+            }
+        }
+        Ok(())
+    }
+
+    /// The first name in `type_anno` which is not a type visible in current scope
+    fn unknown_type_name<'a>(&self, type_anno: &'a TypeAnnotation) -> Option<&'a str> {
+        match type_anno {
+            TypeAnnotation::Identifier(name) => Some(name.as_str())
+                .filter(|name| self.scope_stack.find_def_except_fn(name).is_unknown()),
+            TypeAnnotation::Ptr(TypePtr { type_anno, .. }) | TypeAnnotation::Vec(type_anno) => {
+                self.unknown_type_name(type_anno)
+            }
+            _ => None,
+        }
+    }
+
+    /// Called after all the structs of a scope are declared, since the fields may refer to
+    /// the structs declared later.
+    fn check_recursive_struct(&self, item_struct: &ItemStruct) -> Result<(), RccError> {
+        let name = item_struct.name();
+        let recursive = self
+            .types
+            .struct_fields(name)
+            .iter()
+            .any(|(_, field)| self.types.contains_struct(field, name));
+        if recursive {
+            return Err(format!(
+                "recursive type `{}` has infinite size, insert some indirection (e.g., a `&`) to make `{}` representable",
+                name, name
+            )
+            .into());
+        }
+        Ok(())
+    }
+
+    fn declare_impl(&mut self, item_impl: &ItemImpl) -> Result<(), RccError> {
+        if let Some(trait_name) = &item_impl.trait_name {
+            if !self.traits.contains_key(trait_name) {
+                return Err(format!("cannot find trait `{}`", trait_name).into());
+            }
+        }
+        let self_type = TypeInfo::from_type_anno(&item_impl.self_type, &self.scope_stack);
+        if self_type.is_unknown() {
+            return Err(format!("cannot find type `{:?}`", item_impl.self_type).into());
+        }
+        let trait_name = match &item_impl.trait_name {
+            Some(trait_name) => trait_name,
+            None => return self.declare_inherent_impl(item_impl, self_type),
+        };
+        if self
+            .impls
+            .insert(
+                (trait_name.clone(), self_type.clone()),
+                item_impl.self_type_name(),
+            )
+            .is_some()
+        {
+            return Err(format!(
+                "conflicting implementations of trait `{}` for type `{:?}`",
+                trait_name, self_type
+            )
+            .into());
+        }
+        for item_fn in item_impl.fns.iter() {
+            let key = (self_type.clone(), item_fn.name.clone());
+            self.declare_method(key, item_impl.fn_symbol(item_fn))?;
+        }
+        Ok(())
+    }
+
+    /// The functions of `impl Point` are called by `Point::f`, and the methods among them
+    /// also by `p.f()`. Only the structs of the file have inherent impls.
+    fn declare_inherent_impl(
+        &mut self,
+        item_impl: &ItemImpl,
+        self_type: TypeInfo,
+    ) -> Result<(), RccError> {
+        match &self_type {
+            TypeInfo::Struct { .. } => {}
+            TypeInfo::LitNum(_) | TypeInfo::Bool | TypeInfo::Char => {
+                let msg = "cannot define inherent `impl` for primitive types";
+                return Err(CompileError::new(ErrorKind::Type, msg).code("E0390").into());
+            }
+            _ => {
+                let msg = "cannot define inherent `impl` for a type outside of the crate where \
+                           the type is defined";
+                return Err(CompileError::new(ErrorKind::Type, msg).code("E0116").into());
+            }
+        }
+        for item_fn in item_impl.fns.iter() {
+            let key = (self_type.clone(), item_fn.name.clone());
+            let symbol = item_impl.fn_symbol(item_fn);
+            if self.assoc_items.insert(key.clone(), symbol.clone()).is_some() {
+                let msg = format!("duplicate definitions with name `{}`", item_fn.name);
+                return Err(CompileError::new(ErrorKind::Resolve, msg).code("E0592").into());
+            }
+            if item_fn.has_receiver() {
+                self.declare_method(key, symbol)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn declare_method(&mut self, key: (TypeInfo, String), symbol: String) -> Result<(), RccError> {
+        if self.methods.contains_key(&key) {
+            let (self_type, name) = key;
+            return Err(format!(
+                "multiple applicable items in scope: `{}` of `{:?}`",
+                name, self_type
+            )
+            .into());
+        }
+        self.methods.insert(key, symbol);
+        Ok(())
+    }
+
+    /// Resolve the instances of the generic functions, which are added to the items of
+    /// `file`. An instance may call generic functions and add more instances.
+    fn visit_instances(&mut self, file: &mut File) -> Result<(), RccError> {
+        let mut idx = 0;
+        while idx < self.instances.len() {
+            let (fn_name, args) = &self.instances[idx];
+            let symbol = generic_fn_name(fn_name, &generic_arg_names(args));
+            let mut instance = mono::instantiate(
+                &self.generic_fns[fn_name],
+                symbol,
+                args,
+                self.scope_stack.scopes_mut(),
+                &mut self.node_count,
+            );
+            self.visit_item_fn(&mut instance)?;
+            file.items.push(Item::Fn(instance));
+            idx += 1;
+        }
+        Ok(())
+    }
+
+    fn visit_item(&mut self, item: &mut Item) -> Result<(), RccError> {
+        match item {
+            // resolved for each list of generic arguments by `visit_instances`
+            Item::Fn(item_fn) if item_fn.is_generic() => {
+                if self.scope_stack.cur_scope_is_global() {
+                    Ok(())
+                } else {
+                    Err(format!(
+                        "generic function `{}` must be declared at the top level",
+                        item_fn.name
+                    )
+                    .into())
+                }
+            }
+            Item::Fn(item_fn) => self.visit_item_fn(item_fn),
+            Item::Struct(item_struct) => self.visit_item_struct(item_struct),
+            Item::ExternalBlock(external_block) => self.visit_item_external_block(external_block),
+            Item::Impl(item_impl) => self.visit_item_impl(item_impl),
+            Item::Trait(_) | Item::Type(_) => Ok(()),
+            Item::Const(item_const) => self.visit_item_const(item_const),
+            _ => unimplemented!(),
+        }
+    }
+
+    fn visit_item_const(&mut self, item_const: &mut ItemConst) -> Result<(), RccError> {
+        if !self.scope_stack.cur_scope_is_global() {
+            return Err(format!(
+                "constant `{}` must be declared at the top level",
+                item_const.name
+            )
+            .into());
+        }
+        let const_context = self.const_context.replace("constants");
+        self.visit_expr(&mut item_const.expr)?;
+        self.const_context = const_context;
+
+        let (var_info, _) = self.scope_stack.find_variable(&item_const.name).unwrap();
+        let type_info = self.types.get(var_info.type_info).clone();
+        Self::try_unify(&mut self.types, &type_info, &item_const.expr);
+        assert_type_is(
+            &self.types,
+            &item_const.expr,
+            &type_info,
+            "mismatched types",
+        )
+    }
+
+    /// Only `const fn`s can be called in constants and `const fn`s.
+    fn check_const_call(&self, call_expr: &CallExpr, context: &str) -> Result<(), RccError> {
+        let name = match (call_expr.method_name(), call_expr.expr.deref()) {
+            (Some(method), _) => method.to_string(),
+            (None, Expr::Path(path_expr)) => {
+                let name = path_expr.segments.join("::");
+                let is_const_fn = self.scope_stack.find_variable(&name).is_none()
+                    && (self.const_fns.contains(&name)
+                        || self.generic_fns.get(&name).is_some_and(|f| f.is_const));
+                if is_const_fn {
+                    return Ok(());
+                }
+                name
+            }
+            (None, _) => {
+                return Err(format!("function pointers cannot be called in {}", context).into())
+            }
+        };
+        Err(format!("cannot call non-const fn `{}` in {}", name, context).into())
+    }
+
+    /// `operation` is only allowed in an `unsafe` block or an `unsafe fn`, `note` tells why.
+    fn check_unsafe(&self, operation: &str, note: &str) -> Result<(), RccError> {
+        if self.in_unsafe {
+            return Ok(());
+        }
+        let msg = format!("{} is unsafe and requires unsafe function or block", operation);
+        Err(CompileError::new(ErrorKind::Type, msg).code("E0133").note(note).into())
+    }
+
+    fn visit_expr(&mut self, expr: &mut Expr) -> Result<(), RccError> {
+        match expr {
+            Expr::Path(path_expr) => self.visit_path_expr(path_expr),
+            Expr::LitNum(lit_num_expr) => {
+                self.lit_nums.push((lit_num_expr.node_id, lit_num_expr.value.clone()));
+                Ok(())
+            }
+            Expr::LitBool(lit_bool) => Ok(()),
+            Expr::LitChar(lig_char) => Ok(()),
+            Expr::LitStr(s) => self.visit_lit_str(s),
+            // `[u8; N]` needs array types
+            Expr::LitByteStr(bytes) => Err(format!(
+                "byte string literal of type `&[u8; {}]` is not supported",
+                bytes.len()
+            )
+            .into()),
+            Expr::Unary(unary_expr) => self.visit_unary_expr(unary_expr),
+            Expr::Block(block_expr) => self.visit_block_expr(block_expr),
+            Expr::Assign(assign_expr) => self.visit_assign_expr(assign_expr),
+            Expr::Range(range_expr) => self.visit_range_expr(range_expr),
+            Expr::BinOp(bin_op_expr) => self.visit_bin_op_expr(bin_op_expr),
+            Expr::Cast(cast_expr) => self.visit_cast_expr(cast_expr),
+            Expr::Grouped(grouped_expr) => self.visit_grouped_expr(grouped_expr),
+            Expr::Array(array_expr) => self.visit_array_expr(array_expr),
+            Expr::ArrayIndex(array_index_expr) => self.visit_array_index_expr(array_index_expr),
+            Expr::Tuple(tuple_expr) => self.visit_tuple_expr(tuple_expr),
+            // Expr::TupleIndex(tuple_index_expr) => self.visit_tuple_index_expr(tuple_index_expr),
+            Expr::Struct(struct_expr) => self.visit_struct_expr(struct_expr),
+            Expr::Call(call_expr) => self.visit_call_expr(call_expr),
+            Expr::FieldAccess(field_access_expr) => self.visit_field_access_expr(field_access_expr),
+            Expr::While(while_expr) => self.visit_while_expr(while_expr),
+            Expr::Loop(loop_expr) => self.visit_loop_expr(loop_expr),
+            Expr::For(for_expr) => self.visit_for_expr(for_expr),
+            Expr::If(if_expr) => self.visit_if_expr(if_expr),
+            Expr::Return(return_expr) => self.visit_return_expr(return_expr),
+            Expr::Break(break_expr) => self.visit_break_expr(break_expr),
+            Expr::Print(print_expr) => self.visit_print_expr(print_expr),
+            Expr::Assert(assert_expr) => self.visit_assert_expr(assert_expr),
+            Expr::Asm(asm_expr) => self.visit_asm_expr(asm_expr),
+            _ => unimplemented!(),
+        }?;
+        debug_assert_ne!(
+            ExprKind::Unknown,
+            expr.kind(),
+            "unknown expr kind: {:?}",
+            expr
+        );
+        Ok(())
+    }
+
+    fn visit_lhs_expr(&mut self, lhs_expr: &mut LhsExpr) -> Result<(), RccError> {
+        let r = match lhs_expr {
+            LhsExpr::Path(expr) => self.visit_path_expr(expr)?,
+            LhsExpr::Deref(expr) => {
+                self.visit_expr(expr)?;
+                let type_info = expr.type_info(&self.types);
+                if !matches!(type_info, TypeInfo::Ptr { .. }) {
+                    return Err(format!("type `{:?}` can not be dereferenced", type_info).into());
+                }
+                if type_info.is_raw_ptr() {
+                    self.check_unsafe("dereference of raw pointer", RAW_PTR_DEREF)?;
+                }
+            }
+            LhsExpr::ArrayIndex(expr) => self.visit_array_index_expr(expr)?,
+            LhsExpr::FieldAccess(expr) => self.visit_field_access_expr(expr)?,
+            _ => todo!("visit lhs expr"),
+        };
+        Ok(r)
+    }
+
+    fn visit_grouped_expr(&mut self, grouped_expr: &mut GroupedExpr) -> Result<(), RccError> {
+        self.visit_expr(grouped_expr)
+    }
+
+    fn visit_item_fn(&mut self, item_fn: &mut ItemFn) -> Result<(), RccError> {
+        // a function declared in a block is mangled unless it is `#[no_mangle]`
+        if item_fn.no_mangle
+            && self.fn_scope.is_some()
+            && !self.plain_symbols.insert(item_fn.name.clone())
+        {
+            let msg = format!("symbol `{}` is already defined", item_fn.name);
+            return Err(CompileError::new(ErrorKind::Resolve, msg).into());
+        }
+        // enter
+        let const_context = std::mem::replace(
+            &mut self.const_context,
+            item_fn.is_const.then_some("constant functions"),
+        );
+        // `break` in a nested function does not break the outer loop
+        let loop_kind = std::mem::replace(&mut self.loop_kind, NotIn);
+        let in_unsafe = std::mem::replace(&mut self.in_unsafe, item_fn.is_unsafe);
+        let fn_scope = self.fn_scope.replace(item_fn.fn_block.scope_id);
+        let mut temp_ret_type = Unknown;
+        std::mem::swap(&mut self.cur_fn_ret_type, &mut temp_ret_type);
+        self.cur_fn_ret_type_stack.push(temp_ret_type);
+        self.cur_fn_ret_type =
+            TypeInfo::from_type_anno(&item_fn.ret_type, &self.scope_stack);
+        if let Some(trait_name) = self.cur_fn_ret_type.dyn_trait() {
+            return Err(format!("returning `&dyn {}` is not supported", trait_name).into());
+        }
+        let ret_type = &self.cur_fn_ret_type;
+        if ret_type.is_slice_ref() || matches!(ret_type, TypeInfo::Tuple(_)) {
+            let msg = format!("returning `{}` is not supported", type_anno(&item_fn.ret_type));
+            return Err(msg.into());
+        }
+        self.check_unsized(&self.cur_fn_ret_type)?;
+        let sig_span = self.sig_spans.get(&item_fn.fn_block.node_id).copied();
+        if let Some(error) = self.backend_num_error(&self.cur_fn_ret_type) {
+            return Err(with_span(error, sig_span));
+        }
+
+        // visit params of function
+        let scope_id = item_fn.fn_block.scope_id;
+        let mut bound = vec![];
+        for (i, param) in item_fn.fn_params.params.iter().enumerate() {
+            let type_info = TypeInfo::from_type_anno(&param._type, &self.scope_stack);
+            self.check_unsized(&type_info)?;
+            check_tuple(&type_info)?;
+            if let Some(error) = self.backend_num_error(&type_info) {
+                return Err(with_span(error, sig_span));
+            }
+            let slot = self.types.new_slot(type_info);
+            if !matches!(param.pattern, Pattern::Identifier(_)) {
+                // bound to the pattern at the start of the function by `IRBuilder`
+                self.add_variable(scope_id, param_var(i).as_str(), VarKind::Local, slot);
+            }
+            self.bind_param_pattern(scope_id, &param.pattern, slot, &mut bound)?;
+        }
+        self.visit_block_expr(&mut item_fn.fn_block)?;
+        let body_type = if item_fn.fn_block.last_expr.is_some() {
+            Self::try_unify(&mut self.types, &self.cur_fn_ret_type, &item_fn.fn_block);
+            item_fn.fn_block.type_info(&self.types)
+        } else {
+            match item_fn.fn_block.stmts.last() {
+                Some(last_stmt) => last_stmt.type_info(&self.types),
+                None => TypeInfo::Unit,
+            }
+        };
+        // a path which does not end with `return` or `!` gives `()`
+        if body_type == TypeInfo::Unit && self.cur_fn_ret_type != TypeInfo::Unit {
+            let msg = format!(
+                "missing return value in function `{}`: expected `{}`, found `()`",
+                item_fn.name, self.cur_fn_ret_type
+            );
+            let mut error = CompileError::new(ErrorKind::Type, msg)
+                .code("E0308")
+                .note("the body has no tail expression, and not every path of it returns");
+            if let Some(span) = self.sig_spans.get(&item_fn.fn_block.node_id) {
+                error = error.span(*span);
+            }
+            return Err(error.into());
+        }
+        self.validate_ret_type(&body_type)?;
+
+        // restore
+        self.cur_fn_ret_type = self
+            .cur_fn_ret_type_stack
+            .pop()
+            .expect("empty cur_fn_ret_type_stack!");
+        self.const_context = const_context;
+        self.in_unsafe = in_unsafe;
+        self.loop_kind = loop_kind;
+        self.fn_scope = fn_scope;
+        Ok(())
+    }
+
+    fn visit_item_struct(&mut self, item_struct: &mut ItemStruct) -> Result<(), RccError> {
+        Ok(())
+    }
+
+    /// Check the methods against the signatures required by the trait.
+    fn visit_item_impl(&mut self, item_impl: &mut ItemImpl) -> Result<(), RccError> {
+        let item_trait = match &item_impl.trait_name {
+            Some(trait_name) => &self.traits[trait_name],
+            None => {
+                for item_fn in item_impl.fns.iter_mut() {
+                    self.visit_item_fn(item_fn)?;
+                }
+                return Ok(());
+            }
+        };
+        for item_fn in item_impl.fns.iter() {
+            let trait_fn = match item_trait.fns.iter().find(|f| f.name == item_fn.name) {
+                Some(trait_fn) => trait_fn,
+                None => {
+                    return Err(format!(
+                        "method `{}` is not a member of trait `{}`",
+                        item_fn.name, item_trait.name
+                    )
+                    .into())
+                }
+            };
+            let expected = trait_fn.fn_params.params.len();
+            if item_fn.fn_params.params.len() != expected {
+                return Err(format!(
+                    "method `{}` has {} parameters but the declaration in trait `{}` has {}",
+                    item_fn.name,
+                    item_fn.fn_params.params.len(),
+                    item_trait.name,
+                    expected
+                )
+                .into());
+            }
+            // `Self` in the trait is the self type of the impl
+            let self_arg = [GenericArg {
+                name: "Self".to_string(),
+                type_info: TypeInfo::Unknown,
+                type_anno: item_impl.self_type.clone(),
+            }];
+            let types = item_fn
+                .fn_params
+                .type_annotations()
+                .into_iter()
+                .zip(trait_fn.fn_params.type_annotations())
+                .chain(std::iter::once((
+                    item_fn.ret_type.clone(),
+                    trait_fn.ret_type.clone(),
+                )));
+            for (found, mut expected) in types {
+                mono::subst_type_anno(&mut expected, &self_arg);
+                if TypeInfo::from_type_anno(&found, &self.scope_stack)
+                    != TypeInfo::from_type_anno(&expected, &self.scope_stack)
+                {
+                    return Err(format!(
+                        "method `{}` has an incompatible type for trait `{}`: expected `{:?}`, found `{:?}`",
+                        item_fn.name, item_trait.name, expected, found
+                    )
+                    .into());
+                }
+            }
+        }
+        for trait_fn in item_trait.fns.iter() {
+            if !item_impl.fns.iter().any(|f| f.name == trait_fn.name) {
+                return Err(format!(
+                    "not all trait items implemented, missing: `{}`",
+                    trait_fn.name
+                )
+                .into());
+            }
+        }
+        for item_fn in item_impl.fns.iter_mut() {
+            self.visit_item_fn(item_fn)?;
+        }
+        Ok(())
+    }
+
+    fn visit_item_external_block(
+        &mut self,
+        external_block: &mut ItemExternalBlock,
+    ) -> Result<(), RccError> {
+        for item in &mut external_block.external_items {
+            match item {
+                ExternalItem::Fn(f) => {
+                    self.visit_external_item_fn(f)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn visit_external_item_fn(
+        &mut self,
+        _external_item_fn: &mut ExternalItemFn,
+    ) -> Result<(), RccError> {
+        // do nothing
+        Ok(())
+    }
+
+    fn visit_stmt(&mut self, stmt: &mut Stmt) -> Result<(), RccError> {
+        match stmt {
+            Stmt::Semi => Ok(()),
+            Stmt::Item(item) => self.visit_item(item),
+            Stmt::Let(let_stmt) => self.visit_let_stmt(let_stmt),
+            Stmt::ExprStmt(expr) => {
                 self.visit_expr(expr)?;
-                let t = expr.type_info();
-                let tp = t.borrow();
-                let type_info = tp.deref();
-                if expr.with_block() && type_info != &TypeInfo::Unit && !type_info.is_never() {
+                let type_info = expr.type_info(&self.types);
+                if expr.with_block() && type_info != TypeInfo::Unit && !type_info.is_never() {
                     return Err(format!(
                         "invalid type for expr stmt: expected `()`, found {:?}",
                         type_info
@@ -641,39 +1902,48 @@ impl SymbolResolver {
     fn visit_let_stmt(&mut self, let_stmt: &mut LetStmt) -> Result<(), RccError> {
         let expr_type_info = if let Some(expr) = &mut let_stmt.rhs {
             self.visit_expr(expr)?;
+            let mut coerced = None;
             if let Some(type_anno) = &let_stmt._type {
                 let anno_type_info =
-                    TypeInfo::from_type_anno(type_anno, self.scope_stack.cur_scope());
-                Self::try_determine_number_type(&anno_type_info, expr);
-                let t = expr.type_info();
-                let tp = t.borrow();
-                let expr_type_info = tp.deref();
-                if !expr_type_info.is(&anno_type_info) {
-                    return Err(format!(
-                        "invalid type in let stmt: expected `{:?}`, found `{:?}`",
-                        anno_type_info, expr_type_info
-                    )
-                    .into());
+                    TypeInfo::from_type_anno(type_anno, &self.scope_stack);
+                self.check_unsized(&anno_type_info)?;
+                coerced = self.try_coerce(&anno_type_info, expr)?;
+                if coerced.is_none() {
+                    Self::try_unify(&mut self.types, &anno_type_info, expr);
+                    let expr_type_info = expr.type_info(&self.types);
+                    if !expr_type_info.is(&anno_type_info) {
+                        let msg = format!(
+                            "invalid type in let stmt: expected `{:?}`, found `{:?}`",
+                            anno_type_info, expr_type_info
+                        );
+                        return Err(CompileError::new(ErrorKind::Type, msg).code("E0308").into());
+                    }
                 }
             }
-            expr.type_info()
+            match coerced {
+                Some(slot) => slot,
+                None => expr.type_slot(&mut self.types),
+            }
         } else {
-            Rc::new(RefCell::new(Unknown))
+            self.types.new_slot(Unknown)
         };
 
-        match &let_stmt.pattern {
-            Pattern::Identifier(ident_pattern) => {
-                self.scope_stack.cur_scope_mut().add_variable(
-                    ident_pattern.ident(),
-                    if ident_pattern.is_mut() {
-                        VarKind::LocalMut
-                    } else {
-                        VarKind::Local
-                    },
-                    expr_type_info,
-                );
-            }
+        if let TypeInfo::Tuple(_) = self.types.get(expr_type_info) {
+            return Err(TUPLE_VALUES.into());
         }
+        let ident = pattern_var(&let_stmt.pattern)?;
+        let kind = if let_stmt.pattern.is_mut() {
+            VarKind::LocalMut
+        } else {
+            VarKind::Local
+        };
+        // the variable is visible from the next statement, so that a variable of the same name
+        // used by the initializer is the one declared before
+        let scope = self.scope_stack.cur_scope_mut();
+        let scope_id = scope.scope_id;
+        scope.cur_stmt_id += 1;
+        self.add_variable(scope_id, ident, kind, expr_type_info);
+        self.scope_stack.cur_scope_mut().cur_stmt_id -= 1;
         Ok(())
     }
 
@@ -686,24 +1956,65 @@ impl SymbolResolver {
     }
 
     fn visit_path_expr(&mut self, path_expr: &mut PathExpr) -> Result<(), RccError> {
+        if path_expr.segments == ["_"] {
+            return Err("`_` can not be used in expressions".into());
+        }
+        // `Point::new` is called by the symbol of `new` in `impl Point`
+        if let [type_name, fn_name] = path_expr.segments.as_slice() {
+            let self_type = self.scope_stack.find_def_except_fn(type_name);
+            if self_type.is_struct() {
+                match self.assoc_items.get(&(self_type, fn_name.clone())) {
+                    Some(symbol) => path_expr.segments = vec![symbol.clone()],
+                    None => {
+                        let msg = format!(
+                            "no function or associated item named `{}` found for struct `{}`",
+                            fn_name, type_name
+                        );
+                        return Err(CompileError::new(ErrorKind::Resolve, msg)
+                            .code("E0599")
+                            .into());
+                    }
+                }
+            }
+        }
         if let Some(ident) = path_expr.segments.last() {
-            let cur_scope = self.scope_stack.cur_scope_mut();
-            if let Some((var_info, _scope_id)) = cur_scope.find_variable(ident) {
-                path_expr.set_type_info_ref(var_info.type_info.clone());
+            if let Some((var_info, scope_id)) = self.scope_stack.find_variable(ident) {
+                if let (VarKind::Local | VarKind::LocalMut, Some(fn_scope)) =
+                    (var_info.kind, self.fn_scope)
+                {
+                    if !self.scope_stack.entered_since(scope_id, fn_scope) {
+                        let msg = "can't capture dynamic environment in a fn item";
+                        return Err(CompileError::new(ErrorKind::Resolve, msg)
+                            .code("E0434")
+                            .note(format!("`{}` is a local variable of the outer function", ident))
+                            .into());
+                    }
+                }
+                path_expr.set_type_info_ref(&mut self.types, var_info.type_info);
                 path_expr.expr_kind = match var_info.kind {
                     VarKind::Static | VarKind::LocalMut => ExprKind::MutablePlace,
                     VarKind::Const | VarKind::Local => ExprKind::Place,
                     VarKind::LitConst => unreachable!(),
                 };
+                self.add_reference(path_expr.node_id, scope_id, ident);
                 Ok(())
             } else {
-                let type_info = cur_scope.find_fn(ident);
+                let type_info = self.scope_stack.find_fn(ident);
                 if !type_info.is_unknown() {
-                    path_expr.set_type_info(type_info);
+                    path_expr.set_type_info(&mut self.types, type_info);
                     path_expr.expr_kind = ExprKind::Value;
+                    if let Some(scope_id) = self.scope_stack.find_fn_scope(ident) {
+                        self.add_reference(path_expr.node_id, scope_id, ident);
+                    }
                     Ok(())
                 } else {
-                    Err(format!("identifier `{}` not found", ident).into())
+                    let msg = format!("identifier `{}` not found", ident);
+                    let mut error = CompileError::new(ErrorKind::Resolve, msg).code("E0425");
+                    let names = self.scope_stack.value_names();
+                    if let Some(name) = similar_name(ident, &names) {
+                        error = error.help(format!("did you mean `{}`?", name));
+                    }
+                    Err(self.error_at(error, path_expr.node_id))
                 }
             }
         } else {
@@ -711,6 +2022,14 @@ impl SymbolResolver {
         }
     }
 
+    /// The path `node_id` refers to the item `name` of `scope_id`, which is a local variable
+    /// if the item is not found.
+    fn add_reference(&mut self, node_id: NodeId, scope_id: ScopeID, name: &str) {
+        if let Some(span) = self.def_spans.get(&(scope_id, name.to_string())) {
+            self.references.insert(node_id, *span);
+        }
+    }
+
     fn visit_lit_str(&mut self, _: &str) -> Result<(), RccError> {
         // do nothing
         Ok(())
@@ -718,28 +2037,46 @@ impl SymbolResolver {
 
     fn visit_unary_expr(&mut self, unary_expr: &mut UnAryExpr) -> Result<(), RccError> {
         self.visit_expr(&mut unary_expr.expr)?;
-        let type_info = unary_expr.expr.type_info();
+        let type_slot = unary_expr.expr.type_slot(&mut self.types);
+        let type_info = self.types.get(type_slot).clone();
         match unary_expr.op {
             UnOp::Deref => {
-                if let TypeInfo::Ptr { kind: _, type_info } = type_info.borrow().deref() {
-                    unary_expr.set_type_info(*type_info.clone());
+                if type_info.is_raw_ptr() {
+                    self.check_unsafe("dereference of raw pointer", RAW_PTR_DEREF)?;
+                }
+                if let TypeInfo::Ptr { kind: _, type_info } = &type_info {
+                    if let TypeInfo::Slice(_) = type_info.as_ref() {
+                        self.check_unsized(type_info)?;
+                    }
+                    unary_expr.set_type_info(&mut self.types, *type_info.clone());
                     unary_expr.expr_kind = unary_expr.expr.kind();
                 } else {
                     return Err(format!("type `{:?}` can not be dereferenced", type_info).into());
                 }
             }
-            UnOp::Not => match type_info.borrow().deref() {
+            UnOp::Not => match &type_info {
+                TypeInfo::LitNum(ln) if ln.is_float() => {
+                    return Err(cannot_apply_unary_op("!", ln).into());
+                }
                 TypeInfo::Bool | TypeInfo::LitNum(_) => {
-                    unary_expr.set_type_info_ref(type_info.clone());
+                    unary_expr.set_type_info_ref(&mut self.types, type_slot);
                     unary_expr.expr_kind = ExprKind::Value;
                 }
                 t => {
                     return Err(format!("cannot apply unary operator `!` to type `{:?}`", t).into())
                 }
             },
-            UnOp::Neg => match type_info.borrow().deref() {
+            UnOp::Neg => match &type_info {
+                TypeInfo::LitNum(ln) if ln.is_unsigned() => {
+                    return Err(cannot_apply_unary_op("-", ln)
+                        .note("unsigned values cannot be negated")
+                        .into());
+                }
                 TypeInfo::LitNum(_) => {
-                    unary_expr.set_type_info_ref(type_info.clone());
+                    if let Expr::LitNum(lit_num_expr) = unary_expr.expr.as_ref() {
+                        self.negated_lits.insert(lit_num_expr.node_id);
+                    }
+                    unary_expr.set_type_info_ref(&mut self.types, type_slot);
                     unary_expr.expr_kind = ExprKind::Value;
                 }
                 tp => {
@@ -749,21 +2086,54 @@ impl SymbolResolver {
                 }
             },
             UnOp::Borrow => {
-                unary_expr.set_type_info(TypeInfo::Ptr {
-                    kind: PtrKind::Ref,
-                    type_info: Box::new(type_info.borrow().deref().clone()),
-                });
+                unary_expr.set_type_info(
+                    &mut self.types,
+                    TypeInfo::Ptr {
+                        kind: PtrKind::Ref,
+                        type_info: Box::new(type_info),
+                    },
+                );
                 unary_expr.expr_kind = ExprKind::Value;
             }
             UnOp::BorrowMut => {
-                todo!("borrow mut")
+                if unary_expr.expr.kind() != ExprKind::MutablePlace {
+                    return Err(format!(
+                        "cannot borrow `{}` as mutable, as it is not declared as mutable",
+                        expr_str(&unary_expr.expr)
+                    )
+                    .into());
+                }
+                unary_expr.set_type_info(
+                    &mut self.types,
+                    TypeInfo::Ptr {
+                        kind: PtrKind::MutRef,
+                        type_info: Box::new(type_info),
+                    },
+                );
+                unary_expr.expr_kind = ExprKind::Value;
             }
         }
         Ok(())
     }
 
     fn visit_block_expr(&mut self, block_expr: &mut BlockExpr) -> Result<(), RccError> {
-        self.scope_stack.enter_scope(block_expr);
+        let in_unsafe = self.in_unsafe;
+        self.in_unsafe |= block_expr.is_unsafe;
+        self.scope_stack.enter_scope(block_expr.scope_id);
+        self.declare_type_aliases(block_expr.stmts.iter().filter_map(|stmt| match stmt {
+            Stmt::Item(item) => Some(item),
+            _ => None,
+        }))?;
+        for stmt in block_expr.stmts.iter() {
+            if let Stmt::Item(Item::Struct(item_struct)) = stmt {
+                self.declare_struct(item_struct)?;
+            }
+        }
+        for stmt in block_expr.stmts.iter() {
+            if let Stmt::Item(Item::Struct(item_struct)) = stmt {
+                self.check_recursive_struct(item_struct)?;
+            }
+        }
 
         for stmt in block_expr.stmts.iter_mut() {
             self.visit_stmt(stmt)?;
@@ -773,21 +2143,25 @@ impl SymbolResolver {
         if let Some(expr) = block_expr.last_expr.as_mut() {
             self.visit_expr(expr)?;
             self.scope_stack.cur_scope_mut().cur_stmt_id += 1;
-            let type_info = expr.type_info();
-            block_expr.set_type_info_ref(type_info);
+            let type_info = expr.type_slot(&mut self.types);
+            block_expr.set_type_info_ref(&mut self.types, type_info);
         } else if block_expr.stmts.is_empty() {
-            block_expr.set_type_info(TypeInfo::Unit);
+            block_expr.set_type_info(&mut self.types, TypeInfo::Unit);
         } else {
             let last_stmt = block_expr.stmts.last().unwrap();
             match last_stmt {
                 Stmt::Semi | Stmt::Let(_) | Stmt::Item(_) => {
-                    block_expr.set_type_info(TypeInfo::Unit);
+                    block_expr.set_type_info(&mut self.types, TypeInfo::Unit);
+                }
+                Stmt::ExprStmt(e) => {
+                    let type_info = e.type_slot(&mut self.types);
+                    block_expr.set_type_info_ref(&mut self.types, type_info);
                 }
-                Stmt::ExprStmt(e) => block_expr.set_type_info_ref(e.type_info()),
             }
         }
 
         self.scope_stack.exit_scope();
+        self.in_unsafe = in_unsafe;
         Ok(())
     }
 
@@ -807,72 +2181,88 @@ impl SymbolResolver {
 
         // check the mutability of place expr lhs
 
-        match assign_expr.lhs.kind() {
-            ExprKind::Place => return Err("lhs is not mutable".into()),
-            ExprKind::Value => return Err("can not assign to lhs".into()),
+        match assign_expr.lhs.kind(&self.types) {
+            ExprKind::Place => {
+                let error = self.immutable_place_error(&assign_expr.lhs);
+                return Err(self.error_at(error, assign_expr.node_id));
+            }
+            ExprKind::Value => {
+                let error = CompileError::new(ErrorKind::Type, "cannot assign to this expression")
+                    .code("E0070")
+                    .note("only variables, fields, indexing and dereferences can be assigned to");
+                return Err(self.error_at(error, assign_expr.node_id));
+            }
             ExprKind::Unknown => unreachable!("lhs kind should not be unknown"),
             ExprKind::MutablePlace => {
                 self.visit_expr(&mut assign_expr.rhs)?;
-                let l_type = assign_expr.lhs.type_info();
-                let r_type = assign_expr.rhs.type_info();
+                let l_type = assign_expr.lhs.type_slot(&mut self.types);
+                let r_type = assign_expr.rhs.type_slot(&mut self.types);
+                let l_type_info = self.types.get(l_type).clone();
+                let r_type_info = self.types.get(r_type).clone();
 
-                debug_assert!(
-                    !r_type.borrow().deref().is_unknown(),
-                    "{:#?}",
-                    assign_expr.rhs
-                );
+                debug_assert!(!r_type_info.is_unknown(), "{:#?}", assign_expr.rhs);
 
                 if matches!(assign_expr.assign_op, AssignOp::ShlEq | AssignOp::ShrEq) {
-                    return if l_type.borrow().deref().is_integer()
-                        && r_type.borrow().deref().is_integer()
-                    {
+                    return if l_type_info.is_integer() && r_type_info.is_integer() {
                         Ok(())
                     } else {
-                        invalid_type_error(l_type.borrow().deref(), assign_expr)
+                        invalid_type_error(&l_type_info, assign_expr)
                     };
                 }
 
-                // set type_info of lhs or rhs
-                match l_type.partial_cmp(&r_type) {
-                    Some(o) => match o {
-                        Ordering::Equal => {}
-                        // let mut a; a = 3i32;
-                        Ordering::Greater => {
-                            match &assign_expr.lhs {
-                                LhsExpr::Path(path_expr) => {
-                                    let ident = path_expr.segments.last().unwrap();
-                                    self.scope_stack
-                                        .cur_scope()
-                                        .update_variable_type(ident, r_type.clone())?;
-                                }
-                                _ => {
-                                    todo!()
-                                }
-                            }
-                            assign_expr.lhs.set_type_info_ref(r_type);
-                        }
-                        // let mut b: i32; b = 4;
-                        Ordering::Less => {
-                            self.may_update_variable_type(&*assign_expr.rhs, l_type.clone())?;
-                            assign_expr.rhs.set_type_info_ref(l_type);
+                if let Some(trait_name) = l_type_info.dyn_trait() {
+                    return Err(
+                        format!("assigning to `&dyn {}` is not supported", trait_name).into(),
+                    );
+                }
+
+                // `s = &a;` coerces the array `a` into the slice `s`
+                let coerced = assign_expr.assign_op == AssignOp::Eq
+                    && self.try_coerce(&l_type_info, &assign_expr.rhs)?.is_some();
+                // `let mut a; a = 3i32;` or `let mut b: i32; b = 4;`
+                if !coerced && self.types.unify(l_type, r_type).is_err() {
+                    if l_type_info.is_number() && r_type_info.is_number() {
+                        let op = &assign_expr.assign_op;
+                        let error = mismatched_num_types(op, &l_type_info, &r_type_info);
+                        return Err(self.error_at(error, assign_expr.node_id));
+                    }
+                    return invalid_type_error(&l_type_info, assign_expr);
+                }
+                // the length of a slice is assigned to the variable `s$len` of `s`, and the
+                // end of a range to `r$end` of `r`
+                let type_info = assign_expr.lhs.type_info(&self.types);
+                let companion_var = |ident: &str| match &type_info {
+                    TypeInfo::Range { .. } => end_var(ident),
+                    _ => len_var(ident),
+                };
+                if type_info.is_slice_ref() || matches!(type_info, TypeInfo::Range { .. }) {
+                    let has_companion_var = match &assign_expr.lhs {
+                        LhsExpr::Path(path_expr) => {
+                            let ident = path_expr.segments.last().unwrap();
+                            self.scope_stack.find_variable(companion_var(ident).as_str()).is_some()
                         }
-                    },
-                    None => {
-                        return invalid_type_error(l_type.borrow().deref(), assign_expr);
+                        _ => false,
+                    };
+                    if !has_companion_var {
+                        let msg = format!("assigning to `{:?}` is not supported", type_info);
+                        return Err(msg.into());
                     }
                 }
+                if coerced {
+                    return Ok(());
+                }
             }
         }
         debug_assert!(
-            assign_expr.rhs.type_info().borrow().is_never()
-                || assign_expr.lhs.type_info() == assign_expr.rhs.type_info(),
+            assign_expr
+                .lhs
+                .type_info(&self.types)
+                .eq_or_never(&assign_expr.rhs.type_info(&self.types)),
             "{:#?}",
             assign_expr
         );
 
-        let t = assign_expr.lhs.type_info();
-        let tp = t.borrow();
-        let type_info = tp.deref();
+        let type_info = &assign_expr.lhs.type_info(&self.types);
         // check compound assignment operators
         // TODO: operator override
         match assign_expr.assign_op {
@@ -892,67 +2282,339 @@ impl SymbolResolver {
         Ok(())
     }
 
+    /// A range value of `{integer}` is `i32`, `for i in 0..n` infers the type from the loop
+    /// instead, see `visit_range_bounds`.
     fn visit_range_expr(&mut self, range_expr: &mut RangeExpr) -> Result<(), RccError> {
-        if let Some(expr) = range_expr.lhs.as_mut() {
-            self.visit_expr(expr)?;
+        let slot = self.visit_range_bounds(range_expr)?;
+        if self.types.get(slot).is_i() {
+            self.types.set(slot, TypeInfo::LitNum(TypeLitNum::I32));
         }
-        if let Some(expr) = range_expr.rhs.as_mut() {
-            self.visit_expr(expr)?;
+        let type_info = TypeInfo::Range {
+            type_info: Box::new(self.types.get(slot).clone()),
+            inclusive: range_expr.range_op == RangeOp::DotDotEq,
+        };
+        let range_slot = range_expr.type_slot(&mut self.types);
+        self.types.set(range_slot, type_info);
+        Ok(())
+    }
+
+    /// The slot shared by the start and the end of `a..b`, which must be integers
+    fn visit_range_bounds(&mut self, range_expr: &mut RangeExpr) -> Result<TypeSlot, RccError> {
+        let (start, end) = match (range_expr.lhs.as_mut(), range_expr.rhs.as_mut()) {
+            (Some(start), Some(end)) => (start, end),
+            _ => return Err("ranges without a start or an end are not supported".into()),
+        };
+        self.visit_expr(start)?;
+        self.visit_expr(end)?;
+        let start_slot = start.type_slot(&mut self.types);
+        let end_slot = end.type_slot(&mut self.types);
+        if self.types.unify(start_slot, end_slot).is_err() {
+            let start_type = self.types.get(start_slot).clone();
+            let end_type = self.types.get(end_slot).clone();
+            if start_type.is_number() && end_type.is_number() {
+                let error = mismatched_num_types(&range_expr.range_op, &start_type, &end_type);
+                return Err(self.error_at(error, range_expr.node_id));
+            }
+            let msg = format!(
+                "mismatched types: expected `{:?}`, found `{:?}`",
+                start_type, end_type
+            );
+            return Err(CompileError::new(ErrorKind::Type, msg).code("E0308").into());
+        }
+        let type_info = self.types.get(start_slot);
+        if !type_info.is_integer() {
+            return Err(format!("ranges of `{:?}` are not supported", type_info).into());
+        }
+        Ok(start_slot)
+    }
+
+    /// `e as T` is supported between the same types, between integer types, from `&T` or
+    /// `&mut T` to a raw pointer of `T`, between raw pointers of any types, and between raw
+    /// pointers and `usize`, e.g. `0x1000_0000 as *mut u32`. An integer literal takes the type it is cast to, or `usize`
+    /// if it is cast to a pointer.
+    fn visit_cast_expr(&mut self, cast_expr: &mut CastExpr) -> Result<(), RccError> {
+        self.visit_expr(&mut cast_expr.expr)?;
+        let to = TypeInfo::from_type_anno(&cast_expr.type_anno, &self.scope_stack);
+        let usize = TypeInfo::LitNum(TypeLitNum::Usize);
+        let lit_type = if to.is_raw_ptr() { &usize } else { &to };
+        Self::try_determine_number_type(&mut self.types, lit_type, cast_expr.expr.as_ref());
+        // `&mut x as *mut u8` of an integer `x` whose type is not known yet
+        if let (Expr::Unary(unary_expr), TypeInfo::Ptr { type_info: to_elem, .. }) =
+            (cast_expr.expr.as_ref(), &to)
+        {
+            if let TypeInfo::Ptr { kind, type_info } = unary_expr.type_info(&self.types) {
+                if type_info.is_i() && matches!(unary_expr.op, UnOp::Borrow | UnOp::BorrowMut) {
+                    Self::try_determine_number_type(&mut self.types, to_elem, &*unary_expr.expr);
+                    let type_info = Box::new(unary_expr.expr.type_info(&self.types));
+                    unary_expr.set_type_info(&mut self.types, TypeInfo::Ptr { kind, type_info });
+                }
+            }
+        }
+        // `&s as &dyn Trait` is the coercion, with the vtable of `impl Trait for S`
+        if to.dyn_trait().is_some()
+            && self.try_coerce_dyn(&to, cast_expr.expr.as_ref())?.is_some()
+        {
+            let slot = cast_expr.type_slot(&mut self.types);
+            self.types.set(slot, to);
+            return Ok(());
+        }
+        let from = cast_expr.expr.type_info(&self.types);
+
+        // the length or the vtable of a fat pointer would be lost
+        let is_fat = |t: &TypeInfo| match t {
+            TypeInfo::Ptr { type_info, .. } => {
+                matches!(type_info.as_ref(), TypeInfo::Slice(_) | TypeInfo::Str | TypeInfo::Dyn(_))
+            }
+            _ => false,
+        };
+        let is_fat_cast = is_fat(&from) || is_fat(&to);
+        let supported = from == to
+            || !is_fat_cast && match (&from, &to) {
+                (
+                    TypeInfo::Ptr {
+                        kind: from_kind @ (PtrKind::Ref | PtrKind::MutRef),
+                        type_info: from_elem,
+                    },
+                    TypeInfo::Ptr {
+                        kind: to_kind @ (PtrKind::ConstRawPtr | PtrKind::MutRawPtr),
+                        type_info: to_elem,
+                    },
+                ) => {
+                    from_elem == to_elem
+                        && (*from_kind == PtrKind::MutRef || *to_kind == PtrKind::ConstRawPtr)
+                }
+                (f, t) if f.is_integer() && t.is_integer() => true,
+                (f, t) if f.is_raw_ptr() => t.is_raw_ptr() || *t == usize,
+                (f, t) if *f == usize => t.is_raw_ptr(),
+                _ => false,
+            };
+        if !supported {
+            let msg = format!("casting `{}` as `{}`", num_type(&from), num_type(&to));
+            let is_int_or_ptr = |t: &TypeInfo| t.is_integer() || t.is_raw_ptr();
+            if is_fat_cast || is_int_or_ptr(&from) && is_int_or_ptr(&to) {
+                return Err(format!("{} is not supported yet", msg).into());
+            }
+            let msg = format!("{} is invalid", msg);
+            return Err(CompileError::new(ErrorKind::Type, msg).code("E0606").into());
         }
+        let slot = cast_expr.type_slot(&mut self.types);
+        self.types.set(slot, to);
         Ok(())
     }
 
     fn visit_bin_op_expr(&mut self, bin_op_expr: &mut BinOpExpr) -> Result<(), RccError> {
         self.visit_expr(&mut bin_op_expr.lhs)?;
         self.visit_expr(&mut bin_op_expr.rhs)?;
+        if let Some(symbol) = self.overloaded_op(bin_op_expr) {
+            return self.visit_overloaded_op(bin_op_expr, symbol);
+        }
 
         let t = self.primitive_bin_ops(
+            bin_op_expr.node_id,
             &mut bin_op_expr.lhs,
             bin_op_expr.bin_op,
             &mut bin_op_expr.rhs,
         )?;
-        bin_op_expr.set_type_info_ref(t.clone());
-        // primitive bin_op || override bin_op
-        let tp = t.borrow();
-        let bin_type = tp.deref();
-        if !bin_type.is_unknown()
-            || self.override_bin_ops.contains(&(
-                bin_op_expr.bin_op,
-                bin_op_expr.lhs.type_info().borrow().deref().clone(),
-                bin_op_expr.rhs.type_info().borrow().deref().clone(),
-            ))
-        {
+        bin_op_expr.set_type_info_ref(&mut self.types, t);
+        let bin_type = self.types.get(t);
+        if !bin_type.is_unknown() {
             Ok(())
         } else {
             Err(format!(
                 "invalid operand type `{:?}` and `{:?}` for `{:?}`",
-                bin_op_expr.lhs.type_info().borrow().deref(),
-                bin_op_expr.rhs.type_info().borrow().deref(),
+                bin_op_expr.lhs.type_info(&self.types),
+                bin_op_expr.rhs.type_info(&self.types),
                 bin_op_expr.bin_op
             )
             .into())
         }
     }
 
+    /// Symbol of the trait method overloading the operator for the type of the lhs, the
+    /// operators of the primitive types are never overloaded.
+    fn overloaded_op(&self, bin_op_expr: &BinOpExpr) -> Option<String> {
+        let lhs_type = bin_op_expr.lhs.type_info(&self.types);
+        if lhs_type.is_number()
+            || matches!(lhs_type, TypeInfo::Bool | TypeInfo::Char | TypeInfo::Unknown)
+        {
+            return None;
+        }
+        let (trait_name, method) = bin_op_expr.bin_op.overloading_trait()?;
+        if !self.impls.contains_key(&(trait_name.to_string(), lhs_type.clone())) {
+            return None;
+        }
+        self.methods.get(&(lhs_type, method.to_string())).cloned()
+    }
+
+    /// `a + b` calls `Add::add(a, b)` of the type of `a`
+    fn visit_overloaded_op(
+        &mut self,
+        bin_op_expr: &mut BinOpExpr,
+        symbol: String,
+    ) -> Result<(), RccError> {
+        let type_fn_ptr = match self.scope_stack.find_fn(&symbol) {
+            TypeInfo::Fn { vis: _, inner } => inner,
+            t => unreachable!("type of method `{}`: {:?}", symbol, t),
+        };
+        if type_fn_ptr.params.len() != 2 {
+            return Err(format!(
+                "method `{}` takes 2 parameters for `{:?}`",
+                symbol, bin_op_expr.bin_op
+            )
+            .into());
+        }
+        // `a == b` is `eq(&a, &b)` with `fn eq(&self, other: &Self)`
+        let self_type = TypeInfo::from_type_anno(&type_fn_ptr.params[0], &self.scope_stack);
+        self.adjust_receiver(&mut bin_op_expr.lhs, &self_type, &symbol)?;
+        let rhs_type = TypeInfo::from_type_anno(&type_fn_ptr.params[1], &self.scope_stack);
+        if let TypeInfo::Ptr { kind: PtrKind::Ref, type_info } = &rhs_type {
+            if bin_op_expr.rhs.type_info(&self.types) == **type_info {
+                self.adjust_receiver(&mut bin_op_expr.rhs, &rhs_type, &symbol)?;
+            }
+        }
+        Self::try_unify(&mut self.types, &rhs_type, bin_op_expr.rhs.as_ref());
+        if !bin_op_expr.rhs.type_info(&self.types).is(&rhs_type) {
+            return Err(format!(
+                "invalid operand type `{:?}` and `{:?}` for `{:?}`",
+                bin_op_expr.lhs.type_info(&self.types),
+                bin_op_expr.rhs.type_info(&self.types),
+                bin_op_expr.bin_op
+            )
+            .into());
+        }
+        let ret_type = TypeInfo::from_type_anno(&type_fn_ptr.ret_type, &self.scope_stack);
+        if matches!(bin_op_expr.bin_op, BinOperator::EqEq | BinOperator::Ne)
+            && ret_type != TypeInfo::Bool
+        {
+            return Err(format!("method `{}` must return `bool`", symbol).into());
+        }
+        let ret_type = self.types.new_slot(ret_type);
+        bin_op_expr.set_type_info_ref(&mut self.types, ret_type);
+        bin_op_expr.op_fn = Some(symbol);
+        Ok(())
+    }
+
+    /// `[a, b, c]` and `[a; N]`, the elements have the same type. Only the elements which
+    /// are copied by their bits are supported.
     fn visit_array_expr(&mut self, array_expr: &mut ArrayExpr) -> Result<(), RccError> {
+        let mut elem_slot: Option<TypeSlot> = None;
         for e in array_expr.elems.iter_mut() {
             self.visit_expr(e)?;
+            let slot = e.type_slot(&mut self.types);
+            match elem_slot {
+                Some(elem_slot) => {
+                    if self.types.unify(elem_slot, slot).is_err() {
+                        let msg = format!(
+                            "mismatched types in array: expected `{:?}`, found `{:?}`",
+                            self.types.get(elem_slot),
+                            self.types.get(slot)
+                        );
+                        return Err(CompileError::new(ErrorKind::Type, msg).code("E0308").into());
+                    }
+                }
+                None => elem_slot = Some(slot),
+            }
         }
-        if let Some(expr) = array_expr.len_expr.expr.as_mut() {
-            self.visit_expr(expr)?;
+        let elem = self.types.get(elem_slot.unwrap()).clone();
+        let copied = match &elem {
+            // fat pointers are not supported
+            TypeInfo::Ptr { kind, .. } => {
+                kind != &PtrKind::Box && !elem.is_slice_ref() && elem.dyn_trait().is_none()
+            }
+            t => t.is_number() || matches!(t, TypeInfo::Bool | TypeInfo::Char),
+        };
+        if !copied {
+            return Err(format!("arrays of `{}` are not supported", elem).into());
         }
+        let len = match &array_expr.len_expr {
+            // evaluated before
+            Some(len_expr) => match array_len(len_expr) {
+                Some(len) => len,
+                None => {
+                    let len = expr_str(len_expr);
+                    let msg = format!("array length `{}` in a constant is not supported", len);
+                    return Err(msg.into());
+                }
+            },
+            None => array_expr.elems.len(),
+        };
+        array_expr.set_type_info(&mut self.types, TypeInfo::Array(Box::new(elem), len));
         Ok(())
     }
 
+    /// `v[i]`: `i` is `usize`, `Vec<T>`, arrays and references to arrays can be indexed.
+    /// A constant index out of the bounds of an array is an error.
     fn visit_array_index_expr(
         &mut self,
         array_index_expr: &mut ArrayIndexExpr,
     ) -> Result<(), RccError> {
-        todo!()
+        self.visit_expr(&mut array_index_expr.expr)?;
+        self.visit_expr(&mut array_index_expr.index_expr)?;
+        let usize = TypeInfo::LitNum(TypeLitNum::Usize);
+        Self::try_unify(&mut self.types, &usize, array_index_expr.index_expr.as_ref());
+        assert_type_is(
+            &self.types,
+            array_index_expr.index_expr.as_ref(),
+            &usize,
+            "invalid type for index",
+        )?;
+        // a reference to an array or a slice is dereferenced
+        let (type_info, expr_kind) = match array_index_expr.expr.type_info(&self.types) {
+            TypeInfo::Ptr {
+                kind: kind @ (PtrKind::Ref | PtrKind::MutRef),
+                type_info,
+            } if matches!(
+                type_info.as_ref(),
+                TypeInfo::Array(..) | TypeInfo::Slice(_) | TypeInfo::Str
+            ) =>
+            {
+                let expr_kind = match kind {
+                    PtrKind::MutRef => ExprKind::MutablePlace,
+                    _ => ExprKind::Place,
+                };
+                (*type_info, expr_kind)
+            }
+            t => (t, array_index_expr.expr.kind()),
+        };
+        let elem = match type_info {
+            TypeInfo::Vec(elem) if elem.is_unknown() => {
+                return Err("type annotations needed for `Vec<_>`".into())
+            }
+            TypeInfo::Vec(elem) => *elem,
+            TypeInfo::Array(elem, len) => {
+                check_array_index(&array_index_expr.index_expr, len)?;
+                *elem
+            }
+            TypeInfo::Slice(elem) => *elem,
+            TypeInfo::Str => {
+                let msg = "the type `str` cannot be indexed by `usize`";
+                return Err(CompileError::new(ErrorKind::Type, msg)
+                    .code("E0277")
+                    .help("the bytes of a string are indexed by `s.as_bytes()[i]`")
+                    .into());
+            }
+            t => return Err(format!("cannot index into a value of type `{:?}`", t).into()),
+        };
+        array_index_expr.set_type_info(&mut self.types, elem);
+        array_index_expr.expr_kind = match expr_kind {
+            ExprKind::MutablePlace => ExprKind::MutablePlace,
+            _ => ExprKind::Place,
+        };
+        Ok(())
     }
 
+    /// `(a, b)`, whose elements are flattened like the fields of a struct
     fn visit_tuple_expr(&mut self, tuple_expr: &mut TupleExpr) -> Result<(), RccError> {
-        todo!()
+        let mut elems = vec![];
+        for e in tuple_expr.elems.iter_mut() {
+            self.visit_expr(e)?;
+            elems.push(e.type_info(&self.types));
+        }
+        let type_info = TypeInfo::Tuple(elems);
+        check_tuple(&type_info)?;
+        let slot = tuple_expr.type_slot(&mut self.types);
+        self.types.set(slot, type_info);
+        Ok(())
     }
 
     fn visit_tuple_index_expr(
@@ -962,54 +2624,756 @@ impl SymbolResolver {
         todo!()
     }
 
+    /// `S { x: 1, ..base }`: each field of `S` is given once, or copied from `base` of type `S`.
     fn visit_struct_expr(&mut self, struct_expr: &mut StructExpr) -> Result<(), RccError> {
-        todo!()
+        let type_info = self.scope_stack.find_def_except_fn(&struct_expr.name);
+        if !type_info.is_struct() {
+            return Err(format!("cannot find struct `{}`", struct_expr.name).into());
+        }
+        // `Pos { x, y }` builds a `Point` if `Pos` is an alias of it
+        if let TypeInfo::Struct { name, .. } = &type_info {
+            struct_expr.name = name.clone();
+        }
+        let fields = self.types.struct_fields(&struct_expr.name).to_vec();
+        let mut given: Vec<&str> = vec![];
+        for (name, expr) in struct_expr.fields.iter_mut() {
+            self.visit_expr(expr)?;
+            let field_type = match fields.iter().find(|(field, _)| field == name) {
+                Some((_, field_type)) => field_type,
+                None => {
+                    return Err(format!(
+                        "struct `{}` has no field named `{}`",
+                        struct_expr.name, name
+                    )
+                    .into())
+                }
+            };
+            if given.contains(&name.as_str()) {
+                return Err(format!("field `{}` specified more than once", name).into());
+            }
+            given.push(name);
+            Self::try_unify(&mut self.types, field_type, expr);
+            assert_type_is(&self.types, expr, field_type, "mismatched types")?;
+        }
+        match struct_expr.base.as_mut() {
+            Some(base) => {
+                self.visit_expr(base)?;
+                assert_type_is(&self.types, base.as_ref(), &type_info, "mismatched types")?;
+            }
+            None => {
+                let missing: Vec<String> = fields
+                    .iter()
+                    .filter(|(field, _)| !given.contains(&field.as_str()))
+                    .map(|(field, _)| format!("`{}`", field))
+                    .collect();
+                if !missing.is_empty() {
+                    return Err(format!(
+                        "missing fields {} in initializer of `{}`",
+                        missing.join(", "),
+                        struct_expr.name
+                    )
+                    .into());
+                }
+            }
+        }
+        let slot = struct_expr.type_slot(&mut self.types);
+        self.types.set(slot, type_info);
+        Ok(())
     }
 
     fn visit_call_expr(&mut self, call_expr: &mut CallExpr) -> Result<(), RccError> {
+        if let Some(context) = self.const_context {
+            self.check_const_call(call_expr, context)?;
+        }
+        if let (None, Expr::Path(path_expr)) = (call_expr.method_name(), call_expr.expr.deref()) {
+            let name = path_expr.segments.join("::");
+            if self.unsafe_fns.contains(&name) && self.scope_stack.find_variable(&name).is_none() {
+                self.check_unsafe(
+                    &format!("call to unsafe function `{}`", name),
+                    "the caller must uphold the requirements of the function",
+                )?;
+            }
+        }
+        if let Some(name) = self.volatile_intrinsic(call_expr) {
+            return self.visit_volatile_call(call_expr, &name);
+        }
+        if call_expr.is_box_new() {
+            return self.visit_box_new(call_expr);
+        }
+        if call_expr.is_vec_new() {
+            return self.visit_vec_new(call_expr);
+        }
+        if call_expr.method_name().is_some() {
+            return self.visit_method_call(call_expr);
+        }
+        if let Some(generic_fn) = self.generic_callee(call_expr) {
+            return self.visit_generic_call(call_expr, &generic_fn);
+        }
+        self.visit_fn_call(call_expr, 0, None)
+    }
+
+    /// `read_volatile` or `write_volatile`, unless a function or a variable of the same name
+    /// is in scope.
+    fn volatile_intrinsic(&self, call_expr: &CallExpr) -> Option<String> {
+        let name = call_expr.volatile_intrinsic()?;
+        let declared = self.scope_stack.find_variable(name).is_some()
+            || self.scope_stack.find_fn_scope(name).is_some();
+        if declared && call_expr.is_path(&[name]) {
+            return None;
+        }
+        Some(name.to_string())
+    }
+
+    /// `read_volatile(p)` of `p: *const T` or `p: *mut T` reads a `T` from `p`, and
+    /// `write_volatile(p, x)` of `p: *mut T` writes `x: T` to `p`. `T` must be a scalar.
+    fn visit_volatile_call(
+        &mut self,
+        call_expr: &mut CallExpr,
+        name: &str,
+    ) -> Result<(), RccError> {
+        self.check_unsafe(
+            &format!("call to unsafe function `{}`", name),
+            "the pointer must be valid and properly aligned",
+        )?;
+        let is_write = name == "write_volatile";
+        let callee = format!("function `{}`", name);
+        check_args_len(&callee, if is_write { 2 } else { 1 }, call_expr.call_params.len())?;
+        let call = call_expr.node_id;
+        let ptr = &mut call_expr.call_params[0];
+        self.visit_expr(ptr)?;
+        let ptr_type = ptr.type_info(&self.types);
+        let pointee = match &ptr_type {
+            TypeInfo::Ptr {
+                kind: PtrKind::MutRawPtr,
+                type_info,
+            } => type_info.as_ref().clone(),
+            TypeInfo::Ptr {
+                kind: PtrKind::ConstRawPtr,
+                type_info,
+            } if !is_write => type_info.as_ref().clone(),
+            t => {
+                let expected = if is_write { "*mut T" } else { "*const T" };
+                let msg = format!(
+                    "mismatched types in argument 1 of {}: expected `{}`, found `{}`",
+                    callee, expected, t
+                );
+                let error = CompileError::new(ErrorKind::Type, msg).code("E0308");
+                return Err(with_span(error, self.arg_span(call, 1)));
+            }
+        };
+        let is_scalar = matches!(pointee, TypeInfo::LitNum(_) | TypeInfo::Bool | TypeInfo::Char);
+        if !is_scalar && !pointee.is_raw_ptr() {
+            return Err(format!("`{}` of `{:?}` is not supported yet", name, pointee).into());
+        }
+        if is_write {
+            let value = &mut call_expr.call_params[1];
+            self.visit_expr(value)?;
+            Self::try_determine_number_type(&mut self.types, &pointee, value);
+            check_arg_type(&self.types, &callee, 2, value, &pointee, self.arg_span(call, 2))?;
+            call_expr.set_type_info(&mut self.types, TypeInfo::Unit);
+        } else {
+            call_expr.set_type_info(&mut self.types, pointee);
+        }
+        Ok(())
+    }
+
+    /// Call a function or a function pointer, the first `visited` params are visited by
+    /// the caller. The first param of a `method` is its receiver.
+    fn visit_fn_call(
+        &mut self,
+        call_expr: &mut CallExpr,
+        visited: usize,
+        method: Option<&str>,
+    ) -> Result<(), RccError> {
         self.visit_expr(&mut call_expr.expr)?;
-        if !call_expr.expr.is_callable() {
+        if !call_expr.expr.is_callable(&self.types) {
             return Err("expr is not callable".into());
         }
-        let t = call_expr.expr.type_info();
-        let tp = t.borrow();
-        let type_info = tp.deref();
-        let type_fn_ptr = match type_info {
-            TypeInfo::FnPtr(fn_ptr) => fn_ptr,
-            TypeInfo::Fn { vis: _, inner } => inner,
-            _ => unreachable!("callable type can only be fn_ptr or fn"),
+        let type_info = call_expr.expr.type_info(&self.types);
+        let type_fn_ptr = match &type_info {
+            TypeInfo::FnPtr(fn_ptr) => fn_ptr,
+            TypeInfo::Fn { vis: _, inner } => inner,
+            _ => unreachable!("callable type can only be fn_ptr or fn"),
+        };
+
+        // arguments are counted from 1, without the receiver
+        let (callee, receivers) = match method {
+            Some(method) => (format!("method `{}`", method), 1),
+            None => (callee_name(call_expr), 0),
+        };
+        check_args_len(
+            &callee,
+            type_fn_ptr.params.len().saturating_sub(receivers),
+            call_expr.call_params.len() - receivers,
+        )?;
+        let call = call_expr.node_id;
+        for (i, (expr, param)) in call_expr
+            .call_params
+            .iter_mut()
+            .zip(type_fn_ptr.params.iter())
+            .enumerate()
+        {
+            if i >= visited {
+                self.visit_expr(expr)?;
+            }
+            let excepted_info = TypeInfo::from_type_anno(param, &self.scope_stack);
+            if self.try_coerce(&excepted_info, expr)?.is_some() {
+                continue;
+            }
+
+            Self::try_unify_elems(&mut self.types, &excepted_info, expr);
+            Self::try_unify(&mut self.types, &excepted_info, expr);
+            let n = i + 1 - receivers;
+            let span = self.arg_span(call, n);
+            check_arg_type(&self.types, &callee, n, expr, &excepted_info, span)?;
+        }
+        call_expr.set_type_info(
+            &mut self.types,
+            TypeInfo::from_type_anno(&type_fn_ptr.ret_type, &self.scope_stack),
+        );
+        Ok(())
+    }
+
+    fn generic_callee(&self, call_expr: &CallExpr) -> Option<ItemFn> {
+        match call_expr.expr.deref() {
+            Expr::Path(path_expr) if path_expr.segments.len() == 1 => {
+                let ident = &path_expr.segments[0];
+                if self.scope_stack.find_variable(ident).is_some() {
+                    return None;
+                }
+                self.generic_fns.get(ident).cloned()
+            }
+            _ => None,
+        }
+    }
+
+    /// `sum(1, 2)` calls the instance `_RCG3sum3i32E` of `fn sum<T: Add>(a: T, b: T) -> T`.
+    /// A generic argument is inferred from the params whose type is `T`, and must be
+    /// a primitive type.
+    fn visit_generic_call(
+        &mut self,
+        call_expr: &mut CallExpr,
+        generic_fn: &ItemFn,
+    ) -> Result<(), RccError> {
+        check_params_len(call_expr, generic_fn.fn_params.params.len())?;
+        let callee = callee_name(call_expr);
+        let mut arg_slots: Vec<Option<TypeSlot>> = vec![None; generic_fn.generics.len()];
+        for (i, (expr, param)) in call_expr
+            .call_params
+            .iter_mut()
+            .zip(generic_fn.fn_params.params.iter())
+            .enumerate()
+        {
+            self.visit_expr(expr)?;
+            let idx = match &param._type {
+                TypeAnnotation::Identifier(ident) => {
+                    generic_fn.generics.iter().position(|g| &g.name == ident)
+                }
+                _ => None,
+            };
+            if let Some(idx) = idx {
+                let slot = expr.type_slot(&mut self.types);
+                match arg_slots[idx] {
+                    Some(arg_slot) => {
+                        if self.types.unify(arg_slot, slot).is_err() {
+                            let expected = self.types.get(arg_slot).clone();
+                            let found = self.types.get(slot).clone();
+                            let span = self.arg_span(call_expr.node_id, i + 1);
+                            return Err(arg_type_error(&callee, i + 1, &expected, &found, span));
+                        }
+                    }
+                    None => arg_slots[idx] = Some(slot),
+                }
+            }
+        }
+
+        let mut args = vec![];
+        for (generic, slot) in generic_fn.generics.iter().zip(arg_slots) {
+            let slot = match slot {
+                Some(slot) => slot,
+                None => {
+                    return Err(format!("type annotations needed for `{}`", generic.name).into())
+                }
+            };
+            let mut type_info = self.types.get(slot).clone();
+            if type_info.is_i() {
+                type_info = TypeInfo::LitNum(TypeLitNum::I32);
+            } else if type_info.is_f() {
+                type_info = TypeInfo::LitNum(TypeLitNum::F64);
+            }
+            self.types.set(slot, type_info.clone());
+            let name = match &type_info {
+                TypeInfo::LitNum(lit) => lit.to_string(),
+                TypeInfo::Bool => "bool".to_string(),
+                TypeInfo::Char => "char".to_string(),
+                t => return Err(format!("generic argument `{:?}` is not supported", t).into()),
+            };
+            for bound in generic.bounds.iter() {
+                if !self.impls.contains_key(&(bound.clone(), type_info.clone())) {
+                    return Err(format!(
+                        "the trait bound `{}: {}` is not satisfied",
+                        name, bound
+                    )
+                    .into());
+                }
+            }
+            args.push(GenericArg {
+                name: generic.name.clone(),
+                type_info,
+                type_anno: TypeAnnotation::Identifier(name),
+            });
+        }
+
+        let symbol = generic_fn_name(&generic_fn.name, &generic_arg_names(&args));
+        let mut params = generic_fn.fn_params.type_annotations();
+        for param in params.iter_mut() {
+            mono::subst_type_anno(param, &args);
+        }
+        let mut ret_type = generic_fn.ret_type.clone();
+        mono::subst_type_anno(&mut ret_type, &args);
+        if self.instance_names.insert(symbol.clone()) {
+            self.scope_stack.add_file_typedef(
+                symbol.clone(),
+                TypeInfo::Fn {
+                    vis: generic_fn.vis(),
+                    inner: TypeFnPtr::new(params, ret_type),
+                },
+            );
+            self.instances.push((generic_fn.name.clone(), args));
+        }
+        if let Expr::Path(path_expr) = call_expr.expr.as_mut() {
+            path_expr.segments = vec![symbol];
+        }
+        self.visit_fn_call(call_expr, call_expr.call_params.len(), None)
+    }
+
+    /// `a.add(b)` calls the method `add` of `impl Add for T` as `<T as Add>::add(a, b)`. The
+    /// receiver is borrowed for a `&self` method, and dereferenced if it is a reference to
+    /// the self type of a `self` method.
+    fn visit_trait_method_call(
+        &mut self,
+        call_expr: &mut CallExpr,
+        symbol: String,
+    ) -> Result<(), RccError> {
+        let method = call_expr.method_name().unwrap().to_string();
+        receiver_as_first_param(call_expr);
+        if let Expr::Path(path_expr) = call_expr.expr.as_mut() {
+            path_expr.segments = vec![symbol.clone()];
+        }
+        let self_param = match self.scope_stack.find_fn(&symbol) {
+            TypeInfo::Fn { vis: _, inner } => inner.params.first().cloned(),
+            t => unreachable!("type of method `{}`: {:?}", symbol, t),
+        };
+        if let Some(self_param) = self_param {
+            let self_type = TypeInfo::from_type_anno(&self_param, &self.scope_stack);
+            self.adjust_receiver(&mut call_expr.call_params[0], &self_type, &method)?;
+        }
+        self.visit_fn_call(call_expr, 1, Some(&method))
+    }
+
+    /// Auto-ref `v` to `&v` or `&mut v`, auto-deref `r` to `*r`, or reborrow `r: &mut T` as
+    /// `&*r`, to match the type of `self` of `method`.
+    fn adjust_receiver(
+        &mut self,
+        receiver: &mut Expr,
+        self_type: &TypeInfo,
+        method: &str,
+    ) -> Result<(), RccError> {
+        let receiver_type = receiver.type_info(&self.types);
+        let ops: &[UnOp] = match (self_type, &receiver_type) {
+            (TypeInfo::Ptr { kind, type_info }, _) if type_info.as_ref() == &receiver_type => {
+                match kind {
+                    PtrKind::Ref => &[UnOp::Borrow],
+                    PtrKind::MutRef if receiver.kind() == ExprKind::MutablePlace => {
+                        &[UnOp::BorrowMut]
+                    }
+                    PtrKind::MutRef => {
+                        let msg = format!(
+                            "cannot borrow `{}` as mutable, as it is not declared as mutable",
+                            expr_str(receiver)
+                        );
+                        return Err(CompileError::new(ErrorKind::Type, msg).code("E0596").into());
+                    }
+                    _ => return Ok(()),
+                }
+            }
+            (
+                TypeInfo::Ptr { kind, type_info },
+                TypeInfo::Ptr {
+                    kind: receiver_kind,
+                    type_info: receiver_info,
+                },
+            ) if type_info == receiver_info => match (kind, receiver_kind) {
+                (PtrKind::Ref, PtrKind::MutRef) => &[UnOp::Deref, UnOp::Borrow],
+                (PtrKind::MutRef, PtrKind::Ref) => {
+                    let msg = format!(
+                        "cannot borrow data in a `&` reference as mutable: `{}` takes `&mut self`",
+                        method
+                    );
+                    return Err(CompileError::new(ErrorKind::Type, msg).code("E0596").into());
+                }
+                _ => return Ok(()),
+            },
+            (
+                _,
+                TypeInfo::Ptr {
+                    kind: PtrKind::Ref | PtrKind::MutRef,
+                    type_info,
+                },
+            ) if type_info.as_ref() == self_type => {
+                if self_type.owns_heap() {
+                    return Err(format!(
+                        "cannot move out of `{:?}` which is behind a reference",
+                        self_type
+                    )
+                    .into());
+                }
+                &[UnOp::Deref]
+            }
+            _ => return Ok(()),
+        };
+        for op in ops {
+            let type_info = match op {
+                UnOp::Deref => match receiver.type_info(&self.types) {
+                    TypeInfo::Ptr { type_info, .. } => *type_info,
+                    t => unreachable!("deref of `{:?}`", t),
+                },
+                _ => self_type.clone(),
+            };
+            let expr = std::mem::replace(receiver, Expr::Path(PathExpr::new()));
+            let mut unary_expr = UnAryExpr::new(op.clone(), expr);
+            unary_expr.node_id = NodeId(self.node_count);
+            self.node_count += 1;
+            unary_expr.expr_kind = match unary_expr.op {
+                UnOp::Deref => unary_expr.expr.kind(),
+                _ => ExprKind::Value,
+            };
+            unary_expr.set_type_info(&mut self.types, type_info);
+            *receiver = Expr::Unary(unary_expr);
+        }
+        Ok(())
+    }
+
+    /// `d.show()` of `d: &dyn Show` calls the method through the vtable of `d`, the call
+    /// becomes `show(d)` with the index of `show` in the vtable.
+    fn visit_dyn_method_call(
+        &mut self,
+        call_expr: &mut CallExpr,
+        trait_name: String,
+    ) -> Result<(), RccError> {
+        let method = call_expr.method_name().unwrap().to_string();
+        let (index, trait_fn) = match self.traits[&trait_name]
+            .fns
+            .iter()
+            .enumerate()
+            .find(|(_, f)| f.name == method)
+        {
+            Some((index, trait_fn)) => (index, trait_fn.clone()),
+            None => {
+                return Err(format!(
+                    "no method named `{}` found for `&dyn {}`",
+                    method, trait_name
+                )
+                .into())
+            }
+        };
+        receiver_as_first_param(call_expr);
+        let receiver_type = call_expr.call_params[0].type_info(&self.types);
+        let self_type = &trait_fn.fn_params.params[0]._type;
+        if matches!(self_type, TypeAnnotation::Ptr(tp) if tp.ptr_kind == PtrKind::MutRef)
+            && !matches!(receiver_type, TypeInfo::Ptr { kind: PtrKind::MutRef, .. })
+        {
+            return Err(format!(
+                "cannot borrow data in a `&` reference as mutable: `{}` takes `&mut self`",
+                method
+            )
+            .into());
+        }
+        // the receiver is not counted
+        let callee = format!("method `{}`", method);
+        let supplied = call_expr.call_params.len() - 1;
+        check_args_len(&callee, trait_fn.fn_params.params.len().saturating_sub(1), supplied)?;
+        let call = call_expr.node_id;
+        for (i, (expr, param)) in call_expr
+            .call_params
+            .iter_mut()
+            .zip(trait_fn.fn_params.params.iter())
+            .enumerate()
+            .skip(1)
+        {
+            self.visit_expr(expr)?;
+            let expected = TypeInfo::from_type_anno(&param._type, &self.scope_stack);
+            if self.try_coerce(&expected, expr)?.is_some() {
+                continue;
+            }
+            Self::try_unify(&mut self.types, &expected, expr);
+            check_arg_type(&self.types, &callee, i, expr, &expected, self.arg_span(call, i))?;
+        }
+        call_expr.set_type_info(
+            &mut self.types,
+            TypeInfo::from_type_anno(&trait_fn.ret_type, &self.scope_stack),
+        );
+        call_expr.vtable_index = Some(index);
+        Ok(())
+    }
+
+    /// `Box::new(expr)`: Box<type of expr>
+    fn visit_box_new(&mut self, call_expr: &mut CallExpr) -> Result<(), RccError> {
+        check_params_len(call_expr, 1)?;
+        let expr = &mut call_expr.call_params[0];
+        self.visit_expr(expr)?;
+        Self::try_determine_number_type(&mut self.types, &TypeInfo::LitNum(TypeLitNum::I32), expr);
+        let type_info = expr.type_info(&self.types);
+        if matches!(type_info, TypeInfo::Unit | TypeInfo::Never | TypeInfo::Struct { .. }) {
+            return Err(format!("can not allocate `{:?}` on the heap", type_info).into());
+        }
+        call_expr.set_type_info(
+            &mut self.types,
+            TypeInfo::Ptr {
+                kind: PtrKind::Box,
+                type_info: Box::new(type_info),
+            },
+        );
+        Ok(())
+    }
+
+    /// `Vec::new()`: `Vec<_>`, `String::new()` and `String::from(s)`: `String`
+    fn visit_vec_new(&mut self, call_expr: &mut CallExpr) -> Result<(), RccError> {
+        if call_expr.is_path(&["Vec", "new"]) {
+            check_params_len(call_expr, 0)?;
+            call_expr.set_type_info(&mut self.types, TypeInfo::Vec(Box::new(TypeInfo::Unknown)));
+            return Ok(());
+        }
+        if call_expr.is_path(&["String", "new"]) {
+            check_params_len(call_expr, 0)?;
+        } else {
+            check_params_len(call_expr, 1)?;
+            let span = self.arg_span(call_expr.node_id, 1);
+            let expr = &mut call_expr.call_params[0];
+            self.visit_expr(expr)?;
+            let callee = "function `String::from`";
+            check_arg_type(&self.types, callee, 1, expr, &TypeInfo::ref_str(), span)?;
+        }
+        call_expr.set_type_info(&mut self.types, TypeInfo::String);
+        Ok(())
+    }
+
+    /// Methods of the traits implemented by the type of the receiver, methods of `Vec<T>`:
+    /// `push(T)`, `len()`; methods of `String`: `push(char)`, `push_str(&str)`, `len()`;
+    /// methods of arrays and slices: `as_ptr()`, `as_mut_ptr()`; methods of raw pointers:
+    /// `add(usize)`, `sub(usize)`, `wrapping_add(usize)`, `wrapping_sub(usize)`.
+    fn visit_method_call(&mut self, call_expr: &mut CallExpr) -> Result<(), RccError> {
+        let method = call_expr.method_name().unwrap().to_string();
+        if let Expr::FieldAccess(field_access_expr) = call_expr.expr.as_mut() {
+            self.visit_expr(&mut field_access_expr.lhs)?;
+            let receiver_type_info = field_access_expr.lhs.type_info(&self.types);
+            if let Some(trait_name) = receiver_type_info.dyn_trait() {
+                return self.visit_dyn_method_call(call_expr, trait_name.to_string());
+            }
+            // `r.show()` of `r: &T` calls the method of `T`
+            let self_type = match &receiver_type_info {
+                TypeInfo::Ptr {
+                    kind: PtrKind::Ref | PtrKind::MutRef,
+                    type_info,
+                } if !self.methods.contains_key(&(receiver_type_info.clone(), method.clone())) => {
+                    type_info.as_ref().clone()
+                }
+                _ => receiver_type_info,
+            };
+            if let Some(symbol) = self.methods.get(&(self_type, method.clone())) {
+                return self.visit_trait_method_call(call_expr, symbol.clone());
+            }
+        }
+        let arg_span = self.arg_span(call_expr.node_id, 1);
+        let CallExpr {
+            expr, call_params, ..
+        } = call_expr;
+        let receiver = match expr.as_mut() {
+            Expr::FieldAccess(field_access_expr) => field_access_expr.lhs.as_mut(),
+            _ => unreachable!(),
         };
+        let receiver_type = receiver.type_slot(&mut self.types);
+        let receiver_type_info = self.types.get(receiver_type).clone();
 
-        if call_expr.call_params.len() != type_fn_ptr.params.len() {
-            return Err(format!(
-                "This function takes {} parameters but {} parameters was supplied",
-                type_fn_ptr.params.len(),
-                call_expr.call_params.len(),
-            )
-            .into());
+        // `s.as_bytes()` is the same pair of (data ptr, len) as `s`
+        if receiver_type_info.is_str_ref() && matches!(method.as_str(), "as_bytes" | "bytes") {
+            check_args_len(&format!("method `{}`", method), 0, call_params.len())?;
+            if method == "bytes" {
+                return Err("`bytes()` is only supported as the iterator of a `for` loop".into());
+            }
+            call_expr.set_type_info(&mut self.types, TypeInfo::ref_bytes());
+            return Ok(());
         }
-        for (expr, param) in call_expr
-            .call_params
-            .iter_mut()
-            .zip(type_fn_ptr.params.iter())
+        if matches!(method.as_str(), "as_ptr" | "as_mut_ptr") {
+            if let Some(ptr) = self.elems_ptr(receiver, &receiver_type_info, &method)? {
+                check_args_len(&format!("method `{}`", method), 0, call_params.len())?;
+                call_expr.set_type_info(&mut self.types, ptr);
+                return Ok(());
+            }
+        }
+        // `p.add(n)` and `p.sub(n)` move the raw pointer `p` by `n` elements
+        if receiver_type_info.is_raw_ptr()
+            && matches!(method.as_str(), "add" | "sub" | "wrapping_add" | "wrapping_sub")
         {
-            self.visit_expr(expr)?;
-            let excepted_info = TypeInfo::from_type_anno(param, self.scope_stack.cur_scope());
+            if !method.starts_with("wrapping_") {
+                self.check_unsafe(
+                    &format!("call to unsafe function `{}`", method),
+                    "the pointer must stay in bounds of the object it points to",
+                )?;
+            }
+            let callee = format!("method `{}`", method);
+            check_args_len(&callee, 1, call_params.len())?;
+            let param = &mut call_params[0];
+            self.visit_expr(param)?;
+            let count = TypeInfo::LitNum(TypeLitNum::Usize);
+            Self::try_determine_number_type(&mut self.types, &count, param);
+            check_arg_type(&self.types, &callee, 1, param, &count, arg_span)?;
+            call_expr.set_type_info(&mut self.types, receiver_type_info);
+            return Ok(());
+        }
+        let expected_params = match method.as_str() {
+            "len" => 0,
+            "push" | "push_str" => 1,
+            _ => usize::MAX,
+        };
+        let param_type = match (&receiver_type_info, method.as_str()) {
+            (TypeInfo::Vec(elem), "push") => Some(elem.deref().clone()),
+            (TypeInfo::String, "push") => Some(TypeInfo::Char),
+            (TypeInfo::String, "push_str") => Some(TypeInfo::ref_str()),
+            (TypeInfo::Vec(_) | TypeInfo::String, "len") => None,
+            (t, "len") if t.is_slice_ref() => None,
+            (t, _) => {
+                return Err(format!("no method named `{}` found for `{:?}`", method, t).into())
+            }
+        };
+        let callee = format!("method `{}`", method);
+        check_args_len(&callee, expected_params, call_params.len())?;
 
-            Self::try_determine_number_type(&excepted_info, expr);
-            assert_type_is(expr, &excepted_info, "invalid type for call expr")?;
+        match param_type {
+            Some(param_type) => {
+                if receiver.kind() != ExprKind::MutablePlace {
+                    return Err(format!(
+                        "cannot borrow `{}` as mutable, as it is not declared as mutable",
+                        expr_str(receiver)
+                    )
+                    .into());
+                }
+                let param = &mut call_params[0];
+                self.visit_expr(param)?;
+                if param_type.is_unknown() {
+                    // `let mut v = Vec::new(); v.push(1);`
+                    Self::try_determine_number_type(
+                        &mut self.types,
+                        &TypeInfo::LitNum(TypeLitNum::I32),
+                        param,
+                    );
+                    let elem = param.type_info(&self.types);
+                    if elem.owns_heap()
+                        || elem.is_slice_ref()
+                        || matches!(
+                            elem,
+                            TypeInfo::Unit
+                                | TypeInfo::Never
+                                | TypeInfo::Struct { .. }
+                                | TypeInfo::Array(..)
+                        )
+                    {
+                        return Err(format!("`Vec<{:?}>` is not supported", elem).into());
+                    }
+                    self.types.set(receiver_type, TypeInfo::Vec(Box::new(elem)));
+                } else {
+                    Self::try_unify(&mut self.types, &param_type, param);
+                    check_arg_type(&self.types, &callee, 1, param, &param_type, arg_span)?;
+                }
+                call_expr.set_type_info(&mut self.types, TypeInfo::Unit);
+            }
+            None => call_expr.set_type_info(&mut self.types, TypeInfo::LitNum(TypeLitNum::Usize)),
         }
-        call_expr.set_type_info(TypeInfo::from_type_anno(
-            &type_fn_ptr.ret_type,
-            self.scope_stack.cur_scope(),
-        ));
         Ok(())
     }
 
+    /// `*const T` of `a.as_ptr()` and `*mut T` of `a.as_mut_ptr()`, where `a` is an array of `T`
+    /// or a reference to an array or a slice of `T`. `None` for the other receivers.
+    fn elems_ptr(
+        &self,
+        receiver: &Expr,
+        receiver_type_info: &TypeInfo,
+        method: &str,
+    ) -> Result<Option<TypeInfo>, RccError> {
+        let (elem, mutable) = match receiver_type_info {
+            TypeInfo::Array(elem, _) => (elem, receiver.kind() == ExprKind::MutablePlace),
+            TypeInfo::Ptr {
+                kind: kind @ (PtrKind::Ref | PtrKind::MutRef),
+                type_info,
+            } => match type_info.as_ref() {
+                TypeInfo::Array(elem, _) | TypeInfo::Slice(elem) => {
+                    (elem, *kind == PtrKind::MutRef)
+                }
+                _ => return Ok(None),
+            },
+            _ => return Ok(None),
+        };
+        let kind = if method == "as_ptr" {
+            PtrKind::ConstRawPtr
+        } else if mutable {
+            PtrKind::MutRawPtr
+        } else {
+            return Err(format!(
+                "cannot borrow `{}` as mutable, as it is not declared as mutable",
+                expr_str(receiver)
+            )
+            .into());
+        };
+        Ok(Some(TypeInfo::Ptr {
+            kind,
+            type_info: elem.clone(),
+        }))
+    }
+
+    /// `a.b.c` is a mutable place if `a` is. `r.x` of a reference `r` to a struct is `(*r).x`,
+    /// which is a mutable place if `r` is `&mut`.
     fn visit_field_access_expr(
         &mut self,
         field_access_expr: &mut FieldAccessExpr,
     ) -> Result<(), RccError> {
+        self.visit_expr(&mut field_access_expr.lhs)?;
+        while let TypeInfo::Ptr {
+            kind: kind @ (PtrKind::Ref | PtrKind::MutRef),
+            type_info,
+        } = field_access_expr.lhs.type_info(&self.types)
+        {
+            let expr = std::mem::replace(&mut *field_access_expr.lhs, Expr::Path(PathExpr::new()));
+            let mut unary_expr = UnAryExpr::new(UnOp::Deref, expr);
+            unary_expr.node_id = NodeId(self.node_count);
+            self.node_count += 1;
+            unary_expr.expr_kind = match kind {
+                PtrKind::MutRef => ExprKind::MutablePlace,
+                _ => ExprKind::Place,
+            };
+            unary_expr.set_type_info(&mut self.types, *type_info);
+            *field_access_expr.lhs = Expr::Unary(unary_expr);
+        }
+        let field = match field_access_expr.field_name() {
+            Some(field) => field.to_string(),
+            None => return Err("expected identifier after `.`".into()),
+        };
+        let field_type = match field_access_expr.lhs.type_info(&self.types) {
+            TypeInfo::Struct { name, .. } => {
+                match self.types.struct_fields(&name).iter().find(|(f, _)| f == &field) {
+                    Some((_, field_type)) => field_type.clone(),
+                    None => {
+                        return Err(format!("no field `{}` on type `{}`", field, name).into())
+                    }
+                }
+            }
+            t => return Err(format!("no field `{}` on type `{:?}`", field, t).into()),
+        };
+        field_access_expr.set_type_info(&mut self.types, field_type);
+        field_access_expr.expr_kind = match field_access_expr.lhs.kind() {
+            ExprKind::MutablePlace => ExprKind::MutablePlace,
+            ExprKind::Place => ExprKind::Place,
+            _ => ExprKind::Value,
+        };
         Ok(())
     }
 
@@ -1019,6 +3383,7 @@ impl SymbolResolver {
         self.loop_kind_stack.push(self.loop_kind);
         self.loop_kind = LoopKind::While;
         assert_type_is(
+            &self.types,
             &*while_expr.0,
             &TypeInfo::Bool,
             "invalid type in while condition",
@@ -1026,6 +3391,7 @@ impl SymbolResolver {
 
         self.visit_block_expr(&mut while_expr.1)?;
         assert_type_is(
+            &self.types,
             &*while_expr.1,
             &TypeInfo::Unit,
             "invalid type in while block",
@@ -1036,16 +3402,75 @@ impl SymbolResolver {
         Ok(())
     }
 
+    /// `for b in s.bytes() { .. }` of `s: &str` binds each byte of `s` to `b: u8`
+    /// `for i in 0..n` gives `i` the type of `0` and `n`, so that e.g. indexing by `i` makes
+    /// them `usize`.
+    fn visit_for_expr(&mut self, for_expr: &mut ForExpr) -> Result<(), RccError> {
+        let slot = match for_expr.expr.as_mut() {
+            Expr::Call(call_expr) if call_expr.method_name() == Some("bytes") => {
+                self.visit_bytes_iter(call_expr)?
+            }
+            Expr::Range(range_expr) => self.visit_range_bounds(range_expr)?,
+            expr => {
+                self.visit_expr(expr)?;
+                match expr.type_info(&self.types) {
+                    TypeInfo::Range { type_info, .. } => self.types.new_slot(*type_info),
+                    type_info => {
+                        let msg = format!("`{:?}` is not an iterator", type_info);
+                        return Err(CompileError::new(ErrorKind::Type, msg)
+                            .code("E0277")
+                            .note("`for` loops only support ranges and `s.bytes()` of `s: &str`")
+                            .into());
+                    }
+                }
+            }
+        };
+
+        let ident = pattern_var(&for_expr.pattern)?;
+        let kind = if for_expr.pattern.is_mut() {
+            VarKind::LocalMut
+        } else {
+            VarKind::Local
+        };
+        self.add_variable(for_expr.block.scope_id, ident, kind, slot);
+
+        self.loop_kind_stack.push(self.loop_kind);
+        self.loop_kind = LoopKind::For;
+        self.visit_block_expr(&mut for_expr.block)?;
+        assert_type_is(
+            &self.types,
+            for_expr.block.as_ref(),
+            &TypeInfo::Unit,
+            "invalid type in for block",
+        )?;
+        self.exit_loop();
+        Ok(())
+    }
+
+    /// The slot of the bytes of `s.bytes()`
+    fn visit_bytes_iter(&mut self, call_expr: &mut CallExpr) -> Result<TypeSlot, RccError> {
+        check_args_len("method `bytes`", 0, call_expr.call_params.len())?;
+        let receiver = match call_expr.expr.as_mut() {
+            Expr::FieldAccess(field_access_expr) => field_access_expr.lhs.as_mut(),
+            _ => unreachable!(),
+        };
+        self.visit_expr(receiver)?;
+        let receiver_type_info = receiver.type_info(&self.types);
+        if !receiver_type_info.is_str_ref() {
+            let msg = format!("no method named `bytes` found for `{:?}`", receiver_type_info);
+            return Err(msg.into());
+        }
+        call_expr.set_type_info(&mut self.types, TypeInfo::ref_bytes());
+        Ok(self.types.new_slot(TypeInfo::LitNum(TypeLitNum::U8)))
+    }
+
     fn visit_loop_expr(&mut self, loop_expr: &mut LoopExpr) -> Result<(), RccError> {
         self.loop_kind_stack.push(self.loop_kind);
-        self.loop_kind = LoopKind::Loop(loop_expr);
+        self.loop_kind = LoopKind::Loop(loop_expr.node_id);
         self.visit_block_expr(&mut loop_expr.expr)?;
         // never return, example: `let a = loop {};`
-        let t = loop_expr.type_info();
-        let tp = t.borrow();
-        let type_info = tp.deref();
-        if type_info.is_unknown() {
-            loop_expr.set_type_info(TypeInfo::Never);
+        if loop_expr.type_info(&self.types).is_unknown() {
+            loop_expr.set_type_info(&mut self.types, TypeInfo::Never);
         }
         self.exit_loop();
         Ok(())
@@ -1064,9 +3489,7 @@ impl SymbolResolver {
 
         for cond in if_expr.conditions.iter_mut() {
             self.visit_expr(cond)?;
-            let t = cond.type_info();
-            let tp = t.borrow();
-            let cond_type_info = tp.deref();
+            let cond_type_info = cond.type_info(&self.types);
             if !cond_type_info.is(&TypeInfo::Bool) {
                 return Err(format!(
                     "invalid type of condition expr: expected `bool`, found: {:?}",
@@ -1076,32 +3499,54 @@ impl SymbolResolver {
             }
         }
 
-        let mut block_type = TypeInfo::Unknown;
+        // the blocks which are not `!` are unified
+        let mut block_type: Option<TypeSlot> = None;
         for block in if_expr.blocks.iter_mut() {
             self.visit_block_expr(block)?;
-            let type_info = block.type_info();
-            let t = type_info.borrow();
-            let tp = t.deref();
-            debug_assert_ne!(&TypeInfo::Unknown, type_info.borrow().deref());
+            let type_slot = block.type_slot(&mut self.types);
+            let type_info = self.types.get(type_slot).clone();
+            debug_assert_ne!(TypeInfo::Unknown, type_info);
 
-            if block_type != TypeInfo::Unknown && !block_type.eq_or_never(tp) {
-                return Err(format!(
-                    "different type of if block: `{:?}`, `{:?}`",
-                    block_type, type_info
-                )
-                .into());
+            if type_info.is_never() {
+                continue;
             }
-
-            if tp != &TypeInfo::Never {
-                block_type = tp.clone();
+            match block_type {
+                Some(block_type) => {
+                    if self.types.unify(block_type, type_slot).is_err() {
+                        let msg = format!(
+                            "`if` and `else` have incompatible types: expected `{}`, \
+                             found `{}`",
+                            self.types.get(block_type),
+                            type_info
+                        );
+                        let error = CompileError::new(ErrorKind::Type, msg).code("E0308");
+                        return Err(self.error_at(error, block.node_id));
+                    }
+                }
+                None => block_type = Some(type_slot),
+            }
+        }
+        // without `else`, the value is `()` if no condition holds
+        if let (true, Some(slot)) = (if_expr.conditions.len() == if_expr.blocks.len(), block_type) {
+            let unit = self.types.new_slot(TypeInfo::Unit);
+            if self.types.unify(slot, unit).is_err() {
+                let msg = format!(
+                    "`if` may be missing an `else` clause: expected `()`, found `{}`",
+                    self.types.get(slot)
+                );
+                let error = CompileError::new(ErrorKind::Type, msg).code("E0317");
+                return Err(self.error_at(error, if_expr.blocks.last().unwrap().node_id));
             }
         }
 
-        if_expr.set_type_info(if block_type == TypeInfo::Unknown {
-            TypeInfo::Never
-        } else {
-            block_type
-        });
+        match block_type {
+            Some(block_type) => if_expr.set_type_info_ref(&mut self.types, block_type),
+            // `if a { return 1; }` goes on if `a` is false
+            None if if_expr.conditions.len() == if_expr.blocks.len() => {
+                if_expr.set_type_info(&mut self.types, TypeInfo::Unit)
+            }
+            None => if_expr.set_type_info(&mut self.types, TypeInfo::Never),
+        }
         Ok(())
     }
 
@@ -1109,11 +3554,9 @@ impl SymbolResolver {
         match return_expr.0.as_mut() {
             Some(expr) => {
                 self.visit_expr(expr)?;
-                Self::try_determine_number_type(&self.cur_fn_ret_type, expr.as_mut());
-                let type_info = expr.type_info();
-                let t = type_info.borrow();
-                let tp = t.deref();
-                self.validate_ret_type(tp)
+                Self::try_unify(&mut self.types, &self.cur_fn_ret_type, expr.as_ref());
+                let type_info = expr.type_info(&self.types);
+                self.validate_ret_type(&type_info)
             }
             None => self.validate_ret_type(&TypeInfo::Unit),
         }
@@ -1121,60 +3564,618 @@ impl SymbolResolver {
 
     fn visit_break_expr(&mut self, break_expr: &mut BreakExpr) -> Result<(), RccError> {
         fn try_set_type_info(
-            loop_expr: *mut LoopExpr,
-            type_info: Rc<RefCell<TypeInfo>>,
+            types: &mut TypeTable,
+            loop_id: NodeId,
+            type_info: TypeSlot,
         ) -> Result<(), RccError> {
-            let tp = type_info.borrow();
-            let t = tp.deref();
-            let loop_expr = unsafe { &mut *loop_expr };
-            let l = loop_expr.type_info();
-            let lt = l.borrow();
-            let loop_type_info = lt.deref();
-            if loop_type_info.is_unknown() {
-                loop_expr.set_type_info_ref(type_info.clone());
+            let loop_type = types.node_slot(loop_id, TypeInfo::Unknown);
+            if types.get(loop_type).is_unknown() {
+                types.bind(loop_id, type_info);
                 Ok(())
-            } else if !t.is(loop_type_info) {
-                Err(format!(
-                    "invalid type for break expr: expected `{:?}`, found {:?}",
-                    loop_type_info, t
-                )
-                .into())
+            } else if types.unify(loop_type, type_info).is_err() {
+                let msg = format!(
+                    "invalid type for break expr: expected `{:?}`, found `{:?}`",
+                    types.get(loop_type),
+                    types.get(type_info)
+                );
+                Err(CompileError::new(ErrorKind::Type, msg).code("E0308").into())
             } else {
                 Ok(())
             }
         }
 
         if !self.loop_kind.is_in_loop() {
-            return Err("break expr can not be out of loop block".into());
+            let msg = "`break` outside of a loop";
+            return Err(CompileError::new(ErrorKind::Type, msg).code("E0268").into());
         }
 
         if let Some(expr) = break_expr.0.as_mut() {
             return match self.loop_kind {
-                LoopKind::Loop(loop_expr) => {
+                LoopKind::Loop(loop_id) => {
                     self.visit_expr(expr)?;
-                    Self::try_determine_number_type(
-                        unsafe { (*loop_expr).type_info().borrow().deref() },
-                        expr.as_mut(),
-                    );
-                    try_set_type_info(loop_expr, expr.type_info())
+                    let type_info = expr.type_slot(&mut self.types);
+                    try_set_type_info(&mut self.types, loop_id, type_info)
+                }
+                kind => {
+                    let keyword = if kind == LoopKind::For { "for" } else { "while" };
+                    let msg = format!("`break` with value from a `{}` loop", keyword);
+                    let help = "only `loop` can return values, use `break` on its own here";
+                    Err(CompileError::new(ErrorKind::Type, msg).code("E0571").help(help).into())
+                }
+            };
+        } else if let LoopKind::Loop(loop_id) = self.loop_kind {
+            let type_info = self.types.new_slot(TypeInfo::Unit);
+            return try_set_type_info(&mut self.types, loop_id, type_info);
+        }
+        Ok(())
+    }
+
+    /// Integers (at most 64 bits), `char`, `&str` and `String` can be printed.
+    fn visit_print_expr(&mut self, print_expr: &mut PrintExpr) -> Result<(), RccError> {
+        if let Some(context) = self.const_context {
+            return Err(format!("cannot call non-const formatting macro in {}", context).into());
+        }
+        for arg in print_expr.args.iter_mut() {
+            self.visit_expr(arg)?;
+            Self::try_determine_number_type(
+                &mut self.types,
+                &TypeInfo::LitNum(TypeLitNum::I32),
+                arg,
+            );
+            let t = arg.type_info(&self.types);
+            let printable = match &t {
+                TypeInfo::LitNum(ln) => {
+                    ln.is_integer() && !matches!(ln, TypeLitNum::I128 | TypeLitNum::U128)
+                }
+                TypeInfo::Char | TypeInfo::String => true,
+                t => t == &TypeInfo::ref_str(),
+            };
+            if !printable {
+                return Err(format!("`{:?}` cannot be formatted with `{{}}`", t).into());
+            }
+        }
+        Ok(())
+    }
+
+    /// The operands of `assert_eq!` and `assert_ne!` are checked by their comparison.
+    fn visit_assert_expr(&mut self, assert_expr: &mut AssertExpr) -> Result<(), RccError> {
+        self.visit_expr(&mut assert_expr.cond)?;
+        let cond_type_info = assert_expr.cond.type_info(&self.types);
+        if !cond_type_info.is(&TypeInfo::Bool) {
+            return Err(format!(
+                "invalid type of condition expr: expected `bool`, found: {:?}",
+                cond_type_info
+            )
+            .into());
+        }
+        Ok(())
+    }
+
+    /// An operand of `asm!` is an integer (at most 64 bits), `bool`, `char` or a thin
+    /// pointer, so that it fits in a register. An output must be a mutable place.
+    fn visit_asm_expr(&mut self, asm_expr: &mut AsmExpr) -> Result<(), RccError> {
+        fn check_type(t: &TypeInfo) -> Result<(), RccError> {
+            let in_reg = match t {
+                TypeInfo::LitNum(ln) => {
+                    ln.is_integer() && !matches!(ln, TypeLitNum::I128 | TypeLitNum::U128)
+                }
+                TypeInfo::Bool | TypeInfo::Char => true,
+                TypeInfo::Ptr { type_info, .. } => {
+                    !matches!(type_info.as_ref(), TypeInfo::Str | TypeInfo::Dyn(_))
+                }
+                _ => false,
+            };
+            if in_reg {
+                Ok(())
+            } else {
+                Err(format!("`{:?}` cannot be passed to `asm!` in a register", t).into())
+            }
+        }
+
+        if let Some(context) = self.const_context {
+            return Err(format!("inline assembly is not allowed in {}", context).into());
+        }
+        self.check_unsafe(
+            "use of inline assembly",
+            "inline assembly is entirely unchecked and can cause undefined behavior",
+        )?;
+        for input in asm_expr.inputs.iter_mut() {
+            self.visit_expr(input)?;
+            Self::try_determine_number_type(
+                &mut self.types,
+                &TypeInfo::LitNum(TypeLitNum::I32),
+                input,
+            );
+            check_type(&input.type_info(&self.types))?;
+        }
+        for output in asm_expr.outputs.iter_mut() {
+            if !matches!(output, LhsExpr::Path(_) | LhsExpr::FieldAccess(_)) {
+                return Err("output operand of `asm!` must be a variable or a field".into());
+            }
+            self.visit_lhs_expr(output)?;
+            if output.kind(&self.types) != ExprKind::MutablePlace {
+                return Err("output operand of `asm!` is not a mutable place".into());
+            }
+            check_type(&output.type_info(&self.types))?;
+        }
+        Ok(())
+    }
+}
+
+fn generic_arg_names(args: &[GenericArg]) -> Vec<String> {
+    args.iter()
+        .map(|arg| match &arg.type_anno {
+            TypeAnnotation::Identifier(name) => name.clone(),
+            t => unreachable!("generic argument `{:?}`", t),
+        })
+        .collect()
+}
+
+/// `a.f(b)` -> `f(a, b)`
+fn receiver_as_first_param(call_expr: &mut CallExpr) {
+    let field_access_expr =
+        match std::mem::replace(call_expr.expr.as_mut(), Expr::Path(PathExpr::new())) {
+            Expr::FieldAccess(field_access_expr) => field_access_expr,
+            _ => unreachable!(),
+        };
+    let FieldAccessExpr { lhs, rhs, .. } = field_access_expr;
+    call_expr.call_params.insert(0, *lhs);
+    *call_expr.expr = *rhs;
+}
+
+/// Whether `type_anno` is or contains the type `name`
+fn mentions_type(type_anno: &TypeAnnotation, name: &str) -> bool {
+    match type_anno {
+        TypeAnnotation::Identifier(ident) => ident == name,
+        TypeAnnotation::Ptr(tp) => mentions_type(&tp.type_anno, name),
+        TypeAnnotation::Vec(elem) => mentions_type(elem, name),
+        _ => false,
+    }
+}
+
+/// Levenshtein distance between `a` and `b`
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut diag = row[0];
+        row[0] = i + 1;
+        for (j, &cb) in b.iter().enumerate() {
+            let substitute = diag + (ca != cb) as usize;
+            diag = row[j + 1];
+            row[j + 1] = substitute.min(row[j] + 1).min(diag + 1);
+        }
+    }
+    row[b.len()]
+}
+
+/// The closest name to the misspelled `ident`, at most a third of its length away like rustc
+fn similar_name<'a>(ident: &str, names: &[&'a str]) -> Option<&'a str> {
+    let max_distance = (ident.chars().count() / 3).max(1);
+    names
+        .iter()
+        .filter(|name| name.chars().all(|c| c.is_alphanumeric() || c == '_'))
+        .map(|&name| (edit_distance(ident, name), name))
+        .filter(|&(distance, _)| distance <= max_distance)
+        .min()
+        .map(|(_, name)| name)
+}
+
+/// `op` of a number whose type does not implement it, e.g. `-` of `u32`
+/// `{integer}` and `{float}` for the numbers whose types are not known yet, like rustc
+fn num_type(type_info: &TypeInfo) -> String {
+    match type_info {
+        TypeInfo::LitNum(TypeLitNum::I) => "{integer}".to_string(),
+        TypeInfo::LitNum(TypeLitNum::F) => "{float}".to_string(),
+        TypeInfo::LitNum(ln) => ln.to_string(),
+        t => t.to_string(),
+    }
+}
+
+/// The float type, or the integer type wider than `reg_width` bits, in `type_info`, if any
+fn backend_num(type_info: &TypeInfo, reg_width: u32) -> Option<TypeLitNum> {
+    match type_info {
+        TypeInfo::LitNum(TypeLitNum::F) => Some(TypeLitNum::F64),
+        TypeInfo::LitNum(lit_type) if lit_type.is_float() => Some(*lit_type),
+        TypeInfo::LitNum(lit_type) => {
+            let bits = match lit_type {
+                TypeLitNum::I64 | TypeLitNum::U64 => 64,
+                TypeLitNum::I128 | TypeLitNum::U128 => 128,
+                _ => 0,
+            };
+            (bits > reg_width).then_some(*lit_type)
+        }
+        TypeInfo::Ptr { type_info, .. }
+        | TypeInfo::Vec(type_info)
+        | TypeInfo::Array(type_info, _)
+        | TypeInfo::Slice(type_info)
+        | TypeInfo::Range { type_info, .. } => backend_num(type_info, reg_width),
+        TypeInfo::Tuple(types) => types.iter().find_map(|t| backend_num(t, reg_width)),
+        _ => None,
+    }
+}
+
+/// The smallest and the largest values of the integer type `lit_type`
+fn int_range(lit_type: TypeLitNum, pointer_width: u32) -> (i128, u128) {
+    let bits = match lit_type {
+        TypeLitNum::I8 | TypeLitNum::U8 => 8,
+        TypeLitNum::I16 | TypeLitNum::U16 => 16,
+        TypeLitNum::I32 | TypeLitNum::U32 => 32,
+        TypeLitNum::I64 | TypeLitNum::U64 => 64,
+        TypeLitNum::I128 | TypeLitNum::U128 => 128,
+        _ => pointer_width,
+    };
+    if lit_type.is_unsigned() {
+        (0, u128::MAX >> (128 - bits))
+    } else {
+        (-1 << (bits - 1), (1 << (bits - 1)) - 1)
+    }
+}
+
+/// Numbers of different types are never converted implicitly, e.g. `1u8 + 2i32`
+fn mismatched_num_types(op: impl Display, lhs: &TypeInfo, rhs: &TypeInfo) -> CompileError {
+    let msg = format!(
+        "mismatched types: expected `{}`, found `{}`",
+        num_type(lhs),
+        num_type(rhs)
+    );
+    CompileError::new(ErrorKind::Type, msg)
+        .code("E0308")
+        .note(format!("both operands of `{}` must have the same type", op))
+        .help("numbers are not converted implicitly, consider casting with `as`")
+}
+
+fn cannot_apply_unary_op(op: &str, ln: &TypeLitNum) -> CompileError {
+    let msg = format!("cannot apply unary operator `{}` to type `{:?}`", op, ln);
+    CompileError::new(ErrorKind::Type, msg).code("E0600")
+}
+
+/// Name of the callee in diagnostics, e.g. "function `add`"
+fn callee_name(call_expr: &CallExpr) -> String {
+    match call_expr.expr.deref() {
+        Expr::Path(path_expr) => {
+            let path = path_expr.segments.join("::");
+            // instances of generic functions are called by their symbols
+            format!("function `{}`", demangle(&path).unwrap_or(path))
+        }
+        _ => "this function".to_string(),
+    }
+}
+
+const TUPLE_VALUES: &str = "tuples are only supported as params and arguments";
+
+const RAW_PTR_DEREF: &str = "raw pointers may be null, dangling or unaligned, which is undefined \
+                             behavior when they are dereferenced";
+
+/// The elements of a tuple are numbers, `bool`, `char`, thin pointers, structs or tuples.
+fn check_tuple(type_info: &TypeInfo) -> Result<(), RccError> {
+    if let TypeInfo::Tuple(elems) = type_info {
+        for elem in elems.iter() {
+            check_tuple(elem)?;
+            let supported = match elem {
+                TypeInfo::Ptr { kind, .. } => {
+                    kind != &PtrKind::Box && !elem.is_slice_ref() && elem.dyn_trait().is_none()
+                }
+                t => {
+                    t.is_number()
+                        || t.is_aggregate()
+                        || matches!(t, TypeInfo::Bool | TypeInfo::Char)
                 }
-                _ => Err("only loop can return values".into()),
             };
-        } else if let LoopKind::Loop(loop_expr) = self.loop_kind {
-            return try_set_type_info(loop_expr, Rc::new(RefCell::new(TypeInfo::Unit)));
+            if !supported {
+                return Err(format!("tuples of `{:?}` are not supported", elem).into());
+            }
+        }
+    }
+    Ok(())
+}
+
+/// The variable bound by the pattern of `let` or `for`
+fn pattern_var(pattern: &Pattern) -> Result<&str, RccError> {
+    pattern.var_name().ok_or_else(|| {
+        format!("pattern `{}` is only supported in the params of functions", pattern).into()
+    })
+}
+
+/// e.g. "2 arguments"
+fn arguments(n: usize) -> String {
+    format!("{} argument{}", n, if n == 1 { "" } else { "s" })
+}
+
+fn check_args_len(callee: &str, expected: usize, supplied: usize) -> Result<(), RccError> {
+    if supplied == expected {
+        return Ok(());
+    }
+    let msg = format!(
+        "{} takes {} but {} {} supplied",
+        callee,
+        arguments(expected),
+        arguments(supplied),
+        if supplied == 1 { "was" } else { "were" }
+    );
+    Err(CompileError::new(ErrorKind::Type, msg).code("E0061").into())
+}
+
+fn check_params_len(call_expr: &CallExpr, len: usize) -> Result<(), RccError> {
+    check_args_len(&callee_name(call_expr), len, call_expr.call_params.len())
+}
+
+/// Lengths of the array types `[T; N]` and of the repeat expressions `[x; N]` are constant
+/// expressions of type `usize`, which are replaced by their values as `usize` literals
+/// before the symbols are resolved:
+///
+/// ```text
+/// const fn size(n: usize) -> usize { n * 2 }
+/// let a: [i32; size(2)] = [0; N + 1];  ->  let a: [i32; 4usize] = [0; 4usize];
+/// ```
+///
+/// The lengths become the constants `$len0`, `$len1`, ... of a file of the constants, `const
+/// fn`s, structs, type aliases and inherent impls of the file, which is resolved and evaluated
+/// like the constants of a file by the IR interpreter. The lengths in that file must be
+/// literals.
+fn eval_array_lens(file: &mut File) -> Result<(), RccError> {
+    let mut lens = ArrayLens {
+        lens: vec![],
+        values: None,
+        scopes: &mut file.scopes,
+        scope_ids: vec![file.scope_id],
+        error: None,
+    };
+    file.items.iter_mut().for_each(|item| lens.visit_item(item));
+    let ArrayLens { lens, error, .. } = lens;
+    if let Some(error) = error {
+        return Err(error);
+    }
+    if lens.is_empty() {
+        return Ok(());
+    }
+    let mut names = HashSet::new();
+    for item in file.items.iter() {
+        if let Item::Const(ItemConst { name, .. }) | Item::Fn(ItemFn { name, .. }) = item {
+            names.insert(name.clone());
+        }
+    }
+    for len in lens.iter() {
+        let mut paths = NonConstPath {
+            names: &names,
+            found: None,
+        };
+        paths.visit_expr(len);
+        if let Some(name) = paths.found {
+            let msg = "attempt to use a non-constant value in a constant";
+            return Err(CompileError::new(ErrorKind::Resolve, msg)
+                .code("E0435")
+                .note(format!("`{}` is not a constant of the file", name))
+                .into());
+        }
+    }
+
+    let mut consts_file = File::new(file.scope_id);
+    consts_file.scopes = file.scopes.clone();
+    consts_file.type_table = file.type_table.clone();
+    consts_file.node_count = file.node_count;
+    consts_file.spans = file.spans.clone();
+    consts_file.def_spans = file.def_spans.clone();
+    for item in file.items.iter() {
+        match item {
+            Item::Const(_) | Item::Struct(_) | Item::Type(_) => {
+                consts_file.items.push(item.clone())
+            }
+            Item::Fn(item_fn) if item_fn.is_const => consts_file.items.push(item.clone()),
+            Item::Impl(item_impl) if item_impl.trait_name.is_none() => {
+                let mut item_impl = item_impl.clone();
+                item_impl.fns.retain(|item_fn| item_fn.is_const);
+                consts_file.items.push(Item::Impl(item_impl));
+            }
+            _ => {}
+        }
+    }
+    let count = lens.len();
+    for (i, len) in lens.into_iter().enumerate() {
+        let usize = TypeAnnotation::from("usize");
+        let item_const = ItemConst::new(Visibility::Priv, array_len_const(i), usize, len);
+        consts_file.items.push(Item::Const(item_const));
+    }
+    let mut resolver = SymbolResolver::new();
+    resolver.eval_array_lens = false;
+    resolver.visit_file(&mut consts_file)?;
+    let consts = IRBuilder::eval_file_consts(&mut consts_file)?;
+    let values: Vec<usize> = (0..count)
+        .map(|i| match consts.get(&array_len_const(i)) {
+            Some(Operand::Usize(value)) => *value,
+            value => unreachable!("length of an array: {:?}", value),
+        })
+        .collect();
+
+    let mut lens = ArrayLens {
+        lens: vec![],
+        values: Some(values.into_iter()),
+        scopes: &mut file.scopes,
+        scope_ids: vec![file.scope_id],
+        error: None,
+    };
+    file.items.iter_mut().for_each(|item| lens.visit_item(item));
+    Ok(())
+}
+
+/// The `usize` value of an array length, which is a literal after `eval_array_lens`
+fn array_len(len: &Expr) -> Option<usize> {
+    match len {
+        Expr::LitNum(lit_num_expr)
+            if matches!(lit_num_expr.suffix(), None | Some(TypeLitNum::Usize)) =>
+        {
+            lit_num_expr.value.parse().ok()
+        }
+        _ => None,
+    }
+}
+
+/// Visits the lengths of the arrays of a file in order. The ones which are not literals are
+/// collected, or replaced by `values`, and the types of the functions in the scopes are
+/// updated with them.
+struct ArrayLens<'a> {
+    lens: Vec<Expr>,
+    values: Option<std::vec::IntoIter<usize>>,
+    scopes: &'a mut ScopeArena,
+    /// the file scope and the scopes of the blocks being visited
+    scope_ids: Vec<ScopeID>,
+    error: Option<RccError>,
+}
+
+impl ArrayLens<'_> {
+    fn visit_len(&mut self, len: &mut Expr) {
+        if array_len(len).is_some() {
+            return;
+        }
+        match self.values.as_mut() {
+            Some(values) => {
+                let value = values.next().unwrap().to_string();
+                *len = Expr::LitNum(LitNumExpr::new(value, TypeLitNum::Usize));
+            }
+            None => self.lens.push(len.clone()),
+        }
+    }
+}
+
+impl VisitorMut for ArrayLens<'_> {
+    fn visit_item(&mut self, item: &mut Item) {
+        self.walk_item(item);
+        if self.values.is_none() {
+            return;
+        }
+        if let Item::Fn(_) | Item::ExternalBlock(_) | Item::Impl(_) = item {
+            let scope_id = *self.scope_ids.last().unwrap();
+            self.scopes.get_mut(scope_id).add_typedef(item);
+        }
+    }
+
+    fn visit_block_expr(&mut self, block_expr: &mut BlockExpr) {
+        self.scope_ids.push(block_expr.scope_id);
+        self.walk_block_expr(block_expr);
+        self.scope_ids.pop();
+    }
+
+    fn visit_item_struct(&mut self, item_struct: &mut ItemStruct) {
+        let types: Vec<&TypeAnnotation> = match item_struct.fields() {
+            Fields::Struct(fields) => fields.iter().map(|f| &f._type).collect(),
+            Fields::Tuple(fields) => fields.iter().map(|f| &f._type).collect(),
+            Fields::None => vec![],
+        };
+        if types.iter().any(|t| matches!(t, TypeAnnotation::Array(_))) {
+            let name = item_struct.name();
+            let msg = format!("array fields of struct `{}` are not supported yet", name);
+            self.error.get_or_insert(msg.into());
+        }
+    }
+
+    fn visit_type(&mut self, type_anno: &mut TypeAnnotation) {
+        match type_anno {
+            TypeAnnotation::Array(array) => {
+                self.visit_type(&mut array._type);
+                self.visit_len(&mut array.len);
+            }
+            TypeAnnotation::Ptr(ptr) => self.visit_type(&mut ptr.type_anno),
+            TypeAnnotation::Vec(elem) | TypeAnnotation::Slice(elem) => self.visit_type(elem),
+            TypeAnnotation::Tuple(types) => types.iter_mut().for_each(|t| self.visit_type(t)),
+            TypeAnnotation::FnPtr(fn_ptr) => {
+                fn_ptr.params.iter_mut().for_each(|t| self.visit_type(t));
+                self.visit_type(&mut fn_ptr.ret_type);
+            }
+            _ => {}
+        }
+    }
+
+    fn visit_array_expr(&mut self, array_expr: &mut ArrayExpr) {
+        for elem in array_expr.elems.iter_mut() {
+            self.visit_expr(elem);
+        }
+        if let Some(len_expr) = array_expr.len_expr.as_mut() {
+            self.visit_len(len_expr);
+        }
+    }
+}
+
+/// The first name of a path in an array length which is not a constant or a function of the
+/// file, e.g. a local variable
+struct NonConstPath<'a> {
+    names: &'a HashSet<String>,
+    found: Option<String>,
+}
+
+impl Visitor for NonConstPath<'_> {
+    fn visit_path_expr(&mut self, path_expr: &PathExpr) {
+        if let [name] = path_expr.segments.as_slice() {
+            if !self.names.contains(name) {
+                self.found.get_or_insert(name.clone());
+            }
+        }
+    }
+}
+
+/// `a[i]` always panics if `i` is a literal out of the bounds of the array `a`.
+fn check_array_index(index_expr: &Expr, len: usize) -> Result<(), RccError> {
+    let index = match index_expr {
+        Expr::LitNum(lit_num_expr) => lit_num_expr.value.parse::<usize>().ok(),
+        _ => None,
+    };
+    match index {
+        Some(index) if index >= len => {
+            let msg = "this operation will panic at runtime";
+            let note = format!(
+                "index out of bounds: the length is {} but the index is {}",
+                len, index
+            );
+            Err(CompileError::new(ErrorKind::Type, msg).note(note).into())
         }
+        _ => Ok(()),
+    }
+}
+
+/// The `n`th argument of `callee`, starting from 1, is `found` instead of `expected`.
+fn arg_type_error(
+    callee: &str,
+    n: usize,
+    expected: &TypeInfo,
+    found: &TypeInfo,
+    span: Option<Span>,
+) -> RccError {
+    let msg = format!(
+        "mismatched types in argument {} of {}: expected `{}`, found `{}`",
+        n, callee, expected, found
+    );
+    with_span(CompileError::new(ErrorKind::Type, msg).code("E0308"), span)
+}
+
+fn with_span(error: CompileError, span: Option<Span>) -> RccError {
+    match span {
+        Some(span) => error.span(span).into(),
+        None => error.into(),
+    }
+}
+
+fn check_arg_type(
+    types: &TypeTable,
+    callee: &str,
+    n: usize,
+    expr: &Expr,
+    expected: &TypeInfo,
+    span: Option<Span>,
+) -> Result<(), RccError> {
+    let found = expr.type_info(types);
+    if found.is(expected) {
         Ok(())
+    } else {
+        Err(arg_type_error(callee, n, expected, &found, span))
     }
 }
 
 pub(super) fn assert_type_is<T: ExprVisit>(
+    types: &TypeTable,
     expr: &T,
     expected_type: &TypeInfo,
     err_msg: &str,
 ) -> Result<(), RccError> {
-    let type_info = expr.type_info();
-    let t = type_info.borrow();
-    let cond_type = t.deref();
+    let cond_type = expr.type_info(types);
     if !cond_type.is(expected_type) {
         return Err(format!(
             "{}: expected {:?}, found {:?}",
@@ -0,0 +1,80 @@
+use crate::analyser::callgraph::CallGraph;
+use crate::ir::cfg::CFGIR;
+use crate::ir::tests::ir_build;
+
+fn call_graph(input: &str) -> CallGraph {
+    CallGraph::new(&CFGIR::new(ir_build(input).unwrap()))
+}
+
+#[test]
+fn callgraph_test() {
+    let graph = call_graph(
+        r#"
+        fn fib(n: i32) -> i32 { if n < 2 { n } else { fib(n - 1) + fib(n - 2) } }
+        fn unused() -> i32 { fib(3) }
+        fn is_even(n: i32) -> bool { if n == 0 { true } else { is_odd(n - 1) } }
+        fn is_odd(n: i32) -> bool { if n == 0 { false } else { is_even(n - 1) } }
+        fn main() {
+            let x = fib(10);
+            let b = is_even(x);
+        }
+    "#,
+    );
+    assert_eq!(vec!["fib", "is_even"], graph.callees("main").collect::<Vec<_>>());
+    assert_eq!(
+        vec!["fib", "unused", "main"],
+        graph.callers("fib").collect::<Vec<_>>()
+    );
+    assert_eq!(vec!["unused"], graph.unreachable_fns());
+    assert_eq!(
+        vec![vec!["fib"], vec!["is_even", "is_odd"]],
+        graph.recursion_cycles()
+    );
+    assert_eq!(
+        "fib -> fib\n\
+         unused -> fib\n\
+         is_even -> is_odd\n\
+         is_odd -> is_even\n\
+         main -> fib, is_even\n\
+         recursive: fib\n\
+         recursive: is_even, is_odd\n\
+         unreachable: unused\n",
+        graph.to_string()
+    );
+}
+
+#[test]
+fn callgraph_pub_fn_test() {
+    let graph = call_graph(
+        r#"
+        fn leaf() {}
+        pub fn api() { leaf(); }
+        fn main() {}
+    "#,
+    );
+    assert!(graph.unreachable_fns().is_empty());
+    assert!(graph.recursion_cycles().is_empty());
+    assert_eq!(vec!["api"], graph.callers("leaf").collect::<Vec<_>>());
+}
+
+#[test]
+fn callgraph_cycle_test() {
+    // callees are declared after their callers, and a cycle not reached from `main` is
+    // unreachable as a whole
+    let graph = call_graph(
+        r#"
+        fn main() { a(3); }
+        fn a(n: i32) { if n > 0 { b(n - 1); } }
+        fn b(n: i32) { if n > 0 { c(n - 1); } }
+        fn c(n: i32) { if n > 0 { a(n - 1); } }
+        fn ping(n: i32) { if n > 0 { pong(n - 1); } }
+        fn pong(n: i32) { if n > 0 { ping(n - 1); } }
+    "#,
+    );
+    assert_eq!(vec!["main", "c"], graph.callers("a").collect::<Vec<_>>());
+    assert_eq!(vec!["ping", "pong"], graph.unreachable_fns());
+    assert_eq!(
+        vec![vec!["a", "b", "c"], vec!["ping", "pong"]],
+        graph.recursion_cycles()
+    );
+}
@@ -5,6 +5,7 @@ use crate::ast::file::File;
 
 mod sym_resolver_tests;
 mod scope_test;
+mod callgraph_tests;
 
 fn get_ast_file(input: &str) -> Result<File, RccError> {
     // lex
@@ -1,20 +1,72 @@
-use crate::analyser::scope::Scope;
+use crate::analyser::scope::{ScopeArena, BUILTIN_SCOPE_ID};
 use crate::analyser::sym_resolver::{TypeInfo, VarInfo, VarKind};
+use crate::analyser::type_table::TypeTable;
 use crate::ast::types::TypeLitNum;
-use std::cell::RefCell;
-use std::rc::Rc;
 
 #[test]
 fn scope_test() {
-    let mut scope = Scope::new(0);
-    let var_info = VarInfo::new(3, VarKind::Local, Rc::new(RefCell::new(TypeInfo::LitNum(TypeLitNum::U64))));
+    let mut scopes = ScopeArena::new();
+    let mut types = TypeTable::new();
+    let scope_id = scopes.new_scope(Some(BUILTIN_SCOPE_ID));
+    let scope = scopes.get_mut(scope_id);
+    let u64_slot = types.new_slot(TypeInfo::LitNum(TypeLitNum::U64));
+    let var_info = VarInfo::new(3, 1, VarKind::Local, u64_slot);
 
     scope.cur_stmt_id = 1;
-    scope.add_variable("a", VarKind::Local, Rc::new(RefCell::new(TypeInfo::Bool)));
+    scope.add_variable("a", VarKind::Local, types.new_slot(TypeInfo::Bool));
     scope.cur_stmt_id = 3;
-    scope.add_variable("a", VarKind::Local, Rc::new(RefCell::new(TypeInfo::LitNum(TypeLitNum::U64))));
+    scope.add_variable("a", VarKind::Local, u64_slot);
     scope.cur_stmt_id = 8;
-    scope.add_variable("a", VarKind::LocalMut, Rc::new(RefCell::new(TypeInfo::Bool)));
+    scope.add_variable("a", VarKind::LocalMut, types.new_slot(TypeInfo::Bool));
     scope.cur_stmt_id = 4;
-    assert_eq!(&var_info, scope.find_variable("a").unwrap().0);
+    assert_eq!(&var_info, scopes.find_variable(scope_id, "a").unwrap().0);
+
+    // variables and types of the fathers
+    let inner_id = scopes.new_scope(Some(scope_id));
+    assert_eq!(scope_id, scopes.find_variable(inner_id, "a").unwrap().1);
+    assert!(scopes.find_variable(inner_id, "b").is_none());
+    assert_eq!(TypeInfo::Bool, scopes.find_def_except_fn(inner_id, "bool"));
+    assert_eq!(TypeInfo::Unknown, scopes.find_fn(inner_id, "bool"));
+}
+
+#[test]
+fn shadowing_scope_test() {
+    let mut scopes = ScopeArena::new();
+    let mut types = TypeTable::new();
+    let scope_id = scopes.new_scope(Some(BUILTIN_SCOPE_ID));
+    let inner_id = scopes.new_scope(Some(scope_id));
+    let bool_slot = types.new_slot(TypeInfo::Bool);
+    let unknown_slot = types.new_slot(TypeInfo::Unknown);
+    // `let a = true; let a = a; { a }; let b = a;`, the variable of a `let` stmt is visible
+    // from the next statement
+    let scope = scopes.get_mut(scope_id);
+    scope.cur_stmt_id = 1;
+    scope.add_variable("a", VarKind::Local, bool_slot);
+    scope.cur_stmt_id = 2;
+    scope.add_variable("a", VarKind::LocalMut, unknown_slot);
+    scope.cur_stmt_id = 4;
+    scope.add_variable("b", VarKind::Local, unknown_slot);
+
+    let version = |scopes: &mut ScopeArena, scope_id, stmt_id, ident| {
+        scopes.get_mut(scope_id).cur_stmt_id = stmt_id;
+        scopes
+            .find_variable(scope_id, ident)
+            .map(|(var_info, _)| var_info.version())
+    };
+    assert_eq!(None, version(&mut scopes, scope_id, 0, "a"));
+    assert_eq!(Some(0), version(&mut scopes, scope_id, 1, "a"));
+    assert_eq!(Some(1), version(&mut scopes, scope_id, 2, "a"));
+    assert_eq!(Some(1), version(&mut scopes, scope_id, 9, "a"));
+    assert_eq!(None, version(&mut scopes, scope_id, 3, "b"));
+    assert_eq!(
+        &VarInfo::new(2, 1, VarKind::LocalMut, unknown_slot),
+        scopes.find_variable(scope_id, "a").unwrap().0
+    );
+
+    // a variable of the father is looked up at the statement of the father
+    scopes.get_mut(scope_id).cur_stmt_id = 1;
+    assert_eq!(Some(0), version(&mut scopes, inner_id, 5, "a"));
+    assert_eq!(None, version(&mut scopes, inner_id, 5, "b"));
+    scopes.get_mut(inner_id).add_variable("a", VarKind::Local, bool_slot);
+    assert_eq!(inner_id, scopes.find_variable(inner_id, "a").unwrap().1);
 }
@@ -1,6 +1,59 @@
-use crate::analyser::sym_resolver::SymbolResolver;
+use crate::analyser::cfg::CfgOptions;
+use crate::analyser::sym_resolver::{SymbolResolver, TypeInfo};
+use crate::ast::item::Item;
+use crate::ast::types::TypeLitNum;
+use crate::ast::NodeId;
 use crate::analyser::tests::get_ast_file;
-use crate::rcc::RccError;
+use crate::rcc::{CompileError, ErrorKind, RccError};
+
+fn ident_not_found(ident: &str) -> RccError {
+    let msg = format!("identifier `{}` not found", ident);
+    CompileError::new(ErrorKind::Resolve, msg).code("E0425").into()
+}
+
+fn invalid_let_type(expected: &str, found: &str) -> RccError {
+    let msg = format!("invalid type in let stmt: expected `{}`, found `{}`", expected, found);
+    CompileError::new(ErrorKind::Type, msg).code("E0308").into()
+}
+
+fn mismatched_num_types(op: &str, expected: &str, found: &str) -> RccError {
+    let msg = format!("mismatched types: expected `{}`, found `{}`", expected, found);
+    CompileError::new(ErrorKind::Type, msg)
+        .code("E0308")
+        .note(format!("both operands of `{}` must have the same type", op))
+        .help("numbers are not converted implicitly, consider casting with `as`")
+        .into()
+}
+
+fn wrong_arg_count(msg: &str) -> RccError {
+    CompileError::new(ErrorKind::Type, msg).code("E0061").into()
+}
+
+fn wrong_arg_type(msg: &str) -> RccError {
+    CompileError::new(ErrorKind::Type, msg).code("E0308").into()
+}
+
+fn missing_return(name: &str, ret_type: &str) -> RccError {
+    let msg = format!(
+        "missing return value in function `{}`: expected `{}`, found `()`",
+        name, ret_type
+    );
+    CompileError::new(ErrorKind::Type, msg)
+        .code("E0308")
+        .note("the body has no tail expression, and not every path of it returns")
+        .into()
+}
+
+const LET_MUT: &str = "a variable is mutable only if it is declared by `let mut`";
+
+fn immutable_var(name: &str) -> RccError {
+    let msg = format!("cannot assign twice to immutable variable `{}`", name);
+    CompileError::new(ErrorKind::Type, msg).code("E0384").note(LET_MUT).into()
+}
+
+fn immutable_place(msg: &str) -> RccError {
+    CompileError::new(ErrorKind::Type, msg).code("E0594").note(LET_MUT).into()
+}
 
 fn file_validate(inputs: &[&str], expecteds: &[Result<(), RccError>]) {
     assert_eq!(inputs.len(), expecteds.len());
@@ -20,6 +73,27 @@ fn file_validate(inputs: &[&str], expecteds: &[Result<(), RccError>]) {
     }
 }
 
+#[test]
+fn similar_name_test() {
+    let suggest = |src: &str| -> Vec<String> {
+        let mut ast_file = get_ast_file(src).unwrap();
+        match SymbolResolver::new().visit_file(&mut ast_file) {
+            Err(RccError::Compile(e)) => e.helps,
+            r => panic!("{:?}", r),
+        }
+    };
+    assert_eq!(
+        vec!["did you mean `count`?"],
+        suggest("fn main() { let mut count = 0; let counter = 1; cont = 2; }")
+    );
+    assert_eq!(
+        vec!["did you mean `print_num`?"],
+        suggest("fn print_num() {} fn main() { print_nm(); }")
+    );
+    // too different
+    assert!(suggest("fn main() { let ab = 0; xy = 2; }").is_empty());
+}
+
 #[test]
 fn ident_not_found_test() {
     let mut sym_resolver = SymbolResolver::new();
@@ -32,9 +106,9 @@ fn ident_not_found_test() {
     "#,
     )
     .unwrap();
-    assert_eq!(1, ast_file.scope.types.len());
+    assert_eq!(1, ast_file.scopes.get(ast_file.scope_id).types.len());
     assert_eq!(
-        Err("identifier `a` not found".into()),
+        Err(ident_not_found("a")),
         sym_resolver.visit_file(&mut ast_file)
     );
 }
@@ -52,10 +126,48 @@ fn let_stmt_add_ident_test() {
     "#,
     )
     .unwrap();
-    assert_eq!(1, ast_file.scope.types.len());
+    assert_eq!(1, ast_file.scopes.get(ast_file.scope_id).types.len());
     assert_eq!(Ok(()), sym_resolver.visit_file(&mut ast_file));
 }
 
+#[test]
+fn nested_fn_test() {
+    let capture = |ident: &str| -> RccError {
+        CompileError::new(ErrorKind::Resolve, "can't capture dynamic environment in a fn item")
+            .code("E0434")
+            .note(format!("`{}` is a local variable of the outer function", ident))
+            .into()
+    };
+    file_validate(
+        &[
+            "fn main() { let a = 3; fn f() -> i32 { a } }",
+            "fn main() { let a = 3; fn f(b: i32) -> i32 { let c = b; fn g() -> i32 { c } 1 } }",
+            "fn main() { let a = f(2); fn f(a: i32) -> i32 { let b = { a + 1 }; b } }",
+            "const N: i32 = 2; fn main() { fn f() -> i32 { N } }",
+            "fn main() { { fn f() {} } f(); }",
+            "fn main() { #[no_mangle] fn f() {} { fn f() {} } }",
+            "fn main() { #[no_mangle] fn main() {} }",
+            "fn main() { { #[no_mangle] fn f() {} } { #[no_mangle] fn f() {} } }",
+            "#[inline] fn f() {} fn main() { f() }",
+            "#[inline(always)] fn f() {} fn main() { f() }",
+            "fn main() { #[inline(never)] fn f() {} }",
+        ],
+        &[
+            Err(capture("a")),
+            Err(capture("c")),
+            Ok(()),
+            Ok(()),
+            Err(ident_not_found("f")),
+            Ok(()),
+            Err(CompileError::new(ErrorKind::Resolve, "symbol `main` is already defined").into()),
+            Err(CompileError::new(ErrorKind::Resolve, "symbol `f` is already defined").into()),
+            Ok(()),
+            Ok(()),
+            Ok(()),
+        ],
+    );
+}
+
 #[test]
 fn type_annotation_test() {
     file_validate(
@@ -80,7 +192,7 @@ fn type_annotation_test() {
         ],
         &[
             Ok(()),
-            Err("invalid type in let stmt: expected `LitNum(i32)`, found `LitNum(i64)`".into()),
+            Err(invalid_let_type("LitNum(i32)", "LitNum(i64)")),
             Ok(()),
         ],
     );
@@ -104,7 +216,7 @@ fn str_test() {
     "#,
     )
     .unwrap();
-    assert_eq!(1, ast_file.scope.types.len());
+    assert_eq!(1, ast_file.scopes.get(ast_file.scope_id).types.len());
     assert_eq!(Ok(()), sym_resolver.visit_file(&mut ast_file));
 }
 
@@ -124,7 +236,7 @@ fn fn_param_test() {
     "#,
     )
     .unwrap();
-    assert_eq!(2, ast_file.scope.types.len());
+    assert_eq!(2, ast_file.scopes.get(ast_file.scope_id).types.len());
     assert_eq!(Ok(()), sym_resolver.visit_file(&mut ast_file));
 }
 
@@ -164,9 +276,9 @@ fn assign_expr_test() {
         ],
         &[
             Ok(()),
-            Err("invalid operand type `LitNum(i32)` and `LitNum(i64)` for `-`".into()),
-            Err("lhs is not mutable".into()),
-            Err("invalid type `LitNum(#i)` for `^=`".into()),
+            Err(mismatched_num_types("-", "i32", "i64")),
+            Err(immutable_var("a")),
+            Err(mismatched_num_types("^=", "{integer}", "f32")),
         ],
     );
 }
@@ -182,10 +294,43 @@ fn bin_op_test() {
       
     "#,
             "fn rem2(x: u32) -> u32 {x%2}",
+            "fn f(a: u8, b: u32) -> u8 { let c = a + 1; let d = b << a; c >> b }",
+            "fn f(a: u8, b: i32) -> bool { a < b }",
+            "fn f(a: i64, b: i32) -> i64 { a % b }",
+            "fn f(a: u16, b: u32) -> u16 { a ^ b }",
+            "fn f(a: i32) -> i32 { a * 2.0 }",
+        ],
+        &[
+            Err("invalid operand type `LitNum(i32)` and `LitNum(#f)` for `%`".into()),
+            Ok(()),
+            Ok(()),
+            Err(mismatched_num_types("<", "u8", "i32")),
+            Err(mismatched_num_types("%", "i64", "i32")),
+            Err(mismatched_num_types("^", "u16", "u32")),
+            Err(mismatched_num_types("*", "i32", "{float}")),
+        ],
+    );
+}
+
+#[test]
+fn un_op_test() {
+    let cannot_apply = |op: &str, ty: &str| {
+        let msg = format!("cannot apply unary operator `{}` to type `{}`", op, ty);
+        CompileError::new(ErrorKind::Type, msg).code("E0600")
+    };
+    file_validate(
+        &[
+            "fn f(a: i32, b: bool, c: u8, d: f64) -> i32 { let e = !b; let g = !c; -d; -!a }",
+            "fn f(a: u32) -> u32 { -a }",
+            "fn f(a: f32) -> f32 { !a }",
+            "fn f(a: bool) -> bool { -a }",
+        ],
+        &[
+            Ok(()),
+            Err(cannot_apply("-", "u32").note("unsigned values cannot be negated").into()),
+            Err(cannot_apply("!", "f32").into()),
+            Err("cannot apply unary operator `-` to type `Bool`".into()),
         ],
-        &[Err(
-            "invalid operand type `LitNum(i32)` and `LitNum(#f)` for `%`".into(),
-        ), Ok(())],
     );
 }
 
@@ -258,6 +403,16 @@ fn loop_test() {
                 break;
             };
         }
+    "#,
+            r#"
+        fn bar() {
+            loop {
+                fn baz() {
+                    break;
+                }
+                break;
+            }
+        }
     "#,
         ],
         &[
@@ -265,8 +420,14 @@ fn loop_test() {
             Ok(()),
             Err("invalid type `Unit` for `=`".into()),
             Ok(()),
-            Err("only loop can return values".into()),
+            Err(CompileError::new(ErrorKind::Type, "`break` with value from a `while` loop")
+                .code("E0571")
+                .help("only `loop` can return values, use `break` on its own here")
+                .into()),
             Ok(()),
+            Err(CompileError::new(ErrorKind::Type, "`break` outside of a loop")
+                .code("E0268")
+                .into()),
         ],
     );
 }
@@ -298,6 +459,10 @@ fn return_test() {
                     a
                  }
             }"#,
+            r#"fn f(a: i32) -> i32 { if a == 2 { return 3; } else { return 4; } }"#,
+            r#"fn f(a: i32) -> i32 { if a == 2 { return 3; } }"#,
+            r#"fn f(a: i32) -> i32 { while a == 2 { return 3; } }"#,
+            r#"fn f() -> bool {}"#,
         ],
         &[
             Ok(()),
@@ -306,8 +471,12 @@ fn return_test() {
             Ok(()),
             Ok(()),
             Ok(()),
-            Err("invalid return type: excepted `LitNum(i32)`, found `Unit`".into()),
+            Err(missing_return("main", "i32")),
+            Ok(()),
             Ok(()),
+            Err(missing_return("f", "i32")),
+            Err(missing_return("f", "i32")),
+            Err(missing_return("f", "bool")),
         ],
     );
 }
@@ -428,21 +597,201 @@ fn call_test() {
         ],
         &[
             Ok(()),
-            Err("invalid type for call expr: expected LitNum(i32), found LitNum(i64)".into()),
-            Err("This function takes 0 parameters but 1 parameters was supplied".into()),
+            Err(wrong_arg_type(
+                "mismatched types in argument 1 of function `foo`: \
+                 expected `i32`, found `i64`",
+            )),
+            Err(wrong_arg_count("function `foo` takes 0 arguments but 1 argument was supplied")),
             Err("expr is not callable".into()),
         ],
     );
 }
 
+#[test]
+fn call_args_test() {
+    let prelude = r#"
+        fn add(a: i32, b: i32) -> i32 { a + b }
+        fn first<T>(a: T, b: i32) -> T { a }
+        trait Show { fn show(&self, n: i32) -> i32; }
+        impl Show for i32 { fn show(&self, n: i32) -> i32 { n } }
+    "#;
+    let programs = [
+        "fn main() { add(1); }",
+        "fn main() { add(1, true); }",
+        "fn main() { first(1u8, 'c'); }",
+        "fn main() { let a = 1i32; a.show(); }",
+        "fn main() { let a = 1i32; a.show(2u8); }",
+    ];
+    let inputs: Vec<String> = programs.iter().map(|p| format!("{}{}", prelude, p)).collect();
+    let inputs: Vec<&str> = inputs.iter().map(String::as_str).collect();
+    file_validate(
+        &inputs,
+        &[
+            Err(wrong_arg_count("function `add` takes 2 arguments but 1 argument was supplied")),
+            Err(wrong_arg_type(
+                "mismatched types in argument 2 of function `add`: \
+                 expected `i32`, found `bool`",
+            )),
+            Err(wrong_arg_type(
+                "mismatched types in argument 2 of function `first::<u8>`: \
+                 expected `i32`, found `char`",
+            )),
+            Err(wrong_arg_count("method `show` takes 1 argument but 0 arguments were supplied")),
+            Err(wrong_arg_type(
+                "mismatched types in argument 1 of method `show`: \
+                 expected `i32`, found `u8`",
+            )),
+        ],
+    );
+}
+
+#[test]
+fn print_expr_test() {
+    file_validate(
+        &[
+            r#"
+                fn main() {
+                    let a = 3u8;
+                    let s = "s";
+                    println!("{} {} {} {}", a, -1, 'c', s);
+                }
+            "#,
+            r#"
+                fn main() {
+                    println!("{}", true);
+                }
+            "#,
+        ],
+        &[
+            Ok(()),
+            Err("`Bool` cannot be formatted with `{}`".into()),
+        ],
+    );
+}
+
+#[test]
+fn assert_expr_test() {
+    file_validate(
+        &[
+            "fn main() { let a = 3u8; assert!(a > 1 && true); assert_eq!(a, 3, \"a\"); assert_ne!(-1, 1); }",
+            "fn main() { assert!(1); }",
+            "fn main() { assert_eq!(1, true); }",
+        ],
+        &[
+            Ok(()),
+            Err("invalid type of condition expr: expected `bool`, found: LitNum(#i)".into()),
+            Err("invalid operand type `LitNum(#i)` and `Bool` for `==`".into()),
+        ],
+    );
+}
+
+#[test]
+fn box_test() {
+    file_validate(
+        &[
+            r#"
+                fn main() {
+                    let mut a: Box<Box<u8>> = Box::new(Box::new(3u8));
+                    **a = 4;
+                    let b: u8 = **a + 1;
+                }
+            "#,
+            "fn main() { let a = Box::new(2); *a = 3; }",
+            "fn main() { let a = Box::new(1, 2); }",
+            "fn main() { let a: Box<i64> = Box::new(2); }",
+            "fn main() { let a = 2; let b = *a; }",
+        ],
+        &[
+            Ok(()),
+            Err(immutable_place("cannot assign to the content of an immutable `Box`")),
+            Err(wrong_arg_count(
+                "function `Box::new` takes 1 argument but 2 arguments were supplied",
+            )),
+            Err(invalid_let_type(
+                "Ptr { kind: Box, type_info: LitNum(i64) }",
+                "Ptr { kind: Box, type_info: LitNum(i32) }",
+            )),
+            Err("type `LitNum(#i)` can not be dereferenced".into()),
+        ],
+    );
+}
+
+#[test]
+fn vec_test() {
+    file_validate(
+        &[
+            r#"
+                fn main() {
+                    let mut v = Vec::new();
+                    v.push(1u8);
+                    v[0] += 2;
+                    let n: usize = v.len();
+                    let mut s = String::new();
+                    s.push('a');
+                    s.push_str("bc");
+                    println!("{} {}", s, v[0]);
+                }
+            "#,
+            "fn main() { let v: Vec<i64> = Vec::new(); v.push(1); }",
+            "fn main() { let mut v: Vec<i64> = Vec::new(); v.push(true); }",
+            "fn main() { let v = Vec::new(); let a = v[0]; }",
+            "fn main() { let mut v = Vec::new(); v.push(1); let a = v[0i32]; }",
+            "fn main() { let s = String::from(\"a\"); let c = s[0]; }",
+            "fn main() { let mut v = Vec::new(); v.push(Box::new(1)); }",
+            "fn main() { let s = String::new(); s.pop(); }",
+        ],
+        &[
+            Ok(()),
+            Err("cannot borrow `v` as mutable, as it is not declared as mutable".into()),
+            Err(wrong_arg_type(
+                "mismatched types in argument 1 of method `push`: \
+                 expected `i64`, found `bool`",
+            )),
+            Err("type annotations needed for `Vec<_>`".into()),
+            Err("invalid type for index: expected LitNum(usize), found LitNum(i32)".into()),
+            Err("cannot index into a value of type `String`".into()),
+            Err("`Vec<Ptr { kind: Box, type_info: LitNum(i32) }>` is not supported".into()),
+            Err("no method named `pop` found for `String`".into()),
+        ],
+    );
+}
+
 #[test]
 fn local_mut_test() {
     file_validate(
-        &[r#"fn add() {
+        &[
+            r#"fn add() {
         let a = 2;
         a = 3;
-    }"#],
-        &[Err("lhs is not mutable".into())],
+    }"#,
+            "fn f() { let mut a = 1; let r = &a; *r = 2; }",
+            "fn f() { let a = [1, 2]; a[0] = 3; }",
+            "fn f() { let mut a = [1, 2]; let s: &[i32] = &a; s[0] += 3; }",
+            "fn f() { let mut a = [1, 2]; let s: &mut [i32] = &mut a; s[0] += 3; }",
+            "fn f() {} fn main() { f = main; }",
+        ],
+        &[
+            Err(immutable_var("a")),
+            Err(CompileError::new(ErrorKind::Type, "cannot assign to immutable borrowed content")
+                .code("E0594")
+                .help("consider borrowing it by `&mut` instead")
+                .into()),
+            Err(CompileError::new(ErrorKind::Type, "cannot assign to an immutable element")
+                .code("E0594")
+                .note(LET_MUT)
+                .note("the elements of a slice are mutable only if it is `&mut [T]`")
+                .into()),
+            Err(CompileError::new(ErrorKind::Type, "cannot assign to an immutable element")
+                .code("E0594")
+                .note(LET_MUT)
+                .note("the elements of a slice are mutable only if it is `&mut [T]`")
+                .into()),
+            Ok(()),
+            Err(CompileError::new(ErrorKind::Type, "cannot assign to this expression")
+                .code("E0070")
+                .note("only variables, fields, indexing and dereferences can be assigned to")
+                .into()),
+        ],
     );
 }
 
@@ -462,7 +811,10 @@ extern "C" {
         let i = 3;
     }
 }
-    "#], &[Ok(()), Err("error in parsing: except ;".into())]);
+    "#], &[
+        Ok(()),
+        Err(CompileError::new(ErrorKind::Parse, "expected one of `->`, `;`; found `{`").into()),
+    ]);
 }
 
 #[test]
@@ -487,6 +839,853 @@ fn unknown_type_test() {
         b = 88;
         b = loop {};
     }
-    "#], &[Ok(()), Err("invalid type `LitNum(i128)` for `=`".into()), Ok(())]);
+    "#], &[Ok(()), Err(mismatched_num_types("=", "i128", "i32")), Ok(())]);
 
-}
\ No newline at end of file
+}
+#[test]
+fn type_table_test() {
+    let mut f = get_ast_file(
+        r#"
+    fn foo() -> i64 {
+        let a = 3;
+        a
+    }
+    "#,
+    )
+    .unwrap();
+    SymbolResolver::new().visit_file(&mut f).unwrap();
+    // `3`, `a` and the block share the type of the return value
+    for id in 0..3 {
+        assert_eq!(Some(&TypeInfo::LitNum(TypeLitNum::I64)), f.type_table.node_type(NodeId(id)));
+    }
+    assert_eq!(None, f.type_table.node_type(NodeId(3)));
+}
+
+#[test]
+fn unify_test() {
+    let mut f = get_ast_file(
+        r#"
+    fn returns_u64() -> u64 { 7 }
+    fn main() {
+        let mut v = 0;
+        v = returns_u64();
+        let w = v + 1;
+    }
+    "#,
+    )
+    .unwrap();
+    SymbolResolver::new().visit_file(&mut f).unwrap();
+    // `0` and `1` are inferred from the later assignment
+    for id in 0..16 {
+        if let Some(TypeInfo::LitNum(lit)) = f.type_table.node_type(NodeId(id)) {
+            assert_eq!(&TypeLitNum::U64, lit, "node {}", id);
+        }
+    }
+
+    file_validate(&[r#"
+    fn foo() -> i64 {
+        let a = 3;
+        let b = if a > 2 { a } else { 5 };
+        let c = loop { if b == 1 { break 1; } break 2i64; };
+        b + c
+    }
+    "#, r#"
+    fn foo() {
+        let mut v = 0;
+        v = 1u8;
+        v = 2u64;
+    }
+    "#, r#"
+    fn foo() {
+        let a = 1;
+        let b = if true { a } else { 2i64 };
+        let c: i32 = a;
+    }
+    "#], &[
+        Ok(()),
+        Err(mismatched_num_types("=", "u8", "u64")),
+        Err(invalid_let_type("LitNum(i32)", "LitNum(i64)")),
+    ]);
+}
+
+#[test]
+fn trait_test() {
+    let add = r#"
+    trait Add { fn add(self, rhs: Self) -> Self; }
+    impl Add for i32 { fn add(self, rhs: Self) -> Self { self + rhs } }
+    "#;
+    let inputs = [
+        "fn sum<T: Add>(a: T, b: T) -> T { a.add(b) } fn main() { let a = sum(1, 2); let b: i32 = a.add(3); }",
+        "fn sum<T: Add>(a: T, b: T) -> T { a.add(b) } fn main() { let a = sum(1u8, 2); }",
+        "fn id<T>(a: T) -> T { a } fn main() { let a: u64 = id(1u64); let b: bool = id(true); }",
+        "fn id<T>(a: T, b: T) -> T { a } fn main() { id(1u8, 2u16); }",
+        "fn id<T>() -> i32 { 0 } fn main() { id(); }",
+        "impl Add for i32 { fn add(self, rhs: i32) -> i32 { rhs } } fn main() {}",
+        "impl Sub for i32 {} fn main() {}",
+        "impl Add for i64 { fn sub(self, rhs: i64) -> i64 { rhs } } fn main() {}",
+        "impl Add for i64 { fn add(self) -> i64 { self } } fn main() {}",
+        "fn main() { fn id<T>(a: T) -> T { a } }",
+    ]
+    .map(|s| format!("{}{}", add, s));
+    file_validate(
+        &inputs.iter().map(|s| s.as_str()).collect::<Vec<_>>(),
+        &[
+            Ok(()),
+            Err("the trait bound `u8: Add` is not satisfied".into()),
+            Ok(()),
+            Err(wrong_arg_type(
+                "mismatched types in argument 2 of function `id`: \
+                 expected `u8`, found `u16`",
+            )),
+            Err("type annotations needed for `T`".into()),
+            Err("conflicting implementations of trait `Add` for type `LitNum(i32)`".into()),
+            Err("cannot find trait `Sub`".into()),
+            Err("method `sub` is not a member of trait `Add`".into()),
+            Err("method `add` has 1 parameters but the declaration in trait `Add` has 2".into()),
+            Err("generic function `id` must be declared at the top level".into()),
+        ],
+    );
+
+    // instances are added to the items of the file
+    let mut f = get_ast_file(&inputs[0]).unwrap();
+    SymbolResolver::new().visit_file(&mut f).unwrap();
+    assert!(matches!(f.items.last(), Some(Item::Fn(item_fn)) if item_fn.name == "_RCG3sum3i32E"));
+}
+
+#[test]
+fn operator_overloading_test() {
+    let traits = r#"
+    trait Add { fn add(self, rhs: Self) -> Self; }
+    trait PartialEq { fn eq(self, rhs: Self) -> bool; }
+    impl Add for String { fn add(self, rhs: String) -> String { self } }
+    "#;
+    let inputs = [
+        "fn main() { let a = String::new(); let b: String = a + String::new(); }",
+        "fn main() { let a = String::new(); let b = a + 1; }",
+        "fn main() { let a = String::new(); let b = a - String::new(); }",
+        "impl PartialEq for String { fn eq(self, rhs: String) -> bool { true } } fn main() { let b: bool = String::new() != String::new(); }",
+    ]
+    .map(|s| format!("{}{}", traits, s));
+    file_validate(
+        &inputs.iter().map(|s| s.as_str()).collect::<Vec<_>>(),
+        &[
+            Ok(()),
+            Err("invalid operand type `String` and `LitNum(#i)` for `+`".into()),
+            Err("invalid operand type `String` and `String` for `-`".into()),
+            Ok(()),
+        ],
+    );
+}
+
+#[test]
+fn dyn_trait_test() {
+    let show = r#"
+    trait Show { fn show(&self) -> i32; fn set(&mut self, v: i32); }
+    impl Show for i32 { fn show(&self) -> i32 { *self } fn set(&mut self, v: i32) {} }
+    trait Clone { fn clone(&self) -> Self; }
+    trait Add { fn add(self, rhs: Self) -> Self; }
+    "#;
+    let inputs = [
+        "fn f(s: &dyn Show) -> i32 { s.show() } fn main() { let a = 3i32; let b = f(&a) + a.show(); }",
+        "fn main() { let mut a = 3; let d: &mut dyn Show = &mut a; d.set(1); }",
+        "fn main() { let a = true; let d: &dyn Show = &a; }",
+        "fn f(s: &dyn Show) { s.set(1); } fn main() {}",
+        "fn f(s: &dyn Show) { s.print(); } fn main() {}",
+        "fn f(s: &dyn Clone) {} fn main() {}",
+        "fn f(s: &dyn Add) {} fn main() {}",
+        "fn f(s: &i32) -> &dyn Show { s } fn main() {}",
+        "fn main() { let a = 3; let mut d: &dyn Show = &a; d = &a; }",
+        "impl Show for u8 { fn show(&self) -> u8 { *self } fn set(&mut self, v: i32) {} } fn main() {}",
+    ]
+    .map(|s| format!("{}{}", show, s));
+    file_validate(
+        &inputs.iter().map(|s| s.as_str()).collect::<Vec<_>>(),
+        &[
+            Ok(()),
+            Ok(()),
+            Err("the trait bound `Bool: Show` is not satisfied".into()),
+            Err("cannot borrow data in a `&` reference as mutable: `set` takes `&mut self`".into()),
+            Err("no method named `print` found for `&dyn Show`".into()),
+            Err("the trait `Clone` cannot be made into an object: method `clone` references the `Self` type".into()),
+            Err("the trait `Add` cannot be made into an object: method `add` has no `&self` receiver".into()),
+            Err("returning `&dyn Show` is not supported".into()),
+            Err("assigning to `&dyn Show` is not supported".into()),
+            Err("method `show` has an incompatible type for trait `Show`: expected `i32`, found `u8`".into()),
+        ],
+    );
+}
+
+#[test]
+fn method_receiver_test() {
+    let counter = r#"
+    trait Counter { fn get(&self) -> i32; fn bump(&mut self); fn take(self) -> i32; }
+    impl Counter for i32 { fn get(&self) -> i32 { *self } fn bump(&mut self) {} fn take(self) -> i32 { self } }
+    "#;
+    let inputs = [
+        "fn main() { let mut n: i32 = 1; n.bump(); let a = n.get() + n.take(); }",
+        "fn main() { let mut n: i32 = 1; let r = &mut n; r.bump(); let a = r.get() + r.take(); }",
+        "fn main() { let n: i32 = 1; let r = &n; let a = r.get() + r.take() + (&n).take(); }",
+        "fn main() { let n: i32 = 1; n.bump(); }",
+        "fn main() { let mut n: i32 = 1; let r = &n; r.bump(); }",
+        "fn main() { let n: i32 = 1; let r = &mut n; }",
+    ]
+    .map(|s| format!("{}{}", counter, s));
+    let cannot_borrow = |msg: &str| -> RccError {
+        CompileError::new(ErrorKind::Type, msg).code("E0596").into()
+    };
+    file_validate(
+        &inputs.iter().map(|s| s.as_str()).collect::<Vec<_>>(),
+        &[
+            Ok(()),
+            Ok(()),
+            Ok(()),
+            Err(cannot_borrow("cannot borrow `n` as mutable, as it is not declared as mutable")),
+            Err(cannot_borrow("cannot borrow data in a `&` reference as mutable: `bump` takes `&mut self`")),
+            Err("cannot borrow `n` as mutable, as it is not declared as mutable".into()),
+        ],
+    );
+}
+
+#[test]
+fn inherent_impl_test() {
+    let point = "struct Point { x: i32, y: i32 }";
+    let inputs = [
+        "impl Point { fn sum(self) -> i32 { self.x + self.y } fn twice(p: Point) -> i32 { p.sum() * 2 } } fn main() { let p = Point { x: 1, y: 2 }; let a = p.sum() + Point::twice(p); }",
+        "fn main() { let p = Point { x: 1, y: 2 }; let a = p.sum(); } impl Point { fn sum(self) -> i32 { self.x } }",
+        "impl Point { fn f(self) {} } impl Point { fn f(self) {} } fn main() {}",
+        "impl i32 { fn f(self) {} } fn main() {}",
+        "impl String { fn f(self) {} } fn main() {}",
+        "impl Point { fn f(self) {} } fn main() { Point::g(); }",
+        "impl Point { fn new() -> i32 { 0 } } fn main() { let p = Point { x: 1, y: 2 }; p.new(); }",
+        "impl Point { fn get(&self) -> i32 { self.x } } fn main() { let p = Point { x: 1, y: 2 }; p.get(); }",
+    ]
+    .map(|s| format!("{}{}", point, s));
+    let error = |kind: ErrorKind, code: &'static str, msg: &str| -> RccError {
+        CompileError::new(kind, msg).code(code).into()
+    };
+    file_validate(
+        &inputs.iter().map(|s| s.as_str()).collect::<Vec<_>>(),
+        &[
+            Ok(()),
+            Ok(()),
+            Err(error(ErrorKind::Resolve, "E0592", "duplicate definitions with name `f`")),
+            Err(error(ErrorKind::Type, "E0390", "cannot define inherent `impl` for primitive types")),
+            Err(error(
+                ErrorKind::Type,
+                "E0116",
+                "cannot define inherent `impl` for a type outside of the crate where the type is defined",
+            )),
+            Err(error(
+                ErrorKind::Resolve,
+                "E0599",
+                "no function or associated item named `g` found for struct `Point`",
+            )),
+            Err("no method named `new` found for `Struct { vis: Priv, name: \"Point\" }`".into()),
+            Ok(()),
+        ],
+    );
+}
+
+#[test]
+fn assoc_const_test() {
+    let point = "struct Point { x: i32, y: i32 }";
+    let inputs = [
+        "impl Point { const N: i32 = Self::M + 1; const M: i32 = 2; fn n(self) -> i32 { Self::N } } fn main() { let a: i32 = Point::N * Point::M; }",
+        "impl Point { const N: i32 = 1; fn N() {} } fn main() {}",
+        "impl Point { const N: i32 = 1; } impl Point { const N: i32 = 2; } fn main() {}",
+        "trait T {} impl T for i32 { const N: i32 = 1; } fn main() {}",
+        "impl Point { const O: Point = Point { x: 0, y: 0 }; } fn main() {}",
+        "impl Point { const N: i32 = 1; } fn main() { let a = Point::M; }",
+        "impl Point { const N: i32 = 1; } fn main() { Point::N = 2; }",
+    ]
+    .map(|s| format!("{}{}", point, s));
+    let error = |kind: ErrorKind, code: &'static str, msg: &str| -> RccError {
+        CompileError::new(kind, msg).code(code).into()
+    };
+    file_validate(
+        &inputs.iter().map(|s| s.as_str()).collect::<Vec<_>>(),
+        &[
+            Ok(()),
+            Err(error(ErrorKind::Resolve, "E0592", "duplicate definitions with name `N`")),
+            Err(error(ErrorKind::Resolve, "E0592", "duplicate definitions with name `N`")),
+            Err(error(ErrorKind::Resolve, "E0438", "const `N` is not a member of trait `T`")),
+            Ok(()),
+            Err(error(
+                ErrorKind::Resolve,
+                "E0599",
+                "no function or associated item named `M` found for struct `Point`",
+            )),
+            Err(CompileError::new(ErrorKind::Type, "cannot assign to constant `Point::N`")
+                .code("E0070")
+                .help("consider declaring a `static mut` instead")
+                .into()),
+        ],
+    );
+}
+
+#[test]
+fn type_alias_test() {
+    let error = |kind: ErrorKind, code: &'static str, msg: &str| -> RccError {
+        CompileError::new(kind, msg).code(code).into()
+    };
+    file_validate(
+        &[
+            "type P = Point; type M = i32; struct Point { x: M, y: M } fn len(p: P) -> M { p.x + p.y } fn main() { let a: M = len(P { x: 1, y: 2 }); }",
+            "type V = Vec<M>; type M = u8; fn inc(r: &mut M) { *r += 1; } fn main() { let mut a: M = 1; inc(&mut a); }",
+            "fn main() { type M = u8; let a: M = 'c'; }",
+            "type M = i32; fn f(m: M) -> i64 { m } fn main() {}",
+            "type A = Foo; fn main() {}",
+            "type A = B; type B = Vec<A>; fn main() {}",
+            "type M = i32; struct M {} fn main() {}",
+        ],
+        &[
+            Ok(()),
+            Ok(()),
+            Err(invalid_let_type("LitNum(u8)", "Char")),
+            Err("invalid return type: excepted `LitNum(i64)`, found `LitNum(i32)`".into()),
+            Err(error(ErrorKind::Resolve, "E0412", "cannot find type `Foo` in this scope")),
+            Err(error(ErrorKind::Type, "E0391", "cycle detected when expanding type alias `A`")),
+            Err(CompileError::new(ErrorKind::Resolve, "the name `M` is defined multiple times")
+                .code("E0428")
+                .note("`M` must be defined only once in this scope")
+                .into()),
+        ],
+    );
+}
+
+#[test]
+fn const_test() {
+    file_validate(
+        &[
+            "const fn size(n: usize) -> usize { n * 5 } const N: usize = size(3); const M: usize = N * 2 + 4; fn main() { let a = M + 1; }",
+            "fn f() -> i32 { 3 } const A: i32 = f(); fn main() {}",
+            "const A: i32 = { print!(\"x\"); 3 }; fn main() {}",
+            "fn main() { const A: i32 = 3; }",
+            "const A: i32 = true; fn main() {}",
+            "const S: String = 3; fn main() {}",
+            "const fn f() -> i32 { g() } fn g() -> i32 { 3 } fn main() {}",
+        ],
+        &[
+            Ok(()),
+            Err("cannot call non-const fn `f` in constants".into()),
+            Err("cannot call non-const formatting macro in constants".into()),
+            Err("constant `A` must be declared at the top level".into()),
+            Err("mismatched types: expected LitNum(i32), found Bool".into()),
+            Err(
+                "the type of constant `S` must be a primitive type or a struct, found `String`"
+                    .into(),
+            ),
+            Err("cannot call non-const fn `g` in constant functions".into()),
+        ],
+    );
+}
+
+#[test]
+fn struct_test() {
+    let point = "struct Point { x: i32, y: i32 } struct Rect { min: Point, max: Point }";
+    let inputs = [
+        "fn main() { let p = Point { x: 1, y: 2 }; let mut r = Rect { min: p, max: Point { x: 3, ..p } }; r.max.y = r.min.x + 1; }",
+        "fn main() { let p = Point { x: 1 }; }",
+        "fn main() { let p = Point { x: 1, y: 2, x: 3 }; }",
+        "fn main() { let p = Point { x: 1, z: 2 }; }",
+        "fn main() { let p = Point { x: true, y: 2 }; }",
+        "fn main() { let r = Rect { min: Point { x: 1, y: 2 }, ..3 }; }",
+        "fn main() { let r = Rect { ..Point { x: 1, y: 2 } }; }",
+        "fn main() { let p = Point { x: 1, y: 2 }; let z = p.z; }",
+        "fn main() { let r = Rect { min: Point { x: 1, y: 2 }, max: Point { x: 1, y: 2 } }; r.min.x = 3; }",
+        "fn main() { let a = Line { x: 3 }; }",
+        "struct S { v: Vec<i32> } fn main() {}",
+        "fn f() -> Point { Point { x: 1, y: 2 } } fn main() {}",
+        "fn main() { let p = Point { x: 1, y: 2 }; let r = &p; }",
+        "fn f(r: &mut Point) -> i32 { r.x = 1; (&r).y } fn main() {}",
+        "fn f(r: &Point) { r.x = 1; } fn main() {}",
+    ]
+    .map(|s| format!("{}{}", point, s));
+    file_validate(
+        &inputs.iter().map(|s| s.as_str()).collect::<Vec<_>>(),
+        &[
+            Ok(()),
+            Err("missing fields `y` in initializer of `Point`".into()),
+            Err("field `x` specified more than once".into()),
+            Err("struct `Point` has no field named `z`".into()),
+            Err("mismatched types: expected LitNum(i32), found Bool".into()),
+            Err("mismatched types: expected Struct { vis: Priv, name: \"Rect\" }, found LitNum(#i)".into()),
+            Err("mismatched types: expected Struct { vis: Priv, name: \"Rect\" }, found Struct { vis: Priv, name: \"Point\" }".into()),
+            Err("no field `z` on type `Point`".into()),
+            Err(immutable_place("cannot assign to a field of an immutable value")),
+            Err("cannot find struct `Line`".into()),
+            Err("field `v` of type `Vec<i32>` is not supported".into()),
+            Ok(()),
+            Ok(()),
+            Ok(()),
+            Err(immutable_place("cannot assign to a field of an immutable value")),
+        ],
+    );
+}
+
+#[test]
+fn recursive_struct_test() {
+    file_validate(
+        &[
+            "struct List { next: List } fn main() {}",
+            "struct A { b: B } struct B { x: i32, a: A } fn main() {}",
+            "struct A { b: B } struct B { c: C } struct C { b: B } fn main() {}",
+            "fn main() { struct Node { x: i32, child: Node } }",
+            "struct A { b: B, c: B } struct B { x: i32 } fn main() {}",
+        ],
+        &[
+            Err("recursive type `List` has infinite size, insert some indirection (e.g., a `&`) to make `List` representable".into()),
+            Err("recursive type `A` has infinite size, insert some indirection (e.g., a `&`) to make `A` representable".into()),
+            Err("recursive type `B` has infinite size, insert some indirection (e.g., a `&`) to make `B` representable".into()),
+            Err("recursive type `Node` has infinite size, insert some indirection (e.g., a `&`) to make `Node` representable".into()),
+            Ok(()),
+        ],
+    );
+}
+
+#[test]
+fn array_test() {
+    file_validate(
+        &[
+            "const N: usize = 2; fn sum(a: [i32; N * 2]) -> i32 { a[0] + a[3] } fn main() { let a = [1; 4]; let b: [i32; 4] = a; let s = sum(b); }",
+            "fn main() { let n = 3; let a = [0; n]; }",
+            "const N: i32 = 2; fn main() { let a = [0u8; N]; }",
+            "const A: usize = B; const B: usize = A; fn main() { let a = [0; A]; }",
+            "fn main() { let a = [0; 2 - 3]; }",
+            "fn main() { let a = [1, true]; }",
+            "fn main() { let a = [1, 2]; let b = a[2]; }",
+            "fn main() { let a: [i32; 3] = [1, 2]; }",
+            "struct S { a: [u8; 2] } fn main() {}",
+            "const fn size(n: usize) -> usize { n * 2 } const N: usize = size(3); struct P {} impl P { const N: usize = N - 5; } fn f(a: [i32; size(1)]) -> [i32; P::N] { [a[1]; P::N] } fn main() { let a: [i32; N] = [0; 6]; }",
+            "const fn size(n: usize) -> usize { n - 4 } fn main() { let a = [0; size(2)]; }",
+            "fn f() -> i32 { 3 } fn main() { let a = [0; f()]; }",
+            "fn main() { let mut v: Vec<[u8; 2]> = Vec::new(); }",
+            "fn main() { let mut v = Vec::new(); v.push([1, 2]); }",
+        ],
+        &[
+            Ok(()),
+            Err(CompileError::new(
+                ErrorKind::Resolve,
+                "attempt to use a non-constant value in a constant",
+            )
+            .code("E0435")
+            .note("`n` is not a constant of the file")
+            .into()),
+            Err("mismatched types: expected LitNum(usize), found LitNum(i32)".into()),
+            Err("evaluation of constant `A` failed: cycle detected when evaluating constant `A_1`"
+                .into()),
+            Err(CompileError::new(ErrorKind::Other, "this arithmetic operation will overflow")
+                .note("attempt to subtract with overflow: `2usize - 3usize`")
+                .into()),
+            Err(CompileError::new(
+                ErrorKind::Type,
+                "mismatched types in array: expected `LitNum(#i)`, found `Bool`",
+            )
+            .code("E0308")
+            .into()),
+            Err(CompileError::new(ErrorKind::Type, "this operation will panic at runtime")
+                .note("index out of bounds: the length is 2 but the index is 2")
+                .into()),
+            Err(invalid_let_type("Array(LitNum(i32), 3)", "Array(LitNum(#i), 2)")),
+            Err("array fields of struct `S` are not supported yet".into()),
+            Ok(()),
+            Err(CompileError::new(ErrorKind::Type, "evaluation of array length failed")
+                .note("this arithmetic operation will overflow")
+                .into()),
+            Err("cannot call non-const fn `f` in constants".into()),
+            Err("`Vec<Array(LitNum(u8), 2)>` is not supported".into()),
+            Err("`Vec<Array(LitNum(#i), 2)>` is not supported".into()),
+        ],
+    );
+}
+
+#[test]
+fn slice_test() {
+    file_validate(
+        &[
+            "fn sum(s: &[u8]) -> u8 { s[0] + s[s.len() - 1] } fn main() { let a = [1, 2]; let mut s: &[u8] = &a; s = &[3u8; 4]; let n = sum(s) + sum(&a); }",
+            "fn f(s: [i32]) {} fn main() {}",
+            "fn main() { let a = [1i32, 2]; let s: &[u8] = &a; }",
+            "fn f(s: &mut [i32]) {} fn main() { let a = [1, 2]; f(&a); }",
+            "fn f(s: &[i32]) -> &[i32] { s } fn main() {}",
+            "struct S { s: &str } fn main() {}",
+            "fn main() { let a = [1, 2]; let s: &[i32] = &a; let b = *s; }",
+            "fn main() { let s = \"a\"; let v = [s, s]; }",
+        ],
+        &[
+            Ok(()),
+            Err(CompileError::new(
+                ErrorKind::Type,
+                "the size for values of type `[LitNum(i32)]` cannot be known at compilation time",
+            )
+            .code("E0277")
+            .into()),
+            Err(invalid_let_type(
+                "Ptr { kind: Ref, type_info: Slice(LitNum(u8)) }",
+                "Ptr { kind: Ref, type_info: Array(LitNum(i32), 2) }",
+            )),
+            Err(wrong_arg_type(
+                "mismatched types in argument 1 of function `f`: \
+                 expected `&mut [i32]`, found `&[{integer}; 2]`",
+            )),
+            Err("returning `&[i32]` is not supported".into()),
+            Err("field `s` of type `TypePtr { ptr_kind: Ref, type_anno: str }` is not supported"
+                .into()),
+            Err(CompileError::new(
+                ErrorKind::Type,
+                "the size for values of type `[LitNum(i32)]` cannot be known at compilation time",
+            )
+            .code("E0277")
+            .into()),
+            Err("arrays of `&str` are not supported".into()),
+        ],
+    );
+}
+
+#[test]
+fn str_bytes_test() {
+    file_validate(
+        &[
+            "fn f(s: &str) -> u8 { let b = s.as_bytes(); let n = b.len() + s.as_bytes().len(); for c in s.bytes() { let d: u8 = c; } b[0] }",
+            "fn main() { let s = \"ab\"; let b = s[0]; }",
+            "fn main() { let s = \"ab\"; let b = s.bytes(); }",
+            "fn main() { let a = [1, 2]; for x in a { } }",
+            "fn main() { let a = [1, 2]; for x in a.bytes() { } }",
+            "fn main() { for x in \"ab\".bytes() { break x; } }",
+            "fn main() { for x in \"ab\".bytes() { let y: i32 = x; } }",
+        ],
+        &[
+            Ok(()),
+            Err(CompileError::new(ErrorKind::Type, "the type `str` cannot be indexed by `usize`")
+                .code("E0277")
+                .help("the bytes of a string are indexed by `s.as_bytes()[i]`")
+                .into()),
+            Err("`bytes()` is only supported as the iterator of a `for` loop".into()),
+            Err(not_iterator("Array(LitNum(#i), 2)")),
+            Err("no method named `bytes` found for `Array(LitNum(#i), 2)`".into()),
+            Err(CompileError::new(ErrorKind::Type, "`break` with value from a `for` loop")
+                .code("E0571")
+                .help("only `loop` can return values, use `break` on its own here")
+                .into()),
+            Err(invalid_let_type("LitNum(i32)", "LitNum(u8)")),
+        ],
+    );
+}
+
+fn not_iterator(type_info: &str) -> RccError {
+    CompileError::new(ErrorKind::Type, format!("`{}` is not an iterator", type_info))
+        .code("E0277")
+        .note("`for` loops only support ranges and `s.bytes()` of `s: &str`")
+        .into()
+}
+
+#[test]
+fn range_test() {
+    file_validate(
+        &[
+            "fn f(n: u64) -> u64 { let mut s = 0; for i in 0..n { s += i; } for i in 1..=3 { s += i; } s }",
+            "fn f(a: [i32; 3]) -> i32 { let mut s = 0; for i in 0..3 { s += a[i]; } s }",
+            "fn f() -> i32 { let mut r = 0..3; r = 1..=2; let mut s = 0; for i in r { s += i; } s }",
+            "fn f() { let r = 0..3; let s: u8 = 0; for i in r { let j: u8 = i; } }",
+            "fn f(n: u8) { for i in 0i32..n {} }",
+            "fn f() { for x in 0.5..2.0 {} }",
+            "fn f() { for x in ..2 {} }",
+            "fn f() { let x = 2; for i in x {} }",
+        ],
+        &[
+            Ok(()),
+            Ok(()),
+            Err("invalid type `Range { type_info: LitNum(i32), inclusive: false }` for `=`".into()),
+            Err(invalid_let_type("LitNum(u8)", "LitNum(i32)")),
+            Err(mismatched_num_types("..", "i32", "u8")),
+            Err("ranges of `LitNum(#f)` are not supported".into()),
+            Err("ranges without a start or an end are not supported".into()),
+            Err(not_iterator("LitNum(#i)")),
+        ],
+    );
+}
+
+#[test]
+fn param_pattern_test() {
+    let bound_twice = |ident: &str| -> RccError {
+        let msg = format!("identifier `{}` is bound more than once in this parameter list", ident);
+        CompileError::new(ErrorKind::Resolve, msg).code("E0415").into()
+    };
+    let tuple_mismatch = |expected: &str, len: usize| -> RccError {
+        let msg = format!(
+            "mismatched types: expected `{}`, found a tuple with {} elements",
+            expected, len
+        );
+        CompileError::new(ErrorKind::Type, msg).code("E0308").into()
+    };
+    file_validate(
+        &[
+            "fn f((a, (b, _)): (i32, (u8, bool)), _: i32) -> i32 { a } fn g() -> i32 { f((1, (2, true)), 3) }",
+            "fn f(t: (u8, u8)) {} fn g(t: (u8, u8)) { f(t); f(((1, 2))); }",
+            "fn f((a, b): (i32, i32)) {} fn g() { f((1, true)); }",
+            "fn f((a, b): i32) {}",
+            "fn f((a, b, c): (i32, i32)) {}",
+            "fn f((a, a): (i32, i32)) {}",
+            "fn f(a: i32, (b, a): (i32, i32)) {}",
+            "fn f(_: i32) -> i32 { _ }",
+            "fn f(t: (i32, i32)) { let u = t; }",
+            "fn f() { let (a, b) = 1; }",
+            "fn f() -> (i32, i32) { }",
+            "fn f(t: (i32, &[i32])) {}",
+            "fn f() { let _ = 1; let _ = true; for _ in 0..2 {} }",
+        ],
+        &[
+            Ok(()),
+            Ok(()),
+            Err(wrong_arg_type(
+                "mismatched types in argument 1 of function `f`: \
+                 expected `(i32, i32)`, found `({integer}, bool)`",
+            )),
+            Err(tuple_mismatch("LitNum(i32)", 2)),
+            Err(tuple_mismatch("Tuple([LitNum(i32), LitNum(i32)])", 3)),
+            Err(bound_twice("a")),
+            Err(bound_twice("a")),
+            Err("`_` can not be used in expressions".into()),
+            Err("tuples are only supported as params and arguments".into()),
+            Err("pattern `(a, b)` is only supported in the params of functions".into()),
+            Err("returning `(i32, i32)` is not supported".into()),
+            Err("tuples of `Ptr { kind: Ref, type_info: Slice(LitNum(i32)) }` \
+                 are not supported"
+                .into()),
+            Ok(()),
+        ],
+    );
+}
+
+#[test]
+fn shadowing_test() {
+    file_validate(
+        &[
+            "fn f() -> i32 { let a = 1; let a = a + 1; let a = a * 2; a }",
+            "fn f(a: i32) -> bool { let a = a > 0; a }",
+            "fn f() -> i32 { let a = 1; let b = { let a = a == 1; a }; if b { a } else { 0 } }",
+            "fn f() -> i32 { let a = true; { let b: bool = a; let a = 1; } a }",
+            "fn f() -> i32 { let value = 1; { let b = value; } let value = true; 0 }",
+            "fn f() -> i32 { let b = value; let value = 1; b }",
+        ],
+        &[
+            Ok(()),
+            Ok(()),
+            Ok(()),
+            Err("invalid return type: excepted `LitNum(i32)`, found `Bool`".into()),
+            Ok(()),
+            Err(ident_not_found("value")),
+        ],
+    );
+}
+
+#[test]
+fn unsafe_test() {
+    let requires_unsafe = |operation: &str, note: &str| -> RccError {
+        let msg = format!("{} is unsafe and requires unsafe function or block", operation);
+        CompileError::new(ErrorKind::Type, msg).code("E0133").note(note).into()
+    };
+    let deref = || {
+        requires_unsafe(
+            "dereference of raw pointer",
+            "raw pointers may be null, dangling or unaligned, which is undefined behavior \
+             when they are dereferenced",
+        )
+    };
+    file_validate(
+        &[
+            "fn f(p: *const i32) -> i32 { *p }",
+            "fn f(p: *mut i32) { *p = 1; }",
+            "fn f(p: *const i32) -> i32 { unsafe { *p } }",
+            "unsafe fn f(p: *mut i32) { *p = *p + 1; }",
+            "fn f(p: &i32, b: Box<i32>) -> i32 { *p + *b }",
+            "unsafe fn g() {} fn f() { g(); }",
+            "unsafe fn g() {} fn f() { unsafe { g() } let g = 1; }",
+            "fn f() { asm!(\"nop\"); }",
+            "fn f() { unsafe { { asm!(\"nop\"); } } }",
+            "unsafe fn f() { fn g(p: *const u8) -> u8 { *p } }",
+            "fn f(p: *const i32) -> i32 { unsafe {} *p }",
+        ],
+        &[
+            Err(deref()),
+            Err(deref()),
+            Ok(()),
+            Ok(()),
+            Ok(()),
+            Err(requires_unsafe(
+                "call to unsafe function `g`",
+                "the caller must uphold the requirements of the function",
+            )),
+            Ok(()),
+            Err(requires_unsafe(
+                "use of inline assembly",
+                "inline assembly is entirely unchecked and can cause undefined behavior",
+            )),
+            Ok(()),
+            Err(deref()),
+            Err(deref()),
+        ],
+    );
+}
+
+#[test]
+fn cast_test() {
+    let invalid = |msg: &str| -> RccError {
+        CompileError::new(ErrorKind::Type, msg).code("E0606").into()
+    };
+    file_validate(
+        &[
+            "fn f() -> *mut u32 { 0x1000_0000 as *mut u32 }",
+            "fn f(p: *mut u8) -> usize { let a: u8 = 1 as u8; p as *const i32 as usize }",
+            "fn f() { let mut x = 1; let p: *mut u8 = &mut x as *mut u8; let y: u8 = x; }",
+            "fn f(x: &i32) { let p = x as *mut i32; }",
+            "fn f() { let a = true as i32; }",
+            "fn f(a: i32) { let b = a as u8; }",
+            "fn f(s: &str) { let p = s as *const str; }",
+        ],
+        &[
+            Ok(()),
+            Ok(()),
+            Ok(()),
+            Err(invalid("casting `&i32` as `*mut i32` is invalid")),
+            Err(invalid("casting `bool` as `i32` is invalid")),
+            Ok(()),
+            Err("casting `&str` as `*const str` is not supported yet".into()),
+        ],
+    );
+}
+
+#[test]
+fn raw_ptr_method_test() {
+    let add_requires_unsafe = CompileError::new(
+        ErrorKind::Type,
+        "call to unsafe function `add` is unsafe and requires unsafe function or block",
+    )
+    .code("E0133")
+    .note("the pointer must stay in bounds of the object it points to");
+    file_validate(
+        &[
+            "fn f(a: &mut [i32]) -> i32 { let p = a.as_mut_ptr(); unsafe { *p.add(1) = 2; *p } }",
+            "fn f(p: *const u8) -> *const u8 { p.wrapping_add(2).wrapping_sub(1) }",
+            "fn f(p: *const u8) -> *const u8 { p.add(2) }",
+            "fn f(a: &[i32]) { let p = a.as_mut_ptr(); }",
+            "unsafe fn f(p: *const u8) { let q = p.add(1i32); }",
+        ],
+        &[
+            Ok(()),
+            Ok(()),
+            Err(add_requires_unsafe.into()),
+            Err("cannot borrow `a` as mutable, \
+                 as it is not declared as mutable"
+                .into()),
+            Err(CompileError::new(
+                ErrorKind::Type,
+                "mismatched types in argument 1 of method `add`: expected `usize`, \
+                 found `i32`",
+            )
+            .code("E0308")
+            .into()),
+        ],
+    );
+}
+
+#[test]
+fn volatile_test() {
+    let read_requires_unsafe = CompileError::new(
+        ErrorKind::Type,
+        "call to unsafe function `read_volatile` is unsafe and requires unsafe function or block",
+    )
+    .code("E0133")
+    .note("the pointer must be valid and properly aligned");
+    file_validate(
+        &[
+            "fn f(p: *mut u32) -> u32 { unsafe { write_volatile(p, 1); core::ptr::read_volatile(p) } }",
+            "unsafe fn f(p: *const bool) { let b: bool = std::ptr::read_volatile(p); }",
+            "fn read_volatile(p: *const u8) -> u8 { 0 } fn f(p: *const u8) -> u8 { read_volatile(p) }",
+            "fn f(p: *const u8) -> u8 { read_volatile(p) }",
+            "unsafe fn f(p: *const u8) { write_volatile(p, 1); }",
+            "unsafe fn f(p: *mut u8) { write_volatile(p, 'a'); }",
+            "unsafe fn f(p: *mut u8) { write_volatile(p); }",
+        ],
+        &[
+            Ok(()),
+            Ok(()),
+            Ok(()),
+            Err(read_requires_unsafe.into()),
+            Err(CompileError::new(
+                ErrorKind::Type,
+                "mismatched types in argument 1 of function `write_volatile`: expected `*mut T`, \
+                 found `*const u8`",
+            )
+            .code("E0308")
+            .into()),
+            Err(CompileError::new(
+                ErrorKind::Type,
+                "mismatched types in argument 2 of function `write_volatile`: expected \
+                 `u8`, found `char`",
+            )
+            .code("E0308")
+            .into()),
+            Err(CompileError::new(
+                ErrorKind::Type,
+                "function `write_volatile` takes 2 arguments but 1 argument was supplied",
+            )
+            .code("E0061")
+            .into()),
+        ],
+    );
+}
+
+#[test]
+fn cfg_test() {
+    let mut cfg_options = CfgOptions::new();
+    cfg_options.insert_flag("feature=\"big\"").unwrap();
+    cfg_options.insert_flag("debug").unwrap();
+    assert_eq!(
+        Err("invalid `--cfg` option big\", expected `name` or `name=\"value\"`".into()),
+        cfg_options.insert_flag("big\"")
+    );
+    assert_eq!(
+        Err("`--cfg target` is not allowed, it is the target platform".into()),
+        cfg_options.insert_flag("target=\"riscv64\"")
+    );
+    let inputs = [
+        r#"
+        #[cfg(feature = "big")]
+        fn word() -> i32 { 8 }
+        #[cfg(not(feature = "big"))]
+        fn word() -> i32 { 4 }
+        fn main() -> i32 { word() }
+        "#,
+        r#"
+        fn main() -> i32 {
+            #[cfg(all(debug, any(test, feature = "big")))]
+            fn n() -> i32 { 1 }
+            #[cfg(test)]
+            fn n() -> i32 { undefined }
+            n()
+        }
+        "#,
+        "#[cfg(debug)] fn f() {} #[cfg(all())] fn f() {}",
+        "#[cfg(test)] fn f() {} fn main() { f(); }",
+        "#[cfg(not(debug, test))] fn f() {}",
+        r#"#[cfg(any(test, all = "x"))] fn f() {}"#,
+    ];
+    let expecteds: [Result<(), RccError>; 6] = [
+        Ok(()),
+        Ok(()),
+        Err(CompileError::new(ErrorKind::Resolve, "the name `f` is defined multiple times")
+            .code("E0428")
+            .note("`f` must be defined only once in this scope")
+            .into()),
+        Err(ident_not_found("f")),
+        Err("`not` takes exactly one predicate".into()),
+        Err("invalid predicate `all = \"x\"`".into()),
+    ];
+    for (input, expected) in inputs.iter().zip(expecteds) {
+        let mut sym_resolver = SymbolResolver::new();
+        sym_resolver.set_cfg_options(cfg_options.clone());
+        let mut ast_file = get_ast_file(input).unwrap();
+        assert_eq!(expected, sym_resolver.visit_file(&mut ast_file), "{}", input);
+    }
+}
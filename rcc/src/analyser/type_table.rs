@@ -0,0 +1,242 @@
+//! Types of the expr nodes, kept out of the AST.
+//!
+//! Each distinct `TypeInfo` is interned once as a `TypeId`. A `TypeSlot` holds the type of
+//! a node or a variable, and may be shared by several of them: `let a = b;` makes `a` use
+//! the slot of `b`, so when the type of `b` is determined later, `a` sees it too.
+//!
+//! Slots are also the type variables of the inference. `unify` merges two slots into one
+//! set of a union-find, so a constraint found later on any of them applies to all:
+//! ```
+//! let mut v = 0;      // v: {integer}
+//! v = returns_u64();  // v and `0`: u64
+//! ```
+//!
+//! The vtables of the coercions from `&T` to `&dyn Trait` and the fields of the structs
+//! are kept here too.
+use crate::analyser::sym_resolver::TypeInfo;
+use crate::ast::NodeId;
+use crate::rcc::RccError;
+use indexmap::IndexMap;
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct TypeId(u32);
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct TypeSlot(u32);
+
+#[derive(Debug, Clone)]
+pub struct TypeTable {
+    types: Vec<TypeInfo>,
+    ids: HashMap<TypeInfo, TypeId>,
+    slots: Vec<TypeId>,
+    /// union-find of the slots, a root is its own parent
+    parents: Vec<u32>,
+    node_slots: HashMap<NodeId, TypeSlot>,
+    /// (trait, type) -> (symbol of the vtable, symbols of the methods in the order of the trait)
+    vtables: IndexMap<(String, TypeInfo), (String, Vec<String>)>,
+    /// name of the struct -> (name, type) of the fields in the order of declaration
+    structs: HashMap<String, Vec<(String, TypeInfo)>>,
+}
+
+impl Default for TypeTable {
+    fn default() -> TypeTable {
+        TypeTable::new()
+    }
+}
+
+impl TypeTable {
+    pub fn new() -> TypeTable {
+        let mut table = TypeTable {
+            types: vec![],
+            ids: HashMap::new(),
+            slots: vec![],
+            parents: vec![],
+            node_slots: HashMap::new(),
+            vtables: IndexMap::new(),
+            structs: HashMap::new(),
+        };
+        table.intern(TypeInfo::Unknown);
+        table
+    }
+
+    pub fn intern(&mut self, type_info: TypeInfo) -> TypeId {
+        if let Some(id) = self.ids.get(&type_info) {
+            return *id;
+        }
+        let id = TypeId(self.types.len() as u32);
+        self.types.push(type_info.clone());
+        self.ids.insert(type_info, id);
+        id
+    }
+
+    pub fn type_info(&self, type_id: TypeId) -> &TypeInfo {
+        &self.types[type_id.0 as usize]
+    }
+
+    pub fn new_slot(&mut self, type_info: TypeInfo) -> TypeSlot {
+        let type_id = self.intern(type_info);
+        self.slots.push(type_id);
+        self.parents.push(self.parents.len() as u32);
+        TypeSlot(self.slots.len() as u32 - 1)
+    }
+
+    /// Representative of the slots unified with `slot`
+    fn find(&self, slot: TypeSlot) -> TypeSlot {
+        let mut idx = slot.0;
+        while self.parents[idx as usize] != idx {
+            idx = self.parents[idx as usize];
+        }
+        TypeSlot(idx)
+    }
+
+    pub fn type_id(&self, slot: TypeSlot) -> TypeId {
+        self.slots[self.find(slot).0 as usize]
+    }
+
+    pub fn get(&self, slot: TypeSlot) -> &TypeInfo {
+        self.type_info(self.type_id(slot))
+    }
+
+    /// Change the type of `slot` and so of all the nodes and variables sharing it.
+    pub fn set(&mut self, slot: TypeSlot, type_info: TypeInfo) {
+        let root = self.find(slot);
+        self.slots[root.0 as usize] = self.intern(type_info);
+    }
+
+    /// Make `expected` and `found` the same type from now on. `Unknown`, `{integer}` and
+    /// `{float}` are refined by the other side. `!` coerces into any type, so it is never
+    /// merged with the other slot.
+    pub fn unify(&mut self, expected: TypeSlot, found: TypeSlot) -> Result<(), RccError> {
+        let (root_a, root_b) = (self.find(expected), self.find(found));
+        if root_a == root_b {
+            return Ok(());
+        }
+        let (a, b) = (self.get(root_a), self.get(root_b));
+        if a.is_never() || b.is_never() {
+            return Ok(());
+        }
+        let type_info = match meet(a, b) {
+            Some(t) => t,
+            None => {
+                return Err(format!("mismatched types: expected `{:?}`, found `{:?}`", a, b).into())
+            }
+        };
+        self.parents[root_b.0 as usize] = root_a.0;
+        self.set(root_a, type_info);
+        Ok(())
+    }
+
+    /// Slot of `node_id`, a new slot of `default` is created if the node has none yet.
+    pub fn node_slot(&mut self, node_id: NodeId, default: TypeInfo) -> TypeSlot {
+        if let Some(slot) = self.node_slots.get(&node_id) {
+            return *slot;
+        }
+        let slot = self.new_slot(default);
+        self.node_slots.insert(node_id, slot);
+        slot
+    }
+
+    /// Let `node_id` share `slot` instead of its own one.
+    pub fn bind(&mut self, node_id: NodeId, slot: TypeSlot) {
+        self.node_slots.insert(node_id, slot);
+    }
+
+    pub fn node_type(&self, node_id: NodeId) -> Option<&TypeInfo> {
+        self.node_slots.get(&node_id).map(|slot| self.get(*slot))
+    }
+
+    /// Ids of the nodes which have a type, in the order of the source
+    pub fn node_ids(&self) -> Vec<NodeId> {
+        let mut node_ids: Vec<NodeId> = self.node_slots.keys().copied().collect();
+        node_ids.sort_by_key(|node_id| node_id.0);
+        node_ids
+    }
+
+    pub fn add_vtable(
+        &mut self,
+        trait_name: &str,
+        type_info: TypeInfo,
+        symbol: String,
+        methods: Vec<String>,
+    ) {
+        self.vtables
+            .entry((trait_name.to_string(), type_info))
+            .or_insert((symbol, methods));
+    }
+
+    /// Symbol of the vtable of `impl trait_name for type_info`
+    pub fn vtable(&self, trait_name: &str, type_info: &TypeInfo) -> Option<&str> {
+        self.vtables
+            .get(&(trait_name.to_string(), type_info.clone()))
+            .map(|(symbol, _)| symbol.as_str())
+    }
+
+    /// (symbol of the vtable, symbols of the methods), in the order they are added
+    pub fn vtables(&self) -> impl Iterator<Item = &(String, Vec<String>)> {
+        self.vtables.values()
+    }
+
+    pub fn add_struct(&mut self, name: &str, fields: Vec<(String, TypeInfo)>) {
+        self.structs.insert(name.to_string(), fields);
+    }
+
+    /// (name, type) of the fields of the struct `name`
+    pub fn struct_fields(&self, name: &str) -> &[(String, TypeInfo)] {
+        self.structs.get(name).map_or(&[], |fields| fields.as_slice())
+    }
+
+    /// Whether a value of `type_info` holds a struct `name` inline, through any depth of fields
+    pub fn contains_struct(&self, type_info: &TypeInfo, name: &str) -> bool {
+        let mut visited = HashSet::new();
+        self.contains_struct_rec(type_info, name, &mut visited)
+    }
+
+    fn contains_struct_rec<'a>(
+        &'a self,
+        type_info: &'a TypeInfo,
+        name: &str,
+        visited: &mut HashSet<&'a str>,
+    ) -> bool {
+        match type_info {
+            TypeInfo::Struct { name: n, .. } if n == name => true,
+            TypeInfo::Struct { name: n, .. } if visited.insert(n.as_str()) => self
+                .struct_fields(n)
+                .iter()
+                .any(|(_, field)| self.contains_struct_rec(field, name, visited)),
+            _ => false,
+        }
+    }
+}
+
+/// The most specific type which both `a` and `b` can be, `None` if they conflict
+fn meet(a: &TypeInfo, b: &TypeInfo) -> Option<TypeInfo> {
+    match (a, b) {
+        (TypeInfo::Vec(a), TypeInfo::Vec(b)) => Some(TypeInfo::Vec(Box::new(meet(a, b)?))),
+        (TypeInfo::Slice(a), TypeInfo::Slice(b)) => Some(TypeInfo::Slice(Box::new(meet(a, b)?))),
+        (TypeInfo::Array(a, len_a), TypeInfo::Array(b, len_b)) if len_a == len_b => {
+            Some(TypeInfo::Array(Box::new(meet(a, b)?), *len_a))
+        }
+        (TypeInfo::Tuple(a), TypeInfo::Tuple(b)) if a.len() == b.len() => Some(TypeInfo::Tuple(
+            a.iter().zip(b).map(|(a, b)| meet(a, b)).collect::<Option<_>>()?,
+        )),
+        (
+            TypeInfo::Ptr {
+                kind: kind_a,
+                type_info: a,
+            },
+            TypeInfo::Ptr {
+                kind: kind_b,
+                type_info: b,
+            },
+        ) if kind_a == kind_b => Some(TypeInfo::Ptr {
+            kind: *kind_a,
+            type_info: Box::new(meet(a, b)?),
+        }),
+        _ => match a.partial_cmp(b)? {
+            Ordering::Greater => Some(b.clone()),
+            Ordering::Equal | Ordering::Less => Some(a.clone()),
+        },
+    }
+}
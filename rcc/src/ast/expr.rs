@@ -1,36 +1,40 @@
-use crate::analyser::scope::Scope;
+use crate::analyser::scope::ScopeID;
 use crate::analyser::sym_resolver::TypeInfo;
+use crate::analyser::type_table::{TypeSlot, TypeTable};
 use crate::ast::expr::Expr::Path;
+use crate::ast::pattern::Pattern;
 use crate::ast::stmt::Stmt;
-use crate::ast::types::TypeLitNum;
-use crate::ast::{FromToken, TokenStart};
+use crate::ast::types::{PtrKind, TypeAnnotation, TypeLitNum};
+use crate::ast::{FromToken, NodeId, TokenStart};
 use crate::from_token;
 use crate::lexer::token::Token;
-use crate::rcc::RccError;
-use std::borrow::BorrowMut;
-use std::cell::RefCell;
+use crate::rcc::{CompileError, ErrorKind, RccError};
 use std::fmt;
 use std::fmt::{Debug, Formatter};
 use std::ops::Deref;
-use std::rc::Rc;
 use strenum::StrEnum;
 
 pub trait ExprVisit {
-    fn type_info(&self) -> Rc<RefCell<TypeInfo>>;
+    /// Slot of the type of the expr in `types`. Exprs whose type follows from their kind,
+    /// e.g. `"abc"` and `while`, get a new slot.
+    fn type_slot(&self, types: &mut TypeTable) -> TypeSlot;
+
+    fn type_info(&self, types: &TypeTable) -> TypeInfo;
 
     /// mutable place expr, immutable place expr or value expr
     fn kind(&self) -> ExprKind;
 
-    fn is_callable(&self) -> bool {
-        let type_info = self.type_info();
-        let t = type_info.deref().borrow();
-        matches!(t.deref(), &TypeInfo::Fn { .. } | &TypeInfo::FnPtr(_))
+    fn is_callable(&self, types: &TypeTable) -> bool {
+        matches!(
+            self.type_info(types),
+            TypeInfo::Fn { .. } | TypeInfo::FnPtr(_)
+        )
     }
 }
 
 pub trait TypeInfoSetter {
-    fn set_type_info(&mut self, type_info: TypeInfo);
-    fn set_type_info_ref(&mut self, type_info: Rc<RefCell<TypeInfo>>);
+    fn set_type_info(&self, types: &mut TypeTable, type_info: TypeInfo);
+    fn set_type_info_ref(&self, types: &mut TypeTable, slot: TypeSlot);
 }
 
 #[derive(Debug, PartialEq, Copy, Clone)]
@@ -41,18 +45,20 @@ pub enum ExprKind {
     Unknown,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone)]
 pub enum Expr {
     Path(PathExpr),
     LitNum(LitNumExpr),
     LitBool(bool),
     LitChar(char),
     LitStr(String),
+    LitByteStr(Vec<u8>),
     Unary(UnAryExpr),
     Block(BlockExpr),
     Assign(AssignExpr),
     Range(RangeExpr),
     BinOp(BinOpExpr),
+    Cast(CastExpr),
     Grouped(GroupedExpr),
     Array(ArrayExpr),
     ArrayIndex(ArrayIndexExpr),
@@ -65,11 +71,14 @@ pub enum Expr {
     FieldAccess(FieldAccessExpr),
     While(WhileExpr),
     Loop(LoopExpr),
-    For,
+    For(ForExpr),
     If(IfExpr),
     Match,
     Return(ReturnExpr),
     Break(BreakExpr),
+    Print(PrintExpr),
+    Assert(AssertExpr),
+    Asm(AsmExpr),
 }
 
 impl Expr {
@@ -77,18 +86,18 @@ impl Expr {
         matches!(
             self,
             Self::Block(_)
-                | Self::Struct(_)
                 | Self::While(_)
                 | Self::Loop(_)
                 | Self::If(_)
                 | Self::Match
-                | Self::For
+                | Self::For(_)
         )
     }
     pub fn is_with_block_token_start(tk: &Token) -> bool {
         matches!(
             tk,
             Token::LeftCurlyBraces
+                | Token::Unsafe
                 | Token::While
                 | Token::Loop
                 | Token::For
@@ -105,31 +114,61 @@ impl From<&str> for Expr {
 }
 
 impl ExprVisit for Expr {
-    fn type_info(&self) -> Rc<RefCell<TypeInfo>> {
+    fn type_slot(&self, types: &mut TypeTable) -> TypeSlot {
+        match self {
+            Self::Path(e) => e.type_slot(types),
+            Self::LitNum(e) => e.type_slot(types),
+            Self::Unary(e) => e.type_slot(types),
+            Self::Block(e) => e.type_slot(types),
+            Self::BinOp(e) => e.type_slot(types),
+            Self::Cast(e) => e.type_slot(types),
+            Self::Grouped(e) => e.type_slot(types),
+            Self::Array(e) => e.type_slot(types),
+            Self::ArrayIndex(e) => e.type_slot(types),
+            Self::Struct(e) => e.type_slot(types),
+            Self::Call(e) => e.type_slot(types),
+            Self::FieldAccess(e) => e.type_slot(types),
+            Self::Loop(e) => e.type_slot(types),
+            Self::If(e) => e.type_slot(types),
+            Self::Range(e) => e.type_slot(types),
+            Self::Tuple(e) => e.type_slot(types),
+            _ => {
+                let type_info = self.type_info(types);
+                types.new_slot(type_info)
+            }
+        }
+    }
+
+    fn type_info(&self, types: &TypeTable) -> TypeInfo {
         match self {
-            Self::Path(e) => e.type_info(),
-            Self::LitStr(_) => Rc::new(RefCell::new(TypeInfo::ref_str())),
-            Self::LitChar(_) => Rc::new(RefCell::new(TypeInfo::Char)),
-            Self::LitBool(_) => Rc::new(RefCell::new(TypeInfo::Bool)),
-            Self::LitNum(ln) => ln.type_info(),
-            Self::Unary(e) => e.type_info(),
-            Self::Block(e) => e.type_info(),
-            Self::Assign(e) => e.type_info(),
-            // Self::Range(e) => e.ret_type(),
-            Self::BinOp(e) => e.type_info(),
-            Self::Grouped(e) => e.type_info(),
-            // Self::Array(e) => e.ret_type(),
-            // Self::ArrayIndex(e) => e.ret_type(),
-            // Self::Tuple(e) => e.ret_type(),
+            Self::Path(e) => e.type_info(types),
+            Self::LitStr(_) => TypeInfo::ref_str(),
+            Self::LitChar(_) => TypeInfo::Char,
+            Self::LitBool(_) => TypeInfo::Bool,
+            Self::LitNum(ln) => ln.type_info(types),
+            Self::Unary(e) => e.type_info(types),
+            Self::Block(e) => e.type_info(types),
+            Self::Assign(e) => e.type_info(types),
+            Self::Range(e) => e.type_info(types),
+            Self::BinOp(e) => e.type_info(types),
+            Self::Cast(e) => e.type_info(types),
+            Self::Grouped(e) => e.type_info(types),
+            Self::Array(e) => e.type_info(types),
+            Self::ArrayIndex(e) => e.type_info(types),
+            Self::Tuple(e) => e.type_info(types),
             // Self::TupleIndex(e) => e.ret_type(),
-            // Self::Struct(e) => e.ret_type(),
-            Self::Call(e) => e.type_info(),
-            // Self::FieldAccess(e) => e.ret_type(),
-            Self::While(e) => e.type_info(),
-            Self::Loop(e) => e.type_info(),
-            Self::If(e) => e.type_info(),
-            Self::Return(e) => e.type_info(),
-            Self::Break(e) => e.type_info(),
+            Self::Struct(e) => e.type_info(types),
+            Self::Call(e) => e.type_info(types),
+            Self::FieldAccess(e) => e.type_info(types),
+            Self::While(e) => e.type_info(types),
+            Self::For(e) => e.type_info(types),
+            Self::Loop(e) => e.type_info(types),
+            Self::If(e) => e.type_info(types),
+            Self::Return(e) => e.type_info(types),
+            Self::Break(e) => e.type_info(types),
+            Self::Print(e) => e.type_info(types),
+            Self::Assert(e) => e.type_info(types),
+            Self::Asm(e) => e.type_info(types),
             _ => unimplemented!("{:?}", self),
         }
     }
@@ -137,52 +176,62 @@ impl ExprVisit for Expr {
     fn kind(&self) -> ExprKind {
         match self {
             Self::Path(e) => e.kind(),
-            Self::LitStr(_) | Self::LitChar(_) | Self::LitBool(_) | Self::LitNum(_) => {
-                ExprKind::Value
-            }
+            Self::LitStr(_)
+            | Self::LitByteStr(_)
+            | Self::LitChar(_)
+            | Self::LitBool(_)
+            | Self::LitNum(_) => ExprKind::Value,
             Self::Unary(u) => u.kind(),
             Self::Block(b) => b.kind(),
             Self::Assign(a) => a.kind(),
             Self::BinOp(b) => b.kind(),
+            Self::Cast(c) => c.kind(),
+            Self::Range(r) => r.kind(),
             Self::Grouped(e) => e.kind(),
+            Self::Array(a) => a.kind(),
+            Self::ArrayIndex(a) => a.kind(),
+            Self::Tuple(t) => t.kind(),
+            Self::Struct(s) => s.kind(),
             Self::Call(c) => c.kind(),
+            Self::FieldAccess(f) => f.kind(),
             Self::While(w) => w.kind(),
+            Self::For(f) => f.kind(),
             Self::Loop(l) => l.kind(),
             Self::If(i) => i.kind(),
             Self::Return(r) => r.kind(),
             Self::Break(b) => b.kind(),
+            Self::Print(p) => p.kind(),
+            Self::Assert(a) => a.kind(),
+            Self::Asm(a) => a.kind(),
             _ => unimplemented!("{:?}", self),
         }
     }
 }
 
 impl TypeInfoSetter for Expr {
-    fn set_type_info(&mut self, type_info: TypeInfo) {
+    fn set_type_info(&self, types: &mut TypeTable, type_info: TypeInfo) {
         match self {
-            Self::Path(p) => {
-                p.type_info.borrow_mut().replace(type_info);
-            }
-            Self::LitNum(l) => {
-                l.set_type_info(type_info);
-            }
-            Self::Unary(u) => u.set_type_info(type_info),
-            Self::BinOp(b) => b.set_type_info(type_info),
+            Self::Path(p) => p.set_type_info(types, type_info),
+            Self::LitNum(l) => l.set_type_info(types, type_info),
+            Self::Unary(u) => u.set_type_info(types, type_info),
+            Self::BinOp(b) => b.set_type_info(types, type_info),
+            Self::ArrayIndex(a) => a.set_type_info(types, type_info),
+            Self::FieldAccess(f) => f.set_type_info(types, type_info),
+            Self::Grouped(e) => e.set_type_info(types, type_info),
             e => unimplemented!("set type_info on {:?}", e),
         }
     }
-    fn set_type_info_ref(&mut self, type_info: Rc<RefCell<TypeInfo>>) {
+    fn set_type_info_ref(&self, types: &mut TypeTable, slot: TypeSlot) {
         match self {
-            Self::Path(p) => p.set_type_info_ref(type_info),
-            Self::LitNum(l) => {
-                l.set_type_info_ref(type_info);
-            }
-            Self::Unary(u) => u.set_type_info_ref(type_info),
+            Self::Path(p) => p.set_type_info_ref(types, slot),
+            Self::LitNum(l) => l.set_type_info_ref(types, slot),
+            Self::Unary(u) => u.set_type_info_ref(types, slot),
             e => unimplemented!("set type_info on {:?}", e),
         }
     }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone)]
 pub enum LhsExpr {
     Path(PathExpr),
     ArrayIndex(ArrayIndexExpr),
@@ -192,93 +241,92 @@ pub enum LhsExpr {
 }
 
 impl LhsExpr {
+    /// Only place expressions can be assigned to, the others are values, e.g. `f() = 1`.
     pub fn from_expr(expr: Expr) -> Result<LhsExpr, RccError> {
-        match expr {
-            Expr::Path(p) => Ok(LhsExpr::Path(p)),
-            Expr::Unary(u) => {
-                if u.op == UnOp::Deref {
-                    Ok(LhsExpr::Deref(u.expr))
-                } else {
-                    Err("invalid lhs expr".into())
-                }
-            }
-            Expr::Grouped(e) => LhsExpr::from_expr(*e),
-            Expr::ArrayIndex(e) => Ok(LhsExpr::ArrayIndex(e)),
-            Expr::TupleIndex(e) => Ok(LhsExpr::TupleIndex(e)),
-            Expr::FieldAccess(e) => Ok(LhsExpr::FieldAccess(e)),
-            _ => Err("invalid lhs expr".into()),
-        }
-    }
-
-    pub fn set_type_info(&mut self, type_info: TypeInfo) {
+        let msg = match expr {
+            Expr::Path(p) => return Ok(LhsExpr::Path(p)),
+            Expr::Unary(u) if u.op == UnOp::Deref => return Ok(LhsExpr::Deref(u.expr)),
+            Expr::Grouped(e) => return LhsExpr::from_expr(*e),
+            Expr::ArrayIndex(e) => return Ok(LhsExpr::ArrayIndex(e)),
+            Expr::TupleIndex(e) => return Ok(LhsExpr::TupleIndex(e)),
+            Expr::FieldAccess(e) => return Ok(LhsExpr::FieldAccess(e)),
+            Expr::Call(_) => "cannot assign to the result of this function call",
+            Expr::LitNum(_)
+            | Expr::LitBool(_)
+            | Expr::LitChar(_)
+            | Expr::LitStr(_)
+            | Expr::LitByteStr(_) => "cannot assign to a literal",
+            _ => "cannot assign to this expression",
+        };
+        Err(CompileError::new(ErrorKind::Type, msg)
+            .code("E0070")
+            .note("only variables, fields, indexing and dereferences can be assigned to")
+            .into())
+    }
+
+    pub fn type_slot(&self, types: &mut TypeTable) -> TypeSlot {
         match self {
-            Self::Path(p) => {
-                p.type_info.replace(type_info);
+            LhsExpr::Path(expr) => expr.type_slot(types),
+            LhsExpr::ArrayIndex(expr) => expr.type_slot(types),
+            LhsExpr::FieldAccess(expr) => expr.type_slot(types),
+            _ => {
+                let type_info = self.type_info(types);
+                types.new_slot(type_info)
             }
-            Self::ArrayIndex(a) => unimplemented!("set array index type info"),
-            Self::TupleIndex(t) => unimplemented!("set tuple index type info"),
-            Self::FieldAccess(f) => unimplemented!("set tuple field type info"),
-            Self::Deref(e) => unimplemented!("set tuple deref type info"),
-        }
-    }
-
-    pub fn set_type_info_ref(&mut self, type_info: Rc<RefCell<TypeInfo>>) {
-        match self {
-            LhsExpr::Path(p) => p.set_type_info_ref(type_info),
-            _ => todo!(),
         }
     }
-}
 
-impl ExprVisit for LhsExpr {
-    fn type_info(&self) -> Rc<RefCell<TypeInfo>> {
+    pub fn type_info(&self, types: &TypeTable) -> TypeInfo {
         match self {
-            LhsExpr::Path(expr) => expr.type_info(),
+            LhsExpr::Path(expr) => expr.type_info(types),
+            LhsExpr::ArrayIndex(expr) => expr.type_info(types),
+            LhsExpr::FieldAccess(expr) => expr.type_info(types),
+            LhsExpr::Deref(expr) => match expr.type_info(types) {
+                TypeInfo::Ptr { kind: _, type_info } => *type_info,
+                _ => TypeInfo::Unknown,
+            },
             _ => todo!(),
         }
     }
 
-    fn kind(&self) -> ExprKind {
+    /// `*p` is mutable if `p` is a mutable reference or pointer, or a mutable `Box`.
+    pub fn kind(&self, types: &TypeTable) -> ExprKind {
         match self {
             LhsExpr::Path(expr) => expr.kind(),
+            LhsExpr::ArrayIndex(expr) => expr.kind(),
+            LhsExpr::FieldAccess(expr) => expr.kind(),
+            LhsExpr::Deref(expr) => match expr.type_info(types) {
+                // `*b = 3` requires `b` to be mutable
+                TypeInfo::Ptr {
+                    kind: PtrKind::Box,
+                    type_info: _,
+                } => expr.kind(),
+                TypeInfo::Ptr {
+                    kind: PtrKind::MutRef | PtrKind::MutRawPtr,
+                    type_info: _,
+                } => ExprKind::MutablePlace,
+                _ => ExprKind::Place,
+            },
             _ => todo!(),
         }
     }
 }
 
-#[derive(Debug, PartialEq)]
-pub struct ConstantExpr<V> {
-    pub expr: Option<Box<Expr>>,
-    const_value: Option<V>,
-}
-
-impl<V> ConstantExpr<V> {
-    pub fn const_value(value: V) -> ConstantExpr<V> {
-        ConstantExpr {
-            expr: None,
-            const_value: Some(value),
-        }
-    }
-
-    pub fn expr(expr: Expr) -> ConstantExpr<V> {
-        ConstantExpr {
-            expr: Some(Box::new(expr)),
-            const_value: None,
-        }
-    }
-}
-
 impl TokenStart for Expr {
     fn is_token_start(tk: &Token) -> bool {
         matches!(
             tk,
             Token::Identifier(_)
+                | Token::SelfValue
+                | Token::SelfType
                 | Token::Literal { .. }
                 | Token::LitString(_)
+                | Token::LitByteString(_)
                 | Token::True
                 | Token::False
                 | Token::DotDot
                 | Token::LeftCurlyBraces
+                | Token::Unsafe
                 | Token::LeftParen
                 | Token::LeftSquareBrackets
                 | Token::For
@@ -293,20 +341,24 @@ impl TokenStart for Expr {
     }
 }
 
+#[derive(Clone)]
 pub struct BlockExpr {
     pub stmts: Vec<Stmt>,
     pub last_expr: Option<Box<Expr>>,
-    pub scope: Scope,
-    type_info: Rc<RefCell<TypeInfo>>,
+    /// `unsafe { ... }`, in which raw pointers can be dereferenced
+    pub is_unsafe: bool,
+    pub scope_id: ScopeID,
+    pub node_id: NodeId,
 }
 
 impl BlockExpr {
-    pub fn new(scope_id: u64) -> BlockExpr {
+    pub fn new(scope_id: ScopeID) -> BlockExpr {
         BlockExpr {
             stmts: vec![],
             last_expr: None,
-            scope: Scope::new(scope_id),
-            type_info: Rc::new(RefCell::new(TypeInfo::Unknown)),
+            is_unsafe: false,
+            scope_id,
+            node_id: NodeId::DUMMY,
         }
     }
 
@@ -336,8 +388,12 @@ impl BlockExpr {
 }
 
 impl ExprVisit for BlockExpr {
-    fn type_info(&self) -> Rc<RefCell<TypeInfo>> {
-        self.type_info.clone()
+    fn type_slot(&self, types: &mut TypeTable) -> TypeSlot {
+        types.node_slot(self.node_id, TypeInfo::Unknown)
+    }
+
+    fn type_info(&self, types: &TypeTable) -> TypeInfo {
+        types.node_type(self.node_id).cloned().unwrap_or(TypeInfo::Unknown)
     }
 
     fn kind(&self) -> ExprKind {
@@ -346,17 +402,29 @@ impl ExprVisit for BlockExpr {
 }
 
 impl TypeInfoSetter for BlockExpr {
-    fn set_type_info(&mut self, type_info: TypeInfo) {
-        self.type_info.replace(type_info);
+    fn set_type_info(&self, types: &mut TypeTable, type_info: TypeInfo) {
+        let slot = self.type_slot(types);
+        types.set(slot, type_info);
     }
 
-    fn set_type_info_ref(&mut self, type_info: Rc<RefCell<TypeInfo>>) {
-        self.type_info = type_info;
+    fn set_type_info_ref(&self, types: &mut TypeTable, slot: TypeSlot) {
+        types.bind(self.node_id, slot);
     }
 }
 
 impl Debug for BlockExpr {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if f.alternate() {
+            return f
+                .debug_struct("BlockExpr")
+                .field("stmts", &self.stmts)
+                .field("last_expr", &self.last_expr)
+                .field("is_unsafe", &self.is_unsafe)
+                .finish();
+        }
+        if self.is_unsafe {
+            write!(f, "unsafe ")?;
+        }
         match &self.last_expr {
             Some(expr) => write!(f, "{{ {:?} {:?} }}", self.stmts, expr),
             None => write!(f, "{{ {:?} }}", self.stmts),
@@ -366,7 +434,9 @@ impl Debug for BlockExpr {
 
 impl PartialEq for BlockExpr {
     fn eq(&self, other: &Self) -> bool {
-        self.stmts.eq(&other.stmts) && self.last_expr.eq(&other.last_expr)
+        self.stmts.eq(&other.stmts)
+            && self.last_expr.eq(&other.last_expr)
+            && self.is_unsafe == other.is_unsafe
     }
 }
 
@@ -375,49 +445,69 @@ impl From<Vec<Stmt>> for BlockExpr {
         BlockExpr {
             stmts,
             last_expr: None,
-            scope: Scope::new(0),
-            type_info: Rc::new(RefCell::new(TypeInfo::Unknown)),
+            is_unsafe: false,
+            scope_id: 0,
+            node_id: NodeId::DUMMY,
         }
     }
 }
 
-#[derive(PartialEq, Debug)]
+#[derive(Debug, Clone)]
 pub struct LitNumExpr {
     pub value: String,
-    type_info: Rc<RefCell<TypeInfo>>,
+    /// type given by the suffix, `i` or `f` if there is none
+    lit_type: TypeLitNum,
+    pub node_id: NodeId,
 }
 
 impl LitNumExpr {
     pub fn new(value: String, ret_type: TypeLitNum) -> LitNumExpr {
         LitNumExpr {
             value,
-            type_info: Rc::new(RefCell::new(TypeInfo::LitNum(ret_type))),
+            lit_type: ret_type,
+            node_id: NodeId::DUMMY,
         }
     }
 
     pub fn integer(value: String) -> LitNumExpr {
         LitNumExpr {
-            type_info: Rc::new(RefCell::new(TypeInfo::LitNum(TypeLitNum::I))),
             value,
+            lit_type: TypeLitNum::I,
+            node_id: NodeId::DUMMY,
         }
     }
 
     pub fn lit_type(mut self, lit_type: TypeLitNum) -> LitNumExpr {
-        self.type_info = Rc::new(RefCell::new(TypeInfo::LitNum(lit_type)));
+        self.lit_type = lit_type;
         self
     }
 
-    pub fn get_lit_type(&mut self) -> TypeLitNum {
-        if let TypeInfo::LitNum(t) = self.type_info.borrow().deref() {
-            return t.clone();
+    /// The type given by the suffix of the literal, if there is one.
+    pub fn suffix(&self) -> Option<TypeLitNum> {
+        match self.lit_type {
+            TypeLitNum::I | TypeLitNum::F => None,
+            t => Some(t),
+        }
+    }
+
+    pub fn get_lit_type(&self, types: &TypeTable) -> TypeLitNum {
+        if let TypeInfo::LitNum(t) = self.type_info(types) {
+            return t;
         }
         panic!("TypeInfo must be lit num")
     }
 }
 
 impl ExprVisit for LitNumExpr {
-    fn type_info(&self) -> Rc<RefCell<TypeInfo>> {
-        self.type_info.clone()
+    fn type_slot(&self, types: &mut TypeTable) -> TypeSlot {
+        types.node_slot(self.node_id, TypeInfo::LitNum(self.lit_type))
+    }
+
+    fn type_info(&self, types: &TypeTable) -> TypeInfo {
+        types
+            .node_type(self.node_id)
+            .cloned()
+            .unwrap_or_else(|| TypeInfo::LitNum(self.lit_type))
     }
 
     fn kind(&self) -> ExprKind {
@@ -426,49 +516,57 @@ impl ExprVisit for LitNumExpr {
 }
 
 impl TypeInfoSetter for LitNumExpr {
-    fn set_type_info(&mut self, type_info: TypeInfo) {
+    fn set_type_info(&self, types: &mut TypeTable, type_info: TypeInfo) {
         match &type_info {
             TypeInfo::LitNum(_) => {
-                self.type_info.replace(type_info);
+                let slot = self.type_slot(types);
+                types.set(slot, type_info);
             }
             _ => panic!("must be lit num"),
         }
     }
 
-    fn set_type_info_ref(&mut self, type_info: Rc<RefCell<TypeInfo>>) {
-        self.type_info = type_info;
+    fn set_type_info_ref(&self, types: &mut TypeTable, slot: TypeSlot) {
+        types.bind(self.node_id, slot);
+    }
+}
+
+impl PartialEq for LitNumExpr {
+    fn eq(&self, other: &Self) -> bool {
+        self.value == other.value && self.lit_type == other.lit_type
     }
 }
 
 impl From<i32> for LitNumExpr {
     fn from(num: i32) -> Self {
-        LitNumExpr {
-            type_info: Rc::new(RefCell::new(TypeInfo::LitNum(TypeLitNum::I))),
-            value: num.to_string(),
-        }
+        LitNumExpr::integer(num.to_string())
     }
 }
 
-#[derive(PartialEq, Debug)]
+#[derive(Debug, Clone)]
 pub struct PathExpr {
     pub segments: Vec<String>,
-    type_info: Rc<RefCell<TypeInfo>>,
     pub expr_kind: ExprKind,
+    pub node_id: NodeId,
 }
 
 impl PathExpr {
     pub fn new() -> Self {
         PathExpr {
             segments: vec![],
-            type_info: Rc::new(RefCell::new(TypeInfo::Unknown)),
             expr_kind: ExprKind::Unknown,
+            node_id: NodeId::DUMMY,
         }
     }
 }
 
-impl PathExpr {
-    pub fn type_info(&self) -> Rc<RefCell<TypeInfo>> {
-        self.type_info.clone()
+impl ExprVisit for PathExpr {
+    fn type_slot(&self, types: &mut TypeTable) -> TypeSlot {
+        types.node_slot(self.node_id, TypeInfo::Unknown)
+    }
+
+    fn type_info(&self, types: &TypeTable) -> TypeInfo {
+        types.node_type(self.node_id).cloned().unwrap_or(TypeInfo::Unknown)
     }
 
     fn kind(&self) -> ExprKind {
@@ -477,12 +575,19 @@ impl PathExpr {
 }
 
 impl TypeInfoSetter for PathExpr {
-    fn set_type_info(&mut self, type_info: TypeInfo) {
-        self.type_info.replace(type_info);
+    fn set_type_info(&self, types: &mut TypeTable, type_info: TypeInfo) {
+        let slot = self.type_slot(types);
+        types.set(slot, type_info);
     }
 
-    fn set_type_info_ref(&mut self, type_info: Rc<RefCell<TypeInfo>>) {
-        self.type_info = type_info;
+    fn set_type_info_ref(&self, types: &mut TypeTable, slot: TypeSlot) {
+        types.bind(self.node_id, slot);
+    }
+}
+
+impl PartialEq for PathExpr {
+    fn eq(&self, other: &Self) -> bool {
+        self.segments == other.segments && self.expr_kind == other.expr_kind
     }
 }
 
@@ -490,8 +595,8 @@ impl From<Vec<String>> for PathExpr {
     fn from(segments: Vec<String>) -> Self {
         PathExpr {
             segments,
-            type_info: Rc::new(RefCell::new(TypeInfo::Unknown)),
             expr_kind: ExprKind::Unknown,
+            node_id: NodeId::DUMMY,
         }
     }
 }
@@ -500,8 +605,8 @@ impl From<Vec<&str>> for PathExpr {
     fn from(segments: Vec<&str>) -> Self {
         PathExpr {
             segments: segments.iter().map(|s| s.to_string()).collect(),
-            type_info: Rc::new(RefCell::new(TypeInfo::Unknown)),
             expr_kind: ExprKind::Unknown,
+            node_id: NodeId::DUMMY,
         }
     }
 }
@@ -510,18 +615,18 @@ impl From<&str> for PathExpr {
     fn from(s: &str) -> Self {
         PathExpr {
             segments: s.split("::").map(|s| s.to_string()).collect(),
-            type_info: Rc::new(RefCell::new(TypeInfo::Unknown)),
             expr_kind: ExprKind::Unknown,
+            node_id: NodeId::DUMMY,
         }
     }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone)]
 pub struct UnAryExpr {
     pub op: UnOp,
     pub expr: Box<Expr>,
-    type_info: Rc<RefCell<TypeInfo>>,
     pub expr_kind: ExprKind,
+    pub node_id: NodeId,
 }
 
 impl UnAryExpr {
@@ -529,15 +634,25 @@ impl UnAryExpr {
         UnAryExpr {
             op,
             expr: Box::new(expr),
-            type_info: Rc::new(RefCell::new(TypeInfo::Unknown)),
             expr_kind: ExprKind::Unknown,
+            node_id: NodeId::DUMMY,
         }
     }
 }
 
+impl PartialEq for UnAryExpr {
+    fn eq(&self, other: &Self) -> bool {
+        self.op == other.op && self.expr == other.expr && self.expr_kind == other.expr_kind
+    }
+}
+
 impl ExprVisit for UnAryExpr {
-    fn type_info(&self) -> Rc<RefCell<TypeInfo>> {
-        self.type_info.clone()
+    fn type_slot(&self, types: &mut TypeTable) -> TypeSlot {
+        types.node_slot(self.node_id, TypeInfo::Unknown)
+    }
+
+    fn type_info(&self, types: &TypeTable) -> TypeInfo {
+        types.node_type(self.node_id).cloned().unwrap_or(TypeInfo::Unknown)
     }
 
     fn kind(&self) -> ExprKind {
@@ -555,16 +670,17 @@ impl TokenStart for UnAryExpr {
 }
 
 impl TypeInfoSetter for UnAryExpr {
-    fn set_type_info(&mut self, type_info: TypeInfo) {
-        self.type_info.replace(type_info);
+    fn set_type_info(&self, types: &mut TypeTable, type_info: TypeInfo) {
+        let slot = self.type_slot(types);
+        types.set(slot, type_info);
     }
 
-    fn set_type_info_ref(&mut self, type_info: Rc<RefCell<TypeInfo>>) {
-        self.type_info = type_info;
+    fn set_type_info_ref(&self, types: &mut TypeTable, slot: TypeSlot) {
+        types.bind(self.node_id, slot);
     }
 }
 
-#[derive(PartialEq)]
+#[derive(PartialEq, Clone)]
 pub enum UnOp {
     /// The `*` operator for dereferencing
     Deref,
@@ -606,11 +722,13 @@ impl FromToken for UnOp {
     }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone)]
 pub struct AssignExpr {
     pub lhs: LhsExpr,
     pub assign_op: AssignOp,
     pub rhs: Box<Expr>,
+    /// the span of `lhs` is kept for the diagnostics of immutable places
+    pub node_id: NodeId,
 }
 
 impl AssignExpr {
@@ -619,13 +737,24 @@ impl AssignExpr {
             lhs,
             assign_op,
             rhs: Box::new(rhs),
+            node_id: NodeId::DUMMY,
         }
     }
 }
 
+impl PartialEq for AssignExpr {
+    fn eq(&self, other: &Self) -> bool {
+        self.lhs == other.lhs && self.assign_op == other.assign_op && self.rhs == other.rhs
+    }
+}
+
 impl ExprVisit for AssignExpr {
-    fn type_info(&self) -> Rc<RefCell<TypeInfo>> {
-        Rc::new(RefCell::new(TypeInfo::Unit))
+    fn type_slot(&self, types: &mut TypeTable) -> TypeSlot {
+        types.new_slot(TypeInfo::Unit)
+    }
+
+    fn type_info(&self, _types: &TypeTable) -> TypeInfo {
+        TypeInfo::Unit
     }
 
     fn kind(&self) -> ExprKind {
@@ -634,7 +763,7 @@ impl ExprVisit for AssignExpr {
 }
 
 from_token! {
-    #[derive(StrEnum, PartialEq)]
+    #[derive(StrEnum, PartialEq, Clone)]
     pub enum AssignOp {
         /// Compound assignment operators
         #[strenum("+=")]
@@ -679,11 +808,13 @@ impl Debug for AssignOp {
     }
 }
 
-#[derive(Debug, PartialEq)]
+/// `a..b` and `a..=b` are pairs of (start, end), see `TypeInfo::Range`
+#[derive(Debug, Clone)]
 pub struct RangeExpr {
     pub lhs: Option<Box<Expr>>,
     pub range_op: RangeOp,
     pub rhs: Option<Box<Expr>>,
+    pub node_id: NodeId,
 }
 
 impl RangeExpr {
@@ -692,6 +823,7 @@ impl RangeExpr {
             lhs: None,
             range_op,
             rhs: None,
+            node_id: NodeId::DUMMY,
         }
     }
 
@@ -714,6 +846,26 @@ impl RangeExpr {
     }
 }
 
+impl PartialEq for RangeExpr {
+    fn eq(&self, other: &Self) -> bool {
+        self.lhs == other.lhs && self.range_op == other.range_op && self.rhs == other.rhs
+    }
+}
+
+impl ExprVisit for RangeExpr {
+    fn type_slot(&self, types: &mut TypeTable) -> TypeSlot {
+        types.node_slot(self.node_id, TypeInfo::Unknown)
+    }
+
+    fn type_info(&self, types: &TypeTable) -> TypeInfo {
+        types.node_type(self.node_id).cloned().unwrap_or(TypeInfo::Unknown)
+    }
+
+    fn kind(&self) -> ExprKind {
+        ExprKind::Value
+    }
+}
+
 impl TokenStart for RangeExpr {
     fn is_token_start(tk: &Token) -> bool {
         tk == &Token::DotDotEq || tk == &Token::DotDot
@@ -721,7 +873,7 @@ impl TokenStart for RangeExpr {
 }
 
 from_token! {
-    #[derive(StrEnum, Debug, PartialEq)]
+    #[derive(StrEnum, Debug, PartialEq, Clone)]
     pub enum RangeOp {
         /// Range operators
         #[strenum("..")]
@@ -733,12 +885,53 @@ from_token! {
     }
 }
 
-#[derive(Debug, PartialEq)]
+/// `expr as type_anno`
+#[derive(Debug, Clone)]
+pub struct CastExpr {
+    pub expr: Box<Expr>,
+    pub type_anno: TypeAnnotation,
+    pub node_id: NodeId,
+}
+
+impl CastExpr {
+    pub fn new(expr: Expr, type_anno: TypeAnnotation) -> Self {
+        CastExpr {
+            expr: Box::new(expr),
+            type_anno,
+            node_id: NodeId::DUMMY,
+        }
+    }
+}
+
+impl PartialEq for CastExpr {
+    fn eq(&self, other: &Self) -> bool {
+        self.expr == other.expr && self.type_anno == other.type_anno
+    }
+}
+
+impl ExprVisit for CastExpr {
+    fn type_slot(&self, types: &mut TypeTable) -> TypeSlot {
+        types.node_slot(self.node_id, TypeInfo::Unknown)
+    }
+
+    fn type_info(&self, types: &TypeTable) -> TypeInfo {
+        types.node_type(self.node_id).cloned().unwrap_or(TypeInfo::Unknown)
+    }
+
+    fn kind(&self) -> ExprKind {
+        ExprKind::Value
+    }
+}
+
+#[derive(Debug, Clone)]
 pub struct BinOpExpr {
     pub lhs: Box<Expr>,
     pub bin_op: BinOperator,
     pub rhs: Box<Expr>,
-    type_info: Rc<RefCell<TypeInfo>>,
+    pub node_id: NodeId,
+    /// Symbol of the trait method overloading the operator, e.g. `_RCM6String3Add3add`,
+    /// filled by the symbol resolver.
+    pub op_fn: Option<String>,
 }
 
 impl BinOpExpr {
@@ -747,14 +940,25 @@ impl BinOpExpr {
             lhs: Box::new(lhs),
             bin_op,
             rhs: Box::new(rhs),
-            type_info: Rc::new(RefCell::new(TypeInfo::Unknown)),
+            node_id: NodeId::DUMMY,
+            op_fn: None,
         }
     }
 }
 
+impl PartialEq for BinOpExpr {
+    fn eq(&self, other: &Self) -> bool {
+        self.lhs == other.lhs && self.bin_op == other.bin_op && self.rhs == other.rhs
+    }
+}
+
 impl ExprVisit for BinOpExpr {
-    fn type_info(&self) -> Rc<RefCell<TypeInfo>> {
-        self.type_info.clone()
+    fn type_slot(&self, types: &mut TypeTable) -> TypeSlot {
+        types.node_slot(self.node_id, TypeInfo::Unknown)
+    }
+
+    fn type_info(&self, types: &TypeTable) -> TypeInfo {
+        types.node_type(self.node_id).cloned().unwrap_or(TypeInfo::Unknown)
     }
 
     fn kind(&self) -> ExprKind {
@@ -763,14 +967,15 @@ impl ExprVisit for BinOpExpr {
 }
 
 impl TypeInfoSetter for BinOpExpr {
-    fn set_type_info(&mut self, type_info: TypeInfo) {
-        self.type_info.replace(type_info.clone());
-        self.lhs.set_type_info(type_info.clone());
-        self.rhs.set_type_info(type_info);
+    fn set_type_info(&self, types: &mut TypeTable, type_info: TypeInfo) {
+        let slot = self.type_slot(types);
+        types.set(slot, type_info.clone());
+        self.lhs.set_type_info(types, type_info.clone());
+        self.rhs.set_type_info(types, type_info);
     }
 
-    fn set_type_info_ref(&mut self, type_info: Rc<RefCell<TypeInfo>>) {
-        self.type_info = type_info;
+    fn set_type_info_ref(&self, types: &mut TypeTable, slot: TypeSlot) {
+        types.bind(self.node_id, slot);
     }
 }
 
@@ -815,9 +1020,6 @@ from_token! {
         #[strenum("||")]
         OrOr,
 
-        /// Type cast operator
-        As,
-
         /// Comparison operators
         #[strenum("==")]
         EqEq,
@@ -858,6 +1060,24 @@ impl BinOperator {
             Ok(&l_prec >= p)
         }
     }
+
+    /// `==`, `!=`, `<`, `<=`, `>` and `>=`
+    pub fn is_comparison(&self) -> bool {
+        Precedence::from_bin_op(self) == Precedence::Cmp
+    }
+
+    /// (trait, method) overloading the operator, `a != b` is `!a.eq(b)`
+    pub fn overloading_trait(&self) -> Option<(&'static str, &'static str)> {
+        match self {
+            BinOperator::Plus => Some(("Add", "add")),
+            BinOperator::Minus => Some(("Sub", "sub")),
+            BinOperator::Star => Some(("Mul", "mul")),
+            BinOperator::Slash => Some(("Div", "div")),
+            BinOperator::Percent => Some(("Rem", "rem")),
+            BinOperator::EqEq | BinOperator::Ne => Some(("PartialEq", "eq")),
+            _ => None,
+        }
+    }
 }
 
 impl Debug for BinOperator {
@@ -871,7 +1091,7 @@ impl Debug for BinOperator {
 /// ```
 /// assert!(Precedence::Add < Precedence::Multi);
 /// ```
-#[derive(Debug, PartialOrd, PartialEq)]
+#[derive(Debug, PartialOrd, PartialEq, Clone)]
 pub enum Precedence {
     Min,
     OrOr,
@@ -883,13 +1103,11 @@ pub enum Precedence {
     Shift,
     Add,
     Multi,
-    As,
 }
 
 impl Precedence {
     pub fn from_bin_op(op: &BinOperator) -> Self {
         match op {
-            BinOperator::As => Self::As,
             BinOperator::Star | BinOperator::Slash | BinOperator::Percent => Self::Multi,
             BinOperator::Plus | BinOperator::Minus => Self::Add,
             BinOperator::Shl | BinOperator::Shr => Self::Shift,
@@ -911,30 +1129,64 @@ impl Precedence {
 /// GroupExpr -> `(` Expr `)`
 pub type GroupedExpr = Box<Expr>;
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone)]
 pub struct ArrayExpr {
     pub elems: Vec<Expr>,
-    pub len_expr: ConstantExpr<usize>,
+    /// `n` of `[x; n]`, which is evaluated at compile time like a constant
+    pub len_expr: Option<Box<Expr>>,
+    pub node_id: NodeId,
 }
 
 impl ArrayExpr {
-    pub fn new(elems: Vec<Expr>, len_expr: ConstantExpr<usize>) -> Self {
-        ArrayExpr { elems, len_expr }
+    /// `[elem; len_expr]`
+    pub fn new(elem: Expr, len_expr: Expr) -> Self {
+        ArrayExpr {
+            elems: vec![elem],
+            len_expr: Some(Box::new(len_expr)),
+            node_id: NodeId::DUMMY,
+        }
     }
 
     pub fn elems(elems: Vec<Expr>) -> ArrayExpr {
-        let length = elems.len();
         ArrayExpr {
             elems,
-            len_expr: ConstantExpr::<usize>::const_value(length),
+            len_expr: None,
+            node_id: NodeId::DUMMY,
         }
     }
+
+    pub fn set_type_info(&self, types: &mut TypeTable, type_info: TypeInfo) {
+        let slot = self.type_slot(types);
+        types.set(slot, type_info);
+    }
 }
 
-#[derive(Debug, PartialEq)]
+impl PartialEq for ArrayExpr {
+    fn eq(&self, other: &Self) -> bool {
+        self.elems == other.elems && self.len_expr == other.len_expr
+    }
+}
+
+impl ExprVisit for ArrayExpr {
+    fn type_slot(&self, types: &mut TypeTable) -> TypeSlot {
+        types.node_slot(self.node_id, TypeInfo::Unknown)
+    }
+
+    fn type_info(&self, types: &TypeTable) -> TypeInfo {
+        types.node_type(self.node_id).cloned().unwrap_or(TypeInfo::Unknown)
+    }
+
+    fn kind(&self) -> ExprKind {
+        ExprKind::Value
+    }
+}
+
+#[derive(Debug, Clone)]
 pub struct ArrayIndexExpr {
     pub expr: Box<Expr>,
     pub index_expr: Box<Expr>,
+    pub expr_kind: ExprKind,
+    pub node_id: NodeId,
 }
 
 impl ArrayIndexExpr {
@@ -942,27 +1194,141 @@ impl ArrayIndexExpr {
         ArrayIndexExpr {
             expr: Box::new(expr),
             index_expr: Box::new(index_expr),
+            expr_kind: ExprKind::Unknown,
+            node_id: NodeId::DUMMY,
         }
     }
+
+    pub fn set_type_info(&self, types: &mut TypeTable, type_info: TypeInfo) {
+        let slot = self.type_slot(types);
+        types.set(slot, type_info);
+    }
+}
+
+impl PartialEq for ArrayIndexExpr {
+    fn eq(&self, other: &Self) -> bool {
+        self.expr == other.expr
+            && self.index_expr == other.index_expr
+            && self.expr_kind == other.expr_kind
+    }
+}
+
+impl ExprVisit for ArrayIndexExpr {
+    fn type_slot(&self, types: &mut TypeTable) -> TypeSlot {
+        types.node_slot(self.node_id, TypeInfo::Unknown)
+    }
+
+    fn type_info(&self, types: &TypeTable) -> TypeInfo {
+        types.node_type(self.node_id).cloned().unwrap_or(TypeInfo::Unknown)
+    }
+
+    fn kind(&self) -> ExprKind {
+        self.expr_kind
+    }
+}
+
+/// `(a, b)`, only used as arguments of tuple params
+#[derive(Debug, Clone)]
+pub struct TupleExpr {
+    pub elems: Vec<Expr>,
+    pub node_id: NodeId,
+}
+
+impl TupleExpr {
+    pub fn new(elems: Vec<Expr>) -> Self {
+        TupleExpr {
+            elems,
+            node_id: NodeId::DUMMY,
+        }
+    }
+}
+
+impl PartialEq for TupleExpr {
+    fn eq(&self, other: &Self) -> bool {
+        self.elems == other.elems
+    }
 }
 
-#[derive(Debug, PartialEq)]
-pub struct TupleExpr(pub Vec<Expr>);
+impl ExprVisit for TupleExpr {
+    fn type_slot(&self, types: &mut TypeTable) -> TypeSlot {
+        types.node_slot(self.node_id, TypeInfo::Unknown)
+    }
+
+    fn type_info(&self, types: &TypeTable) -> TypeInfo {
+        types.node_type(self.node_id).cloned().unwrap_or(TypeInfo::Unknown)
+    }
+
+    fn kind(&self) -> ExprKind {
+        ExprKind::Value
+    }
+}
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone)]
 pub struct TupleIndexExpr {
     // TODO
 }
 
-#[derive(Debug, PartialEq)]
-pub struct StructExpr;
+/// `S { x: 1, y, ..base }`, the field `y` is short for `y: y`. The fields which are not
+/// listed are copied from `base`.
+#[derive(Debug, Clone)]
+pub struct StructExpr {
+    pub name: String,
+    pub fields: Vec<(String, Expr)>,
+    pub base: Option<Box<Expr>>,
+    pub node_id: NodeId,
+}
+
+impl StructExpr {
+    pub fn new(name: String) -> Self {
+        StructExpr {
+            name,
+            fields: vec![],
+            base: None,
+            node_id: NodeId::DUMMY,
+        }
+    }
+
+    pub fn field(mut self, name: &str, expr: Expr) -> Self {
+        self.fields.push((name.to_string(), expr));
+        self
+    }
+
+    pub fn base(mut self, base: Expr) -> Self {
+        self.base = Some(Box::new(base));
+        self
+    }
+}
+
+impl PartialEq for StructExpr {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name && self.fields == other.fields && self.base == other.base
+    }
+}
+
+impl ExprVisit for StructExpr {
+    fn type_slot(&self, types: &mut TypeTable) -> TypeSlot {
+        types.node_slot(self.node_id, TypeInfo::Unknown)
+    }
+
+    fn type_info(&self, types: &TypeTable) -> TypeInfo {
+        types.node_type(self.node_id).cloned().unwrap_or(TypeInfo::Unknown)
+    }
+
+    fn kind(&self) -> ExprKind {
+        ExprKind::Value
+    }
+}
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone)]
 pub struct ReturnExpr(pub Option<Box<Expr>>);
 
 impl ExprVisit for ReturnExpr {
-    fn type_info(&self) -> Rc<RefCell<TypeInfo>> {
-        Rc::new(RefCell::new(TypeInfo::Never))
+    fn type_slot(&self, types: &mut TypeTable) -> TypeSlot {
+        types.new_slot(TypeInfo::Never)
+    }
+
+    fn type_info(&self, _types: &TypeTable) -> TypeInfo {
+        TypeInfo::Never
     }
 
     fn kind(&self) -> ExprKind {
@@ -970,12 +1336,141 @@ impl ExprVisit for ReturnExpr {
     }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone)]
 pub struct BreakExpr(pub Option<Box<Expr>>);
 
 impl ExprVisit for BreakExpr {
-    fn type_info(&self) -> Rc<RefCell<TypeInfo>> {
-        Rc::new(RefCell::new(TypeInfo::Never))
+    fn type_slot(&self, types: &mut TypeTable) -> TypeSlot {
+        types.new_slot(TypeInfo::Never)
+    }
+
+    fn type_info(&self, _types: &TypeTable) -> TypeInfo {
+        TypeInfo::Never
+    }
+
+    fn kind(&self) -> ExprKind {
+        ExprKind::Value
+    }
+}
+
+/// `print!(...)` or `println!(...)`
+#[derive(Debug, PartialEq, Clone)]
+pub struct PrintExpr {
+    pub newline: bool,
+    /// Pieces of the format string around the `{}` placeholders,
+    /// there is always one more piece than arguments.
+    pub pieces: Vec<String>,
+    pub args: Vec<Expr>,
+}
+
+impl PrintExpr {
+    pub fn new(newline: bool, pieces: Vec<String>, args: Vec<Expr>) -> Self {
+        debug_assert_eq!(pieces.len(), args.len() + 1);
+        PrintExpr {
+            newline,
+            pieces,
+            args,
+        }
+    }
+}
+
+impl ExprVisit for PrintExpr {
+    fn type_slot(&self, types: &mut TypeTable) -> TypeSlot {
+        types.new_slot(TypeInfo::Unit)
+    }
+
+    fn type_info(&self, _types: &TypeTable) -> TypeInfo {
+        TypeInfo::Unit
+    }
+
+    fn kind(&self) -> ExprKind {
+        ExprKind::Value
+    }
+}
+
+/// `assert!(cond)`, `assert_eq!(a, b)` or `assert_ne!(a, b)`, with an optional message
+#[derive(Debug, PartialEq, Clone)]
+pub struct AssertExpr {
+    pub kind: AssertKind,
+    /// `a == b` of `assert_eq!(a, b)` and `a != b` of `assert_ne!(a, b)`
+    pub cond: Box<Expr>,
+    /// The panic message instead of `assertion failed: <cond>`
+    pub msg: Option<String>,
+    /// Line of the macro, 0 if unknown
+    pub line: u32,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum AssertKind {
+    Assert,
+    Eq,
+    Ne,
+}
+
+impl AssertKind {
+    pub fn macro_name(self) -> &'static str {
+        match self {
+            AssertKind::Assert => "assert",
+            AssertKind::Eq => "assert_eq",
+            AssertKind::Ne => "assert_ne",
+        }
+    }
+}
+
+impl AssertExpr {
+    pub fn new(kind: AssertKind, cond: Expr, msg: Option<String>, line: u32) -> Self {
+        AssertExpr {
+            kind,
+            cond: Box::new(cond),
+            msg,
+            line,
+        }
+    }
+}
+
+impl ExprVisit for AssertExpr {
+    fn type_slot(&self, types: &mut TypeTable) -> TypeSlot {
+        types.new_slot(TypeInfo::Unit)
+    }
+
+    fn type_info(&self, _types: &TypeTable) -> TypeInfo {
+        TypeInfo::Unit
+    }
+
+    fn kind(&self) -> ExprKind {
+        ExprKind::Value
+    }
+}
+
+/// `asm!("csrr {}, mscratch", out(reg) x)`, only the register class `reg` is supported
+#[derive(Debug, PartialEq, Clone)]
+pub struct AsmExpr {
+    /// The template strings joined by `\n`. Placeholders are numbered by the parser, inputs
+    /// come before outputs, e.g. `csrw mscratch, {0}`. `{{` and `}}` are escaped braces.
+    pub template: String,
+    /// `in(reg) expr`
+    pub inputs: Vec<Expr>,
+    /// `out(reg) place`
+    pub outputs: Vec<LhsExpr>,
+}
+
+impl AsmExpr {
+    pub fn new(template: String, inputs: Vec<Expr>, outputs: Vec<LhsExpr>) -> Self {
+        AsmExpr {
+            template,
+            inputs,
+            outputs,
+        }
+    }
+}
+
+impl ExprVisit for AsmExpr {
+    fn type_slot(&self, types: &mut TypeTable) -> TypeSlot {
+        types.new_slot(TypeInfo::Unit)
+    }
+
+    fn type_info(&self, _types: &TypeTable) -> TypeInfo {
+        TypeInfo::Unit
     }
 
     fn kind(&self) -> ExprKind {
@@ -983,11 +1478,14 @@ impl ExprVisit for BreakExpr {
     }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone)]
 pub struct CallExpr {
     pub expr: Box<Expr>,
     pub call_params: CallParams,
-    type_info: Rc<RefCell<TypeInfo>>,
+    /// Index of the method in the vtable if it is called on a trait object, the first
+    /// param is the trait object. Set by the symbol resolver.
+    pub vtable_index: Option<usize>,
+    pub node_id: NodeId,
 }
 
 pub type CallParams = Vec<Expr>;
@@ -997,7 +1495,8 @@ impl CallExpr {
         CallExpr {
             expr: Box::new(expr),
             call_params: vec![],
-            type_info: Rc::new(RefCell::new(TypeInfo::Unknown)),
+            vtable_index: None,
+            node_id: NodeId::DUMMY,
         }
     }
 
@@ -1006,14 +1505,68 @@ impl CallExpr {
         self
     }
 
-    pub fn set_type_info(&mut self, type_info: TypeInfo) {
-        self.type_info.replace(type_info);
+    /// `Box::new(expr)` allocates on the heap instead of calling a function.
+    pub fn is_box_new(&self) -> bool {
+        self.is_path(&["Box", "new"])
+    }
+
+    /// `Vec::new()`, `String::new()` and `String::from(s)` create vectors by runtime calls.
+    pub fn is_vec_new(&self) -> bool {
+        self.is_path(&["Vec", "new"])
+            || self.is_path(&["String", "new"])
+            || self.is_path(&["String", "from"])
+    }
+
+    /// `read_volatile` or `write_volatile` in `read_volatile(p)`, `ptr::write_volatile(p, x)`,
+    /// `core::ptr::read_volatile(p)` or `std::ptr::read_volatile(p)`
+    pub fn volatile_intrinsic(&self) -> Option<&str> {
+        let (name, prefix) = match self.expr.deref() {
+            Expr::Path(p) => p.segments.split_last()?,
+            _ => return None,
+        };
+        let prefixes: [&[&str]; 4] = [&[], &["ptr"], &["core", "ptr"], &["std", "ptr"]];
+        match name.as_str() {
+            "read_volatile" | "write_volatile" if prefixes.iter().any(|p| prefix == *p) => {
+                Some(name)
+            }
+            _ => None,
+        }
+    }
+
+    pub fn is_path(&self, segments: &[&str]) -> bool {
+        matches!(self.expr.deref(), Expr::Path(p) if p.segments == segments)
+    }
+
+    /// `push` in `v.push(3)`
+    pub fn method_name(&self) -> Option<&str> {
+        match self.expr.deref() {
+            Expr::FieldAccess(FieldAccessExpr { rhs, .. }) => match rhs.deref() {
+                Expr::Path(p) if p.segments.len() == 1 => Some(&p.segments[0]),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    pub fn set_type_info(&self, types: &mut TypeTable, type_info: TypeInfo) {
+        let slot = self.type_slot(types);
+        types.set(slot, type_info);
+    }
+}
+
+impl PartialEq for CallExpr {
+    fn eq(&self, other: &Self) -> bool {
+        self.expr == other.expr && self.call_params == other.call_params
     }
 }
 
 impl ExprVisit for CallExpr {
-    fn type_info(&self) -> Rc<RefCell<TypeInfo>> {
-        self.type_info.clone()
+    fn type_slot(&self, types: &mut TypeTable) -> TypeSlot {
+        types.node_slot(self.node_id, TypeInfo::Unknown)
+    }
+
+    fn type_info(&self, types: &TypeTable) -> TypeInfo {
+        types.node_type(self.node_id).cloned().unwrap_or(TypeInfo::Unknown)
     }
 
     fn kind(&self) -> ExprKind {
@@ -1021,10 +1574,13 @@ impl ExprVisit for CallExpr {
     }
 }
 
-#[derive(Debug, PartialEq)]
+/// `a.b`, `a.b.c` is `(a.b).c`. The rhs is the method name of a method call.
+#[derive(Debug, Clone)]
 pub struct FieldAccessExpr {
     pub lhs: Box<Expr>,
     pub rhs: Box<Expr>,
+    pub expr_kind: ExprKind,
+    pub node_id: NodeId,
 }
 
 impl FieldAccessExpr {
@@ -1032,15 +1588,50 @@ impl FieldAccessExpr {
         FieldAccessExpr {
             lhs: Box::new(lhs),
             rhs: Box::new(rhs),
+            expr_kind: ExprKind::Unknown,
+            node_id: NodeId::DUMMY,
+        }
+    }
+
+    /// `b` in `a.b`
+    pub fn field_name(&self) -> Option<&str> {
+        match self.rhs.deref() {
+            Expr::Path(p) if p.segments.len() == 1 => Some(&p.segments[0]),
+            _ => None,
         }
     }
+
+    pub fn set_type_info(&self, types: &mut TypeTable, type_info: TypeInfo) {
+        let slot = self.type_slot(types);
+        types.set(slot, type_info);
+    }
 }
 
-#[derive(Debug, PartialEq)]
+impl PartialEq for FieldAccessExpr {
+    fn eq(&self, other: &Self) -> bool {
+        self.lhs == other.lhs && self.rhs == other.rhs && self.expr_kind == other.expr_kind
+    }
+}
+
+impl ExprVisit for FieldAccessExpr {
+    fn type_slot(&self, types: &mut TypeTable) -> TypeSlot {
+        types.node_slot(self.node_id, TypeInfo::Unknown)
+    }
+
+    fn type_info(&self, types: &TypeTable) -> TypeInfo {
+        types.node_type(self.node_id).cloned().unwrap_or(TypeInfo::Unknown)
+    }
+
+    fn kind(&self) -> ExprKind {
+        self.expr_kind
+    }
+}
+
+#[derive(Debug, Clone)]
 pub struct IfExpr {
     pub conditions: Vec<Expr>,
     pub blocks: Vec<BlockExpr>,
-    type_info: Rc<RefCell<TypeInfo>>,
+    pub node_id: NodeId,
 }
 
 impl IfExpr {
@@ -1048,7 +1639,7 @@ impl IfExpr {
         IfExpr {
             conditions: vec![],
             blocks: vec![],
-            type_info: Rc::new(RefCell::new(TypeInfo::Unknown)),
+            node_id: NodeId::DUMMY,
         }
     }
 
@@ -1056,7 +1647,7 @@ impl IfExpr {
         IfExpr {
             conditions,
             blocks,
-            type_info: Rc::new(RefCell::new(TypeInfo::Unknown)),
+            node_id: NodeId::DUMMY,
         }
     }
 
@@ -1069,9 +1660,19 @@ impl IfExpr {
     }
 }
 
+impl PartialEq for IfExpr {
+    fn eq(&self, other: &Self) -> bool {
+        self.conditions == other.conditions && self.blocks == other.blocks
+    }
+}
+
 impl ExprVisit for IfExpr {
-    fn type_info(&self) -> Rc<RefCell<TypeInfo>> {
-        self.type_info.clone()
+    fn type_slot(&self, types: &mut TypeTable) -> TypeSlot {
+        types.node_slot(self.node_id, TypeInfo::Unknown)
+    }
+
+    fn type_info(&self, types: &TypeTable) -> TypeInfo {
+        types.node_type(self.node_id).cloned().unwrap_or(TypeInfo::Unknown)
     }
 
     fn kind(&self) -> ExprKind {
@@ -1080,30 +1681,31 @@ impl ExprVisit for IfExpr {
 }
 
 impl TypeInfoSetter for IfExpr {
-    fn set_type_info(&mut self, type_info: TypeInfo) {
-        self.type_info.replace(type_info.clone());
-        for t in self.blocks.iter_mut() {
-            let tp = t.type_info();
-            let tp1 = tp.borrow();
-            let tp2 = tp1.deref();
-            if tp2 != &TypeInfo::Never {
-                std::mem::drop(tp1);
-                t.set_type_info(type_info.clone());
+    fn set_type_info(&self, types: &mut TypeTable, type_info: TypeInfo) {
+        let slot = self.type_slot(types);
+        types.set(slot, type_info.clone());
+        for t in self.blocks.iter() {
+            if t.type_info(types) != TypeInfo::Never {
+                t.set_type_info(types, type_info.clone());
             }
         }
     }
 
-    fn set_type_info_ref(&mut self, type_info: Rc<RefCell<TypeInfo>>) {
-        self.type_info = type_info;
+    fn set_type_info_ref(&self, types: &mut TypeTable, slot: TypeSlot) {
+        types.bind(self.node_id, slot);
     }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone)]
 pub struct WhileExpr(pub Box<Expr>, pub Box<BlockExpr>);
 
 impl ExprVisit for WhileExpr {
-    fn type_info(&self) -> Rc<RefCell<TypeInfo>> {
-        Rc::new(RefCell::new(TypeInfo::Unit))
+    fn type_slot(&self, types: &mut TypeTable) -> TypeSlot {
+        types.new_slot(TypeInfo::Unit)
+    }
+
+    fn type_info(&self, _types: &TypeTable) -> TypeInfo {
+        TypeInfo::Unit
     }
 
     fn kind(&self) -> ExprKind {
@@ -1111,24 +1713,56 @@ impl ExprVisit for WhileExpr {
     }
 }
 
-#[derive(Debug, PartialEq)]
+/// `for b in s.bytes() { .. }`, the pattern is bound in the scope of the block
+#[derive(Debug, PartialEq, Clone)]
+pub struct ForExpr {
+    pub pattern: Pattern,
+    pub expr: Box<Expr>,
+    pub block: Box<BlockExpr>,
+}
+
+impl ExprVisit for ForExpr {
+    fn type_slot(&self, types: &mut TypeTable) -> TypeSlot {
+        types.new_slot(TypeInfo::Unit)
+    }
+
+    fn type_info(&self, _types: &TypeTable) -> TypeInfo {
+        TypeInfo::Unit
+    }
+
+    fn kind(&self) -> ExprKind {
+        ExprKind::Value
+    }
+}
+
+#[derive(Debug, Clone)]
 pub struct LoopExpr {
     pub expr: Box<BlockExpr>,
-    type_info: Rc<RefCell<TypeInfo>>,
+    pub node_id: NodeId,
 }
 
 impl LoopExpr {
     pub fn new(expr: BlockExpr) -> LoopExpr {
         LoopExpr {
             expr: Box::new(expr),
-            type_info: Rc::new(RefCell::new(TypeInfo::Unknown)),
+            node_id: NodeId::DUMMY,
         }
     }
 }
 
+impl PartialEq for LoopExpr {
+    fn eq(&self, other: &Self) -> bool {
+        self.expr == other.expr
+    }
+}
+
 impl ExprVisit for LoopExpr {
-    fn type_info(&self) -> Rc<RefCell<TypeInfo>> {
-        self.type_info.clone()
+    fn type_slot(&self, types: &mut TypeTable) -> TypeSlot {
+        types.node_slot(self.node_id, TypeInfo::Unknown)
+    }
+
+    fn type_info(&self, types: &TypeTable) -> TypeInfo {
+        types.node_type(self.node_id).cloned().unwrap_or(TypeInfo::Unknown)
     }
 
     fn kind(&self) -> ExprKind {
@@ -1137,11 +1771,12 @@ impl ExprVisit for LoopExpr {
 }
 
 impl TypeInfoSetter for LoopExpr {
-    fn set_type_info(&mut self, type_info: TypeInfo) {
-        self.type_info = Rc::new(RefCell::new(type_info));
+    fn set_type_info(&self, types: &mut TypeTable, type_info: TypeInfo) {
+        let slot = types.new_slot(type_info);
+        types.bind(self.node_id, slot);
     }
 
-    fn set_type_info_ref(&mut self, type_info: Rc<RefCell<TypeInfo>>) {
-        self.type_info = type_info;
+    fn set_type_info_ref(&self, types: &mut TypeTable, slot: TypeSlot) {
+        types.bind(self.node_id, slot);
     }
 }
@@ -1,32 +1,60 @@
 use crate::ast::item::Item;
-use crate::analyser::scope::Scope;
-use crate::analyser::scope::BULITIN_SCOPE;
+use crate::analyser::scope::{ScopeArena, ScopeID};
+use crate::analyser::type_table::TypeTable;
+use crate::ast::NodeId;
+use crate::diagnostic::Span;
+use std::collections::HashMap;
 use std::fmt::{Debug, Formatter};
+use std::ops::Range;
 
 pub struct File {
     pub items: Vec<Item>,
-    pub scope: Scope,
+    pub scope_id: ScopeID,
+    /// all the scopes in the file
+    pub scopes: ScopeArena,
+    /// types of the expr nodes, filled by the symbol resolver
+    pub type_table: TypeTable,
+    /// range of each item in the token stream
+    pub item_tokens: Vec<Range<usize>>,
+    /// number of the expr nodes created by the parser, the ids of the nodes created later
+    /// start from it
+    pub node_count: u32,
+    /// block -> lines of its statements and last expr, for debug info
+    pub stmt_lines: HashMap<NodeId, Vec<u32>>,
+    /// node -> its span in the source, for diagnostics
+    pub spans: HashMap<NodeId, Span>,
+    /// body of a function -> span of its signature, for diagnostics
+    pub sig_spans: HashMap<NodeId, Span>,
+    /// call -> spans of its arguments, for diagnostics
+    pub arg_spans: HashMap<NodeId, Vec<Span>>,
+    /// (scope, name) of the functions, structs and constants -> span of the name
+    pub def_spans: HashMap<(ScopeID, String), Span>,
+    /// path -> span of the name of the item it refers to, filled by the symbol resolver
+    pub references: HashMap<NodeId, Span>,
 }
 
 impl File {
-    pub fn new(scope_id: u64) -> File {
-        let mut file_scope = Scope::new(scope_id);
-        file_scope.set_father_as_builtin_scope();
+    pub fn new(scope_id: ScopeID) -> File {
         File {
             items: vec![],
-            scope: file_scope
+            scope_id,
+            scopes: ScopeArena::new(),
+            type_table: TypeTable::new(),
+            item_tokens: vec![],
+            node_count: 0,
+            stmt_lines: HashMap::new(),
+            spans: HashMap::new(),
+            sig_spans: HashMap::new(),
+            arg_spans: HashMap::new(),
+            def_spans: HashMap::new(),
+            references: HashMap::new(),
         }
     }
-
-    pub fn items(mut self, items: Vec<Item>) -> File {
-        self.items = items;
-        self
-    }
 }
 
 impl Debug for File {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{:?}", self.items)
+        f.debug_list().entries(self.items.iter()).finish()
     }
 }
 
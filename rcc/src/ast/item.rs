@@ -1,11 +1,13 @@
-use crate::ast::expr::BlockExpr;
+use crate::ast::expr::{BlockExpr, Expr};
 use crate::ast::pattern::Pattern;
 use crate::ast::types::TypeAnnotation;
 use crate::ast::{NamedASTNode, TokenStart, Visibility};
+use crate::ir::mangle::{impl_fn_name, inherent_item_name};
 use crate::lexer::token::Token;
 use crate::rcc::RccError;
+use std::fmt;
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone)]
 pub enum Item {
     /// fn add(a, b) { a + b }
     Fn(ItemFn),
@@ -17,41 +19,74 @@ pub enum Item {
     Enum(TypeEnum),
 
     /// type Int = i32;
-    Type,
+    Type(ItemTypeAlias),
 
     /// const A: i32 = 2;
-    Const,
+    Const(ItemConst),
 
     /// static B: i32 = 3;
     Static,
 
-    /// impl Foo { ... }
-    Impl,
+    /// impl Add for i32 { ... }
+    Impl(ItemImpl),
+
+    /// trait Add { fn add(self, rhs: Self) -> Self; }
+    Trait(ItemTrait),
 
     /// extern "C" {}
     ExternalBlock(ItemExternalBlock),
+
+    /// #[cfg(feature = "foo")] fn foo() {}
+    Cfg(ItemCfg),
 }
 
 impl TokenStart for Item {
     fn is_token_start(tk: &Token) -> bool {
         matches!(
             tk,
-            Token::Pub
+            Token::Pound
+                | Token::Pub
                 | Token::Priv
                 | Token::Fn
                 | Token::Const
                 | Token::Static
                 | Token::Struct
                 | Token::Enum
+                | Token::Type
                 | Token::Impl
+                | Token::Trait
+                | Token::Extern
+                | Token::Unsafe
         )
     }
 }
 
+impl Item {
+    /// Names of the functions, structs, type aliases and constants defined by the item in its
+    /// scope
+    pub fn defined_names(&self) -> Vec<&str> {
+        match self {
+            Self::Fn(item_fn) => vec![&item_fn.name],
+            Self::Struct(item_struct) => vec![item_struct.name()],
+            Self::Const(item_const) => vec![&item_const.name],
+            Self::Type(item_type_alias) => vec![&item_type_alias.name],
+            Self::ExternalBlock(item_external_block) => item_external_block
+                .external_items
+                .iter()
+                .map(|item| match item {
+                    ExternalItem::Fn(f) => f.name.as_str(),
+                })
+                .collect(),
+            _ => vec![],
+        }
+    }
+}
+
 impl NamedASTNode for Item {
     fn ident_name(&self) -> &str {
         match self {
             Self::Fn(item_fn) => item_fn.ident_name(),
+            Self::Const(item_const) => &item_const.name,
             _ => unimplemented!(),
         }
     }
@@ -64,13 +99,33 @@ pub trait FnSignature {
     fn ret_type(&self) -> TypeAnnotation;
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone)]
 pub struct ItemFn {
     vis: Visibility,
     pub name: String,
+    /// `fn add<T: Add>(a: T, b: T) -> T`, a generic function is only compiled as its instances
+    pub generics: Vec<GenericParam>,
     pub fn_params: FnParams,
     pub ret_type: TypeAnnotation,
     pub fn_block: BlockExpr,
+    /// `Some` if the function is defined as `extern "C" fn`
+    pub abi: Option<ABI>,
+    /// `const fn`, which can be called in constants
+    pub is_const: bool,
+    /// `unsafe fn`, whose body is an unsafe context and whose calls must be in one
+    pub is_unsafe: bool,
+    /// lines of the `///` doc comments, without `///`
+    pub docs: Vec<String>,
+    /// Section of the code given by `#[link_section = "..."]`, `.text` if it is `None`
+    pub link_section: Option<String>,
+    /// `#[no_mangle]`, the symbol is the name of the function and it is global
+    pub no_mangle: bool,
+    /// `#[inline]`, `#[inline(always)]` or `#[inline(never)]`. Functions are not inlined yet,
+    /// so all of them are recorded as hints only.
+    pub inline: Option<InlineHint>,
+    /// `#[cold]`, the function is unlikely to be called, so the blocks calling it are laid
+    /// out after the other blocks of the caller
+    pub is_cold: bool,
 }
 
 impl ItemFn {
@@ -84,11 +139,90 @@ impl ItemFn {
         ItemFn {
             vis,
             name,
+            generics: vec![],
             fn_params,
             ret_type,
             fn_block,
+            abi: None,
+            is_const: false,
+            is_unsafe: false,
+            docs: vec![],
+            link_section: None,
+            no_mangle: false,
+            inline: None,
+            is_cold: false,
+        }
+    }
+
+    pub fn generics(mut self, generics: Vec<GenericParam>) -> ItemFn {
+        self.generics = generics;
+        self
+    }
+
+    pub fn is_generic(&self) -> bool {
+        !self.generics.is_empty()
+    }
+
+    /// The first param is `self`, `&self` or `&mut self`
+    pub fn has_receiver(&self) -> bool {
+        match self.fn_params.params.first() {
+            Some(FnParam {
+                pattern: Pattern::Identifier(ident_pattern),
+                ..
+            }) => ident_pattern.ident() == "self",
+            _ => false,
         }
     }
+
+    pub fn abi(mut self, abi: ABI) -> ItemFn {
+        self.abi = Some(abi);
+        self
+    }
+
+    pub fn const_fn(mut self) -> ItemFn {
+        self.is_const = true;
+        self
+    }
+
+    pub fn unsafe_fn(mut self) -> ItemFn {
+        self.is_unsafe = true;
+        self
+    }
+
+    pub fn docs(mut self, docs: Vec<String>) -> ItemFn {
+        self.docs = docs;
+        self
+    }
+
+    pub fn link_section(mut self, link_section: String) -> ItemFn {
+        self.link_section = Some(link_section);
+        self
+    }
+
+    pub fn no_mangle(mut self) -> ItemFn {
+        self.no_mangle = true;
+        self
+    }
+
+    pub fn inline(mut self, inline: InlineHint) -> ItemFn {
+        self.inline = Some(inline);
+        self
+    }
+
+    pub fn cold(mut self) -> ItemFn {
+        self.is_cold = true;
+        self
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum InlineHint {
+    /// `#[inline]`
+    Hint,
+    /// `#[inline(always)]`
+    Always,
+    /// `#[inline(never)]`
+    Never,
 }
 
 impl FnSignature for ItemFn {
@@ -109,7 +243,21 @@ impl FnSignature for ItemFn {
     }
 }
 
-#[derive(Debug, PartialEq)]
+/// `T: Add + Sub`
+#[derive(Debug, PartialEq, Clone)]
+pub struct GenericParam {
+    pub name: String,
+    /// names of the traits which `T` must implement
+    pub bounds: Vec<String>,
+}
+
+impl GenericParam {
+    pub fn new(name: String, bounds: Vec<String>) -> GenericParam {
+        GenericParam { name, bounds }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
 pub struct FnParams {
     pub params: Vec<FnParam>,
 }
@@ -137,7 +285,7 @@ impl From<Vec<FnParam>> for FnParams {
     }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone)]
 pub struct FnParam {
     pub pattern: Pattern,
     pub _type: TypeAnnotation,
@@ -151,7 +299,7 @@ impl FnParam {
 
 impl TokenStart for FnParam {
     fn is_token_start(tk: &Token) -> bool {
-        Pattern::is_token_start(tk)
+        matches!(tk, Token::SelfValue | Token::And) || Pattern::is_token_start(tk)
     }
 }
 
@@ -164,11 +312,13 @@ impl NamedASTNode for ItemFn {
 /// # Examples
 /// `struct Student { name: String, age: u32 }`
 /// `pub struct Teacher(String, u32);`
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone)]
 pub struct ItemStruct {
     vis: Visibility,
     name: String,
     fields: Fields,
+    /// lines of the `///` doc comments, without `///`
+    pub docs: Vec<String>,
 }
 
 impl ItemStruct {
@@ -177,9 +327,15 @@ impl ItemStruct {
             vis,
             name,
             fields: Fields::None,
+            docs: vec![],
         }
     }
 
+    pub fn docs(mut self, docs: Vec<String>) -> Self {
+        self.docs = docs;
+        self
+    }
+
     pub fn struct_fields(mut self, struct_fields: Vec<StructField>) -> Self {
         self.fields = Fields::Struct(struct_fields);
         self
@@ -215,6 +371,12 @@ pub struct TypeEnum {
     enum_items: Vec<EnumVariant>,
 }
 
+impl TypeEnum {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+}
+
 #[derive(Debug, PartialEq, Clone, Eq, Hash)]
 pub struct EnumVariant {
     name: String,
@@ -245,7 +407,7 @@ pub struct TupleField {
 }
 
 /// `extern "C" { fn foo(); }`
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone)]
 pub struct ItemExternalBlock {
     abi: ABI,
     pub external_items: Vec<ExternalItem>,
@@ -258,9 +420,13 @@ impl ItemExternalBlock {
             external_items,
         }
     }
+
+    pub fn abi(&self) -> ABI {
+        self.abi
+    }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone, Copy)]
 pub enum ABI {
     C,
 }
@@ -274,17 +440,102 @@ impl ABI {
     }
 }
 
-#[derive(Debug, PartialEq)]
+/// An item which is only compiled if the predicate of its `#[cfg(...)]` holds. It is
+/// replaced by the item or dropped before the symbols are resolved, see `analyser::cfg`.
+#[derive(Debug, PartialEq, Clone)]
+pub struct ItemCfg {
+    pub cfg: Attribute,
+    pub item: Box<Item>,
+}
+
+impl ItemCfg {
+    pub fn new(cfg: Attribute, item: Item) -> ItemCfg {
+        ItemCfg {
+            cfg,
+            item: Box::new(item),
+        }
+    }
+
+    /// `P` of `#[cfg(P)]`
+    pub fn predicate(&self) -> &Attribute {
+        &self.cfg.args[0]
+    }
+}
+
+/// OuterAttribute -> `#` `[` Meta `]`
+///
+/// Meta -> identifier ( `=` LitStr | `(` ( Meta `,` )* Meta? `)` )?
+///
+/// e.g. `#[no_mangle]`, `#[link_section = ".fast_ram"]` and `#[inline(always)]`, whose
+/// args are the nested metas.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Attribute {
+    pub name: String,
+    pub value: Option<String>,
+    pub args: Vec<Attribute>,
+}
+
+impl Attribute {
+    pub fn new(name: String, value: Option<String>) -> Attribute {
+        Attribute {
+            name,
+            value,
+            args: vec![],
+        }
+    }
+
+    pub fn args(mut self, args: Vec<Attribute>) -> Attribute {
+        self.args = args;
+        self
+    }
+
+    /// `#[name]`
+    pub fn is_word(&self) -> bool {
+        self.value.is_none() && self.args.is_empty()
+    }
+
+    /// The names of the args of `#[name(a, b)]`, which are words
+    pub fn word_args(&self) -> Option<Vec<&str>> {
+        self.args
+            .iter()
+            .map(|arg| arg.is_word().then_some(arg.name.as_str()))
+            .collect()
+    }
+}
+
+impl fmt::Display for Attribute {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name)?;
+        if let Some(value) = &self.value {
+            write!(f, " = {:?}", value)?;
+        }
+        if !self.args.is_empty() {
+            let args: Vec<String> = self.args.iter().map(|arg| arg.to_string()).collect();
+            write!(f, "({})", args.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+impl TokenStart for Attribute {
+    fn is_token_start(tk: &Token) -> bool {
+        tk == &Token::Pound
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
 pub enum ExternalItem {
     Fn(ExternalItemFn),
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone)]
 pub struct ExternalItemFn {
     vis: Visibility,
     pub name: String,
     pub fn_params: FnParams,
     pub ret_type: TypeAnnotation,
+    /// Symbol of the function given by `#[link_name = "..."]`
+    pub link_name: Option<String>,
 }
 
 impl ExternalItemFn {
@@ -299,8 +550,14 @@ impl ExternalItemFn {
             name,
             fn_params,
             ret_type,
+            link_name: None,
         }
     }
+
+    pub fn link_name(mut self, link_name: String) -> ExternalItemFn {
+        self.link_name = Some(link_name);
+        self
+    }
 }
 
 impl FnSignature for ExternalItemFn {
@@ -318,3 +575,126 @@ impl FnSignature for ExternalItemFn {
         self.ret_type.clone()
     }
 }
+
+/// `const N: usize = size(3);`, the value is computed at compile time.
+#[derive(Debug, PartialEq, Clone)]
+pub struct ItemConst {
+    pub vis: Visibility,
+    pub name: String,
+    pub type_anno: TypeAnnotation,
+    pub expr: Expr,
+}
+
+impl ItemConst {
+    pub fn new(vis: Visibility, name: String, type_anno: TypeAnnotation, expr: Expr) -> ItemConst {
+        ItemConst {
+            vis,
+            name,
+            type_anno,
+            expr,
+        }
+    }
+}
+
+/// `type Meters = i32;`, the alias is the same type as its target wherever it is used.
+#[derive(Debug, PartialEq, Clone)]
+pub struct ItemTypeAlias {
+    pub vis: Visibility,
+    pub name: String,
+    pub type_anno: TypeAnnotation,
+}
+
+impl ItemTypeAlias {
+    pub fn new(vis: Visibility, name: String, type_anno: TypeAnnotation) -> ItemTypeAlias {
+        ItemTypeAlias {
+            vis,
+            name,
+            type_anno,
+        }
+    }
+}
+
+/// `trait Add { fn add(self, rhs: Self) -> Self; }`
+#[derive(Debug, PartialEq, Clone)]
+pub struct ItemTrait {
+    vis: Visibility,
+    pub name: String,
+    pub fns: Vec<TraitItemFn>,
+}
+
+impl ItemTrait {
+    pub fn new(vis: Visibility, name: String, fns: Vec<TraitItemFn>) -> ItemTrait {
+        ItemTrait { vis, name, fns }
+    }
+
+    pub fn vis(&self) -> Visibility {
+        self.vis
+    }
+}
+
+/// Signature of a method required by a trait, `Self` is the implementing type.
+#[derive(Debug, PartialEq, Clone)]
+pub struct TraitItemFn {
+    pub name: String,
+    pub fn_params: FnParams,
+    pub ret_type: TypeAnnotation,
+}
+
+impl TraitItemFn {
+    pub fn new(name: String, fn_params: FnParams, ret_type: TypeAnnotation) -> TraitItemFn {
+        TraitItemFn {
+            name,
+            fn_params,
+            ret_type,
+        }
+    }
+}
+
+/// `impl Add for i32 { fn add(self, rhs: i32) -> i32 { ... } }`, or an inherent impl
+/// `impl Point { const N: i32 = 2; fn len(&self) -> i32 { ... } }` without a trait.
+///
+/// `Self` in the methods and constants is replaced with `self_type` by the parser.
+#[derive(Debug, PartialEq, Clone)]
+pub struct ItemImpl {
+    pub trait_name: Option<String>,
+    pub self_type: TypeAnnotation,
+    pub fns: Vec<ItemFn>,
+    /// associated constants, moved to the items of the file by the symbol resolver
+    pub consts: Vec<ItemConst>,
+}
+
+impl ItemImpl {
+    pub fn new(
+        trait_name: Option<String>,
+        self_type: TypeAnnotation,
+        fns: Vec<ItemFn>,
+    ) -> ItemImpl {
+        ItemImpl {
+            trait_name,
+            self_type,
+            fns,
+            consts: vec![],
+        }
+    }
+
+    pub fn consts(mut self, consts: Vec<ItemConst>) -> ItemImpl {
+        self.consts = consts;
+        self
+    }
+
+    /// Symbol of the method `item_fn` of this impl
+    pub fn fn_symbol(&self, item_fn: &ItemFn) -> String {
+        match &self.trait_name {
+            Some(trait_name) => impl_fn_name(&self.self_type_name(), trait_name, &item_fn.name),
+            None => inherent_item_name(&self.self_type_name(), &item_fn.name),
+        }
+    }
+
+    /// Name of `self_type`, which is a primitive type or a struct
+    pub fn self_type_name(&self) -> String {
+        match &self.self_type {
+            TypeAnnotation::Identifier(name) => name.clone(),
+            t => unreachable!("impl for `{:?}`", t),
+        }
+    }
+}
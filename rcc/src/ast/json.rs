@@ -0,0 +1,448 @@
+//! JSON form of the AST for external tools, printed by `--emit ast-json`.
+//!
+//! ```text
+//! {"kind":"File","items":[{"kind":"Fn","name":"main","pub":false,"const":false,
+//!  "unsafe":false,"abi":null,"docs":[],"generics":[],"params":[],"ret_type":"i32",
+//!  "block":{"kind":"Block","stmts":[],"expr":{"kind":"LitNum","value":"0","suffix":null},
+//!  "unsafe":false}}]}
+//! ```
+//!
+//! Every node is an object whose `kind` is the name of its variant. Types are written as
+//! source text, and the AST is dumped before symbol resolution, so nothing is resolved.
+use crate::ast::expr::{BlockExpr, Expr, LhsExpr, UnOp};
+use crate::ast::file::File;
+use crate::ast::item::{
+    ExternalItem, Fields, FnParams, FnSignature, GenericParam, Item, ItemConst, ItemFn,
+    ItemStruct,
+};
+use crate::ast::pattern::Pattern;
+use crate::ast::printer::type_anno;
+use crate::ast::stmt::Stmt;
+use crate::ast::types::TypeAnnotation;
+use crate::ast::Visibility;
+use crate::json::Json;
+
+fn node(kind: &str, mut members: Vec<(&str, Json)>) -> Json {
+    members.insert(0, ("kind", Json::from(kind)));
+    Json::object(members)
+}
+
+fn string(s: &str) -> Json {
+    Json::from(s)
+}
+
+fn array<'a, T>(elems: &'a [T], f: impl Fn(&'a T) -> Json) -> Json {
+    Json::Array(elems.iter().map(f).collect())
+}
+
+fn optional<T>(value: Option<T>, f: impl Fn(T) -> Json) -> Json {
+    value.map_or(Json::Null, f)
+}
+
+fn type_json(t: &TypeAnnotation) -> Json {
+    Json::String(type_anno(t))
+}
+
+fn is_pub(vis: Visibility) -> Json {
+    Json::Bool(vis == Visibility::Pub)
+}
+
+fn docs(docs: &[String]) -> Json {
+    array(docs, |doc| string(doc))
+}
+
+impl From<&File> for Json {
+    fn from(file: &File) -> Self {
+        node("File", vec![("items", array(&file.items, Json::from))])
+    }
+}
+
+fn fn_params(fn_params: &FnParams) -> Json {
+    array(&fn_params.params, |param| {
+        Json::object(vec![
+            ("pattern", Json::from(&param.pattern)),
+            ("type", type_json(&param._type)),
+        ])
+    })
+}
+
+fn generic_param(param: &GenericParam) -> Json {
+    Json::object(vec![
+        ("name", string(&param.name)),
+        ("bounds", array(&param.bounds, |b| string(b))),
+    ])
+}
+
+fn item_const_json(item_const: &ItemConst) -> Json {
+    node(
+        "Const",
+        vec![
+            ("name", string(&item_const.name)),
+            ("pub", is_pub(item_const.vis)),
+            ("type", type_json(&item_const.type_anno)),
+            ("expr", Json::from(&item_const.expr)),
+        ],
+    )
+}
+
+fn item_fn(item_fn: &ItemFn) -> Json {
+    node(
+        "Fn",
+        vec![
+            ("name", string(&item_fn.name)),
+            ("pub", is_pub(item_fn.vis())),
+            ("const", Json::Bool(item_fn.is_const)),
+            ("unsafe", Json::Bool(item_fn.is_unsafe)),
+            ("abi", optional(item_fn.abi, |abi| string(&format!("{:?}", abi)))),
+            ("docs", docs(&item_fn.docs)),
+            ("link_section", optional(item_fn.link_section.as_deref(), string)),
+            ("no_mangle", Json::Bool(item_fn.no_mangle)),
+            ("inline", optional(item_fn.inline, |inline| string(&format!("{:?}", inline)))),
+            ("cold", Json::Bool(item_fn.is_cold)),
+            ("generics", array(&item_fn.generics, generic_param)),
+            ("params", fn_params(&item_fn.fn_params)),
+            ("ret_type", type_json(&item_fn.ret_type)),
+            ("block", Json::from(&item_fn.fn_block)),
+        ],
+    )
+}
+
+fn item_struct(item_struct: &ItemStruct) -> Json {
+    let fields = match item_struct.fields() {
+        Fields::None => Json::Null,
+        Fields::Tuple(fields) => array(fields, |field| {
+            Json::object(vec![
+                ("pub", is_pub(field.vis)),
+                ("type", type_json(&field._type)),
+            ])
+        }),
+        Fields::Struct(fields) => array(fields, |field| {
+            Json::object(vec![
+                ("pub", is_pub(field.vis)),
+                ("name", string(&field.name)),
+                ("type", type_json(&field._type)),
+            ])
+        }),
+    };
+    node(
+        "Struct",
+        vec![
+            ("name", string(item_struct.name())),
+            ("pub", is_pub(item_struct.vis())),
+            ("docs", docs(&item_struct.docs)),
+            ("tuple", Json::Bool(matches!(item_struct.fields(), Fields::Tuple(_)))),
+            ("fields", fields),
+        ],
+    )
+}
+
+impl From<&Item> for Json {
+    fn from(item: &Item) -> Self {
+        match item {
+            Item::Fn(f) => item_fn(f),
+            Item::Struct(s) => item_struct(s),
+            Item::Const(item_const) => item_const_json(item_const),
+            Item::Trait(item_trait) => node(
+                "Trait",
+                vec![
+                    ("name", string(&item_trait.name)),
+                    ("pub", is_pub(item_trait.vis())),
+                    (
+                        "fns",
+                        array(&item_trait.fns, |f| {
+                            Json::object(vec![
+                                ("name", string(&f.name)),
+                                ("params", fn_params(&f.fn_params)),
+                                ("ret_type", type_json(&f.ret_type)),
+                            ])
+                        }),
+                    ),
+                ],
+            ),
+            Item::Impl(item_impl) => node(
+                "Impl",
+                vec![
+                    ("trait", optional(item_impl.trait_name.as_deref(), string)),
+                    ("self_type", type_json(&item_impl.self_type)),
+                    ("fns", array(&item_impl.fns, item_fn)),
+                    ("consts", array(&item_impl.consts, item_const_json)),
+                ],
+            ),
+            Item::ExternalBlock(block) => node(
+                "ExternalBlock",
+                vec![
+                    ("abi", string(&format!("{:?}", block.abi()))),
+                    (
+                        "fns",
+                        array(&block.external_items, |ExternalItem::Fn(f)| {
+                            Json::object(vec![
+                                ("name", string(&f.name)),
+                                ("pub", is_pub(f.vis())),
+                                ("link_name", optional(f.link_name.as_deref(), string)),
+                                ("params", fn_params(&f.fn_params)),
+                                ("ret_type", type_json(&f.ret_type)),
+                            ])
+                        }),
+                    ),
+                ],
+            ),
+            Item::Enum(_) => node("Enum", vec![]),
+            Item::Type(item_type_alias) => node(
+                "Type",
+                vec![
+                    ("name", string(&item_type_alias.name)),
+                    ("pub", is_pub(item_type_alias.vis)),
+                    ("type", type_json(&item_type_alias.type_anno)),
+                ],
+            ),
+            Item::Static => node("Static", vec![]),
+            Item::Cfg(item_cfg) => node(
+                "Cfg",
+                vec![
+                    ("predicate", string(&item_cfg.predicate().to_string())),
+                    ("item", Json::from(item_cfg.item.as_ref())),
+                ],
+            ),
+        }
+    }
+}
+
+impl From<&Pattern> for Json {
+    fn from(pattern: &Pattern) -> Self {
+        match pattern {
+            Pattern::Identifier(ident_pattern) => node(
+                "Identifier",
+                vec![
+                    ("ident", string(ident_pattern.ident())),
+                    ("mut", Json::Bool(ident_pattern.is_mut())),
+                ],
+            ),
+            Pattern::Wild => node("Wild", vec![]),
+            Pattern::Tuple(patterns) => {
+                node("Tuple", vec![("patterns", array(patterns, Json::from))])
+            }
+        }
+    }
+}
+
+impl From<&Stmt> for Json {
+    fn from(stmt: &Stmt) -> Self {
+        match stmt {
+            Stmt::Semi => node("Semi", vec![]),
+            Stmt::Item(item) => Json::from(item),
+            Stmt::Let(let_stmt) => node(
+                "Let",
+                vec![
+                    ("pattern", Json::from(&let_stmt.pattern)),
+                    ("type", optional(let_stmt._type.as_ref(), type_json)),
+                    ("rhs", optional(let_stmt.rhs.as_ref(), Json::from)),
+                ],
+            ),
+            Stmt::ExprStmt(expr) => node("ExprStmt", vec![("expr", Json::from(expr))]),
+        }
+    }
+}
+
+impl From<&BlockExpr> for Json {
+    fn from(block: &BlockExpr) -> Self {
+        node(
+            "Block",
+            vec![
+                ("stmts", array(&block.stmts, Json::from)),
+                ("expr", optional(block.last_expr.as_deref(), Json::from)),
+                ("unsafe", Json::Bool(block.is_unsafe)),
+            ],
+        )
+    }
+}
+
+impl From<&LhsExpr> for Json {
+    fn from(lhs_expr: &LhsExpr) -> Self {
+        match lhs_expr {
+            LhsExpr::Path(path_expr) => Json::from(&Expr::Path(path_expr.clone())),
+            LhsExpr::ArrayIndex(e) => Json::from(&Expr::ArrayIndex(e.clone())),
+            LhsExpr::TupleIndex(e) => Json::from(&Expr::TupleIndex(e.clone())),
+            LhsExpr::FieldAccess(e) => Json::from(&Expr::FieldAccess(e.clone())),
+            LhsExpr::Deref(expr) => node(
+                "Unary",
+                vec![("op", string("*")), ("expr", Json::from(expr.as_ref()))],
+            ),
+        }
+    }
+}
+
+impl From<&Expr> for Json {
+    fn from(expr: &Expr) -> Self {
+        match expr {
+            Expr::Path(path_expr) => node(
+                "Path",
+                vec![("segments", array(&path_expr.segments, |s| string(s)))],
+            ),
+            Expr::LitNum(lit_num_expr) => node(
+                "LitNum",
+                vec![
+                    ("value", string(&lit_num_expr.value)),
+                    ("suffix", optional(lit_num_expr.suffix(), |t| string(&t.to_string()))),
+                ],
+            ),
+            Expr::LitBool(b) => node("LitBool", vec![("value", Json::Bool(*b))]),
+            Expr::LitChar(c) => node("LitChar", vec![("value", string(&c.to_string()))]),
+            Expr::LitStr(s) => node("LitStr", vec![("value", string(s))]),
+            Expr::LitByteStr(bytes) => node(
+                "LitByteStr",
+                vec![("value", array(bytes, |b| Json::from(*b as u64)))],
+            ),
+            Expr::Unary(unary_expr) => {
+                let op = match unary_expr.op {
+                    UnOp::Deref => "*",
+                    UnOp::Not => "!",
+                    UnOp::Neg => "-",
+                    UnOp::Borrow => "&",
+                    UnOp::BorrowMut => "&mut",
+                };
+                node(
+                    "Unary",
+                    vec![("op", string(op)), ("expr", Json::from(unary_expr.expr.as_ref()))],
+                )
+            }
+            Expr::Block(block) => Json::from(block),
+            Expr::Assign(assign_expr) => node(
+                "Assign",
+                vec![
+                    ("op", string(&assign_expr.assign_op.to_string())),
+                    ("lhs", Json::from(&assign_expr.lhs)),
+                    ("rhs", Json::from(assign_expr.rhs.as_ref())),
+                ],
+            ),
+            Expr::Range(range_expr) => node(
+                "Range",
+                vec![
+                    ("op", string(&range_expr.range_op.to_string())),
+                    ("lhs", optional(range_expr.lhs.as_deref(), Json::from)),
+                    ("rhs", optional(range_expr.rhs.as_deref(), Json::from)),
+                ],
+            ),
+            Expr::BinOp(bin_op_expr) => node(
+                "BinOp",
+                vec![
+                    ("op", string(&bin_op_expr.bin_op.to_string())),
+                    ("lhs", Json::from(bin_op_expr.lhs.as_ref())),
+                    ("rhs", Json::from(bin_op_expr.rhs.as_ref())),
+                ],
+            ),
+            Expr::Cast(cast_expr) => node(
+                "Cast",
+                vec![
+                    ("expr", Json::from(cast_expr.expr.as_ref())),
+                    ("type", type_json(&cast_expr.type_anno)),
+                ],
+            ),
+            Expr::Grouped(expr) => node("Grouped", vec![("expr", Json::from(expr.as_ref()))]),
+            Expr::Array(array_expr) => node(
+                "Array",
+                vec![
+                    ("elems", array(&array_expr.elems, Json::from)),
+                    ("len", optional(array_expr.len_expr.as_deref(), Json::from)),
+                ],
+            ),
+            Expr::ArrayIndex(array_index_expr) => node(
+                "ArrayIndex",
+                vec![
+                    ("expr", Json::from(array_index_expr.expr.as_ref())),
+                    ("index", Json::from(array_index_expr.index_expr.as_ref())),
+                ],
+            ),
+            Expr::Tuple(tuple_expr) => {
+                node("Tuple", vec![("elems", array(&tuple_expr.elems, Json::from))])
+            }
+            Expr::Struct(struct_expr) => node(
+                "Struct",
+                vec![
+                    ("name", string(&struct_expr.name)),
+                    (
+                        "fields",
+                        array(&struct_expr.fields, |(name, expr)| {
+                            Json::object(vec![("name", string(name)), ("expr", Json::from(expr))])
+                        }),
+                    ),
+                    ("base", optional(struct_expr.base.as_deref(), Json::from)),
+                ],
+            ),
+            Expr::Call(call_expr) => node(
+                "Call",
+                vec![
+                    ("expr", Json::from(call_expr.expr.as_ref())),
+                    ("args", array(&call_expr.call_params, Json::from)),
+                ],
+            ),
+            Expr::FieldAccess(field_access_expr) => node(
+                "FieldAccess",
+                vec![
+                    ("lhs", Json::from(field_access_expr.lhs.as_ref())),
+                    ("rhs", Json::from(field_access_expr.rhs.as_ref())),
+                ],
+            ),
+            Expr::While(while_expr) => node(
+                "While",
+                vec![
+                    ("cond", Json::from(while_expr.0.as_ref())),
+                    ("block", Json::from(while_expr.1.as_ref())),
+                ],
+            ),
+            Expr::Loop(loop_expr) => {
+                node("Loop", vec![("block", Json::from(loop_expr.expr.as_ref()))])
+            }
+            Expr::For(for_expr) => node(
+                "For",
+                vec![
+                    ("pattern", Json::from(&for_expr.pattern)),
+                    ("expr", Json::from(for_expr.expr.as_ref())),
+                    ("block", Json::from(for_expr.block.as_ref())),
+                ],
+            ),
+            Expr::If(if_expr) => node(
+                "If",
+                vec![
+                    ("conds", array(&if_expr.conditions, Json::from)),
+                    ("blocks", array(&if_expr.blocks, Json::from)),
+                ],
+            ),
+            Expr::Return(return_expr) => node(
+                "Return",
+                vec![("expr", optional(return_expr.0.as_deref(), Json::from))],
+            ),
+            Expr::Break(break_expr) => {
+                node("Break", vec![("expr", optional(break_expr.0.as_deref(), Json::from))])
+            }
+            Expr::Print(print_expr) => node(
+                "Print",
+                vec![
+                    ("newline", Json::Bool(print_expr.newline)),
+                    ("pieces", array(&print_expr.pieces, |s| string(s))),
+                    ("args", array(&print_expr.args, Json::from)),
+                ],
+            ),
+            Expr::Assert(assert_expr) => node(
+                "Assert",
+                vec![
+                    ("macro", string(assert_expr.kind.macro_name())),
+                    ("cond", Json::from(assert_expr.cond.as_ref())),
+                    ("msg", optional(assert_expr.msg.as_ref(), |s| string(s))),
+                    ("line", Json::Number(f64::from(assert_expr.line))),
+                ],
+            ),
+            Expr::Asm(asm_expr) => node(
+                "Asm",
+                vec![
+                    ("template", string(&asm_expr.template)),
+                    ("inputs", array(&asm_expr.inputs, Json::from)),
+                    ("outputs", array(&asm_expr.outputs, Json::from)),
+                ],
+            ),
+            Expr::TupleIndex(_) => node("TupleIndex", vec![]),
+            Expr::EnumVariant => node("EnumVariant", vec![]),
+            Expr::MethodCall => node("MethodCall", vec![]),
+            Expr::Match => node("Match", vec![]),
+        }
+    }
+}
@@ -7,6 +7,9 @@ pub mod types;
 pub mod expr;
 pub mod stmt;
 pub mod pattern;
+pub mod json;
+pub mod printer;
+pub mod visit;
 
 #[macro_export]
 macro_rules! from_token {
@@ -50,6 +53,15 @@ from_token! {
 }
 
 
+/// Id of an expr node whose type is kept in `TypeTable`, assigned by the parser.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct NodeId(pub u32);
+
+impl NodeId {
+    /// Id of the nodes not created by the parser
+    pub const DUMMY: NodeId = NodeId(u32::MAX);
+}
+
 pub trait NamedASTNode {
     fn ident_name(&self) -> &str;
 }
@@ -1,18 +1,56 @@
 use crate::ast::TokenStart;
 use crate::lexer::token::Token;
+use std::fmt;
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone)]
 pub enum Pattern {
     Identifier(IdentPattern),
+    /// `_`
+    Wild,
+    /// `(a, _, (b, c))`
+    Tuple(Vec<Pattern>),
+}
+
+impl Pattern {
+    /// Variable bound by an identifier pattern. The value matched by `_` is kept in the
+    /// variable `_`, which can not be used in expressions. `None` for a tuple pattern.
+    pub fn var_name(&self) -> Option<&str> {
+        match self {
+            Pattern::Identifier(ident_pattern) => Some(ident_pattern.ident()),
+            Pattern::Wild => Some("_"),
+            Pattern::Tuple(_) => None,
+        }
+    }
+
+    pub fn is_mut(&self) -> bool {
+        matches!(self, Pattern::Identifier(ident_pattern) if ident_pattern.is_mut())
+    }
 }
 
 impl TokenStart for Pattern {
     fn is_token_start(tk: &Token) -> bool {
-        IdentPattern::is_token_start(tk)
+        IdentPattern::is_token_start(tk) || matches!(tk, Token::Mut | Token::LeftParen)
+    }
+}
+
+impl fmt::Display for Pattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Pattern::Identifier(ident_pattern) if ident_pattern.is_mut() => {
+                write!(f, "mut {}", ident_pattern.ident())
+            }
+            Pattern::Identifier(ident_pattern) => write!(f, "{}", ident_pattern.ident()),
+            Pattern::Wild => write!(f, "_"),
+            Pattern::Tuple(patterns) if patterns.len() == 1 => write!(f, "({},)", patterns[0]),
+            Pattern::Tuple(patterns) => {
+                let patterns: Vec<String> = patterns.iter().map(|p| p.to_string()).collect();
+                write!(f, "({})", patterns.join(", "))
+            }
+        }
     }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone)]
 pub struct IdentPattern {
     ident: String,
     is_mut: bool,
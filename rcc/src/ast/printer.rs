@@ -0,0 +1,672 @@
+//! Rendering of the AST back to canonical source text, used by `rcc --fmt`.
+//!
+//! ```text
+//! /// add a to b
+//! pub fn add(a: i32, b: i32) -> i32 {
+//!     a + b
+//! }
+//! ```
+//!
+//! Items and statements are put on their own lines and indented by four spaces. Parentheses
+//! are kept as `GroupedExpr`s by the parser, so the printed text parses to the same AST.
+use crate::ast::expr::{
+    AsmExpr, AssertExpr, AssertKind, BinOpExpr, BlockExpr, Expr, LhsExpr, PrintExpr, StructExpr,
+    UnOp,
+};
+use crate::ast::file::File;
+use crate::ast::item::{
+    ExternalItem, ExternalItemFn, Fields, FnParams, FnSignature, GenericParam, Item, ItemConst,
+    InlineHint, ItemFn, ItemStruct,
+};
+use crate::ast::pattern::Pattern;
+use crate::ast::stmt::Stmt;
+use crate::ast::types::{PtrKind, TypeAnnotation};
+use crate::ast::Visibility;
+
+const INDENT: &str = "    ";
+
+pub fn print_file(file: &File) -> String {
+    let mut printer = Printer::new();
+    for (i, item) in file.items.iter().enumerate() {
+        if i > 0 {
+            printer.out.push('\n');
+        }
+        printer.item(item);
+    }
+    printer.out
+}
+
+pub struct Printer {
+    out: String,
+    indent: usize,
+}
+
+impl Default for Printer {
+    fn default() -> Printer {
+        Printer::new()
+    }
+}
+
+impl Printer {
+    pub fn new() -> Printer {
+        Printer {
+            out: String::new(),
+            indent: 0,
+        }
+    }
+
+    /// Start a new line at the current indentation
+    fn line(&mut self) {
+        for _ in 0..self.indent {
+            self.out.push_str(INDENT);
+        }
+    }
+
+    fn docs(&mut self, docs: &[String]) {
+        for doc in docs {
+            self.line();
+            self.out.push_str(&format!("///{}\n", doc));
+        }
+    }
+
+    pub fn item(&mut self, item: &Item) {
+        match item {
+            Item::Fn(item_fn) => self.item_fn(item_fn),
+            Item::Struct(item_struct) => self.item_struct(item_struct),
+            Item::Const(item_const) => self.item_const(item_const),
+            Item::Type(item_type_alias) => {
+                self.line();
+                self.out.push_str(&format!(
+                    "{}type {} = {};\n",
+                    vis(item_type_alias.vis),
+                    item_type_alias.name,
+                    type_anno(&item_type_alias.type_anno)
+                ));
+            }
+            Item::Trait(item_trait) => {
+                self.line();
+                self.out.push_str(&format!(
+                    "{}trait {} {{\n",
+                    vis(item_trait.vis()),
+                    item_trait.name
+                ));
+                self.indent += 1;
+                for trait_fn in item_trait.fns.iter() {
+                    self.line();
+                    self.out.push_str(&fn_signature(
+                        &trait_fn.name,
+                        &[],
+                        &trait_fn.fn_params,
+                        &trait_fn.ret_type,
+                    ));
+                    self.out.push_str(";\n");
+                }
+                self.indent -= 1;
+                self.line();
+                self.out.push_str("}\n");
+            }
+            Item::Impl(item_impl) => {
+                self.line();
+                self.out.push_str("impl ");
+                if let Some(trait_name) = &item_impl.trait_name {
+                    self.out.push_str(&format!("{} for ", trait_name));
+                }
+                self.out.push_str(&format!("{} {{\n", type_anno(&item_impl.self_type)));
+                self.indent += 1;
+                for item_const in item_impl.consts.iter() {
+                    self.item_const(item_const);
+                }
+                for (i, item_fn) in item_impl.fns.iter().enumerate() {
+                    if i > 0 || !item_impl.consts.is_empty() {
+                        self.out.push('\n');
+                    }
+                    self.item_fn(item_fn);
+                }
+                self.indent -= 1;
+                self.line();
+                self.out.push_str("}\n");
+            }
+            Item::ExternalBlock(block) => {
+                self.line();
+                self.out.push_str(&format!("extern \"{:?}\" {{\n", block.abi()));
+                self.indent += 1;
+                for ExternalItem::Fn(external_fn) in block.external_items.iter() {
+                    self.external_fn(external_fn);
+                }
+                self.indent -= 1;
+                self.line();
+                self.out.push_str("}\n");
+            }
+            Item::Cfg(item_cfg) => {
+                self.line();
+                self.out.push_str(&format!("#[{}]\n", item_cfg.cfg));
+                self.item(&item_cfg.item);
+            }
+            Item::Enum(_) | Item::Static => unimplemented!("{:?}", item),
+        }
+    }
+
+    fn item_const(&mut self, item_const: &ItemConst) {
+        self.line();
+        self.out.push_str(&format!(
+            "{}const {}: {} = ",
+            vis(item_const.vis),
+            item_const.name,
+            type_anno(&item_const.type_anno)
+        ));
+        self.expr(&item_const.expr);
+        self.out.push_str(";\n");
+    }
+
+    fn item_fn(&mut self, item_fn: &ItemFn) {
+        self.docs(&item_fn.docs);
+        if let Some(link_section) = &item_fn.link_section {
+            self.line();
+            self.out.push_str(&format!("#[link_section = {:?}]\n", link_section));
+        }
+        if item_fn.no_mangle {
+            self.line();
+            self.out.push_str("#[no_mangle]\n");
+        }
+        if let Some(inline) = item_fn.inline {
+            self.line();
+            self.out.push_str(match inline {
+                InlineHint::Hint => "#[inline]\n",
+                InlineHint::Always => "#[inline(always)]\n",
+                InlineHint::Never => "#[inline(never)]\n",
+            });
+        }
+        if item_fn.is_cold {
+            self.line();
+            self.out.push_str("#[cold]\n");
+        }
+        self.line();
+        self.out.push_str(vis(item_fn.vis()));
+        if item_fn.is_const {
+            self.out.push_str("const ");
+        }
+        if item_fn.is_unsafe {
+            self.out.push_str("unsafe ");
+        }
+        if let Some(abi) = item_fn.abi {
+            self.out.push_str(&format!("extern \"{:?}\" ", abi));
+        }
+        self.out.push_str(&fn_signature(
+            &item_fn.name,
+            &item_fn.generics,
+            &item_fn.fn_params,
+            &item_fn.ret_type,
+        ));
+        self.out.push(' ');
+        self.block(&item_fn.fn_block);
+        self.out.push('\n');
+    }
+
+    fn external_fn(&mut self, external_fn: &ExternalItemFn) {
+        if let Some(link_name) = &external_fn.link_name {
+            self.line();
+            self.out.push_str(&format!("#[link_name = {:?}]\n", link_name));
+        }
+        self.line();
+        self.out.push_str(vis(external_fn.vis()));
+        self.out.push_str(&fn_signature(
+            &external_fn.name,
+            &[],
+            &external_fn.fn_params,
+            &external_fn.ret_type,
+        ));
+        self.out.push_str(";\n");
+    }
+
+    fn item_struct(&mut self, item_struct: &ItemStruct) {
+        self.docs(&item_struct.docs);
+        self.line();
+        self.out.push_str(&format!(
+            "{}struct {}",
+            vis(item_struct.vis()),
+            item_struct.name()
+        ));
+        match item_struct.fields() {
+            Fields::None => self.out.push_str(";\n"),
+            Fields::Tuple(fields) => {
+                let fields: Vec<String> = fields
+                    .iter()
+                    .map(|f| format!("{}{}", vis(f.vis), type_anno(&f._type)))
+                    .collect();
+                self.out.push_str(&format!("({});\n", fields.join(", ")));
+            }
+            Fields::Struct(fields) if fields.is_empty() => self.out.push_str(" {}\n"),
+            Fields::Struct(fields) => {
+                self.out.push_str(" {\n");
+                self.indent += 1;
+                for field in fields {
+                    self.line();
+                    self.out.push_str(&format!(
+                        "{}{}: {},\n",
+                        vis(field.vis),
+                        field.name,
+                        type_anno(&field._type)
+                    ));
+                }
+                self.indent -= 1;
+                self.line();
+                self.out.push_str("}\n");
+            }
+        }
+    }
+
+    /// `{` and `}` of a non-empty block are on different lines, `}` is not followed by '\n'.
+    pub fn block(&mut self, block: &BlockExpr) {
+        if block.is_unsafe {
+            self.out.push_str("unsafe ");
+        }
+        if block.stmts.is_empty() && block.last_expr.is_none() {
+            self.out.push_str("{}");
+            return;
+        }
+        self.out.push_str("{\n");
+        self.indent += 1;
+        for stmt in block.stmts.iter() {
+            self.stmt(stmt);
+        }
+        if let Some(expr) = &block.last_expr {
+            self.line();
+            self.expr(expr);
+            self.out.push('\n');
+        }
+        self.indent -= 1;
+        self.line();
+        self.out.push('}');
+    }
+
+    fn stmt(&mut self, stmt: &Stmt) {
+        match stmt {
+            Stmt::Semi => {
+                self.line();
+                self.out.push_str(";\n");
+            }
+            Stmt::Item(item) => self.item(item),
+            Stmt::Let(let_stmt) => {
+                self.line();
+                self.out.push_str(&format!("let {}", let_stmt.pattern));
+                if let Some(t) = &let_stmt._type {
+                    self.out.push_str(&format!(": {}", type_anno(t)));
+                }
+                if let Some(rhs) = &let_stmt.rhs {
+                    self.out.push_str(" = ");
+                    self.expr(rhs);
+                }
+                self.out.push_str(";\n");
+            }
+            Stmt::ExprStmt(expr) => {
+                self.line();
+                self.expr(expr);
+                if !expr.with_block() {
+                    self.out.push(';');
+                }
+                self.out.push('\n');
+            }
+        }
+    }
+
+    pub fn expr(&mut self, expr: &Expr) {
+        match expr {
+            Expr::Path(path_expr) => self.out.push_str(&path_expr.segments.join("::")),
+            Expr::LitNum(lit_num_expr) => {
+                self.out.push_str(&lit_num_expr.value);
+                if let Some(suffix) = lit_num_expr.suffix() {
+                    self.out.push_str(&suffix.to_string());
+                }
+            }
+            Expr::LitBool(b) => self.out.push_str(&b.to_string()),
+            Expr::LitChar(c) => self.out.push_str(&format!("{:?}", c)),
+            Expr::LitStr(s) => self.out.push_str(&format!("{:?}", s)),
+            Expr::LitByteStr(bytes) => {
+                self.out.push_str("b\"");
+                for b in bytes {
+                    self.out.extend(std::ascii::escape_default(*b).map(char::from));
+                }
+                self.out.push('"');
+            }
+            Expr::Unary(unary_expr) => {
+                self.out.push_str(match unary_expr.op {
+                    UnOp::Deref => "*",
+                    UnOp::Not => "!",
+                    UnOp::Neg => "-",
+                    UnOp::Borrow => "&",
+                    UnOp::BorrowMut => "&mut ",
+                });
+                self.expr(&unary_expr.expr);
+            }
+            Expr::Block(block) => self.block(block),
+            Expr::Assign(assign_expr) => {
+                self.lhs_expr(&assign_expr.lhs);
+                self.out.push_str(&format!(" {} ", assign_expr.assign_op));
+                self.expr(&assign_expr.rhs);
+            }
+            Expr::Range(range_expr) => {
+                if let Some(lhs) = &range_expr.lhs {
+                    self.expr(lhs);
+                }
+                self.out.push_str(&range_expr.range_op.to_string());
+                if let Some(rhs) = &range_expr.rhs {
+                    self.expr(rhs);
+                }
+            }
+            Expr::BinOp(bin_op_expr) => {
+                self.expr(&bin_op_expr.lhs);
+                self.out.push_str(&format!(" {} ", bin_op_expr.bin_op));
+                self.expr(&bin_op_expr.rhs);
+            }
+            Expr::Cast(cast_expr) => {
+                self.expr(&cast_expr.expr);
+                self.out.push_str(" as ");
+                self.out.push_str(&type_anno(&cast_expr.type_anno));
+            }
+            Expr::Grouped(expr) => {
+                self.out.push('(');
+                self.expr(expr);
+                self.out.push(')');
+            }
+            Expr::Array(array_expr) => {
+                self.out.push('[');
+                match &array_expr.len_expr {
+                    Some(len_expr) => {
+                        self.expr(&array_expr.elems[0]);
+                        self.out.push_str("; ");
+                        self.expr(len_expr);
+                    }
+                    None => self.exprs(&array_expr.elems),
+                }
+                self.out.push(']');
+            }
+            Expr::ArrayIndex(array_index_expr) => {
+                self.expr(&array_index_expr.expr);
+                self.out.push('[');
+                self.expr(&array_index_expr.index_expr);
+                self.out.push(']');
+            }
+            Expr::Tuple(tuple_expr) => {
+                self.out.push('(');
+                self.exprs(&tuple_expr.elems);
+                if tuple_expr.elems.len() == 1 {
+                    self.out.push(',');
+                }
+                self.out.push(')');
+            }
+            Expr::Struct(struct_expr) => self.struct_expr(struct_expr),
+            Expr::Call(call_expr) => {
+                self.expr(&call_expr.expr);
+                self.out.push('(');
+                self.exprs(&call_expr.call_params);
+                self.out.push(')');
+            }
+            Expr::FieldAccess(field_access_expr) => {
+                self.expr(&field_access_expr.lhs);
+                self.out.push('.');
+                self.expr(&field_access_expr.rhs);
+            }
+            Expr::While(while_expr) => {
+                self.out.push_str("while ");
+                self.expr(&while_expr.0);
+                self.out.push(' ');
+                self.block(&while_expr.1);
+            }
+            Expr::Loop(loop_expr) => {
+                self.out.push_str("loop ");
+                self.block(&loop_expr.expr);
+            }
+            Expr::For(for_expr) => {
+                self.out.push_str("for ");
+                self.out.push_str(&for_expr.pattern.to_string());
+                self.out.push_str(" in ");
+                self.expr(&for_expr.expr);
+                self.out.push(' ');
+                self.block(&for_expr.block);
+            }
+            Expr::If(if_expr) => {
+                for (i, block) in if_expr.blocks.iter().enumerate() {
+                    if i > 0 {
+                        self.out.push_str(" else ");
+                    }
+                    if let Some(cond) = if_expr.conditions.get(i) {
+                        self.out.push_str("if ");
+                        self.expr(cond);
+                        self.out.push(' ');
+                    }
+                    self.block(block);
+                }
+            }
+            Expr::Return(return_expr) => {
+                self.out.push_str("return");
+                if let Some(expr) = &return_expr.0 {
+                    self.out.push(' ');
+                    self.expr(expr);
+                }
+            }
+            Expr::Break(break_expr) => {
+                self.out.push_str("break");
+                if let Some(expr) = &break_expr.0 {
+                    self.out.push(' ');
+                    self.expr(expr);
+                }
+            }
+            Expr::Print(print_expr) => self.print_expr(print_expr),
+            Expr::Assert(assert_expr) => self.assert_expr(assert_expr),
+            Expr::Asm(asm_expr) => self.asm_expr(asm_expr),
+            Expr::TupleIndex(_)
+            | Expr::EnumVariant
+            | Expr::MethodCall
+            | Expr::Match => unimplemented!("{:?}", expr),
+        }
+    }
+
+    /// Exprs separated by `, `
+    fn exprs(&mut self, exprs: &[Expr]) {
+        for (i, expr) in exprs.iter().enumerate() {
+            if i > 0 {
+                self.out.push_str(", ");
+            }
+            self.expr(expr);
+        }
+    }
+
+    fn lhs_expr(&mut self, lhs_expr: &LhsExpr) {
+        match lhs_expr {
+            LhsExpr::Path(path_expr) => self.out.push_str(&path_expr.segments.join("::")),
+            LhsExpr::ArrayIndex(array_index_expr) => {
+                self.expr(&Expr::ArrayIndex(array_index_expr.clone()))
+            }
+            LhsExpr::FieldAccess(field_access_expr) => {
+                self.expr(&Expr::FieldAccess(field_access_expr.clone()))
+            }
+            LhsExpr::Deref(expr) => {
+                self.out.push('*');
+                self.expr(expr);
+            }
+            LhsExpr::TupleIndex(_) => unimplemented!("{:?}", lhs_expr),
+        }
+    }
+
+    /// `S { x: 1, y, ..base }`, `y` is short for `y: y`
+    fn struct_expr(&mut self, struct_expr: &StructExpr) {
+        self.out.push_str(&struct_expr.name);
+        if struct_expr.fields.is_empty() && struct_expr.base.is_none() {
+            self.out.push_str(" {}");
+            return;
+        }
+        self.out.push_str(" { ");
+        for (i, (name, expr)) in struct_expr.fields.iter().enumerate() {
+            if i > 0 {
+                self.out.push_str(", ");
+            }
+            self.out.push_str(name);
+            match expr {
+                Expr::Path(p) if p.segments == [name.as_str()] => {}
+                _ => {
+                    self.out.push_str(": ");
+                    self.expr(expr);
+                }
+            }
+        }
+        if let Some(base) = &struct_expr.base {
+            if !struct_expr.fields.is_empty() {
+                self.out.push_str(", ");
+            }
+            self.out.push_str("..");
+            self.expr(base);
+        }
+        self.out.push_str(" }");
+    }
+
+    fn print_expr(&mut self, print_expr: &PrintExpr) {
+        let name = if print_expr.newline { "println" } else { "print" };
+        if print_expr.newline && print_expr.pieces == [""] {
+            self.out.push_str("println!()");
+            return;
+        }
+        let fmt: Vec<String> = print_expr
+            .pieces
+            .iter()
+            .map(|piece| piece.replace('{', "{{").replace('}', "}}"))
+            .collect();
+        self.out.push_str(&format!("{}!({:?}", name, fmt.join("{}")));
+        for arg in print_expr.args.iter() {
+            self.out.push_str(", ");
+            self.expr(arg);
+        }
+        self.out.push(')');
+    }
+
+    /// `assert_eq!(a, b)` is printed from its condition `a == b`
+    fn assert_expr(&mut self, assert_expr: &AssertExpr) {
+        self.out.push_str(&format!("{}!(", assert_expr.kind.macro_name()));
+        match (assert_expr.kind, assert_expr.cond.as_ref()) {
+            (AssertKind::Assert, cond) => self.expr(cond),
+            (_, Expr::BinOp(BinOpExpr { lhs, rhs, .. })) => {
+                self.expr(lhs);
+                self.out.push_str(", ");
+                self.expr(rhs);
+            }
+            (_, cond) => unreachable!("`{}` is not a comparison", expr_str(cond)),
+        }
+        if let Some(msg) = &assert_expr.msg {
+            let msg = msg.replace('{', "{{").replace('}', "}}");
+            self.out.push_str(&format!(", {:?}", msg));
+        }
+        self.out.push(')');
+    }
+
+    /// A line of the template is a string, the placeholders keep their numbers
+    fn asm_expr(&mut self, asm_expr: &AsmExpr) {
+        self.out.push_str("asm!(");
+        for (i, line) in asm_expr.template.split('\n').enumerate() {
+            if i > 0 {
+                self.out.push_str(", ");
+            }
+            self.out.push_str(&format!("{:?}", line));
+        }
+        for input in asm_expr.inputs.iter() {
+            self.out.push_str(", in(reg) ");
+            self.expr(input);
+        }
+        for output in asm_expr.outputs.iter() {
+            self.out.push_str(", out(reg) ");
+            self.lhs_expr(output);
+        }
+        self.out.push(')');
+    }
+}
+
+fn vis(vis: Visibility) -> &'static str {
+    match vis {
+        Visibility::Pub => "pub ",
+        Visibility::Priv => "",
+    }
+}
+
+/// `fn add<T: Add>(a: T, b: T) -> T`, the return type is omitted if it is `()`
+fn fn_signature(
+    name: &str,
+    generics: &[GenericParam],
+    fn_params: &FnParams,
+    ret_type: &TypeAnnotation,
+) -> String {
+    let mut s = format!("fn {}", name);
+    if !generics.is_empty() {
+        let generics: Vec<String> = generics
+            .iter()
+            .map(|g| match g.bounds.is_empty() {
+                true => g.name.clone(),
+                false => format!("{}: {}", g.name, g.bounds.join(" + ")),
+            })
+            .collect();
+        s.push_str(&format!("<{}>", generics.join(", ")));
+    }
+    let params: Vec<String> = fn_params
+        .params
+        .iter()
+        .map(|param| match (&param.pattern, &param._type) {
+            // `Self` in the params of a method is replaced with the type of the impl
+            (Pattern::Identifier(p), t) if p.ident() == "self" => match t {
+                TypeAnnotation::Ptr(ptr) if ptr.ptr_kind == PtrKind::Ref => "&self".to_string(),
+                TypeAnnotation::Ptr(ptr) if ptr.ptr_kind == PtrKind::MutRef => {
+                    "&mut self".to_string()
+                }
+                _ => "self".to_string(),
+            },
+            (p, t) => format!("{}: {}", p, type_anno(t)),
+        })
+        .collect();
+    s.push_str(&format!("({})", params.join(", ")));
+    if ret_type != &TypeAnnotation::Unit {
+        s.push_str(&format!(" -> {}", type_anno(ret_type)));
+    }
+    s
+}
+
+pub fn expr_str(expr: &Expr) -> String {
+    let mut printer = Printer::new();
+    printer.expr(expr);
+    printer.out
+}
+
+pub fn type_anno(anno: &TypeAnnotation) -> String {
+    match anno {
+        TypeAnnotation::Identifier(name) => name.clone(),
+        TypeAnnotation::Tuple(types) if types.len() == 1 => format!("({},)", type_anno(&types[0])),
+        TypeAnnotation::Tuple(types) => {
+            let types: Vec<String> = types.iter().map(type_anno).collect();
+            format!("({})", types.join(", "))
+        }
+        TypeAnnotation::Slice(t) => format!("[{}]", type_anno(t)),
+        TypeAnnotation::FnPtr(fn_ptr) => {
+            let params: Vec<String> = fn_ptr.params.iter().map(type_anno).collect();
+            match fn_ptr.ret_type.as_ref() {
+                TypeAnnotation::Unit => format!("fn({})", params.join(", ")),
+                t => format!("fn({}) -> {}", params.join(", "), type_anno(t)),
+            }
+        }
+        TypeAnnotation::Ptr(ptr) => {
+            let t = type_anno(&ptr.type_anno);
+            match ptr.ptr_kind {
+                PtrKind::Ref => format!("&{}", t),
+                PtrKind::MutRef => format!("&mut {}", t),
+                PtrKind::MutRawPtr => format!("*mut {}", t),
+                PtrKind::ConstRawPtr => format!("*const {}", t),
+                PtrKind::Box => format!("Box<{}>", t),
+            }
+        }
+        TypeAnnotation::Vec(t) => format!("Vec<{}>", type_anno(t)),
+        TypeAnnotation::Dyn(trait_name) => format!("dyn {}", trait_name),
+        TypeAnnotation::Never => "!".to_string(),
+        TypeAnnotation::Unit => "()".to_string(),
+        TypeAnnotation::Bool => "bool".to_string(),
+        TypeAnnotation::Str => "str".to_string(),
+        TypeAnnotation::Char => "char".to_string(),
+        TypeAnnotation::Array(array) => {
+            format!("[{}; {}]", type_anno(&array._type), expr_str(&array.len))
+        }
+        TypeAnnotation::Unknown => format!("{:?}", anno),
+    }
+}
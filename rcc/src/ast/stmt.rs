@@ -1,12 +1,12 @@
 use super::pattern::Pattern;
 use crate::analyser::sym_resolver::TypeInfo;
+use crate::analyser::type_table::TypeTable;
 use crate::ast::expr::{Expr, ExprVisit};
 use crate::ast::item::Item;
 use crate::ast::stmt::Stmt::ExprStmt;
 use crate::ast::types::TypeAnnotation;
-use std::ops::Deref;
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone)]
 pub enum Stmt {
     Semi,
     Item(Item),
@@ -15,14 +15,12 @@ pub enum Stmt {
 }
 
 impl Stmt {
-    pub fn type_info(&self) -> TypeInfo {
+    pub fn type_info(&self, types: &TypeTable) -> TypeInfo {
         match self {
             Self::Semi | Self::Item(_) | Self::Let(_) => TypeInfo::Unit,
             Self::ExprStmt(e) => {
                 if e.with_block() {
-                    let tp = e.type_info();
-                    let t = tp.borrow();
-                    t.deref().clone()
+                    e.type_info(types)
                 } else if let Expr::Return(_) = e {
                     TypeInfo::Never
                 } else {
@@ -46,7 +44,7 @@ impl From<Expr> for Stmt {
     }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone)]
 pub struct LetStmt {
     pub pattern: Pattern,
     pub _type: Option<TypeAnnotation>,
@@ -73,8 +71,6 @@ impl LetStmt {
     }
 
     pub fn is_mut(&self) -> bool {
-        match &self.pattern {
-            Pattern::Identifier(i) => i.is_mut(),
-        }
+        self.pattern.is_mut()
     }
 }
@@ -1,6 +1,9 @@
+use crate::ast::expr::Expr;
 use crate::ast::item::{ExternalItemFn, ItemFn, FnSignature};
+use crate::ast::printer::expr_str;
 use crate::ast::types::TypeAnnotation::{Identifier, Tuple};
 use std::fmt::{Debug, Formatter};
+use std::hash::{Hash, Hasher};
 use strenum::StrEnum;
 
 #[derive(PartialEq, Clone, Eq, Hash)]
@@ -24,6 +27,12 @@ pub enum TypeAnnotation {
 
     Ptr(TypePtr),
 
+    /// `Vec<i32>`, a growable array on the heap
+    Vec(Box<TypeAnnotation>),
+
+    /// `dyn Display`, only used behind a pointer
+    Dyn(String),
+
     /// !
     Never,
     Unit,
@@ -51,10 +60,12 @@ impl Debug for TypeAnnotation {
         match self {
             Self::Identifier(s) => f.write_str(&s),
             Self::Tuple(tp) => write!(f, "({:?})", tp),
-            Self::Array(ta) => write!(f, "[{:?}; {}]", ta._type, ta.len),
+            Self::Array(ta) => write!(f, "[{:?}; {}]", ta._type, expr_str(&ta.len)),
             Self::Slice(ts) => write!(f, "[{:?}]", ts),
             Self::FnPtr(fptr) => write!(f, "{:?}", fptr),
             Self::Ptr(ptr) => write!(f, "{:?}", ptr),
+            Self::Vec(elem) => write!(f, "Vec<{:?}>", elem),
+            Self::Dyn(trait_name) => write!(f, "dyn {}", trait_name),
             Self::Never => write!(f, "!"),
             Self::Unit => write!(f, "()"),
             Self::Bool => write!(f, "bool"),
@@ -68,10 +79,30 @@ impl Debug for TypeAnnotation {
 pub type TypeTuple = Vec<TypeAnnotation>;
 pub type TypeSlice = Box<TypeAnnotation>;
 
-#[derive(Debug, PartialEq, Clone, Eq, Hash)]
+/// `[T; len]`, the length is a constant expression which is replaced by its `usize` value
+/// before the symbols are resolved
+#[derive(Debug, PartialEq, Clone)]
 pub struct TypeArray {
-    _type: Box<TypeAnnotation>,
-    len: u32,
+    pub _type: Box<TypeAnnotation>,
+    pub len: Box<Expr>,
+}
+
+impl TypeArray {
+    pub fn new(_type: TypeAnnotation, len: Expr) -> TypeArray {
+        TypeArray {
+            _type: Box::new(_type),
+            len: Box::new(len),
+        }
+    }
+}
+
+impl Eq for TypeArray {}
+
+/// `len` is left out, arrays which only differ in their lengths collide
+impl Hash for TypeArray {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self._type.hash(state);
+    }
 }
 
 #[derive(Debug, PartialEq, Eq, Clone, Hash)]
@@ -104,6 +135,8 @@ pub enum PtrKind {
     MutRawPtr,
     /// *const i32
     ConstRawPtr,
+    /// Box<i32>, owns a heap allocation which is freed at the end of its scope
+    Box,
 }
 
 #[derive(Debug, PartialEq, Eq, Hash, Clone)]
@@ -156,6 +189,11 @@ impl TypeLitNum {
         use TypeLitNum::*;
         matches!(self, F | F32 | F64)
     }
+
+    pub fn is_unsigned(&self) -> bool {
+        use TypeLitNum::*;
+        matches!(self, U8 | U16 | U32 | U64 | U128 | Usize)
+    }
 }
 
 impl Debug for TypeLitNum {
@@ -0,0 +1,483 @@
+//! Traversal of the AST. `Visitor` borrows the nodes and `VisitorMut` borrows them mutably.
+//!
+//! Every `visit_*` method calls its `walk_*` method by default, which visits the children of
+//! the node, so a pass only overrides the nodes it cares about:
+//!
+//! ```ignore
+//! struct CallCounter(usize);
+//!
+//! impl Visitor for CallCounter {
+//!     fn visit_call_expr(&mut self, call_expr: &CallExpr) {
+//!         self.0 += 1;
+//!         self.walk_call_expr(call_expr);
+//!     }
+//! }
+//! ```
+//!
+//! An overridden `visit_*` which does not call `walk_*` skips the children of the node.
+//! A file is visited by visiting each of its items.
+//! Types, patterns, paths and structs are leaves.
+use crate::ast::expr::{
+    ArrayExpr, ArrayIndexExpr, AsmExpr, AssertExpr, AssignExpr, BinOpExpr, BlockExpr, BreakExpr,
+    CallExpr, CastExpr, Expr, FieldAccessExpr, ForExpr, IfExpr, LhsExpr, LitNumExpr, LoopExpr,
+    PathExpr, PrintExpr, RangeExpr, ReturnExpr, StructExpr, TupleExpr, UnAryExpr, WhileExpr,
+};
+use crate::ast::item::{
+    ExternalItem, ExternalItemFn, FnParams, Item, ItemConst, ItemExternalBlock, ItemFn,
+    ItemImpl, ItemStruct, ItemTrait, ItemTypeAlias,
+};
+use crate::ast::pattern::Pattern;
+use crate::ast::stmt::{LetStmt, Stmt};
+use crate::ast::types::TypeAnnotation;
+
+macro_rules! make_visitor {
+    ($(#[$attr:meta])* $visitor:ident, $iter:ident, $($mutability:tt)?) => {
+        $(#[$attr])*
+        pub trait $visitor {
+            fn visit_item(&mut self, item: &$($mutability)? Item) {
+                self.walk_item(item)
+            }
+
+            fn walk_item(&mut self, item: &$($mutability)? Item) {
+                match item {
+                    Item::Fn(item_fn) => self.visit_item_fn(item_fn),
+                    Item::Struct(item_struct) => self.visit_item_struct(item_struct),
+                    Item::Const(item_const) => self.visit_item_const(item_const),
+                    Item::Trait(item_trait) => self.visit_item_trait(item_trait),
+                    Item::Impl(item_impl) => self.visit_item_impl(item_impl),
+                    Item::ExternalBlock(block) => self.visit_item_external_block(block),
+                    Item::Type(item_type_alias) => self.visit_item_type_alias(item_type_alias),
+                    Item::Cfg(item_cfg) => self.visit_item(&$($mutability)? item_cfg.item),
+                    Item::Enum(_) | Item::Static => {}
+                }
+            }
+
+            fn visit_item_fn(&mut self, item_fn: &$($mutability)? ItemFn) {
+                self.walk_item_fn(item_fn)
+            }
+
+            fn walk_item_fn(&mut self, item_fn: &$($mutability)? ItemFn) {
+                self.visit_fn_params(&$($mutability)? item_fn.fn_params);
+                self.visit_type(&$($mutability)? item_fn.ret_type);
+                self.visit_block_expr(&$($mutability)? item_fn.fn_block);
+            }
+
+            fn visit_fn_params(&mut self, fn_params: &$($mutability)? FnParams) {
+                self.walk_fn_params(fn_params)
+            }
+
+            fn walk_fn_params(&mut self, fn_params: &$($mutability)? FnParams) {
+                for param in fn_params.params.$iter() {
+                    self.visit_pattern(&$($mutability)? param.pattern);
+                    self.visit_type(&$($mutability)? param._type);
+                }
+            }
+
+            fn visit_item_struct(&mut self, _item_struct: &$($mutability)? ItemStruct) {}
+
+            fn visit_item_const(&mut self, item_const: &$($mutability)? ItemConst) {
+                self.walk_item_const(item_const)
+            }
+
+            fn walk_item_const(&mut self, item_const: &$($mutability)? ItemConst) {
+                self.visit_type(&$($mutability)? item_const.type_anno);
+                self.visit_expr(&$($mutability)? item_const.expr);
+            }
+
+            fn visit_item_type_alias(&mut self, item_type_alias: &$($mutability)? ItemTypeAlias) {
+                self.walk_item_type_alias(item_type_alias)
+            }
+
+            fn walk_item_type_alias(
+                &mut self,
+                item_type_alias: &$($mutability)? ItemTypeAlias,
+            ) {
+                self.visit_type(&$($mutability)? item_type_alias.type_anno);
+            }
+
+            fn visit_item_trait(&mut self, item_trait: &$($mutability)? ItemTrait) {
+                self.walk_item_trait(item_trait)
+            }
+
+            fn walk_item_trait(&mut self, item_trait: &$($mutability)? ItemTrait) {
+                for trait_fn in item_trait.fns.$iter() {
+                    self.visit_fn_params(&$($mutability)? trait_fn.fn_params);
+                    self.visit_type(&$($mutability)? trait_fn.ret_type);
+                }
+            }
+
+            fn visit_item_impl(&mut self, item_impl: &$($mutability)? ItemImpl) {
+                self.walk_item_impl(item_impl)
+            }
+
+            fn walk_item_impl(&mut self, item_impl: &$($mutability)? ItemImpl) {
+                self.visit_type(&$($mutability)? item_impl.self_type);
+                for item_fn in item_impl.fns.$iter() {
+                    self.visit_item_fn(item_fn);
+                }
+                for item_const in item_impl.consts.$iter() {
+                    self.visit_item_const(item_const);
+                }
+            }
+
+            fn visit_item_external_block(&mut self, block: &$($mutability)? ItemExternalBlock) {
+                self.walk_item_external_block(block)
+            }
+
+            fn walk_item_external_block(&mut self, block: &$($mutability)? ItemExternalBlock) {
+                for ExternalItem::Fn(external_fn) in block.external_items.$iter() {
+                    self.visit_external_item_fn(external_fn);
+                }
+            }
+
+            fn visit_external_item_fn(&mut self, external_fn: &$($mutability)? ExternalItemFn) {
+                self.walk_external_item_fn(external_fn)
+            }
+
+            fn walk_external_item_fn(&mut self, external_fn: &$($mutability)? ExternalItemFn) {
+                self.visit_fn_params(&$($mutability)? external_fn.fn_params);
+                self.visit_type(&$($mutability)? external_fn.ret_type);
+            }
+
+            fn visit_block_expr(&mut self, block_expr: &$($mutability)? BlockExpr) {
+                self.walk_block_expr(block_expr)
+            }
+
+            fn walk_block_expr(&mut self, block_expr: &$($mutability)? BlockExpr) {
+                for stmt in block_expr.stmts.$iter() {
+                    self.visit_stmt(stmt);
+                }
+                if let Some(expr) = &$($mutability)? block_expr.last_expr {
+                    self.visit_expr(expr);
+                }
+            }
+
+            fn visit_stmt(&mut self, stmt: &$($mutability)? Stmt) {
+                self.walk_stmt(stmt)
+            }
+
+            fn walk_stmt(&mut self, stmt: &$($mutability)? Stmt) {
+                match stmt {
+                    Stmt::Semi => {}
+                    Stmt::Item(item) => self.visit_item(item),
+                    Stmt::Let(let_stmt) => self.visit_let_stmt(let_stmt),
+                    Stmt::ExprStmt(expr) => self.visit_expr(expr),
+                }
+            }
+
+            fn visit_let_stmt(&mut self, let_stmt: &$($mutability)? LetStmt) {
+                self.walk_let_stmt(let_stmt)
+            }
+
+            fn walk_let_stmt(&mut self, let_stmt: &$($mutability)? LetStmt) {
+                self.visit_pattern(&$($mutability)? let_stmt.pattern);
+                if let Some(type_anno) = &$($mutability)? let_stmt._type {
+                    self.visit_type(type_anno);
+                }
+                if let Some(rhs) = &$($mutability)? let_stmt.rhs {
+                    self.visit_expr(rhs);
+                }
+            }
+
+            fn visit_pattern(&mut self, _pattern: &$($mutability)? Pattern) {}
+
+            fn visit_type(&mut self, _type_anno: &$($mutability)? TypeAnnotation) {}
+
+            fn visit_expr(&mut self, expr: &$($mutability)? Expr) {
+                self.walk_expr(expr)
+            }
+
+            fn walk_expr(&mut self, expr: &$($mutability)? Expr) {
+                match expr {
+                    Expr::Path(path_expr) => self.visit_path_expr(path_expr),
+                    Expr::LitNum(lit_num_expr) => self.visit_lit_num_expr(lit_num_expr),
+                    Expr::Unary(unary_expr) => self.visit_unary_expr(unary_expr),
+                    Expr::Block(block_expr) => self.visit_block_expr(block_expr),
+                    Expr::Assign(assign_expr) => self.visit_assign_expr(assign_expr),
+                    Expr::Range(range_expr) => self.visit_range_expr(range_expr),
+                    Expr::BinOp(bin_op_expr) => self.visit_bin_op_expr(bin_op_expr),
+                    Expr::Cast(cast_expr) => self.visit_cast_expr(cast_expr),
+                    Expr::Grouped(expr) => self.visit_expr(expr),
+                    Expr::Array(array_expr) => self.visit_array_expr(array_expr),
+                    Expr::ArrayIndex(array_index_expr) => {
+                        self.visit_array_index_expr(array_index_expr)
+                    }
+                    Expr::Tuple(tuple_expr) => self.visit_tuple_expr(tuple_expr),
+                    Expr::Struct(struct_expr) => self.visit_struct_expr(struct_expr),
+                    Expr::Call(call_expr) => self.visit_call_expr(call_expr),
+                    Expr::FieldAccess(field_access_expr) => {
+                        self.visit_field_access_expr(field_access_expr)
+                    }
+                    Expr::While(while_expr) => self.visit_while_expr(while_expr),
+                    Expr::Loop(loop_expr) => self.visit_loop_expr(loop_expr),
+                    Expr::For(for_expr) => self.visit_for_expr(for_expr),
+                    Expr::If(if_expr) => self.visit_if_expr(if_expr),
+                    Expr::Return(return_expr) => self.visit_return_expr(return_expr),
+                    Expr::Break(break_expr) => self.visit_break_expr(break_expr),
+                    Expr::Print(print_expr) => self.visit_print_expr(print_expr),
+                    Expr::Assert(assert_expr) => self.visit_assert_expr(assert_expr),
+                    Expr::Asm(asm_expr) => self.visit_asm_expr(asm_expr),
+                    Expr::LitBool(_)
+                    | Expr::LitChar(_)
+                    | Expr::LitStr(_)
+                    | Expr::LitByteStr(_)
+                    | Expr::TupleIndex(_)
+                    | Expr::EnumVariant
+                    | Expr::MethodCall
+                    | Expr::Match => {}
+                }
+            }
+
+            fn visit_lhs_expr(&mut self, lhs_expr: &$($mutability)? LhsExpr) {
+                self.walk_lhs_expr(lhs_expr)
+            }
+
+            fn walk_lhs_expr(&mut self, lhs_expr: &$($mutability)? LhsExpr) {
+                match lhs_expr {
+                    LhsExpr::Path(path_expr) => self.visit_path_expr(path_expr),
+                    LhsExpr::ArrayIndex(array_index_expr) => {
+                        self.visit_array_index_expr(array_index_expr)
+                    }
+                    LhsExpr::FieldAccess(field_access_expr) => {
+                        self.visit_field_access_expr(field_access_expr)
+                    }
+                    LhsExpr::Deref(expr) => self.visit_expr(expr),
+                    LhsExpr::TupleIndex(_) => {}
+                }
+            }
+
+            fn visit_path_expr(&mut self, _path_expr: &$($mutability)? PathExpr) {}
+
+            fn visit_lit_num_expr(&mut self, _lit_num_expr: &$($mutability)? LitNumExpr) {}
+
+            fn visit_unary_expr(&mut self, unary_expr: &$($mutability)? UnAryExpr) {
+                self.walk_unary_expr(unary_expr)
+            }
+
+            fn walk_unary_expr(&mut self, unary_expr: &$($mutability)? UnAryExpr) {
+                self.visit_expr(&$($mutability)? unary_expr.expr);
+            }
+
+            fn visit_assign_expr(&mut self, assign_expr: &$($mutability)? AssignExpr) {
+                self.walk_assign_expr(assign_expr)
+            }
+
+            fn walk_assign_expr(&mut self, assign_expr: &$($mutability)? AssignExpr) {
+                self.visit_lhs_expr(&$($mutability)? assign_expr.lhs);
+                self.visit_expr(&$($mutability)? assign_expr.rhs);
+            }
+
+            fn visit_range_expr(&mut self, range_expr: &$($mutability)? RangeExpr) {
+                self.walk_range_expr(range_expr)
+            }
+
+            fn walk_range_expr(&mut self, range_expr: &$($mutability)? RangeExpr) {
+                if let Some(lhs) = &$($mutability)? range_expr.lhs {
+                    self.visit_expr(lhs);
+                }
+                if let Some(rhs) = &$($mutability)? range_expr.rhs {
+                    self.visit_expr(rhs);
+                }
+            }
+
+            fn visit_cast_expr(&mut self, cast_expr: &$($mutability)? CastExpr) {
+                self.walk_cast_expr(cast_expr)
+            }
+
+            fn walk_cast_expr(&mut self, cast_expr: &$($mutability)? CastExpr) {
+                self.visit_expr(&$($mutability)? cast_expr.expr);
+                self.visit_type(&$($mutability)? cast_expr.type_anno);
+            }
+
+            fn visit_bin_op_expr(&mut self, bin_op_expr: &$($mutability)? BinOpExpr) {
+                self.walk_bin_op_expr(bin_op_expr)
+            }
+
+            fn walk_bin_op_expr(&mut self, bin_op_expr: &$($mutability)? BinOpExpr) {
+                self.visit_expr(&$($mutability)? bin_op_expr.lhs);
+                self.visit_expr(&$($mutability)? bin_op_expr.rhs);
+            }
+
+            fn visit_array_expr(&mut self, array_expr: &$($mutability)? ArrayExpr) {
+                self.walk_array_expr(array_expr)
+            }
+
+            fn walk_array_expr(&mut self, array_expr: &$($mutability)? ArrayExpr) {
+                for elem in array_expr.elems.$iter() {
+                    self.visit_expr(elem);
+                }
+                if let Some(len_expr) = &$($mutability)? array_expr.len_expr {
+                    self.visit_expr(len_expr);
+                }
+            }
+
+            fn visit_array_index_expr(
+                &mut self,
+                array_index_expr: &$($mutability)? ArrayIndexExpr,
+            ) {
+                self.walk_array_index_expr(array_index_expr)
+            }
+
+            fn walk_array_index_expr(
+                &mut self,
+                array_index_expr: &$($mutability)? ArrayIndexExpr,
+            ) {
+                self.visit_expr(&$($mutability)? array_index_expr.expr);
+                self.visit_expr(&$($mutability)? array_index_expr.index_expr);
+            }
+
+            fn visit_tuple_expr(&mut self, tuple_expr: &$($mutability)? TupleExpr) {
+                self.walk_tuple_expr(tuple_expr)
+            }
+
+            fn walk_tuple_expr(&mut self, tuple_expr: &$($mutability)? TupleExpr) {
+                for elem in tuple_expr.elems.$iter() {
+                    self.visit_expr(elem);
+                }
+            }
+
+            fn visit_struct_expr(&mut self, struct_expr: &$($mutability)? StructExpr) {
+                self.walk_struct_expr(struct_expr)
+            }
+
+            fn walk_struct_expr(&mut self, struct_expr: &$($mutability)? StructExpr) {
+                for (_, expr) in struct_expr.fields.$iter() {
+                    self.visit_expr(expr);
+                }
+                if let Some(base) = &$($mutability)? struct_expr.base {
+                    self.visit_expr(base);
+                }
+            }
+
+            fn visit_call_expr(&mut self, call_expr: &$($mutability)? CallExpr) {
+                self.walk_call_expr(call_expr)
+            }
+
+            fn walk_call_expr(&mut self, call_expr: &$($mutability)? CallExpr) {
+                self.visit_expr(&$($mutability)? call_expr.expr);
+                for param in call_expr.call_params.$iter() {
+                    self.visit_expr(param);
+                }
+            }
+
+            fn visit_field_access_expr(
+                &mut self,
+                field_access_expr: &$($mutability)? FieldAccessExpr,
+            ) {
+                self.walk_field_access_expr(field_access_expr)
+            }
+
+            fn walk_field_access_expr(
+                &mut self,
+                field_access_expr: &$($mutability)? FieldAccessExpr,
+            ) {
+                self.visit_expr(&$($mutability)? field_access_expr.lhs);
+                self.visit_expr(&$($mutability)? field_access_expr.rhs);
+            }
+
+            fn visit_while_expr(&mut self, while_expr: &$($mutability)? WhileExpr) {
+                self.walk_while_expr(while_expr)
+            }
+
+            fn walk_while_expr(&mut self, while_expr: &$($mutability)? WhileExpr) {
+                self.visit_expr(&$($mutability)? while_expr.0);
+                self.visit_block_expr(&$($mutability)? while_expr.1);
+            }
+
+            fn visit_loop_expr(&mut self, loop_expr: &$($mutability)? LoopExpr) {
+                self.walk_loop_expr(loop_expr)
+            }
+
+            fn walk_loop_expr(&mut self, loop_expr: &$($mutability)? LoopExpr) {
+                self.visit_block_expr(&$($mutability)? loop_expr.expr);
+            }
+
+            fn visit_for_expr(&mut self, for_expr: &$($mutability)? ForExpr) {
+                self.walk_for_expr(for_expr)
+            }
+
+            fn walk_for_expr(&mut self, for_expr: &$($mutability)? ForExpr) {
+                self.visit_pattern(&$($mutability)? for_expr.pattern);
+                self.visit_expr(&$($mutability)? for_expr.expr);
+                self.visit_block_expr(&$($mutability)? for_expr.block);
+            }
+
+            fn visit_if_expr(&mut self, if_expr: &$($mutability)? IfExpr) {
+                self.walk_if_expr(if_expr)
+            }
+
+            fn walk_if_expr(&mut self, if_expr: &$($mutability)? IfExpr) {
+                for cond in if_expr.conditions.$iter() {
+                    self.visit_expr(cond);
+                }
+                for block in if_expr.blocks.$iter() {
+                    self.visit_block_expr(block);
+                }
+            }
+
+            fn visit_return_expr(&mut self, return_expr: &$($mutability)? ReturnExpr) {
+                self.walk_return_expr(return_expr)
+            }
+
+            fn walk_return_expr(&mut self, return_expr: &$($mutability)? ReturnExpr) {
+                if let Some(expr) = &$($mutability)? return_expr.0 {
+                    self.visit_expr(expr);
+                }
+            }
+
+            fn visit_break_expr(&mut self, break_expr: &$($mutability)? BreakExpr) {
+                self.walk_break_expr(break_expr)
+            }
+
+            fn walk_break_expr(&mut self, break_expr: &$($mutability)? BreakExpr) {
+                if let Some(expr) = &$($mutability)? break_expr.0 {
+                    self.visit_expr(expr);
+                }
+            }
+
+            fn visit_print_expr(&mut self, print_expr: &$($mutability)? PrintExpr) {
+                self.walk_print_expr(print_expr)
+            }
+
+            fn walk_print_expr(&mut self, print_expr: &$($mutability)? PrintExpr) {
+                for arg in print_expr.args.$iter() {
+                    self.visit_expr(arg);
+                }
+            }
+
+            fn visit_assert_expr(&mut self, assert_expr: &$($mutability)? AssertExpr) {
+                self.walk_assert_expr(assert_expr)
+            }
+
+            fn walk_assert_expr(&mut self, assert_expr: &$($mutability)? AssertExpr) {
+                self.visit_expr(&$($mutability)? assert_expr.cond);
+            }
+
+            fn visit_asm_expr(&mut self, asm_expr: &$($mutability)? AsmExpr) {
+                self.walk_asm_expr(asm_expr)
+            }
+
+            fn walk_asm_expr(&mut self, asm_expr: &$($mutability)? AsmExpr) {
+                for input in asm_expr.inputs.$iter() {
+                    self.visit_expr(input);
+                }
+                for output in asm_expr.outputs.$iter() {
+                    self.visit_lhs_expr(output);
+                }
+            }
+        }
+    };
+}
+
+make_visitor!(
+    /// Read-only traversal, e.g. for lints and metrics
+    Visitor,
+    iter,
+);
+
+make_visitor!(
+    /// Traversal which may rewrite the nodes in place, e.g. for renamers
+    VisitorMut,
+    iter_mut,
+    mut
+);
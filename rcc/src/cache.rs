@@ -0,0 +1,265 @@
+//! Incremental compilation. The assembly of each function is saved in a cache directory,
+//! keyed by a hash of
+//!
+//! - the tokens of the function and the id of its first scope, which is in its labels
+//! - signatures and attributes of all the functions, tokens of `const fn`s and of the other
+//!   items
+//! - the code generation options and the version of rcc
+//!
+//! IR building and code generation are skipped for a function whose key is found.
+//! `.LC<n>` labels of read only strings are saved as indexes into the strings used by
+//! the function, so that the assembly is still valid when the labels of the module change.
+//! Functions are emitted in the order they are defined, so the output is the same as the one
+//! compiled without the cache.
+use crate::ast::file::File;
+use crate::ast::item::{FnSignature, Item, ItemFn};
+use crate::ast::visit::Visitor;
+use crate::code_gen::{gen_funcs, CodeGenOptions, TargetPlatform};
+use crate::ir::cfg::{PrecompiledFn, CFG, CFGIR};
+use crate::json::Json;
+use crate::lexer::token::Token;
+use crate::rcc::RccError;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// Assembly of a function and what is needed to reuse it
+#[derive(Debug, PartialEq)]
+pub struct CachedFn {
+    pub precompiled: PrecompiledFn,
+    /// `.LC<n>` labels are indexes of `ro_strs`
+    pub asm: String,
+    pub ro_strs: Vec<String>,
+}
+
+pub struct FnCache {
+    dir: PathBuf,
+    /// function name -> key
+    keys: HashMap<String, u64>,
+    /// functions of the file and of its impls in order of definition, including the ones
+    /// which are not cached
+    fn_names: Vec<String>,
+    /// function name -> entry found in the cache
+    reused: HashMap<String, CachedFn>,
+}
+
+impl FnCache {
+    /// `fingerprint` identifies the options which change the code of functions.
+    pub fn new(
+        dir: &Path,
+        tokens: &[Token],
+        file: &File,
+        fingerprint: &str,
+    ) -> Result<FnCache, RccError> {
+        std::fs::create_dir_all(dir)?;
+        let mut context = DefaultHasher::new();
+        env!("CARGO_PKG_VERSION").hash(&mut context);
+        fingerprint.hash(&mut context);
+        for (item, range) in file.items.iter().zip(file.item_tokens.iter()) {
+            match item {
+                // the values of constants depend on the bodies of `const fn`s
+                // callers depend on the symbols of their callees and on whether they are cold
+                Item::Fn(item_fn) if !item_fn.is_const => format!(
+                    "{:?} {} {:?} {:?} {:?} {} {:?} {:?} {}",
+                    item_fn.vis(),
+                    item_fn.name,
+                    item_fn.params(),
+                    item_fn.ret_type,
+                    item_fn.abi,
+                    item_fn.is_cold,
+                    item_fn.inline,
+                    item_fn.link_section,
+                    item_fn.no_mangle
+                )
+                .hash(&mut context),
+                _ => hash_tokens(&tokens[range.clone()], &mut context),
+            }
+        }
+        let context = context.finish();
+
+        let mut cache = FnCache {
+            dir: dir.to_path_buf(),
+            keys: HashMap::new(),
+            fn_names: vec![],
+            reused: HashMap::new(),
+        };
+        for (item, range) in file.items.iter().zip(file.item_tokens.iter()) {
+            match item {
+                Item::Fn(item_fn) => {
+                    cache.fn_names.push(item_fn.name.clone());
+                    // a function declaring functions in its body is not cached, they are
+                    // built with it
+                    if has_nested_fn(item_fn) {
+                        continue;
+                    }
+                    let mut hasher = DefaultHasher::new();
+                    context.hash(&mut hasher);
+                    item_fn.fn_block.scope_id.hash(&mut hasher);
+                    hash_tokens(&tokens[range.clone()], &mut hasher);
+                    cache.keys.insert(item_fn.name.clone(), hasher.finish());
+                }
+                // methods are not top level items, they are always compiled
+                Item::Impl(item_impl) => cache
+                    .fn_names
+                    .extend(item_impl.fns.iter().map(|f| item_impl.fn_symbol(f))),
+                _ => {}
+            }
+        }
+        for name in cache.keys.keys() {
+            if let Some(cached_fn) = cache.load(name) {
+                cache.reused.insert(name.clone(), cached_fn);
+            }
+        }
+        Ok(cache)
+    }
+
+    /// Functions whose entries are found, IR building and code generation are skipped for them
+    pub fn reused(&self) -> &HashMap<String, CachedFn> {
+        &self.reused
+    }
+
+    fn path(&self, name: &str) -> Option<PathBuf> {
+        let key = self.keys.get(name)?;
+        Some(self.dir.join(format!("{:016x}.json", key)))
+    }
+
+    /// A missing or broken entry is a miss.
+    fn load(&self, name: &str) -> Option<CachedFn> {
+        let content = std::fs::read_to_string(self.path(name)?).ok()?;
+        let json = Json::from_str(&content).ok()?;
+        let strings = |key: &str| -> Option<Vec<String>> {
+            match json.get(key)? {
+                Json::Array(elems) => elems.iter().map(|e| e.as_str().map(String::from)).collect(),
+                _ => None,
+            }
+        };
+        Some(CachedFn {
+            precompiled: PrecompiledFn {
+                name: name.to_string(),
+                callees: strings("callees")?,
+                returns_value: matches!(json.get("returns_value")?, Json::Bool(true)),
+            },
+            asm: json.get("asm")?.as_str()?.to_string(),
+            ro_strs: strings("ro_strs")?,
+        })
+    }
+
+    fn store(&self, cached_fn: &CachedFn) -> Result<(), RccError> {
+        let path = match self.path(&cached_fn.precompiled.name) {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+        let strings = |strs: &[String]| Json::Array(strs.iter().map(|s| Json::from(s.as_str())).collect());
+        let json = Json::object(vec![
+            ("asm", Json::from(cached_fn.asm.as_str())),
+            ("ro_strs", strings(&cached_fn.ro_strs)),
+            ("callees", strings(&cached_fn.precompiled.callees)),
+            ("returns_value", Json::Bool(cached_fn.precompiled.returns_value)),
+        ]);
+        std::fs::write(path, json.to_string())?;
+        Ok(())
+    }
+
+    /// Generate code of the whole program like `code_gen::gen_code`. Reused functions
+    /// are copied from the cache, the others are generated and saved to the cache. A reused
+    /// function is written before the first generated one defined after it, a generated
+    /// function which is not an item, e.g. a nested one, is written where it is generated.
+    pub fn gen_code(
+        &self,
+        target: TargetPlatform,
+        options: CodeGenOptions,
+        cfg_ir: &CFGIR,
+        jobs: usize,
+        fn_ro_strs: &HashMap<String, Vec<usize>>,
+        out: &mut dyn Write,
+    ) -> Result<(), RccError> {
+        let cfgs: Vec<&CFG> = cfg_ir.cfgs.iter().collect();
+        let codes = gen_funcs(target, &options, &cfgs, jobs)?;
+
+        let mut backend = target.create_backend(options);
+        backend.gen_module_begin(cfg_ir, out)?;
+        let mut next = 0;
+        for (cfg, code) in cfgs.iter().zip(codes.iter()) {
+            if let Some(i) = self.fn_names[next..].iter().position(|n| n == &cfg.func_name) {
+                self.write_reused(&self.fn_names[next..next + i], fn_ro_strs, out)?;
+                next += i + 1;
+            }
+            out.write_all(code)?;
+            if !self.keys.contains_key(&cfg.func_name) {
+                continue;
+            }
+            let ro_strs = &fn_ro_strs[&cfg.func_name];
+            let asm = String::from_utf8_lossy(code);
+            self.store(&CachedFn {
+                precompiled: PrecompiledFn::from_cfg(cfg),
+                asm: relocate_ro_strs(&asm, |n| ro_strs.iter().position(|i| *i == n).unwrap()),
+                ro_strs: ro_strs
+                    .iter()
+                    .map(|i| cfg_ir.ro_local_strs.get_index(*i).unwrap().1.clone())
+                    .collect(),
+            })?;
+        }
+        self.write_reused(&self.fn_names[next..], fn_ro_strs, out)?;
+        backend.gen_module_end(cfg_ir, out)
+    }
+
+    /// Copy the reused functions among `names` from the cache
+    fn write_reused(
+        &self,
+        names: &[String],
+        fn_ro_strs: &HashMap<String, Vec<usize>>,
+        out: &mut dyn Write,
+    ) -> Result<(), RccError> {
+        for name in names.iter() {
+            if let Some(cached_fn) = self.reused.get(name) {
+                let ro_strs = &fn_ro_strs[name];
+                write!(out, "{}", relocate_ro_strs(&cached_fn.asm, |i| ro_strs[i]))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Whether a function is declared in the body of `item_fn`
+fn has_nested_fn(item_fn: &ItemFn) -> bool {
+    struct NestedFns(bool);
+
+    impl Visitor for NestedFns {
+        fn visit_item_fn(&mut self, _item_fn: &ItemFn) {
+            self.0 = true;
+        }
+    }
+
+    let mut nested_fns = NestedFns(false);
+    nested_fns.visit_block_expr(&item_fn.fn_block);
+    nested_fns.0
+}
+
+fn hash_tokens<H: Hasher>(tokens: &[Token], hasher: &mut H) {
+    for token in tokens.iter() {
+        format!("{:?}", token).hash(hasher);
+    }
+}
+
+/// Replace the number of every `.LC<n>` label by `f(n)`.
+pub fn relocate_ro_strs(asm: &str, f: impl Fn(usize) -> usize) -> String {
+    let mut out = String::with_capacity(asm.len());
+    let mut rest = asm;
+    while let Some(i) = rest.find(".LC") {
+        out.push_str(&rest[..i + 3]);
+        rest = &rest[i + 3..];
+        let digits = rest.find(|c: char| !c.is_ascii_digit()).unwrap_or(rest.len());
+        if digits == 0 {
+            continue;
+        }
+        let n: usize = rest[..digits].parse().unwrap();
+        out.push_str(&f(n).to_string());
+        rest = &rest[digits..];
+    }
+    out.push_str(rest);
+    out
+}
+
@@ -0,0 +1,1027 @@
+//! Integrated assembler of the RISC-V assembly emitted by `RiscvBackend`, used by
+//! `--emit obj` to write a relocatable ELF object without an external `as`.
+//!
+//! It accepts the instructions of RV32IM and RV64IM, the pseudo instructions which the
+//! backend emits, e.g. `li`, `call` and `bnez`, and the directives `.text`, `.section`,
+//! `.bss`, `.globl`, `.align`, `.byte`, `.half`, `.word`, `.dword`, `.zero` and `.string`.
+//! A numeric label like `1:` is referenced by `1b` before it and `1f` after it.
+//!
+//! ```text
+//! .L1_2:                      bne a5,a4,.L1_2     -> resolved, the target is in .text
+//!     call  foo               auipc ra,0; jalr ra  -> resolved if foo is in .text
+//!     lui   a1,%hi(.LC0)      R_RISCV_HI20 .LC0
+//! ```
+//!
+//! Branches, jumps and calls to labels of the same section are resolved by the assembler,
+//! other references become relocations. `R_RISCV_RELAX` is never emitted, so the linker
+//! keeps the code as it is. `.file` and `.loc` are ignored, the object has no line
+//! information.
+use crate::code_gen::elf::{
+    ElfClass, ObjectFile, RelocKind, Relocation, SectionId, SectionKind, Symbol, SymbolBinding,
+    SymbolId, SymbolKind,
+};
+use crate::code_gen::riscv::RV64_XLEN;
+use crate::rcc::RccError;
+use std::collections::HashMap;
+
+const NOP: u32 = 0x0000_0013;
+
+/// Assemble the text of a module for a target of `xlen` bits
+pub fn assemble(text: &str, xlen: u32) -> Result<ObjectFile, RccError> {
+    let mut assembler = Assembler::new(xlen);
+    for (i, line) in text.lines().enumerate() {
+        assembler.line(line).map_err(|e| format!("line {}: {}", i + 1, e))?;
+    }
+    assembler.finish()
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum FixupKind {
+    Branch,
+    Jal,
+    /// `auipc` and `jalr` of `call` and `tail`
+    Call,
+    /// `.word a-b`, `minus` is `b`
+    Diff32 { minus: usize },
+    Reloc(RelocKind),
+}
+
+/// Reference to a symbol which is resolved after all the labels are defined
+struct Fixup {
+    section: SectionId,
+    offset: u64,
+    kind: FixupKind,
+    /// index in `Assembler::names`
+    symbol: usize,
+    line: usize,
+}
+
+/// `%hi(sym)` and the other operators of relocations
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum Modifier {
+    Hi,
+    Lo,
+    PcrelHi,
+    PcrelLo,
+}
+
+enum Imm {
+    Value(i64),
+    Symbol(Modifier, String),
+}
+
+struct Assembler {
+    xlen: u32,
+    obj: ObjectFile,
+    section: SectionId,
+    sections: HashMap<String, SectionId>,
+    /// names of the symbols referenced or defined, in order of appearance
+    names: Vec<String>,
+    name_ids: HashMap<String, usize>,
+    /// name id -> section and offset of the label
+    labels: HashMap<usize, (SectionId, u64)>,
+    globals: Vec<usize>,
+    /// numeric label -> number of its definitions so far
+    numeric_labels: HashMap<u64, usize>,
+    fixups: Vec<Fixup>,
+    line: usize,
+}
+
+impl Assembler {
+    fn new(xlen: u32) -> Assembler {
+        let class = if xlen == RV64_XLEN {
+            ElfClass::Elf64
+        } else {
+            ElfClass::Elf32
+        };
+        let mut obj = ObjectFile::new(class);
+        let text = obj.add_section(".text", SectionKind::Code, 4);
+        let mut sections = HashMap::new();
+        sections.insert(".text".to_string(), text);
+        Assembler {
+            xlen,
+            obj,
+            section: text,
+            sections,
+            names: vec![],
+            name_ids: HashMap::new(),
+            labels: HashMap::new(),
+            globals: vec![],
+            numeric_labels: HashMap::new(),
+            fixups: vec![],
+            line: 0,
+        }
+    }
+
+    fn name_id(&mut self, name: &str) -> usize {
+        if let Some(id) = self.name_ids.get(name) {
+            return *id;
+        }
+        self.names.push(name.to_string());
+        self.name_ids.insert(name.to_string(), self.names.len() - 1);
+        self.names.len() - 1
+    }
+
+    /// `1b` is the last definition of `1`, `1f` is the next one
+    fn label_ref(&mut self, s: &str) -> Result<usize, String> {
+        if let Some(n) = s.strip_suffix('b').and_then(|n| n.parse::<u64>().ok()) {
+            return match self.numeric_labels.get(&n) {
+                Some(k) => Ok(self.name_id(&numeric_label(n, *k))),
+                None => Err(format!("label `{}` is not defined before", n)),
+            };
+        }
+        if let Some(n) = s.strip_suffix('f').and_then(|n| n.parse::<u64>().ok()) {
+            let k = self.numeric_labels.get(&n).copied().unwrap_or(0) + 1;
+            return Ok(self.name_id(&numeric_label(n, k)));
+        }
+        if s.is_empty() || !s.chars().all(is_symbol_char) || s.starts_with(char::is_numeric) {
+            return Err(format!("invalid symbol `{}`", s));
+        }
+        Ok(self.name_id(s))
+    }
+
+    fn define_label(&mut self, name: &str) -> Result<(), String> {
+        let name = match name.parse::<u64>() {
+            Ok(n) => {
+                let k = self.numeric_labels.entry(n).or_insert(0);
+                *k += 1;
+                numeric_label(n, *k)
+            }
+            Err(_) => name.to_string(),
+        };
+        let id = self.name_id(&name);
+        let offset = self.offset();
+        if self.labels.insert(id, (self.section, offset)).is_some() {
+            return Err(format!("label `{}` is defined twice", name));
+        }
+        Ok(())
+    }
+
+    fn offset(&self) -> u64 {
+        self.obj.section(self.section).data.len() as u64
+    }
+
+    fn emit(&mut self, bytes: &[u8]) {
+        self.obj.section_mut(self.section).data.extend_from_slice(bytes);
+    }
+
+    fn emit_inst(&mut self, inst: u32) {
+        self.emit(&inst.to_le_bytes());
+    }
+
+    fn fixup(&mut self, kind: FixupKind, symbol: usize) {
+        self.fixups.push(Fixup {
+            section: self.section,
+            offset: self.offset(),
+            kind,
+            symbol,
+            line: self.line,
+        });
+    }
+
+    fn line(&mut self, line: &str) -> Result<(), String> {
+        self.line += 1;
+        let mut line = strip_comment(line).trim();
+        // labels, e.g. `main:` or `1:`
+        while let Some(colon) = line.find(':') {
+            let name = &line[..colon];
+            if name.is_empty() || !name.chars().all(is_symbol_char) {
+                break;
+            }
+            self.define_label(name)?;
+            line = line[colon + 1..].trim();
+        }
+        if line.is_empty() {
+            return Ok(());
+        }
+        let (mnemonic, operands) = match line.find(char::is_whitespace) {
+            Some(i) => (&line[..i], line[i..].trim()),
+            None => (line, ""),
+        };
+        if mnemonic.starts_with('.') {
+            self.directive(mnemonic, operands)
+        } else {
+            let operands: Vec<&str> = if operands.is_empty() {
+                vec![]
+            } else {
+                operands.split(',').map(str::trim).collect()
+            };
+            self.instruction(mnemonic, &operands)
+        }
+    }
+
+    fn directive(&mut self, directive: &str, operands: &str) -> Result<(), String> {
+        match directive {
+            ".text" => self.switch_section(".text", None),
+            ".bss" => self.switch_section(".bss", None),
+            ".data" => self.switch_section(".data", None),
+            ".section" => {
+                let mut parts = operands.split(',').map(str::trim);
+                let name = parts.next().unwrap_or("");
+                let flags = parts.next().map(|flags| flags.trim_matches('"'));
+                let kind = match (flags, parts.next()) {
+                    (_, Some("@nobits")) => Some(SectionKind::ZeroInit),
+                    (Some(flags), _) if flags.contains('x') => Some(SectionKind::Code),
+                    (Some(flags), _) if flags.contains('w') => Some(SectionKind::Data),
+                    (Some(_), _) => Some(SectionKind::ReadOnlyData),
+                    (None, _) => None,
+                };
+                self.switch_section(name, kind)
+            }
+            ".globl" | ".global" => {
+                let id = self.label_ref(operands)?;
+                self.globals.push(id);
+                Ok(())
+            }
+            ".align" | ".p2align" => {
+                let n = parse_int(operands)?;
+                if !(0..16).contains(&n) {
+                    return Err(format!("invalid alignment {}", n));
+                }
+                self.align(1 << n);
+                Ok(())
+            }
+            ".balign" => {
+                let n = parse_int(operands)?;
+                if n <= 0 || n.count_ones() != 1 || n >= 1 << 16 {
+                    return Err(format!("invalid alignment {}", n));
+                }
+                self.align(n as u64);
+                Ok(())
+            }
+            ".zero" | ".space" => {
+                let n = parse_int(operands)?;
+                if n < 0 {
+                    return Err(format!("invalid size {}", n));
+                }
+                let len = self.obj.section(self.section).data.len() + n as usize;
+                self.obj.section_mut(self.section).data.resize(len, 0);
+                Ok(())
+            }
+            ".string" | ".asciz" | ".ascii" => {
+                let mut bytes = parse_string(operands)?;
+                if directive != ".ascii" {
+                    bytes.push(0);
+                }
+                self.emit(&bytes);
+                Ok(())
+            }
+            ".byte" | ".half" | ".short" | ".word" | ".long" | ".dword" | ".quad" => {
+                let size = match directive {
+                    ".byte" => 1,
+                    ".half" | ".short" => 2,
+                    ".word" | ".long" => 4,
+                    _ => 8,
+                };
+                for value in operands.split(',').map(str::trim) {
+                    self.data(value, size)?;
+                }
+                Ok(())
+            }
+            ".file" | ".loc" => Ok(()),
+            _ => Err(format!("unsupported directive `{}`", directive)),
+        }
+    }
+
+    fn switch_section(&mut self, name: &str, kind: Option<SectionKind>) -> Result<(), String> {
+        if name.is_empty() || !name.chars().all(is_symbol_char) {
+            return Err(format!("invalid section name `{}`", name));
+        }
+        self.section = match self.sections.get(name) {
+            Some(id) => *id,
+            None => {
+                let kind = kind.unwrap_or_else(|| section_kind(name));
+                let align = if kind == SectionKind::Code { 4 } else { 1 };
+                let id = self.obj.add_section(name, kind, align);
+                self.sections.insert(name.to_string(), id);
+                id
+            }
+        };
+        Ok(())
+    }
+
+    /// Pad the current section to a multiple of `align` bytes, by `nop` in code
+    fn align(&mut self, align: u64) {
+        let section = self.obj.section_mut(self.section);
+        section.align = section.align.max(align);
+        let is_code = section.kind == SectionKind::Code;
+        while !(section.data.len() as u64).is_multiple_of(align) {
+            if is_code && section.data.len().is_multiple_of(4) {
+                section.data.extend_from_slice(&NOP.to_le_bytes());
+            } else {
+                section.data.push(0);
+            }
+        }
+    }
+
+    /// A number, a symbol, or the difference of two symbols in a `.word`
+    fn data(&mut self, value: &str, size: usize) -> Result<(), String> {
+        if let Ok(n) = parse_int(value) {
+            let bits = size as u32 * 8;
+            if bits < 64 && (n < -(1 << (bits - 1)) || n >= 1 << bits) {
+                return Err(format!("{} does not fit in {} bytes", n, size));
+            }
+            self.emit(&n.to_le_bytes()[..size]);
+            return Ok(());
+        }
+        let kind = match (value.split_once('-'), size) {
+            (Some((a, b)), 4) => {
+                let minus = self.label_ref(b.trim())?;
+                let symbol = self.label_ref(a.trim())?;
+                self.fixup(FixupKind::Diff32 { minus }, symbol);
+                self.emit(&[0; 4]);
+                return Ok(());
+            }
+            (None, 4) => RelocKind::Abs32,
+            (None, 8) => RelocKind::Abs64,
+            _ => return Err(format!("invalid value `{}` of {} bytes", value, size)),
+        };
+        let symbol = self.label_ref(value)?;
+        self.fixup(FixupKind::Reloc(kind), symbol);
+        self.emit(&vec![0; size]);
+        Ok(())
+    }
+
+    fn instruction(&mut self, mnemonic: &str, ops: &[&str]) -> Result<(), String> {
+        let is_rv64 = self.xlen == RV64_XLEN;
+        if !is_rv64 && is_rv64_only(mnemonic) {
+            return Err(format!("`{}` is only available on RV64", mnemonic));
+        }
+        if let Some((funct7, funct3, opcode)) = r_type_inst(mnemonic) {
+            expect_operands(mnemonic, ops, 3)?;
+            let (rd, rs1, rs2) = (reg(ops[0])?, reg(ops[1])?, reg(ops[2])?);
+            self.emit_inst(r_type(funct7, rs2, rs1, funct3, rd, opcode));
+            return Ok(());
+        }
+        if let Some((funct3, opcode)) = i_type_inst(mnemonic) {
+            expect_operands(mnemonic, ops, 3)?;
+            let (rd, rs1) = (reg(ops[0])?, reg(ops[1])?);
+            return self.i_type_imm(ops[2], rs1, funct3, rd, opcode);
+        }
+        if let Some((funct6, funct3, opcode)) = shift_inst(mnemonic) {
+            expect_operands(mnemonic, ops, 3)?;
+            let (rd, rs1) = (reg(ops[0])?, reg(ops[1])?);
+            let max = if opcode == 0x1b { 32 } else { self.xlen as i64 };
+            let shamt = parse_int(ops[2])?;
+            if !(0..max).contains(&shamt) {
+                return Err(format!("shift amount {} is out of range", shamt));
+            }
+            let imm = ((funct6 << 6) | shamt as u32) as i32;
+            self.emit_inst(i_type(imm, rs1, funct3, rd, opcode));
+            return Ok(());
+        }
+        if let Some(funct3) = load_inst(mnemonic) {
+            expect_operands(mnemonic, ops, 2)?;
+            let rd = reg(ops[0])?;
+            let (imm, rs1) = mem_operand(ops[1])?;
+            return self.i_type_imm(imm, rs1, funct3, rd, 0x03);
+        }
+        if let Some(funct3) = store_inst(mnemonic) {
+            expect_operands(mnemonic, ops, 2)?;
+            let rs2 = reg(ops[0])?;
+            let (imm, rs1) = mem_operand(ops[1])?;
+            let imm = match parse_imm(imm)? {
+                Imm::Value(n) => imm12(n)?,
+                Imm::Symbol(modifier, symbol) => {
+                    let kind = match modifier {
+                        Modifier::Lo => RelocKind::Lo12S,
+                        Modifier::PcrelLo => RelocKind::PcrelLo12S,
+                        _ => return Err(format!("invalid offset `{}`", imm)),
+                    };
+                    let symbol = self.label_ref(&symbol)?;
+                    self.fixup(FixupKind::Reloc(kind), symbol);
+                    0
+                }
+            };
+            self.emit_inst(s_type(imm, rs2, rs1, funct3, 0x23));
+            return Ok(());
+        }
+        if let Some(funct3) = branch_inst(mnemonic) {
+            expect_operands(mnemonic, ops, 3)?;
+            return self.branch(funct3, reg(ops[0])?, reg(ops[1])?, ops[2]);
+        }
+        match mnemonic {
+            "lui" | "auipc" => {
+                expect_operands(mnemonic, ops, 2)?;
+                let rd = reg(ops[0])?;
+                let opcode = if mnemonic == "lui" { 0x37 } else { 0x17 };
+                let imm = match parse_imm(ops[1])? {
+                    Imm::Value(n) if (0..1 << 20).contains(&n) => n as u32,
+                    Imm::Symbol(Modifier::Hi, symbol) if mnemonic == "lui" => {
+                        let symbol = self.label_ref(&symbol)?;
+                        self.fixup(FixupKind::Reloc(RelocKind::Hi20), symbol);
+                        0
+                    }
+                    Imm::Symbol(Modifier::PcrelHi, symbol) if mnemonic == "auipc" => {
+                        let symbol = self.label_ref(&symbol)?;
+                        self.fixup(FixupKind::Reloc(RelocKind::PcrelHi20), symbol);
+                        0
+                    }
+                    _ => return Err(format!("invalid immediate `{}`", ops[1])),
+                };
+                self.emit_inst((imm << 12) | (rd << 7) | opcode);
+            }
+            "jal" => match ops {
+                [target] => self.jal(1, target)?,
+                [rd, target] => self.jal(reg(rd)?, target)?,
+                _ => return Err(format!("`{}` expects 1 or 2 operands", mnemonic)),
+            },
+            "jalr" => match ops {
+                [rs1] => self.emit_inst(i_type(0, reg(rs1)?, 0, 1, 0x67)),
+                [rd, mem] if mem.contains('(') => {
+                    let (imm, rs1) = mem_operand(mem)?;
+                    self.i_type_imm(imm, rs1, 0, reg(rd)?, 0x67)?;
+                }
+                [rd, rs1] => self.emit_inst(i_type(0, reg(rs1)?, 0, reg(rd)?, 0x67)),
+                [rd, rs1, imm] => self.i_type_imm(imm, reg(rs1)?, 0, reg(rd)?, 0x67)?,
+                _ => return Err(format!("`{}` expects 1 to 3 operands", mnemonic)),
+            },
+            "ecall" | "ebreak" | "fence" | "nop" | "ret" => {
+                expect_operands(mnemonic, ops, 0)?;
+                self.emit_inst(match mnemonic {
+                    "ecall" => 0x0000_0073,
+                    "ebreak" => 0x0010_0073,
+                    "fence" => 0x0ff0_000f,
+                    "nop" => NOP,
+                    _ => i_type(0, 1, 0, 0, 0x67),
+                });
+            }
+            "li" => {
+                expect_operands(mnemonic, ops, 2)?;
+                let rd = reg(ops[0])?;
+                for inst in self.load_imm(rd, parse_int(ops[1])?)? {
+                    self.emit_inst(inst);
+                }
+            }
+            "mv" | "not" | "neg" | "negw" | "sext.w" | "seqz" | "snez" | "sltz" | "sgtz" => {
+                expect_operands(mnemonic, ops, 2)?;
+                let (rd, rs) = (reg(ops[0])?, reg(ops[1])?);
+                self.emit_inst(match mnemonic {
+                    "mv" => i_type(0, rs, 0, rd, 0x13),
+                    "not" => i_type(-1, rs, 4, rd, 0x13),
+                    "neg" => r_type(0x20, rs, 0, 0, rd, 0x33),
+                    "negw" => r_type(0x20, rs, 0, 0, rd, 0x3b),
+                    "sext.w" => i_type(0, rs, 0, rd, 0x1b),
+                    "seqz" => i_type(1, rs, 3, rd, 0x13),
+                    "snez" => r_type(0, rs, 0, 3, rd, 0x33),
+                    "sltz" => r_type(0, 0, rs, 2, rd, 0x33),
+                    _ => r_type(0, rs, 0, 2, rd, 0x33),
+                });
+            }
+            "beqz" | "bnez" | "blez" | "bgez" | "bltz" | "bgtz" => {
+                expect_operands(mnemonic, ops, 2)?;
+                let rs = reg(ops[0])?;
+                let (funct3, rs1, rs2) = match mnemonic {
+                    "beqz" => (0, rs, 0),
+                    "bnez" => (1, rs, 0),
+                    "blez" => (5, 0, rs),
+                    "bgez" => (5, rs, 0),
+                    "bltz" => (4, rs, 0),
+                    _ => (4, 0, rs),
+                };
+                self.branch(funct3, rs1, rs2, ops[1])?;
+            }
+            "bgt" | "ble" | "bgtu" | "bleu" => {
+                expect_operands(mnemonic, ops, 3)?;
+                // the operands of `blt`, `bge`, ... swapped
+                let funct3 = match mnemonic {
+                    "bgt" => 4,
+                    "ble" => 5,
+                    "bgtu" => 6,
+                    _ => 7,
+                };
+                self.branch(funct3, reg(ops[1])?, reg(ops[0])?, ops[2])?;
+            }
+            "j" => {
+                expect_operands(mnemonic, ops, 1)?;
+                self.jal(0, ops[0])?;
+            }
+            "jr" => {
+                expect_operands(mnemonic, ops, 1)?;
+                self.emit_inst(i_type(0, reg(ops[0])?, 0, 0, 0x67));
+            }
+            "call" | "tail" => {
+                expect_operands(mnemonic, ops, 1)?;
+                // `tail` jumps through t1, `call` through ra and returns to it
+                let (tmp, rd) = if mnemonic == "call" { (1, 1) } else { (6, 0) };
+                let symbol = self.label_ref(ops[0])?;
+                self.fixup(FixupKind::Call, symbol);
+                self.emit_inst((tmp << 7) | 0x17);
+                self.emit_inst(i_type(0, tmp, 0, rd, 0x67));
+            }
+            _ => return Err(format!("unknown instruction `{}`", mnemonic)),
+        }
+        Ok(())
+    }
+
+    /// I-type instruction whose immediate may be `%lo(sym)` or `%pcrel_lo(label)`
+    fn i_type_imm(
+        &mut self,
+        imm: &str,
+        rs1: u32,
+        funct3: u32,
+        rd: u32,
+        opcode: u32,
+    ) -> Result<(), String> {
+        let imm = match parse_imm(imm)? {
+            Imm::Value(n) => imm12(n)?,
+            Imm::Symbol(modifier, symbol) => {
+                let kind = match modifier {
+                    Modifier::Lo => RelocKind::Lo12I,
+                    Modifier::PcrelLo => RelocKind::PcrelLo12I,
+                    _ => return Err(format!("invalid immediate `{}`", imm)),
+                };
+                let symbol = self.label_ref(&symbol)?;
+                self.fixup(FixupKind::Reloc(kind), symbol);
+                0
+            }
+        };
+        self.emit_inst(i_type(imm, rs1, funct3, rd, opcode));
+        Ok(())
+    }
+
+    fn branch(&mut self, funct3: u32, rs1: u32, rs2: u32, target: &str) -> Result<(), String> {
+        let symbol = self.label_ref(target)?;
+        self.fixup(FixupKind::Branch, symbol);
+        self.emit_inst(b_type(0, rs2, rs1, funct3));
+        Ok(())
+    }
+
+    fn jal(&mut self, rd: u32, target: &str) -> Result<(), String> {
+        let symbol = self.label_ref(target)?;
+        self.fixup(FixupKind::Jal, symbol);
+        self.emit_inst(j_type(0, rd));
+        Ok(())
+    }
+
+    /// Instructions of `li rd,value`: `addi`, `lui` and `addi(w)`, then on RV64 the upper
+    /// bits are built first and shifted, like `generateInstSeq` of LLVM.
+    fn load_imm(&self, rd: u32, value: i64) -> Result<Vec<u32>, String> {
+        let is_rv64 = self.xlen == RV64_XLEN;
+        let value = if is_rv64 {
+            value
+        } else if (i32::MIN as i64..=u32::MAX as i64).contains(&value) {
+            value as i32 as i64
+        } else {
+            return Err(format!("{} does not fit in 32 bits", value));
+        };
+        let lo12 = sign_extend(value, 12);
+        if (i32::MIN as i64..=i32::MAX as i64).contains(&value) {
+            if lo12 == value {
+                return Ok(vec![i_type(value as i32, 0, 0, rd, 0x13)]);
+            }
+            let hi20 = (value.wrapping_sub(lo12) >> 12) as u32 & 0xfffff;
+            let mut insts = vec![(hi20 << 12) | (rd << 7) | 0x37];
+            if lo12 != 0 {
+                // `addiw` keeps the sign extension of 32-bit values on RV64
+                let opcode = if is_rv64 { 0x1b } else { 0x13 };
+                insts.push(i_type(lo12 as i32, rd, 0, rd, opcode));
+            }
+            return Ok(insts);
+        }
+        let hi52 = (value as u64).wrapping_add(0x800) >> 12;
+        let shift = 12 + hi52.trailing_zeros();
+        let hi = sign_extend((hi52 >> (shift - 12)) as i64, 64 - shift);
+        let mut insts = self.load_imm(rd, hi)?;
+        insts.push(i_type(shift as i32, rd, 1, rd, 0x13));
+        if lo12 != 0 {
+            insts.push(i_type(lo12 as i32, rd, 0, rd, 0x13));
+        }
+        Ok(insts)
+    }
+
+    fn finish(mut self) -> Result<ObjectFile, RccError> {
+        for fixup in std::mem::take(&mut self.fixups) {
+            self.resolve(&fixup).map_err(|e| {
+                format!("line {}: `{}`: {}", fixup.line, self.names[fixup.symbol], e)
+            })?;
+        }
+        // labels which are not referenced by relocations, except `.L` ones
+        for id in 0..self.names.len() {
+            if self.labels.contains_key(&id) && !self.names[id].starts_with(".L") {
+                self.symbol(id);
+            }
+        }
+        for id in self.globals.clone() {
+            let symbol = self.symbol(id);
+            self.obj.symbol_mut(symbol).binding = SymbolBinding::Global;
+        }
+        Ok(self.obj)
+    }
+
+    /// Symbol of the name `id`, added to the object if it is not yet
+    fn symbol(&mut self, id: usize) -> SymbolId {
+        let name = &self.names[id];
+        if let Some(symbol) = self.obj.symbol_id(name) {
+            return symbol;
+        }
+        match self.labels.get(&id) {
+            Some((section, offset)) => {
+                let kind = match self.obj.section(*section).kind {
+                    SectionKind::Code => SymbolKind::Func,
+                    _ => SymbolKind::Object,
+                };
+                self.obj.add_symbol(Symbol {
+                    name: name.clone(),
+                    section: Some(*section),
+                    value: *offset,
+                    size: 0,
+                    binding: SymbolBinding::Local,
+                    kind,
+                })
+            }
+            None => self.obj.get_or_add_undefined(name),
+        }
+    }
+
+    fn resolve(&mut self, fixup: &Fixup) -> Result<(), String> {
+        let target = self.labels.get(&fixup.symbol).copied();
+        let local_offset = match target {
+            Some((section, offset)) if section == fixup.section => {
+                Some(offset as i64 - fixup.offset as i64)
+            }
+            _ => None,
+        };
+        let data = &mut self.obj.section_mut(fixup.section).data;
+        let at = fixup.offset as usize;
+        let read = |data: &[u8], at: usize| {
+            u32::from_le_bytes([data[at], data[at + 1], data[at + 2], data[at + 3]])
+        };
+        let write = |data: &mut [u8], at: usize, inst: u32| {
+            data[at..at + 4].copy_from_slice(&inst.to_le_bytes())
+        };
+        let kind = match (fixup.kind, local_offset) {
+            (FixupKind::Branch, Some(offset)) => {
+                if !(-4096..4096).contains(&offset) {
+                    return Err("branch target is out of range".to_string());
+                }
+                let inst = read(data, at) | b_type(offset as i32, 0, 0, 0);
+                write(data, at, inst);
+                return Ok(());
+            }
+            (FixupKind::Jal, Some(offset)) => {
+                if !(-(1 << 20)..1 << 20).contains(&offset) {
+                    return Err("jump target is out of range".to_string());
+                }
+                let inst = read(data, at) | j_type(offset as i32, 0);
+                write(data, at, inst);
+                return Ok(());
+            }
+            (FixupKind::Call, Some(offset)) => {
+                let lo12 = sign_extend(offset, 12);
+                let hi20 = (offset - lo12) >> 12;
+                let inst = read(data, at) | ((hi20 as u32 & 0xfffff) << 12);
+                write(data, at, inst);
+                let inst = read(data, at + 4) | ((lo12 as u32 & 0xfff) << 20);
+                write(data, at + 4, inst);
+                return Ok(());
+            }
+            (FixupKind::Diff32 { minus }, _) => {
+                let minus_target = self.labels.get(&minus).copied();
+                match (target, minus_target) {
+                    (Some((a_section, a)), Some((b_section, b))) if a_section == b_section => {
+                        let diff = (a as i64 - b as i64) as i32;
+                        write(data, at, diff as u32);
+                        return Ok(());
+                    }
+                    _ => {
+                        let minus = self.symbol(minus);
+                        self.relocate(fixup, minus, RelocKind::Sub32);
+                        RelocKind::Add32
+                    }
+                }
+            }
+            (FixupKind::Branch, None) => RelocKind::Branch,
+            (FixupKind::Jal, None) => RelocKind::Jal,
+            (FixupKind::Call, None) => RelocKind::CallPlt,
+            (FixupKind::Reloc(kind), _) => kind,
+        };
+        let symbol = self.symbol(fixup.symbol);
+        self.relocate(fixup, symbol, kind);
+        Ok(())
+    }
+
+    fn relocate(&mut self, fixup: &Fixup, symbol: SymbolId, kind: RelocKind) {
+        self.obj.add_relocation(
+            fixup.section,
+            Relocation {
+                offset: fixup.offset,
+                symbol,
+                kind,
+                addend: 0,
+            },
+        );
+    }
+}
+
+fn numeric_label(n: u64, k: usize) -> String {
+    format!(".Ltmp{}_{}", n, k)
+}
+
+fn is_symbol_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_' || c == '.' || c == '$'
+}
+
+/// Kind of a section which is not given by the flags of `.section`
+fn section_kind(name: &str) -> SectionKind {
+    if name.starts_with(".text") {
+        SectionKind::Code
+    } else if name.starts_with(".rodata") {
+        SectionKind::ReadOnlyData
+    } else if name.starts_with(".bss") || name.starts_with(".sbss") {
+        SectionKind::ZeroInit
+    } else {
+        SectionKind::Data
+    }
+}
+
+/// `line` without the comment from `#` outside a string
+fn strip_comment(line: &str) -> &str {
+    let mut in_str = false;
+    let mut escaped = false;
+    for (i, c) in line.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if in_str => escaped = true,
+            '"' => in_str = !in_str,
+            '#' if !in_str => return &line[..i],
+            _ => {}
+        }
+    }
+    line
+}
+
+fn expect_operands(mnemonic: &str, ops: &[&str], n: usize) -> Result<(), String> {
+    if ops.len() == n {
+        Ok(())
+    } else {
+        Err(format!("`{}` expects {} operands, found {}", mnemonic, n, ops.len()))
+    }
+}
+
+fn parse_int(s: &str) -> Result<i64, String> {
+    let (negative, digits) = match s.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, s),
+    };
+    let value = match digits.strip_prefix("0x").or_else(|| digits.strip_prefix("0X")) {
+        Some(hex) => u64::from_str_radix(hex, 16),
+        None => digits.parse::<u64>(),
+    }
+    .map_err(|_| format!("invalid number `{}`", s))?;
+    if negative {
+        if value > 1 << 63 {
+            return Err(format!("{} is out of range", s));
+        }
+        Ok((value as i64).wrapping_neg())
+    } else {
+        Ok(value as i64)
+    }
+}
+
+/// A number, or `%hi(sym)`, `%lo(sym)`, `%pcrel_hi(sym)` and `%pcrel_lo(label)`
+fn parse_imm(s: &str) -> Result<Imm, String> {
+    let modifiers = [
+        ("%hi(", Modifier::Hi),
+        ("%lo(", Modifier::Lo),
+        ("%pcrel_hi(", Modifier::PcrelHi),
+        ("%pcrel_lo(", Modifier::PcrelLo),
+    ];
+    for (prefix, modifier) in modifiers.iter() {
+        if let Some(rest) = s.strip_prefix(prefix) {
+            return match rest.strip_suffix(')') {
+                Some(symbol) => Ok(Imm::Symbol(*modifier, symbol.trim().to_string())),
+                None => Err(format!("invalid immediate `{}`", s)),
+            };
+        }
+    }
+    parse_int(s).map(Imm::Value)
+}
+
+/// `imm(reg)`, the immediate may be omitted
+fn mem_operand(s: &str) -> Result<(&str, u32), String> {
+    let open = s.rfind('(').ok_or_else(|| format!("invalid memory operand `{}`", s))?;
+    let reg_name = s[open + 1..]
+        .strip_suffix(')')
+        .ok_or_else(|| format!("invalid memory operand `{}`", s))?;
+    let imm = s[..open].trim();
+    Ok((if imm.is_empty() { "0" } else { imm }, reg(reg_name.trim())?))
+}
+
+/// Content of a string literal of assembly, see `escape_asm_str` of the backend
+fn parse_string(s: &str) -> Result<Vec<u8>, String> {
+    let inner = s
+        .strip_prefix('"')
+        .and_then(|s| s.strip_suffix('"'))
+        .ok_or_else(|| format!("invalid string `{}`", s))?;
+    let mut bytes = vec![];
+    let mut chars = inner.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            let mut buf = [0; 4];
+            bytes.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+            continue;
+        }
+        match chars.next() {
+            Some('n') => bytes.push(b'\n'),
+            Some('t') => bytes.push(b'\t'),
+            Some('r') => bytes.push(b'\r'),
+            Some(c @ '0'..='7') => {
+                let mut n = c.to_digit(8).unwrap();
+                for _ in 0..2 {
+                    match chars.peek().and_then(|c| c.to_digit(8)) {
+                        Some(d) => {
+                            n = n * 8 + d;
+                            chars.next();
+                        }
+                        None => break,
+                    }
+                }
+                bytes.push(n as u8);
+            }
+            Some(c @ ('\\' | '"')) => bytes.push(c as u8),
+            _ => return Err(format!("invalid escape in `{}`", s)),
+        }
+    }
+    Ok(bytes)
+}
+
+fn reg(name: &str) -> Result<u32, String> {
+    const ABI_NAMES: [&str; 32] = [
+        "zero", "ra", "sp", "gp", "tp", "t0", "t1", "t2", "s0", "s1", "a0", "a1", "a2", "a3",
+        "a4", "a5", "a6", "a7", "s2", "s3", "s4", "s5", "s6", "s7", "s8", "s9", "s10", "s11",
+        "t3", "t4", "t5", "t6",
+    ];
+    if let Some(n) = ABI_NAMES.iter().position(|r| *r == name) {
+        return Ok(n as u32);
+    }
+    match name {
+        "fp" => Ok(8),
+        _ => match name.strip_prefix('x').and_then(|n| n.parse::<u32>().ok()) {
+            Some(n) if n < 32 => Ok(n),
+            _ => Err(format!("invalid register `{}`", name)),
+        },
+    }
+}
+
+fn imm12(n: i64) -> Result<i32, String> {
+    if (-2048..2048).contains(&n) {
+        Ok(n as i32)
+    } else {
+        Err(format!("immediate {} does not fit in 12 bits", n))
+    }
+}
+
+fn sign_extend(value: i64, bits: u32) -> i64 {
+    (value << (64 - bits)) >> (64 - bits)
+}
+
+fn is_rv64_only(mnemonic: &str) -> bool {
+    matches!(
+        mnemonic,
+        "ld" | "sd" | "lwu" | "addiw" | "slliw" | "srliw" | "sraiw" | "negw" | "sext.w"
+    ) || r_type_inst(mnemonic).is_some_and(|(_, _, opcode)| opcode == 0x3b)
+}
+
+/// funct7, funct3 and opcode of the instructions of OP and OP-32
+fn r_type_inst(mnemonic: &str) -> Option<(u32, u32, u32)> {
+    let inst = match mnemonic {
+        "add" => (0, 0, 0x33),
+        "sub" => (0x20, 0, 0x33),
+        "sll" => (0, 1, 0x33),
+        "slt" => (0, 2, 0x33),
+        "sltu" => (0, 3, 0x33),
+        "xor" => (0, 4, 0x33),
+        "srl" => (0, 5, 0x33),
+        "sra" => (0x20, 5, 0x33),
+        "or" => (0, 6, 0x33),
+        "and" => (0, 7, 0x33),
+        "mul" => (1, 0, 0x33),
+        "mulh" => (1, 1, 0x33),
+        "mulhsu" => (1, 2, 0x33),
+        "mulhu" => (1, 3, 0x33),
+        "div" => (1, 4, 0x33),
+        "divu" => (1, 5, 0x33),
+        "rem" => (1, 6, 0x33),
+        "remu" => (1, 7, 0x33),
+        "addw" => (0, 0, 0x3b),
+        "subw" => (0x20, 0, 0x3b),
+        "sllw" => (0, 1, 0x3b),
+        "srlw" => (0, 5, 0x3b),
+        "sraw" => (0x20, 5, 0x3b),
+        "mulw" => (1, 0, 0x3b),
+        "divw" => (1, 4, 0x3b),
+        "divuw" => (1, 5, 0x3b),
+        "remw" => (1, 6, 0x3b),
+        "remuw" => (1, 7, 0x3b),
+        _ => return None,
+    };
+    Some(inst)
+}
+
+/// funct3 and opcode of the instructions of OP-IMM and OP-IMM-32 except the shifts
+fn i_type_inst(mnemonic: &str) -> Option<(u32, u32)> {
+    let inst = match mnemonic {
+        "addi" => (0, 0x13),
+        "slti" => (2, 0x13),
+        "sltiu" => (3, 0x13),
+        "xori" => (4, 0x13),
+        "ori" => (6, 0x13),
+        "andi" => (7, 0x13),
+        "addiw" => (0, 0x1b),
+        _ => return None,
+    };
+    Some(inst)
+}
+
+/// funct6, funct3 and opcode of the shifts by an immediate
+fn shift_inst(mnemonic: &str) -> Option<(u32, u32, u32)> {
+    let inst = match mnemonic {
+        "slli" => (0, 1, 0x13),
+        "srli" => (0, 5, 0x13),
+        "srai" => (0x10, 5, 0x13),
+        "slliw" => (0, 1, 0x1b),
+        "srliw" => (0, 5, 0x1b),
+        "sraiw" => (0x10, 5, 0x1b),
+        _ => return None,
+    };
+    Some(inst)
+}
+
+fn load_inst(mnemonic: &str) -> Option<u32> {
+    let funct3 = match mnemonic {
+        "lb" => 0,
+        "lh" => 1,
+        "lw" => 2,
+        "ld" => 3,
+        "lbu" => 4,
+        "lhu" => 5,
+        "lwu" => 6,
+        _ => return None,
+    };
+    Some(funct3)
+}
+
+fn store_inst(mnemonic: &str) -> Option<u32> {
+    let funct3 = match mnemonic {
+        "sb" => 0,
+        "sh" => 1,
+        "sw" => 2,
+        "sd" => 3,
+        _ => return None,
+    };
+    Some(funct3)
+}
+
+fn branch_inst(mnemonic: &str) -> Option<u32> {
+    let funct3 = match mnemonic {
+        "beq" => 0,
+        "bne" => 1,
+        "blt" => 4,
+        "bge" => 5,
+        "bltu" => 6,
+        "bgeu" => 7,
+        _ => return None,
+    };
+    Some(funct3)
+}
+
+fn r_type(funct7: u32, rs2: u32, rs1: u32, funct3: u32, rd: u32, opcode: u32) -> u32 {
+    (funct7 << 25) | (rs2 << 20) | (rs1 << 15) | (funct3 << 12) | (rd << 7) | opcode
+}
+
+fn i_type(imm: i32, rs1: u32, funct3: u32, rd: u32, opcode: u32) -> u32 {
+    ((imm as u32 & 0xfff) << 20) | (rs1 << 15) | (funct3 << 12) | (rd << 7) | opcode
+}
+
+fn s_type(imm: i32, rs2: u32, rs1: u32, funct3: u32, opcode: u32) -> u32 {
+    let imm = imm as u32;
+    (((imm >> 5) & 0x7f) << 25)
+        | (rs2 << 20)
+        | (rs1 << 15)
+        | (funct3 << 12)
+        | ((imm & 0x1f) << 7)
+        | opcode
+}
+
+fn b_type(imm: i32, rs2: u32, rs1: u32, funct3: u32) -> u32 {
+    let imm = imm as u32;
+    (((imm >> 12) & 1) << 31)
+        | (((imm >> 5) & 0x3f) << 25)
+        | (rs2 << 20)
+        | (rs1 << 15)
+        | (funct3 << 12)
+        | (((imm >> 1) & 0xf) << 8)
+        | (((imm >> 11) & 1) << 7)
+        | 0x63
+}
+
+fn j_type(imm: i32, rd: u32) -> u32 {
+    let imm = imm as u32;
+    (((imm >> 20) & 1) << 31)
+        | (((imm >> 1) & 0x3ff) << 21)
+        | (((imm >> 11) & 1) << 20)
+        | (((imm >> 12) & 0xff) << 12)
+        | (rd << 7)
+        | 0x6f
+}
@@ -0,0 +1,139 @@
+//! Generate C prototypes of the `pub extern "C"` functions, so that objects compiled
+//! by rcc can be called from C.
+use crate::ast::file::File;
+use crate::ast::item::{FnSignature, Item, ItemFn, ABI};
+use crate::ast::pattern::Pattern;
+use crate::ast::types::{PtrKind, TypeAnnotation};
+use crate::ast::Visibility;
+use crate::rcc::RccError;
+use std::io::Write;
+
+pub fn gen_c_header(file: &File, out: &mut dyn Write) -> Result<(), RccError> {
+    writeln!(out, "/* Generated by rcc. */")?;
+    writeln!(out, "#pragma once")?;
+    writeln!(out)?;
+    writeln!(out, "#include <stdbool.h>")?;
+    writeln!(out, "#include <stdint.h>")?;
+    writeln!(out)?;
+    writeln!(out, "#ifdef __cplusplus")?;
+    writeln!(out, "extern \"C\" {{")?;
+    writeln!(out, "#endif")?;
+    writeln!(out)?;
+    for item in file.items.iter() {
+        if let Item::Fn(item_fn) = item {
+            if item_fn.vis() == Visibility::Pub && item_fn.abi == Some(ABI::C) {
+                writeln!(out, "{};", fn_prototype(item_fn)?)?;
+            }
+        }
+    }
+    writeln!(out)?;
+    writeln!(out, "#ifdef __cplusplus")?;
+    writeln!(out, "}}")?;
+    writeln!(out, "#endif")?;
+    Ok(())
+}
+
+/// `int32_t add(int32_t a, int32_t b)`, a slice `s` is passed as `s` and `s_len`
+fn fn_prototype(item_fn: &ItemFn) -> Result<String, RccError> {
+    let mut params = vec![];
+    for (i, param) in item_fn.fn_params.params.iter().enumerate() {
+        let c_type = c_type(&param._type)
+            .ok_or_else(|| not_ffi_safe(&param._type, &item_fn.name))?;
+        // `_` is named by its position
+        let name = match &param.pattern {
+            Pattern::Identifier(ident) => ident.ident().to_string(),
+            _ => format!("_{}", i),
+        };
+        params.push(declaration(&c_type, &name));
+        if is_slice_ref(&param._type) {
+            params.push(declaration("uintptr_t", &format!("{}_len", name)));
+        }
+    }
+    let params = if params.is_empty() {
+        "void".to_string()
+    } else {
+        params.join(", ")
+    };
+    let ret_type = match &item_fn.ret_type {
+        TypeAnnotation::Never => "void".to_string(),
+        t => c_type(t).ok_or_else(|| not_ffi_safe(t, &item_fn.name))?,
+    };
+    Ok(format!(
+        "{}({})",
+        declaration(&ret_type, &item_fn.name),
+        params
+    ))
+}
+
+/// `int32_t a`, `const char *s`
+fn declaration(c_type: &str, name: &str) -> String {
+    if c_type.ends_with('*') {
+        format!("{}{}", c_type, name)
+    } else {
+        format!("{} {}", c_type, name)
+    }
+}
+
+fn not_ffi_safe(type_anno: &TypeAnnotation, fn_name: &str) -> RccError {
+    format!(
+        "`{:?}` in `{}` can not be represented in C",
+        type_anno, fn_name
+    )
+    .into()
+}
+
+/// `&[T]`, `&mut [T]` or `&str`
+fn is_slice_ref(type_anno: &TypeAnnotation) -> bool {
+    match type_anno {
+        TypeAnnotation::Str => true,
+        TypeAnnotation::Ptr(ptr) => {
+            matches!(ptr.ptr_kind, PtrKind::Ref | PtrKind::MutRef)
+                && (matches!(ptr.type_anno.as_ref(), TypeAnnotation::Slice(_))
+                    || *ptr.type_anno == TypeAnnotation::from("str"))
+        }
+        _ => false,
+    }
+}
+
+fn c_type(type_anno: &TypeAnnotation) -> Option<String> {
+    let t = match type_anno {
+        TypeAnnotation::Identifier(s) => match s.as_str() {
+            "i8" => "int8_t",
+            "i16" => "int16_t",
+            "i32" => "int32_t",
+            "i64" => "int64_t",
+            "isize" => "intptr_t",
+            "u8" => "uint8_t",
+            "u16" => "uint16_t",
+            "u32" => "uint32_t",
+            "u64" => "uint64_t",
+            "usize" => "uintptr_t",
+            "f32" => "float",
+            "f64" => "double",
+            "bool" => "bool",
+            "char" => "char",
+            _ => return None,
+        },
+        TypeAnnotation::Unit => "void",
+        TypeAnnotation::Bool => "bool",
+        TypeAnnotation::Char => "char",
+        // strings are also terminated by '\0'
+        TypeAnnotation::Str => "const char *",
+        TypeAnnotation::Ptr(ptr) if *ptr.type_anno == TypeAnnotation::from("str") => {
+            "const char *"
+        }
+        TypeAnnotation::Ptr(ptr) => {
+            // the data pointer of a slice
+            let pointee = match ptr.type_anno.as_ref() {
+                TypeAnnotation::Slice(elem) => c_type(elem)?,
+                t => c_type(t)?,
+            };
+            return Some(match ptr.ptr_kind {
+                PtrKind::Ref | PtrKind::ConstRawPtr => format!("const {} *", pointee),
+                PtrKind::MutRef | PtrKind::MutRawPtr | PtrKind::Box => format!("{} *", pointee),
+            });
+        }
+        _ => return None,
+    };
+    Some(t.to_string())
+}
@@ -0,0 +1,556 @@
+//! Relocatable ELF object file (ELF32 for RV32, ELF64 for RV64).
+//!
+//! Layout of the output:
+//!
+//! ```
+//! +--------------------+
+//! |    ELF header      |
+//! +--------------------+
+//! | user sections      | .text, .rodata, .data, ...
+//! +--------------------+
+//! | .rela.xxx          | one for each user section which has relocations
+//! +--------------------+
+//! | .symtab            |
+//! | .strtab            |
+//! | .shstrtab          |
+//! +--------------------+
+//! | section headers    |
+//! +--------------------+
+//! ```
+use crate::rcc::RccError;
+use std::io::Write;
+
+const EM_RISCV: u16 = 243;
+const ET_REL: u16 = 1;
+
+const SHT_PROGBITS: u32 = 1;
+const SHT_SYMTAB: u32 = 2;
+const SHT_STRTAB: u32 = 3;
+const SHT_RELA: u32 = 4;
+const SHT_NOBITS: u32 = 8;
+
+const SHF_WRITE: u64 = 0x1;
+const SHF_ALLOC: u64 = 0x2;
+const SHF_EXECINSTR: u64 = 0x4;
+const SHF_INFO_LINK: u64 = 0x40;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ElfClass {
+    Elf32,
+    Elf64,
+}
+
+impl ElfClass {
+    fn word_size(&self) -> usize {
+        match self {
+            ElfClass::Elf32 => 4,
+            ElfClass::Elf64 => 8,
+        }
+    }
+
+    fn ehdr_size(&self) -> usize {
+        match self {
+            ElfClass::Elf32 => 52,
+            ElfClass::Elf64 => 64,
+        }
+    }
+
+    fn shdr_size(&self) -> usize {
+        match self {
+            ElfClass::Elf32 => 40,
+            ElfClass::Elf64 => 64,
+        }
+    }
+
+    fn sym_size(&self) -> usize {
+        match self {
+            ElfClass::Elf32 => 16,
+            ElfClass::Elf64 => 24,
+        }
+    }
+
+    fn rela_size(&self) -> usize {
+        match self {
+            ElfClass::Elf32 => 12,
+            ElfClass::Elf64 => 24,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum SectionKind {
+    /// .text
+    Code,
+    /// .rodata
+    ReadOnlyData,
+    /// .data
+    Data,
+    /// .bss
+    ZeroInit,
+}
+
+impl SectionKind {
+    fn sh_type(&self) -> u32 {
+        match self {
+            SectionKind::ZeroInit => SHT_NOBITS,
+            _ => SHT_PROGBITS,
+        }
+    }
+
+    fn sh_flags(&self) -> u64 {
+        match self {
+            SectionKind::Code => SHF_ALLOC | SHF_EXECINSTR,
+            SectionKind::ReadOnlyData => SHF_ALLOC,
+            SectionKind::Data | SectionKind::ZeroInit => SHF_ALLOC | SHF_WRITE,
+        }
+    }
+}
+
+pub type SectionId = usize;
+pub type SymbolId = usize;
+
+pub struct Section {
+    pub name: String,
+    pub kind: SectionKind,
+    pub align: u64,
+    /// Content of the section, or zeros of `ZeroInit` sections
+    pub data: Vec<u8>,
+    pub relocations: Vec<Relocation>,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum SymbolBinding {
+    Local,
+    Global,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum SymbolKind {
+    NoType,
+    Object,
+    Func,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Symbol {
+    pub name: String,
+    /// `None` if the symbol is undefined
+    pub section: Option<SectionId>,
+    pub value: u64,
+    pub size: u64,
+    pub binding: SymbolBinding,
+    pub kind: SymbolKind,
+}
+
+/// Relocation types of RISC-V psABI
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum RelocKind {
+    Abs32 = 1,
+    Abs64 = 2,
+    Branch = 16,
+    Jal = 17,
+    CallPlt = 19,
+    PcrelHi20 = 23,
+    PcrelLo12I = 24,
+    PcrelLo12S = 25,
+    Hi20 = 26,
+    Lo12I = 27,
+    Lo12S = 28,
+    Add32 = 35,
+    Sub32 = 39,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Relocation {
+    /// offset in the section
+    pub offset: u64,
+    pub symbol: SymbolId,
+    pub kind: RelocKind,
+    pub addend: i64,
+}
+
+pub struct ObjectFile {
+    class: ElfClass,
+    sections: Vec<Section>,
+    symbols: Vec<Symbol>,
+}
+
+impl ObjectFile {
+    pub fn new(class: ElfClass) -> ObjectFile {
+        ObjectFile {
+            class,
+            sections: vec![],
+            symbols: vec![],
+        }
+    }
+
+    pub fn add_section(&mut self, name: &str, kind: SectionKind, align: u64) -> SectionId {
+        self.sections.push(Section {
+            name: name.to_string(),
+            kind,
+            align,
+            data: vec![],
+            relocations: vec![],
+        });
+        self.sections.len() - 1
+    }
+
+    pub fn section(&self, id: SectionId) -> &Section {
+        &self.sections[id]
+    }
+
+    pub fn section_mut(&mut self, id: SectionId) -> &mut Section {
+        &mut self.sections[id]
+    }
+
+    pub fn add_symbol(&mut self, symbol: Symbol) -> SymbolId {
+        self.symbols.push(symbol);
+        self.symbols.len() - 1
+    }
+
+    pub fn symbol_id(&self, name: &str) -> Option<SymbolId> {
+        self.symbols.iter().position(|s| s.name == name)
+    }
+
+    pub fn symbol_mut(&mut self, id: SymbolId) -> &mut Symbol {
+        &mut self.symbols[id]
+    }
+
+    /// Return the id of symbol `name`, an undefined global symbol
+    /// is added if it does not exist.
+    pub fn get_or_add_undefined(&mut self, name: &str) -> SymbolId {
+        match self.symbol_id(name) {
+            Some(id) => id,
+            None => self.add_symbol(Symbol {
+                name: name.to_string(),
+                section: None,
+                value: 0,
+                size: 0,
+                binding: SymbolBinding::Global,
+                kind: SymbolKind::NoType,
+            }),
+        }
+    }
+
+    pub fn add_relocation(&mut self, section: SectionId, relocation: Relocation) {
+        self.sections[section].relocations.push(relocation);
+    }
+
+    pub fn write(&self, out: &mut dyn Write) -> Result<(), RccError> {
+        for s in self.symbols.iter() {
+            if let Some(section) = s.section {
+                if section >= self.sections.len() {
+                    return Err(format!("symbol `{}` is in an unknown section", s.name).into());
+                }
+            }
+        }
+        let bytes = ElfWriter::new(self).write();
+        out.write_all(&bytes)?;
+        Ok(())
+    }
+}
+
+struct SectionHeader {
+    name: u32,
+    sh_type: u32,
+    flags: u64,
+    offset: u64,
+    size: u64,
+    link: u32,
+    info: u32,
+    align: u64,
+    entsize: u64,
+}
+
+struct StrTab {
+    data: Vec<u8>,
+}
+
+impl StrTab {
+    fn new() -> StrTab {
+        StrTab { data: vec![0] }
+    }
+
+    fn add(&mut self, s: &str) -> u32 {
+        if s.is_empty() {
+            return 0;
+        }
+        let offset = self.data.len() as u32;
+        self.data.extend_from_slice(s.as_bytes());
+        self.data.push(0);
+        offset
+    }
+}
+
+struct ElfWriter<'obj> {
+    obj: &'obj ObjectFile,
+    buf: Vec<u8>,
+    headers: Vec<SectionHeader>,
+    shstrtab: StrTab,
+}
+
+impl<'obj> ElfWriter<'obj> {
+    fn new(obj: &'obj ObjectFile) -> ElfWriter<'obj> {
+        ElfWriter {
+            obj,
+            buf: vec![],
+            headers: vec![],
+            shstrtab: StrTab::new(),
+        }
+    }
+
+    fn write(mut self) -> Vec<u8> {
+        let class = self.obj.class;
+        // filled after all the sections are written
+        self.buf.resize(class.ehdr_size(), 0);
+
+        // null section
+        self.headers.push(SectionHeader {
+            name: 0,
+            sh_type: 0,
+            flags: 0,
+            offset: 0,
+            size: 0,
+            link: 0,
+            info: 0,
+            align: 0,
+            entsize: 0,
+        });
+
+        // user sections, section index = id + 1
+        for section in self.obj.sections.iter() {
+            let align = section.align.max(1);
+            self.align_to(align as usize);
+            let offset = self.buf.len() as u64;
+            if section.kind != SectionKind::ZeroInit {
+                self.buf.extend_from_slice(&section.data);
+            }
+            let name = self.shstrtab.add(&section.name);
+            self.headers.push(SectionHeader {
+                name,
+                sh_type: section.kind.sh_type(),
+                flags: section.kind.sh_flags(),
+                offset,
+                size: section.data.len() as u64,
+                link: 0,
+                info: 0,
+                align,
+                entsize: 0,
+            });
+        }
+
+        // local symbols must come before global symbols
+        let mut sym_order: Vec<SymbolId> = (0..self.obj.symbols.len()).collect();
+        sym_order.sort_by_key(|id| self.obj.symbols[*id].binding == SymbolBinding::Global);
+        // 0 is the null symbol
+        let mut sym_index = vec![0; self.obj.symbols.len()];
+        for (i, id) in sym_order.iter().enumerate() {
+            sym_index[*id] = i + 1;
+        }
+        let first_global = sym_order
+            .iter()
+            .position(|id| self.obj.symbols[*id].binding == SymbolBinding::Global)
+            .unwrap_or(sym_order.len())
+            + 1;
+
+        let user_sections = self.obj.sections.len();
+        let rela_sections = self
+            .obj
+            .sections
+            .iter()
+            .filter(|s| !s.relocations.is_empty())
+            .count();
+        let symtab_index = (1 + user_sections + rela_sections) as u32;
+
+        // .rela.xxx
+        for (id, section) in self.obj.sections.iter().enumerate() {
+            if section.relocations.is_empty() {
+                continue;
+            }
+            self.align_to(class.word_size());
+            let offset = self.buf.len() as u64;
+            for r in section.relocations.iter() {
+                let sym = sym_index[r.symbol] as u64;
+                match class {
+                    ElfClass::Elf32 => {
+                        self.u32(r.offset as u32);
+                        self.u32(((sym as u32) << 8) | r.kind as u32);
+                        self.u32(r.addend as i32 as u32);
+                    }
+                    ElfClass::Elf64 => {
+                        self.u64(r.offset);
+                        self.u64((sym << 32) | r.kind as u64);
+                        self.u64(r.addend as u64);
+                    }
+                }
+            }
+            let name = self.shstrtab.add(&format!(".rela{}", section.name));
+            self.headers.push(SectionHeader {
+                name,
+                sh_type: SHT_RELA,
+                flags: SHF_INFO_LINK,
+                offset,
+                size: (section.relocations.len() * class.rela_size()) as u64,
+                link: symtab_index,
+                info: id as u32 + 1,
+                align: class.word_size() as u64,
+                entsize: class.rela_size() as u64,
+            });
+        }
+
+        // .symtab
+        let mut strtab = StrTab::new();
+        self.align_to(class.word_size());
+        let offset = self.buf.len() as u64;
+        self.buf.resize(self.buf.len() + class.sym_size(), 0);
+        for id in sym_order.iter() {
+            let sym = &self.obj.symbols[*id];
+            let name = strtab.add(&sym.name);
+            let bind = match sym.binding {
+                SymbolBinding::Local => 0,
+                SymbolBinding::Global => 1,
+            };
+            let ty = match sym.kind {
+                SymbolKind::NoType => 0,
+                SymbolKind::Object => 1,
+                SymbolKind::Func => 2,
+            };
+            let info = (bind << 4) | ty;
+            let shndx = sym.section.map_or(0, |s| s as u16 + 1);
+            match class {
+                ElfClass::Elf32 => {
+                    self.u32(name);
+                    self.u32(sym.value as u32);
+                    self.u32(sym.size as u32);
+                    self.buf.push(info);
+                    self.buf.push(0);
+                    self.u16(shndx);
+                }
+                ElfClass::Elf64 => {
+                    self.u32(name);
+                    self.buf.push(info);
+                    self.buf.push(0);
+                    self.u16(shndx);
+                    self.u64(sym.value);
+                    self.u64(sym.size);
+                }
+            }
+        }
+        let name = self.shstrtab.add(".symtab");
+        self.headers.push(SectionHeader {
+            name,
+            sh_type: SHT_SYMTAB,
+            flags: 0,
+            offset,
+            size: ((sym_order.len() + 1) * class.sym_size()) as u64,
+            link: symtab_index + 1,
+            info: first_global as u32,
+            align: class.word_size() as u64,
+            entsize: class.sym_size() as u64,
+        });
+
+        // .strtab
+        let name = self.shstrtab.add(".strtab");
+        self.push_strtab(name, &strtab.data);
+
+        // .shstrtab
+        let name = self.shstrtab.add(".shstrtab");
+        let shstrtab = std::mem::replace(&mut self.shstrtab, StrTab::new());
+        self.push_strtab(name, &shstrtab.data);
+
+        // section headers
+        self.align_to(class.word_size());
+        let shoff = self.buf.len() as u64;
+        let headers = std::mem::take(&mut self.headers);
+        for h in headers.iter() {
+            self.u32(h.name);
+            self.u32(h.sh_type);
+            self.word(h.flags);
+            // sh_addr
+            self.word(0);
+            self.word(h.offset);
+            self.word(h.size);
+            self.u32(h.link);
+            self.u32(h.info);
+            self.word(h.align);
+            self.word(h.entsize);
+        }
+
+        let ehdr = self.elf_header(shoff, headers.len() as u16);
+        self.buf[..ehdr.len()].copy_from_slice(&ehdr);
+        self.buf
+    }
+
+    fn push_strtab(&mut self, name: u32, data: &[u8]) {
+        let offset = self.buf.len() as u64;
+        self.buf.extend_from_slice(data);
+        self.headers.push(SectionHeader {
+            name,
+            sh_type: SHT_STRTAB,
+            flags: 0,
+            offset,
+            size: data.len() as u64,
+            link: 0,
+            info: 0,
+            align: 1,
+            entsize: 0,
+        });
+    }
+
+    fn elf_header(&self, shoff: u64, shnum: u16) -> Vec<u8> {
+        let class = self.obj.class;
+        let mut w = ElfWriter::new(self.obj);
+        w.buf.extend_from_slice(b"\x7fELF");
+        w.buf.push(match class {
+            ElfClass::Elf32 => 1,
+            ElfClass::Elf64 => 2,
+        });
+        // little endian, version 1, System V ABI
+        w.buf.extend_from_slice(&[1, 1, 0]);
+        w.buf.resize(16, 0);
+        w.u16(ET_REL);
+        w.u16(EM_RISCV);
+        w.u32(1);
+        // e_entry, e_phoff
+        w.word(0);
+        w.word(0);
+        w.word(shoff);
+        // e_flags: soft float ABI
+        w.u32(0);
+        w.u16(class.ehdr_size() as u16);
+        // e_phentsize, e_phnum
+        w.u16(0);
+        w.u16(0);
+        w.u16(class.shdr_size() as u16);
+        w.u16(shnum);
+        // .shstrtab is the last section
+        w.u16(shnum - 1);
+        w.buf
+    }
+
+    fn align_to(&mut self, align: usize) {
+        while !self.buf.len().is_multiple_of(align) {
+            self.buf.push(0);
+        }
+    }
+
+    fn u16(&mut self, n: u16) {
+        self.buf.extend_from_slice(&n.to_le_bytes());
+    }
+
+    fn u32(&mut self, n: u32) {
+        self.buf.extend_from_slice(&n.to_le_bytes());
+    }
+
+    fn u64(&mut self, n: u64) {
+        self.buf.extend_from_slice(&n.to_le_bytes());
+    }
+
+    /// 4 bytes in ELF32 and 8 bytes in ELF64
+    fn word(&mut self, n: u64) {
+        match self.obj.class {
+            ElfClass::Elf32 => self.u32(n as u32),
+            ElfClass::Elf64 => self.u64(n),
+        }
+    }
+}
@@ -1,27 +1,201 @@
-pub mod riscv32;
+pub mod assembler;
+pub mod c_header;
+pub mod elf;
+pub mod riscv;
 pub(crate) mod simple_allocator;
+mod tests;
 
 use strenum::StrEnum;
-use crate::ir::cfg::CFG;
-use crate::rcc::OptimizeLevel;
+use crate::intern::Symbol;
+use crate::ir::cfg::{CFG, CFGIR};
+use crate::rcc::{OptimizeLevel, RccError};
+use crate::code_gen::riscv::{RiscvBackend, RISCV32, RISCV64};
 use crate::code_gen::simple_allocator::SimpleAllocator;
-use crate::ir::IRType;
+use crate::ir::{IRInst, IRType};
+use std::io::Write;
 
-#[derive(StrEnum)]
+#[derive(Copy, Clone, Debug, PartialEq, StrEnum)]
 pub enum TargetPlatform {
-    Riscv32
+    Riscv32,
+    Riscv64,
+}
+
+impl TargetPlatform {
+    pub fn desc(&self) -> &'static TargetDesc {
+        match self {
+            TargetPlatform::Riscv32 => &RISCV32,
+            TargetPlatform::Riscv64 => &RISCV64,
+        }
+    }
+
+    pub fn create_backend(&self, options: CodeGenOptions) -> Box<dyn CodeGenBackend> {
+        match self {
+            TargetPlatform::Riscv32 | TargetPlatform::Riscv64 => {
+                Box::new(RiscvBackend::new(self.desc(), options))
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct CodeGenOptions {
+    pub opt_level: OptimizeLevel,
+    /// Emit `_start` which calls `main` and exits with its return value.
+    pub emit_start: bool,
+    /// Compare sp with `__rcc_stack_limit` in every function prologue and abort on overflow,
+    /// for targets without a guard page below the stack.
+    pub stack_check: bool,
+    /// Size in bytes of the stack which `_start` sets up in `.bss`. The stack given by the
+    /// loader is used if it is `None`, then `__rcc_stack_limit` must come from the linker script.
+    pub stack_size: Option<u32>,
+    /// Source files compiled as one text: (name, line of the text where the file starts).
+    /// `loc` instructions are emitted as `.file` and `.loc` directives if it is not empty.
+    pub debug_files: Vec<(String, u32)>,
+    /// Emit `mul`, `div` and `rem` of the M extension. Without it they are calls of runtime
+    /// routines like `__mulsi3` and `__divsi3`, which are emitted at the end of the module.
+    pub m_extension: bool,
+    /// Emit position-independent code: addresses of symbols are computed from the pc by
+    /// `auipc` and `addi`, and jump tables hold offsets, so that the code can run at any
+    /// address, e.g. in a shared object or a relocated bootloader.
+    pub pic: bool,
+}
+
+impl CodeGenOptions {
+    pub fn new(opt_level: OptimizeLevel) -> CodeGenOptions {
+        CodeGenOptions {
+            opt_level,
+            emit_start: false,
+            stack_check: false,
+            stack_size: None,
+            debug_files: vec![],
+            m_extension: true,
+            pic: false,
+        }
+    }
+}
+
+/// Description of a target: pointer width, register set and calling convention.
+#[derive(Debug)]
+pub struct TargetDesc {
+    pub name: &'static str,
+    /// in bits
+    pub pointer_width: u32,
+    /// alignment of the stack pointer in bytes
+    pub stack_align: u32,
+    /// registers used to pass arguments, in order
+    pub arg_regs: &'static [&'static str],
+    pub ret_reg: &'static str,
+    /// registers preserved across calls, the first one is the frame pointer
+    pub callee_saved_regs: &'static [&'static str],
+}
+
+pub trait CodeGenBackend {
+    /// Generate code which comes before all the functions, e.g. read only data.
+    fn gen_module_begin(&mut self, _cfg_ir: &CFGIR, _out: &mut dyn Write) -> Result<(), RccError> {
+        Ok(())
+    }
+
+    fn gen_func(&mut self, cfg: &CFG, out: &mut dyn Write) -> Result<(), RccError>;
+
+    fn gen_module_end(&mut self, _cfg_ir: &CFGIR, _out: &mut dyn Write) -> Result<(), RccError> {
+        Ok(())
+    }
+}
+
+/// Generate code of `cfgs` on `jobs` threads, each of which has its own backend. Functions
+/// are split into contiguous chunks, and their code is returned in the order of `cfgs`, so
+/// the output does not depend on `jobs`.
+pub fn gen_funcs(
+    target: TargetPlatform,
+    options: &CodeGenOptions,
+    cfgs: &[&CFG],
+    jobs: usize,
+) -> Result<Vec<Vec<u8>>, RccError> {
+    let gen_chunk = |cfgs: &[&CFG]| -> Result<Vec<Vec<u8>>, RccError> {
+        let mut backend = target.create_backend(options.clone());
+        let mut codes = Vec::with_capacity(cfgs.len());
+        for cfg in cfgs.iter() {
+            let mut code = vec![];
+            backend.gen_func(cfg, &mut code)?;
+            codes.push(code);
+        }
+        Ok(codes)
+    };
+    if jobs <= 1 || cfgs.len() <= 1 {
+        return gen_chunk(cfgs);
+    }
+    let chunk_size = cfgs.len().div_ceil(jobs);
+    let chunks: Vec<Result<Vec<Vec<u8>>, RccError>> = std::thread::scope(|s| {
+        let handles: Vec<_> = cfgs
+            .chunks(chunk_size)
+            .map(|chunk| s.spawn(move || gen_chunk(chunk)))
+            .collect();
+        handles
+            .into_iter()
+            .map(|handle| handle.join().expect("code generation thread panicked"))
+            .collect()
+    });
+    let mut codes = Vec::with_capacity(cfgs.len());
+    for chunk in chunks {
+        codes.extend(chunk?);
+    }
+    Ok(codes)
+}
+
+/// Generate code of the whole program, functions are generated by `gen_funcs`.
+pub fn gen_code(
+    target: TargetPlatform,
+    options: CodeGenOptions,
+    cfg_ir: &CFGIR,
+    jobs: usize,
+    out: &mut dyn Write,
+) -> Result<(), RccError> {
+    let cfgs: Vec<&CFG> = cfg_ir.cfgs.iter().collect();
+    let codes = gen_funcs(target, &options, &cfgs, jobs)?;
+    let mut backend = target.create_backend(options);
+    backend.gen_module_begin(cfg_ir, out)?;
+    for code in codes.iter() {
+        out.write_all(code)?;
+    }
+    backend.gen_module_end(cfg_ir, out)
 }
 
 pub trait Allocator {
     fn get_frame_size(&self) -> u32;
 
     /// Return offset from fp
-    fn get_fp_offset(&mut self, var_name: &str, ir_type: &IRType) -> u32;
+    fn get_fp_offset(&mut self, var_name: Symbol, ir_type: &IRType) -> u32;
 }
 
-pub fn create_allocator<'cfg>(opt_level: OptimizeLevel, cfg: &'cfg CFG, addr_size: u32) -> Box<dyn Allocator + 'cfg>  {
+/// Callee-saved registers other than the frame pointer which the inline assembly of `cfg`
+/// names, they are saved in the stack frame. The code of the compiler uses no other ones.
+pub fn asm_saved_regs(cfg: &CFG, target: &TargetDesc) -> Vec<&'static str> {
+    let mut regs = vec![];
+    for inst in cfg.iter_inst() {
+        if let IRInst::Asm { template, .. } = inst {
+            for word in template.split(|c: char| !c.is_ascii_alphanumeric()) {
+                let reg = target.callee_saved_regs[1..].iter().find(|reg| **reg == word);
+                if let Some(reg) = reg.filter(|reg| !regs.contains(*reg)) {
+                    regs.push(*reg);
+                }
+            }
+        }
+    }
+    regs
+}
+
+/// `is_leaf` is whether the function calls nothing, so ra is not saved. It is false for a
+/// leaf of the CFG if the backend calls runtime routines in it.
+pub fn create_allocator<'cfg>(
+    opt_level: OptimizeLevel,
+    cfg: &'cfg CFG,
+    target: &TargetDesc,
+    is_leaf: bool,
+) -> Box<dyn Allocator + 'cfg> {
     match opt_level {
-        OptimizeLevel::Zero => Box::new(SimpleAllocator::new(cfg, addr_size)),
-        OptimizeLevel::One => todo!()
+        // TODO: register allocator for higher optimize levels
+        OptimizeLevel::Zero | OptimizeLevel::One | OptimizeLevel::Two => {
+            Box::new(SimpleAllocator::new(cfg, target, is_leaf))
+        }
     }
-}
\ No newline at end of file
+}
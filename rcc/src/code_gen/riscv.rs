@@ -0,0 +1,1711 @@
+//! Code generation for RV32 and RV64.
+//!
+//! b(byte): 8bit
+//! h(half word): 16bit
+//! w(word): 32bit
+//! d(double word): 64bit
+use crate::analyser::callgraph::CallGraph;
+use crate::analyser::sym_resolver::VarKind;
+use crate::ast::expr::BinOperator;
+use crate::code_gen::{
+    asm_saved_regs, create_allocator, Allocator, CodeGenBackend, CodeGenOptions, TargetDesc,
+};
+use crate::intern::Symbol;
+use crate::ir::cfg::{CFG, CFGIR};
+use crate::ir::var_name::{
+    branch_name, pcrel_label, ALLOC, FP, FREE, MEMCPY, PANIC, PRINT_CHAR, PRINT_INT, PRINT_STR,
+    PRINT_STRING, PRINT_UINT, RA, STRING_PUSH_STR, VEC_FREE, VEC_INDEX, VEC_LEN, VEC_NEW,
+    VEC_PUSH, switch_table_name,
+};
+use crate::ir::{IRInst, IRType, Jump, Operand, Place};
+use crate::rcc::RccError;
+use std::io::Write;
+
+pub const RV32_XLEN: u32 = 32;
+pub const RV64_XLEN: u32 = 64;
+
+const ARG_REGS: &[&str] = &["a0", "a1", "a2", "a3", "a4", "a5", "a6", "a7"];
+const CALLEE_SAVED_REGS: &[&str] = &[
+    "s0", "s1", "s2", "s3", "s4", "s5", "s6", "s7", "s8", "s9", "s10", "s11",
+];
+
+/// RV32IM, ILP32 calling convention
+pub const RISCV32: TargetDesc = TargetDesc {
+    name: "riscv32",
+    pointer_width: RV32_XLEN,
+    stack_align: 8,
+    arg_regs: ARG_REGS,
+    ret_reg: "a0",
+    callee_saved_regs: CALLEE_SAVED_REGS,
+};
+
+/// RV64IM, LP64 calling convention
+pub const RISCV64: TargetDesc = TargetDesc {
+    name: "riscv64",
+    pointer_width: RV64_XLEN,
+    stack_align: 16,
+    arg_regs: ARG_REGS,
+    ret_reg: "a0",
+    callee_saved_regs: CALLEE_SAVED_REGS,
+};
+
+/// Registers of the operands of inline assembly, in order
+const ASM_REGS: [&str; 7] = ["t0", "t1", "t2", "t3", "t4", "t5", "t6"];
+
+/// `exit` system call of Linux
+const SYS_EXIT: u32 = 93;
+
+/// Exit code of a program which panics
+const PANIC_EXIT_CODE: u32 = 101;
+
+/// Largest 12-bit immediate of `addi`, loads and stores
+const MAX_IMM: u32 = 2047;
+
+/// Size of the heap used by `Box` in bytes
+const HEAP_SIZE: u32 = 64 * 1024;
+
+/// `write(fd = 1, buf = a0, count = strlen(a0))`
+const RUNTIME_PRINT_STR: &str = "\
+__rcc_print_str:
+\tmv\ta1,a0
+\tmv\ta2,a0
+1:
+\tlbu\ta3,0(a2)
+\tbeqz\ta3,2f
+\taddi\ta2,a2,1
+\tj\t1b
+2:
+\tsub\ta2,a2,a1
+\tli\ta0,1
+\tli\ta7,64
+\tecall
+\tret
+";
+
+/// `write(fd = 2, buf = a0, count = strlen(a0))`, then `exit(101)`
+const RUNTIME_PANIC: &str = "\
+__rcc_panic:
+\tmv\ta1,a0
+\tmv\ta2,a0
+1:
+\tlbu\ta3,0(a2)
+\tbeqz\ta3,2f
+\taddi\ta2,a2,1
+\tj\t1b
+2:
+\tsub\ta2,a2,a1
+\tli\ta0,2
+\tli\ta7,64
+\tecall
+";
+
+/// Memory of a bump allocator is never reused.
+const RUNTIME_FREE: &str = "\
+__rcc_free:
+\tret
+";
+
+/// Write the least significant byte of a0.
+const RUNTIME_PRINT_CHAR: &str = "\
+__rcc_print_char:
+\taddi\tsp,sp,-16
+\tsb\ta0,0(sp)
+\tli\ta0,1
+\tmv\ta1,sp
+\tli\ta2,1
+\tli\ta7,64
+\tecall
+\taddi\tsp,sp,16
+\tret
+";
+
+/// Write a0 in decimal. The digits are put into a buffer on the stack from the end,
+/// t0 is set if a '-' should be written before them.
+const RUNTIME_PRINT_INT: &str = "\
+__rcc_print_int:
+\tli\tt0,0
+\tbgez\ta0,__rcc_print_digits
+\tli\tt0,1
+\tneg\ta0,a0
+\tj\t__rcc_print_digits
+__rcc_print_uint:
+\tli\tt0,0
+__rcc_print_digits:
+\taddi\tsp,sp,-32
+\taddi\ta1,sp,32
+\tli\ta2,10
+1:
+\tremu\ta3,a0,a2
+\tdivu\ta0,a0,a2
+\taddi\ta3,a3,48
+\taddi\ta1,a1,-1
+\tsb\ta3,0(a1)
+\tbnez\ta0,1b
+\tbeqz\tt0,2f
+\tli\ta3,45
+\taddi\ta1,a1,-1
+\tsb\ta3,0(a1)
+2:
+\taddi\ta2,sp,32
+\tsub\ta2,a2,a1
+\tli\ta0,1
+\tli\ta7,64
+\tecall
+\taddi\tsp,sp,32
+\tret
+";
+
+/// `remu a3,a0,a2` and `divu a0,a0,a2` of `__rcc_print_digits` without the M extension
+const PRINT_DIGITS_DIV: (&str, &str) = (
+    "\tremu\ta3,a0,a2\n\tdivu\ta0,a0,a2\n",
+    "\tmv\tt3,a0\n\tmv\tt4,a2\n\tjal\tt5,__rcc_udivmod\n\tmv\ta0,t3\n\tmv\ta3,t4\n",
+);
+
+/// `t3 = t3 * t4` by shifts and adds. It returns to t5 and only changes t2-t4 and t6, so
+/// that the runtime can use it without saving registers.
+const RUNTIME_MUL: &str = "\
+__rcc_mul:
+\tmv\tt6,t3
+\tli\tt3,0
+1:
+\tandi\tt2,t4,1
+\tbeqz\tt2,2f
+\tadd\tt3,t3,t6
+2:
+\tslli\tt6,t6,1
+\tsrli\tt4,t4,1
+\tbnez\tt4,1b
+\tjr\tt5
+";
+
+/// Unsigned `t3 / t4` in t3 and `t3 % t4` in t4 by shifts and subtractions, with the same
+/// registers as `__rcc_mul`. Like `divu` and `remu`, `x / 0` is all ones and `x % 0` is `x`.
+const RUNTIME_UDIVMOD: &str = "\
+__rcc_udivmod:
+\tmv\tt6,t3
+\tli\tt3,-1
+\tbeqz\tt4,4f
+\tli\tt3,0
+\tli\tt2,1
+1:
+\tbgeu\tt4,t6,2f
+\tbltz\tt4,2f
+\tslli\tt4,t4,1
+\tslli\tt2,t2,1
+\tj\t1b
+2:
+\tbltu\tt6,t4,3f
+\tsub\tt6,t6,t4
+\tor\tt3,t3,t2
+3:
+\tsrli\tt4,t4,1
+\tsrli\tt2,t2,1
+\tbnez\tt2,2b
+4:
+\tmv\tt4,t6
+\tjr\tt5
+";
+
+/// `dest = src` of `a2` bytes, byte by byte
+const RUNTIME_MEMCPY: &str = "\
+__rcc_memcpy:
+\tbeqz\ta2,2f
+1:
+\tlbu\tt0,0(a1)
+\tsb\tt0,0(a0)
+\taddi\ta0,a0,1
+\taddi\ta1,a1,1
+\taddi\ta2,a2,-1
+\tbnez\ta2,1b
+2:
+\tret
+";
+
+/// A larger `Memcpy` calls `__rcc_memcpy` instead of an inline loop
+const MEMCPY_INLINE_MAX: u32 = 64;
+
+pub struct RiscvBackend {
+    target: &'static TargetDesc,
+    options: CodeGenOptions,
+    /// number of `auipc` emitted in the runtime, see `CodeGenOptions::pic`
+    pcrel_labels: usize,
+}
+
+impl RiscvBackend {
+    pub fn new(target: &'static TargetDesc, options: CodeGenOptions) -> RiscvBackend {
+        debug_assert!(target.pointer_width == RV32_XLEN || target.pointer_width == RV64_XLEN);
+        RiscvBackend {
+            target,
+            options,
+            pcrel_labels: 0,
+        }
+    }
+
+    /// `reg = &symbol` in the runtime, whose labels of `auipc` are in scope 0
+    fn gen_load_addr(
+        &mut self,
+        reg: &str,
+        symbol: &str,
+        out: &mut dyn Write,
+    ) -> Result<(), RccError> {
+        let label = self.options.pic.then(|| pcrel_label(0, self.pcrel_labels));
+        self.pcrel_labels += label.is_some() as usize;
+        load_addr(reg, symbol, label.as_deref(), out)
+    }
+
+    /// ```
+    /// _start:
+    ///     call    main
+    ///     li      a7,93       # exit(a0)
+    ///     ecall
+    /// ```
+    ///
+    /// If a stack size is given, sp is first set to the top of a stack in `.bss` whose
+    /// bottom is `__rcc_stack_limit`.
+    fn gen_start(&mut self, cfg_ir: &CFGIR, out: &mut dyn Write) -> Result<(), RccError> {
+        let returns_value = match cfg_ir.cfgs.iter().find(|cfg| cfg.func_name == "main") {
+            Some(main) => main.returns_value(),
+            None => match cfg_ir.precompiled_fns.iter().find(|f| f.name == "main") {
+                Some(main) => main.returns_value,
+                None => return Err("`main` function not found".into()),
+            },
+        };
+        writeln!(out, "\t.globl  _start")?;
+        writeln!(out, "_start:")?;
+        if self.options.stack_size.is_some() {
+            self.gen_load_addr("sp", "__rcc_stack_top", out)?;
+        }
+        writeln!(out, "\tcall\tmain")?;
+        if !returns_value {
+            writeln!(out, "\tli\t{},0", self.target.ret_reg)?;
+        }
+        writeln!(out, "\tli\ta7,{}", SYS_EXIT)?;
+        writeln!(out, "\tecall")?;
+        if let Some(stack_size) = self.options.stack_size {
+            writeln!(out, "\t.bss")?;
+            writeln!(out, "\t.align\t4")?;
+            writeln!(out, "\t.globl  __rcc_stack_limit")?;
+            writeln!(out, "__rcc_stack_limit:")?;
+            writeln!(out, "\t.zero\t{}", stack_size)?;
+            writeln!(out, "__rcc_stack_top:")?;
+            writeln!(out, "\t.text")?;
+        }
+        Ok(())
+    }
+
+    /// Called by a function prologue when sp is below `__rcc_stack_limit`. It must not touch
+    /// the stack.
+    fn gen_runtime_stack_overflow(&mut self, out: &mut dyn Write) -> Result<(), RccError> {
+        // write(2, "stack overflow\n", 15), then exit
+        writeln!(out, "__rcc_stack_overflow:")?;
+        writeln!(out, "\tli\ta0,2")?;
+        self.gen_load_addr("a1", "__rcc_stack_overflow_msg", out)?;
+        writeln!(out, "\tli\ta2,15")?;
+        writeln!(out, "\tli\ta7,64")?;
+        writeln!(out, "\tecall")?;
+        writeln!(out, "\tli\ta0,{}", PANIC_EXIT_CODE)?;
+        writeln!(out, "\tli\ta7,{}", SYS_EXIT)?;
+        writeln!(out, "\tecall")?;
+        writeln!(out, "\t.section\t.rodata")?;
+        writeln!(out, "__rcc_stack_overflow_msg:")?;
+        writeln!(out, "\t.string \"stack overflow\\n\"")?;
+        writeln!(out, "\t.text")?;
+        Ok(())
+    }
+
+    /// Runtime functions used by `print!`, `println!`, heap allocation and runtime checks,
+    /// they only use Linux system calls so that no libc is required. Only the functions
+    /// called by the program are emitted.
+    fn gen_runtime(&mut self, cfg_ir: &CFGIR, out: &mut dyn Write) -> Result<(), RccError> {
+        let call_graph = CallGraph::new(cfg_ir);
+        let is_called = |name: &str| call_graph.callers(name).next().is_some();
+        if is_called(PRINT_STR) {
+            write!(out, "{}", RUNTIME_PRINT_STR)?;
+        }
+        if is_called(PRINT_CHAR) {
+            write!(out, "{}", RUNTIME_PRINT_CHAR)?;
+        }
+        let prints_int = is_called(PRINT_INT) || is_called(PRINT_UINT);
+        if prints_int && self.options.m_extension {
+            write!(out, "{}", RUNTIME_PRINT_INT)?;
+        } else if prints_int {
+            let (div, soft_div) = PRINT_DIGITS_DIV;
+            write!(out, "{}", RUNTIME_PRINT_INT.replace(div, soft_div))?;
+        }
+        let uses_vec = [
+            VEC_NEW,
+            VEC_PUSH,
+            VEC_INDEX,
+            VEC_LEN,
+            VEC_FREE,
+            STRING_PUSH_STR,
+            PRINT_STRING,
+        ]
+        .iter()
+        .any(|f| is_called(f));
+        if uses_vec {
+            self.gen_runtime_vec(out)?;
+        }
+        if is_called(ALLOC) || uses_vec {
+            self.gen_runtime_alloc(out)?;
+        }
+        if is_called(FREE) {
+            write!(out, "{}", RUNTIME_FREE)?;
+        }
+        if is_called(PANIC) || uses_vec {
+            write!(out, "{}", RUNTIME_PANIC)?;
+            writeln!(out, "\tli\ta0,{}", PANIC_EXIT_CODE)?;
+            writeln!(out, "\tli\ta7,{}", SYS_EXIT)?;
+            writeln!(out, "\tecall")?;
+        }
+        if self.options.stack_check {
+            self.gen_runtime_stack_overflow(out)?;
+        }
+        if !self.options.m_extension {
+            let routines = self.soft_routines(cfg_ir);
+            self.gen_runtime_mul_div(&routines, uses_vec, prints_int, out)?;
+        }
+        // functions from the cache may call it, see `CFG::callees`
+        let calls_memcpy = cfg_ir.cfgs.iter().flat_map(|cfg| cfg.iter_inst()).any(|inst| {
+            matches!(inst, IRInst::Memcpy { size, .. } if *size > MEMCPY_INLINE_MAX)
+        });
+        if calls_memcpy || is_called(MEMCPY) {
+            write!(out, "{}", RUNTIME_MEMCPY)?;
+        }
+        Ok(())
+    }
+
+    /// Runtime routines called instead of the instructions of the M extension, in order of
+    /// first use
+    fn soft_routines(&self, cfg_ir: &CFGIR) -> Vec<String> {
+        let mut routines: Vec<String> = vec![];
+        for inst in cfg_ir.cfgs.iter().flat_map(|cfg| cfg.iter_inst()) {
+            if let IRInst::BinOp { op, dest, .. } = inst {
+                if let Some(inst) = m_ext_inst(op, &dest.ir_type) {
+                    let routine = soft_routine(inst, self.target.pointer_width);
+                    if !routines.contains(&routine) {
+                        routines.push(routine);
+                    }
+                }
+            }
+        }
+        routines
+    }
+
+    /// `__mulsi3`, `__divsi3`, `__modsi3`, `__udivsi3` and `__umodsi3` (`di3` on RV64) take
+    /// operands in a0 and a1 like C functions and call `__rcc_mul` or `__rcc_udivmod`, on the
+    /// absolute values for the signed ones. Signed division truncates toward zero and the
+    /// remainder has the sign of the dividend, division by zero gives the same results as
+    /// `div` and `rem`.
+    fn gen_runtime_mul_div(
+        &mut self,
+        routines: &[String],
+        uses_vec: bool,
+        prints_int: bool,
+        out: &mut dyn Write,
+    ) -> Result<(), RccError> {
+        let mut uses_mul = uses_vec;
+        let mut uses_udivmod = prints_int;
+        for routine in routines.iter() {
+            writeln!(out, "{}:", routine)?;
+            writeln!(out, "\tmv\tt3,a0")?;
+            writeln!(out, "\tmv\tt4,a1")?;
+            if routine.starts_with("__mul") {
+                writeln!(out, "\tjal\tt5,__rcc_mul")?;
+                writeln!(out, "\tmv\ta0,t3")?;
+                writeln!(out, "\tret")?;
+                uses_mul = true;
+                continue;
+            }
+            uses_udivmod = true;
+            // the unsigned routines need no sign fixups
+            let unsigned_result = if routine.starts_with("__udiv") {
+                Some("t3")
+            } else if routine.starts_with("__umod") {
+                Some("t4")
+            } else {
+                None
+            };
+            if let Some(reg) = unsigned_result {
+                writeln!(out, "\tjal\tt5,__rcc_udivmod")?;
+                writeln!(out, "\tmv\ta0,{}", reg)?;
+                writeln!(out, "\tret")?;
+                continue;
+            }
+            writeln!(out, "\tbgez\ta0,1f")?;
+            writeln!(out, "\tneg\tt3,t3")?;
+            writeln!(out, "1:")?;
+            writeln!(out, "\tbgez\ta1,2f")?;
+            writeln!(out, "\tneg\tt4,t4")?;
+            writeln!(out, "2:")?;
+            writeln!(out, "\tjal\tt5,__rcc_udivmod")?;
+            if routine.starts_with("__div") {
+                // the quotient is negative if the signs differ, x / 0 is -1
+                writeln!(out, "\tbeqz\ta1,3f")?;
+                writeln!(out, "\txor\ta1,a0,a1")?;
+                writeln!(out, "\tbgez\ta1,3f")?;
+                writeln!(out, "\tneg\tt3,t3")?;
+                writeln!(out, "3:")?;
+                writeln!(out, "\tmv\ta0,t3")?;
+            } else {
+                writeln!(out, "\tbgez\ta0,3f")?;
+                writeln!(out, "\tneg\tt4,t4")?;
+                writeln!(out, "3:")?;
+                writeln!(out, "\tmv\ta0,t4")?;
+            }
+            writeln!(out, "\tret")?;
+        }
+        if uses_mul {
+            write!(out, "{}", RUNTIME_MUL)?;
+        }
+        if uses_udivmod {
+            write!(out, "{}", RUNTIME_UDIVMOD)?;
+        }
+        Ok(())
+    }
+
+    /// `mul rd,rs1,rs2`, or a call of `__rcc_mul` without the M extension
+    fn gen_mul(&self, rd: &str, rs1: &str, rs2: &str, out: &mut dyn Write) -> Result<(), RccError> {
+        if self.options.m_extension {
+            writeln!(out, "\tmul\t{},{},{}", rd, rs1, rs2)?;
+        } else {
+            writeln!(out, "\tmv\tt3,{}", rs1)?;
+            writeln!(out, "\tmv\tt4,{}", rs2)?;
+            writeln!(out, "\tjal\tt5,__rcc_mul")?;
+            writeln!(out, "\tmv\t{},t3", rd)?;
+        }
+        Ok(())
+    }
+
+    /// `ld`/`sd` on RV64, `lw`/`sw` on RV32
+    fn load_store_word(&self) -> (&'static str, &'static str) {
+        if self.target.pointer_width == RV64_XLEN {
+            ("ld", "sd")
+        } else {
+            ("lw", "sw")
+        }
+    }
+
+    /// Runtime functions of `Vec<T>` and `String`. A vector is a pointer to `{ data, len, cap }`,
+    /// the capacity is doubled (at least 4) when a full vector is pushed. The memory of the old
+    /// data is not freed since the bump allocator never reuses memory.
+    fn gen_runtime_vec(&mut self, out: &mut dyn Write) -> Result<(), RccError> {
+        let (load, store) = self.load_store_word();
+        let len = self.target.pointer_width / 8;
+        let cap = len * 2;
+
+        writeln!(out, "{}:", VEC_NEW)?;
+        writeln!(out, "\taddi\tsp,sp,-16")?;
+        writeln!(out, "\t{}\tra,0(sp)", store)?;
+        writeln!(out, "\tli\ta0,{}", cap + len)?;
+        writeln!(out, "\tcall\t{}", ALLOC)?;
+        writeln!(out, "\t{}\tzero,0(a0)", store)?;
+        writeln!(out, "\t{}\tzero,{}(a0)", store, len)?;
+        writeln!(out, "\t{}\tzero,{}(a0)", store, cap)?;
+        writeln!(out, "\t{}\tra,0(sp)", load)?;
+        writeln!(out, "\taddi\tsp,sp,16")?;
+        writeln!(out, "\tret")?;
+
+        // a0: vector, a1: size of an element, return the address of the new element
+        writeln!(out, "{}:", VEC_PUSH)?;
+        writeln!(out, "\t{}\ta2,{}(a0)", load, len)?;
+        writeln!(out, "\t{}\ta3,{}(a0)", load, cap)?;
+        writeln!(out, "\tbltu\ta2,a3,4f")?;
+        writeln!(out, "\taddi\tsp,sp,-32")?;
+        writeln!(out, "\t{}\tra,0(sp)", store)?;
+        writeln!(out, "\t{}\ta0,8(sp)", store)?;
+        writeln!(out, "\t{}\ta1,16(sp)", store)?;
+        writeln!(out, "\tslli\ta3,a3,1")?;
+        writeln!(out, "\tbnez\ta3,1f")?;
+        writeln!(out, "\tli\ta3,4")?;
+        writeln!(out, "1:")?;
+        writeln!(out, "\t{}\ta3,24(sp)", store)?;
+        self.gen_mul("a0", "a3", "a1", out)?;
+        writeln!(out, "\tcall\t{}", ALLOC)?;
+        // copy len * size bytes to the new data
+        writeln!(out, "\t{}\ta1,8(sp)", load)?;
+        writeln!(out, "\t{}\ta2,0(a1)", load)?;
+        writeln!(out, "\t{}\ta3,{}(a1)", load, len)?;
+        writeln!(out, "\t{}\ta4,16(sp)", load)?;
+        self.gen_mul("a3", "a3", "a4", out)?;
+        writeln!(out, "\tmv\ta4,a0")?;
+        writeln!(out, "2:")?;
+        writeln!(out, "\tbeqz\ta3,3f")?;
+        writeln!(out, "\tlbu\ta5,0(a2)")?;
+        writeln!(out, "\tsb\ta5,0(a4)")?;
+        writeln!(out, "\taddi\ta2,a2,1")?;
+        writeln!(out, "\taddi\ta4,a4,1")?;
+        writeln!(out, "\taddi\ta3,a3,-1")?;
+        writeln!(out, "\tj\t2b")?;
+        writeln!(out, "3:")?;
+        writeln!(out, "\t{}\ta0,0(a1)", store)?;
+        writeln!(out, "\t{}\ta3,24(sp)", load)?;
+        writeln!(out, "\t{}\ta3,{}(a1)", store, cap)?;
+        writeln!(out, "\tmv\ta0,a1")?;
+        writeln!(out, "\t{}\ta1,16(sp)", load)?;
+        writeln!(out, "\t{}\tra,0(sp)", load)?;
+        writeln!(out, "\taddi\tsp,sp,32")?;
+        writeln!(out, "\t{}\ta2,{}(a0)", load, len)?;
+        writeln!(out, "4:")?;
+        writeln!(out, "\taddi\ta3,a2,1")?;
+        writeln!(out, "\t{}\ta3,{}(a0)", store, len)?;
+        self.gen_mul("a2", "a2", "a1", out)?;
+        writeln!(out, "\t{}\ta0,0(a0)", load)?;
+        writeln!(out, "\tadd\ta0,a0,a2")?;
+        writeln!(out, "\tret")?;
+
+        // a0: vector, a1: index, a2: size of an element
+        writeln!(out, "{}:", VEC_INDEX)?;
+        writeln!(out, "\t{}\ta3,{}(a0)", load, len)?;
+        writeln!(out, "\tbgeu\ta1,a3,1f")?;
+        self.gen_mul("a1", "a1", "a2", out)?;
+        writeln!(out, "\t{}\ta0,0(a0)", load)?;
+        writeln!(out, "\tadd\ta0,a0,a1")?;
+        writeln!(out, "\tret")?;
+        writeln!(out, "1:")?;
+        self.gen_load_addr("a0", "__rcc_index_msg", out)?;
+        writeln!(out, "\tj\t{}", PANIC)?;
+
+        writeln!(out, "{}:", VEC_LEN)?;
+        writeln!(out, "\t{}\ta0,{}(a0)", load, len)?;
+        writeln!(out, "\tret")?;
+
+        writeln!(out, "{}:", VEC_FREE)?;
+        writeln!(out, "\tret")?;
+
+        // push the bytes of the string a1 to the vector a0
+        writeln!(out, "{}:", STRING_PUSH_STR)?;
+        writeln!(out, "\taddi\tsp,sp,-32")?;
+        writeln!(out, "\t{}\tra,0(sp)", store)?;
+        writeln!(out, "\t{}\ta0,8(sp)", store)?;
+        writeln!(out, "1:")?;
+        writeln!(out, "\tlbu\ta2,0(a1)")?;
+        writeln!(out, "\tbeqz\ta2,2f")?;
+        writeln!(out, "\taddi\ta1,a1,1")?;
+        writeln!(out, "\t{}\ta1,16(sp)", store)?;
+        writeln!(out, "\t{}\ta2,24(sp)", store)?;
+        writeln!(out, "\t{}\ta0,8(sp)", load)?;
+        writeln!(out, "\tli\ta1,1")?;
+        writeln!(out, "\tcall\t{}", VEC_PUSH)?;
+        writeln!(out, "\t{}\ta2,24(sp)", load)?;
+        writeln!(out, "\tsb\ta2,0(a0)")?;
+        writeln!(out, "\t{}\ta1,16(sp)", load)?;
+        writeln!(out, "\tj\t1b")?;
+        writeln!(out, "2:")?;
+        writeln!(out, "\t{}\tra,0(sp)", load)?;
+        writeln!(out, "\taddi\tsp,sp,32")?;
+        writeln!(out, "\tret")?;
+
+        // write(fd = 1, buf = data, count = len)
+        writeln!(out, "{}:", PRINT_STRING)?;
+        writeln!(out, "\t{}\ta2,{}(a0)", load, len)?;
+        writeln!(out, "\t{}\ta1,0(a0)", load)?;
+        writeln!(out, "\tli\ta0,1")?;
+        writeln!(out, "\tli\ta7,64")?;
+        writeln!(out, "\tecall")?;
+        writeln!(out, "\tret")?;
+
+        writeln!(out, "\t.section\t.rodata")?;
+        writeln!(out, "__rcc_index_msg:")?;
+        writeln!(out, "\t.string \"panicked at 'index out of bounds'\\n\"")?;
+        writeln!(out, "\t.text")?;
+        Ok(())
+    }
+
+    /// A bump allocator which returns `a0` bytes aligned to 8 from `__rcc_heap`.
+    /// The program exits if the heap is exhausted.
+    fn gen_runtime_alloc(&mut self, out: &mut dyn Write) -> Result<(), RccError> {
+        let (load, store) = self.load_store_word();
+        writeln!(out, "__rcc_alloc:")?;
+        self.gen_load_addr("a1", "__rcc_heap_top", out)?;
+        writeln!(out, "\t{}\ta2,0(a1)", load)?;
+        writeln!(out, "\tbnez\ta2,1f")?;
+        self.gen_load_addr("a2", "__rcc_heap", out)?;
+        writeln!(out, "1:")?;
+        writeln!(out, "\taddi\ta0,a0,7")?;
+        writeln!(out, "\tandi\ta0,a0,-8")?;
+        writeln!(out, "\tadd\ta0,a2,a0")?;
+        self.gen_load_addr("a3", "__rcc_heap_end", out)?;
+        writeln!(out, "\tbgtu\ta0,a3,2f")?;
+        writeln!(out, "\t{}\ta0,0(a1)", store)?;
+        writeln!(out, "\tmv\ta0,a2")?;
+        writeln!(out, "\tret")?;
+        // write(2, "out of memory\n", 14), then exit
+        writeln!(out, "2:")?;
+        writeln!(out, "\tli\ta0,2")?;
+        self.gen_load_addr("a1", "__rcc_oom_msg", out)?;
+        writeln!(out, "\tli\ta2,14")?;
+        writeln!(out, "\tli\ta7,64")?;
+        writeln!(out, "\tecall")?;
+        writeln!(out, "\tli\ta0,{}", PANIC_EXIT_CODE)?;
+        writeln!(out, "\tli\ta7,{}", SYS_EXIT)?;
+        writeln!(out, "\tecall")?;
+        writeln!(out, "\t.section\t.rodata")?;
+        writeln!(out, "__rcc_oom_msg:")?;
+        writeln!(out, "\t.string \"out of memory\\n\"")?;
+        writeln!(out, "\t.bss")?;
+        writeln!(out, "\t.align\t3")?;
+        writeln!(out, "__rcc_heap_top:")?;
+        writeln!(out, "\t.zero\t8")?;
+        writeln!(out, "__rcc_heap:")?;
+        writeln!(out, "\t.zero\t{}", HEAP_SIZE)?;
+        writeln!(out, "__rcc_heap_end:")?;
+        writeln!(out, "\t.text")?;
+        Ok(())
+    }
+}
+
+impl CodeGenBackend for RiscvBackend {
+    /// Read only local strings and the beginning of `.text` section
+    /// Read only strings and vtables. An entry of a vtable takes 8 bytes on RV32 too, the
+    /// high word is 0.
+    fn gen_module_begin(&mut self, cfg_ir: &CFGIR, out: &mut dyn Write) -> Result<(), RccError> {
+        for (i, (name, _)) in self.options.debug_files.iter().enumerate() {
+            writeln!(out, "\t.file\t{} \"{}\"", i + 1, escape_asm_str(name))?;
+        }
+        if !cfg_ir.ro_local_strs.is_empty() || !cfg_ir.vtables.is_empty() {
+            writeln!(out, "\t.text")?;
+            writeln!(out, "\t.section\t.rodata")?;
+            for (label, s) in cfg_ir.ro_local_strs.iter() {
+                writeln!(out, "{}:", label)?;
+                writeln!(out, "\t.string \"{}\"", escape_asm_str(s))?;
+            }
+            // the entries of vtables are relocated when a position-independent image is loaded
+            if self.options.pic && !cfg_ir.vtables.is_empty() {
+                writeln!(out, "\t.section\t.data.rel.ro")?;
+            }
+            for (symbol, methods) in cfg_ir.vtables.iter() {
+                writeln!(out, "\t.align\t3")?;
+                writeln!(out, "{}:", symbol)?;
+                for method in methods.iter() {
+                    if self.target.pointer_width == RV64_XLEN {
+                        writeln!(out, "\t.dword\t{}", method)?;
+                    } else {
+                        writeln!(out, "\t.word\t{}", method)?;
+                        writeln!(out, "\t.word\t0")?;
+                    }
+                }
+            }
+        }
+        writeln!(out, "\t.text")?;
+        Ok(())
+    }
+
+    fn gen_func(&mut self, cfg: &CFG, out: &mut dyn Write) -> Result<(), RccError> {
+        let mut func_gen = FuncCodeGen::new(cfg, out, &self.options, self.target);
+        func_gen.stack_check = self.options.stack_check;
+        func_gen.debug_files = &self.options.debug_files;
+        func_gen.gen_function()
+    }
+
+    fn gen_module_end(&mut self, cfg_ir: &CFGIR, out: &mut dyn Write) -> Result<(), RccError> {
+        if let Some(stack_size) = self.options.stack_size {
+            if !self.options.emit_start {
+                return Err("the stack can only be set up by `_start`".into());
+            }
+            if stack_size == 0 || !stack_size.is_multiple_of(self.target.stack_align) {
+                return Err(format!(
+                    "stack size {} is not a positive multiple of {}",
+                    stack_size, self.target.stack_align
+                )
+                .into());
+            }
+        }
+        self.gen_runtime(cfg_ir, out)?;
+        if self.options.emit_start {
+            self.gen_start(cfg_ir, out)?;
+        }
+        Ok(())
+    }
+}
+
+struct FuncCodeGen<'w: 'codegen, 'codegen> {
+    cfg: &'codegen CFG,
+    output: &'w mut dyn Write,
+    allocator: Box<dyn Allocator + 'codegen>,
+    frame_size: u32,
+    target: &'static TargetDesc,
+    xlen: u32,
+    /// compare sp with `__rcc_stack_limit` after it is decreased in the prologue
+    stack_check: bool,
+    /// number of jump tables emitted
+    switch_tables: usize,
+    /// number of `auipc` emitted
+    pcrel_labels: usize,
+    /// see `CodeGenOptions::pic`
+    pic: bool,
+    /// see `CodeGenOptions::debug_files`
+    debug_files: &'codegen [(String, u32)],
+    /// see `CodeGenOptions::m_extension`
+    m_extension: bool,
+    /// whether ra is not saved, see `create_allocator`
+    is_leaf: bool,
+    /// see `asm_saved_regs`
+    saved_regs: Vec<&'static str>,
+}
+
+impl<'w: 'codegen, 'codegen> FuncCodeGen<'w, 'codegen> {
+    fn new(
+        cfg: &'codegen CFG,
+        output: &'w mut dyn Write,
+        options: &CodeGenOptions,
+        target: &'static TargetDesc,
+    ) -> FuncCodeGen<'w, 'codegen> {
+        let xlen = target.pointer_width;
+        // runtime routines of multiplication and division are called without the M extension,
+        // and `__rcc_memcpy` by large copies
+        let is_leaf = cfg.is_leaf
+            && !cfg.iter_inst().any(|inst| match inst {
+                IRInst::BinOp { op, dest, .. } => {
+                    !options.m_extension && m_ext_inst(op, &dest.ir_type).is_some()
+                }
+                IRInst::Memcpy { size, .. } => *size > MEMCPY_INLINE_MAX,
+                _ => false,
+            });
+        let allocator = create_allocator(options.opt_level, cfg, target, is_leaf);
+        let frame_size = allocator.get_frame_size();
+        FuncCodeGen {
+            cfg,
+            output,
+            allocator,
+            frame_size,
+            target,
+            xlen,
+            stack_check: false,
+            switch_tables: 0,
+            pcrel_labels: 0,
+            pic: options.pic,
+            debug_files: &[],
+            m_extension: options.m_extension,
+            is_leaf,
+            saved_regs: asm_saved_regs(cfg, target),
+        }
+    }
+
+    /// # Calling convention and stack frame of RC
+    ///
+    /// [calling convention of minidecaf]: https://decaf-lang.github.io/minidecaf-tutorial/docs/lab9/calling.html
+    /// [RISC-V ELF psABI specification]: https://github.com/riscv/riscv-elf-psabi-doc/blob/master/riscv-elf.md
+    /// [Volume I: RISC-V User-Level ISA V2.1draft]: https://riscv.org/wp-content/uploads/2015/01/riscv-calling.pdf
+    /// [RICS-V ISA Specifications]: https://riscv.org/technical/specifications/
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// fn foo(arg0: i32, arg1: i32, arg2: i32, arg3: i32,
+    ///        arg4: i32, arg5: i32, arg6: i32, arg7: i32,
+    ///        arg8: i32, arg9: i32) {
+    /// }
+    /// a0: arg0, a1: arg1, ...
+    ///
+    ///
+    /// High Address
+    ///
+    /// |  ...   |
+    /// +--------+     |
+    /// |  arg9  |     |-- stack frame of foo's caller
+    /// +--------+     |
+    /// |  arg8  |     |
+    /// +--------+ <---- fp(s0)
+    /// |   ra   |     |
+    /// +--------+     |
+    /// | old fp |     |-- stack frame of function foo
+    /// +--------+     |
+    /// | locals |     |
+    /// |(arg0 - |     |
+    /// | arg9)  |     |
+    /// +--------+     |
+    /// |outgoing|     |
+    /// |  args  |     |
+    /// +--------+ <---- sp
+    ///
+    /// Low Address
+    /// ```
+    ///
+    /// Arguments after the 8th are passed on the stack, each one takes `xlen / 8` bytes.
+    /// All the arguments are copied to the locals area on entry.
+    ///
+    /// Every value lives in the stack frame and only caller-saved registers are used as
+    /// temporaries, so s0 and the callee-saved registers named by inline assembly are the
+    /// only ones to save, the latter below old fp.
+    /// A function with `#[link_section]` is emitted in its section, then `.text` is restored
+    /// for the following functions.
+    fn gen_function(&mut self) -> Result<(), RccError> {
+        if self.cfg.func_link_section.is_some() {
+            writeln!(self.output, "\t{}", self.text_section())?;
+        }
+        if self.cfg.func_is_global {
+            writeln!(self.output, "\t.globl  {}", self.cfg.func_name)?;
+        }
+        writeln!(self.output, "{}:", self.cfg.func_name)?;
+        if !self.cfg.basic_blocks.is_empty() {
+            self.gen_function_entry()?;
+            self.gen_save_args()?;
+            self.gen_instructions()?;
+            self.gen_exit_function()?;
+        }
+        writeln!(self.output, "\tret")?;
+        if self.cfg.func_link_section.is_some() {
+            writeln!(self.output, "\t.text")?;
+        }
+        Ok(())
+    }
+
+    /// Directive of the section of the code of the function
+    fn text_section(&self) -> String {
+        match &self.cfg.func_link_section {
+            Some(section) => format!(".section\t{},\"ax\",@progbits", section),
+            None => ".text".to_string(),
+        }
+    }
+
+    /// A frame larger than the 12-bit immediates of `addi`, e.g. with a large array, is set up
+    /// through `t0`, which holds the new fp while the registers are saved.
+    fn gen_function_entry(&mut self) -> Result<(), RccError> {
+        debug_assert!(self.frame_size >= 8);
+        let is_large = self.frame_size > MAX_IMM;
+        // set sp
+        if is_large {
+            writeln!(self.output, "\tli\tt0,{}", self.frame_size)?;
+            writeln!(self.output, "\tsub\tsp,sp,t0")?;
+        } else {
+            writeln!(self.output, "\taddi\tsp,sp,-{}", self.frame_size)?;
+        }
+        if self.stack_check {
+            // nothing has been stored into the new frame yet
+            self.gen_load_addr("t0", "__rcc_stack_limit")?;
+            writeln!(self.output, "\tbgeu\tsp,t0,1f")?;
+            writeln!(self.output, "\tj\t__rcc_stack_overflow")?;
+            writeln!(self.output, "1:")?;
+        }
+        let reg_size = self.xlen / 8;
+        let (base, base_offset) = if is_large {
+            writeln!(self.output, "\tli\tt0,{}", self.frame_size)?;
+            writeln!(self.output, "\tadd\tt0,sp,t0")?;
+            ("t0", 0)
+        } else {
+            ("sp", self.frame_size as i32)
+        };
+        if !self.is_leaf {
+            // save ra
+            let offset = self.allocator.get_fp_offset(Symbol::intern(RA), &IRType::Addr);
+            debug_assert_eq!(reg_size, offset);
+            self.store_data(reg_size, "ra", base_offset - offset as i32, base)?;
+        }
+        // save old fp(s0)
+        let offset = self.allocator.get_fp_offset(Symbol::intern(FP), &IRType::Addr);
+        self.store_data(reg_size, "s0", base_offset - offset as i32, base)?;
+        for reg in self.saved_regs.clone() {
+            let offset = self.allocator.get_fp_offset(Symbol::intern(reg), &IRType::Addr);
+            self.store_data(reg_size, reg, base_offset - offset as i32, base)?;
+        }
+        // set fp
+        if is_large {
+            writeln!(self.output, "\tmv\ts0,t0")?;
+        } else {
+            writeln!(self.output, "\taddi\ts0,sp,{}", self.frame_size)?;
+        }
+        Ok(())
+    }
+
+    fn gen_exit_function(&mut self) -> Result<(), RccError> {
+        if self.frame_size > MAX_IMM {
+            return self.gen_exit_large_function();
+        }
+        for reg in self.saved_regs.iter() {
+            let offset = self.allocator.get_fp_offset(Symbol::intern(reg), &IRType::Addr);
+            let inst = self.load_inst(&IRType::Addr);
+            writeln!(self.output, "\t{}\t{},{}(sp)", inst, reg, self.frame_size - offset)?;
+        }
+        if !self.is_leaf {
+            // restore ra
+            let offset = self.allocator.get_fp_offset(Symbol::intern(RA), &IRType::Addr);
+            debug_assert_eq!(self.xlen / 8, offset);
+            let inst = self.load_inst(&IRType::Addr);
+            writeln!(self.output, "\t{}\tra,{}(sp)", inst, self.frame_size - offset)?;
+        }
+        // restore old fp
+        let offset = self.allocator.get_fp_offset(Symbol::intern(FP), &IRType::Addr);
+        let inst = self.load_inst(&IRType::Addr);
+        writeln!(self.output, "\t{}\ts0,{}(sp)", inst, self.frame_size - offset)?;
+        // restore sp
+        writeln!(self.output, "\taddi\tsp,sp,{}", self.frame_size)?;
+        Ok(())
+    }
+
+    /// The registers are restored from their offsets from fp, which is the old sp
+    fn gen_exit_large_function(&mut self) -> Result<(), RccError> {
+        let inst = self.load_inst(&IRType::Addr);
+        for reg in self.saved_regs.iter() {
+            let offset = self.allocator.get_fp_offset(Symbol::intern(reg), &IRType::Addr);
+            writeln!(self.output, "\t{}\t{},-{}(s0)", inst, reg, offset)?;
+        }
+        if !self.is_leaf {
+            let offset = self.allocator.get_fp_offset(Symbol::intern(RA), &IRType::Addr);
+            writeln!(self.output, "\t{}\tra,-{}(s0)", inst, offset)?;
+        }
+        let offset = self.allocator.get_fp_offset(Symbol::intern(FP), &IRType::Addr);
+        writeln!(self.output, "\tmv\tt0,s0")?;
+        writeln!(self.output, "\t{}\ts0,-{}(t0)", inst, offset)?;
+        writeln!(self.output, "\tmv\tsp,t0")?;
+        Ok(())
+    }
+
+    fn gen_save_args(&mut self) -> Result<(), RccError> {
+        let arg_regs = self.target.arg_regs;
+        let reg_size = self.xlen / 8;
+        for i in 0..self.cfg.fn_args.len() {
+            let arg_name = self.cfg.get_name_of_fn_arg(i).unwrap();
+            let (_, ir_type) = self.cfg.local_variables.get(&arg_name).unwrap();
+            let offset = self.allocator.get_fp_offset(arg_name, ir_type);
+            let size = ir_type.byte_size(self.xlen);
+            match arg_regs.get(i) {
+                Some(arg_reg) => self.store_data(size, arg_reg, -(offset as i32), "s0")?,
+                None => {
+                    // copy from the stack frame of the caller
+                    let caller_offset = (i - arg_regs.len()) as u32 * reg_size;
+                    let inst = self.load_inst(ir_type);
+                    writeln!(self.output, "\t{}\ta5,{}(s0)", inst, caller_offset)?;
+                    self.store_data(size, "a5", -(offset as i32), "s0")?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// `ret` in a block other than the last one, e.g. followed by cold blocks, jumps to the
+    /// exit of the function, which is labeled as the block after the last one.
+    fn gen_instructions(&mut self) -> Result<(), RccError> {
+        let exit = branch_name(self.cfg.func_scope_id, self.cfg.basic_blocks.len());
+        let mut jumps_to_exit = false;
+        for bb in self.cfg.basic_blocks.iter() {
+            if !bb.predecessors.is_empty() {
+                writeln!(self.output, "{}:", branch_name(self.cfg.func_scope_id, bb.id))?;
+            }
+            for inst in bb.instructions.iter() {
+                self.gen_instruction(inst)?;
+            }
+            let is_last = bb.id + 1 == self.cfg.basic_blocks.len();
+            if let (Some(IRInst::Ret(_)), false) = (bb.instructions.back(), is_last) {
+                writeln!(self.output, "\tj\t{}", exit)?;
+                jumps_to_exit = true;
+            }
+        }
+        if jumps_to_exit {
+            writeln!(self.output, "{}:", exit)?;
+        }
+        Ok(())
+    }
+
+    fn gen_instruction(&mut self, inst: &IRInst) -> Result<(), RccError> {
+        match inst {
+            IRInst::Ret(o) => self.load_arg(self.target.ret_reg, o)?,
+            IRInst::LoadData { dest, src } => match dest.kind {
+                VarKind::Local | VarKind::LocalMut => {
+                    let offset = self.allocator.get_fp_offset(dest.label, &dest.ir_type);
+                    self.load_data("a5", src)?;
+                    let size = dest.ir_type.byte_size(self.xlen);
+                    self.store_data(size, "a5", -(offset as i32), "s0")?;
+                }
+                _ => unimplemented!(),
+            },
+            IRInst::BinOp {
+                op,
+                dest,
+                src1,
+                src2,
+            } => {
+                if let Some(inst) = self.un_op_inst(op, dest, src1, src2) {
+                    let src = if src1.is_imm() { src2 } else { src1 };
+                    self.load_data("a5", src)?;
+                    writeln!(self.output, "\t{}\ta5,a5", inst)?;
+                    let offset = self.allocator.get_fp_offset(dest.label, &dest.ir_type);
+                    let size = dest.ir_type.byte_size(self.xlen);
+                    return self.store_data(size, "a5", -(offset as i32), "s0");
+                }
+                debug_assert!(!src1.is_imm());
+                // `a < b` as a value, e.g. the result of `PartialEq::eq`
+                if let Some(insts) = cmp_insts(op, is_unsigned(src1)) {
+                    self.load_data("a4", src1)?;
+                    self.load_data("a5", src2)?;
+                    for inst in insts {
+                        writeln!(self.output, "\t{}", inst)?;
+                    }
+                    let offset = self.allocator.get_fp_offset(dest.label, &dest.ir_type);
+                    let size = dest.ir_type.byte_size(self.xlen);
+                    return self.store_data(size, "a5", -(offset as i32), "s0");
+                }
+                if src2.is_imm() {
+                    self.load_data("a5", src1)?;
+                    self.bin_op_imm(op, dest, "a5", src2)?;
+                } else {
+                    self.load_data("a4", src1)?;
+                    self.load_data("a5", src2)?;
+                    self.bin_op(op, dest, "a4", "a5")?;
+                }
+            }
+            IRInst::Load { ty, dest, addr, .. } => {
+                self.load_data("a4", addr)?;
+                let inst = self.load_inst(ty);
+                writeln!(self.output, "\t{}\ta5,0(a4)", inst)?;
+                let offset = self.allocator.get_fp_offset(dest.label, &dest.ir_type);
+                let size = dest.ir_type.byte_size(self.xlen);
+                self.store_data(size, "a5", -(offset as i32), "s0")?;
+            }
+            IRInst::Store { ty, addr, src, .. } => {
+                self.load_data("a5", src)?;
+                self.load_data("a4", addr)?;
+                let size = ty.byte_size(self.xlen);
+                self.store_data(size, "a5", 0, "a4")?;
+            }
+            IRInst::LoadAddr { dest, symbol } => {
+                match symbol {
+                    Operand::Place(p) if matches!(p.kind, VarKind::Local | VarKind::LocalMut) => {
+                        let offset = self.allocator.get_fp_offset(p.label, &p.ir_type);
+                        // an array may be beyond the immediates of `addi`
+                        if offset > MAX_IMM + 1 {
+                            writeln!(self.output, "\tli\tt0,{}", offset)?;
+                            writeln!(self.output, "\tsub\ta5,s0,t0")?;
+                        } else {
+                            writeln!(self.output, "\taddi\ta5,s0,-{}", offset)?;
+                        }
+                    }
+                    _ => self.load_data("a5", symbol)?,
+                }
+                let offset = self.allocator.get_fp_offset(dest.label, &dest.ir_type);
+                let size = dest.ir_type.byte_size(self.xlen);
+                self.store_data(size, "a5", -(offset as i32), "s0")?;
+            }
+            IRInst::Call { callee, args } => {
+                self.pass_fn_args(args)?;
+                match callee {
+                    Operand::FnLabel(fn_name) => writeln!(self.output, "\tcall\t{}", fn_name)?,
+                    // a function pointer, e.g. a method in a vtable
+                    _ => {
+                        self.load_data("t1", callee)?;
+                        writeln!(self.output, "\tjalr\tt1")?;
+                    }
+                }
+            }
+            IRInst::Jump { label } => {
+                writeln!(self.output, "\tj\t{}", branch_name(self.cfg.func_scope_id, *label))?;
+            }
+            IRInst::JumpIfCond {
+                cond,
+                src1,
+                src2,
+                label,
+            } => {
+                self.load_data("a4", src1)?;
+                self.load_data("a5", src2)?;
+                let inst = match (cond, is_unsigned(src1)) {
+                    (Jump::JEq, _) => "beq",
+                    (Jump::JGe, false) => "ble",
+                    (Jump::JGe, true) => "bleu",
+                    (Jump::JLt, false) => "bgt",
+                    (Jump::JLt, true) => "bgtu",
+                    (Jump::JNe, _) => "bne",
+                };
+                writeln!(self.output, "\t{}\ta5,a4,{}", inst, branch_name(self.cfg.func_scope_id, *label))?;
+            }
+            IRInst::Switch {
+                src,
+                cases,
+                default,
+            } => self.gen_switch(src, cases, *default)?,
+            IRInst::Asm {
+                template,
+                inputs,
+                outputs,
+            } => self.gen_asm(template, inputs, outputs)?,
+            IRInst::Memcpy { size, srcs, dests } => self.gen_memcpy(*size, srcs, dests)?,
+            IRInst::Loc(line) => self.gen_loc(*line)?,
+            IRInst::BoundsCheck { .. } => {
+                unreachable!("bounds checks are lowered by the `bounds-checks` pass")
+            }
+            IRInst::JumpIf { cond, label } | IRInst::JumpIfNot { cond, label } => {
+                self.load_data("a5", cond)?;
+                let inst = match inst {
+                    IRInst::JumpIf { .. } => "bnez",
+                    _ => "beqz",
+                };
+                let label = branch_name(self.cfg.func_scope_id, *label);
+                writeln!(self.output, "\t{}\ta5,{}", inst, label)?;
+            }
+            _ => {
+                todo!()
+            }
+        }
+        Ok(())
+    }
+
+    /// The fields of the struct variables are laid out like the structs, see
+    /// `SimpleAllocator`, so the memory from the first field is copied. A small copy is a
+    /// loop over the largest units which the size and the addresses are aligned to, a large
+    /// one calls `__rcc_memcpy`.
+    fn gen_memcpy(&mut self, size: u32, srcs: &[Operand], dests: &[Place]) -> Result<(), RccError> {
+        let src_offset = match &srcs[0] {
+            Operand::Place(src) => self.allocator.get_fp_offset(src.label, &src.ir_type),
+            o => unreachable!("memcpy from {:?}", o),
+        };
+        let dest_offset = self.allocator.get_fp_offset(dests[0].label, &dests[0].ir_type);
+        if size > MEMCPY_INLINE_MAX {
+            writeln!(self.output, "\taddi\ta0,s0,-{}", dest_offset)?;
+            writeln!(self.output, "\taddi\ta1,s0,-{}", src_offset)?;
+            writeln!(self.output, "\tli\ta2,{}", size)?;
+            writeln!(self.output, "\tcall\t{}", MEMCPY)?;
+            return Ok(());
+        }
+        let mut unit = self.xlen / 8;
+        while ![size, src_offset, dest_offset].iter().all(|n| n.is_multiple_of(unit)) {
+            unit /= 2;
+        }
+        let (load, store) = match unit {
+            1 => ("lb", "sb"),
+            2 => ("lh", "sh"),
+            4 => ("lw", "sw"),
+            _ => ("ld", "sd"),
+        };
+        writeln!(self.output, "\taddi\ta5,s0,-{}", src_offset)?;
+        writeln!(self.output, "\taddi\ta4,s0,-{}", dest_offset)?;
+        writeln!(self.output, "\tli\ta3,{}", size / unit)?;
+        writeln!(self.output, "1:")?;
+        writeln!(self.output, "\t{}\ta2,0(a5)", load)?;
+        writeln!(self.output, "\t{}\ta2,0(a4)", store)?;
+        writeln!(self.output, "\taddi\ta5,a5,{}", unit)?;
+        writeln!(self.output, "\taddi\ta4,a4,{}", unit)?;
+        writeln!(self.output, "\taddi\ta3,a3,-1")?;
+        writeln!(self.output, "\tbnez\ta3,1b")?;
+        Ok(())
+    }
+
+    /// The operands of inline assembly are passed in `t0`-`t6` in order, the template is
+    /// emitted a line per instruction.
+    fn gen_asm(
+        &mut self,
+        template: &str,
+        inputs: &[Operand],
+        outputs: &[Place],
+    ) -> Result<(), RccError> {
+        if inputs.len() + outputs.len() > ASM_REGS.len() {
+            return Err(format!("`asm!` has more than {} operands", ASM_REGS.len()).into());
+        }
+        let mut sizes = inputs
+            .iter()
+            .map(|input| input.byte_size(self.xlen))
+            .chain(outputs.iter().map(|output| output.ir_type.byte_size(self.xlen)));
+        if sizes.any(|size| size > self.xlen / 8) {
+            let message = format!("operand of `asm!` does not fit in a {}-bit register", self.xlen);
+            return Err(message.into());
+        }
+        for (input, reg) in inputs.iter().zip(ASM_REGS.iter()) {
+            self.load_data(reg, input)?;
+        }
+        for line in asm_template(template, &ASM_REGS).lines() {
+            writeln!(self.output, "\t{}", line.trim())?;
+        }
+        for (output, reg) in outputs.iter().zip(ASM_REGS[inputs.len()..].iter()) {
+            let offset = self.allocator.get_fp_offset(output.label, &output.ir_type);
+            let size = output.ir_type.byte_size(self.xlen);
+            self.store_data(size, reg, -(offset as i32), "s0")?;
+        }
+        Ok(())
+    }
+
+    /// `.loc file line`, where `line` of the text is mapped to the file which contains it
+    fn gen_loc(&mut self, line: u32) -> Result<(), RccError> {
+        let file = self.debug_files.iter().rposition(|(_, start)| *start <= line);
+        if let Some(i) = file {
+            let line = line - self.debug_files[i].1 + 1;
+            writeln!(self.output, "\t.loc\t{} {}", i + 1, line)?;
+        }
+        Ok(())
+    }
+
+    /// `reg = &symbol`, PC-relative in position-independent code
+    fn gen_load_addr(&mut self, reg: &str, symbol: &str) -> Result<(), RccError> {
+        let scope_id = self.cfg.func_scope_id;
+        let label = self.pic.then(|| pcrel_label(scope_id, self.pcrel_labels));
+        self.pcrel_labels += label.is_some() as usize;
+        load_addr(reg, symbol, label.as_deref(), self.output)
+    }
+
+    /// A dense switch jumps through a table in `.rodata` indexed by `src - min`, where the
+    /// values out of range and the holes go to `default`. A sparse one is a chain of `beq`.
+    /// In position-independent code the entries are 32-bit offsets from the table.
+    fn gen_switch(
+        &mut self,
+        src: &Operand,
+        cases: &[(i64, usize)],
+        default: usize,
+    ) -> Result<(), RccError> {
+        let scope_id = self.cfg.func_scope_id;
+        self.load_data("a5", src)?;
+        let min = cases.iter().map(|(value, _)| *value).min().unwrap_or(0);
+        let max = cases.iter().map(|(value, _)| *value).max().unwrap_or(0);
+        let len = (max.wrapping_sub(min) as u64).saturating_add(1);
+        if cases.is_empty() || len > 3 * cases.len() as u64 {
+            for (value, label) in cases.iter() {
+                writeln!(self.output, "\tli\ta4,{}", value)?;
+                writeln!(self.output, "\tbeq\ta5,a4,{}", branch_name(scope_id, *label))?;
+            }
+            writeln!(self.output, "\tj\t{}", branch_name(scope_id, default))?;
+            return Ok(());
+        }
+
+        let table = switch_table_name(scope_id, self.switch_tables);
+        self.switch_tables += 1;
+        if min != 0 {
+            writeln!(self.output, "\tli\ta4,{}", min)?;
+            writeln!(self.output, "\tsub\ta5,a5,a4")?;
+        }
+        writeln!(self.output, "\tli\ta4,{}", len)?;
+        writeln!(self.output, "\tbgeu\ta5,a4,{}", branch_name(scope_id, default))?;
+        let (shift, directive) = if self.pic {
+            (2, ".word")
+        } else if self.xlen == RV64_XLEN {
+            (3, ".dword")
+        } else {
+            (2, ".word")
+        };
+        writeln!(self.output, "\tslli\ta5,a5,{}", shift)?;
+        self.gen_load_addr("a4", &table)?;
+        writeln!(self.output, "\tadd\ta5,a5,a4")?;
+        if self.pic {
+            writeln!(self.output, "\tlw\ta5,0(a5)")?;
+            writeln!(self.output, "\tadd\ta5,a5,a4")?;
+        } else {
+            writeln!(self.output, "\t{}\ta5,0(a5)", self.load_inst(&IRType::Addr))?;
+        }
+        writeln!(self.output, "\tjr\ta5")?;
+
+        writeln!(self.output, "\t.section\t.rodata")?;
+        writeln!(self.output, "\t.align\t{}", shift)?;
+        writeln!(self.output, "{}:", table)?;
+        for i in 0..len {
+            let value = min.wrapping_add(i as i64);
+            let label = match cases.iter().find(|(v, _)| *v == value) {
+                Some((_, label)) => *label,
+                None => default,
+            };
+            let label = branch_name(scope_id, label);
+            if self.pic {
+                writeln!(self.output, "\t{}\t{}-{}", directive, label, table)?;
+            } else {
+                writeln!(self.output, "\t{}\t{}", directive, label)?;
+            }
+        }
+        writeln!(self.output, "\t{}", self.text_section())?;
+        Ok(())
+    }
+
+    /// A slice or a trait object is two arguments, so it is passed in two registers like an
+    /// aggregate of two words in the psABI.
+    fn pass_fn_args(&mut self, args: &[Operand]) -> Result<(), RccError> {
+        let arg_regs = self.target.arg_regs;
+        // pass by stack, every argument takes `xlen / 8` bytes
+        let reg_size = self.xlen / 8;
+        for (i, arg) in args.iter().enumerate().skip(arg_regs.len()) {
+            self.load_data("a5", arg)?;
+            let offset = (i - arg_regs.len()) as u32 * reg_size;
+            let size = arg.byte_size(self.xlen);
+            self.store_data(size, "a5", offset as i32, "sp")?;
+        }
+        // pass by registers
+        for (arg, arg_reg) in args.iter().zip(arg_regs) {
+            self.load_arg(arg_reg, arg)?;
+        }
+        Ok(())
+    }
+
+    /// The psABI of RV64 requires 32-bit arguments and return values to be sign-extended
+    /// in registers, even if they are unsigned, so that C functions can be called directly.
+    fn load_arg(&mut self, reg_name: &str, arg: &Operand) -> Result<(), RccError> {
+        if self.xlen == RV64_XLEN {
+            match arg {
+                Operand::U32(u) => {
+                    writeln!(self.output, "\tli\t{},{}", reg_name, *u as i32)?;
+                    return Ok(());
+                }
+                Operand::Place(p)
+                    if p.ir_type == IRType::U32
+                        && matches!(p.kind, VarKind::Local | VarKind::LocalMut) =>
+                {
+                    let offset = self.allocator.get_fp_offset(p.label, &p.ir_type);
+                    writeln!(self.output, "\tlw\t{},-{}(s0)", reg_name, offset)?;
+                    return Ok(());
+                }
+                _ => {}
+            }
+        }
+        self.load_data(reg_name, arg)
+    }
+
+    /// lb, lbu, lh, lhu, lw, lwu(RV64 only), ld(RV64 only)
+    fn load_inst(&self, ir_type: &IRType) -> &'static str {
+        match (ir_type.byte_size(self.xlen), ir_type.is_unsigned()) {
+            (1, false) => "lb",
+            (1, true) => "lbu",
+            (2, false) => "lh",
+            (2, true) => "lhu",
+            (4, true) if self.xlen == RV64_XLEN => "lwu",
+            (4, _) => "lw",
+            (8, _) if self.xlen == RV64_XLEN => "ld",
+            _ => todo!(),
+        }
+    }
+
+    fn load_data(&mut self, reg_name: &str, operand: &Operand) -> Result<(), RccError> {
+        let asm_operand = AsmOperand::from_operand(operand, &mut *self.allocator);
+        let size = operand.byte_size(self.xlen);
+        match asm_operand {
+            AsmOperand::Imm(s) => {
+                writeln!(self.output, "\tli\t{},{}", reg_name, s)?;
+            }
+            AsmOperand::FpOffset(offset) => {
+                let inst = match operand {
+                    Operand::Place(p) => self.load_inst(&p.ir_type),
+                    _ => unreachable!(),
+                };
+                writeln!(self.output, "\t{}\t{},-{}(s0)", inst, reg_name, offset)?;
+            }
+            AsmOperand::Symbol(symbol) => self.gen_load_addr(reg_name, &symbol)?,
+            AsmOperand::Never | AsmOperand::Unit => {}
+            AsmOperand::FnRet(_ir_type) => {
+                debug_assert!(size <= self.xlen / 8);
+                let ret_reg = self.target.ret_reg;
+                if reg_name != ret_reg {
+                    writeln!(self.output, "\tmv\t{},{}", reg_name, ret_reg)?;
+                }
+            }
+            _ => unimplemented!("{:?}", asm_operand),
+        }
+        Ok(())
+    }
+
+    /// sb(store byte), sh(store half-word), sw(store word), sd(store double word, RV64 only)
+    fn store_data(
+        &mut self,
+        src_byte_size: u32,
+        src_reg_name: &str,
+        offset: i32,
+        tar_reg_name: &str,
+    ) -> Result<(), RccError> {
+        let inst = match src_byte_size {
+            1 => "sb",
+            2 => "sh",
+            4 => "sw",
+            8 if self.xlen == RV64_XLEN => "sd",
+            _ => todo!(),
+        };
+        writeln!(
+            self.output,
+            "\t{}\t{},{}({})",
+            inst, src_reg_name, offset, tar_reg_name
+        )?;
+        Ok(())
+    }
+
+    /// RV64 operates on 32-bit values with `addw`, `subw`, `mulw`, ...
+    /// `neg`, `seqz` or `not` of the unary operations lowered by the IR builder:
+    /// `0 - a`, `a ^ true` and `a ^ -1`.
+    fn un_op_inst(
+        &self,
+        op: &BinOperator,
+        dest: &Place,
+        src1: &Operand,
+        src2: &Operand,
+    ) -> Option<&'static str> {
+        match op {
+            BinOperator::Minus if src1.is_zero() && !src2.is_imm() => {
+                Some(if self.word_suffix(&dest.ir_type) == "w" { "negw" } else { "neg" })
+            }
+            BinOperator::Caret if !src1.is_imm() && src2 == &Operand::Bool(true) => Some("seqz"),
+            BinOperator::Caret
+                if !src1.is_imm()
+                    && dest.ir_type.is_int()
+                    && src2 == &Operand::int(dest.ir_type, -1) =>
+            {
+                Some("not")
+            }
+            _ => None,
+        }
+    }
+
+    fn word_suffix(&self, ir_type: &IRType) -> &'static str {
+        if self.xlen == RV64_XLEN && ir_type.byte_size(self.xlen) <= 4 {
+            "w"
+        } else {
+            ""
+        }
+    }
+
+    fn bin_op(
+        &mut self,
+        op: &BinOperator,
+        dest: &Place,
+        reg_src1: &str,
+        reg_src2: &str,
+    ) -> Result<(), RccError> {
+        match dest.kind {
+            VarKind::LocalMut | VarKind::Local => {
+                let offset = self.allocator.get_fp_offset(dest.label, &dest.ir_type);
+                let inst = match op {
+                    BinOperator::Plus => "add",
+                    BinOperator::Minus => "sub",
+                    BinOperator::And => "and",
+                    BinOperator::Or => "or",
+                    BinOperator::Caret => "xor",
+                    _ => match m_ext_inst(op, &dest.ir_type) {
+                        Some(inst) if !self.m_extension => {
+                            // a0 and a1 are free since every value lives in the stack frame
+                            writeln!(self.output, "\tmv\ta0,{}", reg_src1)?;
+                            writeln!(self.output, "\tmv\ta1,{}", reg_src2)?;
+                            writeln!(self.output, "\tcall\t{}", soft_routine(inst, self.xlen))?;
+                            writeln!(self.output, "\tmv\ta5,a0")?;
+                            let size = dest.ir_type.byte_size(self.xlen);
+                            return self.store_data(size, "a5", -(offset as i32), "s0");
+                        }
+                        Some(inst) => inst,
+                        None => todo!(),
+                    },
+                };
+                // bitwise instructions have no `w` forms
+                let suffix = match op {
+                    BinOperator::And | BinOperator::Or | BinOperator::Caret => "",
+                    _ => self.word_suffix(&dest.ir_type),
+                };
+                writeln!(
+                    self.output,
+                    "\t{}{}\ta5,{},{}",
+                    inst, suffix, reg_src1, reg_src2
+                )?;
+                self.store_data(
+                    dest.ir_type.byte_size(self.xlen),
+                    "a5",
+                    -(offset as i32),
+                    "s0",
+                )?;
+            }
+            _ => unimplemented!(),
+        }
+        Ok(())
+    }
+
+    fn bin_op_imm(
+        &mut self,
+        op: &BinOperator,
+        dest: &Place,
+        reg_src1: &str,
+        src2: &Operand,
+    ) -> Result<(), RccError> {
+        let asm_src2 = AsmOperand::from_operand(src2, &mut *self.allocator);
+        match asm_src2 {
+            AsmOperand::Imm(s) => match dest.kind {
+                VarKind::LocalMut | VarKind::Local => {
+                    let offset = self.allocator.get_fp_offset(dest.label, &dest.ir_type);
+                    // a larger immediate is loaded to a register
+                    let max = MAX_IMM as i64;
+                    let imm = s.parse::<i64>().ok();
+                    match op {
+                        BinOperator::Plus if imm.is_some_and(|n| (-max - 1..=max).contains(&n)) => {
+                            let suffix = self.word_suffix(&dest.ir_type);
+                            writeln!(self.output, "\taddi{}\ta5,{},{}", suffix, reg_src1, s)?;
+                            self.store_data(
+                                dest.ir_type.byte_size(self.xlen),
+                                "a5",
+                                -(offset as i32),
+                                "s0",
+                            )?;
+                        }
+                        BinOperator::Minus if imm.is_some_and(|n| (0..=max + 1).contains(&n)) => {
+                            let suffix = self.word_suffix(&dest.ir_type);
+                            writeln!(self.output, "\taddi{}\ta5,{},-{}", suffix, reg_src1, s)?;
+                            self.store_data(
+                                dest.ir_type.byte_size(self.xlen),
+                                "a5",
+                                -(offset as i32),
+                                "s0",
+                            )?;
+                        }
+                        _ => {
+                            self.load_data("a4", &src2)?;
+                            self.bin_op(op, dest, reg_src1, "a4")?;
+                        }
+                    }
+                }
+                _ => unimplemented!(),
+            },
+            _ => todo!(),
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+pub enum AsmOperand {
+    Imm(String),
+    Imm64(String, String),
+    Imm128(String, String, String, String),
+    Reg(String),
+    FpOffset(u32),
+    /// address of a symbol
+    Symbol(String),
+    Never,
+    Unit,
+    FnRet(IRType),
+}
+
+impl AsmOperand {
+    pub fn from_operand(operand: &Operand, allocator: &mut dyn Allocator) -> AsmOperand {
+        match operand {
+            Operand::Char(c) => Self::Imm((*c as u8).to_string()),
+            Operand::I8(i) => Self::Imm(i.to_string()),
+            Operand::I16(i) => Self::Imm(i.to_string()),
+            Operand::I32(i) => Self::Imm(i.to_string()),
+            Operand::I64(i) => Self::Imm(i.to_string()),
+            Operand::Isize(i) => Self::Imm(i.to_string()),
+            Operand::U8(i) => Self::Imm(i.to_string()),
+            Operand::U16(i) => Self::Imm(i.to_string()),
+            Operand::U32(i) => Self::Imm(i.to_string()),
+            Operand::U64(i) => Self::Imm(i.to_string()),
+            Operand::Usize(i) => Self::Imm(i.to_string()),
+            Operand::Bool(b) => Self::Imm((*b as u8).to_string()),
+            Operand::Place(p) => {
+                match p.kind {
+                    VarKind::Local | VarKind::LocalMut => {
+                        Self::FpOffset(allocator.get_fp_offset(p.label, &p.ir_type))
+                    }
+                    VarKind::LitConst => Self::Symbol(p.label.to_string()),
+                    // todo
+                    _ => Self::Unit,
+                }
+            }
+            Operand::Unit => Self::Unit,
+            Operand::Never => Self::Never,
+            Operand::FnRetPlace(ir_type) => Self::FnRet(ir_type.clone()),
+            _ => unimplemented!("{:?}", operand),
+        }
+    }
+}
+
+/// Instruction of the M extension which computes `op` on values of `ir_type`
+/// Whether the operand is compared as an unsigned integer
+fn is_unsigned(operand: &Operand) -> bool {
+    match operand {
+        Operand::Place(p) => p.ir_type.is_unsigned(),
+        operand => matches!(
+            operand,
+            Operand::U8(_) | Operand::U16(_) | Operand::U32(_) | Operand::U64(_) | Operand::Usize(_)
+        ),
+    }
+}
+
+/// `a5 = a4 op a5` of the comparison `op`, `slt` or `sltu` gives `<` and `>`, which are
+/// negated by `xori` for `>=` and `<=`
+fn cmp_insts(op: &BinOperator, unsigned: bool) -> Option<&'static [&'static str]> {
+    Some(match (op, unsigned) {
+        (BinOperator::Lt, false) => &["slt\ta5,a4,a5"],
+        (BinOperator::Lt, true) => &["sltu\ta5,a4,a5"],
+        (BinOperator::Gt, false) => &["slt\ta5,a5,a4"],
+        (BinOperator::Gt, true) => &["sltu\ta5,a5,a4"],
+        (BinOperator::Le, false) => &["slt\ta5,a5,a4", "xori\ta5,a5,1"],
+        (BinOperator::Le, true) => &["sltu\ta5,a5,a4", "xori\ta5,a5,1"],
+        (BinOperator::Ge, false) => &["slt\ta5,a4,a5", "xori\ta5,a5,1"],
+        (BinOperator::Ge, true) => &["sltu\ta5,a4,a5", "xori\ta5,a5,1"],
+        (BinOperator::EqEq, _) => &["xor\ta5,a4,a5", "seqz\ta5,a5"],
+        (BinOperator::Ne, _) => &["xor\ta5,a4,a5", "snez\ta5,a5"],
+        _ => return None,
+    })
+}
+
+fn m_ext_inst(op: &BinOperator, ir_type: &IRType) -> Option<&'static str> {
+    match op {
+        BinOperator::Star => Some("mul"),
+        BinOperator::Slash => match ir_type {
+            IRType::U8 | IRType::U16 | IRType::U32 | IRType::U64 | IRType::Usize => Some("divu"),
+            _ => Some("div"),
+        },
+        BinOperator::Percent => match ir_type {
+            IRType::I8 | IRType::I16 | IRType::I32 | IRType::I64 | IRType::Isize => Some("rem"),
+            IRType::U8 | IRType::U16 | IRType::U32 | IRType::U64 | IRType::Usize => Some("remu"),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Runtime routine which replaces `inst` of the M extension, named like the routines of
+/// libgcc. They work on whole registers, so they are `__mulsi3`, ... on RV32 and
+/// `__muldi3`, ... on RV64.
+fn soft_routine(inst: &str, xlen: u32) -> String {
+    let name = match inst {
+        "mul" => "mul",
+        "div" => "div",
+        "divu" => "udiv",
+        "rem" => "mod",
+        "remu" => "umod",
+        _ => unreachable!(),
+    };
+    let mode = if xlen == RV64_XLEN { "di" } else { "si" };
+    format!("__{}{}3", name, mode)
+}
+
+/// `reg = &symbol` by `lui` and `addi` of the absolute address, or by `auipc` and `addi` of
+/// the offset from the pc if the `auipc` has a label, which `%pcrel_lo` refers to.
+fn load_addr(
+    reg: &str,
+    symbol: &str,
+    pcrel_label: Option<&str>,
+    out: &mut dyn Write,
+) -> Result<(), RccError> {
+    match pcrel_label {
+        Some(label) => {
+            writeln!(out, "{}:", label)?;
+            writeln!(out, "\tauipc\t{},%pcrel_hi({})", reg, symbol)?;
+            writeln!(out, "\taddi\t{},{},%pcrel_lo({})", reg, reg, label)?;
+        }
+        None => {
+            writeln!(out, "\tlui\t{},%hi({})", reg, symbol)?;
+            writeln!(out, "\taddi\t{},{},%lo({})", reg, reg, symbol)?;
+        }
+    }
+    Ok(())
+}
+
+/// Escape `s` for `.string`. Bytes other than printable ASCII are written in octal, so the
+/// UTF-8 encoding of a non-ASCII char is kept.
+fn escape_asm_str(s: &str) -> String {
+    let mut res = String::with_capacity(s.len());
+    for b in s.bytes() {
+        match b {
+            b'"' => res.push_str("\\\""),
+            b'\\' => res.push_str("\\\\"),
+            b'\n' => res.push_str("\\n"),
+            b'\t' => res.push_str("\\t"),
+            b' '..=b'~' => res.push(b as char),
+            _ => res.push_str(&format!("\\{:03o}", b)),
+        }
+    }
+    res
+}
+
+/// Replace `{n}` in the template of inline assembly by `regs[n]`, `{{` and `}}` by braces.
+fn asm_template(template: &str, regs: &[&str]) -> String {
+    let mut res = String::with_capacity(template.len());
+    let mut chars = template.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '{' | '}' if chars.peek() == Some(&c) => {
+                chars.next();
+                res.push(c);
+            }
+            '{' => {
+                let n: String = chars.by_ref().take_while(|c| *c != '}').collect();
+                res.push_str(regs[n.parse::<usize>().unwrap()]);
+            }
+            _ => res.push(c),
+        }
+    }
+    res
+}
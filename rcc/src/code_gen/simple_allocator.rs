@@ -1,53 +1,181 @@
-use crate::code_gen::Allocator;
+use crate::code_gen::{asm_saved_regs, Allocator, TargetDesc};
+use crate::intern::Symbol;
 use crate::ir::cfg::CFG;
-use crate::ir::IRType;
+use crate::ir::var_name::{split_field_var, FP, RA};
+use crate::ir::{IRInst, IRType, Operand};
+use indexmap::IndexMap;
 use std::collections::HashMap;
 
+/// Every variable lives in the stack frame.
+///
+/// Layout from fp: ra (if not leaf), old fp, the callee-saved registers used by inline
+/// assembly, the struct variables copied by a `Memcpy` or borrowed, then other local
+/// variables grouped by size in descending order, so that each variable is aligned to its
+/// size, then the arrays aligned to 8 bytes, which keeps the offsets of the scalars small.
+/// Arguments passed on the stack to callees are at the bottom of the frame.
+///
+/// The fields of a struct variable copied by a `Memcpy` or borrowed are laid out like the
+/// struct in a block aligned to 8 bytes, so that the copy is a copy of memory and the fields
+/// are at their offsets from the address of the struct.
 pub struct SimpleAllocator<'cfg> {
     cfg: &'cfg CFG,
     addr_size: u32,
-    offset: u32,
-    var_offsets: HashMap<String, u32>,
+    /// byte size -> (offset of the last allocated variable, end of the region)
+    regions: HashMap<u32, (u32, u32)>,
+    /// offset of the last variable which is not in `cfg.local_variables`
+    overflow: u32,
+    frame_size: u32,
+    var_offsets: HashMap<Symbol, u32>,
+}
+
+fn align_to(n: u32, align: u32) -> u32 {
+    if align == 0 || n.is_multiple_of(align) {
+        n
+    } else {
+        (n / align + 1) * align
+    }
 }
 
 impl<'cfg> SimpleAllocator<'cfg> {
-    pub(crate) fn new(cfg: &CFG, addr_size: u32) -> SimpleAllocator {
+    pub(crate) fn new(
+        cfg: &'cfg CFG,
+        target: &TargetDesc,
+        is_leaf: bool,
+    ) -> SimpleAllocator<'cfg> {
+        let addr_size = target.pointer_width;
         debug_assert!(addr_size == 32 || addr_size == 64);
+        let reg_size = addr_size / 8;
+        let mut var_offsets = HashMap::new();
+        let mut offset = 0;
+        if !is_leaf {
+            offset += reg_size;
+            var_offsets.insert(Symbol::intern(RA), offset);
+        }
+        offset += reg_size;
+        var_offsets.insert(Symbol::intern(FP), offset);
+        for reg in asm_saved_regs(cfg, target) {
+            offset += reg_size;
+            var_offsets.insert(Symbol::intern(reg), offset);
+        }
+
+        for (size, fields) in struct_blocks(cfg, addr_size).into_values() {
+            offset = align_to(offset, 8) + align_to(size, 8);
+            for (label, field_offset) in fields {
+                var_offsets.insert(label, offset - field_offset);
+            }
+        }
+
+        let mut counts: HashMap<u32, u32> = HashMap::new();
+        let mut arrays = vec![];
+        for (label, (_id, ir_type)) in cfg.local_variables.iter() {
+            match ir_type {
+                IRType::Array(size) => arrays.push((*label, u32::from(*size))),
+                _ if !var_offsets.contains_key(label) => {
+                    *counts.entry(ir_type.byte_size(addr_size)).or_insert(0) += 1;
+                }
+                _ => {}
+            }
+        }
+        let mut sizes: Vec<u32> = counts.keys().copied().collect();
+        sizes.sort_unstable_by(|a, b| b.cmp(a));
+        let mut regions = HashMap::new();
+        for size in sizes {
+            offset = align_to(offset, size);
+            let end = offset + size * counts[&size];
+            regions.insert(size, (offset, end));
+            offset = end;
+        }
+        for (label, size) in arrays {
+            offset = align_to(offset, 8) + align_to(size, 8);
+            var_offsets.insert(label, offset);
+        }
+
+        let outgoing_args = cfg
+            .iter_inst()
+            .filter_map(|inst| match inst {
+                IRInst::Call { args, .. } => Some(args.len().saturating_sub(target.arg_regs.len())),
+                _ => None,
+            })
+            .max()
+            .unwrap_or(0) as u32;
+        let frame_size = align_to(offset + outgoing_args * reg_size, target.stack_align);
         SimpleAllocator {
             cfg,
             addr_size,
-            offset: 0,
-            var_offsets: HashMap::new(),
+            regions,
+            overflow: frame_size,
+            frame_size,
+            var_offsets,
         }
     }
 }
 
-impl<'cfg> Allocator for SimpleAllocator<'cfg> {
-    fn get_frame_size(&self) -> u32 {
-        // s0
-        let mut frame_size = self.addr_size / 8;
-        if !self.cfg.is_leaf {
-            frame_size *= 2;
-        }
-        // locals
-        for (_id, ir_type) in self.cfg.local_variables.values() {
-            frame_size += ir_type.byte_size(self.addr_size);
+/// Struct variable -> (size, its field variables with their offsets in the struct), for the
+/// struct variables of the places copied by a `Memcpy` or borrowed. The size covers the
+/// copies, so it includes the padding of the struct.
+fn struct_blocks(cfg: &CFG, addr_size: u32) -> IndexMap<Symbol, (u32, Vec<(Symbol, u32)>)> {
+    let mut blocks: IndexMap<Symbol, (u32, Vec<(Symbol, u32)>)> = IndexMap::new();
+    for inst in cfg.iter_inst() {
+        match inst {
+            IRInst::Memcpy { size, srcs, dests } => {
+                let src = match srcs.first() {
+                    Some(Operand::Place(src)) => src,
+                    _ => continue,
+                };
+                for label in [src.label, dests[0].label] {
+                    let (var, offset) = split_field_var(label.as_str()).unwrap();
+                    let (end, _) = blocks.entry(var).or_default();
+                    *end = (*end).max(offset + size);
+                }
+            }
+            // the other fields are reached from the address of a field
+            IRInst::LoadAddr {
+                symbol: Operand::Place(place),
+                ..
+            } => {
+                if let Some((var, _)) = split_field_var(place.label.as_str()) {
+                    blocks.entry(var).or_default();
+                }
+            }
+            _ => {}
         }
-        if frame_size % 8 == 0 {
-            frame_size
-        } else {
-            (frame_size / 8 + 1) * 8
+    }
+    for (label, (_id, ir_type)) in cfg.local_variables.iter() {
+        if let Some((var, offset)) = split_field_var(label.as_str()) {
+            if let Some((end, fields)) = blocks.get_mut(&var) {
+                *end = (*end).max(offset + ir_type.byte_size(addr_size));
+                fields.push((*label, offset));
+            }
         }
     }
+    blocks
+}
+
+impl<'cfg> Allocator for SimpleAllocator<'cfg> {
+    fn get_frame_size(&self) -> u32 {
+        self.frame_size
+    }
 
-    fn get_fp_offset(&mut self, var_name: &str, ir_type: &IRType) -> u32 {
-        match self.var_offsets.get(var_name) {
+    fn get_fp_offset(&mut self, var_name: Symbol, ir_type: &IRType) -> u32 {
+        match self.var_offsets.get(&var_name) {
             Some(offset) => *offset,
             None => {
                 let size = ir_type.byte_size(self.addr_size);
-                self.offset += size;
-                self.var_offsets.insert(var_name.to_string(), self.offset);
-                self.offset
+                let offset = match self.regions.get_mut(&size) {
+                    Some((last, end))
+                        if *last + size <= *end
+                            && self.cfg.local_variables.contains_key(&var_name) =>
+                    {
+                        *last += size;
+                        *last
+                    }
+                    _ => {
+                        self.overflow = align_to(self.overflow, size) + size;
+                        self.overflow
+                    }
+                };
+                self.var_offsets.insert(var_name, offset);
+                offset
             }
         }
     }
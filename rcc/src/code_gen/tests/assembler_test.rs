@@ -0,0 +1,146 @@
+use crate::code_gen::assembler::assemble;
+use crate::code_gen::elf::{RelocKind, SymbolBinding};
+use crate::rcc::RccError;
+
+fn words(data: &[u8]) -> Vec<u32> {
+    data.chunks(4)
+        .map(|w| u32::from_le_bytes([w[0], w[1], w[2], w[3]]))
+        .collect()
+}
+
+#[test]
+fn assemble_test() {
+    let text = "\
+\t.globl  main
+main:
+\taddi\tsp,sp,-16
+\tsd\ts0,8(sp)
+\tlw\ta5,-20(s0)
+\tmulw\ta5,a4,a5
+\tsrai\ta0,a0,63
+1:
+\tbnez\ta3,1b
+\tbgt\ta5,a4,1f
+\tjal\tt5,1f
+\tjalr\tt1
+1:
+\tret
+";
+    let obj = assemble(text, 64).unwrap();
+    // encoded by llvm-mc
+    assert_eq!(
+        vec![
+            0xff010113, 0x00813423, 0xfec42783, 0x02f707bb, 0x43f55513, 0x00069063, 0x00f74663,
+            0x00800f6f, 0x000300e7, 0x00008067
+        ],
+        words(&obj.section(0).data)
+    );
+    assert!(obj.section(0).relocations.is_empty());
+    assert!(obj.symbol_id("main").is_some());
+
+    assert_eq!(
+        Err(RccError::from("line 1: `mulw` is only available on RV64")),
+        assemble("\tmulw\ta5,a4,a5", 32).map(|_| ())
+    );
+    assert_eq!(
+        Err(RccError::from("line 2: unknown instruction `fadd.s`")),
+        assemble("\n\tfadd.s\tfa0,fa0,fa1", 32).map(|_| ())
+    );
+}
+
+#[test]
+fn assemble_reloc_test() {
+    let text = "\
+\t.section\t.rodata
+.LC0:
+\t.string \"a\\n\\303\\251\"
+.LSW1_0:
+\t.word\t.L1_1-.LSW1_0
+\t.text
+\t.globl  main
+main:
+\tlui\ta0,%hi(.LC0)
+\taddi\ta0,a0,%lo(.LC0)
+.LPC1_0:
+\tauipc\ta1,%pcrel_hi(.LSW1_0)
+\taddi\ta1,a1,%pcrel_lo(.LPC1_0)
+\tcall\tputs
+.L1_1:
+\tcall\tmain
+\tj\texit
+";
+    let mut obj = assemble(text, 32).unwrap();
+    let rodata = obj.section(1);
+    assert_eq!(".rodata", rodata.name);
+    assert_eq!(b"a\n\xc3\xa9\0", &rodata.data[..5]);
+    let kinds: Vec<RelocKind> = rodata.relocations.iter().map(|r| r.kind).collect();
+    assert_eq!(vec![RelocKind::Sub32, RelocKind::Add32], kinds);
+
+    let text = obj.section(0);
+    let relocations: Vec<(u64, RelocKind)> =
+        text.relocations.iter().map(|r| (r.offset, r.kind)).collect();
+    assert_eq!(
+        vec![
+            (0, RelocKind::Hi20),
+            (4, RelocKind::Lo12I),
+            (8, RelocKind::PcrelHi20),
+            (12, RelocKind::PcrelLo12I),
+            (16, RelocKind::CallPlt),
+            (32, RelocKind::Jal),
+        ],
+        relocations
+    );
+    // `call main` is resolved: auipc ra,0; jalr -24(ra)
+    assert_eq!(vec![0x00000097, 0xfe8080e7], words(&text.data[24..32]));
+
+    let main = obj.symbol_id("main").unwrap();
+    assert_eq!(SymbolBinding::Global, obj.symbol_mut(main).binding);
+    let puts = obj.symbol_id("puts").unwrap();
+    assert_eq!(None, obj.symbol_mut(puts).section);
+    // `.L` labels are symbols only if relocations refer to them
+    assert!(obj.symbol_id(".LPC1_0").is_some());
+    assert!(obj.symbol_id(".L1_1").is_some());
+}
+
+/// Value of the register after `li` on RV64
+fn run_li(value: i64) -> i64 {
+    let obj = assemble(&format!("\tli\ta0,{}", value), 64).unwrap();
+    let mut a0: i64 = 0;
+    for inst in words(&obj.section(0).data) {
+        let imm12 = (inst as i32 >> 20) as i64;
+        let rs1 = if (inst >> 15) & 0x1f == 0 { 0 } else { a0 };
+        a0 = match (inst & 0x7f, (inst >> 12) & 7) {
+            (0x37, _) => (inst & 0xfffff000) as i32 as i64,
+            (0x13, 0) => rs1.wrapping_add(imm12),
+            (0x1b, 0) => rs1.wrapping_add(imm12) as i32 as i64,
+            (0x13, 1) => a0 << (imm12 & 0x3f),
+            _ => panic!("unexpected instruction {:#x}", inst),
+        };
+    }
+    a0
+}
+
+#[test]
+fn assemble_li_test() {
+    let values = [
+        0,
+        -1,
+        2047,
+        -2048,
+        4096,
+        0x7fffffff,
+        -0x80000000,
+        0x80000000,
+        4000000000,
+        81985529216486895,
+        i64::MAX,
+        i64::MIN,
+        0x123456789abcdef0,
+    ];
+    for value in values.iter() {
+        assert_eq!(*value, run_li(*value), "li {}", value);
+    }
+    let obj = assemble("\tli\ta0,4294967295", 32).unwrap();
+    // addi a0,zero,-1
+    assert_eq!(vec![0xfff00513], words(&obj.section(0).data));
+}
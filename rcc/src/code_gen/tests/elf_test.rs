@@ -0,0 +1,173 @@
+use crate::code_gen::elf::{
+    ElfClass, ObjectFile, RelocKind, Relocation, SectionKind, Symbol, SymbolBinding, SymbolKind,
+};
+
+/// ```
+/// main:
+///     auipc   ra,0        # call putchar
+///     jalr    ra,0(ra)
+///     ret
+/// ```
+fn hello_object(class: ElfClass) -> Vec<u8> {
+    let mut obj = ObjectFile::new(class);
+    let text = obj.add_section(".text", SectionKind::Code, 4);
+    let rodata = obj.add_section(".rodata", SectionKind::ReadOnlyData, 1);
+    let code: [u32; 3] = [0x00000097, 0x000080e7, 0x00008067];
+    for inst in code.iter() {
+        obj.section_mut(text).data.extend_from_slice(&inst.to_le_bytes());
+    }
+    obj.section_mut(rodata).data.extend_from_slice(b"hello\0");
+
+    obj.add_symbol(Symbol {
+        name: "main".into(),
+        section: Some(text),
+        value: 0,
+        size: 12,
+        binding: SymbolBinding::Global,
+        kind: SymbolKind::Func,
+    });
+    obj.add_symbol(Symbol {
+        name: ".LC0".into(),
+        section: Some(rodata),
+        value: 0,
+        size: 6,
+        binding: SymbolBinding::Local,
+        kind: SymbolKind::Object,
+    });
+    let putchar = obj.get_or_add_undefined("putchar");
+    obj.add_relocation(
+        text,
+        Relocation {
+            offset: 0,
+            symbol: putchar,
+            kind: RelocKind::CallPlt,
+            addend: 0,
+        },
+    );
+    let mut out = vec![];
+    obj.write(&mut out).unwrap();
+    out
+}
+
+fn u16_at(elf: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([elf[offset], elf[offset + 1]])
+}
+
+fn u32_at(elf: &[u8], offset: usize) -> u32 {
+    let mut bytes = [0; 4];
+    bytes.copy_from_slice(&elf[offset..offset + 4]);
+    u32::from_le_bytes(bytes)
+}
+
+fn u64_at(elf: &[u8], offset: usize) -> u64 {
+    let mut bytes = [0; 8];
+    bytes.copy_from_slice(&elf[offset..offset + 8]);
+    u64::from_le_bytes(bytes)
+}
+
+fn c_str_at(elf: &[u8], offset: usize) -> String {
+    let end = elf[offset..].iter().position(|b| *b == 0).unwrap();
+    String::from_utf8(elf[offset..offset + end].to_vec()).unwrap()
+}
+
+/// (name, type, offset, size, link, info) of each section
+fn section_headers(elf: &[u8]) -> Vec<(String, u32, usize, usize, u32, u32)> {
+    let is_64 = elf[4] == 2;
+    let (shoff, shentsize, shnum, shstrndx) = if is_64 {
+        (u64_at(elf, 0x28) as usize, u16_at(elf, 0x3a), u16_at(elf, 0x3c), u16_at(elf, 0x3e))
+    } else {
+        (u32_at(elf, 0x20) as usize, u16_at(elf, 0x2e), u16_at(elf, 0x30), u16_at(elf, 0x32))
+    };
+    let header = |i: usize| {
+        let h = shoff + i * shentsize as usize;
+        if is_64 {
+            (
+                u32_at(elf, h),
+                u32_at(elf, h + 4),
+                u64_at(elf, h + 0x18) as usize,
+                u64_at(elf, h + 0x20) as usize,
+                u32_at(elf, h + 0x28),
+                u32_at(elf, h + 0x2c),
+            )
+        } else {
+            (
+                u32_at(elf, h),
+                u32_at(elf, h + 4),
+                u32_at(elf, h + 0x10) as usize,
+                u32_at(elf, h + 0x14) as usize,
+                u32_at(elf, h + 0x18),
+                u32_at(elf, h + 0x1c),
+            )
+        }
+    };
+    let shstrtab_offset = header(shstrndx as usize).2;
+    (0..shnum as usize)
+        .map(|i| {
+            let (name, sh_type, offset, size, link, info) = header(i);
+            let name = c_str_at(elf, shstrtab_offset + name as usize);
+            (name, sh_type, offset, size, link, info)
+        })
+        .collect()
+}
+
+fn test_hello_object(class: ElfClass, class_byte: u8, sym_size: usize) {
+    let elf = hello_object(class);
+    assert_eq!(b"\x7fELF", &elf[..4]);
+    assert_eq!(class_byte, elf[4]);
+    // ET_REL, EM_RISCV
+    assert_eq!(1, u16_at(&elf, 0x10));
+    assert_eq!(243, u16_at(&elf, 0x12));
+
+    let headers = section_headers(&elf);
+    let names: Vec<&str> = headers.iter().map(|h| h.0.as_str()).collect();
+    assert_eq!(
+        vec!["", ".text", ".rodata", ".rela.text", ".symtab", ".strtab", ".shstrtab"],
+        names
+    );
+
+    let (_, _, text_offset, text_size, _, _) = headers[1];
+    assert_eq!(12, text_size);
+    assert_eq!(0x00008067, u32_at(&elf, text_offset + 8));
+
+    // .rela.text applies to .text and uses .symtab
+    let (_, sh_type, _, _, link, info) = headers[3];
+    assert_eq!((4, 4, 1), (sh_type, link, info));
+
+    // null, .LC0 | main, putchar
+    let (_, sh_type, symtab_offset, symtab_size, link, first_global) = headers[4];
+    assert_eq!((2, 5, 2), (sh_type, link, first_global));
+    assert_eq!(4 * sym_size, symtab_size);
+    let strtab_offset = headers[5].2;
+    let sym_names: Vec<String> = (0..4)
+        .map(|i| c_str_at(&elf, strtab_offset + u32_at(&elf, symtab_offset + i * sym_size) as usize))
+        .collect();
+    assert_eq!(vec!["", ".LC0", "main", "putchar"], sym_names);
+}
+
+#[test]
+fn elf32_test() {
+    test_hello_object(ElfClass::Elf32, 1, 16);
+}
+
+#[test]
+fn elf64_test() {
+    test_hello_object(ElfClass::Elf64, 2, 24);
+}
+
+#[test]
+fn unknown_section_test() {
+    let mut obj = ObjectFile::new(ElfClass::Elf32);
+    obj.add_symbol(Symbol {
+        name: "foo".into(),
+        section: Some(3),
+        value: 0,
+        size: 0,
+        binding: SymbolBinding::Global,
+        kind: SymbolKind::Func,
+    });
+    let mut out = vec![];
+    assert_eq!(
+        Err("symbol `foo` is in an unknown section".into()),
+        obj.write(&mut out)
+    );
+}
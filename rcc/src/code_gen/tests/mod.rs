@@ -0,0 +1,4 @@
+#[cfg(test)]
+mod assembler_test;
+#[cfg(test)]
+mod elf_test;
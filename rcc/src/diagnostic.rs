@@ -0,0 +1,271 @@
+//! Rendering of compiler diagnostics for terminals, in the style of rustc:
+//!
+//! ```text
+//! error[E0384]: cannot assign twice to immutable variable `a`
+//!  --> foo.rc:3:5
+//!   |
+//! 2 |     let a = 1;
+//!   |         - first assignment
+//! 3 |     a = 2;
+//!   |     ^^^^^ cannot assign twice
+//!   |
+//!   = note: a variable is mutable only if it is declared by `let mut`
+//! ```
+use std::io::IsTerminal;
+
+/// Byte range `[lo, hi)` in the text of a `SourceMap`
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Span {
+    pub lo: usize,
+    pub hi: usize,
+}
+
+impl Span {
+    pub fn new(lo: usize, hi: usize) -> Span {
+        debug_assert!(lo <= hi);
+        Span { lo, hi }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Label {
+    pub span: Span,
+    pub message: String,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Diagnostic {
+    /// e.g. `E0384`
+    pub code: Option<String>,
+    pub message: String,
+    /// underlined with `^`
+    pub primary: Option<Label>,
+    /// underlined with `-`, e.g. "variable declared here"
+    pub secondary: Vec<Label>,
+    pub notes: Vec<String>,
+    pub helps: Vec<String>,
+}
+
+impl Diagnostic {
+    pub fn error(message: String) -> Diagnostic {
+        Diagnostic {
+            code: None,
+            message,
+            primary: None,
+            secondary: vec![],
+            notes: vec![],
+            helps: vec![],
+        }
+    }
+
+    pub fn code(mut self, code: &str) -> Diagnostic {
+        self.code = Some(code.to_string());
+        self
+    }
+
+    pub fn note(mut self, note: &str) -> Diagnostic {
+        self.notes.push(note.to_string());
+        self
+    }
+
+    pub fn help(mut self, help: &str) -> Diagnostic {
+        self.helps.push(help.to_string());
+        self
+    }
+
+    pub fn primary(mut self, span: Span, message: &str) -> Diagnostic {
+        self.primary = Some(Label {
+            span,
+            message: message.to_string(),
+        });
+        self
+    }
+
+    pub fn secondary(mut self, span: Span, message: &str) -> Diagnostic {
+        self.secondary.push(Label {
+            span,
+            message: message.to_string(),
+        });
+        self
+    }
+}
+
+/// The input files, which are compiled together as one text.
+#[derive(Default)]
+pub struct SourceMap {
+    text: String,
+    /// (file name, offset of the first byte in `text`)
+    files: Vec<(String, usize)>,
+}
+
+impl SourceMap {
+    pub fn new() -> SourceMap {
+        SourceMap::default()
+    }
+
+    pub fn add_file(&mut self, name: &str, src: &str) {
+        self.files.push((name.to_string(), self.text.len()));
+        self.text.push_str(src);
+        self.text.push('\n');
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// File name, line number and column number (both start from 1) of `offset`
+    pub fn lookup(&self, offset: usize) -> (&str, usize, usize) {
+        let (name, start) = self
+            .files
+            .iter()
+            .rev()
+            .find(|(_, start)| *start <= offset)
+            .map(|(name, start)| (name.as_str(), *start))
+            .unwrap_or(("<input>", 0));
+        let before = &self.text[start..offset];
+        let line = before.matches('\n').count() + 1;
+        let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+        let col = before[line_start..].chars().count() + 1;
+        (name, line, col)
+    }
+
+    /// Name of each file and the line of the text where it starts, from 1
+    pub fn file_lines(&self) -> Vec<(String, u32)> {
+        self.files
+            .iter()
+            .map(|(name, start)| {
+                let line = self.text[..*start].matches('\n').count() as u32 + 1;
+                (name.clone(), line)
+            })
+            .collect()
+    }
+
+    /// Text of the line containing `offset` without '\n', and the offset where it starts
+    fn line_of(&self, offset: usize) -> (&str, usize) {
+        let start = self.text[..offset].rfind('\n').map_or(0, |i| i + 1);
+        let end = self.text[offset..]
+            .find('\n')
+            .map_or(self.text.len(), |i| offset + i);
+        (&self.text[start..end], start)
+    }
+}
+
+const RESET: &str = "\x1b[0m";
+const BOLD: &str = "\x1b[1m";
+const RED: &str = "\x1b[1;31m";
+const BLUE: &str = "\x1b[1;34m";
+
+/// Colors are used if stderr is a terminal and `NO_COLOR` is not set.
+pub fn stderr_supports_color() -> bool {
+    std::env::var_os("NO_COLOR").is_none() && std::io::stderr().is_terminal()
+}
+
+pub struct Renderer<'a> {
+    source_map: &'a SourceMap,
+    color: bool,
+}
+
+impl<'a> Renderer<'a> {
+    pub fn new(source_map: &'a SourceMap, color: bool) -> Renderer<'a> {
+        Renderer { source_map, color }
+    }
+
+    fn paint(&self, color: &str, s: &str) -> String {
+        if self.color {
+            format!("{}{}{}", color, s, RESET)
+        } else {
+            s.to_string()
+        }
+    }
+
+    pub fn render(&self, diagnostic: &Diagnostic) -> String {
+        let level = match &diagnostic.code {
+            Some(code) => format!("error[{}]", code),
+            None => "error".to_string(),
+        };
+        let mut out = format!(
+            "{}{}\n",
+            self.paint(RED, &level),
+            self.paint(BOLD, &format!(": {}", diagnostic.message))
+        );
+
+        // (line number, label, is primary)
+        let mut labels = vec![];
+        for (label, is_primary) in diagnostic
+            .primary
+            .iter()
+            .map(|l| (l, true))
+            .chain(diagnostic.secondary.iter().map(|l| (l, false)))
+        {
+            let (_, line, _) = self.source_map.lookup(label.span.lo);
+            labels.push((line, label, is_primary));
+        }
+        if labels.is_empty() {
+            self.render_notes(diagnostic, 0, &mut out);
+            return out;
+        }
+        labels.sort_by_key(|(line, _, _)| *line);
+
+        let width = labels.last().unwrap().0.to_string().len();
+        let gutter = self.paint(BLUE, &format!("{} |", " ".repeat(width)));
+        let main_span = diagnostic.primary.as_ref().map_or(labels[0].1.span, |l| l.span);
+        let (file, line, col) = self.source_map.lookup(main_span.lo);
+        out.push_str(&format!(
+            "{}{} {}:{}:{}\n",
+            " ".repeat(width),
+            self.paint(BLUE, "-->"),
+            file,
+            line,
+            col
+        ));
+        out.push_str(&gutter);
+        out.push('\n');
+
+        let mut last_line = None;
+        for (line, label, is_primary) in labels {
+            let (text, line_start) = self.source_map.line_of(label.span.lo);
+            if last_line != Some(line) {
+                out.push_str(&self.paint(BLUE, &format!("{:>w$} |", line, w = width)));
+                out.push_str(&format!(" {}\n", text));
+                last_line = Some(line);
+            }
+            // the underline stops at the end of the line
+            let lo = label.span.lo - line_start;
+            let hi = (label.span.hi - line_start).min(text.len()).max(lo);
+            let indent = text[..lo].chars().count();
+            let len = text[lo..hi].chars().count().max(1);
+            let (mark, color) = if is_primary {
+                ("^", RED)
+            } else {
+                ("-", BLUE)
+            };
+            let underline = format!("{} {}", mark.repeat(len), label.message);
+            out.push_str(&format!(
+                "{} {}{}\n",
+                gutter,
+                " ".repeat(indent),
+                self.paint(color, underline.trim_end())
+            ));
+        }
+        if !diagnostic.notes.is_empty() || !diagnostic.helps.is_empty() {
+            out.push_str(&gutter);
+            out.push('\n');
+        }
+        self.render_notes(diagnostic, width, &mut out);
+        out
+    }
+
+    /// `= note: ...` and `= help: ...` lines aligned with the gutter
+    fn render_notes(&self, diagnostic: &Diagnostic, width: usize, out: &mut String) {
+        let notes = diagnostic.notes.iter().map(|note| ("note", note));
+        for (kind, note) in notes.chain(diagnostic.helps.iter().map(|help| ("help", help))) {
+            out.push_str(&format!(
+                "{} {} {}: {}\n",
+                " ".repeat(width),
+                self.paint(BLUE, "="),
+                self.paint(BOLD, kind),
+                note
+            ));
+        }
+    }
+}
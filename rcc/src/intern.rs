@@ -0,0 +1,80 @@
+//! Interned identifiers. A `Symbol` is a `u32` handle of a string in the global interner,
+//! so it is cheap to copy, hash and compare, and it is valid on every thread.
+//!
+//! The strings are leaked and never freed, the interner only grows with the number of
+//! distinct identifiers.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt::{Arguments, Debug, Display, Formatter, Write};
+use std::sync::{OnceLock, RwLock};
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Symbol(u32);
+
+#[derive(Default)]
+struct Interner {
+    symbols: HashMap<&'static str, Symbol>,
+    strings: Vec<&'static str>,
+}
+
+fn interner() -> &'static RwLock<Interner> {
+    static INTERNER: OnceLock<RwLock<Interner>> = OnceLock::new();
+    INTERNER.get_or_init(Default::default)
+}
+
+thread_local! {
+    /// Buffer of `Symbol::intern_fmt`, so a name which is already interned is not allocated
+    static FMT_BUF: RefCell<String> = const { RefCell::new(String::new()) };
+}
+
+impl Symbol {
+    /// The symbol of `s`, `s` is added to the interner if it is not interned yet.
+    pub fn intern(s: &str) -> Symbol {
+        if let Some(symbol) = Symbol::find(s) {
+            return symbol;
+        }
+        let mut interner = interner().write().unwrap();
+        // another thread may have interned `s` between the locks
+        if let Some(&symbol) = interner.symbols.get(s) {
+            return symbol;
+        }
+        let symbol = Symbol(interner.strings.len() as u32);
+        let s: &'static str = Box::leak(s.to_string().into_boxed_str());
+        interner.strings.push(s);
+        interner.symbols.insert(s, symbol);
+        symbol
+    }
+
+    /// The symbol of the formatted string, e.g. `Symbol::intern_fmt(format_args!("{}_{}", a, 1))`
+    pub fn intern_fmt(args: Arguments<'_>) -> Symbol {
+        FMT_BUF.with(|buf| {
+            let mut buf = buf.borrow_mut();
+            buf.clear();
+            buf.write_fmt(args).unwrap();
+            Symbol::intern(&buf)
+        })
+    }
+
+    /// The symbol of `s` without interning it. A name that is not interned is never
+    /// declared, so lookups use it to avoid growing the interner.
+    pub fn find(s: &str) -> Option<Symbol> {
+        interner().read().unwrap().symbols.get(s).copied()
+    }
+
+    pub fn as_str(self) -> &'static str {
+        interner().read().unwrap().strings[self.0 as usize]
+    }
+}
+
+impl Display for Symbol {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl Debug for Symbol {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        Debug::fmt(self.as_str(), f)
+    }
+}
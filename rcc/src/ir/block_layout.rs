@@ -0,0 +1,160 @@
+//! Layout of cold basic blocks.
+//!
+//! A basic block calling a `#[cold]` function is unlikely to be executed, so it is moved
+//! after the other basic blocks. The likely path falls through, and the unlikely one jumps
+//! out of line and back:
+//!
+//! ```text
+//! (1) mut b_3:i32 = a_3:i32              (1) mut b_3:i32 = a_3:i32
+//! (2) if a_3:i32 >= 0i32 goto (5)        (2) if a_3:i32 < 0i32 goto (5)
+//! (3) call @fail()                       (3) $1_1:i32 = mut b_3:i32 + 1i32
+//! (4) mut b_3:i32 = $ret:i32       ->    (4) ret $1_1:i32
+//! (5) $1_1:i32 = mut b_3:i32 + 1i32      (5) call @fail()
+//! (6) ret $1_1:i32                       (6) mut b_3:i32 = $ret:i32
+//!                                        (7) goto (3)
+//! ```
+//!
+//! `ret` is no longer the last instruction, the backend jumps from it to the exit of the
+//! function.
+//!
+//! A conditional jump whose fall through block is moved away is inverted if its target
+//! becomes the next block, otherwise it falls through to a new block of `goto`.
+use crate::ir::cfg::{BasicBlock, BasicBlockId, CFG, CFGIR};
+use crate::ir::{IRInst, Jump, Operand};
+use std::collections::{HashSet, LinkedList};
+
+impl CFGIR {
+    pub fn layout_cold_blocks(&mut self) {
+        let cold_fns: HashSet<String> = self
+            .cfgs
+            .iter()
+            .filter(|cfg| cfg.func_is_cold)
+            .map(|cfg| cfg.func_name.clone())
+            .collect();
+        if cold_fns.is_empty() {
+            return;
+        }
+        for cfg in self.cfgs.iter_mut() {
+            cfg.layout_cold_blocks(&cold_fns);
+        }
+    }
+}
+
+impl CFG {
+    pub fn layout_cold_blocks(&mut self, cold_fns: &HashSet<String>) {
+        let len = self.basic_blocks.len();
+        // the end of the last block is the end of the function
+        if len < 3 || falls_through(&self.basic_blocks[len - 1]) {
+            return;
+        }
+        // the entry block stays in place
+        let is_cold = |bb: &BasicBlock| {
+            bb.id != 0
+                && bb.instructions.iter().any(|inst| match inst {
+                    IRInst::Call {
+                        callee: Operand::FnLabel(f),
+                        ..
+                    } => cold_fns.contains(f),
+                    _ => false,
+                })
+        };
+        let (cold, hot): (Vec<BasicBlockId>, Vec<BasicBlockId>) = self
+            .basic_blocks
+            .iter()
+            .map(|bb| bb.id)
+            .partition(|id| is_cold(&self.basic_blocks[*id]));
+        let order: Vec<BasicBlockId> = hot.into_iter().chain(cold).collect();
+        if order.iter().enumerate().all(|(i, id)| i == *id) {
+            return;
+        }
+
+        // (old id of the block or `None` for a new block of `goto`, block)
+        let mut blocks: Vec<(Option<BasicBlockId>, BasicBlock)> = vec![];
+        let mut basic_blocks: Vec<Option<BasicBlock>> =
+            std::mem::take(&mut self.basic_blocks).into_iter().map(Some).collect();
+        for (i, &id) in order.iter().enumerate() {
+            let mut bb = basic_blocks[id].take().unwrap();
+            let next = order.get(i + 1).copied();
+            let fall_through = id + 1;
+            if !falls_through(&bb) || next == Some(fall_through) {
+                blocks.push((Some(id), bb));
+                continue;
+            }
+            match bb.instructions.back_mut() {
+                Some(inst) if inst.is_jump() && next == Some(inst.jump_label()) => {
+                    invert(inst, fall_through);
+                    blocks.push((Some(id), bb));
+                }
+                Some(inst) if inst.is_jump() => {
+                    blocks.push((Some(id), bb));
+                    let mut instructions = LinkedList::new();
+                    instructions.push_back(IRInst::jump(fall_through));
+                    let goto = BasicBlock {
+                        id: 0,
+                        predecessors: vec![],
+                        instructions,
+                    };
+                    blocks.push((None, goto));
+                }
+                _ => {
+                    bb.instructions.push_back(IRInst::jump(fall_through));
+                    blocks.push((Some(id), bb));
+                }
+            }
+        }
+
+        let mut new_ids = vec![0; len];
+        for (new_id, (old_id, _)) in blocks.iter().enumerate() {
+            if let Some(old_id) = old_id {
+                new_ids[*old_id] = new_id;
+            }
+        }
+        self.basic_blocks = blocks
+            .into_iter()
+            .enumerate()
+            .map(|(new_id, (_, mut bb))| {
+                bb.id = new_id;
+                if let Some(inst) = bb.instructions.back_mut() {
+                    for label in inst.jump_labels_mut() {
+                        *label = new_ids[*label];
+                    }
+                }
+                bb
+            })
+            .collect();
+        self.delete_jumps_to_next_block();
+        self.rebuild_predecessors();
+    }
+}
+
+/// Whether the block may continue with the next block
+fn falls_through(bb: &BasicBlock) -> bool {
+    !matches!(
+        bb.instructions.back(),
+        Some(IRInst::Jump { .. } | IRInst::Switch { .. } | IRInst::Ret(_))
+    )
+}
+
+/// `if cond goto L` -> `if not cond goto label`
+fn invert(inst: &mut IRInst, label: BasicBlockId) {
+    let inverted = match std::mem::replace(inst, IRInst::jump(label)) {
+        IRInst::JumpIf { cond, .. } => IRInst::jump_if_not(cond, label),
+        IRInst::JumpIfNot { cond, .. } => IRInst::jump_if(cond, label),
+        IRInst::JumpIfCond {
+            cond,
+            src1,
+            src2,
+            ..
+        } => {
+            let cond = match cond {
+                Jump::JEq => Jump::JNe,
+                Jump::JNe => Jump::JEq,
+                Jump::JLt => Jump::JGe,
+                Jump::JGe => Jump::JLt,
+            };
+            IRInst::jump_if_cond(cond, src1, src2, label)
+        }
+        inst => unreachable!("`{}` is not a conditional jump", inst),
+    };
+    *inst = inverted;
+}
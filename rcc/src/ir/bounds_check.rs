@@ -0,0 +1,197 @@
+//! Bounds checks of array and slice indexing.
+//!
+//! The IR builder guards each indexed access of an array or a slice with a `BoundsCheck`
+//! when the checks are on, see `bounds_checks`. This pass deletes the checks which are
+//! proven in bounds and lowers the others to a conditional jump over a call of the panic
+//! routine, which splits the basic block:
+//!
+//! ```text
+//! fn f(a: addr, i: usize) scope 2 {
+//!     (1) check i_2:usize < 3usize else panic(lit .LC0:char)
+//!     (2) $1_2:usize = i_2:usize * 4usize
+//!     (3) $2_2:addr = a_2:addr + $1_2:usize
+//!     (4) $0_1:i32 = load i32 *$2_2:addr
+//!     (5) ret $0_1:i32
+//! }
+//! ```
+//!
+//! becomes
+//!
+//! ```text
+//! fn f(a: addr, i: usize) scope 2 {
+//! bb0:
+//!     if i_2:usize < 3usize goto bb2
+//! bb1:
+//!     call @__rcc_panic(lit .LC0:char)
+//! bb2:
+//!     $1_2:usize = i_2:usize * 4usize
+//!     ...
+//! }
+//! ```
+//!
+//! An index is in bounds if its maximum is less than a constant length. The maximum of a
+//! constant is itself, and the maximum of a local variable is the largest of its definitions
+//! when each of them is a constant or a copy of such a variable. A variable whose address is
+//! taken, a field of a struct whose address is taken, or a function argument, may be anything.
+use crate::ir::cfg::{BasicBlock, CFG, CFGIR};
+use crate::ir::var_name::{split_field_var, PANIC};
+use crate::ir::{IRInst, Jump, Operand};
+use crate::rcc::OptimizeLevel;
+use std::collections::{HashMap, HashSet, LinkedList};
+
+/// Whether indexing is checked: at -O0 or with `--checks`
+pub fn bounds_checks(opt_level: OptimizeLevel, checks: bool) -> bool {
+    checks || opt_level == OptimizeLevel::Zero
+}
+
+impl CFGIR {
+    pub fn insert_bounds_checks(&mut self) {
+        for cfg in self.cfgs.iter_mut() {
+            cfg.insert_bounds_checks();
+        }
+    }
+}
+
+impl CFG {
+    pub fn insert_bounds_checks(&mut self) {
+        let ranges = Ranges::new(self);
+        // (basic block id, instruction id) of the checks which are proven
+        let mut elided = vec![];
+        for bb in self.basic_blocks.iter() {
+            for (inst_id, inst) in bb.instructions.iter().enumerate() {
+                if let IRInst::BoundsCheck { index, len, .. } = inst {
+                    if ranges.in_bounds(index, len) {
+                        elided.push((bb.id, inst_id));
+                    }
+                }
+            }
+        }
+        for bb in self.basic_blocks.iter_mut() {
+            let bb_id = bb.id;
+            bb.instructions = std::mem::take(&mut bb.instructions)
+                .into_iter()
+                .enumerate()
+                .filter(|(inst_id, _)| elided.binary_search(&(bb_id, *inst_id)).is_err())
+                .map(|(_, inst)| inst)
+                .collect();
+        }
+
+        let checks = |bb: &BasicBlock| {
+            bb.instructions
+                .iter()
+                .filter(|inst| matches!(inst, IRInst::BoundsCheck { .. }))
+                .count()
+        };
+        if self.basic_blocks.iter().all(|bb| checks(bb) == 0) {
+            return;
+        }
+        // each check adds a block of the panic and a block of the rest
+        let mut new_ids = Vec::with_capacity(self.basic_blocks.len());
+        let mut next_id = 0;
+        for bb in self.basic_blocks.iter() {
+            new_ids.push(next_id);
+            next_id += 1 + 2 * checks(bb);
+        }
+
+        let mut basic_blocks = Vec::with_capacity(next_id);
+        for mut bb in std::mem::take(&mut self.basic_blocks) {
+            if let Some(inst) = bb.instructions.back_mut() {
+                for label in inst.jump_labels_mut() {
+                    *label = new_ids[*label];
+                }
+            }
+            let mut id = new_ids[bb.id];
+            let mut instructions = LinkedList::new();
+            for inst in bb.instructions {
+                match inst {
+                    IRInst::BoundsCheck { index, len, msg } => {
+                        let check = IRInst::jump_if_cond(Jump::JLt, index, len, id + 2);
+                        instructions.push_back(check);
+                        basic_blocks.push(BasicBlock::new(id, instructions));
+                        let panic = IRInst::call(Operand::FnLabel(PANIC.into()), vec![msg]);
+                        basic_blocks.push(BasicBlock::new(id + 1, LinkedList::from([panic])));
+                        instructions = LinkedList::new();
+                        id += 2;
+                    }
+                    inst => instructions.push_back(inst),
+                }
+            }
+            basic_blocks.push(BasicBlock::new(id, instructions));
+        }
+        self.basic_blocks = basic_blocks;
+        self.rebuild_predecessors();
+        self.is_leaf = false;
+    }
+}
+
+/// Definitions of the local variables, for the maximums of the indexes
+struct Ranges<'cfg> {
+    /// variable -> sources of its definitions, `None` if one of them is not a copy
+    defs: HashMap<&'cfg str, Option<Vec<&'cfg Operand>>>,
+}
+
+impl<'cfg> Ranges<'cfg> {
+    fn new(cfg: &'cfg CFG) -> Ranges<'cfg> {
+        let mut defs: HashMap<&str, Option<Vec<&Operand>>> = HashMap::new();
+        for arg in cfg.fn_args_local_var.iter() {
+            defs.insert(arg.as_str(), None);
+        }
+        // struct variables whose fields may be written through a pointer
+        let mut address_taken = HashSet::new();
+        for inst in cfg.iter_inst() {
+            if let IRInst::LoadAddr {
+                symbol: Operand::Place(p),
+                ..
+            } = inst
+            {
+                defs.insert(p.label.as_str(), None);
+                if let Some((var, _)) = split_field_var(p.label.as_str()) {
+                    address_taken.insert(var);
+                }
+            }
+            for dest in inst.dest_places() {
+                let entry = defs.entry(dest.label.as_str()).or_insert_with(|| Some(vec![]));
+                match (entry, inst) {
+                    (Some(srcs), IRInst::LoadData { src, .. }) => srcs.push(src),
+                    (entry, _) => *entry = None,
+                }
+            }
+        }
+        for (label, srcs) in defs.iter_mut() {
+            if split_field_var(label).is_some_and(|(var, _)| address_taken.contains(&var)) {
+                *srcs = None;
+            }
+        }
+        Ranges { defs }
+    }
+
+    fn in_bounds(&self, index: &'cfg Operand, len: &Operand) -> bool {
+        match (self.max(index, &mut HashSet::new()), len) {
+            (Some(max), Operand::Usize(len)) => max < *len,
+            _ => false,
+        }
+    }
+
+    /// The maximum of `operand` if it is known, `visiting` are the variables whose maximums
+    /// are being computed
+    fn max(&self, operand: &'cfg Operand, visiting: &mut HashSet<&'cfg str>) -> Option<usize> {
+        let p = match operand {
+            Operand::Usize(n) => return Some(*n),
+            Operand::Place(p) => p,
+            _ => return None,
+        };
+        if !visiting.insert(p.label.as_str()) {
+            return None;
+        }
+        let srcs = match self.defs.get(p.label.as_str()) {
+            Some(Some(srcs)) if !srcs.is_empty() => srcs,
+            _ => return None,
+        };
+        let mut max = 0;
+        for src in srcs {
+            max = max.max(self.max(src, visiting)?);
+        }
+        visiting.remove(p.label.as_str());
+        Some(max)
+    }
+}
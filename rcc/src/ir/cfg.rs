@@ -1,6 +1,8 @@
+use crate::intern::Symbol;
 use crate::ir::linear_ir::{Func, LinearIR};
-use crate::ir::var_name::local_var;
-use crate::ir::{IRInst, IRType};
+use crate::ir::var_name::{local_var, MEMCPY};
+use crate::ir::{IRInst, IRType, Operand};
+use indexmap::IndexMap;
 use std::collections::{BTreeSet, HashMap, LinkedList};
 use crate::rcc::RccError;
 use crate::ir::dataflow::reaching_definitions::ReachingDefinitionsAnalysis;
@@ -9,8 +11,35 @@ use crate::ir::dataflow::reaching_definitions::ReachingDefinitionsAnalysis;
 pub struct CFGIR {
     pub cfgs: Vec<CFG>,
 
-    /// read only local strings, <label, value>
-    pub ro_local_strs: HashMap<String, String>,
+    /// read only local strings, <label, value>, in the order of labels
+    pub ro_local_strs: IndexMap<String, String>,
+
+    /// symbol of a vtable -> symbols of its methods
+    pub vtables: IndexMap<String, Vec<String>>,
+
+    /// Functions whose assembly is reused from the incremental cache, they have no `CFG`
+    pub precompiled_fns: Vec<PrecompiledFn>,
+}
+
+/// What code generation of the whole module needs to know about a function from the
+/// incremental cache, see `crate::cache`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PrecompiledFn {
+    pub name: String,
+    /// labels of the functions it calls
+    pub callees: Vec<String>,
+    /// whether it returns a value other than `()` and `!`
+    pub returns_value: bool,
+}
+
+impl PrecompiledFn {
+    pub fn from_cfg(cfg: &CFG) -> PrecompiledFn {
+        PrecompiledFn {
+            name: cfg.func_name.clone(),
+            callees: cfg.callees(),
+            returns_value: cfg.returns_value(),
+        }
+    }
 }
 
 impl CFGIR {
@@ -19,6 +48,8 @@ impl CFGIR {
         CFGIR {
             cfgs,
             ro_local_strs: linear_ir.ro_local_strs,
+            vtables: linear_ir.vtables,
+            precompiled_fns: vec![],
         }
     }
 
@@ -36,15 +67,19 @@ pub struct CFG {
     pub basic_blocks: Vec<BasicBlock>,
 
     /// Information of local variables
-    /// <variable name, (variable id, variable's IRType)>
-    pub local_variables: HashMap<String, (usize, IRType)>,
+    /// <variable name, (variable id, variable's IRType)>, in the order of ids
+    pub local_variables: IndexMap<Symbol, (usize, IRType)>,
 
     /// function information
     pub func_name: String,
     pub func_scope_id: u64,
     pub func_is_global: bool,
-    pub fn_args: Vec<(String, IRType)>,
-    pub fn_args_local_var: Vec<String>,
+    /// see `ItemFn::link_section`
+    pub func_link_section: Option<String>,
+    /// see `ItemFn::is_cold`
+    pub func_is_cold: bool,
+    pub fn_args: Vec<(Symbol, IRType)>,
+    pub fn_args_local_var: Vec<Symbol>,
     pub is_leaf: bool,
 }
 
@@ -123,6 +158,16 @@ impl CFG {
                             Some(vec![*label])
                         }
                     }
+                    IRInst::Switch { .. } => {
+                        let mut bs = vec![];
+                        for label in inst.jump_labels_mut() {
+                            *label = *label_map.get(label).unwrap();
+                            if !bs.contains(label) {
+                                bs.push(*label);
+                            }
+                        }
+                        Some(bs)
+                    }
                     _ => {
                         if i < last_bb_id {
                             Some(vec![i + 1])
@@ -143,7 +188,7 @@ impl CFG {
 
         let mut fn_args_local_var = Vec::with_capacity(func.fn_args.len());
         for (arg, _) in &func.fn_args {
-            fn_args_local_var.push(local_var(arg, func.block_scope_id));
+            fn_args_local_var.push(local_var(arg.as_str(), func.block_scope_id));
         }
 
         CFG {
@@ -152,47 +197,71 @@ impl CFG {
             func_name: func.name,
             func_scope_id: func.block_scope_id,
             func_is_global: func.is_global,
+            func_link_section: func.link_section,
+            func_is_cold: func.is_cold,
             fn_args: func.fn_args,
             fn_args_local_var,
             is_leaf,
         }
     }
 
-    /// Get all successors of BasicBlock with id `bb_id`
+    /// Get all successors of BasicBlock with id `bb_id`, including the fall through block
     pub fn successors_of(&self, bb_id: BasicBlockId) -> Vec<usize> {
         debug_assert!(bb_id < self.basic_blocks.len(), "bb_id out of range");
 
-        match self
-            .basic_blocks
-            .get(bb_id)
-            .unwrap()
-            .instructions
-            .back()
-            .unwrap()
-        {
-            IRInst::Jump { label } => vec![*label],
-
-            IRInst::JumpIf { label, .. }
-            | IRInst::JumpIfNot { label, .. }
-            | IRInst::JumpIfCond { label, .. } => {
+        let next = Some(bb_id + 1).filter(|id| *id < self.basic_blocks.len());
+        match self.basic_blocks.get(bb_id).unwrap().instructions.back() {
+            Some(IRInst::Jump { label }) => vec![*label],
+
+            Some(IRInst::JumpIf { label, .. })
+            | Some(IRInst::JumpIfNot { label, .. })
+            | Some(IRInst::JumpIfCond { label, .. }) => {
                 let mut succ = vec![*label];
-                if bb_id < self.basic_blocks.len() - 1 {
-                    succ.push(bb_id + 1);
-                }
+                succ.extend(next.filter(|id| id != label));
+                succ
+            }
+            Some(inst @ IRInst::Switch { .. }) => {
+                let mut succ = inst.jump_labels();
+                succ.sort_unstable();
+                succ.dedup();
                 succ
             }
-            _ => vec![],
+            Some(IRInst::Ret(_)) => vec![],
+            _ => next.into_iter().collect(),
         }
     }
 
-    pub fn get_name_of_fn_arg(&self, i: usize) -> Option<String> {
+    pub fn get_name_of_fn_arg(&self, i: usize) -> Option<Symbol> {
         let (raw_name, _) = self.fn_args.get(i)?;
-        Some(local_var(raw_name, self.func_scope_id))
+        Some(local_var(raw_name.as_str(), self.func_scope_id))
     }
 
     pub fn iter_inst(&self) -> CFGIterMut {
         CFGIterMut::new(self)
     }
+
+    /// Labels of the called functions, in order of first call. `__rcc_memcpy` is called by
+    /// a `Memcpy`, which the backend may expand to a call.
+    pub fn callees(&self) -> Vec<String> {
+        let mut callees: Vec<String> = vec![];
+        for inst in self.iter_inst() {
+            let f = match inst {
+                IRInst::Call { callee: Operand::FnLabel(f), .. } => f.as_str(),
+                IRInst::Memcpy { .. } => MEMCPY,
+                _ => continue,
+            };
+            if !callees.iter().any(|callee| callee == f) {
+                callees.push(f.to_string());
+            }
+        }
+        callees
+    }
+
+    /// Whether the function returns a value other than `()` and `!`
+    pub fn returns_value(&self) -> bool {
+        self.iter_inst()
+            .any(|inst| matches!(inst, IRInst::Ret(o) if !o.is_unit_or_never()))
+    }
 }
 
 fn get_leaders_and_is_leaf(func: &Func) -> (BTreeSet<usize>, bool) {
@@ -215,6 +284,10 @@ fn get_leaders_and_is_leaf(func: &Func) -> (BTreeSet<usize>, bool) {
                     insert_leaders!(leaders, label, i + 2);
                 }
             }
+            IRInst::Switch { .. } => {
+                leaders.extend(inst.jump_labels());
+                leaders.insert(i + 2);
+            }
             IRInst::Call { .. } => {
                 is_leaf = false;
             }
@@ -226,25 +299,28 @@ fn get_leaders_and_is_leaf(func: &Func) -> (BTreeSet<usize>, bool) {
     (leaders, is_leaf)
 }
 
-fn get_local_variables(func: &Func) -> HashMap<String, (usize, IRType)> {
-    let mut local_variables = HashMap::new();
+fn get_local_variables(func: &Func) -> IndexMap<Symbol, (usize, IRType)> {
+    let mut local_variables = IndexMap::new();
     let mut next_id: usize = 0;
     for arg in &func.fn_args {
-        let var_name = local_var(&arg.0, func.block_scope_id);
+        let var_name = local_var(arg.0.as_str(), func.block_scope_id);
         local_variables.insert(var_name, (next_id, arg.1));
     }
 
     for inst in func.insts.iter() {
-        match inst {
-            IRInst::BinOp { dest, .. }
-            | IRInst::LoadData { dest, .. }
-            | IRInst::LoadAddr { dest, .. } => {
-                if !local_variables.contains_key(&dest.label) {
-                    local_variables.insert(dest.label.clone(), (next_id, dest.ir_type));
-                    next_id += 1;
-                }
+        // an array in the stack frame is only used by its address
+        let array = match inst {
+            IRInst::LoadAddr {
+                symbol: Operand::Place(p),
+                ..
+            } if matches!(p.ir_type, IRType::Array(_)) => Some(p),
+            _ => None,
+        };
+        for dest in inst.dest_places().into_iter().chain(array) {
+            if !local_variables.contains_key(&dest.label) {
+                local_variables.insert(dest.label, (next_id, dest.ir_type));
+                next_id += 1;
             }
-            _ => {}
         }
     }
     local_variables
@@ -0,0 +1,94 @@
+//! Graphviz DOT form of the basic blocks of each function, printed by `--emit cfg-dot`.
+//!
+//! ```text
+//! digraph cfg {
+//!     node [shape=box, fontname="monospace"];
+//!     subgraph "cluster_main" {
+//!         label="fn main";
+//!         "main.bb0" [label="bb0:\lif a_1:i32 >= 2i32 goto bb2\l"];
+//!         "main.bb1" [label="bb1:\lmut a_1:i32 = 1i32\l"];
+//!         "main.bb2" [label="bb2:\lret ()\l"];
+//!         "main.bb0" -> "main.bb2" [label="T"];
+//!         "main.bb0" -> "main.bb1" [label="F"];
+//!         "main.bb1" -> "main.bb2";
+//!     }
+//! }
+//! ```
+//!
+//! Jump labels of a CFG are ids of basic blocks, so they are printed as `bbN`. The edge to
+//! the target of a conditional jump is labeled by the condition which takes it.
+use crate::ir::cfg::{BasicBlock, CFG, CFGIR};
+use crate::ir::ir_text::cfg_inst_text;
+use crate::ir::IRInst;
+use crate::rcc::RccError;
+use std::io::Write;
+
+pub fn write_dot(cfg_ir: &CFGIR, out: &mut dyn Write) -> Result<(), RccError> {
+    writeln!(out, "digraph cfg {{")?;
+    writeln!(out, "    node [shape=box, fontname=\"monospace\"];")?;
+    for cfg in cfg_ir.cfgs.iter() {
+        write_cfg(cfg, out)?;
+    }
+    writeln!(out, "}}")?;
+    Ok(())
+}
+
+fn write_cfg(cfg: &CFG, out: &mut dyn Write) -> Result<(), RccError> {
+    let name = escape(&cfg.func_name);
+    let node = |id: usize| format!("\"{}.bb{}\"", name, id);
+    writeln!(out, "    subgraph \"cluster_{}\" {{", name)?;
+    writeln!(out, "        label=\"fn {}\";", name)?;
+    for bb in cfg.basic_blocks.iter() {
+        let mut label = format!("bb{}:\\l", bb.id);
+        for inst in bb.instructions.iter() {
+            label.push_str(&escape(&cfg_inst_text(inst)));
+            label.push_str("\\l");
+        }
+        writeln!(out, "        {} [label=\"{}\"];", node(bb.id), label)?;
+    }
+    for bb in cfg.basic_blocks.iter() {
+        for (succ, label) in successors(cfg, bb) {
+            match label {
+                Some(label) => {
+                    writeln!(out, "        {} -> {} [label=\"{}\"];", node(bb.id), node(succ), label)?
+                }
+                None => writeln!(out, "        {} -> {};", node(bb.id), node(succ))?,
+            }
+        }
+    }
+    writeln!(out, "    }}")?;
+    Ok(())
+}
+
+/// Successors of `bb` with the labels of their edges. A block which does not end with a jump
+/// or `ret` falls through to the next one. The edges of a switch are labeled by the case
+/// values and `_` for the default.
+fn successors(cfg: &CFG, bb: &BasicBlock) -> Vec<(usize, Option<String>)> {
+    let next = Some(bb.id + 1).filter(|id| *id < cfg.basic_blocks.len());
+    let branch = |label: usize, taken: &str, not_taken: &str| {
+        let mut succ = vec![(label, Some(taken.to_string()))];
+        succ.extend(next.map(|id| (id, Some(not_taken.to_string()))));
+        succ
+    };
+    match bb.instructions.back() {
+        Some(IRInst::Jump { label }) => vec![(*label, None)],
+        Some(IRInst::JumpIf { label, .. }) | Some(IRInst::JumpIfCond { label, .. }) => {
+            branch(*label, "T", "F")
+        }
+        Some(IRInst::JumpIfNot { label, .. }) => branch(*label, "F", "T"),
+        Some(IRInst::Switch { cases, default, .. }) => {
+            let mut succ: Vec<_> = cases
+                .iter()
+                .map(|(value, label)| (*label, Some(value.to_string())))
+                .collect();
+            succ.push((*default, Some("_".to_string())));
+            succ
+        }
+        Some(IRInst::Ret(_)) => vec![],
+        _ => next.map(|id| (id, None)).into_iter().collect(),
+    }
+}
+
+fn escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
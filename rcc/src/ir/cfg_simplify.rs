@@ -0,0 +1,252 @@
+//! CFG cleanup pass.
+//!
+//! Runs after constant propagation, see `CFG::propagate_constants`:
+//! 1. instructions after `ret` in the same basic block are deleted;
+//! 2. conditional jumps and switches whose condition is known at compile time
+//!    become `goto L` or are deleted;
+//! 3. jumps to a basic block that only contains `goto L` are redirected to `L`;
+//! 4. empty basic blocks and basic blocks unreachable from the entry block
+//!    are removed;
+//! 5. jumps to the next basic block are deleted.
+//!
+//! Step 3 to 5 are repeated until nothing changes.
+use crate::ir::cfg::{BasicBlockId, CFG, CFGIR};
+use crate::ir::{jump_cond_may_constant_fold, IRInst, Operand};
+use std::collections::HashSet;
+
+impl CFGIR {
+    pub fn simplify_cfg(&mut self) {
+        for cfg in self.cfgs.iter_mut() {
+            cfg.simplify();
+        }
+    }
+}
+
+impl CFG {
+    pub fn simplify(&mut self) {
+        if self.basic_blocks.is_empty() {
+            return;
+        }
+        self.delete_insts_after_ret();
+        self.fold_constant_jumps();
+        loop {
+            self.thread_jumps();
+            let removed = self.remove_useless_blocks();
+            let deleted = self.delete_jumps_to_next_block();
+            if !removed && !deleted {
+                break;
+            }
+        }
+        self.rebuild_predecessors();
+        self.is_leaf = !self
+            .iter_inst()
+            .any(|inst| matches!(inst, IRInst::Call { .. }));
+    }
+
+    /// Successors of basic block `bb_id`, including the fall through block.
+    fn out_edges(&self, bb_id: BasicBlockId) -> Vec<BasicBlockId> {
+        let mut edges = vec![];
+        let falls_through = match self.basic_blocks[bb_id].instructions.back() {
+            Some(IRInst::Jump { label }) => {
+                edges.push(*label);
+                false
+            }
+            Some(IRInst::JumpIf { label, .. })
+            | Some(IRInst::JumpIfNot { label, .. })
+            | Some(IRInst::JumpIfCond { label, .. }) => {
+                edges.push(*label);
+                true
+            }
+            Some(inst @ IRInst::Switch { .. }) => {
+                for label in inst.jump_labels() {
+                    if !edges.contains(&label) {
+                        edges.push(label);
+                    }
+                }
+                false
+            }
+            Some(IRInst::Ret(_)) => false,
+            _ => true,
+        };
+        if falls_through && bb_id + 1 < self.basic_blocks.len() && !edges.contains(&(bb_id + 1)) {
+            edges.push(bb_id + 1);
+        }
+        edges
+    }
+
+    fn delete_insts_after_ret(&mut self) {
+        for bb in self.basic_blocks.iter_mut() {
+            if let Some(pos) = bb
+                .instructions
+                .iter()
+                .position(|inst| matches!(inst, IRInst::Ret(_)))
+            {
+                bb.instructions.split_off(pos + 1);
+            }
+        }
+    }
+
+    /// `if true goto L` -> `goto L`
+    /// `if false goto L` -> (deleted)
+    /// `switch 2i32 [2: goto L, ...] else goto M` -> `goto L`
+    fn fold_constant_jumps(&mut self) {
+        for bb in self.basic_blocks.iter_mut() {
+            let (taken, label) = match bb.instructions.back() {
+                Some(IRInst::JumpIf {
+                    cond: Operand::Bool(b),
+                    label,
+                }) => (*b, *label),
+                Some(IRInst::JumpIfNot {
+                    cond: Operand::Bool(b),
+                    label,
+                }) => (!*b, *label),
+                Some(IRInst::JumpIfCond {
+                    cond,
+                    src1,
+                    src2,
+                    label,
+                }) => match jump_cond_may_constant_fold(cond, src1, src2) {
+                    Some(b) => (b, *label),
+                    None => continue,
+                },
+                Some(IRInst::Switch {
+                    src,
+                    cases,
+                    default,
+                }) => match src.int_value() {
+                    Some(value) => match cases.iter().find(|(v, _)| *v == value) {
+                        Some((_, label)) => (true, *label),
+                        None => (true, *default),
+                    },
+                    None => continue,
+                },
+                _ => continue,
+            };
+            bb.instructions.pop_back();
+            if taken {
+                bb.instructions.push_back(IRInst::jump(label));
+            }
+        }
+    }
+
+    /// Follow the chain of basic blocks which only contain `goto L`
+    /// (or nothing at all) starting from `target`.
+    fn thread_target(&self, mut target: BasicBlockId) -> BasicBlockId {
+        let mut visited = HashSet::new();
+        while visited.insert(target) {
+            let instructions = &self.basic_blocks[target].instructions;
+            match instructions.front() {
+                None if target + 1 < self.basic_blocks.len() => target += 1,
+                Some(IRInst::Jump { label }) if instructions.len() == 1 => target = *label,
+                _ => break,
+            }
+        }
+        target
+    }
+
+    fn thread_jumps(&mut self) {
+        for bb_id in 0..self.basic_blocks.len() {
+            match self.basic_blocks[bb_id].instructions.back() {
+                Some(inst) if !inst.jump_labels().is_empty() => {
+                    let targets: Vec<BasicBlockId> = inst
+                        .jump_labels()
+                        .into_iter()
+                        .map(|label| self.thread_target(label))
+                        .collect();
+                    let bb = self.basic_blocks.get_mut(bb_id).unwrap();
+                    let labels = bb.instructions.back_mut().unwrap().jump_labels_mut();
+                    for (label, target) in labels.into_iter().zip(targets) {
+                        *label = target;
+                    }
+                }
+                Some(IRInst::Ret(_)) | None => {}
+                // fall through to a block which only contains `goto L`
+                Some(_) => {
+                    if bb_id + 1 < self.basic_blocks.len() {
+                        let target = self.thread_target(bb_id + 1);
+                        if target != bb_id + 1 {
+                            let bb = self.basic_blocks.get_mut(bb_id).unwrap();
+                            bb.instructions.push_back(IRInst::jump(target));
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Remove unreachable basic blocks and empty basic blocks (except the last one).
+    /// Return true if any basic block is removed.
+    fn remove_useless_blocks(&mut self) -> bool {
+        let mut reachable = vec![false; self.basic_blocks.len()];
+        let mut stack = vec![0];
+        while let Some(bb_id) = stack.pop() {
+            if !reachable[bb_id] {
+                reachable[bb_id] = true;
+                stack.extend(self.out_edges(bb_id));
+            }
+        }
+
+        // An empty basic block falls through to the next one, so jumps to it
+        // have been redirected by `thread_jumps`.
+        let last_bb_id = self.basic_blocks.len() - 1;
+        let keep: Vec<bool> = self
+            .basic_blocks
+            .iter()
+            .map(|bb| reachable[bb.id] && (bb.id == last_bb_id || !bb.instructions.is_empty()))
+            .collect();
+        if keep.iter().all(|k| *k) {
+            return false;
+        }
+
+        // a removed basic block is mapped to the next kept one
+        let mut new_ids = vec![0; self.basic_blocks.len()];
+        let mut next_id = 0;
+        for (old_id, k) in keep.iter().enumerate() {
+            new_ids[old_id] = next_id;
+            if *k {
+                next_id += 1;
+            }
+        }
+
+        let basic_blocks = std::mem::take(&mut self.basic_blocks);
+        self.basic_blocks = basic_blocks
+            .into_iter()
+            .filter(|bb| keep[bb.id])
+            .map(|mut bb| {
+                bb.id = new_ids[bb.id];
+                if let Some(inst) = bb.instructions.back_mut() {
+                    for label in inst.jump_labels_mut() {
+                        *label = new_ids[*label];
+                    }
+                }
+                bb
+            })
+            .collect();
+        true
+    }
+
+    /// Return true if any jump is deleted.
+    pub(super) fn delete_jumps_to_next_block(&mut self) -> bool {
+        let mut deleted = false;
+        for bb in self.basic_blocks.iter_mut() {
+            if let Some(inst) = bb.instructions.back() {
+                if inst.is_jump() && inst.jump_label() == bb.id + 1 {
+                    bb.instructions.pop_back();
+                    deleted = true;
+                }
+            }
+        }
+        deleted
+    }
+
+    pub(super) fn rebuild_predecessors(&mut self) {
+        for bb in self.basic_blocks.iter_mut() {
+            bb.predecessors.clear();
+        }
+        for bb_id in 0..self.basic_blocks.len() {
+            for succ in self.out_edges(bb_id) {
+                self.basic_blocks[succ].predecessors.push(bb_id);
+            }
+        }
+    }
+}
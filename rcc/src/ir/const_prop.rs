@@ -0,0 +1,102 @@
+//! Constant propagation.
+//!
+//! A local variable read where every definition reaching it assigns the same constant is
+//! replaced by the constant, and a binary operation on constants is folded. It runs before
+//! simplify-cfg, which then folds the jumps whose conditions become constants:
+//!
+//! ```text
+//! mut a_2:i32 = 3i32
+//! $0_2:bool = mut a_2:i32 > 1i32      ->  $0_2:bool = true
+//! if $0_2:bool goto bb2               ->  if true goto bb2
+//! ```
+//!
+//! Folding an operation may make its destination a constant, so it is repeated until nothing
+//! changes. Only the operands which the backend takes as immediates are replaced, the
+//! definitions left unused are deleted by dead-store-elimination.
+use crate::intern::Symbol;
+use crate::ir::cfg::{CFG, CFGIR};
+use crate::ir::dataflow::reaching_definitions::ReachingDefinitionsAnalysis;
+use crate::ir::{bin_op_may_constant_fold, IRInst, Operand, Overflow};
+use crate::rcc::RccError;
+
+impl CFGIR {
+    pub fn propagate_constants(&mut self) -> Result<(), RccError> {
+        for cfg in self.cfgs.iter_mut() {
+            cfg.propagate_constants()?;
+        }
+        Ok(())
+    }
+}
+
+impl CFG {
+    pub fn propagate_constants(&mut self) -> Result<(), RccError> {
+        loop {
+            let mut analysis = ReachingDefinitionsAnalysis::new(self);
+            analysis.apply()?;
+            let mut uses = analysis.constant_uses().into_iter().peekable();
+            let mut changed = false;
+            for (bb_id, bb) in self.basic_blocks.iter_mut().enumerate() {
+                for (inst_id, inst) in bb.instructions.iter_mut().enumerate() {
+                    if let Some((_, constants)) = uses.next_if(|(id, _)| *id == (bb_id, inst_id)) {
+                        changed |= replace_operands(inst, &constants);
+                    }
+                }
+            }
+            if !changed {
+                return Ok(());
+            }
+        }
+    }
+}
+
+/// Replace the operands of `inst` which are `constants`, true if `inst` is changed
+fn replace_operands(inst: &mut IRInst, constants: &[(Symbol, Operand)]) -> bool {
+    let constant = |operand: &Operand| -> Option<Operand> {
+        match operand {
+            Operand::Place(place) => constants
+                .iter()
+                .find(|(label, _)| *label == place.label)
+                .map(|(_, value)| value.clone()),
+            _ => None,
+        }
+    };
+    let replace = |operand: &mut Operand| -> bool {
+        match constant(operand) {
+            Some(value) => {
+                *operand = value;
+                true
+            }
+            None => false,
+        }
+    };
+    match inst {
+        IRInst::LoadData { src, .. }
+        | IRInst::Store { src, .. }
+        | IRInst::JumpIf { cond: src, .. }
+        | IRInst::JumpIfNot { cond: src, .. }
+        | IRInst::Ret(src) => replace(src),
+        IRInst::JumpIfCond { src1, src2, .. } => replace(src1) | replace(src2),
+        IRInst::Call { args, .. } => {
+            args.iter_mut().fold(false, |changed, arg| replace(arg) | changed)
+        }
+        IRInst::BinOp {
+            op,
+            dest,
+            src1,
+            src2,
+        } => {
+            let lhs = constant(src1).unwrap_or_else(|| src1.clone());
+            let rhs = constant(src2).unwrap_or_else(|| src2.clone());
+            if lhs.is_imm() && rhs.is_imm() {
+                // a division by zero is left to the runtime
+                if let Ok(Some(value)) = bin_op_may_constant_fold(op, &lhs, &rhs, Overflow::Wrap) {
+                    *inst = IRInst::load_data(dest.clone(), value);
+                    return true;
+                }
+            }
+            // the backend only takes an immediate as the second operand
+            !src1.is_imm() && replace(src2)
+        }
+        _ => false,
+    }
+}
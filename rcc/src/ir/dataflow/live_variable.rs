@@ -99,13 +99,16 @@ impl<'cfg> LiveVariableAnalysis<'cfg> {
         let in_state = self.in_states.get_mut(bb_id).unwrap();
         in_state.clone_from(out_state);
         match inst {
-            IRInst::LoadAddr { .. } => {
-                todo!()
-            }
-            IRInst::LoadData { dest, src } => {
+            IRInst::LoadData { dest, src }
+            | IRInst::LoadAddr { dest, symbol: src }
+            | IRInst::Load { dest, addr: src, .. } => {
                 gen!(self, dest, in_state);
                 kill!(self, src, in_state);
             }
+            IRInst::Store { addr, src, .. } => {
+                kill!(self, addr, in_state);
+                kill!(self, src, in_state);
+            }
             IRInst::BinOp {
                 dest, src1, src2, ..
             } => {
@@ -113,18 +116,29 @@ impl<'cfg> LiveVariableAnalysis<'cfg> {
                 kill!(self, src1, in_state);
                 kill!(self, src2, in_state);
             }
-            IRInst::JumpIf { cond, .. } | IRInst::JumpIfNot { cond, .. } => {
+            IRInst::JumpIf { cond, .. }
+            | IRInst::JumpIfNot { cond, .. }
+            | IRInst::Switch { src: cond, .. } => {
                 kill!(self, cond, in_state);
             }
             IRInst::JumpIfCond { src1, src2, .. } => {
                 kill!(self, src1, in_state);
                 kill!(self, src2, in_state);
             }
-            IRInst::Call {args, ..} => {
+            IRInst::Call { callee, args } => {
+                kill!(self, callee, in_state);
                 for arg in args {
                     kill!(self, arg, in_state);
                 }
             }
+            IRInst::Memcpy { srcs, dests, .. } => {
+                for dest in dests {
+                    gen!(self, dest, in_state);
+                }
+                for src in srcs {
+                    kill!(self, src, in_state);
+                }
+            }
             _ => {}
         }
     }
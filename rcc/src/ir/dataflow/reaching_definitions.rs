@@ -1,13 +1,18 @@
 use crate::analyser::sym_resolver::VarKind;
+use crate::intern::Symbol;
 use crate::ir::cfg::{BasicBlock, BasicBlockId, CFG};
-use crate::ir::{IRInst, Operand, Place};
+use crate::ir::var_name::split_field_var;
+use crate::ir::{IRInst, IRType, Operand, Place};
 use crate::rcc::RccError;
 use bit_vector::BitVector;
-use std::collections::{HashMap, VecDeque};
+use std::collections::{HashMap, HashSet, VecDeque};
+
+/// Places of an instruction and their constants, see `constant_uses`
+pub type Constants = Vec<(Symbol, Operand)>;
 
 pub struct ReachingDefinitionsAnalysis<'cfg> {
     cfg: &'cfg CFG,
-    definitions: HashMap<&'cfg String, Vec<(usize, BasicBlockId, isize)>>,
+    definitions: HashMap<Symbol, Vec<(usize, BasicBlockId, isize)>>,
     num_definitions: usize,
 
     ins: Vec<BitVector>,
@@ -17,9 +22,9 @@ pub struct ReachingDefinitionsAnalysis<'cfg> {
 
 impl<'cfg> ReachingDefinitionsAnalysis<'cfg> {
     pub fn new(cfg: &'cfg CFG) -> ReachingDefinitionsAnalysis {
-        fn add_definitions<'a>(
-            dest: &'a Place,
-            definitions: &mut HashMap<&'a String, Vec<(usize, BasicBlockId, isize)>>,
+        fn add_definitions(
+            dest: &Place,
+            definitions: &mut HashMap<Symbol, Vec<(usize, BasicBlockId, isize)>>,
             next_definition_id: &mut usize,
             bb_id: BasicBlockId,
             inst_id: isize,
@@ -30,7 +35,7 @@ impl<'cfg> ReachingDefinitionsAnalysis<'cfg> {
                         Some(ids) => ids.push((*next_definition_id, bb_id, inst_id)),
                         None => {
                             definitions
-                                .insert(&dest.label, vec![(*next_definition_id, bb_id, inst_id)]);
+                                .insert(dest.label, vec![(*next_definition_id, bb_id, inst_id)]);
                         }
                     }
 
@@ -41,35 +46,27 @@ impl<'cfg> ReachingDefinitionsAnalysis<'cfg> {
         }
 
         // (definition_id, bb_id, inst_id)
-        let mut definitions: HashMap<&String, Vec<(usize, usize, isize)>> = HashMap::new();
+        let mut definitions: HashMap<Symbol, Vec<(usize, usize, isize)>> = HashMap::new();
         let mut next_definition_id = cfg.fn_args.len();
 
         // function arguments' definitions
         for definition_id in 0..next_definition_id {
             definitions.insert(
-                &cfg.fn_args_local_var[definition_id],
+                cfg.fn_args_local_var[definition_id],
                 vec![(definition_id, 0, -1)],
             );
         }
 
         for (bb_id, bb) in cfg.basic_blocks.iter().enumerate() {
             for (inst_id, inst) in bb.instructions.iter().enumerate() {
-                match inst {
-                    IRInst::BinOp { op: _op, dest, .. } => add_definitions(
+                for dest in inst.dest_places() {
+                    add_definitions(
                         dest,
                         &mut definitions,
                         &mut next_definition_id,
                         bb_id,
                         inst_id as isize,
-                    ),
-                    IRInst::LoadData { dest, .. } => add_definitions(
-                        dest,
-                        &mut definitions,
-                        &mut next_definition_id,
-                        bb_id,
-                        inst_id as isize,
-                    ),
-                    _ => {}
+                    );
                 }
             }
         }
@@ -115,7 +112,7 @@ impl<'cfg> ReachingDefinitionsAnalysis<'cfg> {
                     self.ins[bb_id] = self.join(&bb.predecessors);
                 }
 
-                self.out_changed = self.block_apply(bb_id, bb)?;
+                self.out_changed |= self.block_apply(bb_id, bb)?;
 
                 for s_bb_id in self.cfg.successors_of(bb_id) {
                     if !visited.get(s_bb_id).unwrap() {
@@ -159,11 +156,35 @@ impl<'cfg> ReachingDefinitionsAnalysis<'cfg> {
                 } => {
                     self.valid(bb_id, src1)?;
                     self.valid(bb_id, src2)?;
-                    self.gen_kill(dest, bb_id, inst_id as isize);
+                    let out = &mut self.outs[bb_id];
+                    gen_kill(&self.definitions, out, dest, bb_id, inst_id as isize);
                 }
-                IRInst::LoadData { dest, src } => {
+                IRInst::LoadData { dest, src }
+                | IRInst::LoadAddr { dest, symbol: src }
+                | IRInst::Load { dest, addr: src, .. } => {
                     self.valid(bb_id, src)?;
-                    self.gen_kill(dest, bb_id, inst_id as isize);
+                    let out = &mut self.outs[bb_id];
+                    gen_kill(&self.definitions, out, dest, bb_id, inst_id as isize);
+                }
+                IRInst::Store { addr, src, .. } => {
+                    self.valid(bb_id, addr)?;
+                    self.valid(bb_id, src)?;
+                }
+                IRInst::Switch { src, .. } => self.valid(bb_id, src)?,
+                IRInst::Memcpy { srcs, dests, .. } => {
+                    for src in srcs.iter() {
+                        self.valid(bb_id, src)?;
+                    }
+                    for dest in dests.iter() {
+                        let out = &mut self.outs[bb_id];
+                        gen_kill(&self.definitions, out, dest, bb_id, inst_id as isize);
+                    }
+                }
+                IRInst::Asm { outputs, .. } => {
+                    for output in outputs.iter() {
+                        let out = &mut self.outs[bb_id];
+                        gen_kill(&self.definitions, out, output, bb_id, inst_id as isize);
+                    }
                 }
                 _ => {}
             }
@@ -174,6 +195,13 @@ impl<'cfg> ReachingDefinitionsAnalysis<'cfg> {
     fn valid(&mut self, in_bb_id: BasicBlockId, operand: &Operand) -> Result<(), RccError> {
         let in_ = &mut self.outs[in_bb_id];
         if let Operand::Place(place) = operand {
+            // addresses of literal strings, statics and consts are always valid, and so are
+            // those of arrays, whose elements are written through them
+            if !matches!(place.kind, VarKind::Local | VarKind::LocalMut)
+                || matches!(place.ir_type, IRType::Array(_))
+            {
+                return Ok(());
+            }
             let mut has_definitions = false;
 
             // None: may use global definitions
@@ -195,13 +223,154 @@ impl<'cfg> ReachingDefinitionsAnalysis<'cfg> {
         Ok(())
     }
 
-    fn gen_kill(&mut self, dest: &Place, bb_id: BasicBlockId, inst_id: isize) {
-        let out = &mut self.outs[bb_id];
-        if let Some(definitions) = self.definitions.get(&dest.label) {
-            for &(definition_id, bb_id2, inst_id2) in definitions {
-                out.set(definition_id, bb_id == bb_id2 && inst_id == inst_id2);
+    /// Definitions which reach no use, as `(bb_id, inst_id)`, valid after `apply`. A variable
+    /// whose address is taken may be read through the pointer, so all its definitions are used,
+    /// and so are those of the other fields of a struct.
+    /// An instruction which defines several places, e.g. a `Memcpy`, is only unused if none
+    /// of them is used.
+    pub fn unused_definitions(&self) -> Vec<(BasicBlockId, usize)> {
+        let mut used = BitVector::new(self.num_definitions);
+        let address_taken = address_taken(self.cfg);
+        let mut used_insts = HashSet::new();
+        for (bb_id, bb) in self.cfg.basic_blocks.iter().enumerate() {
+            let mut reaching = self.ins[bb_id].clone();
+            for (inst_id, inst) in bb.instructions.iter().enumerate() {
+                // a volatile load is kept even if its value is unused
+                if inst.is_volatile() {
+                    used_insts.insert((bb_id, inst_id));
+                }
+                for operand in inst.src_operands() {
+                    if let Operand::Place(place) = operand {
+                        let definitions = self.definitions.get(&place.label).into_iter().flatten();
+                        for &(definition_id, _, _) in definitions {
+                            if reaching.get(definition_id).unwrap() {
+                                used.set(definition_id, true);
+                            }
+                        }
+                    }
+                }
+                for dest in inst.dest_places() {
+                    gen_kill(&self.definitions, &mut reaching, dest, bb_id, inst_id as isize);
+                }
+            }
+        }
+
+        let mut unused = vec![];
+        for (label, definitions) in self.definitions.iter() {
+            for &(definition_id, bb_id, inst_id) in definitions {
+                // definitions of the arguments have no instructions
+                if inst_id < 0 {
+                    continue;
+                }
+                let var = split_field_var(label.as_str()).map(|(var, _)| var);
+                if address_taken.contains(&var.unwrap_or(*label))
+                    || used.get(definition_id).unwrap()
+                {
+                    used_insts.insert((bb_id, inst_id as usize));
+                } else {
+                    unused.push((bb_id, inst_id as usize));
+                }
             }
         }
+        unused.retain(|inst| !used_insts.contains(inst));
+        unused.sort_unstable();
+        unused.dedup();
+        unused
+    }
+
+    /// Constants of the places read by each instruction, as `((bb_id, inst_id), [(label,
+    /// value)])`, valid after `apply`. A place is a constant if every definition of it which
+    /// reaches the instruction assigns the same constant of its type. A variable whose
+    /// address is taken may be written through the pointer, so it is never a constant.
+    pub fn constant_uses(&self) -> Vec<((BasicBlockId, usize), Constants)> {
+        let address_taken = address_taken(self.cfg);
+        let mut constants = vec![None; self.num_definitions];
+        for definitions in self.definitions.values() {
+            for &(definition_id, bb_id, inst_id) in definitions {
+                // definitions of the arguments have no instructions
+                if inst_id < 0 {
+                    continue;
+                }
+                let inst = self.cfg.basic_blocks[bb_id].instructions.iter().nth(inst_id as usize);
+                if let Some(IRInst::LoadData { dest, src }) = inst {
+                    if src.imm_type() == Some(dest.ir_type) {
+                        constants[definition_id] = Some(src);
+                    }
+                }
+            }
+        }
+
+        let mut uses = vec![];
+        for (bb_id, bb) in self.cfg.basic_blocks.iter().enumerate() {
+            let mut reaching = self.ins[bb_id].clone();
+            for (inst_id, inst) in bb.instructions.iter().enumerate() {
+                let mut inst_uses: Constants = vec![];
+                for operand in inst.src_operands() {
+                    let place = match operand {
+                        Operand::Place(place) => place,
+                        _ => continue,
+                    };
+                    let var = split_field_var(place.label.as_str()).map(|(var, _)| var);
+                    if address_taken.contains(&var.unwrap_or(place.label))
+                        || inst_uses.iter().any(|(label, _)| *label == place.label)
+                    {
+                        continue;
+                    }
+                    let mut values = self
+                        .definitions
+                        .get(&place.label)
+                        .into_iter()
+                        .flatten()
+                        .filter(|(definition_id, _, _)| reaching.get(*definition_id).unwrap())
+                        .map(|(definition_id, _, _)| constants[*definition_id]);
+                    if let Some(Some(value)) = values.next() {
+                        if values.all(|v| v == Some(value)) {
+                            inst_uses.push((place.label, value.clone()));
+                        }
+                    }
+                }
+                if !inst_uses.is_empty() {
+                    uses.push(((bb_id, inst_id), inst_uses));
+                }
+                for dest in inst.dest_places() {
+                    gen_kill(&self.definitions, &mut reaching, dest, bb_id, inst_id as isize);
+                }
+            }
+        }
+        uses
+    }
+}
+
+/// Variables whose address is taken, the struct variable for a field, since the other
+/// fields of a struct are reached through the same pointer
+fn address_taken(cfg: &CFG) -> HashSet<Symbol> {
+    let mut address_taken = HashSet::new();
+    for inst in cfg.iter_inst() {
+        if let IRInst::LoadAddr {
+            symbol: Operand::Place(place),
+            ..
+        } = inst
+        {
+            let var = split_field_var(place.label.as_str()).map(|(var, _)| var);
+            address_taken.insert(var.unwrap_or(place.label));
+        }
+    }
+    address_taken
+}
+
+/// The definition of `dest` at `(bb_id, inst_id)` reaches `state` and kills the other
+/// definitions of `dest`.
+fn gen_kill(
+    definitions: &HashMap<Symbol, Vec<(usize, BasicBlockId, isize)>>,
+    state: &mut BitVector,
+    dest: &Place,
+    bb_id: BasicBlockId,
+    inst_id: isize,
+) {
+    if let Some(definitions) = definitions.get(&dest.label) {
+        for &(definition_id, bb_id2, inst_id2) in definitions {
+            state.set(definition_id, bb_id == bb_id2 && inst_id == inst_id2);
+        }
     }
 }
 
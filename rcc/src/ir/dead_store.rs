@@ -0,0 +1,48 @@
+//! Dead store elimination.
+//!
+//! A definition of a local variable which reaches no use, e.g. a value overwritten before it
+//! is read, is deleted together with its stack store. Deleting a definition may leave the
+//! definitions of its operands unused, so it is repeated until nothing changes:
+//!
+//! ```text
+//! mut a_2:i32 = 0i32          (deleted)
+//! mut a_2:i32 = 5i32
+//! $0_1:i32 = mut a_2:i32 + 1i32
+//! ret $0_1:i32
+//! ```
+//!
+//! Calls, stores through pointers, volatile loads and jumps are never deleted.
+use crate::ir::cfg::{CFG, CFGIR};
+use crate::ir::dataflow::reaching_definitions::ReachingDefinitionsAnalysis;
+use crate::rcc::RccError;
+
+impl CFGIR {
+    pub fn eliminate_dead_stores(&mut self) -> Result<(), RccError> {
+        for cfg in self.cfgs.iter_mut() {
+            cfg.eliminate_dead_stores()?;
+        }
+        Ok(())
+    }
+}
+
+impl CFG {
+    pub fn eliminate_dead_stores(&mut self) -> Result<(), RccError> {
+        loop {
+            let mut analysis = ReachingDefinitionsAnalysis::new(self);
+            analysis.apply()?;
+            let unused = analysis.unused_definitions();
+            if unused.is_empty() {
+                return Ok(());
+            }
+            for bb in self.basic_blocks.iter_mut() {
+                let bb_id = bb.id;
+                bb.instructions = std::mem::take(&mut bb.instructions)
+                    .into_iter()
+                    .enumerate()
+                    .filter(|(inst_id, _)| unused.binary_search(&(bb_id, *inst_id)).is_err())
+                    .map(|(_, inst)| inst)
+                    .collect();
+            }
+        }
+    }
+}
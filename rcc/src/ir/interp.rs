@@ -0,0 +1,639 @@
+//! Interpreter of `LinearIR`.
+//!
+//! Tests can assert the observable behavior of a program (the value returned by `main`
+//! and the characters written by `putchar` or `print!`) instead of exact instruction sequences.
+//!
+//! A value is stored at each address of a heap allocation which is written, e.g. the elements
+//! of an array, so that use after free and double free are reported as errors. Each element
+//! of a `Vec` is a heap allocation of its own.
+//!
+//! The entries of the vtables are allocated on the heap before running. A local variable is
+//! moved to the heap when its address is taken, so that writing through the pointer changes
+//! the variable, and the field variables of a struct are moved together. These allocations
+//! are never freed.
+//!
+//! It also evaluates constants at compile time. The value of a constant `N_1` is returned by
+//! the function `N_1$init`, which is called the first time `N_1` is used. In constants, the
+//! runtime functions (heap allocation, printing, ...) can not be called.
+use crate::analyser::sym_resolver::VarKind;
+use crate::intern::Symbol;
+use crate::ir::linear_ir::{Func, LinearIR};
+use crate::ir::var_name::{
+    const_init_fn, local_var, split_field_var, ALLOC, FREE, MEMCPY, PANIC, PRINT_CHAR,
+    PRINT_INT, PRINT_STR, PRINT_STRING, PRINT_UINT, STRING_PUSH_STR, VEC_FREE, VEC_INDEX,
+    VEC_LEN, VEC_NEW, VEC_PUSH,
+};
+use crate::ir::{
+    bin_op_may_constant_fold, jump_cond_may_constant_fold, IRInst, IRType, Jump, Operand,
+    Overflow, Place,
+};
+use crate::rcc::RccError;
+use indexmap::IndexMap;
+use std::collections::{BTreeMap, HashMap};
+
+const DEFAULT_MAX_STEPS: usize = 1_000_000;
+const MAX_CALL_DEPTH: usize = 256;
+/// Address of the first heap allocation
+const HEAP_BASE: usize = 0x10000;
+
+pub struct Interpreter<'ir> {
+    funcs: HashMap<&'ir str, &'ir Func>,
+    ro_local_strs: &'ir IndexMap<String, String>,
+    steps: usize,
+    max_steps: usize,
+    call_depth: usize,
+    /// address -> value, `None` if it is not initialized
+    heap: HashMap<usize, Option<Operand>>,
+    /// address -> size of the allocations which are not freed
+    allocs: BTreeMap<usize, usize>,
+    heap_top: usize,
+    /// address of a vector -> addresses of its elements
+    vecs: HashMap<usize, Vec<usize>>,
+    /// symbol of a vtable -> address of its first entry
+    vtables: HashMap<&'ir str, usize>,
+    /// symbol of a read-only string -> address of its first byte
+    ro_strs: HashMap<&'ir str, usize>,
+    /// number of the allocations which are never freed
+    pinned: usize,
+    /// symbol -> value of the evaluated constants
+    consts: HashMap<String, Operand>,
+    /// constants being evaluated, for detecting cycles
+    evaluating: Vec<String>,
+    const_eval: bool,
+
+    /// Characters written by `putchar` and `print!`
+    pub output: Vec<u8>,
+}
+
+/// Local variables and the return value of the last call
+struct Frame {
+    variables: HashMap<Symbol, Operand>,
+    /// local variable whose address is taken -> its address on the heap
+    addrs: HashMap<Symbol, usize>,
+    ret_value: Operand,
+}
+
+impl<'ir> Interpreter<'ir> {
+    pub fn new(ir: &'ir LinearIR) -> Interpreter<'ir> {
+        let mut interp = Interpreter {
+            funcs: ir.funcs.iter().map(|f| (f.name.as_str(), f)).collect(),
+            ro_local_strs: &ir.ro_local_strs,
+            steps: 0,
+            max_steps: DEFAULT_MAX_STEPS,
+            call_depth: 0,
+            heap: HashMap::new(),
+            allocs: BTreeMap::new(),
+            heap_top: HEAP_BASE,
+            vecs: HashMap::new(),
+            vtables: HashMap::new(),
+            ro_strs: HashMap::new(),
+            pinned: 0,
+            consts: HashMap::new(),
+            evaluating: vec![],
+            const_eval: false,
+            output: vec![],
+        };
+        for (symbol, methods) in ir.vtables.iter() {
+            // each entry takes 8 bytes, so they are adjacent
+            let entries: Vec<usize> = methods
+                .iter()
+                .map(|method| interp.alloc_pinned(Operand::FnLabel(method.clone())))
+                .collect();
+            interp.vtables.insert(symbol, entries.first().copied().unwrap_or(HEAP_BASE));
+        }
+        // the bytes of a string are adjacent and end with `\0`
+        for (symbol, s) in ir.ro_local_strs.iter() {
+            let addr = interp.alloc(s.len() + 1);
+            for (i, b) in s.bytes().chain(std::iter::once(0)).enumerate() {
+                interp.heap.insert(addr + i, Some(Operand::U8(b)));
+            }
+            interp.pinned += 1;
+            interp.ro_strs.insert(symbol, addr);
+        }
+        interp
+    }
+
+    /// Executing more than `max_steps` instructions is an error.
+    #[cfg(test)]
+    pub fn set_max_steps(&mut self, max_steps: usize) {
+        self.max_steps = max_steps;
+    }
+
+    /// Forbid calling the runtime functions, for evaluating constants.
+    pub fn set_const_eval(&mut self, const_eval: bool) {
+        self.const_eval = const_eval;
+    }
+
+    /// Value of the constant `symbol`, computed by its initializer function once.
+    pub fn eval_const(&mut self, symbol: &str) -> Result<Operand, RccError> {
+        if let Some(value) = self.consts.get(symbol) {
+            return Ok(value.clone());
+        }
+        if self.evaluating.iter().any(|s| s == symbol) {
+            return Err(format!("cycle detected when evaluating constant `{}`", symbol).into());
+        }
+        self.evaluating.push(symbol.to_string());
+        let value = self.call(&const_init_fn(symbol), vec![]);
+        self.evaluating.pop();
+        let value = value?;
+        self.consts.insert(symbol.to_string(), value.clone());
+        Ok(value)
+    }
+
+    /// Number of heap allocations which are not freed.
+    #[cfg(test)]
+    pub fn live_allocations(&self) -> usize {
+        self.allocs.len() - self.pinned
+    }
+
+    /// Run `main` and return its exit value.
+    #[cfg(test)]
+    pub fn run(&mut self) -> Result<Operand, RccError> {
+        self.call("main", vec![])
+    }
+
+    pub fn call(&mut self, fn_name: &str, args: Vec<Operand>) -> Result<Operand, RccError> {
+        let func = match self.funcs.get(fn_name) {
+            Some(func) => *func,
+            None => return self.call_builtin(fn_name, args),
+        };
+        if func.fn_args.len() != args.len() {
+            return Err(format!(
+                "function `{}` takes {} arguments but {} were supplied",
+                fn_name,
+                func.fn_args.len(),
+                args.len()
+            )
+            .into());
+        }
+        if self.call_depth >= MAX_CALL_DEPTH {
+            return Err("stack overflow".into());
+        }
+
+        let mut frame = Frame {
+            variables: HashMap::new(),
+            addrs: HashMap::new(),
+            ret_value: Operand::Unit,
+        };
+        for ((arg_name, _), arg) in func.fn_args.iter().zip(args) {
+            frame
+                .variables
+                .insert(local_var(arg_name.as_str(), func.block_scope_id), arg);
+        }
+
+        self.call_depth += 1;
+        let res = self.exec(func, &mut frame);
+        self.call_depth -= 1;
+        res
+    }
+
+    fn call_builtin(&mut self, fn_name: &str, args: Vec<Operand>) -> Result<Operand, RccError> {
+        if self.const_eval {
+            return Err(format!("`{}` can not be called in constants", fn_name).into());
+        }
+        match (fn_name, args.as_slice()) {
+            ("putchar", [Operand::I32(c)]) => {
+                self.output.push(*c as u8);
+                Ok(Operand::Unit)
+            }
+            (PRINT_STR, [Operand::Place(p)]) => match self.ro_local_strs.get(p.label.as_str()) {
+                Some(s) => {
+                    self.output.extend(s.bytes());
+                    Ok(Operand::Unit)
+                }
+                None => Err(format!("`{}` is not a string", p.label).into()),
+            },
+            (PANIC, [Operand::Place(p)]) => match self.ro_local_strs.get(p.label.as_str()) {
+                Some(msg) => Err(msg.trim_end().into()),
+                None => Err(format!("`{}` is not a string", p.label).into()),
+            },
+            (ALLOC, [Operand::Usize(size)]) => Ok(Operand::Usize(self.alloc(*size))),
+            (FREE, [Operand::Usize(addr)]) => {
+                self.free(*addr)?;
+                Ok(Operand::Unit)
+            }
+            (MEMCPY, [Operand::Usize(dest), Operand::Usize(src), Operand::Usize(size)]) => {
+                for i in 0..*size {
+                    if !self.is_allocated(src + i) || !self.is_allocated(dest + i) {
+                        return Err(format!("invalid memory access `{:#x}`", src + i).into());
+                    }
+                    let value = self.heap.get(&(src + i)).cloned().flatten();
+                    self.heap.insert(dest + i, value);
+                }
+                Ok(Operand::Unit)
+            }
+            (VEC_NEW, []) => {
+                let addr = self.alloc(0);
+                self.vecs.insert(addr, vec![]);
+                Ok(Operand::Usize(addr))
+            }
+            (VEC_PUSH, [Operand::Usize(addr), Operand::Usize(size)]) => {
+                let elem = self.alloc(*size);
+                self.elems(*addr)?.push(elem);
+                Ok(Operand::Usize(elem))
+            }
+            (VEC_INDEX, [Operand::Usize(addr), Operand::Usize(index), Operand::Usize(_)]) => {
+                let elems = self.elems(*addr)?;
+                match elems.get(*index) {
+                    Some(elem) => Ok(Operand::Usize(*elem)),
+                    None => Err(format!(
+                        "index out of bounds: the len is {} but the index is {}",
+                        elems.len(),
+                        index
+                    )
+                    .into()),
+                }
+            }
+            (VEC_LEN, [Operand::Usize(addr)]) => Ok(Operand::Usize(self.elems(*addr)?.len())),
+            (VEC_FREE, [Operand::Usize(addr)]) => {
+                for elem in std::mem::take(self.elems(*addr)?) {
+                    self.free(elem)?;
+                }
+                self.vecs.remove(addr);
+                self.free(*addr)?;
+                Ok(Operand::Unit)
+            }
+            (STRING_PUSH_STR, [Operand::Usize(addr), Operand::Place(p)]) => {
+                let s = match self.ro_local_strs.get(p.label.as_str()) {
+                    Some(s) => s.clone(),
+                    None => return Err(format!("`{}` is not a string", p.label).into()),
+                };
+                for c in s.chars() {
+                    let elem = self.alloc(1);
+                    self.heap.insert(elem, Some(Operand::Char(c)));
+                    self.elems(*addr)?.push(elem);
+                }
+                Ok(Operand::Unit)
+            }
+            (PRINT_STRING, [Operand::Usize(addr)]) => {
+                let mut s = String::new();
+                for elem in self.elems(*addr)?.clone() {
+                    match self.heap.get(&elem) {
+                        Some(Some(Operand::Char(c))) => s.push(*c),
+                        _ => return Err(format!("invalid memory access `{:#x}`", elem).into()),
+                    }
+                }
+                self.output.extend(s.bytes());
+                Ok(Operand::Unit)
+            }
+            (PRINT_CHAR, [Operand::Char(c)]) => {
+                self.output.extend(c.to_string().bytes());
+                Ok(Operand::Unit)
+            }
+            (PRINT_INT | PRINT_UINT, [i]) => {
+                let s = match i {
+                    Operand::I8(n) => n.to_string(),
+                    Operand::I16(n) => n.to_string(),
+                    Operand::I32(n) => n.to_string(),
+                    Operand::I64(n) => n.to_string(),
+                    Operand::Isize(n) => n.to_string(),
+                    Operand::U8(n) => n.to_string(),
+                    Operand::U16(n) => n.to_string(),
+                    Operand::U32(n) => n.to_string(),
+                    Operand::U64(n) => n.to_string(),
+                    Operand::Usize(n) => n.to_string(),
+                    o => return Err(format!("`{}` is not an integer", o).into()),
+                };
+                self.output.extend(s.bytes());
+                Ok(Operand::Unit)
+            }
+            _ => Err(format!("undefined function `{}`", fn_name).into()),
+        }
+    }
+
+    fn alloc(&mut self, size: usize) -> usize {
+        let addr = self.heap_top;
+        // an empty allocation still has a unique address
+        self.heap_top += size.max(1).div_ceil(8) * 8;
+        self.heap.insert(addr, None);
+        self.allocs.insert(addr, size);
+        addr
+    }
+
+    fn alloc_pinned(&mut self, value: Operand) -> usize {
+        let addr = self.alloc(8);
+        self.heap.insert(addr, Some(value));
+        self.pinned += 1;
+        addr
+    }
+
+    fn free(&mut self, addr: usize) -> Result<(), RccError> {
+        match self.allocs.remove(&addr) {
+            Some(size) => {
+                for a in addr..addr + size.max(1) {
+                    self.heap.remove(&a);
+                }
+                Ok(())
+            }
+            None => Err(format!("free of invalid pointer {:#x}", addr).into()),
+        }
+    }
+
+    /// `addr` is in an allocation which is not freed
+    fn is_allocated(&self, addr: usize) -> bool {
+        match self.allocs.range(..=addr).next_back() {
+            Some((base, size)) => addr < base + size.max(&1),
+            None => false,
+        }
+    }
+
+    fn elems(&mut self, addr: usize) -> Result<&mut Vec<usize>, RccError> {
+        match self.vecs.get_mut(&addr) {
+            Some(elems) => Ok(elems),
+            None => Err(format!("`{:#x}` is not a vector", addr).into()),
+        }
+    }
+
+    fn exec(&mut self, func: &Func, frame: &mut Frame) -> Result<Operand, RccError> {
+        // index of `func.insts`, label - 1
+        let mut pc = 0;
+        while let Some(inst) = func.insts.get(pc) {
+            self.steps += 1;
+            if self.steps > self.max_steps {
+                return Err("too many steps, infinite loop?".into());
+            }
+            pc += 1;
+            for operand in inst.src_operands() {
+                match operand {
+                    Operand::Place(p)
+                        if p.kind == VarKind::Const
+                            && self.funcs.contains_key(const_init_fn(p.label.as_str()).as_str()) =>
+                    {
+                        self.eval_const(p.label.as_str())?;
+                    }
+                    _ => {}
+                }
+            }
+            match inst {
+                IRInst::BinOp {
+                    op,
+                    dest,
+                    src1,
+                    src2,
+                } => {
+                    let l = self.ro_str_addr(self.eval(frame, src1)?);
+                    let r = self.ro_str_addr(self.eval(frame, src2)?);
+                    // the arithmetic of constants is checked, and of programs wraps
+                    let overflow = if self.const_eval {
+                        Overflow::Error
+                    } else {
+                        Overflow::Wrap
+                    };
+                    match bin_op_may_constant_fold(op, &l, &r, overflow)? {
+                        Some(value) => {
+                            self.set_var(frame, dest.label, value);
+                        }
+                        None => {
+                            return Err(format!("unsupported operation: {} {} {}", l, op, r).into())
+                        }
+                    }
+                }
+                IRInst::Jump { label } => pc = label - 1,
+                IRInst::JumpIfCond {
+                    cond,
+                    src1,
+                    src2,
+                    label,
+                } => {
+                    let l = self.eval(frame, src1)?;
+                    let r = self.eval(frame, src2)?;
+                    match jump_cond_may_constant_fold(cond, &l, &r) {
+                        Some(true) => pc = label - 1,
+                        Some(false) => {}
+                        None => {
+                            return Err(format!("unsupported comparison: {} {} {}", l, cond, r).into())
+                        }
+                    }
+                }
+                IRInst::JumpIf { cond, label } => {
+                    if self.eval_bool(frame, cond)? {
+                        pc = label - 1;
+                    }
+                }
+                IRInst::JumpIfNot { cond, label } => {
+                    if !self.eval_bool(frame, cond)? {
+                        pc = label - 1;
+                    }
+                }
+                IRInst::Switch {
+                    src,
+                    cases,
+                    default,
+                } => {
+                    let value = self.eval(frame, src)?;
+                    let value = match value.int_value() {
+                        Some(value) => value,
+                        None => return Err(format!("invalid switch value `{}`", value).into()),
+                    };
+                    pc = match cases.iter().find(|(v, _)| *v == value) {
+                        Some((_, label)) => label - 1,
+                        None => default - 1,
+                    };
+                }
+                IRInst::LoadData { dest, src } => {
+                    let mut value = self.eval(frame, src)?;
+                    // `a as u8` of an integer `a` truncates or extends it
+                    if dest.ir_type.is_int() {
+                        value = value.cast_int(dest.ir_type).unwrap_or(value);
+                    }
+                    self.set_var(frame, dest.label, value);
+                }
+                IRInst::LoadAddr { dest, symbol } => {
+                    let value = match symbol {
+                        Operand::Place(p) if matches!(p.kind, VarKind::Local | VarKind::LocalMut) => {
+                            Operand::Usize(self.local_addr(frame, p)?)
+                        }
+                        _ => self.eval(frame, symbol)?,
+                    };
+                    self.set_var(frame, dest.label, value);
+                }
+                IRInst::Load { dest, addr, .. } => {
+                    let addr_value = self.eval_addr(frame, addr)?;
+                    let value = match self.heap.get(&addr_value) {
+                        Some(Some(value)) => value.clone(),
+                        _ if self.is_allocated(addr_value) => {
+                            return Err(format!("`{}` is not initialized", addr).into())
+                        }
+                        _ => return Err(format!("invalid memory access `{}`", addr).into()),
+                    };
+                    self.set_var(frame, dest.label, value);
+                }
+                IRInst::Store { addr, src, .. } => {
+                    let value = self.eval(frame, src)?;
+                    let addr_value = self.eval_addr(frame, addr)?;
+                    if !self.is_allocated(addr_value) {
+                        return Err(format!("invalid memory access `{}`", addr).into());
+                    }
+                    self.heap.insert(addr_value, Some(value));
+                }
+                IRInst::Call { callee, args } => {
+                    let fn_name = match self.eval(frame, callee)? {
+                        Operand::FnLabel(fn_name) => fn_name,
+                        o => return Err(format!("`{}` is not callable", o).into()),
+                    };
+                    let mut values = Vec::with_capacity(args.len());
+                    for arg in args.iter() {
+                        values.push(self.eval(frame, arg)?);
+                    }
+                    frame.ret_value = self.call(&fn_name, values)?;
+                }
+                IRInst::Ret(operand) => return self.eval(frame, operand),
+                IRInst::Asm { .. } => {
+                    return Err("inline assembly cannot be interpreted".into())
+                }
+                IRInst::Memcpy { srcs, dests, .. } => self.copy(frame, srcs, dests)?,
+                IRInst::BoundsCheck { index, len, msg } => {
+                    self.check_bounds(frame, index, len, msg)?
+                }
+                IRInst::Loc(_) => {}
+            }
+        }
+        Ok(Operand::Unit)
+    }
+
+    /// Panic with `msg` unless `index < len`
+    fn check_bounds(
+        &mut self,
+        frame: &mut Frame,
+        index: &Operand,
+        len: &Operand,
+        msg: &Operand,
+    ) -> Result<(), RccError> {
+        let index = self.eval(frame, index)?;
+        let len = self.eval(frame, len)?;
+        match jump_cond_may_constant_fold(&Jump::JLt, &index, &len) {
+            Some(true) => Ok(()),
+            Some(false) => {
+                let msg = self.eval(frame, msg)?;
+                self.call(PANIC, vec![msg]).map(|_| ())
+            }
+            None => Err(format!("unsupported comparison: {} < {}", index, len).into()),
+        }
+    }
+
+    /// Copy the fields of a struct, all of them are read before any is written
+    fn copy(
+        &mut self,
+        frame: &mut Frame,
+        srcs: &[Operand],
+        dests: &[Place],
+    ) -> Result<(), RccError> {
+        let mut values = Vec::with_capacity(srcs.len());
+        for src in srcs.iter() {
+            values.push(self.eval(frame, src)?);
+        }
+        for (dest, value) in dests.iter().zip(values) {
+            self.set_var(frame, dest.label, value);
+        }
+        Ok(())
+    }
+
+    /// Address of the local variable `place`, which is moved to the heap the first time. The
+    /// field variables of a struct are moved together, at their offsets in the struct. An array
+    /// in the stack frame takes its bytes on the heap.
+    fn local_addr(&mut self, frame: &mut Frame, place: &Place) -> Result<usize, RccError> {
+        if let Some(&addr) = frame.addrs.get(&place.label) {
+            return Ok(addr);
+        }
+        if let IRType::Array(size) = place.ir_type {
+            let addr = self.alloc(size as usize);
+            self.pinned += 1;
+            frame.addrs.insert(place.label, addr);
+            return Ok(addr);
+        }
+        let struct_var = match split_field_var(place.label.as_str()) {
+            Some((var, _)) => var,
+            None => {
+                let addr = self.alloc_pinned(self.eval(frame, &Operand::Place(place.clone()))?);
+                frame.addrs.insert(place.label, addr);
+                return Ok(addr);
+            }
+        };
+        let fields: Vec<(Symbol, usize)> = frame
+            .variables
+            .keys()
+            .filter_map(|label| match split_field_var(label.as_str()) {
+                Some((var, offset)) if var == struct_var => Some((*label, offset as usize)),
+                _ => None,
+            })
+            .collect();
+        let size = fields.iter().map(|(_, offset)| offset + 8).max().unwrap_or(8);
+        let base = self.alloc(size);
+        self.pinned += 1;
+        for (label, offset) in fields {
+            let value = frame.variables.remove(&label);
+            self.heap.insert(base + offset, value);
+            frame.addrs.insert(label, base + offset);
+        }
+        match frame.addrs.get(&place.label) {
+            Some(&addr) => Ok(addr),
+            None => Err(format!("`{}` is used before definition", place.label).into()),
+        }
+    }
+
+    /// Write a local variable, which is on the heap if its address is taken
+    fn set_var(&mut self, frame: &mut Frame, label: Symbol, value: Operand) {
+        match frame.addrs.get(&label) {
+            Some(addr) => {
+                self.heap.insert(*addr, Some(value));
+            }
+            None => {
+                frame.variables.insert(label, value);
+            }
+        }
+    }
+
+    fn eval(&self, frame: &Frame, operand: &Operand) -> Result<Operand, RccError> {
+        match operand {
+            Operand::Place(p) => match p.kind {
+                VarKind::LitConst if self.vtables.contains_key(p.label.as_str()) => {
+                    Ok(Operand::Usize(self.vtables[p.label.as_str()]))
+                }
+                VarKind::Const if self.consts.contains_key(p.label.as_str()) => {
+                    Ok(self.consts[p.label.as_str()].clone())
+                }
+                // address of a symbol
+                VarKind::LitConst | VarKind::Const | VarKind::Static => Ok(operand.clone()),
+                VarKind::Local | VarKind::LocalMut => {
+                    let value = match frame.addrs.get(&p.label) {
+                        Some(addr) => self.heap.get(addr).cloned().flatten(),
+                        None => frame.variables.get(&p.label).cloned(),
+                    };
+                    match value {
+                        Some(value) => Ok(value),
+                        None => Err(format!("`{}` is used before definition", p.label).into()),
+                    }
+                }
+            },
+            Operand::FnRetPlace(_) => Ok(frame.ret_value.clone()),
+            o => Ok(o.clone()),
+        }
+    }
+
+    /// A read-only string is a symbol, except in the arithmetic of its address
+    fn ro_str_addr(&self, value: Operand) -> Operand {
+        match &value {
+            Operand::Place(p) if p.kind == VarKind::LitConst => {
+                match self.ro_strs.get(p.label.as_str()) {
+                    Some(addr) => Operand::Usize(*addr),
+                    None => value,
+                }
+            }
+            _ => value,
+        }
+    }
+
+    fn eval_addr(&self, frame: &Frame, addr: &Operand) -> Result<usize, RccError> {
+        match self.ro_str_addr(self.eval(frame, addr)?) {
+            Operand::Usize(addr) => Ok(addr),
+            o => Err(format!("`{}` is not an address", o).into()),
+        }
+    }
+
+    fn eval_bool(&self, frame: &Frame, cond: &Operand) -> Result<bool, RccError> {
+        match self.eval(frame, cond)? {
+            Operand::Bool(b) => Ok(b),
+            o => Err(format!("invalid condition `{}`", o).into()),
+        }
+    }
+}
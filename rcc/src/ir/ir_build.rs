@@ -1,36 +1,90 @@
-use crate::analyser::scope::ScopeStack;
+use crate::analyser::scope::{ScopeID, ScopeStack};
+use crate::analyser::type_table::TypeTable;
 use crate::analyser::sym_resolver::{TypeInfo, VarKind};
 use crate::ast::expr::{
-    ArrayExpr, ArrayIndexExpr, AssignExpr, AssignOp, BinOpExpr, BinOperator, BlockExpr, BreakExpr,
-    CallExpr, Expr, ExprKind, ExprVisit, FieldAccessExpr, GroupedExpr, IfExpr, LhsExpr, LitNumExpr,
-    LoopExpr, PathExpr, RangeExpr, ReturnExpr, StructExpr, TupleExpr, TupleIndexExpr, UnAryExpr,
+    ArrayExpr, ArrayIndexExpr, AsmExpr, AssertExpr, AssignExpr, AssignOp, BinOpExpr, BinOperator, BlockExpr, BreakExpr,
+    CallExpr, CastExpr, Expr, ExprKind, ExprVisit, FieldAccessExpr, ForExpr, GroupedExpr, IfExpr, LhsExpr, LitNumExpr,
+    LoopExpr, PathExpr, PrintExpr, RangeOp, ReturnExpr, StructExpr, TupleExpr, TupleIndexExpr, UnAryExpr,
     UnOp, WhileExpr,
 };
 use crate::ast::file::File;
-use crate::ast::item::{Item, ItemFn, ItemStruct};
+use crate::ast::item::{ExternalItem, Item, ItemConst, ItemExternalBlock, ItemFn, ItemStruct};
 use crate::ast::pattern::{IdentPattern, Pattern};
+use crate::ast::printer::expr_str;
 use crate::ast::stmt::{LetStmt, Stmt};
-use crate::ast::types::TypeLitNum;
-use crate::ast::AST;
+use crate::ast::types::{PtrKind, TypeLitNum};
+use crate::ast::{NodeId, AST};
+use crate::intern::Symbol;
 use crate::ir;
-use crate::ir::linear_ir::LinearIR;
+use crate::ir::bounds_check::bounds_checks;
+use crate::ir::interp::Interpreter;
+use crate::ir::linear_ir::{Func, LinearIR};
+use crate::ir::mangle::{demangle, nested_fn_name};
+use crate::ir::var_name::{
+    const_init_fn, end_var, field_var, is_array_len_const, len_var, local_var, param_var,
+    split_field_var, versioned_var, vtable_var,
+    ALLOC, FREE, MEMCPY, PANIC, PRINT_CHAR, PRINT_INT, PRINT_STR, PRINT_STRING, PRINT_UINT,
+    SRET, STRING_PUSH_STR, VEC_FREE, VEC_INDEX, VEC_LEN, VEC_NEW, VEC_PUSH,
+};
 use crate::ir::Jump::*;
-use crate::ir::{IRInst, IRType, Jump, Operand, Place};
-use crate::rcc::{OptimizeLevel, RccError};
-use std::cell::RefCell;
-use std::ops::Deref;
-use std::rc::Rc;
+use crate::ir::{
+    field_offsets, layout, scalar_fields, IRInst, IRType, Jump, Operand, Overflow, Place,
+};
+use crate::rcc::{CompileError, ErrorKind, OptimizeLevel, RccError};
+use std::collections::{HashMap, HashSet};
+
+/// An if-else chain with fewer cases is not lowered to a switch
+const MIN_SWITCH_CASES: usize = 4;
+
+/// A smaller struct is copied field by field instead of by a `Memcpy`
+const MEMCPY_MIN_SIZE: u32 = 32;
 
 pub struct IRBuilder {
     ir_output: LinearIR,
     fn_ret_temp_var: Vec<Place>,
 
     scope_stack: ScopeStack,
+    types: TypeTable,
 
     // (place = loop expr, break link)
     loop_var_stack: Vec<(Option<Place>, usize)>,
 
     optimize_level: OptimizeLevel,
+
+    /// insert runtime checks which branch to `__rcc_panic`
+    checks: bool,
+    /// `+`, `-` and `*` of integers branch to `__rcc_panic` on overflow instead of wrapping
+    overflow_checks: bool,
+
+    /// external function name -> `#[link_name]`
+    link_names: HashMap<String, String>,
+
+    /// Variables owning heap memory which are moved, they are not freed at the end of their scopes
+    moved_variables: HashSet<Symbol>,
+
+    /// function name -> read only strings, for functions whose assembly is reused from the
+    /// incremental cache. Their strings are added without building IR.
+    reused_fns: HashMap<String, Vec<String>>,
+
+    /// function name -> indexes of the `.LC` labels it uses, in order of first use
+    fn_ro_strs: HashMap<String, Vec<usize>>,
+
+    /// symbol -> value of the constants, which are used as literals
+    consts: HashMap<Symbol, Operand>,
+
+    /// build `loc` instructions from the lines of the statements
+    debug_info: bool,
+
+    /// block -> lines of its statements and last expr, taken from the file
+    stmt_lines: HashMap<NodeId, Vec<u32>>,
+
+    /// line of the statement being built, for the messages of the runtime checks. 0 is an
+    /// unknown line.
+    cur_line: u32,
+
+    /// (block, name) -> symbol of the functions declared in the blocks of functions, which
+    /// are built as functions of their own
+    nested_fns: HashMap<(ScopeID, String), String>,
 }
 
 impl IRBuilder {
@@ -39,11 +93,43 @@ impl IRBuilder {
             ir_output: LinearIR::new(),
             fn_ret_temp_var: vec![],
             scope_stack: ScopeStack::new(),
+            types: TypeTable::new(),
             loop_var_stack: vec![],
             optimize_level,
+            checks: false,
+            overflow_checks: false,
+            link_names: HashMap::new(),
+            moved_variables: HashSet::new(),
+            reused_fns: HashMap::new(),
+            fn_ro_strs: HashMap::new(),
+            consts: HashMap::new(),
+            debug_info: false,
+            stmt_lines: HashMap::new(),
+            cur_line: 0,
+            nested_fns: HashMap::new(),
         }
     }
 
+    pub fn set_checks(&mut self, checks: bool) {
+        self.checks = checks;
+    }
+
+    pub fn set_overflow_checks(&mut self, overflow_checks: bool) {
+        self.overflow_checks = overflow_checks;
+    }
+
+    pub fn set_debug_info(&mut self, debug_info: bool) {
+        self.debug_info = debug_info;
+    }
+
+    pub fn set_reused_fns(&mut self, reused_fns: HashMap<String, Vec<String>>) {
+        self.reused_fns = reused_fns;
+    }
+
+    pub fn fn_ro_strs(&self) -> &HashMap<String, Vec<usize>> {
+        &self.fn_ro_strs
+    }
+
     pub(crate) fn generate_ir(&mut self, ast: &mut AST) -> Result<LinearIR, RccError> {
         self.visit_file(&mut ast.file)?;
         let mut output = LinearIR::new();
@@ -51,59 +137,258 @@ impl IRBuilder {
         Ok(output)
     }
 
-    fn gen_temp_var(&mut self, type_info: Rc<RefCell<TypeInfo>>) -> Place {
-        let t = type_info.borrow();
-        let tp = t.deref();
-        let ir_type = IRType::from_type_info(tp).unwrap();
-        std::mem::drop(t);
+    fn gen_temp_var(&mut self, type_info: TypeInfo) -> Place {
+        self.try_gen_temp_var(type_info).unwrap()
+    }
+
+    /// A temporary variable of a scalar type, an error for a struct or a tuple
+    fn try_gen_temp_var(&mut self, type_info: TypeInfo) -> Result<Place, RccError> {
+        let ir_type = IRType::from_type_info(&type_info)?;
+        let type_info = self.types.new_slot(type_info);
+        let label = self
+            .scope_stack
+            .cur_scope_mut()
+            .gen_temp_variable(type_info);
+        Ok(Place::local(label, ir_type))
+    }
+
+    /// Places of the scalar fields of a new temporary struct, e.g. `$3.0_2` and `$3.4_2`
+    fn gen_temp_struct(&mut self, type_info: TypeInfo) -> Vec<Place> {
+        let fields = scalar_fields(&type_info, &self.types);
+        let type_info = self.types.new_slot(type_info);
         let label = self
             .scope_stack
             .cur_scope_mut()
             .gen_temp_variable(type_info);
-        Place::local(label, ir_type)
+        let (ident, scope_id) = label.as_str().rsplit_once('_').unwrap();
+        fields
+            .into_iter()
+            .map(|(offset, field)| {
+                let ir_type = IRType::from_type_info(&field).unwrap();
+                let field = field_var(ident, offset);
+                let label = Symbol::intern_fmt(format_args!("{}_{}", field, scope_id));
+                Place::local(label, ir_type)
+            })
+            .collect()
     }
 
     fn gen_variable(&mut self, ident: &str, var_kind: VarKind) -> Place {
-        let res = self.scope_stack.cur_scope().find_variable(ident).unwrap();
-        let ir_type = IRType::from_var_info(res.0).unwrap();
-        Place::variable(ident, res.1, var_kind, ir_type)
+        let (var, scope_id) = self.scope_stack.find_variable(ident).unwrap();
+        let ir_type = IRType::from_var_info(var, &self.types).unwrap();
+        Place::variable(versioned_var(ident, var.version()).as_str(), scope_id, var_kind, ir_type)
     }
 
     fn visit_file(&mut self, file: &mut File) -> Result<(), RccError> {
         self.scope_stack.enter_file(file);
-        for item in file.items.iter_mut() {
-            self.visit_item(item)?;
+        self.types = std::mem::take(&mut file.type_table);
+        self.stmt_lines = std::mem::take(&mut file.stmt_lines);
+        for (symbol, methods) in self.types.vtables() {
+            self.ir_output.vtables.insert(symbol.clone(), methods.clone());
+        }
+        // external functions may be called before they are declared
+        for item in file.items.iter() {
+            if let Item::ExternalBlock(item_block) = item {
+                self.add_link_names(item_block);
+            }
+        }
+        let result = self.eval_consts(file).and_then(|_| {
+            file.items
+                .iter_mut()
+                .try_for_each(|item| self.visit_item(item))
+        });
+        file.type_table = std::mem::take(&mut self.types);
+        file.stmt_lines = std::mem::take(&mut self.stmt_lines);
+        self.scope_stack.exit_file(file);
+        result
+    }
+
+    /// Build the IR of the `const fn`s and of the initializers of the constants aside, and
+    /// run it by the interpreter. A constant is used before its value is known only there.
+    fn eval_consts(&mut self, file: &mut File) -> Result<(), RccError> {
+        if !file.items.iter().any(|item| matches!(item, Item::Const(_))) {
+            return Ok(());
+        }
+        let output = std::mem::replace(&mut self.ir_output, LinearIR::new());
+        let result = file.items.iter_mut().try_for_each(|item| match item {
+            Item::Fn(item_fn) if item_fn.is_const && !item_fn.is_generic() => {
+                self.visit_item_fn(&item_fn.name.clone(), item_fn)
+            }
+            Item::Const(item_const) => self.visit_const_init(item_const),
+            _ => Ok(()),
+        });
+        let const_ir = std::mem::replace(&mut self.ir_output, output);
+        result?;
+
+        let mut interpreter = Interpreter::new(&const_ir);
+        interpreter.set_const_eval(true);
+        for item in file.items.iter() {
+            if let Item::Const(item_const) = item {
+                for symbol in self.const_labels(&item_const.name) {
+                    let value = interpreter.eval_const(symbol.as_str()).map_err(|e| -> RccError {
+                        let name = &item_const.name;
+                        if is_array_len_const(name) {
+                            let msg = "evaluation of array length failed";
+                            let error = CompileError::new(ErrorKind::Type, msg);
+                            return error.note(e.to_string()).into();
+                        }
+                        let name = demangle(name).unwrap_or_else(|| name.clone());
+                        format!("evaluation of constant `{}` failed: {}", name, e).into()
+                    })?;
+                    self.consts.insert(symbol, value);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Values of the constants of a resolved file by their names, only the ones of primitive
+    /// types. The symbol resolver computes the lengths of arrays by them.
+    pub(crate) fn eval_file_consts(file: &mut File) -> Result<HashMap<String, Operand>, RccError> {
+        let mut builder = IRBuilder::new(OptimizeLevel::Zero);
+        builder.scope_stack.enter_file(file);
+        builder.types = std::mem::take(&mut file.type_table);
+        let result = builder.eval_consts(file);
+        file.type_table = std::mem::take(&mut builder.types);
+        builder.scope_stack.exit_file(file);
+        result?;
+        let mut consts = HashMap::new();
+        for item in file.items.iter() {
+            if let Item::Const(item_const) = item {
+                let symbol = local_var(&item_const.name, file.scope_id);
+                if let Some(value) = builder.consts.get(&symbol) {
+                    consts.insert(item_const.name.clone(), value.clone());
+                }
+            }
+        }
+        Ok(consts)
+    }
+
+    /// Labels of the values of a constant, one for each scalar field of a struct
+    fn const_labels(&self, name: &str) -> Vec<Symbol> {
+        let (var, scope_id) = self.scope_stack.find_variable(name).unwrap();
+        if self.types.get(var.type_info).is_struct() {
+            self.struct_var_places(name).into_iter().map(|place| place.label).collect()
+        } else {
+            vec![local_var(name, scope_id)]
+        }
+    }
+
+    /// `const N: usize = size(3);` -> `fn N_1$init() -> usize { size(3) }`. A struct constant
+    /// has an init function for each of its scalar fields, `fn O.4_1$init() -> i32 { ... }`.
+    fn visit_const_init(&mut self, item_const: &mut ItemConst) -> Result<(), RccError> {
+        let (var, scope_id) = self.scope_stack.find_variable(&item_const.name).unwrap();
+        let ret_info = self.types.get(var.type_info).clone();
+        for (i, symbol) in self.const_labels(&item_const.name).into_iter().enumerate() {
+            self.ir_output
+                .funcs
+                .push(Func::new(const_init_fn(symbol.as_str()), false, vec![], scope_id));
+            let operand = if ret_info.is_struct() {
+                self.visit_struct_value(&mut item_const.expr)?.swap_remove(i)
+            } else {
+                let dest = self.gen_temp_var(ret_info.clone());
+                self.visit_expr(&mut item_const.expr, Some(dest), false)?
+            };
+            self.ir_output.add_instructions(IRInst::Ret(operand));
         }
         Ok(())
     }
 
     fn visit_item(&mut self, item: &mut Item) -> Result<(), RccError> {
         match item {
-            Item::Fn(item_fn) => self.visit_item_fn(item_fn),
+            Item::Fn(item_fn) => {
+                self.ir_output.take_used_ro_strs();
+                match self.reused_fns.get(&item_fn.name) {
+                    Some(strs) => {
+                        for s in strs.iter() {
+                            self.ir_output.add_ro_local_str(s.clone());
+                        }
+                    }
+                    // its instances are added to the items by the symbol resolver
+                    None if item_fn.is_generic() => {}
+                    None => self.visit_item_fn(&item_fn.name.clone(), item_fn)?,
+                }
+                let ro_strs = self.ir_output.take_used_ro_strs();
+                self.fn_ro_strs.insert(item_fn.name.clone(), ro_strs);
+                Ok(())
+            }
             Item::Struct(item_struct) => self.visit_item_struct(item_struct),
             Item::ExternalBlock(item_block) => {
-                // do nothing
+                self.add_link_names(item_block);
+                Ok(())
+            }
+            Item::Impl(item_impl) => {
+                let symbols: Vec<String> =
+                    item_impl.fns.iter().map(|f| item_impl.fn_symbol(f)).collect();
+                for (symbol, item_fn) in symbols.iter().zip(item_impl.fns.iter_mut()) {
+                    self.visit_item_fn(symbol, item_fn)?;
+                }
                 Ok(())
             }
+            Item::Trait(_) | Item::Const(_) | Item::Type(_) => Ok(()),
             _ => unimplemented!(),
         }
     }
 
-    fn visit_item_fn(&mut self, item_fn: &mut ItemFn) -> Result<(), RccError> {
-        self.ir_output.add_func(item_fn)?;
+    fn add_link_names(&mut self, item_block: &ItemExternalBlock) {
+        for item in item_block.external_items.iter() {
+            match item {
+                ExternalItem::Fn(f) => {
+                    if let Some(link_name) = &f.link_name {
+                        self.link_names.insert(f.name.clone(), link_name.clone());
+                    }
+                }
+            }
+        }
+    }
+
+    /// `fn_name` is the symbol of the function, e.g. `_RCM3i323Add3add` for a method.
+    fn visit_item_fn(&mut self, fn_name: &str, item_fn: &mut ItemFn) -> Result<(), RccError> {
+        // the params are looked up at the first statement, before they are shadowed
+        let ret_info = TypeInfo::from_type_anno(&item_fn.ret_type, &self.scope_stack);
+        self.scope_stack.enter_scope(item_fn.fn_block.scope_id);
+        self.ir_output
+            .add_func(fn_name, item_fn, &ret_info, self.scope_stack.scopes(), &self.types)?;
+        self.bind_param_patterns(item_fn);
+        self.scope_stack.exit_scope();
 
-        let info = self.scope_stack.cur_scope().find_fn(&item_fn.name);
+        // a function declared in a block is found by its name there
+        let info = if self.scope_stack.cur_scope_is_global() {
+            self.scope_stack.find_fn(fn_name)
+        } else {
+            self.scope_stack.find_fn(&item_fn.name)
+        };
         assert_eq!(info, TypeInfo::from_fn_signature(item_fn));
 
-        let ret_info = TypeInfo::from_type_anno(&item_fn.ret_type, self.scope_stack.cur_scope());
+        let diverges = ret_info == TypeInfo::Never;
         // visit function block
-        let dest = self.gen_temp_var(Rc::new(RefCell::new(ret_info)));
-        self.fn_ret_temp_var.push(dest.clone());
-
-        let operand = self.visit_block_expr(&mut item_fn.fn_block, Some(dest), false)?;
+        let operand = if ret_info.is_struct() {
+            let scope_id = item_fn.fn_block.scope_id;
+            let sret = Place::variable(SRET, scope_id, VarKind::Local, IRType::Addr);
+            self.fn_ret_temp_var.push(sret.clone());
+            self.visit_struct_fn_block(&mut item_fn.fn_block, &ret_info, sret)?
+        } else if let TypeInfo::Array(..) = ret_info {
+            let scope_id = item_fn.fn_block.scope_id;
+            let sret = Place::variable(SRET, scope_id, VarKind::Local, IRType::Addr);
+            self.fn_ret_temp_var.push(sret);
+            let dest = self.gen_temp_var(ret_info.clone());
+            match self.visit_block_expr(&mut item_fn.fn_block, Some(dest), false)? {
+                src @ Operand::Place(_) => {
+                    self.gen_array_ret(src, &ret_info)?;
+                    Operand::Unit
+                }
+                operand => operand,
+            }
+        } else {
+            let dest = self.gen_temp_var(ret_info);
+            self.fn_ret_temp_var.push(dest.clone());
+            self.visit_block_expr(&mut item_fn.fn_block, Some(dest), false)?
+        };
 
         if item_fn.fn_block.last_expr.is_none() && item_fn.fn_block.stmts.is_empty() {
             self.ir_output.add_instructions(IRInst::Ret(Operand::Unit));
+        } else if diverges {
+            // unreachable, there is no value of `!` to return
+            self.ir_output.add_instructions(IRInst::Ret(Operand::Never));
         } else if !item_fn.fn_block.last_stmt_is_return() {
             self.ir_output.add_instructions(IRInst::Ret(operand));
         }
@@ -112,13 +397,84 @@ impl IRBuilder {
         Ok(())
     }
 
+    /// Copy the params whose patterns are tuples to the variables of the patterns, e.g.
+    /// `fn f(a: i32, (b, _): (i32, i32))` starts with `b = arg$1.0`.
+    fn bind_param_patterns(&mut self, item_fn: &ItemFn) {
+        for (i, param) in item_fn.fn_params.params.iter().enumerate() {
+            if let Pattern::Tuple(patterns) = &param.pattern {
+                let arg = param_var(i);
+                let (var, _) = self.scope_stack.find_variable(arg.as_str()).unwrap();
+                let type_info = self.types.get(var.type_info).clone();
+                self.bind_tuple_pattern(patterns, &type_info, arg.as_str(), 0);
+            }
+        }
+    }
+
+    /// Bind `patterns` to the elements of the tuple of `type_info` at `offset` of the param
+    /// variable `arg`.
+    fn bind_tuple_pattern(
+        &mut self,
+        patterns: &[Pattern],
+        type_info: &TypeInfo,
+        arg: &str,
+        offset: u32,
+    ) {
+        let elems: Vec<&TypeInfo> = match type_info {
+            TypeInfo::Tuple(elems) => elems.iter().collect(),
+            t => unreachable!("`{:?}` is not a tuple", t),
+        };
+        let offsets = field_offsets(&elems, &self.types);
+        for ((pattern, elem), elem_offset) in patterns.iter().zip(elems).zip(offsets) {
+            let offset = offset + elem_offset;
+            match pattern {
+                Pattern::Identifier(ident_pattern) => {
+                    let ident = ident_pattern.ident();
+                    let (var, scope_id) = self.scope_stack.find_variable(ident).unwrap();
+                    let kind = var.kind();
+                    let srcs = scalar_fields(elem, &self.types)
+                        .into_iter()
+                        .map(|(field_offset, field)| {
+                            let label = field_var(arg, offset + field_offset).as_str();
+                            let ir_type = IRType::from_type_info(&field).unwrap();
+                            let place = Place::variable(label, scope_id, VarKind::Local, ir_type);
+                            Operand::Place(place)
+                        })
+                        .collect();
+                    let dests = if elem.is_aggregate() {
+                        self.struct_var_places(ident)
+                    } else {
+                        vec![self.gen_variable(ident, kind)]
+                    };
+                    self.copy_fields(dests, srcs, elem);
+                }
+                Pattern::Wild => {}
+                Pattern::Tuple(patterns) => self.bind_tuple_pattern(patterns, elem, arg, offset),
+            }
+        }
+    }
+
+    /// A function declared in a block is built aside, the instructions of the function
+    /// declaring it are added after it again.
+    fn visit_nested_fn(&mut self, item_fn: &mut ItemFn) -> Result<(), RccError> {
+        if item_fn.is_generic() {
+            return Err("generic functions declared in blocks are not supported yet".into());
+        }
+        let key = (self.scope_stack.cur_scope().scope_id, item_fn.name.clone());
+        let symbol = self.nested_fns[&key].clone();
+        let outer = self.ir_output.funcs.pop().unwrap();
+        let result = self.visit_item_fn(&symbol, item_fn);
+        self.ir_output.funcs.push(outer);
+        result
+    }
+
     fn visit_item_struct(&mut self, item_struct: &mut ItemStruct) -> Result<(), RccError> {
-        unimplemented!()
+        Ok(())
     }
 
     fn visit_stmt(&mut self, stmt: &mut Stmt) -> Result<(), RccError> {
         match stmt {
             Stmt::Semi => Ok(()),
+            Stmt::Item(Item::Fn(item_fn)) => self.visit_nested_fn(item_fn),
             Stmt::Item(item) => self.visit_item(item),
             Stmt::Let(let_stmt) => self.visit_let_stmt(let_stmt),
             Stmt::ExprStmt(expr) => {
@@ -132,18 +488,64 @@ impl IRBuilder {
     fn visit_let_stmt(&mut self, let_stmt: &mut LetStmt) -> Result<(), RccError> {
         let is_mut = let_stmt.is_mut();
         if let Some(rhs) = &mut let_stmt.rhs {
-            match &let_stmt.pattern {
-                Pattern::Identifier(ident_pattern) => {
-                    let ident = ident_pattern.ident();
-                    let dest = self.gen_variable(
-                        ident,
-                        if is_mut {
-                            VarKind::LocalMut
-                        } else {
-                            VarKind::Local
-                        },
-                    );
-                    let rhs = self.visit_expr(rhs, Some(dest), false)?;
+            // a tuple pattern is rejected by `SymbolResolver`
+            let ident = let_stmt.pattern.var_name().unwrap();
+            let kind = if is_mut {
+                VarKind::LocalMut
+            } else {
+                VarKind::Local
+            };
+            // the variable is visible from the next statement, while `rhs` is visited in this
+            // one, where a variable of the same name is the one declared before
+            self.scope_stack.cur_scope_mut().cur_stmt_id += 1;
+            let (var, _) = self.scope_stack.find_variable(ident).unwrap();
+            let type_info = self.types.get(var.type_info).clone();
+            let mut dests = if type_info.is_struct() {
+                self.struct_var_places(ident)
+            } else {
+                vec![self.gen_variable(ident, kind)]
+            };
+            let companion = if type_info.is_slice_ref() {
+                Some(len_var(ident))
+            } else if let TypeInfo::Range { .. } = type_info {
+                Some(end_var(ident))
+            } else {
+                type_info.dyn_trait().map(|_| vtable_var(ident))
+            };
+            if let Some(companion) = companion {
+                dests.push(self.gen_variable(companion.as_str(), kind));
+            }
+            self.scope_stack.cur_scope_mut().cur_stmt_id -= 1;
+
+            if type_info.is_struct() {
+                let srcs = self.visit_struct_value(rhs)?;
+                self.copy_fields(dests, srcs, &type_info);
+                return Ok(());
+            }
+            if type_info.is_slice_ref() {
+                let (data, len) = self.visit_slice_expr(rhs)?;
+                let (dest, len_dest) = (dests[0].clone(), dests[1].clone());
+                self.ir_output.add_instructions(IRInst::load_data(dest, data));
+                self.ir_output.add_instructions(IRInst::load_data(len_dest, len));
+                return Ok(());
+            }
+            if let TypeInfo::Range { .. } = type_info {
+                let (start, end) = self.visit_range_value(rhs)?;
+                let (dest, end_dest) = (dests[0].clone(), dests[1].clone());
+                self.ir_output.add_instructions(IRInst::load_data(dest, start));
+                self.ir_output.add_instructions(IRInst::load_data(end_dest, end));
+                return Ok(());
+            }
+            match type_info.dyn_trait() {
+                Some(trait_name) => {
+                    let trait_name = trait_name.to_string();
+                    let (data, vtable) = self.visit_dyn_expr(rhs, &trait_name)?;
+                    let (dest, vtable_dest) = (dests[0].clone(), dests[1].clone());
+                    self.ir_output.add_instructions(IRInst::load_data(dest, data));
+                    self.ir_output.add_instructions(IRInst::load_data(vtable_dest, vtable));
+                }
+                None => {
+                    self.visit_expr(rhs, Some(dests.remove(0)), false)?;
                 }
             }
         }
@@ -165,21 +567,35 @@ impl IRBuilder {
             Expr::Unary(unary_expr) => self.visit_unary_expr(unary_expr, dest, remain_temp),
             Expr::Block(block_expr) => self.visit_block_expr(block_expr, dest, remain_temp),
             Expr::Assign(assign_expr) => self.visit_assign_expr(assign_expr),
-            // Expr::Range(range_expr) => self.visit_range_expr(range_expr),
+            // a range is a pair of values, see `visit_range_value`
+            Expr::Range(_) => Err("ranges are only supported in `let`, `=` and `for`".into()),
             Expr::BinOp(bin_op_expr) => self.visit_bin_op_expr(bin_op_expr, dest),
+            Expr::Cast(cast_expr) => self.visit_cast_expr(cast_expr, dest, remain_temp),
             Expr::Grouped(grouped_expr) => self.visit_grouped_expr(grouped_expr, dest, remain_temp),
-            // Expr::Array(array_expr) => self.visit_array_expr(array_expr),
-            // Expr::ArrayIndex(array_index_expr) => self.visit_array_index_expr(array_index_expr),
-            // Expr::Tuple(tuple_expr) => self.visit_tuple_expr(tuple_expr),
+            Expr::Array(array_expr) => self.visit_array_expr(array_expr, dest),
+            Expr::ArrayIndex(array_index_expr) => {
+                self.visit_array_index_expr(array_index_expr, dest)
+            }
+            // the elements of a tuple are passed as arguments, see `visit_struct_value`
+            Expr::Tuple(_) => Err("tuples are only supported as params and arguments".into()),
             // Expr::TupleIndex(tuple_index_expr) => self.visit_tuple_index_expr(tuple_index_expr),
-            // Expr::Struct(struct_expr) => self.visit_struct_expr(struct_expr),
+            Expr::Struct(struct_expr) => {
+                self.visit_struct_expr(struct_expr)?;
+                Ok(Operand::Unit)
+            }
             Expr::Call(call_expr) => self.visit_call_expr(call_expr, dest),
-            // Expr::FieldAccess(field_access_expr) => self.visit_field_access_expr(field_access_expr),
+            Expr::FieldAccess(field_access_expr) => {
+                self.visit_field_access_expr(field_access_expr, dest, remain_temp)
+            }
             Expr::While(while_expr) => self.visit_while_expr(while_expr),
             Expr::Loop(loop_expr) => self.visit_loop_expr(loop_expr, dest),
+            Expr::For(for_expr) => self.visit_for_expr(for_expr),
             Expr::If(if_expr) => self.visit_if_expr(if_expr, dest),
             Expr::Return(return_expr) => self.visit_return_expr(return_expr, dest),
             Expr::Break(break_expr) => self.visit_break_expr(break_expr, dest),
+            Expr::Print(print_expr) => self.visit_print_expr(print_expr),
+            Expr::Assert(assert_expr) => self.visit_assert_expr(assert_expr),
+            Expr::Asm(asm_expr) => self.visit_asm_expr(asm_expr),
             _ => unimplemented!(),
         };
         debug_assert_ne!(
@@ -194,6 +610,7 @@ impl IRBuilder {
     fn visit_lhs_expr(&mut self, lhs_expr: &mut LhsExpr) -> Result<Operand, RccError> {
         let r = match lhs_expr {
             LhsExpr::Path(expr) => self.visit_path_expr(expr, None, false)?,
+            LhsExpr::FieldAccess(expr) => self.visit_field_access_expr(expr, None, false)?,
             _ => todo!("visit lhs expr"),
         };
         Ok(r)
@@ -208,6 +625,42 @@ impl IRBuilder {
         self.visit_expr(grouped_expr, dest, remain_temp)
     }
 
+    /// The casts between pointers keep the bits of the value, only the type of the place
+    /// holding it changes. The casts between integers truncate or extend the value to the
+    /// size of the new type.
+    ///
+    /// ```
+    /// $0 = &a
+    /// dest = $0
+    /// ```
+    fn visit_cast_expr(
+        &mut self,
+        cast_expr: &mut CastExpr,
+        dest: Option<Place>,
+        remain_temp: bool,
+    ) -> Result<Operand, RccError> {
+        let from = cast_expr.expr.type_info(&self.types);
+        let to = cast_expr.type_info(&self.types);
+        if IRType::from_type_info(&from)? == IRType::from_type_info(&to)? {
+            return self.visit_expr(&mut cast_expr.expr, dest, remain_temp);
+        }
+        let temp = self.gen_temp_var(from);
+        let operand = self.visit_expr(&mut cast_expr.expr, Some(temp), false)?;
+        // `-1i32 as u8` is the immediate `255u8`
+        let to_type = IRType::from_type_info(&to)?;
+        let operand = match operand.cast_int(to_type).filter(|_| to_type.is_int()) {
+            Some(imm) if dest.is_none() => return Ok(imm),
+            Some(imm) => imm,
+            None => operand,
+        };
+        let dest = match dest {
+            Some(d) => d,
+            None => self.gen_temp_var(to),
+        };
+        self.ir_output.add_instructions(IRInst::load_data(dest.clone(), operand));
+        Ok(Operand::Place(dest))
+    }
+
     fn visit_pattern(&mut self, pattern: &mut Pattern) -> Result<Operand, RccError> {
         unimplemented!()
     }
@@ -228,10 +681,25 @@ impl IRBuilder {
         // TODO path segmentation
         let ident = path_expr.segments.last().unwrap();
 
-        let cur_scope = self.scope_stack.cur_scope();
-        if let Some((var, scope_id)) = cur_scope.find_variable(ident) {
-            let ir_type = IRType::from_var_info(var)?;
-            let operand = Operand::Place(Place::variable(ident, scope_id, var.kind(), ir_type));
+        if let Some((var, scope_id)) = self.scope_stack.find_variable(ident) {
+            if let TypeInfo::Struct { name, .. } = self.types.get(var.type_info) {
+                return Err(format!("this expression of type `{}` is not supported", name).into());
+            }
+            let ir_type = IRType::from_var_info(var, &self.types)?;
+            let label = versioned_var(ident, var.version());
+            let place = Place::variable(label.as_str(), scope_id, var.kind(), ir_type);
+            if let Some(value) = self.consts.get(&place.label) {
+                return self.lit(value.clone(), dest, remain_temp);
+            }
+            let type_info = self.types.get(var.type_info).clone();
+            if let (TypeInfo::Array(..), Some(d)) = (&type_info, &dest) {
+                self.gen_array_copy(Operand::Place(place), &type_info, d.clone())?;
+                return Ok(Operand::Place(d.clone()));
+            }
+            if type_info.owns_heap() {
+                self.moved_variables.insert(place.label);
+            }
+            let operand = Operand::Place(place);
             if let Some(d) = dest {
                 if !d.is_temp() || remain_temp {
                     self.ir_output
@@ -239,8 +707,12 @@ impl IRBuilder {
                 }
             }
             Ok(operand)
-        } else if !cur_scope.find_fn(ident).is_unknown() {
-            Ok(Operand::FnLabel(ident.clone()))
+        } else if let Some(scope_id) = self.scope_stack.find_fn_scope(ident) {
+            let symbol = match self.nested_fns.get(&(scope_id, ident.clone())) {
+                Some(symbol) => symbol,
+                None => self.link_names.get(ident).unwrap_or(ident),
+            };
+            Ok(Operand::FnLabel(symbol.clone()))
         } else {
             Err("error in visit path expr: ident not found".into())
         }
@@ -270,23 +742,8 @@ impl IRBuilder {
         dest: Option<Place>,
         remain_temp: bool,
     ) -> Result<Operand, RccError> {
-        let t = lit_num_expr.get_lit_type();
-        let operand = match t {
-            TypeLitNum::I8 => Operand::I8(lit_num_expr.value.parse()?),
-            TypeLitNum::I16 => Operand::I16(lit_num_expr.value.parse()?),
-            TypeLitNum::I | TypeLitNum::I32 => Operand::I32(lit_num_expr.value.parse()?),
-            TypeLitNum::I64 => Operand::I64(lit_num_expr.value.parse()?),
-            TypeLitNum::I128 => Operand::I128(lit_num_expr.value.parse()?),
-            TypeLitNum::Isize => Operand::Isize(lit_num_expr.value.parse()?),
-            TypeLitNum::U8 => Operand::U8(lit_num_expr.value.parse()?),
-            TypeLitNum::U16 => Operand::U16(lit_num_expr.value.parse()?),
-            TypeLitNum::U32 => Operand::U32(lit_num_expr.value.parse()?),
-            TypeLitNum::U64 => Operand::U64(lit_num_expr.value.parse()?),
-            TypeLitNum::U128 => Operand::U128(lit_num_expr.value.parse()?),
-            TypeLitNum::Usize => Operand::Usize(lit_num_expr.value.parse()?),
-            TypeLitNum::F32 => Operand::F32(lit_num_expr.value.parse()?),
-            TypeLitNum::F | TypeLitNum::F64 => Operand::F64(lit_num_expr.value.parse()?),
-        };
+        let t = lit_num_expr.get_lit_type(&self.types);
+        let operand = lit_num_operand(&lit_num_expr.value, t)?;
         self.lit(operand, dest, remain_temp)
     }
 
@@ -326,18 +783,283 @@ impl IRBuilder {
     ) -> Result<Operand, RccError> {
         // let operand = self.visit_expr(&mut unary_expr.expr, d)?;
         match unary_expr.op {
-            UnOp::Neg => {
-                let temp_dest = self.gen_temp_var(unary_expr.expr.type_info());
+            UnOp::Neg | UnOp::Not => {
+                // `-128i8` is a literal, whose magnitude alone does not fit in `i8`
+                if let (UnOp::Neg, Expr::LitNum(lit_num_expr)) =
+                    (&unary_expr.op, unary_expr.expr.as_ref())
+                {
+                    let t = lit_num_expr.get_lit_type(&self.types);
+                    let operand = lit_num_operand(&format!("-{}", lit_num_expr.value), t)?;
+                    return self.lit(operand, dest, remain_temp);
+                }
+                let temp_dest = self.gen_temp_var(unary_expr.expr.type_info(&self.types));
                 let operand = self.visit_expr(&mut unary_expr.expr, Some(temp_dest), false)?;
-                let operand = match operand {
-                    Operand::I8(i) => Operand::I8(-i),
-                    Operand::I16(i) => Operand::I16(-i),
-                    Operand::I32(i) => Operand::I32(-i),
-                    _ => todo!(),
+                match ir::un_op_may_constant_fold(&unary_expr.op, &operand, Overflow::Error)? {
+                    Some(operand) => self.lit(operand, dest, remain_temp),
+                    None => match dest {
+                        Some(d) => {
+                            self.gen_un_op(&unary_expr.op, operand, d.clone());
+                            Ok(Operand::Place(d))
+                        }
+                        None => Ok(Operand::Unit),
+                    },
+                }
+            }
+            UnOp::Deref => {
+                let type_info = unary_expr.type_info(&self.types);
+                if let (TypeInfo::Array(..), Some(d)) = (&type_info, &dest) {
+                    let addr = self.visit_ptr_expr(&mut unary_expr.expr)?;
+                    self.gen_array_copy(addr, &type_info, d.clone())?;
+                    return Ok(Operand::Place(d.clone()));
+                }
+                if type_info.owns_heap() {
+                    // the value is moved out, the outer `Box` is not freed
+                    self.move_out_of(&unary_expr.expr);
+                }
+                let addr = self.visit_ptr_expr(&mut unary_expr.expr)?;
+                match dest {
+                    Some(d) => {
+                        self.ir_output.add_instructions(IRInst::load(d.clone(), addr));
+                        Ok(Operand::Place(d))
+                    }
+                    None => Ok(Operand::Unit),
+                }
+            }
+            UnOp::Borrow | UnOp::BorrowMut => {
+                let dest = match dest {
+                    Some(d) => d,
+                    None => return Ok(Operand::Unit),
                 };
-                self.lit(operand, dest, remain_temp)
+                match unary_expr.expr.as_mut() {
+                    // `&*p` is `p`
+                    Expr::Unary(inner) if inner.op == UnOp::Deref => {
+                        let addr = self.visit_ptr_expr(&mut inner.expr)?;
+                        self.ir_output
+                            .add_instructions(IRInst::load_data(dest.clone(), addr));
+                    }
+                    expr if is_behind_ptr(expr) => {
+                        let addr = self.visit_place_addr(expr)?;
+                        self.ir_output
+                            .add_instructions(IRInst::load_data(dest.clone(), addr));
+                    }
+                    // `&a` of an array is the address of its elements, which `a` holds
+                    expr if matches!(expr.type_info(&self.types), TypeInfo::Array(..)) => {
+                        let addr = self.visit_ptr_expr(expr)?;
+                        self.ir_output
+                            .add_instructions(IRInst::load_data(dest.clone(), addr));
+                    }
+                    expr if expr.type_info(&self.types).is_struct() => {
+                        let place = self.visit_borrowed_struct(expr)?;
+                        self.ir_output.add_instructions(IRInst::LoadAddr {
+                            dest: dest.clone(),
+                            symbol: Operand::Place(place),
+                        });
+                    }
+                    expr => {
+                        let place = self.visit_borrowed_expr(expr)?;
+                        self.ir_output.add_instructions(IRInst::LoadAddr {
+                            dest: dest.clone(),
+                            symbol: Operand::Place(place),
+                        });
+                    }
+                }
+                Ok(Operand::Place(dest))
+            }
+        }
+    }
+
+    /// Place of the borrowed `expr`, a value which is not a variable is stored in a temporary
+    /// variable first. Borrowing a variable does not move it.
+    fn visit_borrowed_expr(&mut self, expr: &mut Expr) -> Result<Place, RccError> {
+        if let Expr::Path(path_expr) = expr {
+            let ident = path_expr.segments.last().unwrap();
+            if let Some((var, scope_id)) = self.scope_stack.find_variable(ident) {
+                let ir_type = IRType::from_var_info(var, &self.types)?;
+                let label = versioned_var(ident, var.version());
+                return Ok(Place::variable(label.as_str(), scope_id, var.kind(), ir_type));
+            }
+        }
+        let temp = self.gen_temp_var(expr.type_info(&self.types));
+        match self.visit_expr(expr, Some(temp.clone()), false)? {
+            Operand::Place(p) if matches!(p.kind, VarKind::Local | VarKind::LocalMut) => Ok(p),
+            operand => {
+                self.ir_output
+                    .add_instructions(IRInst::load_data(temp.clone(), operand));
+                Ok(temp)
+            }
+        }
+    }
+
+    /// The first scalar field of the borrowed struct `expr`, whose fields are laid out like
+    /// the struct from its address. A value which is not a variable, or a field of one, is
+    /// stored in a temporary struct first.
+    fn visit_borrowed_struct(&mut self, expr: &mut Expr) -> Result<Place, RccError> {
+        let type_info = expr.type_info(&self.types);
+        let places = match expr {
+            Expr::Path(_) | Expr::FieldAccess(_) => self
+                .visit_struct_value(expr)?
+                .into_iter()
+                .map(|operand| match operand {
+                    Operand::Place(place) => place,
+                    o => unreachable!("field of a variable: {:?}", o),
+                })
+                .collect(),
+            Expr::Grouped(grouped_expr) => return self.visit_borrowed_struct(grouped_expr),
+            _ => {
+                let srcs = self.visit_struct_value(expr)?;
+                let temps = self.gen_temp_struct(type_info.clone());
+                self.copy_fields(temps.clone(), srcs, &type_info);
+                temps
+            }
+        };
+        match places.into_iter().next() {
+            Some(place) => Ok(place),
+            None => Err(format!("borrowing `{:?}` without fields is not supported", type_info).into()),
+        }
+    }
+
+    /// (data pointer, vtable pointer) of the trait object `expr`. `&T` is coerced into
+    /// `&dyn Trait` with the vtable of `impl Trait for T`, so is `&t as &dyn Trait`.
+    fn visit_dyn_expr(
+        &mut self,
+        expr: &mut Expr,
+        trait_name: &str,
+    ) -> Result<(Operand, Operand), RccError> {
+        let type_info = expr.type_info(&self.types);
+        if type_info.dyn_trait().is_none() {
+            let symbol = match &type_info {
+                TypeInfo::Ptr { type_info, .. } => self.types.vtable(trait_name, type_info),
+                _ => None,
+            };
+            let symbol = match symbol {
+                Some(symbol) => Symbol::intern(symbol),
+                None => unreachable!("no vtable of `{}` for `{:?}`", trait_name, type_info),
+            };
+            let data_place = self.gen_temp_var(type_info);
+            let data = self.visit_expr(expr, Some(data_place), false)?;
+            return Ok((data, Operand::Place(Place::lit_const(symbol, IRType::Addr))));
+        }
+        match expr {
+            Expr::Path(path_expr) => {
+                let ident = path_expr.segments.last().unwrap();
+                let kind = self.scope_stack.find_variable(ident).unwrap().0.kind();
+                let data = self.gen_variable(ident, kind);
+                let vtable = self.gen_variable(vtable_var(ident).as_str(), kind);
+                Ok((Operand::Place(data), Operand::Place(vtable)))
+            }
+            Expr::Grouped(grouped_expr) => self.visit_dyn_expr(grouped_expr, trait_name),
+            Expr::Cast(cast_expr) => self.visit_dyn_expr(&mut cast_expr.expr, trait_name),
+            _ => Err(format!("this expression of type `&dyn {}` is not supported", trait_name).into()),
+        }
+    }
+
+    /// (data pointer, length) of the slice or string `expr`. `&[T; N]` is coerced into `&[T]`
+    /// with the address of the elements of the array and the length `N`.
+    fn visit_slice_expr(&mut self, expr: &mut Expr) -> Result<(Operand, Operand), RccError> {
+        let type_info = expr.type_info(&self.types);
+        if let TypeInfo::Ptr { type_info: array, .. } = &type_info {
+            if let TypeInfo::Array(_, len) = array.as_ref() {
+                let ptr_place = self.gen_temp_var(type_info.clone());
+                let data = self.visit_expr(expr, Some(ptr_place), false)?;
+                return Ok((data, Operand::Usize(*len)));
+            }
+        }
+        match expr {
+            Expr::LitStr(s) => {
+                let len = Operand::Usize(s.len());
+                Ok((self.ir_output.add_ro_local_str(s.clone()), len))
+            }
+            Expr::Path(path_expr) => {
+                let ident = path_expr.segments.last().unwrap();
+                let kind = self.scope_stack.find_variable(ident).unwrap().0.kind();
+                let data = self.gen_variable(ident, kind);
+                let len = self.gen_variable(len_var(ident).as_str(), kind);
+                Ok((Operand::Place(data), Operand::Place(len)))
+            }
+            Expr::Grouped(grouped_expr) => self.visit_slice_expr(grouped_expr),
+            // `s.as_bytes()` is `s` retyped
+            Expr::Call(call_expr) if call_expr.method_name() == Some("as_bytes") => {
+                match call_expr.expr.as_mut() {
+                    Expr::FieldAccess(field_access_expr) => {
+                        self.visit_slice_expr(&mut field_access_expr.lhs)
+                    }
+                    _ => unreachable!(),
+                }
+            }
+            _ => Err(format!("this expression of type `{:?}` is not supported", type_info).into()),
+        }
+    }
+
+    /// The pair of (start, end) of a range. The start and the end of `a..b` are evaluated in
+    /// order, those of a range variable `r` are `r` and `r$end`.
+    fn visit_range_value(&mut self, expr: &mut Expr) -> Result<(Operand, Operand), RccError> {
+        match expr {
+            Expr::Range(range_expr) => {
+                let start = range_expr.lhs.as_mut().unwrap();
+                let start_place = self.gen_temp_var(start.type_info(&self.types));
+                let start = self.visit_expr(start, Some(start_place), false)?;
+                let end = range_expr.rhs.as_mut().unwrap();
+                let end_place = self.gen_temp_var(end.type_info(&self.types));
+                let end = self.visit_expr(end, Some(end_place), false)?;
+                Ok((start, end))
+            }
+            Expr::Path(path_expr) => {
+                let ident = path_expr.segments.last().unwrap();
+                let kind = self.scope_stack.find_variable(ident).unwrap().0.kind();
+                let start = self.gen_variable(ident, kind);
+                let end = self.gen_variable(end_var(ident).as_str(), kind);
+                Ok((Operand::Place(start), Operand::Place(end)))
+            }
+            Expr::Grouped(grouped_expr) => self.visit_range_value(grouped_expr),
+            _ => {
+                let type_info = expr.type_info(&self.types);
+                Err(format!("this expression of type `{:?}` is not supported", type_info).into())
+            }
+        }
+    }
+
+    /// Address held by the pointer `expr`. Dereferencing a `Box` variable does not move it.
+    fn visit_ptr_expr(&mut self, expr: &mut Expr) -> Result<Operand, RccError> {
+        match expr {
+            Expr::Path(path_expr) => {
+                let ident = path_expr.segments.last().unwrap();
+                if let Some((var, scope_id)) = self.scope_stack.find_variable(ident) {
+                    let ir_type = IRType::from_var_info(var, &self.types)?;
+                    let label = versioned_var(ident, var.version());
+                    let place = Place::variable(label.as_str(), scope_id, var.kind(), ir_type);
+                    return Ok(Operand::Place(place));
+                }
+            }
+            // `**b`, `*r` of an array is the address `r` of its elements
+            Expr::Unary(unary_expr) if unary_expr.op == UnOp::Deref => {
+                let addr = self.visit_ptr_expr(&mut unary_expr.expr)?;
+                if let TypeInfo::Array(..) = unary_expr.type_info(&self.types) {
+                    return Ok(addr);
+                }
+                let dest = self.gen_temp_var(unary_expr.type_info(&self.types));
+                self.ir_output.add_instructions(IRInst::load(dest.clone(), addr));
+                return Ok(Operand::Place(dest));
+            }
+            _ => {}
+        }
+        let addr = self.gen_temp_var(expr.type_info(&self.types));
+        self.visit_expr(expr, Some(addr), false)
+    }
+
+    /// Mark the variable `b` in `*b`, `**b`, ... as moved.
+    fn move_out_of(&mut self, expr: &Expr) {
+        match expr {
+            Expr::Path(path_expr) => {
+                let ident = path_expr.segments.last().unwrap();
+                if let Some((var, scope_id)) = self.scope_stack.find_variable(ident) {
+                    let label = versioned_var(ident, var.version());
+                    self.moved_variables.insert(local_var(label.as_str(), scope_id));
+                }
             }
-            _ => todo!(),
+            Expr::Unary(unary_expr) if unary_expr.op == UnOp::Deref => {
+                self.move_out_of(&unary_expr.expr)
+            }
+            _ => {}
         }
     }
 
@@ -347,12 +1069,9 @@ impl IRBuilder {
         dest: Option<Place>,
         remain_temp: bool,
     ) -> Result<Operand, RccError> {
-        self.scope_stack.enter_scope(block_expr);
-        for stmt in block_expr.stmts.iter_mut() {
-            self.visit_stmt(stmt)?;
-        }
-
-        let result = Ok(if let Some(expr) = &mut block_expr.last_expr {
+        let (outer_line, last_line) = self.visit_block_stmts(block_expr)?;
+        let result = if let Some(expr) = &mut block_expr.last_expr {
+            self.gen_loc(last_line);
             let is_none = dest.is_none();
             let res = self.visit_expr(&mut *expr, dest, remain_temp)?;
             if is_none && !res.is_unit_or_never() {
@@ -365,12 +1084,219 @@ impl IRBuilder {
             res
         } else {
             Operand::Unit
-        });
+        };
+        self.exit_block(outer_line, result != Operand::Never);
+        Ok(result)
+    }
+
+    /// The body of a function returning a struct, whose value is stored to the address
+    /// `sret`, see `SRET`.
+    ///
+    /// ```
+    /// $0 = ret$ + 4
+    /// *ret$ = x
+    /// *$0 = y
+    /// ret ()
+    /// ```
+    fn visit_struct_fn_block(
+        &mut self,
+        block_expr: &mut BlockExpr,
+        ret_info: &TypeInfo,
+        sret: Place,
+    ) -> Result<Operand, RccError> {
+        let (outer_line, last_line) = self.visit_block_stmts(block_expr)?;
+        let result = match &mut block_expr.last_expr {
+            Some(expr) if expr.type_info(&self.types) != TypeInfo::Never => {
+                self.gen_loc(last_line);
+                let srcs = self.visit_struct_value(expr)?;
+                self.gen_struct_ret(srcs, ret_info, sret);
+                Operand::Unit
+            }
+            Some(expr) => self.visit_expr(expr, None, false)?,
+            None => Operand::Unit,
+        };
+        self.exit_block(outer_line, result != Operand::Never);
+        Ok(result)
+    }
+
+    /// Store the fields of a returned struct to the address `sret`
+    fn gen_struct_ret(&mut self, srcs: Vec<Operand>, type_info: &TypeInfo, sret: Place) {
+        let fields = scalar_fields(type_info, &self.types);
+        for ((offset, field), src) in fields.into_iter().zip(srcs) {
+            let addr = self.gen_ptr_offset(Operand::Place(sret.clone()), offset, &field);
+            let ty = IRType::from_type_info(&field).unwrap();
+            self.ir_output.add_instructions(IRInst::store(ty, addr, src));
+        }
+    }
+
+    /// Copy a returned array to the address `ret$`, see `SRET`
+    fn gen_array_ret(&mut self, src: Operand, type_info: &TypeInfo) -> Result<(), RccError> {
+        let sret = self.fn_ret_temp_var.last().unwrap().clone();
+        let size = Operand::Usize(array_size(type_info)?);
+        self.gen_memcpy(Operand::Place(sret), src, size);
+        Ok(())
+    }
+
+    /// Enter the scope of the block and visit its statements, return the line of the code
+    /// before the block and the line of its last expression.
+    fn visit_block_stmts(
+        &mut self,
+        block_expr: &mut BlockExpr,
+    ) -> Result<(u32, Option<u32>), RccError> {
+        self.scope_stack.enter_scope(block_expr.scope_id);
+        // the functions declared in the block may be called before their declarations
+        for stmt in block_expr.stmts.iter() {
+            if let Stmt::Item(Item::Fn(item_fn)) = stmt {
+                let outer = &self.ir_output.cur_func_mut().name;
+                let symbol = if item_fn.no_mangle {
+                    item_fn.name.clone()
+                } else {
+                    nested_fn_name(outer, &item_fn.name, block_expr.scope_id)
+                };
+                self.nested_fns
+                    .insert((block_expr.scope_id, item_fn.name.clone()), symbol);
+            }
+        }
+        let lines = self.stmt_lines.get(&block_expr.node_id).cloned().unwrap_or_default();
+        let outer_line = self.cur_line;
+        for (i, stmt) in block_expr.stmts.iter_mut().enumerate() {
+            self.gen_loc(lines.get(i).copied());
+            self.visit_stmt(stmt)?;
+            self.scope_stack.cur_scope_mut().cur_stmt_id += 1;
+        }
+        Ok((outer_line, lines.get(block_expr.stmts.len()).copied()))
+    }
+
+    /// Drop the variables of the block unless its end is not reached, and exit its scope
+    fn exit_block(&mut self, outer_line: u32, reached: bool) {
+        if reached {
+            self.gen_drops();
+        }
+        self.cur_line = outer_line;
         self.scope_stack.exit_scope();
-        result
+    }
+
+    /// Mark the following instructions with `line` if debug info is on. A mark followed by no
+    /// instruction is replaced, 0 is an unknown line.
+    fn gen_loc(&mut self, line: Option<u32>) {
+        let line = match line {
+            Some(line) if line != 0 => line,
+            _ => return,
+        };
+        self.cur_line = line;
+        if !self.debug_info {
+            return;
+        }
+        let insts = &mut self.ir_output.cur_func_mut().insts;
+        match insts.back_mut() {
+            Some(IRInst::Loc(last)) => *last = line,
+            _ => insts.push_back(IRInst::Loc(line)),
+        }
+    }
+
+    /// Free the heap memory owned by the variables of current scope which are not moved.
+    fn gen_drops(&mut self) {
+        let scope = self.scope_stack.cur_scope();
+        let owners: Vec<(Place, TypeInfo)> = scope
+            .heap_owners(&self.types)
+            .into_iter()
+            .map(|(ident, var_info)| {
+                let label = versioned_var(ident, var_info.version()).as_str();
+                let place = Place::variable(label, scope.scope_id, var_info.kind(), IRType::Addr);
+                (place, self.types.get(var_info.type_info).clone())
+            })
+            .filter(|(place, _)| !self.moved_variables.contains(&place.label))
+            .collect();
+        for (place, type_info) in owners {
+            self.gen_drop(place, &type_info);
+        }
+    }
+
+    /// Free `Box<T>` after dropping `T`. The elements of `Vec<T>` and `String` do not own heap
+    /// memory, so the whole vector is freed at once.
+    ///
+    /// ```
+    /// $0 = *b
+    /// call @__rcc_free($0)
+    /// call @__rcc_free(b)
+    /// ```
+    fn gen_drop(&mut self, place: Place, type_info: &TypeInfo) {
+        let free = match type_info {
+            TypeInfo::Vec(_) | TypeInfo::String => VEC_FREE,
+            TypeInfo::Ptr {
+                kind: _,
+                type_info: pointee,
+            } => {
+                if pointee.owns_heap() {
+                    let inner = self.gen_temp_var(*pointee.clone());
+                    let addr = Operand::Place(place.clone());
+                    self.ir_output.add_instructions(IRInst::load(inner.clone(), addr));
+                    self.gen_drop(inner, pointee);
+                }
+                FREE
+            }
+            t => unreachable!("`{:?}` does not own heap memory", t),
+        };
+        self.ir_output.add_instructions(IRInst::call(
+            Operand::FnLabel(free.into()),
+            vec![Operand::Place(place)],
+        ));
     }
 
     fn visit_assign_expr(&mut self, assign_expr: &mut AssignExpr) -> Result<Operand, RccError> {
+        match &assign_expr.lhs {
+            LhsExpr::Deref(_) | LhsExpr::ArrayIndex(_) => {
+                return self.visit_indirect_assign_expr(assign_expr);
+            }
+            LhsExpr::FieldAccess(field_access_expr) if is_behind_ptr(&field_access_expr.lhs) => {
+                return self.visit_indirect_assign_expr(assign_expr);
+            }
+            _ => {}
+        }
+        let type_info = assign_expr.lhs.type_info(&self.types);
+        if type_info.is_struct() {
+            let srcs = self.visit_struct_value(&mut assign_expr.rhs)?;
+            let dests = match &mut assign_expr.lhs {
+                LhsExpr::Path(path_expr) => {
+                    self.struct_var_places(path_expr.segments.last().unwrap())
+                }
+                LhsExpr::FieldAccess(field_access_expr) => self
+                    .visit_field_fields(field_access_expr)?
+                    .into_iter()
+                    .map(|operand| match operand {
+                        Operand::Place(place) => place,
+                        o => unreachable!("field of a mutable place: {:?}", o),
+                    })
+                    .collect(),
+                _ => unreachable!(),
+            };
+            self.copy_fields(dests, srcs, &type_info);
+            return Ok(Operand::Unit);
+        }
+        if type_info.is_slice_ref() {
+            let (data, len) = self.visit_slice_expr(&mut assign_expr.rhs)?;
+            let ident = match &assign_expr.lhs {
+                LhsExpr::Path(path_expr) => path_expr.segments.last().unwrap(),
+                _ => unreachable!(),
+            };
+            let dest = self.gen_variable(ident, VarKind::LocalMut);
+            let len_dest = self.gen_variable(len_var(ident).as_str(), VarKind::LocalMut);
+            self.ir_output.add_instructions(IRInst::load_data(dest, data));
+            self.ir_output.add_instructions(IRInst::load_data(len_dest, len));
+            return Ok(Operand::Unit);
+        }
+        if let TypeInfo::Range { .. } = type_info {
+            let (start, end) = self.visit_range_value(&mut assign_expr.rhs)?;
+            let ident = match &assign_expr.lhs {
+                LhsExpr::Path(path_expr) => path_expr.segments.last().unwrap(),
+                _ => unreachable!(),
+            };
+            let dest = self.gen_variable(ident, VarKind::LocalMut);
+            let end_dest = self.gen_variable(end_var(ident).as_str(), VarKind::LocalMut);
+            self.ir_output.add_instructions(IRInst::load_data(dest, start));
+            self.ir_output.add_instructions(IRInst::load_data(end_dest, end));
+            return Ok(Operand::Unit);
+        }
         let operand = self.visit_lhs_expr(&mut assign_expr.lhs)?;
         let p = match operand {
             Operand::Place(p) => p,
@@ -379,17 +1305,19 @@ impl IRBuilder {
 
         macro_rules! add_inst {
             ($bin_op:path) => {{
-                let rhs_dest = self.gen_temp_var(assign_expr.lhs.type_info());
+                let rhs_dest = self.gen_temp_var(assign_expr.lhs.type_info(&self.types));
                 let rhs = self.visit_expr(&mut assign_expr.rhs, Some(rhs_dest), false)?;
-                self.ir_output.add_instructions(IRInst::bin_op(
-                    $bin_op,
-                    p.clone(),
-                    Operand::Place(p),
-                    rhs.clone(),
-                ))
+                self.bin_op(Operand::Place(p.clone()), rhs, $bin_op, p)?;
             }};
         }
         match assign_expr.assign_op {
+            // the new elements are computed in a new array before `a` is changed, e.g.
+            // `a = [a[1], a[0]]`
+            AssignOp::Eq if matches!(type_info, TypeInfo::Array(..)) => {
+                let rhs_dest = self.gen_temp_var(type_info.clone());
+                let rhs = self.visit_expr(&mut assign_expr.rhs, Some(rhs_dest), false)?;
+                self.ir_output.add_instructions(IRInst::load_data(p, rhs));
+            }
             AssignOp::Eq => {
                 let rhs = self.visit_expr(&mut assign_expr.rhs, Some(p.clone()), false)?;
             }
@@ -407,8 +1335,257 @@ impl IRBuilder {
         Ok(Operand::Unit)
     }
 
-    fn visit_range_expr(&mut self, range_expr: &mut RangeExpr) -> Result<Operand, RccError> {
-        unimplemented!()
+    /// `*p = rhs`, `v[i] += rhs`, ...
+    ///
+    /// ```
+    /// $0 = *p
+    /// $0 = $0 + rhs
+    /// *p = $0
+    /// ```
+    fn visit_indirect_assign_expr(
+        &mut self,
+        assign_expr: &mut AssignExpr,
+    ) -> Result<Operand, RccError> {
+        let type_info = assign_expr.lhs.type_info(&self.types);
+        let addr = match &mut assign_expr.lhs {
+            LhsExpr::Deref(expr) => self.visit_ptr_expr(expr)?,
+            LhsExpr::ArrayIndex(array_index_expr) => self.gen_elem_addr(array_index_expr)?,
+            LhsExpr::FieldAccess(field_access_expr) => self.visit_field_addr(field_access_expr)?,
+            _ => unreachable!(),
+        };
+        // `*r = S { .. }` stores the fields one by one
+        if type_info.is_struct() {
+            let srcs = self.visit_struct_value(&mut assign_expr.rhs)?;
+            for ((offset, field), src) in scalar_fields(&type_info, &self.types).into_iter().zip(srcs) {
+                let field_addr = self.gen_ptr_offset(addr.clone(), offset, &field);
+                let ty = IRType::from_type_info(&field)?;
+                self.ir_output.add_instructions(IRInst::store(ty, field_addr, src));
+            }
+            return Ok(Operand::Unit);
+        }
+        let rhs_dest = self.gen_temp_var(type_info.clone());
+        let rhs = self.visit_expr(&mut assign_expr.rhs, Some(rhs_dest), false)?;
+        // `*r = [x, y]` copies the elements to the array `r` points to
+        if let TypeInfo::Array(..) = type_info {
+            let size = Operand::Usize(array_size(&type_info)?);
+            self.gen_memcpy(addr, rhs, size);
+            return Ok(Operand::Unit);
+        }
+        let ty = IRType::from_type_info(&type_info)?;
+        let op = match assign_expr.assign_op {
+            AssignOp::Eq => {
+                self.ir_output.add_instructions(IRInst::store(ty, addr, rhs));
+                return Ok(Operand::Unit);
+            }
+            AssignOp::ShrEq => BinOperator::Shr,
+            AssignOp::ShlEq => BinOperator::Shl,
+            AssignOp::PlusEq => BinOperator::Plus,
+            AssignOp::MinusEq => BinOperator::Minus,
+            AssignOp::StarEq => BinOperator::Star,
+            AssignOp::SlashEq => BinOperator::Slash,
+            AssignOp::PercentEq => BinOperator::Percent,
+            AssignOp::AndEq => BinOperator::And,
+            AssignOp::OrEq => BinOperator::Or,
+            AssignOp::CaretEq => BinOperator::Caret,
+        };
+        let value = self.gen_temp_var(type_info);
+        self.ir_output.add_instructions(IRInst::load(value.clone(), addr.clone()));
+        let src = self.bin_op(Operand::Place(value.clone()), rhs, op, value)?;
+        self.ir_output.add_instructions(IRInst::store(ty, addr, src));
+        Ok(Operand::Unit)
+    }
+
+    /// Call `__rcc_panic` unless `src1 cond src2`.
+    ///
+    /// ```
+    /// (n)   if src1 cond src2 goto (n+2)
+    /// (n+1) call __rcc_panic("panicked at '<msg>', line <line> in `<fn>`\n")
+    /// (n+2) ...
+    /// ```
+    fn gen_check(&mut self, cond: Jump, src1: Operand, src2: Operand, msg: &str) {
+        let msg = self.panic_msg(msg, self.cur_line);
+        let label = self.ir_output.next_inst_id() + 2;
+        self.ir_output
+            .add_instructions(IRInst::jump_if_cond(cond, src1, src2, label));
+        self.gen_panic(msg);
+    }
+
+    /// The message of a panic at `line` of the current function, without the line if it is
+    /// unknown
+    fn panic_msg(&mut self, msg: &str, line: u32) -> String {
+        let fn_name = &self.ir_output.cur_func_mut().name;
+        if line == 0 {
+            format!("panicked at '{}' in `{}`\n", msg, fn_name)
+        } else {
+            format!("panicked at '{}', line {} in `{}`\n", msg, line, fn_name)
+        }
+    }
+
+    /// `call __rcc_panic(msg)`, which writes `msg` to stderr and exits with 101
+    fn gen_panic(&mut self, msg: String) {
+        let msg = self.ir_output.add_ro_local_str(msg);
+        self.ir_output
+            .add_instructions(IRInst::call(Operand::FnLabel(PANIC.to_string()), vec![msg]));
+    }
+
+    /// `-a` and `!a` are lowered to binary operations:
+    ///
+    /// ```
+    /// -a (integer)  dest = 0 - a        after a != MIN is checked with overflow checks
+    /// -a (float)    dest = -0.0 - a     so that -(0.0) is -0.0
+    /// !a (bool)     dest = a ^ true
+    /// !a (integer)  dest = a ^ -1
+    /// ```
+    fn gen_un_op(&mut self, op: &UnOp, a: Operand, dest: Place) {
+        let ir_type = operand_type(&a);
+        let (bin_op, lhs, rhs) = match (op, ir_type) {
+            (UnOp::Neg, IRType::F32) => (BinOperator::Minus, Operand::F32(-0.0), a),
+            (UnOp::Neg, IRType::F64) => (BinOperator::Minus, Operand::F64(-0.0), a),
+            (UnOp::Neg, _) => {
+                if self.overflow_checks {
+                    let bits = ir_type.byte_size(isize::BITS) * 8;
+                    let min = Operand::int(ir_type, 1 << (bits - 1));
+                    self.gen_check(JNe, a.clone(), min, "attempt to negate with overflow");
+                }
+                (BinOperator::Minus, Operand::zero(ir_type), a)
+            }
+            (UnOp::Not, IRType::Bool) => (BinOperator::Caret, a, Operand::Bool(true)),
+            (UnOp::Not, _) => (BinOperator::Caret, a, Operand::int(ir_type, -1)),
+            (op, _) => unreachable!("`{:?}` is not lowered to a binary operation", op),
+        };
+        self.ir_output
+            .add_instructions(IRInst::bin_op(bin_op, dest, lhs, rhs));
+    }
+
+    /// RISC-V does not trap on division by zero.
+    fn gen_div_check(
+        &mut self,
+        op: &BinOperator,
+        dividend: &Operand,
+        divisor: &Operand,
+    ) -> Result<(), RccError> {
+        if !matches!(op, BinOperator::Slash | BinOperator::Percent) {
+            return Ok(());
+        }
+        // `a / 0` always panics, so it is an error like in rustc
+        if divisor.is_zero() {
+            return Err(ir::div_by_zero_error(op, dividend));
+        }
+        if !self.checks {
+            return Ok(());
+        }
+        if let Operand::Place(p) = divisor {
+            let zero = Operand::zero(p.ir_type);
+            let msg = if op == &BinOperator::Slash {
+                "attempt to divide by zero"
+            } else {
+                "attempt to calculate the remainder with a divisor of zero"
+            };
+            self.gen_check(JNe, divisor.clone(), zero, msg);
+        }
+        Ok(())
+    }
+
+    /// Call `__rcc_panic` unless `src1 cond src2` of `if_negative` or `if_not_negative`,
+    /// chosen by the sign of `b`.
+    ///
+    /// ```
+    /// (n)   if b >= 0 goto (n+3)
+    /// (n+1) if <if_negative> goto (n+5)
+    /// (n+2) goto (n+4)
+    /// (n+3) if <if_not_negative> goto (n+5)
+    /// (n+4) call __rcc_panic("panicked at '<msg>', line <line> in `<fn>`\n")
+    /// (n+5) ...
+    /// ```
+    fn gen_sign_check(
+        &mut self,
+        b: Operand,
+        if_negative: (Jump, Operand, Operand),
+        if_not_negative: (Jump, Operand, Operand),
+        msg: &str,
+    ) {
+        let n = self.ir_output.next_inst_id();
+        let zero = Operand::zero(operand_type(&b));
+        self.ir_output
+            .add_instructions(IRInst::jump_if_cond(JGe, b, zero, n + 3));
+        let (cond, src1, src2) = if_negative;
+        self.ir_output
+            .add_instructions(IRInst::jump_if_cond(cond, src1, src2, n + 5));
+        self.ir_output.add_instructions(IRInst::jump(n + 4));
+        let (cond, src1, src2) = if_not_negative;
+        self.gen_check(cond, src1, src2, msg);
+    }
+
+    /// `dest = a op b` which panics on overflow, for `+`, `-` and `*` of integers. The
+    /// result is computed in a temp and checked before it is stored in `dest`, which may be
+    /// `a` or `b`.
+    ///
+    /// ```
+    /// t = a + b               t = a - b               t = a * b
+    /// unsigned: t >= a        unsigned: a >= b        a == 0, or
+    /// signed:                 signed:                 b != MIN if a == -1, and
+    ///   b < 0: t < a            b < 0: a < t            t / a == b
+    ///   b >= 0: t >= a          b >= 0: a >= t
+    /// dest = t
+    /// ```
+    fn gen_checked_op(&mut self, op: BinOperator, a: Operand, b: Operand, dest: Place) {
+        let ir_type = dest.ir_type;
+        let signed = ir_type.is_signed_int();
+        let msg = ir::overflow_msg(&op);
+        let t = self.gen_temp_var(int_type_info(ir_type));
+        let result = Operand::Place(t.clone());
+        if op == BinOperator::Minus && !signed {
+            self.gen_check(JGe, a.clone(), b.clone(), &msg);
+        }
+        self.ir_output
+            .add_instructions(IRInst::bin_op(op, t, a.clone(), b.clone()));
+        match op {
+            BinOperator::Plus if signed => self.gen_sign_check(
+                b,
+                (JLt, result.clone(), a.clone()),
+                (JGe, result.clone(), a),
+                &msg,
+            ),
+            BinOperator::Plus => self.gen_check(JGe, result.clone(), a, &msg),
+            BinOperator::Minus if signed => self.gen_sign_check(
+                b,
+                (JLt, a.clone(), result.clone()),
+                (JGe, a, result.clone()),
+                &msg,
+            ),
+            BinOperator::Star => {
+                // (n)   if a == 0 goto (n+6)
+                // (n+1) if a != -1 goto (n+3)      signed only
+                // (n+2) if b == MIN goto (n+5)     signed only
+                // (n+3) q = t / a
+                // (n+4) if q == b goto (n+6)
+                // (n+5) call __rcc_panic(...)
+                let n = self.ir_output.next_inst_id();
+                let end = if signed { n + 6 } else { n + 4 };
+                let zero = Operand::zero(ir_type);
+                self.ir_output
+                    .add_instructions(IRInst::jump_if_cond(JEq, a.clone(), zero, end));
+                if signed {
+                    let bits = ir_type.byte_size(isize::BITS) * 8;
+                    let min = Operand::int(ir_type, 1 << (bits - 1));
+                    let minus_one = Operand::int(ir_type, -1);
+                    self.ir_output
+                        .add_instructions(IRInst::jump_if_cond(JNe, a.clone(), minus_one, n + 3));
+                    self.ir_output
+                        .add_instructions(IRInst::jump_if_cond(JEq, b.clone(), min, n + 5));
+                }
+                let q = self.gen_temp_var(int_type_info(ir_type));
+                self.ir_output.add_instructions(IRInst::bin_op(
+                    BinOperator::Slash,
+                    q.clone(),
+                    result.clone(),
+                    a,
+                ));
+                self.gen_check(JEq, Operand::Place(q), b, &msg);
+            }
+            _ => {}
+        }
+        self.ir_output.add_instructions(IRInst::load_data(dest, result));
     }
 
     fn bin_op(
@@ -418,8 +1595,16 @@ impl IRBuilder {
         op: BinOperator,
         dest: Place,
     ) -> Result<Operand, RccError> {
-        self.ir_output
-            .add_instructions(IRInst::bin_op(op, dest.clone(), lhs, rhs));
+        self.gen_div_check(&op, &lhs, &rhs)?;
+        if self.overflow_checks
+            && matches!(op, BinOperator::Plus | BinOperator::Minus | BinOperator::Star)
+            && dest.ir_type.is_int()
+        {
+            self.gen_checked_op(op, lhs, rhs, dest.clone());
+        } else {
+            self.ir_output
+                .add_instructions(IRInst::bin_op(op, dest.clone(), lhs, rhs));
+        }
         Ok(Operand::Place(dest))
     }
 
@@ -428,14 +1613,24 @@ impl IRBuilder {
         bin_op_expr: &mut BinOpExpr,
         dest: Option<Place>,
     ) -> Result<Operand, RccError> {
-        let d = self.gen_temp_var(bin_op_expr.lhs.type_info());
+        if matches!(bin_op_expr.bin_op, BinOperator::AndAnd | BinOperator::OrOr) {
+            return self.visit_logic_bin_expr(bin_op_expr, dest);
+        }
+        if bin_op_expr.op_fn.is_some() {
+            if bin_op_expr.type_info(&self.types).is_struct() {
+                self.visit_struct_op(bin_op_expr)?;
+                return Ok(Operand::Unit);
+            }
+            return self.overloaded_op(bin_op_expr, None, dest);
+        }
+        let d = self.try_gen_temp_var(bin_op_expr.lhs.type_info(&self.types))?;
         let lhs = self.visit_expr(&mut bin_op_expr.lhs, Some(d), false)?;
-        let d = self.gen_temp_var(bin_op_expr.rhs.type_info());
+        let d = self.try_gen_temp_var(bin_op_expr.rhs.type_info(&self.types))?;
         let rhs = self.visit_expr(&mut bin_op_expr.rhs, Some(d), false)?;
 
-        // TODO operator override
-
-        let fold_option = ir::bin_op_may_constant_fold(&bin_op_expr.bin_op, &lhs, &rhs)?;
+        // `i32::MAX + 1` is an error like in rustc, whether overflow is checked or not
+        let fold_option =
+            ir::bin_op_may_constant_fold(&bin_op_expr.bin_op, &lhs, &rhs, Overflow::Error)?;
 
         match dest {
             Some(d) => match fold_option {
@@ -446,123 +1641,1087 @@ impl IRBuilder {
         }
     }
 
-    /// ## Example1
+    /// `a != b` with `impl PartialEq for T`, the operands are passed like the arguments of a
+    /// call, so a struct operand is passed as its fields and `&a` as its address. The address
+    /// of a returned struct `sret` is passed before them.
     ///
-    /// let a = A() && B() || C() || D();
+    /// ```
+    /// call @T$PartialEq$eq(a, b)
+    /// dest = ret
+    /// dest = dest ^ true
+    /// ```
+    fn overloaded_op(
+        &mut self,
+        bin_op_expr: &mut BinOpExpr,
+        sret: Option<Operand>,
+        dest: Option<Place>,
+    ) -> Result<Operand, RccError> {
+        let symbol = bin_op_expr.op_fn.clone().unwrap();
+        let mut args: Vec<Operand> = sret.into_iter().collect();
+        for operand in [&mut bin_op_expr.lhs, &mut bin_op_expr.rhs] {
+            args.extend(self.visit_call_params(std::slice::from_mut(&mut **operand), &[])?);
+        }
+        self.ir_output
+            .add_instructions(IRInst::call(Operand::FnLabel(symbol), args));
+        match dest {
+            Some(d) => {
+                self.ir_output
+                    .add_instructions(IRInst::load_data(d.clone(), Operand::FnRetPlace(d.ir_type)));
+                if bin_op_expr.bin_op == BinOperator::Ne {
+                    self.ir_output.add_instructions(IRInst::bin_op(
+                        BinOperator::Caret,
+                        d.clone(),
+                        Operand::Place(d.clone()),
+                        Operand::Bool(true),
+                    ));
+                }
+                Ok(Operand::Place(d))
+            }
+            None => Ok(Operand::Unit),
+        }
+    }
+
+    /// `a + b` returning a struct, see `visit_struct_call`
+    fn visit_struct_op(&mut self, bin_op_expr: &mut BinOpExpr) -> Result<Vec<Operand>, RccError> {
+        let (temps, sret) = self.gen_sret_temps(bin_op_expr.type_info(&self.types));
+        self.overloaded_op(bin_op_expr, Some(sret), None)?;
+        Ok(temps.into_iter().map(Operand::Place).collect())
+    }
+
+    /// `A() && B()` calls `B()` only if `A()` is true, so it is lowered by the jumps of a
+    /// condition:
     ///
-    /// <=>
-    /// (1) a_0 = A()
-    /// (2) if not a_0 goto (6)
-    /// (3) a_0 = B()
-    /// (4) if not a_0 goto (6)
-    /// (5) goto ()
-    /// (6) a_0 = C()
-    /// (7) if a_0 goto ()
-    /// (8) a_0 = D()
-    /// (9) if a_0 goto ()
-    /// a_0 = C()
-    /// if a_0 goto LABEL
-    /// a_0 = D()
-    /// if a_0 goto LABEL
-    /// LABEL:
-    /// ...
+    /// ```
+    /// (1) if not A() goto (5)
+    /// (2) if not B() goto (5)
+    /// (3) dest = true
+    /// (4) goto (6)
+    /// (5) dest = false
+    /// (6) ...
+    /// ```
+    fn visit_logic_bin_expr(
+        &mut self,
+        bin_op_expr: &mut BinOpExpr,
+        dest: Option<Place>,
+    ) -> Result<Operand, RccError> {
+        let mut miss_link = 0;
+        self.gen_logic_cond_jump(bin_op_expr, false, &mut miss_link)?;
+        let d = match dest {
+            Some(d) => d,
+            None => {
+                let next_id = self.ir_output.next_inst_id();
+                self.back_patch(miss_link, next_id);
+                return Ok(Operand::Unit);
+            }
+        };
+        self.ir_output.add_instructions(IRInst::load_data(d.clone(), Operand::Bool(true)));
+        if miss_link != 0 {
+            let jump_id = self.ir_output.next_inst_id();
+            self.ir_output.add_instructions(IRInst::jump(0));
+            let miss_id = self.ir_output.next_inst_id();
+            self.back_patch(miss_link, miss_id);
+            self.ir_output.add_instructions(IRInst::load_data(d.clone(), Operand::Bool(false)));
+            let next_id = self.ir_output.next_inst_id();
+            self.back_patch(jump_id, next_id);
+        }
+        Ok(Operand::Place(d))
+    }
+
+    /// `[a, b]` and `[a; N]` take a new array `$0` of `N * size` bytes in the stack frame for
+    /// `N` elements of `size` bytes. The elements of `[a; N]` are stored by a loop.
     ///
-    /// ## Example2
+    /// ```
+    /// dest = &$0                            dest = &$0
+    /// *dest = a                             $1 = 0
+    /// $1 = dest + size                      if $1 >= N * size goto (end)
+    /// *$1 = b                               $2 = dest + $1
+    ///                                       *$2 = a
+    ///                                       $1 = $1 + size
+    ///                                       goto (if)
+    /// ```
+    fn visit_array_expr(
+        &mut self,
+        array_expr: &mut ArrayExpr,
+        dest: Option<Place>,
+    ) -> Result<Operand, RccError> {
+        let dest = match dest {
+            Some(d) => d,
+            None => {
+                for elem in array_expr.elems.iter_mut() {
+                    self.visit_expr(elem, None, false)?;
+                }
+                return Ok(Operand::Unit);
+            }
+        };
+        let array_type = array_expr.type_info(&self.types);
+        let (elem_type, len) = match &array_type {
+            TypeInfo::Array(elem_type, len) => (elem_type.as_ref().clone(), *len),
+            t => unreachable!("type of array: {:?}", t),
+        };
+        // the elements share a type slot, literals take the type of the array, e.g. `u8` in
+        // `let a: [u8; 2] = [1, 2];`
+        let elem_slot = array_expr.elems[0].type_slot(&mut self.types);
+        self.types.set(elem_slot, elem_type.clone());
+
+        let size = elem_size(&elem_type)?;
+        self.gen_array_slot(&array_type, dest.clone())?;
+        if array_expr.len_expr.is_none() {
+            for (i, elem) in array_expr.elems.iter_mut().enumerate() {
+                let value = self.gen_temp_var(elem_type.clone());
+                let src = self.visit_expr(elem, Some(value), false)?;
+                let addr = self.gen_offset(&dest, Operand::Usize(i * size), &elem_type);
+                let ty = IRType::from_type_info(&elem_type)?;
+                self.ir_output.add_instructions(IRInst::store(ty, addr, src));
+            }
+            return Ok(Operand::Place(dest));
+        }
+        let value = self.gen_temp_var(elem_type.clone());
+        let src = self.visit_expr(&mut array_expr.elems[0], Some(value), false)?;
+        let offset = self.gen_temp_var(TypeInfo::LitNum(TypeLitNum::Usize));
+        self.ir_output
+            .add_instructions(IRInst::load_data(offset.clone(), Operand::Usize(0)));
+        let cond_id = self.ir_output.next_inst_id();
+        self.ir_output.add_instructions(IRInst::jump_if_cond(
+            JGe,
+            Operand::Place(offset.clone()),
+            Operand::Usize(len * size),
+            cond_id + 5,
+        ));
+        let addr = self.gen_offset(&dest, Operand::Place(offset.clone()), &elem_type);
+        let ty = IRType::from_type_info(&elem_type)?;
+        self.ir_output.add_instructions(IRInst::store(ty, addr, src));
+        self.ir_output.add_instructions(IRInst::bin_op(
+            BinOperator::Plus,
+            offset.clone(),
+            Operand::Place(offset),
+            Operand::Usize(size),
+        ));
+        self.ir_output.add_instructions(IRInst::jump(cond_id));
+        Ok(Operand::Place(dest))
+    }
+
+    /// `dest = &$0` of a new array `$0` of `type_info` in the stack frame, return its size
+    fn gen_array_slot(&mut self, type_info: &TypeInfo, dest: Place) -> Result<usize, RccError> {
+        let size = array_size(type_info)?;
+        if size > u16::MAX as usize {
+            let msg = format!("arrays of more than {} bytes are not supported", u16::MAX);
+            return Err(msg.into());
+        }
+        let slot = self.types.new_slot(type_info.clone());
+        let label = self.scope_stack.cur_scope_mut().gen_temp_variable(slot);
+        self.ir_output.add_instructions(IRInst::LoadAddr {
+            dest,
+            symbol: Operand::Place(Place::local(label, IRType::Array(size as u16))),
+        });
+        Ok(size)
+    }
+
+    /// Address `offset` bytes after `base`, of an element of `elem_type`
+    fn gen_offset(&mut self, base: &Place, offset: Operand, elem_type: &TypeInfo) -> Operand {
+        if offset.is_zero() {
+            return Operand::Place(base.clone());
+        }
+        let addr = self.gen_temp_var(TypeInfo::Ptr {
+            kind: PtrKind::MutRawPtr,
+            type_info: Box::new(elem_type.clone()),
+        });
+        self.ir_output.add_instructions(IRInst::bin_op(
+            BinOperator::Plus,
+            addr.clone(),
+            Operand::Place(base.clone()),
+            offset,
+        ));
+        Operand::Place(addr)
+    }
+
+    /// Arrays are copied to new arrays in the stack frame when they are used by value, e.g.
+    /// `let b = a;` and `f(a)`, which passes the address of the copy.
     ///
-    /// if A() && B() || C() && (D() || E()) {
-    ///     ...
-    /// }
+    /// ```
+    /// dest = &$0
+    /// call @__rcc_memcpy(dest, src, size)
+    /// ```
+    fn gen_array_copy(
+        &mut self,
+        src: Operand,
+        type_info: &TypeInfo,
+        dest: Place,
+    ) -> Result<(), RccError> {
+        let size = self.gen_array_slot(type_info, dest.clone())?;
+        self.gen_memcpy(Operand::Place(dest), src, Operand::Usize(size));
+        Ok(())
+    }
+
+    /// `call @__rcc_memcpy(dest, src, size)`
+    fn gen_memcpy(&mut self, dest: Operand, src: Operand, size: Operand) {
+        self.ir_output.add_instructions(IRInst::call(
+            Operand::FnLabel(MEMCPY.into()),
+            vec![dest, src, size],
+        ));
+    }
+
+    fn visit_array_index_expr(
+        &mut self,
+        array_index_expr: &mut ArrayIndexExpr,
+        dest: Option<Place>,
+    ) -> Result<Operand, RccError> {
+        let addr = self.gen_elem_addr(array_index_expr)?;
+        match dest {
+            Some(d) => {
+                self.ir_output.add_instructions(IRInst::load(d.clone(), addr));
+                Ok(Operand::Place(d))
+            }
+            None => Ok(Operand::Unit),
+        }
+    }
+
+    /// Address of `v[i]`, the runtime panics if `i` is out of bounds.
     ///
-    /// <=>
+    /// ```
+    /// call @__rcc_vec_index(v, i, size of element)
+    /// $0 = ret
+    /// ```
+    fn gen_elem_addr(&mut self, array_index_expr: &mut ArrayIndexExpr) -> Result<Operand, RccError> {
+        let elem_type = array_index_expr.type_info(&self.types);
+        let elem_size = elem_size(&elem_type)?;
+        match array_index_expr.expr.type_info(&self.types) {
+            TypeInfo::Array(_, len) => {
+                let array = self.visit_ptr_expr(&mut array_index_expr.expr)?;
+                return self.gen_array_elem_addr(array_index_expr, array, Operand::Usize(len));
+            }
+            TypeInfo::Ptr { type_info, .. } => match *type_info {
+                TypeInfo::Array(_, len) => {
+                    let array = self.visit_ptr_expr(&mut array_index_expr.expr)?;
+                    return self.gen_array_elem_addr(array_index_expr, array, Operand::Usize(len));
+                }
+                TypeInfo::Slice(_) => {
+                    let (data, len) = self.visit_slice_expr(&mut array_index_expr.expr)?;
+                    return self.gen_array_elem_addr(array_index_expr, data, len);
+                }
+                _ => {}
+            },
+            _ => {}
+        }
+        let vec = self.visit_ptr_expr(&mut array_index_expr.expr)?;
+        let index_place = self.gen_temp_var(array_index_expr.index_expr.type_info(&self.types));
+        let index = self.visit_expr(&mut array_index_expr.index_expr, Some(index_place), false)?;
+        self.ir_output.add_instructions(IRInst::call(
+            Operand::FnLabel(VEC_INDEX.into()),
+            vec![vec, index, Operand::Usize(elem_size)],
+        ));
+        let addr = self.gen_elem_ptr(&elem_type);
+        Ok(Operand::Place(addr))
+    }
+
+    /// Address of `a[i]` of the array or slice `a` of `len` elements. The check is lowered
+    /// or deleted by the `bounds-checks` pass, see `bounds_checks`.
     ///
-    /// (1) if not A() goto (4)
-    /// (2) if not B() goto (4)
-    /// (3) goto (7)
-    /// (4) if not C() goto ()
-    /// (5) if D() goto (7)
-    /// (6) if E() goto (7)
-    /// (7) ... // do something
-    /// (8) ...
-    fn visit_logic_bin_expr(
+    /// ```
+    /// check i < len else panic("... index out of bounds ...")
+    /// $0 = i * size
+    /// $1 = a + $0
+    /// ```
+    fn gen_array_elem_addr(
         &mut self,
-        bin_op_expr: &mut BinOpExpr,
-        dest: Place,
+        array_index_expr: &mut ArrayIndexExpr,
+        array: Operand,
+        len: Operand,
+    ) -> Result<Operand, RccError> {
+        let elem_type = array_index_expr.type_info(&self.types);
+        let size = elem_size(&elem_type)?;
+        let index_place = self.gen_temp_var(array_index_expr.index_expr.type_info(&self.types));
+        let index = self.visit_expr(&mut array_index_expr.index_expr, Some(index_place), false)?;
+        if bounds_checks(self.optimize_level, self.checks) {
+            let msg = match &len {
+                Operand::Usize(n) => format!("index out of bounds: the len is {}", n),
+                _ => "index out of bounds".to_string(),
+            };
+            let msg = self.panic_msg(&msg, self.cur_line);
+            let msg = self.ir_output.add_ro_local_str(msg);
+            self.ir_output.add_instructions(IRInst::BoundsCheck {
+                index: index.clone(),
+                len,
+                msg,
+            });
+        }
+        let offset = match index {
+            Operand::Usize(i) => Operand::Usize(i * size),
+            index if size == 1 => index,
+            index => {
+                let offset = self.gen_temp_var(TypeInfo::LitNum(TypeLitNum::Usize));
+                self.ir_output.add_instructions(IRInst::bin_op(
+                    BinOperator::Star,
+                    offset.clone(),
+                    index,
+                    Operand::Usize(size),
+                ));
+                Operand::Place(offset)
+            }
+        };
+        let base = match array {
+            Operand::Place(base) => base,
+            o => unreachable!("array `{}` is not in a variable", o),
+        };
+        Ok(self.gen_offset(&base, offset, &elem_type))
+    }
+
+    /// `$0 = ret`, where `ret` is the address of an element
+    fn gen_elem_ptr(&mut self, elem_type: &TypeInfo) -> Place {
+        let ptr = self.gen_temp_var(TypeInfo::Ptr {
+            kind: PtrKind::MutRawPtr,
+            type_info: Box::new(elem_type.clone()),
+        });
+        self.ir_output.add_instructions(IRInst::load_data(
+            ptr.clone(),
+            Operand::FnRetPlace(IRType::Addr),
+        ));
+        ptr
+    }
+
+    /// Operands of the scalar fields of `(a, b)`, the elements are evaluated into temporary
+    /// variables in order.
+    fn visit_tuple_expr(&mut self, tuple_expr: &mut TupleExpr) -> Result<Vec<Operand>, RccError> {
+        let mut operands = vec![];
+        for e in tuple_expr.elems.iter_mut() {
+            operands.extend(self.visit_field_value(e)?);
+        }
+        Ok(operands)
+    }
+
+    fn visit_tuple_index_expr(
+        &mut self,
+        tuple_index_expr: &mut TupleIndexExpr,
+    ) -> Result<Operand, RccError> {
+        unimplemented!()
+    }
+
+    /// Places of the scalar fields of the struct variable `ident`, see `scalar_fields`.
+    fn struct_var_places(&self, ident: &str) -> Vec<Place> {
+        let (var, scope_id) = self.scope_stack.find_variable(ident).unwrap();
+        let type_info = self.types.get(var.type_info);
+        let ident = versioned_var(ident, var.version());
+        scalar_fields(type_info, &self.types)
+            .into_iter()
+            .map(|(offset, field)| {
+                let ir_type = IRType::from_type_info(&field).unwrap();
+                let label = field_var(ident.as_str(), offset).as_str();
+                Place::variable(label, scope_id, var.kind(), ir_type)
+            })
+            .collect()
+    }
+
+    /// Copy a struct value of `type_info`. A large struct which is a field of a variable, or
+    /// a variable, is copied by a `Memcpy`. The fields of other values, e.g. struct
+    /// expressions, are copied one by one.
+    fn copy_fields(&mut self, dests: Vec<Place>, srcs: Vec<Operand>, type_info: &TypeInfo) {
+        debug_assert_eq!(dests.len(), srcs.len());
+        let size = layout(type_info, &self.types).0;
+        let is_var_field = |src: &Operand| match src {
+            Operand::Place(place) => {
+                matches!(place.kind, VarKind::Local | VarKind::LocalMut)
+                    && split_field_var(place.label.as_str()).is_some()
+            }
+            _ => false,
+        };
+        if size >= MEMCPY_MIN_SIZE && srcs.iter().all(is_var_field) {
+            self.ir_output.add_instructions(IRInst::Memcpy { size, srcs, dests });
+            return;
+        }
+        for (dest, src) in dests.into_iter().zip(srcs) {
+            self.ir_output.add_instructions(IRInst::load_data(dest, src));
+        }
+    }
+
+    /// Operands of the scalar fields of the struct or tuple value `expr`. The fields of a
+    /// variable are its places.
+    fn visit_struct_value(&mut self, expr: &mut Expr) -> Result<Vec<Operand>, RccError> {
+        match expr {
+            Expr::Path(path_expr) => Ok(self
+                .struct_var_places(path_expr.segments.last().unwrap())
+                .into_iter()
+                .map(|place| match self.consts.get(&place.label) {
+                    Some(value) => value.clone(),
+                    None => Operand::Place(place),
+                })
+                .collect()),
+            Expr::Grouped(grouped_expr) => self.visit_struct_value(grouped_expr),
+            Expr::Struct(struct_expr) => self.visit_struct_expr(struct_expr),
+            Expr::Tuple(tuple_expr) => self.visit_tuple_expr(tuple_expr),
+            Expr::FieldAccess(field_access_expr) => self.visit_field_fields(field_access_expr),
+            Expr::Unary(unary_expr) if unary_expr.op == UnOp::Deref => {
+                let type_info = unary_expr.type_info(&self.types);
+                let addr = self.visit_ptr_expr(&mut unary_expr.expr)?;
+                Ok(self.gen_load_fields(addr, &type_info))
+            }
+            Expr::Call(call_expr) => self.visit_struct_call(call_expr),
+            Expr::BinOp(bin_op_expr) if bin_op_expr.op_fn.is_some() => {
+                self.visit_struct_op(bin_op_expr)
+            }
+            _ => match expr.type_info(&self.types) {
+                TypeInfo::Struct { name, .. } => {
+                    Err(format!("this expression of type `{}` is not supported", name).into())
+                }
+                t @ TypeInfo::Tuple(_) => {
+                    Err(format!("this expression of type `{:?}` is not supported", t).into())
+                }
+                t => unreachable!("`{:?}` is not a struct", t),
+            },
+        }
+    }
+
+    /// `S { x: a.y, ..a }`: the given fields are evaluated into temporary variables in the
+    /// order they are written, so `a = S { x: a.y, y: a.x }` swaps the fields of `a`. The
+    /// other fields are copied from the base.
+    fn visit_struct_expr(&mut self, struct_expr: &mut StructExpr) -> Result<Vec<Operand>, RccError> {
+        let mut given = HashMap::new();
+        for (name, expr) in struct_expr.fields.iter_mut() {
+            let operands = self.visit_field_value(expr)?;
+            given.insert(name.clone(), operands);
+        }
+        let base = match struct_expr.base.as_mut() {
+            Some(base) => self.visit_struct_value(base)?,
+            None => vec![],
+        };
+
+        let mut operands = vec![];
+        for (name, field) in self.types.struct_fields(&struct_expr.name) {
+            let start = operands.len();
+            match given.remove(name) {
+                Some(field_operands) => operands.extend(field_operands),
+                None => {
+                    let len = scalar_fields(field, &self.types).len();
+                    operands.extend_from_slice(&base[start..start + len]);
+                }
+            }
+        }
+        Ok(operands)
+    }
+
+    /// Operands of the scalar fields of a field of a struct expression or an element of a
+    /// tuple, which are kept in temporary variables.
+    fn visit_field_value(&mut self, expr: &mut Expr) -> Result<Vec<Operand>, RccError> {
+        let type_info = expr.type_info(&self.types);
+        if type_info.is_aggregate() {
+            let fields = scalar_fields(&type_info, &self.types);
+            let srcs = self.visit_struct_value(expr)?;
+            Ok(srcs
+                .into_iter()
+                .zip(fields)
+                .map(|(src, (_, field))| self.to_temp(src, field))
+                .collect())
+        } else {
+            let temp = self.gen_temp_var(type_info.clone());
+            let src = self.visit_expr(expr, Some(temp), false)?;
+            Ok(vec![self.to_temp(src, type_info)])
+        }
+    }
+
+    /// Copy `operand` into a temporary variable unless it is a constant or a temporary
+    /// variable, whose value does not change.
+    fn to_temp(&mut self, operand: Operand, type_info: TypeInfo) -> Operand {
+        match operand {
+            Operand::Place(place) if !place.is_temp() => {
+                let temp = self.gen_temp_var(type_info);
+                self.ir_output
+                    .add_instructions(IRInst::load_data(temp.clone(), Operand::Place(place)));
+                Operand::Place(temp)
+            }
+            operand => operand,
+        }
+    }
+
+    fn visit_call_expr(
+        &mut self,
+        call_expr: &mut CallExpr,
+        dest: Option<Place>,
+    ) -> Result<Operand, RccError> {
+        if let Some(name) = call_expr.volatile_intrinsic() {
+            let declared = self.scope_stack.find_variable(name).is_some()
+                || self.scope_stack.find_fn_scope(name).is_some();
+            if !declared || !call_expr.is_path(&[name]) {
+                let is_write = name == "write_volatile";
+                return self.visit_volatile_call(call_expr, is_write, dest);
+            }
+        }
+        if call_expr.is_box_new() {
+            return self.visit_box_new(call_expr, dest);
+        }
+        if call_expr.is_vec_new() {
+            return self.visit_vec_new(call_expr, dest);
+        }
+        if let Some(method) = call_expr.method_name() {
+            let method = method.to_string();
+            return self.visit_method_call(call_expr, &method, dest);
+        }
+        if call_expr.type_info(&self.types).is_struct() {
+            self.visit_struct_call(call_expr)?;
+            return Ok(Operand::Unit);
+        }
+        if let TypeInfo::Array(..) = call_expr.type_info(&self.types) {
+            return self.visit_array_call(call_expr, dest);
+        }
+        if let Some(index) = call_expr.vtable_index {
+            return self.visit_dyn_call(call_expr, index, None, dest);
+        }
+        self.gen_call(call_expr, None, dest)
+    }
+
+    /// A call of a function returning a struct passes the address of a temporary struct,
+    /// which the function writes the struct to. Return the fields of the temporary struct.
+    ///
+    /// ```
+    /// $0.0 = 0
+    /// $0.4 = 0
+    /// $1 = &$0.0
+    /// call @f($1, x)
+    /// ```
+    fn visit_struct_call(&mut self, call_expr: &mut CallExpr) -> Result<Vec<Operand>, RccError> {
+        let (temps, sret) = self.gen_sret_temps(call_expr.type_info(&self.types));
+        match call_expr.vtable_index {
+            Some(index) => self.visit_dyn_call(call_expr, index, Some(sret), None)?,
+            None => self.gen_call(call_expr, Some(sret), None)?,
+        };
+        Ok(temps.into_iter().map(Operand::Place).collect())
+    }
+
+    /// A call of a function returning an array passes the address of a new array, which the
+    /// function copies the array to.
+    ///
+    /// ```
+    /// dest = &$0
+    /// call @f(dest, x)
+    /// ```
+    fn visit_array_call(
+        &mut self,
+        call_expr: &mut CallExpr,
+        dest: Option<Place>,
+    ) -> Result<Operand, RccError> {
+        let type_info = call_expr.type_info(&self.types);
+        let dest = match dest {
+            Some(d) => d,
+            None => self.gen_temp_var(type_info.clone()),
+        };
+        self.gen_array_slot(&type_info, dest.clone())?;
+        let sret = Some(Operand::Place(dest.clone()));
+        match call_expr.vtable_index {
+            Some(index) => self.visit_dyn_call(call_expr, index, sret, None)?,
+            None => self.gen_call(call_expr, sret, None)?,
+        };
+        Ok(Operand::Place(dest))
+    }
+
+    /// A temporary struct which a returned struct is written to, and its address
+    fn gen_sret_temps(&mut self, type_info: TypeInfo) -> (Vec<Place>, Operand) {
+        let temps = self.gen_temp_struct(type_info.clone());
+        // the fields are defined before they are written through the address
+        for temp in temps.iter() {
+            let zero = match temp.ir_type {
+                IRType::Bool => Operand::Bool(false),
+                IRType::Char => Operand::Char('\0'),
+                IRType::F32 => Operand::F32(0.0),
+                IRType::F64 => Operand::F64(0.0),
+                IRType::Addr => Operand::Usize(0),
+                ir_type => Operand::zero(ir_type),
+            };
+            self.ir_output.add_instructions(IRInst::load_data(temp.clone(), zero));
+        }
+        // nothing is written to the address of a struct without fields
+        let sret = match temps.first() {
+            Some(first) => {
+                let addr = self.gen_temp_var(TypeInfo::Ptr {
+                    kind: PtrKind::MutRef,
+                    type_info: Box::new(type_info),
+                });
+                self.ir_output.add_instructions(IRInst::LoadAddr {
+                    dest: addr.clone(),
+                    symbol: Operand::Place(first.clone()),
+                });
+                Operand::Place(addr)
+            }
+            None => Operand::Usize(0),
+        };
+        (temps, sret)
+    }
+
+    /// `f(x)`, or `f(sret, x)` with the address of a returned struct
+    fn gen_call(
+        &mut self,
+        call_expr: &mut CallExpr,
+        sret: Option<Operand>,
+        dest: Option<Place>,
+    ) -> Result<Operand, RccError> {
+        let callee_place = self.gen_temp_var(raw_ptr());
+        let callee = self.visit_expr(&mut call_expr.expr, Some(callee_place), false)?;
+        let param_types = match call_expr.expr.type_info(&self.types) {
+            TypeInfo::Fn { vis: _, inner } | TypeInfo::FnPtr(inner) => inner
+                .params
+                .iter()
+                .map(|param| TypeInfo::from_type_anno(param, &self.scope_stack))
+                .collect(),
+            _ => vec![],
+        };
+        let mut params: Vec<Operand> = sret.into_iter().collect();
+        params.extend(self.visit_call_params(&mut call_expr.call_params, &param_types)?);
+        self.ir_output
+            .add_instructions(IRInst::call(callee, params));
+        match dest {
+            Some(d) => {
+                self.ir_output
+                    .add_instructions(IRInst::load_data(d.clone(), Operand::FnRetPlace(d.ir_type)));
+                Ok(Operand::Place(d))
+            }
+            None => Ok(Operand::Unit),
+        }
+    }
+
+    /// `read_volatile(p)` is `dest = volatile *p`, which is kept even if `dest` is unused, and
+    /// `write_volatile(p, x)` is `volatile *p = x`.
+    fn visit_volatile_call(
+        &mut self,
+        call_expr: &mut CallExpr,
+        is_write: bool,
+        dest: Option<Place>,
+    ) -> Result<Operand, RccError> {
+        let addr = self.visit_ptr_expr(&mut call_expr.call_params[0])?;
+        if is_write {
+            let value = &mut call_expr.call_params[1];
+            let value_place = self.gen_temp_var(value.type_info(&self.types));
+            let ty = value_place.ir_type;
+            let src = self.visit_expr(value, Some(value_place), false)?;
+            self.ir_output.add_instructions(IRInst::Store {
+                ty,
+                addr,
+                src,
+                volatile: true,
+            });
+            return Ok(Operand::Unit);
+        }
+        let place = match &dest {
+            Some(d) => d.clone(),
+            None => self.gen_temp_var(call_expr.type_info(&self.types)),
+        };
+        self.ir_output.add_instructions(IRInst::Load {
+            ty: place.ir_type,
+            dest: place.clone(),
+            addr,
+            volatile: true,
+        });
+        Ok(dest.map_or(Operand::Unit, Operand::Place))
+    }
+
+    /// Operands of the params, a trait object or a slice is passed as two operands and a struct
+    /// as its scalar fields.
+    fn visit_call_params(
+        &mut self,
+        call_params: &mut [Expr],
+        param_types: &[TypeInfo],
+    ) -> Result<Vec<Operand>, RccError> {
+        let mut params = vec![];
+        for (i, e) in call_params.iter_mut().enumerate() {
+            if e.type_info(&self.types).is_aggregate() {
+                params.extend(self.visit_struct_value(e)?);
+                continue;
+            }
+            if let Some(trait_name) = param_types.get(i).and_then(|t| t.dyn_trait()) {
+                let (data, vtable) = self.visit_dyn_expr(e, trait_name)?;
+                params.push(data);
+                params.push(vtable);
+                continue;
+            }
+            if param_types.get(i).is_some_and(|t| t.is_slice_ref()) {
+                let (data, len) = self.visit_slice_expr(e)?;
+                params.push(data);
+                params.push(len);
+                continue;
+            }
+            let param_place = self.gen_temp_var(e.type_info(&self.types));
+            params.push(self.visit_expr(e, Some(param_place), false)?);
+        }
+        Ok(params)
+    }
+
+    /// `d.show(x)` of `d: &dyn Show`, `show` is the method at `index` of the vtable of `d`.
+    /// An entry of a vtable takes 8 bytes like other pointer-sized values. The address of a
+    /// returned struct `sret` is passed before `d`.
+    ///
+    /// ```
+    /// $0 = d$vt + 8 * index
+    /// $1 = *$0
+    /// call $1(d, x)
+    /// dest = ret
+    /// ```
+    fn visit_dyn_call(
+        &mut self,
+        call_expr: &mut CallExpr,
+        index: usize,
+        sret: Option<Operand>,
+        dest: Option<Place>,
+    ) -> Result<Operand, RccError> {
+        let (receiver, params) = call_expr.call_params.split_first_mut().unwrap();
+        let trait_name = receiver.type_info(&self.types).dyn_trait().unwrap().to_string();
+        let (data, vtable) = self.visit_dyn_expr(receiver, &trait_name)?;
+        let entry = if index == 0 {
+            vtable
+        } else {
+            let entry = self.gen_temp_var(raw_ptr());
+            self.ir_output.add_instructions(IRInst::bin_op(
+                BinOperator::Plus,
+                entry.clone(),
+                vtable,
+                Operand::Usize(8 * index),
+            ));
+            Operand::Place(entry)
+        };
+        let method = self.gen_temp_var(raw_ptr());
+        self.ir_output.add_instructions(IRInst::load(method.clone(), entry));
+        let param_types: Vec<TypeInfo> =
+            params.iter().map(|e| e.type_info(&self.types)).collect();
+        let mut args: Vec<Operand> = sret.into_iter().collect();
+        args.push(data);
+        args.extend(self.visit_call_params(params, &param_types)?);
+        self.ir_output
+            .add_instructions(IRInst::call(Operand::Place(method), args));
+        match dest {
+            Some(d) => {
+                self.ir_output
+                    .add_instructions(IRInst::load_data(d.clone(), Operand::FnRetPlace(d.ir_type)));
+                Ok(Operand::Place(d))
+            }
+            None => Ok(Operand::Unit),
+        }
+    }
+
+    /// `Box::new(expr)`, the elements of an array are copied to the heap
+    ///
+    /// ```
+    /// $0 = expr
+    /// call @__rcc_alloc(size of expr)
+    /// dest = ret
+    /// *dest = $0
+    /// ```
+    fn visit_box_new(
+        &mut self,
+        call_expr: &mut CallExpr,
+        dest: Option<Place>,
     ) -> Result<Operand, RccError> {
-        debug_assert!(matches!(
-            bin_op_expr.bin_op,
-            BinOperator::AndAnd | BinOperator::OrOr
+        let expr = &mut call_expr.call_params[0];
+        let type_info = expr.type_info(&self.types);
+        let value_place = self.gen_temp_var(type_info.clone());
+        let value = self.visit_expr(expr, Some(value_place.clone()), false)?;
+        // pointer-sized values take 8 bytes, so that the IR does not depend on the target
+        let size = match type_info {
+            TypeInfo::Array(..) => array_size(&type_info)?,
+            _ => value_place.ir_type.byte_size(64) as usize,
+        };
+        self.ir_output.add_instructions(IRInst::call(
+            Operand::FnLabel(ALLOC.into()),
+            vec![Operand::Usize(size)],
         ));
-        // let lhs = self.visit_expr(&mut bin_op_expr.lhs, dest)?;
-        // let if_inst = if bin_op_expr.bin_op == BinOperator::AndAnd {
-        //     IRInst::jump_if_not(lhs)
-        // } else {
-        //     IRInst::jump_if(lhs)
-        // };
-        // let if_idx = self.ir_output.instructions.len();
-        // self.ir_output.add_instructions(if_inst);
-        // let rhs = self.visit_expr(
-        //     &mut bin_op_expr.rhs,
-        //     dest.clone(),
-        // );
-        todo!()
-    }
-
-    fn visit_array_expr(&mut self, array_expr: &mut ArrayExpr) -> Result<Operand, RccError> {
-        unimplemented!()
+        let dest = match dest {
+            Some(d) => d,
+            None => self.gen_temp_var(call_expr.type_info(&self.types)),
+        };
+        self.ir_output.add_instructions(IRInst::load_data(
+            dest.clone(),
+            Operand::FnRetPlace(IRType::Addr),
+        ));
+        if let TypeInfo::Array(..) = type_info {
+            self.gen_memcpy(Operand::Place(dest.clone()), value, Operand::Usize(size));
+        } else {
+            let ty = value_place.ir_type;
+            self.ir_output.add_instructions(IRInst::store(ty, Operand::Place(dest.clone()), value));
+        }
+        Ok(Operand::Place(dest))
     }
 
-    fn visit_array_index_expr(
+    /// `Vec::new()`, `String::new()` and `String::from(s)`, a temporary vector is freed at once.
+    ///
+    /// ```
+    /// call @__rcc_vec_new()
+    /// dest = ret
+    /// $0 = s
+    /// call @__rcc_string_push_str(dest, $0)
+    /// ```
+    fn visit_vec_new(
         &mut self,
-        array_index_expr: &mut ArrayIndexExpr,
+        call_expr: &mut CallExpr,
+        dest: Option<Place>,
     ) -> Result<Operand, RccError> {
-        unimplemented!()
-    }
-
-    fn visit_tuple_expr(&mut self, tuple_expr: &mut TupleExpr) -> Result<Operand, RccError> {
-        unimplemented!()
+        self.ir_output
+            .add_instructions(IRInst::call(Operand::FnLabel(VEC_NEW.into()), vec![]));
+        let vec = match &dest {
+            Some(d) => d.clone(),
+            None => self.gen_temp_var(call_expr.type_info(&self.types)),
+        };
+        self.ir_output.add_instructions(IRInst::load_data(
+            vec.clone(),
+            Operand::FnRetPlace(IRType::Addr),
+        ));
+        if let [s] = call_expr.call_params.as_mut_slice() {
+            let s_place = self.gen_temp_var(s.type_info(&self.types));
+            let s = self.visit_expr(s, Some(s_place), false)?;
+            self.ir_output.add_instructions(IRInst::call(
+                Operand::FnLabel(STRING_PUSH_STR.into()),
+                vec![Operand::Place(vec.clone()), s],
+            ));
+        }
+        if dest.is_none() {
+            self.ir_output.add_instructions(IRInst::call(
+                Operand::FnLabel(VEC_FREE.into()),
+                vec![Operand::Place(vec)],
+            ));
+            return Ok(Operand::Unit);
+        }
+        Ok(Operand::Place(vec))
     }
 
-    fn visit_tuple_index_expr(
+    /// `v.len()`, `v.push(x)` and `s.push_str(x)`, the receiver `v` is not moved. The length of
+    /// a slice is its second half.
+    ///
+    /// ```
+    /// $0 = x
+    /// call @__rcc_vec_push(v, size of x)
+    /// $1 = ret
+    /// *$1 = $0
+    /// ```
+    fn visit_method_call(
         &mut self,
-        tuple_index_expr: &mut TupleIndexExpr,
+        call_expr: &mut CallExpr,
+        method: &str,
+        dest: Option<Place>,
     ) -> Result<Operand, RccError> {
-        unimplemented!()
+        let CallExpr {
+            expr, call_params, ..
+        } = call_expr;
+        let receiver = match expr.as_mut() {
+            Expr::FieldAccess(field_access_expr) => field_access_expr.lhs.as_mut(),
+            _ => unreachable!(),
+        };
+        let receiver_type = receiver.type_info(&self.types);
+        if matches!(method, "as_ptr" | "as_mut_ptr") {
+            let data = match receiver_type {
+                TypeInfo::Array(..) => self.visit_ptr_expr(receiver)?,
+                _ => self.visit_slice_expr(receiver)?.0,
+            };
+            return self.lit(data, dest, false);
+        }
+        if receiver_type.is_raw_ptr() {
+            return self.visit_ptr_offset(receiver, method, &mut call_params[0], dest);
+        }
+        // the length of a slice is known without a call, and `s.as_bytes()` is only used by
+        // `visit_slice_expr`
+        if receiver_type.is_slice_ref() {
+            let (_, len) = self.visit_slice_expr(receiver)?;
+            if method == "as_bytes" {
+                return Ok(Operand::Unit);
+            }
+            return self.lit(len, dest, false);
+        }
+        let vec = self.visit_ptr_expr(receiver)?;
+        match (method, call_params.as_mut_slice()) {
+            ("len", []) => {
+                self.ir_output
+                    .add_instructions(IRInst::call(Operand::FnLabel(VEC_LEN.into()), vec![vec]));
+                match dest {
+                    Some(d) => {
+                        self.ir_output.add_instructions(IRInst::load_data(
+                            d.clone(),
+                            Operand::FnRetPlace(IRType::Usize),
+                        ));
+                        Ok(Operand::Place(d))
+                    }
+                    None => Ok(Operand::Unit),
+                }
+            }
+            ("push_str", [s]) => {
+                let s_place = self.gen_temp_var(s.type_info(&self.types));
+                let s = self.visit_expr(s, Some(s_place), false)?;
+                self.ir_output.add_instructions(IRInst::call(
+                    Operand::FnLabel(STRING_PUSH_STR.into()),
+                    vec![vec, s],
+                ));
+                Ok(Operand::Unit)
+            }
+            ("push", [elem]) => {
+                let elem_type = elem.type_info(&self.types);
+                let elem_size = elem_size(&elem_type)?;
+                let elem_place = self.gen_temp_var(elem_type.clone());
+                let value = self.visit_expr(elem, Some(elem_place), false)?;
+                self.ir_output.add_instructions(IRInst::call(
+                    Operand::FnLabel(VEC_PUSH.into()),
+                    vec![vec, Operand::Usize(elem_size)],
+                ));
+                let addr = self.gen_elem_ptr(&elem_type);
+                let ty = IRType::from_type_info(&elem_type)?;
+                self.ir_output.add_instructions(IRInst::store(ty, Operand::Place(addr), value));
+                Ok(Operand::Unit)
+            }
+            _ => unreachable!("unknown method `{}`", method),
+        }
     }
 
-    fn visit_struct_expr(&mut self, struct_expr: &mut StructExpr) -> Result<Operand, RccError> {
-        unimplemented!()
+    /// `p.add(n)` of a raw pointer `p` to `T` is `p` moved by `n` times the size of `T`, and
+    /// `p.sub(n)` is moved back. The wrapping methods are the same.
+    ///
+    /// ```
+    /// $0 = n * size
+    /// dest = p + $0
+    /// ```
+    fn visit_ptr_offset(
+        &mut self,
+        ptr: &mut Expr,
+        method: &str,
+        count: &mut Expr,
+        dest: Option<Place>,
+    ) -> Result<Operand, RccError> {
+        let ptr_type = ptr.type_info(&self.types);
+        let size = match &ptr_type {
+            TypeInfo::Ptr { type_info, .. } => elem_size(type_info)?,
+            t => unreachable!("`{:?}` is not a pointer", t),
+        };
+        let ptr = self.visit_ptr_expr(ptr)?;
+        let usize = TypeInfo::LitNum(TypeLitNum::Usize);
+        let count_place = self.gen_temp_var(usize.clone());
+        let count = self.visit_expr(count, Some(count_place), false)?;
+        let dest = match dest {
+            Some(d) => d,
+            None => return Ok(Operand::Unit),
+        };
+        let offset = match count {
+            Operand::Usize(n) => Operand::Usize(n.wrapping_mul(size)),
+            count if size == 1 => count,
+            count => {
+                let offset = self.gen_temp_var(usize);
+                self.ir_output.add_instructions(IRInst::bin_op(
+                    BinOperator::Star,
+                    offset.clone(),
+                    count,
+                    Operand::Usize(size),
+                ));
+                Operand::Place(offset)
+            }
+        };
+        if offset.is_zero() {
+            return self.lit(ptr, Some(dest), false);
+        }
+        let op = if method.ends_with("add") {
+            BinOperator::Plus
+        } else {
+            BinOperator::Minus
+        };
+        self.ir_output
+            .add_instructions(IRInst::bin_op(op, dest.clone(), ptr, offset));
+        Ok(Operand::Place(dest))
     }
 
-    fn visit_call_expr(
+    /// `a.b.c` of a scalar type is the place of `a` at the sum of the offsets of `b` and `c`.
+    fn visit_field_access_expr(
         &mut self,
-        call_expr: &mut CallExpr,
+        field_access_expr: &mut FieldAccessExpr,
         dest: Option<Place>,
+        remain_temp: bool,
     ) -> Result<Operand, RccError> {
-        let callee_place = self.gen_temp_var(call_expr.type_info());
-        let callee = self.visit_expr(&mut call_expr.expr, Some(callee_place), false)?;
-
-        let mut params = vec![];
-        for e in call_expr.call_params.iter_mut() {
-            let param_place = self.gen_temp_var(e.type_info());
-            params.push(self.visit_expr(e, Some(param_place), false)?);
+        let operands = self.visit_field_fields(field_access_expr)?;
+        if field_access_expr.type_info(&self.types).is_struct() {
+            return Ok(Operand::Unit);
         }
-        self.ir_output
-            .add_instructions(IRInst::call(callee, params));
-        match dest {
-            Some(d) => {
+        let operand = operands.into_iter().next().unwrap();
+        if let Some(d) = dest {
+            if !d.is_temp() || remain_temp {
                 self.ir_output
-                    .add_instructions(IRInst::load_data(d.clone(), Operand::FnRetPlace(d.ir_type)));
-                Ok(Operand::Place(d))
+                    .add_instructions(IRInst::load_data(d, operand.clone()));
             }
-            None => Ok(Operand::Unit),
         }
+        Ok(operand)
     }
 
-    fn visit_field_access_expr(
+    /// Operands of the scalar fields of `a.b`, which are a part of those of `a`. The fields
+    /// of `(*r).b` are loaded from the memory `r` points to.
+    fn visit_field_fields(
+        &mut self,
+        field_access_expr: &mut FieldAccessExpr,
+    ) -> Result<Vec<Operand>, RccError> {
+        if is_behind_ptr(&field_access_expr.lhs) {
+            let type_info = field_access_expr.type_info(&self.types);
+            let addr = self.visit_field_addr(field_access_expr)?;
+            return Ok(self.gen_load_fields(addr, &type_info));
+        }
+        let struct_name = match field_access_expr.lhs.type_info(&self.types) {
+            TypeInfo::Struct { name, .. } => name,
+            t => unreachable!("field access on `{:?}`", t),
+        };
+        let field_name = field_access_expr.field_name().unwrap();
+        let mut start = 0;
+        let mut len = 0;
+        for (name, field) in self.types.struct_fields(&struct_name) {
+            len = scalar_fields(field, &self.types).len();
+            if name == field_name {
+                break;
+            }
+            start += len;
+        }
+        let mut operands = self.visit_struct_value(&mut field_access_expr.lhs)?;
+        operands.truncate(start + len);
+        operands.drain(..start);
+        Ok(operands)
+    }
+
+    /// Address of the place `expr` behind a pointer, see `is_behind_ptr`
+    fn visit_place_addr(&mut self, expr: &mut Expr) -> Result<Operand, RccError> {
+        match expr {
+            Expr::Unary(unary_expr) if unary_expr.op == UnOp::Deref => {
+                self.visit_ptr_expr(&mut unary_expr.expr)
+            }
+            Expr::Grouped(grouped_expr) => self.visit_place_addr(grouped_expr),
+            Expr::FieldAccess(field_access_expr) => self.visit_field_addr(field_access_expr),
+            e => unreachable!("`{:?}` is not behind a pointer", e),
+        }
+    }
+
+    /// `&(*r).b` is `r` plus the offset of `b`
+    fn visit_field_addr(
         &mut self,
         field_access_expr: &mut FieldAccessExpr,
     ) -> Result<Operand, RccError> {
-        unimplemented!()
+        let struct_name = match field_access_expr.lhs.type_info(&self.types) {
+            TypeInfo::Struct { name, .. } => name,
+            t => unreachable!("field access on `{:?}`", t),
+        };
+        let field_name = field_access_expr.field_name().unwrap();
+        let fields = self.types.struct_fields(&struct_name);
+        let types: Vec<&TypeInfo> = fields.iter().map(|(_, field)| field).collect();
+        let (offset, field) = fields
+            .iter()
+            .zip(field_offsets(&types, &self.types))
+            .find(|((name, _), _)| name == field_name)
+            .map(|((_, field), offset)| (offset, field.clone()))
+            .unwrap();
+        let addr = self.visit_place_addr(&mut field_access_expr.lhs)?;
+        Ok(self.gen_ptr_offset(addr, offset, &field))
+    }
+
+    /// Address `offset` bytes after the pointer `addr`, of a value of `type_info`
+    fn gen_ptr_offset(&mut self, addr: Operand, offset: u32, type_info: &TypeInfo) -> Operand {
+        match addr {
+            Operand::Place(base) => {
+                self.gen_offset(&base, Operand::Usize(offset as usize), type_info)
+            }
+            o => unreachable!("pointer `{}` is not in a variable", o),
+        }
+    }
+
+    /// Load the scalar fields of a struct value of `type_info` at `addr` into temporary
+    /// variables.
+    fn gen_load_fields(&mut self, addr: Operand, type_info: &TypeInfo) -> Vec<Operand> {
+        let mut operands = vec![];
+        for (offset, field) in scalar_fields(type_info, &self.types) {
+            let field_addr = self.gen_ptr_offset(addr.clone(), offset, &field);
+            let temp = self.gen_temp_var(field);
+            self.ir_output
+                .add_instructions(IRInst::load(temp.clone(), field_addr));
+            operands.push(Operand::Place(temp));
+        }
+        operands
     }
 
     fn visit_loop_block(
@@ -573,13 +2732,9 @@ impl IRBuilder {
         let operand = self.visit_block_expr(loop_block, None, false)?;
         assert!(operand.is_unit_or_never());
         self.ir_output.add_instructions(IRInst::jump(loop_start_id));
-        let (d, mut link) = self.loop_var_stack.pop().unwrap();
+        let (_, link) = self.loop_var_stack.pop().unwrap();
         let next_id = self.ir_output.next_inst_id();
-        while link != 0 {
-            let inst = self.ir_output.get_inst_by_id(link);
-            link = inst.jump_label();
-            inst.set_jump_label(next_id);
-        }
+        self.back_patch(link, next_id);
         Ok(())
     }
 
@@ -588,66 +2743,178 @@ impl IRBuilder {
         let loop_start_id = self.ir_output.next_inst_id();
 
         let mut next_back_patch_link = 0;
-        // while condition
-        match while_expr.0.as_mut() {
-            Expr::BinOp(e) => match e.bin_op {
-                BinOperator::AndAnd => {
-                    todo!()
-                }
-                BinOperator::OrOr => {
-                    todo!()
-                }
-                BinOperator::Ne => {
-                    self.gen_jump_cond(e, JEq, &mut next_back_patch_link)?;
-                }
-                BinOperator::EqEq => {
-                    self.gen_jump_cond(e, JNe, &mut next_back_patch_link)?;
-                }
-                BinOperator::Le => {
-                    self.gen_jump_cond_reverse(e, JLt, &mut next_back_patch_link)?;
-                }
-                BinOperator::Lt => {
-                    self.gen_jump_cond(e, JGe, &mut next_back_patch_link)?;
-                }
-                BinOperator::Gt => {
-                    self.gen_jump_cond_reverse(e, JGe, &mut next_back_patch_link)?;
-                }
-                BinOperator::Ge => {
-                    self.gen_jump_cond(e, JLt, &mut next_back_patch_link)?;
-                }
-                _ => {
-                    let d = self.gen_temp_var(e.type_info());
-                    let operand = self.visit_bin_op_expr(e, Some(d))?;
-
-                    next_back_patch_link = self.ir_output.next_inst_id();
-                    let ir_inst = IRInst::jump_if_not(operand, 0);
-                    self.ir_output.add_instructions(ir_inst);
-                }
-            },
-            // todo: unary expr, lit bool
-            e => {
-                let d = self.gen_temp_var(e.type_info());
-                let operand = self.visit_expr(e, Some(d), false)?;
-
-                next_back_patch_link = self.ir_output.next_inst_id();
-                let ir_inst = IRInst::jump_if_not(operand, 0);
-                self.ir_output.add_instructions(ir_inst);
-            }
-        }
+        self.gen_cond_jump(&mut while_expr.0, false, &mut next_back_patch_link)?;
         self.loop_var_stack.push((None, next_back_patch_link));
         self.visit_loop_block(&mut while_expr.1, loop_start_id)?;
         Ok(Operand::Unit)
     }
 
+    /// `for b in s.bytes()` walks the pair of (data ptr, len) of `s`, and `for i in a..b` the
+    /// integers from `a`. The pair is copied before the loop, and the loop variable is assigned
+    /// before `BLOCK`:
+    ///
+    /// ```
+    /// for b in s.bytes() {}      for i in a..b {}           for i in a..=b {}
+    ///
+    /// (1) $d = data              (1) $i = a                 (1) $i = a
+    /// (2) $n = len               (2) $n = b                 (2) $n = b
+    /// (3) $i = 0                 (3) if $i >= $n goto NEXT  (3) if $n < $i goto NEXT
+    /// (4) if $i >= $n goto NEXT  (4) i = $i                 (4) goto (7)
+    /// (5) $0 = $d + $i           (5) $i = $i + 1            (5) if $i == $n goto NEXT
+    /// (6) b = *$0                BLOCK                      (6) $i = $i + 1
+    /// (7) $i = $i + 1            goto (3)                   (7) i = $i
+    /// BLOCK                                                 BLOCK
+    /// goto (4)                                              goto (5)
+    /// ```
+    ///
+    /// `a..=b` checks `$i == $n` before the increment, so that `$i` does not overflow if `b`
+    /// is the maximum of its type.
+    fn visit_for_expr(&mut self, for_expr: &mut ForExpr) -> Result<Operand, RccError> {
+        let kind = if for_expr.pattern.is_mut() {
+            VarKind::LocalMut
+        } else {
+            VarKind::Local
+        };
+        let ident = for_expr.pattern.var_name().unwrap();
+        let scope_id = for_expr.block.scope_id;
+        let loop_start_id = match for_expr.expr.as_mut() {
+            Expr::Call(call_expr) => {
+                let receiver = match call_expr.expr.as_mut() {
+                    Expr::FieldAccess(field_access_expr) => field_access_expr.lhs.as_mut(),
+                    _ => unreachable!(),
+                };
+                let byte = Place::variable(ident, scope_id, kind, IRType::U8);
+                self.gen_bytes_loop(receiver, byte)?
+            }
+            range => {
+                let (elem, inclusive) = match &*range {
+                    Expr::Range(range_expr) => (
+                        range_expr.lhs.as_ref().unwrap().type_info(&self.types),
+                        range_expr.range_op == RangeOp::DotDotEq,
+                    ),
+                    range => match range.type_info(&self.types) {
+                        TypeInfo::Range {
+                            type_info,
+                            inclusive,
+                        } => (*type_info, inclusive),
+                        t => unreachable!("`{:?}` is not an iterator", t),
+                    },
+                };
+                let ir_type = IRType::from_type_info(&elem)?;
+                let loop_var = Place::variable(ident, scope_id, kind, ir_type);
+                self.gen_range_loop(range, inclusive, loop_var, elem)?
+            }
+        };
+        self.visit_loop_block(&mut for_expr.block, loop_start_id)?;
+        Ok(Operand::Unit)
+    }
+
+    /// The head of the loop over the bytes of `receiver`, see `visit_for_expr`
+    fn gen_bytes_loop(&mut self, receiver: &mut Expr, byte: Place) -> Result<usize, RccError> {
+        let (data, len) = self.visit_slice_expr(receiver)?;
+        let data_place = self.gen_temp_var(TypeInfo::ref_bytes());
+        self.ir_output.add_instructions(IRInst::load_data(data_place.clone(), data));
+        let len_place = self.gen_temp_var(TypeInfo::LitNum(TypeLitNum::Usize));
+        self.ir_output.add_instructions(IRInst::load_data(len_place.clone(), len));
+        let index = self.gen_temp_var(TypeInfo::LitNum(TypeLitNum::Usize));
+        self.ir_output.add_instructions(IRInst::load_data(index.clone(), Operand::Usize(0)));
+
+        let loop_start_id = self.ir_output.next_inst_id();
+        self.ir_output.add_instructions(IRInst::jump_if_cond(
+            JGe,
+            Operand::Place(index.clone()),
+            Operand::Place(len_place),
+            0,
+        ));
+        let u8 = TypeInfo::LitNum(TypeLitNum::U8);
+        let addr = self.gen_offset(&data_place, Operand::Place(index.clone()), &u8);
+        self.ir_output.add_instructions(IRInst::load(byte, addr));
+        self.ir_output.add_instructions(IRInst::bin_op(
+            BinOperator::Plus,
+            index.clone(),
+            Operand::Place(index),
+            Operand::Usize(1),
+        ));
+        self.loop_var_stack.push((None, loop_start_id));
+        Ok(loop_start_id)
+    }
+
+    /// The head of the loop over the integers of `range`, see `visit_for_expr`
+    fn gen_range_loop(
+        &mut self,
+        range: &mut Expr,
+        inclusive: bool,
+        loop_var: Place,
+        elem: TypeInfo,
+    ) -> Result<usize, RccError> {
+        let (start, end) = self.visit_range_value(range)?;
+        let one = Operand::int(IRType::from_type_info(&elem)?, 1);
+        let next = self.gen_temp_var(elem.clone());
+        self.ir_output.add_instructions(IRInst::load_data(next.clone(), start));
+        let end_place = self.gen_temp_var(elem);
+        self.ir_output.add_instructions(IRInst::load_data(end_place.clone(), end));
+        let (next_op, end) = (Operand::Place(next.clone()), Operand::Place(end_place));
+
+        let loop_start_id = if inclusive {
+            let empty_id = self.ir_output.next_inst_id();
+            self.ir_output.add_instructions(IRInst::jump_if_cond(
+                JLt,
+                end.clone(),
+                next_op.clone(),
+                0,
+            ));
+            let body_jump_id = self.ir_output.next_inst_id();
+            self.ir_output.add_instructions(IRInst::jump(0));
+            let loop_start_id = self.ir_output.next_inst_id();
+            self.ir_output.add_instructions(IRInst::jump_if_cond(
+                JEq,
+                next_op.clone(),
+                end,
+                empty_id,
+            ));
+            self.ir_output.add_instructions(IRInst::bin_op(
+                BinOperator::Plus,
+                next,
+                next_op.clone(),
+                one,
+            ));
+            let body_id = self.ir_output.next_inst_id();
+            self.back_patch(body_jump_id, body_id);
+            self.ir_output.add_instructions(IRInst::load_data(loop_var, next_op));
+            loop_start_id
+        } else {
+            let loop_start_id = self.ir_output.next_inst_id();
+            self.ir_output.add_instructions(IRInst::jump_if_cond(
+                JGe,
+                next_op.clone(),
+                end,
+                0,
+            ));
+            self.ir_output.add_instructions(IRInst::load_data(loop_var, next_op.clone()));
+            self.ir_output.add_instructions(IRInst::bin_op(
+                BinOperator::Plus,
+                next,
+                next_op,
+                one,
+            ));
+            loop_start_id
+        };
+        self.loop_var_stack.push((None, loop_start_id));
+        Ok(loop_start_id)
+    }
+
     fn visit_loop_expr(
         &mut self,
         loop_expr: &mut LoopExpr,
         dest: Option<Place>,
     ) -> Result<Operand, RccError> {
         let loop_start_id = self.ir_output.next_inst_id();
-        self.loop_var_stack.push((dest.clone(), 0));
+        // `break;` gives `()`, which is not stored
+        let is_unit = loop_expr.type_info(&self.types) == TypeInfo::Unit;
+        self.loop_var_stack.push((dest.clone().filter(|_| !is_unit), 0));
         self.visit_loop_block(&mut loop_expr.expr, loop_start_id)?;
         match dest {
+            Some(_) if is_unit => Ok(Operand::Unit),
             Some(p) => Ok(Operand::Place(p)),
             None => Ok(Operand::Never),
         }
@@ -757,6 +3024,9 @@ impl IRBuilder {
         if_expr: &mut IfExpr,
         dest: Option<Place>,
     ) -> Result<Operand, RccError> {
+        if let Some(values) = self.switch_values(if_expr) {
+            return self.visit_if_expr_as_switch(if_expr, values, dest);
+        }
         let mut direct_jump_link = 0usize;
         let mut last_cond_jump = 0usize;
 
@@ -772,64 +3042,17 @@ impl IRBuilder {
         }
 
         for (i, cond) in if_expr.conditions.iter_mut().enumerate() {
-            match cond {
-                Expr::BinOp(e) => match e.bin_op {
-                    BinOperator::AndAnd => {
-                        todo!()
-                    }
-                    BinOperator::OrOr => {
-                        todo!()
-                    }
-                    BinOperator::Ne => {
-                        self.gen_jump_cond(e, JEq, &mut last_cond_jump)?;
-                        visit_block!(i, ir_inst);
-                    }
-                    BinOperator::EqEq => {
-                        self.gen_jump_cond(e, JNe, &mut last_cond_jump)?;
-                        visit_block!(i, ir_inst);
-                    }
-                    BinOperator::Le => {
-                        self.gen_jump_cond_reverse(e, JLt, &mut last_cond_jump)?;
-                        visit_block!(i, ir_inst);
-                    }
-                    BinOperator::Lt => {
-                        self.gen_jump_cond(e, JGe, &mut last_cond_jump)?;
-                        visit_block!(i, ir_inst);
-                    }
-                    BinOperator::Gt => {
-                        self.gen_jump_cond_reverse(e, JGe, &mut last_cond_jump)?;
-                        visit_block!(i, ir_inst);
-                    }
-                    BinOperator::Ge => {
-                        self.gen_jump_cond(e, JLt, &mut last_cond_jump)?;
-                        visit_block!(i, ir_inst);
-                    }
-                    _ => {
-                        let d = self.gen_temp_var(e.type_info());
-                        let operand = self.visit_bin_op_expr(e, Some(d))?;
-                        let ir_inst = IRInst::jump_if_not(operand, last_cond_jump);
-                        self.ir_output.add_instructions(ir_inst);
-                        visit_block!(i, ir_inst);
-                    }
-                },
-                // todo: unary expr, lit bool
-                e => {
-                    let d = self.gen_temp_var(e.type_info());
-                    let operand = self.visit_expr(e, Some(d), false)?;
-                    let ir_inst = IRInst::jump_if_not(operand, last_cond_jump);
-                    last_cond_jump = self.ir_output.next_inst_id();
-                    self.ir_output.add_instructions(ir_inst);
-                    visit_block!(i, ir_inst);
-                }
-            }
+            // the previous condition jumps here if it is false
+            let cond_label = self.ir_output.next_inst_id();
+            self.back_patch(last_cond_jump, cond_label);
+            last_cond_jump = 0;
+            self.gen_cond_jump(cond, false, &mut last_cond_jump)?;
+            visit_block!(i, ir_inst);
         }
 
         // back patch the last jump condition
-        if last_cond_jump != 0 {
-            let jump_label = self.ir_output.next_inst_id();
-            let inst_to_backpatch = self.ir_output.get_inst_by_id(last_cond_jump);
-            inst_to_backpatch.set_jump_label(jump_label);
-        }
+        let else_label = self.ir_output.next_inst_id();
+        self.back_patch(last_cond_jump, else_label);
 
         // visit else block
         if if_expr.blocks.len() == if_expr.conditions.len() + 1 {
@@ -839,6 +3062,113 @@ impl IRBuilder {
         let jump_label = self.ir_output.next_inst_id();
 
         // back patch all the direct jump
+        self.back_patch(direct_jump_link, jump_label);
+
+        match dest {
+            Some(d) => Ok(Operand::Place(d)),
+            None => Ok(Operand::Unit),
+        }
+    }
+
+    /// Values of the cases if `if_expr` is a dense chain comparing a variable of an integer
+    /// type with distinct literals, e.g. `if x == 1 {} else if x == 3 {} else if x == 2 {}`.
+    fn switch_values(&self, if_expr: &IfExpr) -> Option<Vec<i64>> {
+        let mut var: Option<&PathExpr> = None;
+        let mut values = vec![];
+        for cond in if_expr.conditions.iter() {
+            let e = match cond {
+                Expr::BinOp(e) if e.bin_op == BinOperator::EqEq && e.op_fn.is_none() => e,
+                _ => return None,
+            };
+            let (path, lit) = match (e.lhs.as_ref(), e.rhs.as_ref()) {
+                (Expr::Path(path), Expr::LitNum(lit)) | (Expr::LitNum(lit), Expr::Path(path)) => {
+                    (path, lit)
+                }
+                _ => return None,
+            };
+            if var.get_or_insert(path).segments != path.segments {
+                return None;
+            }
+            let value: i64 = lit.value.parse().ok()?;
+            if values.contains(&value) {
+                return None;
+            }
+            values.push(value);
+        }
+        match var?.type_info(&self.types) {
+            TypeInfo::LitNum(TypeLitNum::I128) | TypeInfo::LitNum(TypeLitNum::U128) => {
+                return None
+            }
+            t if t.is_integer() => {}
+            _ => return None,
+        }
+        let min = *values.iter().min()? as i128;
+        let max = *values.iter().max()? as i128;
+        if values.len() < MIN_SWITCH_CASES || max - min >= 2 * values.len() as i128 {
+            return None;
+        }
+        Some(values)
+    }
+
+    /// ```text
+    /// switch x [1: goto B1, 3: goto B2, ...] else goto ELSE
+    /// B1: ...
+    ///     goto NEXT
+    /// B2: ...
+    ///     goto NEXT
+    /// ELSE: ...
+    /// NEXT:
+    /// ```
+    fn visit_if_expr_as_switch(
+        &mut self,
+        if_expr: &mut IfExpr,
+        values: Vec<i64>,
+        dest: Option<Place>,
+    ) -> Result<Operand, RccError> {
+        let src = match &mut if_expr.conditions[0] {
+            Expr::BinOp(e) => {
+                let var = if let Expr::Path(_) = e.lhs.as_ref() {
+                    e.lhs.as_mut()
+                } else {
+                    e.rhs.as_mut()
+                };
+                let d = self.gen_temp_var(var.type_info(&self.types));
+                self.visit_expr(var, Some(d), false)?
+            }
+            _ => unreachable!(),
+        };
+        let switch_id = self.ir_output.next_inst_id();
+        self.ir_output.add_instructions(IRInst::Switch {
+            src,
+            cases: vec![],
+            default: 0,
+        });
+
+        let mut cases = vec![];
+        let mut direct_jump_link = 0usize;
+        for (i, value) in values.into_iter().enumerate() {
+            cases.push((value, self.ir_output.next_inst_id()));
+            self.visit_block_expr(&mut if_expr.blocks[i], dest.clone(), true)?;
+            if i != if_expr.blocks.len() - 1 {
+                self.ir_output.add_instructions(IRInst::jump(direct_jump_link));
+                direct_jump_link = self.ir_output.next_inst_id() - 1;
+            }
+        }
+        let else_label = self.ir_output.next_inst_id();
+        if if_expr.blocks.len() == if_expr.conditions.len() + 1 {
+            self.visit_block_expr(if_expr.blocks.last_mut().unwrap(), dest.clone(), true)?;
+        }
+        if let IRInst::Switch {
+            cases: c,
+            default,
+            ..
+        } = self.ir_output.get_inst_by_id(switch_id)
+        {
+            *c = cases;
+            *default = else_label;
+        }
+
+        let jump_label = self.ir_output.next_inst_id();
         while direct_jump_link != 0 {
             let inst_to_backpatch = self.ir_output.get_inst_by_id(direct_jump_link);
             direct_jump_link = inst_to_backpatch.jump_label();
@@ -851,54 +3181,125 @@ impl IRBuilder {
         }
     }
 
-    fn gen_jump_cond(
+    /// Jumps if `cond` is `jump_if`. The jumps are added to the `link` of the jumps whose
+    /// labels are back patched later.
+    ///
+    /// ```
+    /// if true {}      ->    (no jump)
+    /// if false {}     ->    goto NEXT
+    /// if !a {}        ->    if a goto NEXT
+    /// if a <= b {}    ->    if b < a goto NEXT
+    /// if f() {}       ->    $0 = f()
+    ///                       if not $0 goto NEXT
+    /// if a && b {}    ->    if not a goto NEXT
+    ///                       if not b goto NEXT
+    /// if a || b {}    ->    if a goto BODY
+    ///                       if not b goto NEXT
+    /// ```
+    fn gen_cond_jump(
         &mut self,
-        e: &mut BinOpExpr,
-        jump: Jump,
-        last_condition_jump: &mut usize,
+        cond: &mut Expr,
+        jump_if: bool,
+        link: &mut usize,
     ) -> Result<(), RccError> {
-        let d = self.gen_temp_var(e.type_info());
-        let lhs = self.visit_expr(&mut e.lhs, Some(d), false)?;
-        let d = self.gen_temp_var(e.type_info());
-        let rhs = self.visit_expr(&mut e.rhs, Some(d), false)?;
-        if *last_condition_jump != 0 {
-            let jump_label = self.ir_output.next_inst_id();
-            let inst_to_backpatch = self.ir_output.get_inst_by_id(*last_condition_jump);
-            inst_to_backpatch.set_jump_label(jump_label);
-        }
-        let ir_inst = IRInst::jump_if_cond(jump, lhs, rhs, 0);
-        *last_condition_jump = self.ir_output.next_inst_id();
-        self.ir_output.add_instructions(ir_inst);
+        let inst = match cond {
+            Expr::Grouped(e) => return self.gen_cond_jump(e, jump_if, link),
+            Expr::Unary(e) if e.op == UnOp::Not => {
+                return self.gen_cond_jump(&mut e.expr, !jump_if, link)
+            }
+            Expr::BinOp(e) if matches!(e.bin_op, BinOperator::AndAnd | BinOperator::OrOr) => {
+                return self.gen_logic_cond_jump(e, jump_if, link)
+            }
+            Expr::BinOp(e) if e.op_fn.is_none() && e.bin_op.is_comparison() => {
+                let l_place = self.gen_temp_var(e.lhs.type_info(&self.types));
+                let lhs = self.visit_expr(&mut e.lhs, Some(l_place), false)?;
+                let r_place = self.gen_temp_var(e.rhs.type_info(&self.types));
+                let rhs = self.visit_expr(&mut e.rhs, Some(r_place), false)?;
+                // `a > b` is `b < a`, `a <= b` is `b >= a`
+                let (op, lhs, rhs) = match e.bin_op {
+                    BinOperator::Gt => (BinOperator::Lt, rhs, lhs),
+                    BinOperator::Le => (BinOperator::Ge, rhs, lhs),
+                    op => (op, lhs, rhs),
+                };
+                let jump = match (op, jump_if) {
+                    (BinOperator::EqEq, true) | (BinOperator::Ne, false) => JEq,
+                    (BinOperator::EqEq, false) | (BinOperator::Ne, true) => JNe,
+                    (BinOperator::Lt, true) | (BinOperator::Ge, false) => JLt,
+                    _ => JGe,
+                };
+                IRInst::jump_if_cond(jump, lhs, rhs, *link)
+            }
+            e => {
+                let operand = match e {
+                    Expr::LitBool(b) => Operand::Bool(*b),
+                    e => {
+                        let d = self.gen_temp_var(TypeInfo::Bool);
+                        self.visit_expr(e, Some(d), false)?
+                    }
+                };
+                match operand {
+                    Operand::Bool(b) if b == jump_if => IRInst::jump(*link),
+                    Operand::Bool(_) => return Ok(()),
+                    operand if jump_if => IRInst::jump_if(operand, *link),
+                    operand => IRInst::jump_if_not(operand, *link),
+                }
+            }
+        };
+        *link = self.ir_output.next_inst_id();
+        self.ir_output.add_instructions(inst);
         Ok(())
     }
 
-    fn gen_jump_cond_reverse(
+    /// Jumps if `a && b` or `a || b` is `jump_if`. `b` is skipped if `a` decides the result.
+    fn gen_logic_cond_jump(
         &mut self,
         e: &mut BinOpExpr,
-        jump: Jump,
-        next_back_patch_link: &mut usize,
+        jump_if: bool,
+        link: &mut usize,
     ) -> Result<(), RccError> {
-        let d = self.gen_temp_var(e.type_info());
-        let lhs = self.visit_expr(&mut e.lhs, Some(d), false)?;
-        let d = self.gen_temp_var(e.type_info());
-        let rhs = self.visit_expr(&mut e.rhs, Some(d), false)?;
-        if *next_back_patch_link != 0 {
-            let jump_label = self.ir_output.next_inst_id();
-            let inst_to_backpatch = self.ir_output.get_inst_by_id(*next_back_patch_link);
-            inst_to_backpatch.set_jump_label(jump_label);
+        let is_and = e.bin_op == BinOperator::AndAnd;
+        if is_and != jump_if {
+            // `A && B` is false if `A` is, `A || B` is true if `A` is
+            self.gen_cond_jump(&mut e.lhs, jump_if, link)?;
+            return self.gen_cond_jump(&mut e.rhs, jump_if, link);
         }
-        let ir_inst = IRInst::jump_if_cond(jump, rhs, lhs, 0);
-        self.ir_output.add_instructions(ir_inst);
-        *next_back_patch_link = self.ir_output.next_inst_id() - 1;
+        let mut skip_link = 0;
+        self.gen_cond_jump(&mut e.lhs, !jump_if, &mut skip_link)?;
+        self.gen_cond_jump(&mut e.rhs, jump_if, link)?;
+        let next_id = self.ir_output.next_inst_id();
+        self.back_patch(skip_link, next_id);
         Ok(())
     }
 
+    /// Set the labels of the jumps in `link` to `label`
+    fn back_patch(&mut self, mut link: usize, label: usize) {
+        while link != 0 {
+            let inst = self.ir_output.get_inst_by_id(link);
+            link = inst.jump_label();
+            inst.set_jump_label(label);
+        }
+    }
+
     fn visit_return_expr(
         &mut self,
         return_expr: &mut ReturnExpr,
         dest: Option<Place>,
     ) -> Result<Operand, RccError> {
         match &mut return_expr.0 {
+            Some(e) if e.type_info(&self.types).is_struct() => {
+                let sret = self.fn_ret_temp_var.last().unwrap().clone();
+                let type_info = e.type_info(&self.types);
+                let srcs = self.visit_struct_value(e)?;
+                self.gen_struct_ret(srcs, &type_info, sret);
+                self.ir_output.add_instructions(IRInst::Ret(Operand::Unit));
+            }
+            Some(e) if matches!(e.type_info(&self.types), TypeInfo::Array(..)) => {
+                let type_info = e.type_info(&self.types);
+                let dest = self.gen_temp_var(type_info.clone());
+                let src = self.visit_expr(e, Some(dest), false)?;
+                self.gen_array_ret(src, &type_info)?;
+                self.ir_output.add_instructions(IRInst::Ret(Operand::Unit));
+            }
             Some(e) => {
                 let ret_place = self.fn_ret_temp_var.last().unwrap();
                 let operand = self.visit_expr(e.as_mut(), Some(ret_place.clone()), false)?;
@@ -928,7 +3329,7 @@ impl IRBuilder {
             Some(e) => {
                 if let Some(p) = break_place {
                     let p = p.clone();
-                    let temp_v = self.gen_temp_var(e.type_info());
+                    let temp_v = self.gen_temp_var(e.type_info(&self.types));
                     let rhs = self.visit_expr(e, Some(temp_v), false)?;
                     self.ir_output.add_instructions(IRInst::load_data(p, rhs));
                 } else {
@@ -957,4 +3358,193 @@ impl IRBuilder {
             None => Ok(Operand::Never),
         }
     }
+
+    /// Every piece of the format string and every argument is written by a call
+    /// to the runtime, e.g. `println!("a = {}", a)` becomes
+    /// `call __rcc_print_str(.LC0); call __rcc_print_int(a); call __rcc_print_str(.LC1)`.
+    fn visit_print_expr(&mut self, print_expr: &mut PrintExpr) -> Result<Operand, RccError> {
+        let PrintExpr {
+            newline,
+            pieces,
+            args,
+        } = print_expr;
+        let last = pieces.len() - 1;
+        for (i, piece) in pieces.iter().enumerate() {
+            let mut piece = piece.clone();
+            if i == last && *newline {
+                piece.push('\n');
+            }
+            if !piece.is_empty() {
+                let s = self.ir_output.add_ro_local_str(piece);
+                self.ir_output.add_instructions(IRInst::call(
+                    Operand::FnLabel(PRINT_STR.to_string()),
+                    vec![s],
+                ));
+            }
+
+            if let Some(arg) = args.get_mut(i) {
+                if arg.type_info(&self.types) == TypeInfo::String {
+                    // printing does not move the string
+                    let s = self.visit_ptr_expr(arg)?;
+                    self.ir_output.add_instructions(IRInst::call(
+                        Operand::FnLabel(PRINT_STRING.to_string()),
+                        vec![s],
+                    ));
+                    continue;
+                }
+                let temp = self.gen_temp_var(arg.type_info(&self.types));
+                let operand = self.visit_expr(arg, Some(temp), false)?;
+                let callee = match IRType::from_type_info(&arg.type_info(&self.types))? {
+                    IRType::I8 | IRType::I16 | IRType::I32 | IRType::I64 | IRType::Isize => {
+                        PRINT_INT
+                    }
+                    IRType::U8 | IRType::U16 | IRType::U32 | IRType::U64 | IRType::Usize => {
+                        PRINT_UINT
+                    }
+                    IRType::Char => PRINT_CHAR,
+                    IRType::Addr => PRINT_STR,
+                    t => unreachable!("`{:?}` can not be printed", t),
+                };
+                self.ir_output.add_instructions(IRInst::call(
+                    Operand::FnLabel(callee.to_string()),
+                    vec![operand],
+                ));
+            }
+        }
+        Ok(Operand::Unit)
+    }
+
+    /// Assertions are checked even if `checks` is off, the message has the line of the macro.
+    ///
+    /// ```
+    /// assert_eq!(a, 1)    ->    (n)   if a == 1i32 goto (n+2)
+    ///                           (n+1) call __rcc_panic("panicked at 'assertion failed:
+    ///                                 a == 1', line <line> in `<fn>`\n")
+    ///                           (n+2) ...
+    /// ```
+    fn visit_assert_expr(&mut self, assert_expr: &mut AssertExpr) -> Result<Operand, RccError> {
+        let msg = match &assert_expr.msg {
+            Some(msg) => msg.clone(),
+            None => format!("assertion failed: {}", expr_str(&assert_expr.cond)),
+        };
+        let msg = self.panic_msg(&msg, assert_expr.line);
+        let mut link = 0;
+        self.gen_cond_jump(&mut assert_expr.cond, true, &mut link)?;
+        self.gen_panic(msg);
+        let label = self.ir_output.next_inst_id();
+        self.back_patch(link, label);
+        Ok(Operand::Unit)
+    }
+
+    fn visit_asm_expr(&mut self, asm_expr: &mut AsmExpr) -> Result<Operand, RccError> {
+        let mut inputs = vec![];
+        for input in asm_expr.inputs.iter_mut() {
+            let temp = self.gen_temp_var(input.type_info(&self.types));
+            inputs.push(self.visit_expr(input, Some(temp), false)?);
+        }
+        let mut outputs = vec![];
+        for output in asm_expr.outputs.iter_mut() {
+            match self.visit_lhs_expr(output)? {
+                Operand::Place(place) => outputs.push(place),
+                o => unreachable!("output of asm: {:?}", o),
+            }
+        }
+        self.ir_output.add_instructions(IRInst::Asm {
+            template: asm_expr.template.clone(),
+            inputs,
+            outputs,
+        });
+        Ok(Operand::Unit)
+    }
+}
+
+/// Elements take the same size on every target like `Box::new`.
+/// `*const ()`, type of the addresses in vtables
+fn raw_ptr() -> TypeInfo {
+    TypeInfo::Ptr {
+        kind: PtrKind::ConstRawPtr,
+        type_info: Box::new(TypeInfo::Unit),
+    }
+}
+
+/// Whether the place `expr` is in the memory of a pointer, e.g. `*r` and `(*r).a.b`, rather
+/// than in variables
+fn is_behind_ptr(expr: &Expr) -> bool {
+    match expr {
+        Expr::Unary(unary_expr) => unary_expr.op == UnOp::Deref,
+        Expr::Grouped(grouped_expr) => is_behind_ptr(grouped_expr),
+        Expr::FieldAccess(field_access_expr) => is_behind_ptr(&field_access_expr.lhs),
+        _ => false,
+    }
+}
+
+fn elem_size(elem_type: &TypeInfo) -> Result<usize, RccError> {
+    Ok(IRType::from_type_info(elem_type)?.byte_size(64) as usize)
+}
+
+/// Bytes of the elements of an array
+fn array_size(type_info: &TypeInfo) -> Result<usize, RccError> {
+    match type_info {
+        TypeInfo::Array(elem_type, len) => Ok(len * elem_size(elem_type)?),
+        t => unreachable!("`{:?}` is not an array", t),
+    }
+}
+
+/// Type of an integer operand
+fn operand_type(operand: &Operand) -> IRType {
+    match operand {
+        Operand::Place(p) => p.ir_type,
+        Operand::I8(_) => IRType::I8,
+        Operand::I16(_) => IRType::I16,
+        Operand::I32(_) => IRType::I32,
+        Operand::I64(_) => IRType::I64,
+        Operand::I128(_) => IRType::I128,
+        Operand::Isize(_) => IRType::Isize,
+        Operand::U8(_) => IRType::U8,
+        Operand::U16(_) => IRType::U16,
+        Operand::U32(_) => IRType::U32,
+        Operand::U64(_) => IRType::U64,
+        Operand::U128(_) => IRType::U128,
+        Operand::Usize(_) => IRType::Usize,
+        o => unreachable!("`{}` is not an integer", o),
+    }
+}
+
+/// Type of the temps holding integers of `ir_type`
+fn int_type_info(ir_type: IRType) -> TypeInfo {
+    TypeInfo::LitNum(match ir_type {
+        IRType::I8 => TypeLitNum::I8,
+        IRType::I16 => TypeLitNum::I16,
+        IRType::I32 => TypeLitNum::I32,
+        IRType::I64 => TypeLitNum::I64,
+        IRType::I128 => TypeLitNum::I128,
+        IRType::Isize => TypeLitNum::Isize,
+        IRType::U8 => TypeLitNum::U8,
+        IRType::U16 => TypeLitNum::U16,
+        IRType::U32 => TypeLitNum::U32,
+        IRType::U64 => TypeLitNum::U64,
+        IRType::U128 => TypeLitNum::U128,
+        IRType::Usize => TypeLitNum::Usize,
+        t => unreachable!("`{:?}` is not an integer type", t),
+    })
+}
+
+/// The literal `value` of the type `t`, which is checked by the symbol resolver
+fn lit_num_operand(value: &str, t: TypeLitNum) -> Result<Operand, RccError> {
+    Ok(match t {
+        TypeLitNum::I8 => Operand::I8(value.parse()?),
+        TypeLitNum::I16 => Operand::I16(value.parse()?),
+        TypeLitNum::I | TypeLitNum::I32 => Operand::I32(value.parse()?),
+        TypeLitNum::I64 => Operand::I64(value.parse()?),
+        TypeLitNum::I128 => Operand::I128(value.parse()?),
+        TypeLitNum::Isize => Operand::Isize(value.parse()?),
+        TypeLitNum::U8 => Operand::U8(value.parse()?),
+        TypeLitNum::U16 => Operand::U16(value.parse()?),
+        TypeLitNum::U32 => Operand::U32(value.parse()?),
+        TypeLitNum::U64 => Operand::U64(value.parse()?),
+        TypeLitNum::U128 => Operand::U128(value.parse()?),
+        TypeLitNum::Usize => Operand::Usize(value.parse()?),
+        TypeLitNum::F32 => Operand::F32(value.parse()?),
+        TypeLitNum::F | TypeLitNum::F64 => Operand::F64(value.parse()?),
+    })
 }
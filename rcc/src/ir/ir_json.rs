@@ -0,0 +1,269 @@
+//! JSON form of IR for external tools, printed by `--emit ir-json`.
+//!
+//! ```text
+//! {"strs":[{"label":".LC0","value":"hello"}],"vtables":[],"funcs":[{"name":"main",
+//!  "pub":true,"args":[],"scope":1,"insts":[{"op":"ret","src":{"kind":"imm","type":"i32",
+//!  "value":"0"}}]}]}
+//! ```
+//!
+//! Instructions keep the fields of `IRInst`, the jump labels refer to instructions in the
+//! same way as the textual form. Immediate values are strings so that 128-bit integers
+//! are exact.
+use crate::analyser::sym_resolver::VarKind;
+use crate::ir::linear_ir::{Func, LinearIR};
+use crate::ir::{IRInst, Operand, Place};
+use crate::json::Json;
+
+impl From<&Place> for Json {
+    fn from(place: &Place) -> Self {
+        let var_kind = match place.kind {
+            VarKind::Local => "local",
+            VarKind::LocalMut => "mut",
+            VarKind::Const => "const",
+            VarKind::Static => "static",
+            VarKind::LitConst => "lit",
+        };
+        Json::object(vec![
+            ("kind", Json::from("place")),
+            ("label", Json::from(place.label.as_str())),
+            ("var_kind", Json::from(var_kind)),
+            ("type", Json::from(place.ir_type.to_string().as_str())),
+        ])
+    }
+}
+
+fn imm(ir_type: &str, value: String) -> Json {
+    Json::object(vec![
+        ("kind", Json::from("imm")),
+        ("type", Json::from(ir_type)),
+        ("value", Json::String(value)),
+    ])
+}
+
+impl From<&Operand> for Json {
+    fn from(operand: &Operand) -> Self {
+        match operand {
+            Operand::F32(n) => imm("f32", format!("{:?}", n)),
+            Operand::F64(n) => imm("f64", format!("{:?}", n)),
+            Operand::Bool(b) => imm("bool", b.to_string()),
+            Operand::Char(c) => imm("char", c.to_string()),
+            Operand::I8(n) => imm("i8", n.to_string()),
+            Operand::I16(n) => imm("i16", n.to_string()),
+            Operand::I32(n) => imm("i32", n.to_string()),
+            Operand::I64(n) => imm("i64", n.to_string()),
+            Operand::I128(n) => imm("i128", n.to_string()),
+            Operand::Isize(n) => imm("isize", n.to_string()),
+            Operand::U8(n) => imm("u8", n.to_string()),
+            Operand::U16(n) => imm("u16", n.to_string()),
+            Operand::U32(n) => imm("u32", n.to_string()),
+            Operand::U64(n) => imm("u64", n.to_string()),
+            Operand::U128(n) => imm("u128", n.to_string()),
+            Operand::Usize(n) => imm("usize", n.to_string()),
+            Operand::Unit => Json::object(vec![("kind", Json::from("unit"))]),
+            Operand::Never => Json::object(vec![("kind", Json::from("never"))]),
+            Operand::Place(place) => Json::from(place),
+            Operand::FnLabel(name) => Json::object(vec![
+                ("kind", Json::from("fn")),
+                ("name", Json::from(name.as_str())),
+            ]),
+            Operand::FnRetPlace(ir_type) => Json::object(vec![
+                ("kind", Json::from("ret")),
+                ("type", Json::from(ir_type.to_string().as_str())),
+            ]),
+        }
+    }
+}
+
+impl From<&IRInst> for Json {
+    fn from(inst: &IRInst) -> Self {
+        let label = |label: &usize| Json::from(*label as u64);
+        match inst {
+            IRInst::BinOp {
+                op,
+                dest,
+                src1,
+                src2,
+            } => Json::object(vec![
+                ("op", Json::from("bin_op")),
+                ("bin_op", Json::from(op.to_string().as_str())),
+                ("dest", Json::from(dest)),
+                ("src1", Json::from(src1)),
+                ("src2", Json::from(src2)),
+            ]),
+            IRInst::Jump { label: l } => {
+                Json::object(vec![("op", Json::from("jump")), ("label", label(l))])
+            }
+            IRInst::JumpIfCond {
+                cond,
+                src1,
+                src2,
+                label: l,
+            } => Json::object(vec![
+                ("op", Json::from("jump_if_cond")),
+                ("cond", Json::from(cond.to_string().as_str())),
+                ("src1", Json::from(src1)),
+                ("src2", Json::from(src2)),
+                ("label", label(l)),
+            ]),
+            IRInst::JumpIf { cond, label: l } => Json::object(vec![
+                ("op", Json::from("jump_if")),
+                ("cond", Json::from(cond)),
+                ("label", label(l)),
+            ]),
+            IRInst::JumpIfNot { cond, label: l } => Json::object(vec![
+                ("op", Json::from("jump_if_not")),
+                ("cond", Json::from(cond)),
+                ("label", label(l)),
+            ]),
+            IRInst::Switch {
+                src,
+                cases,
+                default,
+            } => Json::object(vec![
+                ("op", Json::from("switch")),
+                ("src", Json::from(src)),
+                (
+                    "cases",
+                    Json::Array(
+                        cases
+                            .iter()
+                            .map(|(value, l)| {
+                                Json::object(vec![
+                                    ("value", Json::String(value.to_string())),
+                                    ("label", label(l)),
+                                ])
+                            })
+                            .collect(),
+                    ),
+                ),
+                ("default", label(default)),
+            ]),
+            IRInst::LoadData { dest, src } => Json::object(vec![
+                ("op", Json::from("load_data")),
+                ("dest", Json::from(dest)),
+                ("src", Json::from(src)),
+            ]),
+            IRInst::LoadAddr { dest, symbol } => Json::object(vec![
+                ("op", Json::from("load_addr")),
+                ("dest", Json::from(dest)),
+                ("symbol", Json::from(symbol)),
+            ]),
+            IRInst::Load {
+                ty,
+                dest,
+                addr,
+                volatile,
+            } => Json::object(vec![
+                ("op", Json::from("load")),
+                ("type", Json::from(ty.to_string().as_str())),
+                ("dest", Json::from(dest)),
+                ("addr", Json::from(addr)),
+                ("volatile", Json::Bool(*volatile)),
+            ]),
+            IRInst::Store {
+                ty,
+                addr,
+                src,
+                volatile,
+            } => Json::object(vec![
+                ("op", Json::from("store")),
+                ("type", Json::from(ty.to_string().as_str())),
+                ("addr", Json::from(addr)),
+                ("src", Json::from(src)),
+                ("volatile", Json::Bool(*volatile)),
+            ]),
+            IRInst::Call { callee, args } => Json::object(vec![
+                ("op", Json::from("call")),
+                ("callee", Json::from(callee)),
+                ("args", Json::Array(args.iter().map(Json::from).collect())),
+            ]),
+            IRInst::Ret(operand) => Json::object(vec![
+                ("op", Json::from("ret")),
+                ("src", Json::from(operand)),
+            ]),
+            IRInst::Asm {
+                template,
+                inputs,
+                outputs,
+            } => Json::object(vec![
+                ("op", Json::from("asm")),
+                ("template", Json::from(template.as_str())),
+                ("inputs", Json::Array(inputs.iter().map(Json::from).collect())),
+                ("outputs", Json::Array(outputs.iter().map(Json::from).collect())),
+            ]),
+            IRInst::Memcpy { size, srcs, dests } => Json::object(vec![
+                ("op", Json::from("memcpy")),
+                ("size", Json::from(*size as u64)),
+                ("srcs", Json::Array(srcs.iter().map(Json::from).collect())),
+                ("dests", Json::Array(dests.iter().map(Json::from).collect())),
+            ]),
+            IRInst::BoundsCheck { index, len, msg } => Json::object(vec![
+                ("op", Json::from("bounds_check")),
+                ("index", Json::from(index)),
+                ("len", Json::from(len)),
+                ("msg", Json::from(msg)),
+            ]),
+            IRInst::Loc(line) => Json::object(vec![
+                ("op", Json::from("loc")),
+                ("line", Json::from(*line as u64)),
+            ]),
+        }
+    }
+}
+
+impl From<&Func> for Json {
+    fn from(func: &Func) -> Self {
+        let args = func
+            .fn_args
+            .iter()
+            .map(|(name, ir_type)| {
+                Json::object(vec![
+                    ("name", Json::from(name.as_str())),
+                    ("type", Json::from(ir_type.to_string().as_str())),
+                ])
+            })
+            .collect();
+        Json::object(vec![
+            ("name", Json::from(func.name.as_str())),
+            ("pub", Json::Bool(func.is_global)),
+            ("args", Json::Array(args)),
+            ("scope", Json::from(func.block_scope_id)),
+            ("section", func.link_section.as_deref().map_or(Json::Null, Json::from)),
+            ("cold", Json::Bool(func.is_cold)),
+            ("insts", Json::Array(func.insts.iter().map(Json::from).collect())),
+        ])
+    }
+}
+
+impl From<&LinearIR> for Json {
+    fn from(ir: &LinearIR) -> Self {
+        let strs = ir
+            .ro_local_strs
+            .iter()
+            .map(|(label, s)| {
+                Json::object(vec![
+                    ("label", Json::from(label.as_str())),
+                    ("value", Json::from(s.as_str())),
+                ])
+            })
+            .collect();
+        let vtables = ir
+            .vtables
+            .iter()
+            .map(|(symbol, methods)| {
+                Json::object(vec![
+                    ("symbol", Json::from(symbol.as_str())),
+                    (
+                        "methods",
+                        Json::Array(methods.iter().map(|m| Json::from(m.as_str())).collect()),
+                    ),
+                ])
+            })
+            .collect();
+        Json::object(vec![
+            ("strs", Json::Array(strs)),
+            ("vtables", Json::Array(vtables)),
+            ("funcs", Json::Array(ir.funcs.iter().map(Json::from).collect())),
+        ])
+    }
+}
@@ -0,0 +1,879 @@
+//! Textual form of IR, like three-address code.
+//!
+//! ```text
+//! .LC0 = "hello"
+//! vtable _RCV3i324Show = [@_RCM3i324Show4show]
+//!
+//! pub fn max(a: i32, b: i32) scope 1 {
+//!     (1) if a_1:i32 >= b_1:i32 goto (4)
+//!     (2) mut $1_1:i32 = a_1:i32 + 1i32
+//!     (3) goto (5)
+//!     (4) call @foo(b_1:i32, 'c')
+//!     (5) ret ()
+//! }
+//! ```
+//!
+//! A switch is printed as `switch a_1:i32 [1: goto (3), 2: goto (5)] else goto (7)`, and
+//! the line of the source of the following instructions as `loc 3`. Inline assembly is
+//! printed as `asm "csrw mscratch, {0}" (a_1:usize) -> ()` with its inputs and outputs, and
+//! a copy of an aggregate as `memcpy 40 (a.0_1:i64, ...) -> (mut b.0_2:i64, ...)`. A bounds
+//! check is printed as `check i_2:usize < 3usize else panic(lit .LC0:char)`. A load and a
+//! store carry the type in memory, `$0_1:i32 = load i32 *p_1:addr` and
+//! `store u8 *p_1:addr = 1u8`, and are prefixed by `volatile` if they are volatile.
+//!
+//! A place is printed as `[kind] label:type`, where kind is one of
+//! `mut`, `const`, `static`, `lit` or omitted for immutable local variables.
+//! Immediates carry their type as a suffix except `bool`, `char`, `()` and `!`.
+//! `@name` is a function label and `$ret:type` is the return value place of a call.
+//!
+//! The basic blocks of a CFG are printed as `bbN:` followed by their instructions, and its
+//! jump labels, which are ids of basic blocks, as `goto bbN`. Only linear IR can be parsed.
+use crate::analyser::sym_resolver::VarKind;
+use crate::ast::expr::BinOperator;
+use crate::intern::Symbol;
+use crate::ir::cfg::CFG;
+use crate::ir::linear_ir::{Func, LinearIR};
+use crate::ir::{IRInst, IRType, Jump, Operand, Place};
+use crate::rcc::RccError;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+const FN_RET_PLACE: &str = "$ret";
+
+impl Display for IRType {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            IRType::F32 => "f32",
+            IRType::F64 => "f64",
+            IRType::Bool => "bool",
+            IRType::Char => "char",
+            IRType::I8 => "i8",
+            IRType::I16 => "i16",
+            IRType::I32 => "i32",
+            IRType::I64 => "i64",
+            IRType::I128 => "i128",
+            IRType::Isize => "isize",
+            IRType::U8 => "u8",
+            IRType::U16 => "u16",
+            IRType::U32 => "u32",
+            IRType::U64 => "u64",
+            IRType::U128 => "u128",
+            IRType::Usize => "usize",
+            IRType::Unit => "()",
+            IRType::Never => "!",
+            IRType::Addr => "addr",
+            IRType::Array(n) => return write!(f, "[{}]", n),
+        };
+        write!(f, "{}", s)
+    }
+}
+
+impl FromStr for IRType {
+    type Err = RccError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "f32" => IRType::F32,
+            "f64" => IRType::F64,
+            "bool" => IRType::Bool,
+            "char" => IRType::Char,
+            "i8" => IRType::I8,
+            "i16" => IRType::I16,
+            "i32" => IRType::I32,
+            "i64" => IRType::I64,
+            "i128" => IRType::I128,
+            "isize" => IRType::Isize,
+            "u8" => IRType::U8,
+            "u16" => IRType::U16,
+            "u32" => IRType::U32,
+            "u64" => IRType::U64,
+            "u128" => IRType::U128,
+            "usize" => IRType::Usize,
+            "()" => IRType::Unit,
+            "!" => IRType::Never,
+            "addr" => IRType::Addr,
+            _ if s.starts_with('[') && s.ends_with(']') => match s[1..s.len() - 1].parse() {
+                Ok(n) => IRType::Array(n),
+                Err(_) => return Err(format!("invalid IR type `{}`", s).into()),
+            },
+            _ => return Err(format!("invalid IR type `{}`", s).into()),
+        })
+    }
+}
+
+impl Display for Place {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self.kind {
+            VarKind::Local => {}
+            VarKind::LocalMut => write!(f, "mut ")?,
+            VarKind::Const => write!(f, "const ")?,
+            VarKind::Static => write!(f, "static ")?,
+            VarKind::LitConst => write!(f, "lit ")?,
+        }
+        write!(f, "{}:{}", self.label, self.ir_type)
+    }
+}
+
+impl Display for Operand {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Operand::F32(n) => write!(f, "{:?}f32", n),
+            Operand::F64(n) => write!(f, "{:?}f64", n),
+            Operand::Bool(b) => write!(f, "{}", b),
+            Operand::Char(c) => write!(f, "'{}'", c.escape_default()),
+            Operand::I8(n) => write!(f, "{}i8", n),
+            Operand::I16(n) => write!(f, "{}i16", n),
+            Operand::I32(n) => write!(f, "{}i32", n),
+            Operand::I64(n) => write!(f, "{}i64", n),
+            Operand::I128(n) => write!(f, "{}i128", n),
+            Operand::Isize(n) => write!(f, "{}isize", n),
+            Operand::U8(n) => write!(f, "{}u8", n),
+            Operand::U16(n) => write!(f, "{}u16", n),
+            Operand::U32(n) => write!(f, "{}u32", n),
+            Operand::U64(n) => write!(f, "{}u64", n),
+            Operand::U128(n) => write!(f, "{}u128", n),
+            Operand::Usize(n) => write!(f, "{}usize", n),
+            Operand::Place(p) => write!(f, "{}", p),
+            Operand::FnLabel(name) => write!(f, "@{}", name),
+            Operand::Unit => write!(f, "()"),
+            Operand::Never => write!(f, "!"),
+            Operand::FnRetPlace(ir_type) => write!(f, "{}:{}", FN_RET_PLACE, ir_type),
+        }
+    }
+}
+
+impl Display for Jump {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            Jump::JEq => "==",
+            Jump::JNe => "!=",
+            Jump::JLt => "<",
+            Jump::JGe => ">=",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+impl Display for IRInst {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            IRInst::BinOp {
+                op,
+                dest,
+                src1,
+                src2,
+            } => write!(f, "{} = {} {} {}", dest, src1, op, src2),
+            IRInst::Jump { label } => write!(f, "goto ({})", label),
+            IRInst::JumpIfCond {
+                cond,
+                src1,
+                src2,
+                label,
+            } => write!(f, "if {} {} {} goto ({})", src1, cond, src2, label),
+            IRInst::JumpIf { cond, label } => write!(f, "if {} goto ({})", cond, label),
+            IRInst::JumpIfNot { cond, label } => write!(f, "if not {} goto ({})", cond, label),
+            IRInst::Switch {
+                src,
+                cases,
+                default,
+            } => {
+                write!(f, "switch {} [", src)?;
+                for (i, (value, label)) in cases.iter().enumerate() {
+                    if i != 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}: goto ({})", value, label)?;
+                }
+                write!(f, "] else goto ({})", default)
+            }
+            IRInst::LoadData { dest, src } => write!(f, "{} = {}", dest, src),
+            IRInst::LoadAddr { dest, symbol } => write!(f, "{} = &{}", dest, symbol),
+            IRInst::Load {
+                ty,
+                dest,
+                addr,
+                volatile,
+            } => {
+                let volatile = if *volatile { "volatile " } else { "" };
+                write!(f, "{} = {}load {} *{}", dest, volatile, ty, addr)
+            }
+            IRInst::Store {
+                ty,
+                addr,
+                src,
+                volatile,
+            } => {
+                let volatile = if *volatile { "volatile " } else { "" };
+                write!(f, "{}store {} *{} = {}", volatile, ty, addr, src)
+            }
+            IRInst::Call { callee, args } => {
+                write!(f, "call {}(", callee)?;
+                for (i, arg) in args.iter().enumerate() {
+                    if i != 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", arg)?;
+                }
+                write!(f, ")")
+            }
+            IRInst::Ret(operand) => write!(f, "ret {}", operand),
+            IRInst::Asm {
+                template,
+                inputs,
+                outputs,
+            } => {
+                write!(f, "asm \"{}\" (", template.escape_default())?;
+                for (i, input) in inputs.iter().enumerate() {
+                    if i != 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", input)?;
+                }
+                write!(f, ") -> (")?;
+                for (i, output) in outputs.iter().enumerate() {
+                    if i != 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", output)?;
+                }
+                write!(f, ")")
+            }
+            IRInst::Memcpy { size, srcs, dests } => {
+                write!(f, "memcpy {} (", size)?;
+                for (i, src) in srcs.iter().enumerate() {
+                    if i != 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", src)?;
+                }
+                write!(f, ") -> (")?;
+                for (i, dest) in dests.iter().enumerate() {
+                    if i != 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", dest)?;
+                }
+                write!(f, ")")
+            }
+            IRInst::BoundsCheck { index, len, msg } => {
+                write!(f, "check {} < {} else panic({})", index, len, msg)
+            }
+            IRInst::Loc(line) => write!(f, "loc {}", line),
+        }
+    }
+}
+
+/// `pub fn name(args) scope N section "s" cold {`
+fn write_fn_header(
+    f: &mut Formatter<'_>,
+    is_global: bool,
+    name: &str,
+    fn_args: &[(Symbol, IRType)],
+    scope_id: u64,
+    link_section: &Option<String>,
+    is_cold: bool,
+) -> std::fmt::Result {
+    if is_global {
+        write!(f, "pub ")?;
+    }
+    write!(f, "fn {}(", name)?;
+    for (i, (arg, ir_type)) in fn_args.iter().enumerate() {
+        if i != 0 {
+            write!(f, ", ")?;
+        }
+        write!(f, "{}: {}", arg, ir_type)?;
+    }
+    write!(f, ") scope {}", scope_id)?;
+    if let Some(section) = link_section {
+        write!(f, " section \"{}\"", section)?;
+    }
+    if is_cold {
+        write!(f, " cold")?;
+    }
+    writeln!(f, " {{")
+}
+
+impl Display for Func {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write_fn_header(
+            f,
+            self.is_global,
+            &self.name,
+            &self.fn_args,
+            self.block_scope_id,
+            &self.link_section,
+            self.is_cold,
+        )?;
+        for (i, inst) in self.insts.iter().enumerate() {
+            writeln!(f, "    ({}) {}", i + 1, inst)?;
+        }
+        writeln!(f, "}}")
+    }
+}
+
+impl Display for CFG {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write_fn_header(
+            f,
+            self.func_is_global,
+            &self.func_name,
+            &self.fn_args,
+            self.func_scope_id,
+            &self.func_link_section,
+            self.func_is_cold,
+        )?;
+        for bb in self.basic_blocks.iter() {
+            writeln!(f, "bb{}:", bb.id)?;
+            for inst in bb.instructions.iter() {
+                writeln!(f, "    {}", cfg_inst_text(inst))?;
+            }
+        }
+        writeln!(f, "}}")
+    }
+}
+
+/// Textual form of an instruction of a CFG, with `goto (N)` replaced by `goto bbN`
+pub fn cfg_inst_text(inst: &IRInst) -> String {
+    let mut text = inst.to_string();
+    for label in inst.jump_labels() {
+        text = text.replace(&format!("goto ({})", label), &format!("goto bb{}", label));
+    }
+    text
+}
+
+impl Display for LinearIR {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for (label, s) in self.ro_local_strs.iter() {
+            writeln!(f, "{} = \"{}\"", label, s.escape_default())?;
+        }
+        for (symbol, methods) in self.vtables.iter() {
+            write!(f, "vtable {} = [", symbol)?;
+            for (i, method) in methods.iter().enumerate() {
+                if i != 0 {
+                    write!(f, ", ")?;
+                }
+                write!(f, "@{}", method)?;
+            }
+            writeln!(f, "]")?;
+        }
+        let has_data = !self.ro_local_strs.is_empty() || !self.vtables.is_empty();
+        for (i, func) in self.funcs.iter().enumerate() {
+            if i != 0 || has_data {
+                writeln!(f)?;
+            }
+            write!(f, "{}", func)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for LinearIR {
+    type Err = RccError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut ir = LinearIR::new();
+        let mut lines = s.lines().enumerate();
+        while let Some((line_no, line)) = lines.next() {
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            let mut parser = IRTextParser::new(line, line_no);
+            if line.starts_with(".LC") {
+                let (label, s) = parser.ro_local_str()?;
+                ir.ro_local_strs.insert(label, s);
+                continue;
+            }
+            if parser.eat_keyword_if("vtable") {
+                let (symbol, methods) = parser.vtable()?;
+                ir.vtables.insert(symbol, methods);
+                continue;
+            }
+            ir.funcs.push(parser.func_header()?);
+            loop {
+                let (line_no, line) = match lines.next() {
+                    Some((line_no, line)) => (line_no, line.trim()),
+                    None => return Err("unexpected end of IR: expect `}`".into()),
+                };
+                if line == "}" {
+                    break;
+                }
+                let inst = IRTextParser::new(line, line_no).inst()?;
+                ir.cur_func_mut().insts.push_back(inst);
+            }
+        }
+        Ok(ir)
+    }
+}
+
+/// Parser of one line of IR text.
+struct IRTextParser<'a> {
+    line: &'a str,
+    rest: &'a str,
+    line_no: usize,
+}
+
+impl<'a> IRTextParser<'a> {
+    fn new(line: &'a str, line_no: usize) -> IRTextParser<'a> {
+        IRTextParser {
+            line,
+            rest: line,
+            line_no,
+        }
+    }
+
+    fn err<T>(&self, expected: &str) -> Result<T, RccError> {
+        Err(format!(
+            "invalid IR at line {}: expect {} at `{}`, in `{}`",
+            self.line_no + 1,
+            expected,
+            self.rest,
+            self.line
+        )
+        .into())
+    }
+
+    fn skip_whitespace(&mut self) {
+        self.rest = self.rest.trim_start();
+    }
+
+    fn is_end(&mut self) -> bool {
+        self.skip_whitespace();
+        self.rest.is_empty()
+    }
+
+    fn eat_if(&mut self, s: &str) -> bool {
+        self.skip_whitespace();
+        if self.rest.starts_with(s) {
+            self.rest = &self.rest[s.len()..];
+            true
+        } else {
+            false
+        }
+    }
+
+    fn eat(&mut self, s: &str) -> Result<(), RccError> {
+        if self.eat_if(s) {
+            Ok(())
+        } else {
+            self.err(&format!("`{}`", s))
+        }
+    }
+
+    /// Eat a keyword followed by whitespace.
+    fn eat_keyword_if(&mut self, keyword: &str) -> bool {
+        self.skip_whitespace();
+        match self.rest.strip_prefix(keyword) {
+            Some(rest) if rest.starts_with(char::is_whitespace) => {
+                self.rest = rest;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Eat `[volatile] load type *` or `[volatile] store type *`, and return whether the access
+    /// is volatile and its type.
+    fn access_if(&mut self, keyword: &str) -> Result<Option<(bool, IRType)>, RccError> {
+        let volatile = self.eat_keyword_if("volatile");
+        if !self.eat_keyword_if(keyword) {
+            return if volatile { self.err(&format!("`{}`", keyword)) } else { Ok(None) };
+        }
+        let ty = self.ir_type()?;
+        self.eat("*")?;
+        Ok(Some((volatile, ty)))
+    }
+
+    /// Characters until whitespace or `end`.
+    fn word(&mut self, end: &str) -> &'a str {
+        self.skip_whitespace();
+        let len = self
+            .rest
+            .find(|c: char| c.is_whitespace() || end.contains(c))
+            .unwrap_or(self.rest.len());
+        let word = &self.rest[..len];
+        self.rest = &self.rest[len..];
+        word
+    }
+
+    fn ir_type(&mut self) -> Result<IRType, RccError> {
+        if self.eat_if("()") {
+            return Ok(IRType::Unit);
+        }
+        IRType::from_str(self.word(",()"))
+    }
+
+    fn label(&mut self) -> Result<usize, RccError> {
+        self.eat("(")?;
+        let label = self.word(")");
+        self.eat(")")?;
+        Ok(usize::from_str(label)?)
+    }
+
+    fn quoted(&mut self, quote: char) -> Result<String, RccError> {
+        self.skip_whitespace();
+        let mut chars = self.rest.chars();
+        if chars.next() != Some(quote) {
+            return self.err(&format!("`{}`", quote));
+        }
+        let mut s = String::new();
+        loop {
+            let c = match chars.next() {
+                Some(c) => c,
+                None => return self.err(&format!("`{}`", quote)),
+            };
+            if c == quote {
+                break;
+            }
+            if c != '\\' {
+                s.push(c);
+                continue;
+            }
+            let escaped = match chars.next() {
+                Some('n') => '\n',
+                Some('r') => '\r',
+                Some('t') => '\t',
+                Some('0') => '\0',
+                Some('\\') => '\\',
+                Some('\'') => '\'',
+                Some('"') => '"',
+                // \u{7f}
+                Some('u') => {
+                    let hex: String = chars.by_ref().skip(1).take_while(|c| *c != '}').collect();
+                    match u32::from_str_radix(&hex, 16).ok().and_then(std::char::from_u32) {
+                        Some(c) => c,
+                        None => return self.err("unicode escape"),
+                    }
+                }
+                _ => return self.err("escape character"),
+            };
+            s.push(escaped);
+        }
+        self.rest = chars.as_str();
+        Ok(s)
+    }
+
+    /// .LC0 = "hello"
+    fn ro_local_str(&mut self) -> Result<(String, String), RccError> {
+        let label = self.word("=").to_string();
+        self.eat("=")?;
+        let s = self.quoted('"')?;
+        Ok((label, s))
+    }
+
+    /// vtable _RCV3i324Show = [@_RCM3i324Show4show, ...]
+    fn vtable(&mut self) -> Result<(String, Vec<String>), RccError> {
+        let symbol = self.word("=").to_string();
+        self.eat("=")?;
+        self.eat("[")?;
+        let mut methods = vec![];
+        while !self.eat_if("]") {
+            if !methods.is_empty() {
+                self.eat(",")?;
+            }
+            self.eat("@")?;
+            methods.push(self.word(",]").to_string());
+        }
+        if !self.is_end() {
+            return self.err("end of line");
+        }
+        Ok((symbol, methods))
+    }
+
+    /// [pub] fn name(arg: type, ...) scope id [section "name"] [cold] {
+    fn func_header(&mut self) -> Result<Func, RccError> {
+        let is_global = self.eat_keyword_if("pub");
+        if !self.eat_keyword_if("fn") {
+            return self.err("`fn`");
+        }
+        let name = self.word("(").to_string();
+        self.eat("(")?;
+        let mut fn_args = vec![];
+        while !self.eat_if(")") {
+            if !fn_args.is_empty() {
+                self.eat(",")?;
+            }
+            let arg = Symbol::intern(self.word(":"));
+            self.eat(":")?;
+            fn_args.push((arg, self.ir_type()?));
+        }
+        if !self.eat_keyword_if("scope") {
+            return self.err("`scope`");
+        }
+        let scope_id = u64::from_str(self.word("{"))?;
+        let mut func = Func::new(name, is_global, fn_args, scope_id);
+        if self.eat_keyword_if("section") {
+            func.link_section = Some(self.quoted('"')?);
+        }
+        func.is_cold = self.eat_keyword_if("cold");
+        self.eat("{")?;
+        if !self.is_end() {
+            return self.err("end of line");
+        }
+        Ok(func)
+    }
+
+    fn inst(&mut self) -> Result<IRInst, RccError> {
+        // ignore the instruction id
+        if self.rest.starts_with('(') {
+            self.label()?;
+        }
+        let inst = if self.eat_keyword_if("goto") {
+            IRInst::jump(self.label()?)
+        } else if self.eat_keyword_if("if") {
+            if self.eat_keyword_if("not") {
+                let cond = self.operand()?;
+                self.goto_label(|label| IRInst::jump_if_not(cond, label))?
+            } else {
+                let src1 = self.operand()?;
+                if self.eat_keyword_if("goto") {
+                    IRInst::jump_if(src1, self.label()?)
+                } else {
+                    let cond = match self.word("") {
+                        "==" => Jump::JEq,
+                        "!=" => Jump::JNe,
+                        "<" => Jump::JLt,
+                        ">=" => Jump::JGe,
+                        _ => return self.err("jump condition"),
+                    };
+                    let src2 = self.operand()?;
+                    self.goto_label(|label| IRInst::jump_if_cond(cond, src1, src2, label))?
+                }
+            }
+        } else if self.eat_keyword_if("switch") {
+            let src = self.operand()?;
+            self.eat("[")?;
+            let mut cases = vec![];
+            while !self.eat_if("]") {
+                if !cases.is_empty() {
+                    self.eat(",")?;
+                }
+                let value = i64::from_str(self.word(":"))?;
+                self.eat(":")?;
+                if !self.eat_keyword_if("goto") {
+                    return self.err("`goto`");
+                }
+                cases.push((value, self.label()?));
+            }
+            if !self.eat_keyword_if("else") {
+                return self.err("`else`");
+            }
+            self.goto_label(|default| IRInst::Switch {
+                src,
+                cases,
+                default,
+            })?
+        } else if self.eat_keyword_if("call") {
+            let callee = self.operand()?;
+            self.eat("(")?;
+            let mut args = vec![];
+            while !self.eat_if(")") {
+                if !args.is_empty() {
+                    self.eat(",")?;
+                }
+                args.push(self.operand()?);
+            }
+            IRInst::call(callee, args)
+        } else if self.eat_keyword_if("ret") {
+            IRInst::Ret(self.operand()?)
+        } else if self.eat_keyword_if("asm") {
+            let template = self.quoted('"')?;
+            self.eat("(")?;
+            let mut inputs = vec![];
+            while !self.eat_if(")") {
+                if !inputs.is_empty() {
+                    self.eat(",")?;
+                }
+                inputs.push(self.operand()?);
+            }
+            self.eat("->")?;
+            self.eat("(")?;
+            let mut outputs = vec![];
+            while !self.eat_if(")") {
+                if !outputs.is_empty() {
+                    self.eat(",")?;
+                }
+                outputs.push(self.place()?);
+            }
+            IRInst::Asm {
+                template,
+                inputs,
+                outputs,
+            }
+        } else if self.eat_keyword_if("memcpy") {
+            let size = u32::from_str(self.word(""))?;
+            self.eat("(")?;
+            let mut srcs = vec![];
+            while !self.eat_if(")") {
+                if !srcs.is_empty() {
+                    self.eat(",")?;
+                }
+                srcs.push(self.operand()?);
+            }
+            self.eat("->")?;
+            self.eat("(")?;
+            let mut dests = vec![];
+            while !self.eat_if(")") {
+                if !dests.is_empty() {
+                    self.eat(",")?;
+                }
+                dests.push(self.place()?);
+            }
+            IRInst::Memcpy { size, srcs, dests }
+        } else if self.eat_keyword_if("check") {
+            let index = self.operand()?;
+            self.eat("<")?;
+            let len = self.operand()?;
+            if !self.eat_keyword_if("else") {
+                return self.err("`else`");
+            }
+            self.eat("panic(")?;
+            let msg = self.operand()?;
+            self.eat(")")?;
+            IRInst::BoundsCheck { index, len, msg }
+        } else if self.eat_keyword_if("loc") {
+            IRInst::Loc(u32::from_str(self.word(""))?)
+        } else if let Some((volatile, ty)) = self.access_if("store")? {
+            let addr = self.operand()?;
+            self.eat("=")?;
+            IRInst::Store {
+                ty,
+                addr,
+                src: self.operand()?,
+                volatile,
+            }
+        } else {
+            let dest = self.place()?;
+            self.eat("=")?;
+            if self.eat_if("&") {
+                IRInst::LoadAddr {
+                    dest,
+                    symbol: self.operand()?,
+                }
+            } else if let Some((volatile, ty)) = self.access_if("load")? {
+                IRInst::Load {
+                    ty,
+                    dest,
+                    addr: self.operand()?,
+                    volatile,
+                }
+            } else {
+                let src1 = self.operand()?;
+                if self.is_end() {
+                    IRInst::load_data(dest, src1)
+                } else {
+                    let op = match BinOperator::from_str(self.word("")) {
+                        Ok(op) => op,
+                        Err(_) => return self.err("binary operator"),
+                    };
+                    let src2 = self.operand()?;
+                    IRInst::BinOp {
+                        op,
+                        dest,
+                        src1,
+                        src2,
+                    }
+                }
+            }
+        };
+        if !self.is_end() {
+            return self.err("end of line");
+        }
+        Ok(inst)
+    }
+
+    fn goto_label(&mut self, f: impl FnOnce(usize) -> IRInst) -> Result<IRInst, RccError> {
+        if !self.eat_keyword_if("goto") {
+            return self.err("`goto`");
+        }
+        Ok(f(self.label()?))
+    }
+
+    fn place(&mut self) -> Result<Place, RccError> {
+        let kind = if self.eat_keyword_if("mut") {
+            VarKind::LocalMut
+        } else if self.eat_keyword_if("const") {
+            VarKind::Const
+        } else if self.eat_keyword_if("static") {
+            VarKind::Static
+        } else if self.eat_keyword_if("lit") {
+            VarKind::LitConst
+        } else {
+            VarKind::Local
+        };
+        let label = self.word(":");
+        if label.is_empty() {
+            return self.err("place");
+        }
+        let label = Symbol::intern(label);
+        self.eat(":")?;
+        Ok(Place::new(label, kind, self.ir_type()?))
+    }
+
+    fn operand(&mut self) -> Result<Operand, RccError> {
+        self.skip_whitespace();
+        let first = match self.rest.chars().next() {
+            Some(c) => c,
+            None => return self.err("operand"),
+        };
+        if first == '\'' {
+            let s = self.quoted('\'')?;
+            let mut chars = s.chars();
+            return match (chars.next(), chars.next()) {
+                (Some(c), None) => Ok(Operand::Char(c)),
+                _ => self.err("char"),
+            };
+        }
+        if self.eat_if("()") {
+            return Ok(Operand::Unit);
+        }
+        if first == '!' {
+            self.eat("!")?;
+            return Ok(Operand::Never);
+        }
+        if first == '@' {
+            self.eat("@")?;
+            return Ok(Operand::FnLabel(self.word(",()").to_string()));
+        }
+        if self.eat_if(FN_RET_PLACE) {
+            self.eat(":")?;
+            return Ok(Operand::FnRetPlace(self.ir_type()?));
+        }
+        if first.is_ascii_digit() || first == '-' {
+            return self.number();
+        }
+        let rest = self.rest;
+        match self.word(",)") {
+            "true" => return Ok(Operand::Bool(true)),
+            "false" => return Ok(Operand::Bool(false)),
+            _ => self.rest = rest,
+        }
+        Ok(Operand::Place(self.place()?))
+    }
+
+    fn number(&mut self) -> Result<Operand, RccError> {
+        let word = self.word(",)");
+        let suffix_start = match word.find(&['i', 'u', 'f'][..]) {
+            Some(i) => i,
+            None => return self.err("number with type suffix"),
+        };
+        let (n, suffix) = word.split_at(suffix_start);
+        Ok(match suffix {
+            "f32" => Operand::F32(f32::from_str(n)?),
+            "f64" => Operand::F64(f64::from_str(n)?),
+            "i8" => Operand::I8(i8::from_str(n)?),
+            "i16" => Operand::I16(i16::from_str(n)?),
+            "i32" => Operand::I32(i32::from_str(n)?),
+            "i64" => Operand::I64(i64::from_str(n)?),
+            "i128" => Operand::I128(i128::from_str(n)?),
+            "isize" => Operand::Isize(isize::from_str(n)?),
+            "u8" => Operand::U8(u8::from_str(n)?),
+            "u16" => Operand::U16(u16::from_str(n)?),
+            "u32" => Operand::U32(u32::from_str(n)?),
+            "u64" => Operand::U64(u64::from_str(n)?),
+            "u128" => Operand::U128(u128::from_str(n)?),
+            "usize" => Operand::Usize(usize::from_str(n)?),
+            _ => return self.err("type suffix"),
+        })
+    }
+}
@@ -1,50 +1,109 @@
+use crate::analyser::scope::ScopeArena;
+use crate::analyser::sym_resolver::TypeInfo;
+use crate::analyser::type_table::TypeTable;
 use crate::ast::item::{ItemFn, FnSignature};
 use crate::ast::pattern::Pattern;
 use crate::ast::Visibility;
-use crate::ir::{IRInst, IRType, Operand, Place};
+use crate::intern::Symbol;
+use crate::ir::var_name::{field_var, len_var, param_var, ro_str_label, vtable_var, SRET};
+use crate::ir::{scalar_fields, IRInst, IRType, Operand, Place};
 use crate::rcc::RccError;
+use indexmap::IndexMap;
 use std::collections::{HashMap, VecDeque};
 
 pub struct LinearIR {
     pub funcs: Vec<Func>,
-    /// label, value
-    pub ro_local_strs: HashMap<String, String>,
+    /// label, value, in the order of labels. Identical strings of the module share a label.
+    pub ro_local_strs: IndexMap<String, String>,
+    /// symbol of a vtable -> symbols of its methods, each entry takes 8 bytes
+    pub vtables: IndexMap<String, Vec<String>>,
+    /// value -> index of its label in `ro_local_strs`
+    ro_str_ids: HashMap<String, usize>,
+    /// indexes of the labels used since the last `take_used_ro_strs`, in order of first use
+    used_ro_strs: Vec<usize>,
 }
 
 impl LinearIR {
     pub fn new() -> LinearIR {
         LinearIR {
             funcs: vec![],
-            ro_local_strs: HashMap::new(),
+            ro_local_strs: IndexMap::new(),
+            vtables: IndexMap::new(),
+            ro_str_ids: HashMap::new(),
+            used_ro_strs: vec![],
         }
     }
 
+    /// Label of the read only string `s`. A string is added once, its label `.LC<n>` is
+    /// numbered in the order of first occurrence in the module.
     pub fn add_ro_local_str(&mut self, s: String) -> Operand {
-        let label = format!(".LC{}", self.ro_local_strs.len());
-        self.ro_local_strs.insert(label.clone(), s);
-        Operand::Place(Place::lit_const(label, IRType::Char))
+        let id = match self.ro_str_ids.get(&s) {
+            Some(id) => *id,
+            None => {
+                let id = self.ro_local_strs.len();
+                self.ro_local_strs.insert(ro_str_label(id), s.clone());
+                self.ro_str_ids.insert(s, id);
+                id
+            }
+        };
+        if !self.used_ro_strs.contains(&id) {
+            self.used_ro_strs.push(id);
+        }
+        Operand::Place(Place::lit_const(Symbol::intern(&ro_str_label(id)), IRType::Char))
+    }
+
+    pub fn take_used_ro_strs(&mut self) -> Vec<usize> {
+        std::mem::take(&mut self.used_ro_strs)
     }
 
-    pub fn add_func(&mut self, item_fn: &ItemFn) -> Result<(), RccError> {
-        let fn_name = item_fn.name.clone();
-        let is_global = item_fn.vis() == Visibility::Pub;
+    pub fn add_func(
+        &mut self,
+        fn_name: &str,
+        item_fn: &ItemFn,
+        ret_info: &TypeInfo,
+        scopes: &ScopeArena,
+        types: &TypeTable,
+    ) -> Result<(), RccError> {
+        let fn_name = fn_name.to_string();
+        let is_global = item_fn.vis() == Visibility::Pub || item_fn.no_mangle;
 
-        let scope = &item_fn.fn_block.scope;
-        let scope_id = scope.scope_id;
+        let scope_id = item_fn.fn_block.scope_id;
         debug_assert_ne!(0, scope_id);
 
+        // a trait object `d` is passed as `d` and `d$vt`, a slice `s` as `s` and `s$len`, and
+        // a struct or a tuple `p` as its scalar fields `p.0`, `p.4`, ... A returned struct or
+        // array is written to the address passed before them.
         let mut fn_args = Vec::new();
-        for param in item_fn.fn_params.params.iter() {
-            fn_args.push(match &param.pattern {
-                Pattern::Identifier(i) => {
-                    let (var_info, _) = scope.find_variable(i.ident()).unwrap();
-                    (i.ident().to_string(), IRType::from_var_info(var_info)?)
+        if ret_info.is_struct() || matches!(ret_info, TypeInfo::Array(..)) {
+            fn_args.push((Symbol::intern(SRET), IRType::Addr));
+        }
+        for (i, param) in item_fn.fn_params.params.iter().enumerate() {
+            let ident = match &param.pattern {
+                Pattern::Identifier(ident_pattern) => Symbol::intern(ident_pattern.ident()),
+                _ => param_var(i),
+            };
+            let (var_info, _) = scopes.find_variable(scope_id, ident.as_str()).unwrap();
+            let type_info = types.get(var_info.type_info);
+            if type_info.is_aggregate() {
+                for (offset, field) in scalar_fields(type_info, types) {
+                    let ir_type = IRType::from_type_info(&field)?;
+                    fn_args.push((field_var(ident.as_str(), offset), ir_type));
                 }
-            });
+                continue;
+            }
+            fn_args.push((ident, IRType::from_var_info(var_info, types)?));
+            if type_info.dyn_trait().is_some() {
+                fn_args.push((vtable_var(ident.as_str()), IRType::Addr));
+            }
+            if type_info.is_slice_ref() {
+                fn_args.push((len_var(ident.as_str()), IRType::Usize));
+            }
         }
 
-        self.funcs
-            .push(Func::new(fn_name, is_global, fn_args, scope_id));
+        let mut func = Func::new(fn_name, is_global, fn_args, scope_id);
+        func.link_section = item_fn.link_section.clone();
+        func.is_cold = item_fn.is_cold;
+        self.funcs.push(func);
         Ok(())
     }
 
@@ -70,15 +129,19 @@ pub struct Func {
     pub name: String,
     pub insts: VecDeque<IRInst>,
     pub is_global: bool,
-    pub fn_args: Vec<(String, IRType)>,
+    pub fn_args: Vec<(Symbol, IRType)>,
     pub block_scope_id: u64,
+    /// see `ItemFn::link_section`
+    pub link_section: Option<String>,
+    /// see `ItemFn::is_cold`
+    pub is_cold: bool,
 }
 
 impl Func {
     pub fn new(
         name: String,
         is_global: bool,
-        fn_args: Vec<(String, IRType)>,
+        fn_args: Vec<(Symbol, IRType)>,
         block_scope_id: u64,
     ) -> Func {
         Func {
@@ -87,6 +150,8 @@ impl Func {
             is_global,
             fn_args,
             block_scope_id,
+            link_section: None,
+            is_cold: false,
         }
     }
 }
@@ -0,0 +1,165 @@
+//! Symbols of functions and vtables which have no plain name in the source code.
+//!
+//! ```text
+//! symbol -> _RC G ident ident+ E      fn instantiated with generic arguments
+//!         | _RC M ident ident ident   method of `impl trait for type`
+//!         | _RC I ident ident         function or constant of `impl type`
+//!         | _RC V ident ident         vtable of `impl trait for type`
+//!         | _RC N ident ident scope E fn declared in a block of a function
+//! ident  -> length bytes              e.g. `3add`
+//! scope  -> decimal number            id of the block, two blocks may declare the same name
+//! ```
+//!
+//! e.g. `add::<i32>` is `_RCG3add3i32E`, `<i32 as Add>::add` is `_RCM3i323Add3add`,
+//! `Point::len` is `_RCI5Point3len`, the vtable of `impl Show for i32` is `_RCV3i324Show` and
+//! `fn inner` declared in the block of scope 3 of `main` is `_RCN4main5inner3E`. Identifiers
+//! are prefixed by their lengths, so two different paths never get the same symbol. Other
+//! functions keep their names, `demangle` maps the symbols back to paths for reading the
+//! assembly.
+const PREFIX: &str = "_RC";
+
+fn push_ident(symbol: &mut String, ident: &str) {
+    symbol.push_str(&ident.len().to_string());
+    symbol.push_str(ident);
+}
+
+/// Symbol of `fn_name` instantiated with the generic arguments `args`, e.g. `_RCG3add3i32E`
+pub fn generic_fn_name(fn_name: &str, args: &[String]) -> String {
+    let mut symbol = format!("{}G", PREFIX);
+    push_ident(&mut symbol, fn_name);
+    for arg in args {
+        push_ident(&mut symbol, arg);
+    }
+    symbol.push('E');
+    symbol
+}
+
+/// Symbol of the method `fn_name` in `impl trait_name for type_name`, e.g. `_RCM3i323Add3add`
+pub fn impl_fn_name(type_name: &str, trait_name: &str, fn_name: &str) -> String {
+    let mut symbol = format!("{}M", PREFIX);
+    for ident in [type_name, trait_name, fn_name].iter() {
+        push_ident(&mut symbol, ident);
+    }
+    symbol
+}
+
+/// Symbol of the function or constant `name` in `impl type_name`, e.g. `_RCI5Point3len`
+pub fn inherent_item_name(type_name: &str, name: &str) -> String {
+    let mut symbol = format!("{}I", PREFIX);
+    push_ident(&mut symbol, type_name);
+    push_ident(&mut symbol, name);
+    symbol
+}
+
+/// Symbol of the vtable of `impl trait_name for type_name`, e.g. `_RCV3i324Show`
+pub fn vtable_name(type_name: &str, trait_name: &str) -> String {
+    let mut symbol = format!("{}V", PREFIX);
+    push_ident(&mut symbol, type_name);
+    push_ident(&mut symbol, trait_name);
+    symbol
+}
+
+/// Symbol of `fn_name` declared in the block `scope_id` of the function `outer`, e.g.
+/// `_RCN4main5inner3E`
+pub fn nested_fn_name(outer: &str, fn_name: &str, scope_id: u64) -> String {
+    let mut symbol = format!("{}N", PREFIX);
+    push_ident(&mut symbol, outer);
+    push_ident(&mut symbol, fn_name);
+    symbol.push_str(&scope_id.to_string());
+    symbol.push('E');
+    symbol
+}
+
+struct Demangler<'a> {
+    rest: &'a str,
+}
+
+impl<'a> Demangler<'a> {
+    fn eat(&mut self, c: char) -> bool {
+        match self.rest.strip_prefix(c) {
+            Some(rest) => {
+                self.rest = rest;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Skip the id of a scope
+    fn scope(&mut self) -> bool {
+        let digits = self.rest.find(|c: char| !c.is_ascii_digit()).unwrap_or(self.rest.len());
+        self.rest = &self.rest[digits..];
+        digits > 0
+    }
+
+    fn ident(&mut self) -> Option<&'a str> {
+        let digits = self.rest.find(|c: char| !c.is_ascii_digit()).unwrap_or(self.rest.len());
+        if digits == 0 || self.rest.starts_with('0') {
+            return None;
+        }
+        let len: usize = self.rest[..digits].parse().ok()?;
+        let ident = self.rest.get(digits..digits.checked_add(len)?)?;
+        self.rest = &self.rest[digits + len..];
+        Some(ident)
+    }
+}
+
+/// Path of a mangled symbol, e.g. `_RCM3i323Add3add` -> `<i32 as Add>::add`. Returns `None`
+/// if `symbol` is not mangled.
+pub fn demangle(symbol: &str) -> Option<String> {
+    let mut d = Demangler {
+        rest: symbol.strip_prefix(PREFIX)?,
+    };
+    let path = if d.eat('G') {
+        let fn_name = d.ident()?;
+        let mut args = vec![d.ident()?];
+        while !d.eat('E') {
+            args.push(d.ident()?);
+        }
+        format!("{}::<{}>", fn_name, args.join(", "))
+    } else if d.eat('M') {
+        let (type_name, trait_name) = (d.ident()?, d.ident()?);
+        format!("<{} as {}>::{}", type_name, trait_name, d.ident()?)
+    } else if d.eat('I') {
+        format!("{}::{}", d.ident()?, d.ident()?)
+    } else if d.eat('V') {
+        format!("<{} as {}>::{{vtable}}", d.ident()?, d.ident()?)
+    } else if d.eat('N') {
+        // the function declaring it may be mangled too
+        let outer = d.ident()?;
+        let outer = demangle(outer).unwrap_or_else(|| outer.to_string());
+        let path = format!("{}::{}", outer, d.ident()?);
+        if !d.scope() || !d.eat('E') {
+            return None;
+        }
+        path
+    } else {
+        return None;
+    };
+    if d.rest.is_empty() {
+        Some(path)
+    } else {
+        None
+    }
+}
+
+fn is_symbol_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_' || c == '$' || c == '.'
+}
+
+/// `text` with each mangled symbol replaced by its path, e.g. `call _RCG3add3i32E` ->
+/// `call add::<i32>`
+pub fn demangle_text(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find(is_symbol_char) {
+        out.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let end = rest.find(|c| !is_symbol_char(c)).unwrap_or(rest.len());
+        let symbol = &rest[..end];
+        out.push_str(&demangle(symbol).unwrap_or_else(|| symbol.to_string()));
+        rest = &rest[end..];
+    }
+    out.push_str(rest);
+    out
+}
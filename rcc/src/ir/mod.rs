@@ -1,17 +1,30 @@
 use std::collections::VecDeque;
+use std::convert::TryFrom;
 use std::fmt::Debug;
-use std::ops::Deref;
 
 use crate::analyser::sym_resolver::{TypeInfo, VarInfo, VarKind};
-use crate::ast::expr::BinOperator;
+use crate::analyser::type_table::TypeTable;
+use crate::ast::expr::{BinOperator, UnOp};
 use crate::ast::types::TypeLitNum;
+use crate::intern::Symbol;
 use crate::ir::var_name::{is_temp_var, local_var};
-use crate::rcc::RccError;
+use crate::rcc::{CompileError, ErrorKind, RccError};
 
+mod block_layout;
+mod bounds_check;
 pub mod cfg;
+pub mod cfg_dot;
+mod cfg_simplify;
+mod const_prop;
 mod dataflow;
+mod dead_store;
+pub mod interp;
 pub mod ir_build;
+mod ir_json;
+mod ir_text;
 mod linear_ir;
+pub mod mangle;
+pub mod passes;
 pub(crate) mod tests;
 pub mod var_name;
 
@@ -52,9 +65,11 @@ impl Operand {
     pub fn byte_size(&self, addr_size: u32) -> u32 {
         match self {
             Self::Unit | Self::Never => 0,
-            Self::Bool(_) | Self::Char(_)| Self::I8(_) | Self::U8(_) => 1,
-            Self::I32(_) | Self::U32(_) => 4,
-            Self::I64(_) | Self::U64(_) => 8,
+            Self::Bool(_) | Self::Char(_) | Self::I8(_) | Self::U8(_) => 1,
+            Self::I16(_) | Self::U16(_) => 2,
+            Self::I32(_) | Self::U32(_) | Self::F32(_) => 4,
+            Self::I64(_) | Self::U64(_) | Self::F64(_) => 8,
+            Self::Isize(_) | Self::Usize(_) => IRType::Isize.byte_size(addr_size),
             Self::Place(p) => p.ir_type.byte_size(addr_size),
             Self::FnRetPlace(ir_type) => ir_type.byte_size(addr_size),
             _ => unimplemented!("{:?}", self),
@@ -62,15 +77,112 @@ impl Operand {
     }
 
     pub fn is_imm(&self) -> bool {
-        matches!(self, Self::Bool(_) | Self::Char(_) |
-         Self::F32(_) | Self::F64(_) |
-         Self::I8(_) | Self::U8(_) |
-         Self::I16(_) | Self::U16(_) |
-                       Self::I32(_) | Self::U32(_) |
-                       Self::I64(_) | Self::U64(_) |
-                       Self::I128(_) | Self::U128(_) |
-                       Self::Isize(_) | Self::Usize(_))
+        matches!(
+            self,
+            Self::Bool(_)
+                | Self::Char(_)
+                | Self::F32(_)
+                | Self::F64(_)
+                | Self::I8(_)
+                | Self::U8(_)
+                | Self::I16(_)
+                | Self::U16(_)
+                | Self::I32(_)
+                | Self::U32(_)
+                | Self::I64(_)
+                | Self::U64(_)
+                | Self::I128(_)
+                | Self::U128(_)
+                | Self::Isize(_)
+                | Self::Usize(_)
+        )
     }
+    /// Type of an immediate
+    pub fn imm_type(&self) -> Option<IRType> {
+        let ir_type = match self {
+            Self::Bool(_) => IRType::Bool,
+            Self::Char(_) => IRType::Char,
+            Self::F32(_) => IRType::F32,
+            Self::F64(_) => IRType::F64,
+            Self::I8(_) => IRType::I8,
+            Self::U8(_) => IRType::U8,
+            Self::I16(_) => IRType::I16,
+            Self::U16(_) => IRType::U16,
+            Self::I32(_) => IRType::I32,
+            Self::U32(_) => IRType::U32,
+            Self::I64(_) => IRType::I64,
+            Self::U64(_) => IRType::U64,
+            Self::I128(_) => IRType::I128,
+            Self::U128(_) => IRType::U128,
+            Self::Isize(_) => IRType::Isize,
+            Self::Usize(_) => IRType::Usize,
+            _ => return None,
+        };
+        Some(ir_type)
+    }
+
+    /// `0` of the integer type `ir_type`
+    pub fn zero(ir_type: IRType) -> Operand {
+        Operand::int(ir_type, 0)
+    }
+
+    /// `n` of the integer type `ir_type`, truncated like `n as T`
+    pub fn int(ir_type: IRType, n: i128) -> Operand {
+        match ir_type {
+            IRType::I8 => Operand::I8(n as i8),
+            IRType::I16 => Operand::I16(n as i16),
+            IRType::I32 => Operand::I32(n as i32),
+            IRType::I64 => Operand::I64(n as i64),
+            IRType::I128 => Operand::I128(n),
+            IRType::Isize => Operand::Isize(n as isize),
+            IRType::U8 => Operand::U8(n as u8),
+            IRType::U16 => Operand::U16(n as u16),
+            IRType::U32 => Operand::U32(n as u32),
+            IRType::U64 => Operand::U64(n as u64),
+            IRType::U128 => Operand::U128(n as u128),
+            IRType::Usize => Operand::Usize(n as usize),
+            t => unreachable!("`{:?}` is not an integer type", t),
+        }
+    }
+
+    /// Whether it is the integer immediate `0`
+    pub fn is_zero(&self) -> bool {
+        match *self {
+            Self::I128(n) => n == 0,
+            Self::U128(n) => n == 0,
+            _ => self.int_value() == Some(0),
+        }
+    }
+
+    /// Value of an integer immediate which fits in `i64`
+    pub fn int_value(&self) -> Option<i64> {
+        match *self {
+            Self::I8(n) => Some(n as i64),
+            Self::I16(n) => Some(n as i64),
+            Self::I32(n) => Some(n as i64),
+            Self::I64(n) => Some(n),
+            Self::Isize(n) => Some(n as i64),
+            Self::U8(n) => Some(n as i64),
+            Self::U16(n) => Some(n as i64),
+            Self::U32(n) => Some(n as i64),
+            Self::U64(n) => i64::try_from(n).ok(),
+            Self::Usize(n) => i64::try_from(n).ok(),
+            _ => None,
+        }
+    }
+
+    /// The integer immediate converted to the integer type `ir_type` like `n as T`
+    pub fn cast_int(&self, ir_type: IRType) -> Option<Operand> {
+        let n = match *self {
+            Self::I128(n) => n,
+            Self::U128(n) => n as i128,
+            Self::U64(n) => n as i128,
+            Self::Usize(n) => n as i128,
+            _ => self.int_value()? as i128,
+        };
+        Some(Operand::int(ir_type, n))
+    }
+
     pub fn is_unit_or_never(&self) -> bool {
         matches!(self, Self::Unit | Self::Never)
     }
@@ -82,13 +194,13 @@ impl Operand {
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct Place {
-    pub label: String,
+    pub label: Symbol,
     pub kind: VarKind,
     pub ir_type: IRType,
 }
 
 impl Place {
-    pub fn new(label: String, kind: VarKind, ir_type: IRType) -> Place {
+    pub fn new(label: Symbol, kind: VarKind, ir_type: IRType) -> Place {
         Place {
             label,
             kind,
@@ -100,7 +212,7 @@ impl Place {
         Place::new(local_var(ident, scope_id), var_kind, ir_type)
     }
 
-    pub fn local(label: String, ir_type: IRType) -> Place {
+    pub fn local(label: Symbol, ir_type: IRType) -> Place {
         Place {
             label,
             kind: VarKind::Local,
@@ -108,7 +220,7 @@ impl Place {
         }
     }
 
-    pub fn local_mut(label: String, ir_type: IRType) -> Place {
+    pub fn local_mut(label: Symbol, ir_type: IRType) -> Place {
         Place {
             label,
             kind: VarKind::LocalMut,
@@ -116,7 +228,7 @@ impl Place {
         }
     }
 
-    pub fn lit_const(label: String, ir_type: IRType) -> Place {
+    pub fn lit_const(label: Symbol, ir_type: IRType) -> Place {
         Place {
             label,
             kind: VarKind::LitConst,
@@ -125,7 +237,7 @@ impl Place {
     }
 
     pub fn is_temp(&self) -> bool {
-        is_temp_var(&self.label)
+        is_temp_var(self.label.as_str())
     }
 }
 
@@ -152,9 +264,44 @@ pub enum IRType {
     Never,
     /// address
     Addr,
+    /// `n` bytes in the stack frame holding the elements of an array, only its address is
+    /// taken. The value of an array is the address of its elements. `u16` keeps `Place` as
+    /// small as the other types do.
+    Array(u16),
 }
 
 impl IRType {
+    pub fn is_int(&self) -> bool {
+        self.is_signed_int()
+            || matches!(
+                self,
+                IRType::U8 | IRType::U16 | IRType::U32 | IRType::U64 | IRType::U128 | IRType::Usize
+            )
+    }
+
+    pub fn is_signed_int(&self) -> bool {
+        matches!(
+            self,
+            IRType::I8 | IRType::I16 | IRType::I32 | IRType::I64 | IRType::I128 | IRType::Isize
+        )
+    }
+
+    /// Unsigned integers, and the types compared and extended like them
+    pub fn is_unsigned(&self) -> bool {
+        matches!(
+            self,
+            IRType::U8
+                | IRType::U16
+                | IRType::U32
+                | IRType::U64
+                | IRType::U128
+                | IRType::Usize
+                | IRType::Bool
+                | IRType::Char
+                | IRType::Addr
+        )
+    }
+
     pub fn byte_size(&self, addr_size: u32) -> u32 {
         match self {
             IRType::I8 | IRType::U8 | IRType::Char | IRType::Bool => 1,
@@ -167,6 +314,7 @@ impl IRType {
                 addr_size / 8
             }
             IRType::Unit | IRType::Never => 0,
+            IRType::Array(n) => u32::from(*n),
         }
     }
 
@@ -192,23 +340,105 @@ impl IRType {
             TypeInfo::Char => IRType::Char,
             TypeInfo::Unit => IRType::Unit,
             TypeInfo::Never => IRType::Never,
-            TypeInfo::Ptr { .. } => IRType::Addr,
-            t => return Err(RccError::Parse(format!("invalid type {:?}", t))),
+            TypeInfo::Ptr { .. } | TypeInfo::Vec(_) | TypeInfo::String | TypeInfo::Array(..) => {
+                IRType::Addr
+            }
+            // the start of a range, its end is another variable
+            TypeInfo::Range { type_info, .. } => IRType::from_type_info(type_info)?,
+            t => return Err(format!("invalid type {:?}", t).into()),
         };
         Ok(ir_type)
     }
 
-    pub fn from_var_info(var_info: &VarInfo) -> Result<IRType, RccError> {
-        let t = var_info.type_info.borrow();
-        let tp = t.deref();
-        Self::from_type_info(tp)
+    pub fn from_var_info(var_info: &VarInfo, types: &TypeTable) -> Result<IRType, RccError> {
+        Self::from_type_info(types.get(var_info.type_info))
     }
 }
 
+/// Scalar fields of `type_info` in the order of declaration with their byte offsets, the
+/// fields of nested structs are flattened. A struct variable is replaced by a variable per
+/// scalar field in the IR. A scalar type is a single field at offset 0. The elements of a
+/// tuple are its fields.
+///
+/// Fields are aligned to their sizes, and pointer-sized values take 8 bytes, so that the
+/// offsets do not depend on the target.
+pub fn scalar_fields(type_info: &TypeInfo, types: &TypeTable) -> Vec<(u32, TypeInfo)> {
+    let mut fields = vec![];
+    push_scalar_fields(type_info, 0, types, &mut fields);
+    fields
+}
+
+fn push_scalar_fields(
+    type_info: &TypeInfo,
+    offset: u32,
+    types: &TypeTable,
+    fields: &mut Vec<(u32, TypeInfo)>,
+) {
+    match aggregate_fields(type_info, types) {
+        Some(aggregate) => {
+            let offsets = field_offsets(&aggregate, types);
+            for (field, field_offset) in aggregate.into_iter().zip(offsets) {
+                push_scalar_fields(field, offset + field_offset, types, fields);
+            }
+        }
+        None => fields.push((offset, type_info.clone())),
+    }
+}
+
+/// Fields of a struct or elements of a tuple, `None` for other types
+fn aggregate_fields<'a>(
+    type_info: &'a TypeInfo,
+    types: &'a TypeTable,
+) -> Option<Vec<&'a TypeInfo>> {
+    match type_info {
+        TypeInfo::Struct { name, .. } => {
+            Some(types.struct_fields(name).iter().map(|(_, field)| field).collect())
+        }
+        TypeInfo::Tuple(elems) => Some(elems.iter().collect()),
+        _ => None,
+    }
+}
+
+/// Byte offsets of the fields of a struct or the elements of a tuple
+pub fn field_offsets(fields: &[&TypeInfo], types: &TypeTable) -> Vec<u32> {
+    let mut offset = 0;
+    fields
+        .iter()
+        .map(|field| {
+            let (size, align) = layout(field, types);
+            let field_offset = align_to(offset, align);
+            offset = field_offset + size;
+            field_offset
+        })
+        .collect()
+}
+
+/// (size, alignment) of `type_info` in bytes
+pub fn layout(type_info: &TypeInfo, types: &TypeTable) -> (u32, u32) {
+    match aggregate_fields(type_info, types) {
+        Some(fields) => {
+            let (mut size, mut align) = (0, 1);
+            for field in fields {
+                let (field_size, field_align) = layout(field, types);
+                size = align_to(size, field_align) + field_size;
+                align = align.max(field_align);
+            }
+            (align_to(size, align), align)
+        }
+        None => {
+            let size = IRType::from_type_info(type_info).map_or(0, |ir_type| ir_type.byte_size(64));
+            (size, size.max(1))
+        }
+    }
+}
+
+fn align_to(offset: u32, align: u32) -> u32 {
+    offset.div_ceil(align) * align
+}
+
 /// Immediate Presentation's Instructions
 #[derive(Debug, PartialEq)]
 pub enum IRInst {
-
     /// dest = src1 op src2
     BinOp {
         op: BinOperator,
@@ -238,24 +468,90 @@ pub enum IRInst {
         label: usize,
     },
 
+    /// goto the label of the case equal to `src`, or `default` if there is none
+    Switch {
+        src: Operand,
+        /// (value, label), values are distinct
+        cases: Vec<(i64, usize)>,
+        default: usize,
+    },
+
     /// dest = src
     LoadData {
         dest: Place,
         src: Operand,
     },
 
-    /// dest = *symbol
+    /// dest = &symbol
+    ///
+    /// `LoadAddr`, `Load` and `Store` are the memory model of IR: references, elements of
+    /// arrays and vectors, boxes and trait objects are all accessed through them. A load or a
+    /// store has the type of the value in memory, so the backend maps them to `lb/lh/lw/ld`
+    /// and `sb/sh/sw/sd` directly.
     LoadAddr {
         dest: Place,
         symbol: Operand,
     },
 
+    /// dest = *addr, a `ty` is loaded
+    ///
+    /// A volatile access, e.g. of a memory-mapped device register, is never deleted, merged or
+    /// reordered with the other volatile accesses by the optimizations, even if its value is
+    /// unused.
+    Load {
+        ty: IRType,
+        dest: Place,
+        addr: Operand,
+        volatile: bool,
+    },
+
+    /// *addr = src, a `ty` is stored
+    Store {
+        ty: IRType,
+        addr: Operand,
+        src: Operand,
+        volatile: bool,
+    },
+
     Call {
         callee: Operand,
         args: Vec<Operand>,
     },
 
     Ret(Operand),
+
+    /// Inline assembly, `{n}` in `template` is the register of the `n`th operand, inputs
+    /// come before outputs. The inputs are loaded into registers before it and the outputs
+    /// are stored to their places after it. It is opaque to the optimizations except that it
+    /// reads the inputs.
+    Asm {
+        template: String,
+        inputs: Vec<Operand>,
+        outputs: Vec<Place>,
+    },
+
+    /// Copy `size` bytes of a struct variable, or a struct field of it, to another one of the
+    /// same type. `srcs` and `dests` are their scalar fields, see `scalar_fields`, so the first
+    /// ones are at the start of the copy. The allocator lays out the fields of a struct
+    /// variable in a `Memcpy` like the struct, and the backend copies the memory at once.
+    Memcpy {
+        size: u32,
+        srcs: Vec<Operand>,
+        dests: Vec<Place>,
+    },
+
+    /// call __rcc_panic(msg) unless index < len, before an indexed access of an array or a
+    /// slice. It is lowered to a conditional jump by the `bounds-checks` pass, or deleted if
+    /// the index is proven in bounds, see `crate::ir::bounds_check`.
+    BoundsCheck {
+        index: Operand,
+        len: Operand,
+        msg: Operand,
+    },
+
+    /// The following instructions come from this line of the source. It is only built for
+    /// debug info and generates no code.
+    Loc(u32),
 }
 
 impl IRInst {
@@ -303,10 +599,81 @@ impl IRInst {
         }
     }
 
+    pub fn load(dest: Place, addr: Operand) -> IRInst {
+        IRInst::Load {
+            ty: dest.ir_type,
+            dest,
+            addr,
+            volatile: false,
+        }
+    }
+
+    pub fn store(ty: IRType, addr: Operand, src: Operand) -> IRInst {
+        IRInst::Store {
+            ty,
+            addr,
+            src,
+            volatile: false,
+        }
+    }
+
     pub fn call(callee: Operand, args: Vec<Operand>) -> IRInst {
         IRInst::Call { callee, args }
     }
 
+    /// Operands read by the instruction, the callee of a call included
+    pub fn src_operands(&self) -> Vec<&Operand> {
+        match self {
+            IRInst::BinOp { src1, src2, .. } | IRInst::JumpIfCond { src1, src2, .. } => {
+                vec![src1, src2]
+            }
+            IRInst::BoundsCheck { index, len, msg } => vec![index, len, msg],
+            IRInst::JumpIf { cond, .. } | IRInst::JumpIfNot { cond, .. } => vec![cond],
+            IRInst::Switch { src, .. } | IRInst::LoadData { src, .. } => vec![src],
+            IRInst::LoadAddr { symbol, .. } => vec![symbol],
+            IRInst::Load { addr, .. } => vec![addr],
+            IRInst::Store { addr, src, .. } => vec![addr, src],
+            IRInst::Call { callee, args } => std::iter::once(callee).chain(args.iter()).collect(),
+            IRInst::Ret(operand) => vec![operand],
+            IRInst::Asm { inputs, .. } | IRInst::Memcpy { srcs: inputs, .. } => {
+                inputs.iter().collect()
+            }
+            IRInst::Jump { .. } | IRInst::Loc(_) => vec![],
+        }
+    }
+
+    /// Places defined by the instruction
+    pub fn dest_places(&self) -> Vec<&Place> {
+        match self {
+            IRInst::BinOp { dest, .. }
+            | IRInst::LoadData { dest, .. }
+            | IRInst::LoadAddr { dest, .. }
+            | IRInst::Load { dest, .. } => vec![dest],
+            IRInst::Asm { outputs, .. } | IRInst::Memcpy { dests: outputs, .. } => {
+                outputs.iter().collect()
+            }
+            _ => vec![],
+        }
+    }
+
+    /// Volatile loads and stores, see `IRInst::Load`
+    pub fn is_volatile(&self) -> bool {
+        matches!(
+            self,
+            IRInst::Load { volatile: true, .. } | IRInst::Store { volatile: true, .. }
+        )
+    }
+
+    pub fn is_jump(&self) -> bool {
+        matches!(
+            self,
+            Self::Jump { .. }
+                | Self::JumpIf { .. }
+                | Self::JumpIfNot { .. }
+                | Self::JumpIfCond { .. }
+        )
+    }
+
     pub fn set_jump_label(&mut self, new_label: usize) {
         match self {
             Self::Jump { label } => *label = new_label,
@@ -322,6 +689,30 @@ impl IRInst {
         }
     }
 
+    /// Labels of all the targets of a jump or `switch`, the fall through is not included
+    pub fn jump_labels(&self) -> Vec<usize> {
+        match self {
+            Self::Switch { cases, default, .. } => {
+                cases.iter().map(|(_, label)| *label).chain(Some(*default)).collect()
+            }
+            inst if inst.is_jump() => vec![inst.jump_label()],
+            _ => vec![],
+        }
+    }
+
+    pub fn jump_labels_mut(&mut self) -> Vec<&mut usize> {
+        match self {
+            Self::Switch { cases, default, .. } => {
+                cases.iter_mut().map(|(_, label)| label).chain(Some(default)).collect()
+            }
+            Self::Jump { label }
+            | Self::JumpIf { label, .. }
+            | Self::JumpIfNot { label, .. }
+            | Self::JumpIfCond { label, .. } => vec![label],
+            _ => vec![],
+        }
+    }
+
     pub fn jump_label(&self) -> usize {
         *match self {
             Self::Jump { label } => label,
@@ -343,62 +734,250 @@ pub enum StrKind {
     Const,
 }
 
+/// What an integer operation which overflows gives
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Overflow {
+    /// an error, e.g. for the operations on constants
+    Error,
+    /// the result wraps around like the instructions of the target. Overflow checks are
+    /// separate instructions, see `--overflow-checks`.
+    Wrap,
+}
+
+/// "attempt to add with overflow" like the panics of rustc
+pub fn overflow_msg(op: &BinOperator) -> String {
+    let op = match op {
+        BinOperator::Plus => "add",
+        BinOperator::Minus => "subtract",
+        BinOperator::Star => "multiply",
+        BinOperator::Slash => "divide",
+        BinOperator::Percent => "calculate the remainder",
+        BinOperator::Shl => "shift left",
+        BinOperator::Shr => "shift right",
+        op => unreachable!("`{}` never overflows", op),
+    };
+    format!("attempt to {} with overflow", op)
+}
+
+/// Error of `/` or `%` whose divisor is the constant zero, `dividend` is shown if it is a
+/// constant too.
+pub fn div_by_zero_error(op: &BinOperator, dividend: &Operand) -> RccError {
+    let dividend = match dividend {
+        Operand::Place(_) => String::new(),
+        dividend => format!("`{}` ", dividend),
+    };
+    let note = if op == &BinOperator::Slash {
+        format!("attempt to divide {}by zero", dividend)
+    } else if dividend.is_empty() {
+        "attempt to calculate the remainder with a divisor of zero".to_string()
+    } else {
+        format!("attempt to calculate the remainder of {}with a divisor of zero", dividend)
+    };
+    CompileError::new(ErrorKind::Other, "this operation will panic at runtime")
+        .note(note)
+        .into()
+}
+
 /// Constant fold optimization.
 /// a = 2 * 3 -> a = 6
-/// TODO other primitive type
+///
+/// Both operands must have the same primitive type. Dividing by zero is an error even if
+/// `overflow` is `Wrap`, like on the host. Floats follow IEEE 754 like rustc: NaN is
+/// unordered, so every comparison with it is false except `!=`.
 pub fn bin_op_may_constant_fold(
     op: &BinOperator,
     src1: &Operand,
     src2: &Operand,
+    overflow: Overflow,
 ) -> Result<Option<Operand>, RccError> {
+    let overflow_error = || -> RccError {
+        CompileError::new(ErrorKind::Other, "this arithmetic operation will overflow")
+            .note(format!("{}: `{} {} {}`", overflow_msg(op), src1, op, src2))
+            .into()
+    };
+    macro_rules! checked {
+        ($i:path, $l:ident, $checked:ident, $wrapping:ident, $r:expr, $err:literal) => {
+            Some($i(match ($l.$checked($r), overflow) {
+                (Some(res), _) => res,
+                // only a division by zero can not wrap
+                (None, Overflow::Error) if $r == 0 => return Err(div_by_zero_error(op, src1)),
+                (None, Overflow::Wrap) if $r == 0 => return Err($err.into()),
+                (None, Overflow::Error) => return Err(overflow_error()),
+                (None, Overflow::Wrap) => $l.$wrapping($r),
+            }))
+        };
+    }
     macro_rules! try_fold_int {
         ($i:path, $l:ident, $r:ident) => {
             match op {
-                BinOperator::Plus => Some($i(match $l.checked_add(*$r) {
-                    Some(res) => res,
-                    None => return Err("add overflow".into()),
-                })),
-                BinOperator::Minus => Some($i(match $l.checked_sub(*$r) {
-                    Some(res) => res,
-                    None => return Err("sub overflow".into()),
-                })),
-                BinOperator::Star => Some($i(match $l.checked_mul(*$r) {
-                    Some(res) => res,
-                    None => return Err("mul overflow".into()),
-                })),
-                BinOperator::Slash => Some($i(match $l.checked_div(*$r) {
-                    Some(res) => res,
-                    None => return Err("div overflow".into()),
-                })),
+                BinOperator::Plus => checked!($i, $l, checked_add, wrapping_add, *$r, ""),
+                BinOperator::Minus => checked!($i, $l, checked_sub, wrapping_sub, *$r, ""),
+                BinOperator::Star => checked!($i, $l, checked_mul, wrapping_mul, *$r, ""),
+                BinOperator::Slash => {
+                    checked!($i, $l, checked_div, wrapping_div, *$r, "div overflow")
+                }
                 BinOperator::Lt => Some(Operand::Bool($l < $r)),
                 BinOperator::Le => Some(Operand::Bool($l <= $r)),
                 BinOperator::Gt => Some(Operand::Bool($l > $r)),
                 BinOperator::Ge => Some(Operand::Bool($l >= $r)),
                 BinOperator::Ne => Some(Operand::Bool($l != $r)),
                 BinOperator::EqEq => Some(Operand::Bool($l == $r)),
-                BinOperator::Shl => Some($i(match $l.checked_shl(*$r as u32) {
-                    Some(res) => res,
-                    None => return Err("shl overflow".into()),
-                })),
-                BinOperator::Shr => Some($i(match $l.checked_shr(*$r as u32) {
-                    Some(res) => res,
-                    None => return Err("shr overflow".into()),
-                })),
+                BinOperator::Shl => {
+                    checked!($i, $l, checked_shl, wrapping_shl, *$r as u32, "")
+                }
+                BinOperator::Shr => {
+                    checked!($i, $l, checked_shr, wrapping_shr, *$r as u32, "")
+                }
                 BinOperator::And => Some($i($l & $r)),
                 BinOperator::Or => Some($i($l | $r)),
                 BinOperator::Caret => Some($i($l ^ $r)),
-                BinOperator::Percent => Some($i(match $l.checked_rem(*$r) {
-                    Some(res) => res,
-                    None => return Err("rem overflow".into()),
-                })),
+                BinOperator::Percent => {
+                    checked!($i, $l, checked_rem, wrapping_rem, *$r, "rem overflow")
+                }
+                _ => None,
+            }
+        };
+    }
+    macro_rules! try_fold_float {
+        ($f:path, $l:ident, $r:ident) => {
+            match op {
+                BinOperator::Plus => Some($f($l + $r)),
+                BinOperator::Minus => Some($f($l - $r)),
+                BinOperator::Star => Some($f($l * $r)),
+                BinOperator::Slash => Some($f($l / $r)),
+                BinOperator::Percent => Some($f($l % $r)),
+                BinOperator::Lt => Some(Operand::Bool($l < $r)),
+                BinOperator::Le => Some(Operand::Bool($l <= $r)),
+                BinOperator::Gt => Some(Operand::Bool($l > $r)),
+                BinOperator::Ge => Some(Operand::Bool($l >= $r)),
+                BinOperator::Ne => Some(Operand::Bool($l != $r)),
+                BinOperator::EqEq => Some(Operand::Bool($l == $r)),
+                _ => None,
+            }
+        };
+    }
+    macro_rules! try_fold_cmp {
+        ($l:ident, $r:ident) => {
+            match op {
+                BinOperator::Lt => Some(Operand::Bool($l < $r)),
+                BinOperator::Le => Some(Operand::Bool($l <= $r)),
+                BinOperator::Gt => Some(Operand::Bool($l > $r)),
+                BinOperator::Ge => Some(Operand::Bool($l >= $r)),
+                BinOperator::Ne => Some(Operand::Bool($l != $r)),
+                BinOperator::EqEq => Some(Operand::Bool($l == $r)),
                 _ => None,
             }
         };
     }
     Ok(match (src1, src2) {
+        (Operand::Bool(l), Operand::Bool(r)) => match op {
+            BinOperator::And | BinOperator::AndAnd => Some(Operand::Bool(*l && *r)),
+            BinOperator::Or | BinOperator::OrOr => Some(Operand::Bool(*l || *r)),
+            BinOperator::Caret => Some(Operand::Bool(l ^ r)),
+            _ => try_fold_cmp!(l, r),
+        },
+        (Operand::Char(l), Operand::Char(r)) => try_fold_cmp!(l, r),
+        (Operand::F32(l), Operand::F32(r)) => try_fold_float!(Operand::F32, l, r),
+        (Operand::F64(l), Operand::F64(r)) => try_fold_float!(Operand::F64, l, r),
+        (Operand::I8(l), Operand::I8(r)) => try_fold_int!(Operand::I8, l, r),
+        (Operand::I16(l), Operand::I16(r)) => try_fold_int!(Operand::I16, l, r),
         (Operand::I32(l), Operand::I32(r)) => try_fold_int!(Operand::I32, l, r),
         (Operand::I64(l), Operand::I64(r)) => try_fold_int!(Operand::I64, l, r),
         (Operand::I128(l), Operand::I128(r)) => try_fold_int!(Operand::I128, l, r),
+        (Operand::Isize(l), Operand::Isize(r)) => try_fold_int!(Operand::Isize, l, r),
+        (Operand::U8(l), Operand::U8(r)) => try_fold_int!(Operand::U8, l, r),
+        (Operand::U16(l), Operand::U16(r)) => try_fold_int!(Operand::U16, l, r),
+        (Operand::U32(l), Operand::U32(r)) => try_fold_int!(Operand::U32, l, r),
+        (Operand::U64(l), Operand::U64(r)) => try_fold_int!(Operand::U64, l, r),
+        (Operand::U128(l), Operand::U128(r)) => try_fold_int!(Operand::U128, l, r),
+        (Operand::Usize(l), Operand::Usize(r)) => try_fold_int!(Operand::Usize, l, r),
         _ => None,
     })
 }
+
+/// Constant fold of `-` and `!`.
+/// a = -(3) -> a = -3
+///
+/// `!` is the logical not of `bool` and the bitwise not of integers. Negating the minimum of
+/// a signed integer overflows like in `bin_op_may_constant_fold`, and negating an unsigned
+/// integer is not folded.
+pub fn un_op_may_constant_fold(
+    op: &UnOp,
+    src: &Operand,
+    overflow: Overflow,
+) -> Result<Option<Operand>, RccError> {
+    macro_rules! try_fold_neg {
+        ($i:path, $v:ident) => {
+            Some($i(match ($v.checked_neg(), overflow) {
+                (Some(res), _) => res,
+                (None, Overflow::Error) => {
+                    return Err(CompileError::new(
+                        ErrorKind::Other,
+                        "this arithmetic operation will overflow",
+                    )
+                    .note(format!("attempt to negate `{}`, which would overflow", src))
+                    .into())
+                }
+                (None, Overflow::Wrap) => $v.wrapping_neg(),
+            }))
+        };
+    }
+    Ok(match (op, src) {
+        (UnOp::Neg, Operand::F32(v)) => Some(Operand::F32(-v)),
+        (UnOp::Neg, Operand::F64(v)) => Some(Operand::F64(-v)),
+        (UnOp::Neg, Operand::I8(v)) => try_fold_neg!(Operand::I8, v),
+        (UnOp::Neg, Operand::I16(v)) => try_fold_neg!(Operand::I16, v),
+        (UnOp::Neg, Operand::I32(v)) => try_fold_neg!(Operand::I32, v),
+        (UnOp::Neg, Operand::I64(v)) => try_fold_neg!(Operand::I64, v),
+        (UnOp::Neg, Operand::I128(v)) => try_fold_neg!(Operand::I128, v),
+        (UnOp::Neg, Operand::Isize(v)) => try_fold_neg!(Operand::Isize, v),
+        (UnOp::Not, Operand::Bool(v)) => Some(Operand::Bool(!v)),
+        (UnOp::Not, Operand::I8(v)) => Some(Operand::I8(!v)),
+        (UnOp::Not, Operand::I16(v)) => Some(Operand::I16(!v)),
+        (UnOp::Not, Operand::I32(v)) => Some(Operand::I32(!v)),
+        (UnOp::Not, Operand::I64(v)) => Some(Operand::I64(!v)),
+        (UnOp::Not, Operand::I128(v)) => Some(Operand::I128(!v)),
+        (UnOp::Not, Operand::Isize(v)) => Some(Operand::Isize(!v)),
+        (UnOp::Not, Operand::U8(v)) => Some(Operand::U8(!v)),
+        (UnOp::Not, Operand::U16(v)) => Some(Operand::U16(!v)),
+        (UnOp::Not, Operand::U32(v)) => Some(Operand::U32(!v)),
+        (UnOp::Not, Operand::U64(v)) => Some(Operand::U64(!v)),
+        (UnOp::Not, Operand::U128(v)) => Some(Operand::U128(!v)),
+        (UnOp::Not, Operand::Usize(v)) => Some(Operand::Usize(!v)),
+        _ => None,
+    })
+}
+
+/// Evaluate the condition of `JumpIfCond` if both operands are immediates.
+/// `if 1 < 2 goto L` -> Some(true)
+pub fn jump_cond_may_constant_fold(cond: &Jump, src1: &Operand, src2: &Operand) -> Option<bool> {
+    macro_rules! try_fold_cmp {
+        ($l:ident, $r:ident) => {
+            Some(match cond {
+                Jump::JEq => $l == $r,
+                Jump::JNe => $l != $r,
+                Jump::JLt => $l < $r,
+                Jump::JGe => $l >= $r,
+            })
+        };
+    }
+    match (src1, src2) {
+        (Operand::Bool(l), Operand::Bool(r)) => try_fold_cmp!(l, r),
+        (Operand::Char(l), Operand::Char(r)) => try_fold_cmp!(l, r),
+        (Operand::F32(l), Operand::F32(r)) => try_fold_cmp!(l, r),
+        (Operand::F64(l), Operand::F64(r)) => try_fold_cmp!(l, r),
+        (Operand::I8(l), Operand::I8(r)) => try_fold_cmp!(l, r),
+        (Operand::I16(l), Operand::I16(r)) => try_fold_cmp!(l, r),
+        (Operand::I32(l), Operand::I32(r)) => try_fold_cmp!(l, r),
+        (Operand::I64(l), Operand::I64(r)) => try_fold_cmp!(l, r),
+        (Operand::I128(l), Operand::I128(r)) => try_fold_cmp!(l, r),
+        (Operand::Isize(l), Operand::Isize(r)) => try_fold_cmp!(l, r),
+        (Operand::U8(l), Operand::U8(r)) => try_fold_cmp!(l, r),
+        (Operand::U16(l), Operand::U16(r)) => try_fold_cmp!(l, r),
+        (Operand::U32(l), Operand::U32(r)) => try_fold_cmp!(l, r),
+        (Operand::U64(l), Operand::U64(r)) => try_fold_cmp!(l, r),
+        (Operand::U128(l), Operand::U128(r)) => try_fold_cmp!(l, r),
+        (Operand::Usize(l), Operand::Usize(r)) => try_fold_cmp!(l, r),
+        _ => None,
+    }
+}
@@ -0,0 +1,209 @@
+//! Passes on `CFGIR` and the `PassManager` which composes them into pipelines.
+use crate::ir::bounds_check::bounds_checks;
+use crate::ir::cfg::CFGIR;
+use crate::rcc::{OptimizeLevel, RccError};
+use std::io::Write;
+use std::time::{Duration, Instant};
+
+#[cfg(test)]
+mod tests;
+
+pub trait Pass {
+    /// Name used by `--print-ir-after=<pass>` and `--stop-after=<pass>`
+    fn name(&self) -> &'static str;
+
+    fn run(&mut self, cfg_ir: &mut CFGIR) -> Result<(), RccError>;
+}
+
+/// See `CFG::insert_bounds_checks`
+pub struct BoundsChecks;
+
+impl Pass for BoundsChecks {
+    fn name(&self) -> &'static str {
+        "bounds-checks"
+    }
+
+    fn run(&mut self, cfg_ir: &mut CFGIR) -> Result<(), RccError> {
+        cfg_ir.insert_bounds_checks();
+        Ok(())
+    }
+}
+
+/// See `CFG::propagate_constants`
+pub struct ConstantPropagation;
+
+impl Pass for ConstantPropagation {
+    fn name(&self) -> &'static str {
+        "constant-propagation"
+    }
+
+    fn run(&mut self, cfg_ir: &mut CFGIR) -> Result<(), RccError> {
+        cfg_ir.propagate_constants()
+    }
+}
+
+/// See `CFG::simplify`
+pub struct SimplifyCFG;
+
+impl Pass for SimplifyCFG {
+    fn name(&self) -> &'static str {
+        "simplify-cfg"
+    }
+
+    fn run(&mut self, cfg_ir: &mut CFGIR) -> Result<(), RccError> {
+        cfg_ir.simplify_cfg();
+        Ok(())
+    }
+}
+
+/// See `CFG::layout_cold_blocks`
+pub struct ColdBlockLayout;
+
+impl Pass for ColdBlockLayout {
+    fn name(&self) -> &'static str {
+        "cold-block-layout"
+    }
+
+    fn run(&mut self, cfg_ir: &mut CFGIR) -> Result<(), RccError> {
+        cfg_ir.layout_cold_blocks();
+        Ok(())
+    }
+}
+
+/// Report variables which may be used before definition.
+pub struct ReachingDefinitions;
+
+impl Pass for ReachingDefinitions {
+    fn name(&self) -> &'static str {
+        "reaching-definitions"
+    }
+
+    fn run(&mut self, cfg_ir: &mut CFGIR) -> Result<(), RccError> {
+        cfg_ir.reaching_definitions_analysis()
+    }
+}
+
+/// See `CFG::eliminate_dead_stores`
+pub struct DeadStoreElimination;
+
+impl Pass for DeadStoreElimination {
+    fn name(&self) -> &'static str {
+        "dead-store-elimination"
+    }
+
+    fn run(&mut self, cfg_ir: &mut CFGIR) -> Result<(), RccError> {
+        cfg_ir.eliminate_dead_stores()
+    }
+}
+
+pub struct PassManager {
+    passes: Vec<Box<dyn Pass>>,
+    print_ir_after: Option<String>,
+    /// the passes after it are skipped
+    stop_after: Option<String>,
+    /// time spent in each pass of the last run, in order
+    timings: Vec<(&'static str, Duration)>,
+}
+
+impl PassManager {
+    pub fn new() -> PassManager {
+        PassManager {
+            passes: vec![],
+            print_ir_after: None,
+            stop_after: None,
+            timings: vec![],
+        }
+    }
+
+    /// Pipelines of each optimize level:
+    ///
+    /// -O0: bounds-checks, reaching-definitions
+    /// -O1: constant-propagation, simplify-cfg, cold-block-layout, reaching-definitions,
+    ///      dead-store-elimination
+    /// -O2: the passes of -O1, then simplify-cfg again to remove the blocks emptied by
+    ///      dead-store-elimination
+    ///
+    /// With `checks`, bounds-checks runs first at every level. A pass which runs twice is
+    /// printed by `--print-ir-after` each time, and `--stop-after` stops at its first run.
+    pub fn with_opt_level(opt_level: OptimizeLevel, checks: bool) -> PassManager {
+        let mut pass_manager = PassManager::new();
+        if bounds_checks(opt_level, checks) {
+            pass_manager.add_pass(Box::new(BoundsChecks));
+        }
+        if opt_level == OptimizeLevel::Zero {
+            pass_manager.add_pass(Box::new(ReachingDefinitions));
+            return pass_manager;
+        }
+        pass_manager.add_pass(Box::new(ConstantPropagation));
+        pass_manager.add_pass(Box::new(SimplifyCFG));
+        pass_manager.add_pass(Box::new(ColdBlockLayout));
+        pass_manager.add_pass(Box::new(ReachingDefinitions));
+        pass_manager.add_pass(Box::new(DeadStoreElimination));
+        if opt_level == OptimizeLevel::Two {
+            pass_manager.add_pass(Box::new(SimplifyCFG));
+        }
+        pass_manager
+    }
+
+    pub fn add_pass(&mut self, pass: Box<dyn Pass>) {
+        self.passes.push(pass);
+    }
+
+    pub fn set_print_ir_after(&mut self, pass_name: Option<String>) {
+        self.print_ir_after = pass_name;
+    }
+
+    pub fn set_stop_after(&mut self, pass_name: Option<String>) {
+        self.stop_after = pass_name;
+    }
+
+    pub fn timings(&self) -> &[(&'static str, Duration)] {
+        &self.timings
+    }
+
+    pub fn pass_names(&self) -> Vec<&'static str> {
+        self.passes.iter().map(|p| p.name()).collect()
+    }
+
+    /// Run the passes in order until the pass named by `stop_after`. The textual IR is
+    /// dumped to `dump` after the pass named by `print_ir_after`.
+    pub fn run<W: Write>(&mut self, cfg_ir: &mut CFGIR, dump: &mut W) -> Result<(), RccError> {
+        let pass_names = self.pass_names();
+        for name in self.print_ir_after.iter().chain(self.stop_after.iter()) {
+            if !pass_names.contains(&name.as_str()) {
+                return Err(format!(
+                    "pass `{}` is not in the pipeline `{}`",
+                    name,
+                    pass_names.join(", ")
+                )
+                .into());
+            }
+        }
+        self.timings.clear();
+        for pass in self.passes.iter_mut() {
+            let start = Instant::now();
+            let result = pass.run(cfg_ir);
+            self.timings.push((pass.name(), start.elapsed()));
+            result?;
+            if self.print_ir_after.as_deref() == Some(pass.name()) {
+                writeln!(dump, "*** IR Dump After {} ***", pass.name())?;
+                for (i, cfg) in cfg_ir.cfgs.iter().enumerate() {
+                    if i != 0 {
+                        writeln!(dump)?;
+                    }
+                    write!(dump, "{}", cfg)?;
+                }
+            }
+            if self.stop_after.as_deref() == Some(pass.name()) {
+                break;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Default for PassManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,422 @@
+use crate::ir::cfg::CFGIR;
+use crate::ir::passes::{
+    BoundsChecks, ColdBlockLayout, ConstantPropagation, Pass, PassManager, SimplifyCFG,
+};
+use crate::ir::tests::ir_build;
+use crate::rcc::OptimizeLevel;
+
+fn get_cfg_ir(input: &str) -> CFGIR {
+    CFGIR::new(ir_build(input).unwrap())
+}
+
+#[test]
+fn pipeline_test() {
+    assert_eq!(
+        vec!["bounds-checks", "reaching-definitions"],
+        PassManager::with_opt_level(OptimizeLevel::Zero, false).pass_names()
+    );
+    assert_eq!(
+        vec!["bounds-checks", "constant-propagation"],
+        PassManager::with_opt_level(OptimizeLevel::One, true).pass_names()[..2]
+    );
+    assert_eq!(
+        vec![
+            "constant-propagation",
+            "simplify-cfg",
+            "cold-block-layout",
+            "reaching-definitions",
+            "dead-store-elimination"
+        ],
+        PassManager::with_opt_level(OptimizeLevel::One, false).pass_names()
+    );
+    assert_eq!(
+        vec![
+            "constant-propagation",
+            "simplify-cfg",
+            "cold-block-layout",
+            "reaching-definitions",
+            "dead-store-elimination",
+            "simplify-cfg"
+        ],
+        PassManager::with_opt_level(OptimizeLevel::Two, false).pass_names()
+    );
+    assert!("3".parse::<OptimizeLevel>().is_err());
+}
+
+#[test]
+fn simplify_cfg_pass_test() {
+    let mut cfg_ir = get_cfg_ir(
+        r#"
+        fn main() {
+            let mut a = 0;
+            if 1 > 2 {
+                a = 1;
+            }
+        }
+    "#,
+    );
+    assert_eq!(3, cfg_ir.cfgs[0].basic_blocks.len());
+    SimplifyCFG.run(&mut cfg_ir).unwrap();
+    assert_eq!(2, cfg_ir.cfgs[0].basic_blocks.len());
+}
+
+#[test]
+fn constant_propagation_pass_test() {
+    let insts = |cfg_ir: &CFGIR| -> Vec<String> {
+        cfg_ir.cfgs[0].iter_inst().map(|i| i.to_string()).collect()
+    };
+
+    // the condition becomes a constant, so simplify-cfg removes the jump
+    let input = "fn f() -> i32 { let mut a = 3; let mut b = 0; if a > 1 { b = a; } b }";
+    let mut cfg_ir = get_cfg_ir(input);
+    ConstantPropagation.run(&mut cfg_ir).unwrap();
+    assert_eq!(
+        vec![
+            "mut a_2:i32 = 3i32",
+            "mut b_2:i32 = 0i32",
+            "if 1i32 >= 3i32 goto (2)",
+            "mut b_2:i32 = 3i32",
+            "ret mut b_2:i32",
+        ],
+        insts(&cfg_ir)
+    );
+    SimplifyCFG.run(&mut cfg_ir).unwrap();
+    assert!(!insts(&cfg_ir).iter().any(|inst| inst.starts_with("if")));
+
+    // `b` is `0` or `1` after the branch
+    let mut cfg_ir = get_cfg_ir("fn g(x: bool) -> i32 { let mut b = 0; if x { b = 1; } b }");
+    ConstantPropagation.run(&mut cfg_ir).unwrap();
+    assert_eq!("ret mut b_2:i32", insts(&cfg_ir).last().unwrap());
+
+    // `a` may be written through `p`
+    let mut cfg_ir = get_cfg_ir("fn h() -> i32 { let mut a = 1; let p = &mut a; *p = 2; a }");
+    ConstantPropagation.run(&mut cfg_ir).unwrap();
+    assert_eq!("ret mut a_2:i32", insts(&cfg_ir).last().unwrap());
+
+    // operations on constants are folded
+    let mut cfg_ir = get_cfg_ir("fn k() -> i32 { let mut a = 3; let mut b = 4; a * b + 1 }");
+    ConstantPropagation.run(&mut cfg_ir).unwrap();
+    assert_eq!(vec!["$0_2:i32 = 12i32", "$0_1:i32 = 13i32"], insts(&cfg_ir)[2..4]);
+}
+
+#[test]
+fn cold_block_layout_pass_test() {
+    let insts = |input: &str| -> Vec<String> {
+        let mut cfg_ir = get_cfg_ir(input);
+        SimplifyCFG.run(&mut cfg_ir).unwrap();
+        ColdBlockLayout.run(&mut cfg_ir).unwrap();
+        let cfg = cfg_ir.cfgs.iter().find(|cfg| cfg.func_name == "f").unwrap();
+        cfg.iter_inst().map(|i| i.to_string()).collect()
+    };
+
+    // the jump to the block calling `fail` is inverted, the likely path falls through to `ret`
+    let input = r#"
+        #[cold]
+        fn fail() -> i32 { 0 }
+        fn f(a: i32) -> i32 {
+            let mut b = a;
+            if a < 0 {
+                b = fail();
+            }
+            b + 1
+        }
+    "#;
+    assert_eq!(
+        vec![
+            "mut b_3:i32 = a_3:i32",
+            "if a_3:i32 < 0i32 goto (2)",
+            "$1_1:i32 = mut b_3:i32 + 1i32",
+            "ret $1_1:i32",
+            "call @fail()",
+            "mut b_3:i32 = $ret:i32",
+            "goto (1)",
+        ],
+        insts(input)
+    );
+
+    // the cold block of a loop body is moved after `ret` and jumps back into the loop
+    let input = r#"
+        #[cold]
+        fn log(i: i32) {}
+        fn f(n: i32) {
+            let mut i = 0;
+            while i < n {
+                if i == 7 {
+                    log(i);
+                }
+                i += 1;
+            }
+        }
+    "#;
+    assert_eq!(
+        vec![
+            "mut i_3:i32 = 0i32",
+            "if mut i_3:i32 >= n_3:i32 goto (4)",
+            "if mut i_3:i32 == 7i32 goto (5)",
+            "mut i_3:i32 = mut i_3:i32 + 1i32",
+            "goto (1)",
+            "ret ()",
+            "call @log(mut i_3:i32)",
+            "goto (3)",
+        ],
+        insts(input)
+    );
+
+    // the conditional jump back falls through to a new block of `goto`
+    let ir: crate::ir::linear_ir::LinearIR = "fn f(a: bool) scope 1 {
+            (1) if a_1:bool goto (5)
+            (2) mut x_1:i32 = mut x_1:i32 + 1i32
+            (3) if a_1:bool goto (2)
+            (4) call @fail()
+            (5) ret ()
+        }"
+    .parse()
+    .unwrap();
+    let mut cfg = crate::ir::cfg::CFG::new(ir.funcs.into_iter().next().unwrap());
+    cfg.layout_cold_blocks(&["fail".to_string()].iter().cloned().collect());
+    let cfg_insts: Vec<String> = cfg.iter_inst().map(|i| i.to_string()).collect();
+    assert_eq!(
+        vec![
+            "if a_1:bool goto (3)",
+            "mut x_1:i32 = mut x_1:i32 + 1i32",
+            "if a_1:bool goto (1)",
+            "goto (4)",
+            "ret ()",
+            "call @fail()",
+            "goto (3)",
+        ],
+        cfg_insts
+    );
+    assert_eq!(vec![0, 4], cfg.basic_blocks[3].predecessors);
+
+    // nothing is moved without cold functions
+    let input = "fn f(a: i32) -> i32 { if a < 0 { return 1; } 2 }";
+    let mut cfg_ir = get_cfg_ir(input);
+    SimplifyCFG.run(&mut cfg_ir).unwrap();
+    let expected: Vec<String> = cfg_ir.cfgs[0].iter_inst().map(|i| i.to_string()).collect();
+    assert_eq!(expected, insts(input));
+}
+
+#[test]
+fn print_ir_after_test() {
+    let mut cfg_ir = get_cfg_ir("fn main() {let a = 2;}");
+    let mut pass_manager = PassManager::with_opt_level(OptimizeLevel::One, false);
+    pass_manager.set_print_ir_after(Some("simplify-cfg".into()));
+    let mut dump = Vec::<u8>::new();
+    pass_manager.run(&mut cfg_ir, &mut dump).unwrap();
+    let dump = String::from_utf8(dump).unwrap();
+    assert_eq!(
+        "*** IR Dump After simplify-cfg ***\n\
+         fn main() scope 2 {\n\
+         bb0:\n    \
+         a_2:i32 = 2i32\n    \
+         ret ()\n\
+         }\n",
+        dump
+    );
+
+    let mut pass_manager = PassManager::with_opt_level(OptimizeLevel::Zero, false);
+    pass_manager.set_print_ir_after(Some("simplify-cfg".into()));
+    assert_eq!(
+        Err(
+            "pass `simplify-cfg` is not in the pipeline `bounds-checks, reaching-definitions`"
+                .into()
+        ),
+        pass_manager.run(&mut cfg_ir, &mut Vec::<u8>::new())
+    );
+}
+
+#[test]
+fn stop_after_test() {
+    let input = "fn main() { let mut a = 0; if 1 > 2 { a = 1; } }";
+    let mut cfg_ir = get_cfg_ir(input);
+    let mut pass_manager = PassManager::with_opt_level(OptimizeLevel::One, false);
+    pass_manager.set_stop_after(Some("simplify-cfg".into()));
+    pass_manager.run(&mut cfg_ir, &mut Vec::<u8>::new()).unwrap();
+    assert_eq!(2, cfg_ir.cfgs[0].basic_blocks.len());
+    let names: Vec<&str> = pass_manager.timings().iter().map(|(name, _)| *name).collect();
+    assert_eq!(vec!["constant-propagation", "simplify-cfg"], names);
+
+    let mut pass_manager = PassManager::with_opt_level(OptimizeLevel::Zero, false);
+    pass_manager.set_stop_after(Some("simplify-cfg".into()));
+    assert_eq!(
+        Err(
+            "pass `simplify-cfg` is not in the pipeline `bounds-checks, reaching-definitions`"
+                .into()
+        ),
+        pass_manager.run(&mut get_cfg_ir(input), &mut Vec::<u8>::new())
+    );
+}
+
+#[test]
+fn o2_pipeline_test() {
+    // the branches only assign `x` which is never read, -O2 removes the blocks emptied by
+    // dead-store-elimination
+    let input = r#"
+        fn f(c: bool) -> i32 {
+            let mut x = 0;
+            if c {
+                x = 1;
+            } else {
+                x = 2;
+            }
+            3
+        }
+    "#;
+    let blocks = |opt_level: OptimizeLevel| -> usize {
+        let mut cfg_ir = get_cfg_ir(input);
+        let mut pass_manager = PassManager::with_opt_level(opt_level, false);
+        pass_manager.run(&mut cfg_ir, &mut Vec::<u8>::new()).unwrap();
+        cfg_ir.cfgs[0].basic_blocks.len()
+    };
+    assert_eq!(4, blocks(OptimizeLevel::One));
+    assert_eq!(1, blocks(OptimizeLevel::Two));
+}
+
+#[test]
+fn dead_store_elimination_pass_test() {
+    let mut cfg_ir = get_cfg_ir(
+        r#"
+        fn foo(x: i32) -> i32 {
+            let mut a = 0;
+            a = x + 5;
+            let mut i = 3;
+            while i > 0 {
+                a = a + 1;
+                i = i - 1;
+            }
+            let t = a * 2;
+            let u = t;
+            a
+        }
+    "#,
+    );
+    crate::ir::passes::DeadStoreElimination.run(&mut cfg_ir).unwrap();
+    let insts: Vec<String> = cfg_ir.cfgs[0].iter_inst().map(|i| i.to_string()).collect();
+    assert_eq!(
+        vec![
+            "mut a_2:i32 = x_2:i32 + 5i32",
+            "mut i_2:i32 = 3i32",
+            "if 0i32 >= mut i_2:i32 goto (3)",
+            "mut a_2:i32 = mut a_2:i32 + 1i32",
+            "mut i_2:i32 = mut i_2:i32 - 1i32",
+            "goto (1)",
+            "ret mut a_2:i32",
+        ],
+        insts
+    );
+
+    // a variable whose address is taken may be read through the pointer
+    let mut cfg_ir = get_cfg_ir(
+        r#"
+        fn bar() -> i32 {
+            let mut a = 1;
+            let p = &mut a;
+            a = 2;
+            *p
+        }
+    "#,
+    );
+    crate::ir::passes::DeadStoreElimination.run(&mut cfg_ir).unwrap();
+    assert_eq!(5, cfg_ir.cfgs[0].iter_inst().count());
+
+    // a volatile load is kept even if its value is unused
+    let mut cfg_ir = get_cfg_ir(
+        r#"
+        fn poll(p: *const u8) {
+            unsafe {
+                read_volatile(p);
+                let s = read_volatile(p);
+                let t = *p;
+            }
+        }
+    "#,
+    );
+    crate::ir::passes::DeadStoreElimination.run(&mut cfg_ir).unwrap();
+    let insts: Vec<String> = cfg_ir.cfgs[0].iter_inst().map(|i| i.to_string()).collect();
+    assert_eq!(
+        vec![
+            "$0_3:u8 = volatile load u8 *p_2:addr",
+            "s_3:u8 = volatile load u8 *p_2:addr",
+            "ret ()"
+        ],
+        insts
+    );
+
+    // a `Memcpy` is deleted only if none of the fields it defines is used, `t.a = 5` is
+    // dead after the copy to `u` is deleted
+    let mut cfg_ir = get_cfg_ir(
+        r#"
+        struct S { a: i64, b: i64, c: i64, d: i64 }
+        fn baz() -> i64 {
+            let s = S { a: 1, b: 2, c: 3, d: 4 };
+            let mut t = s;
+            t.a = 5;
+            let u = t;
+            t.b
+        }
+    "#,
+    );
+    crate::ir::passes::DeadStoreElimination.run(&mut cfg_ir).unwrap();
+    let insts: Vec<String> = cfg_ir.cfgs[0].iter_inst().map(|i| i.to_string()).collect();
+    assert_eq!(6, insts.len());
+    assert_eq!(
+        "memcpy 32 (s.0_2:i64, s.8_2:i64, s.16_2:i64, s.24_2:i64) -> \
+         (mut t.0_2:i64, mut t.8_2:i64, mut t.16_2:i64, mut t.24_2:i64)",
+        insts[4]
+    );
+    assert_eq!("ret mut t.8_2:i64", insts[5]);
+}
+
+#[test]
+fn bounds_checks_pass_test() {
+    let insts = |input: &str| -> Vec<String> {
+        let mut cfg_ir = get_cfg_ir(input);
+        BoundsChecks.run(&mut cfg_ir).unwrap();
+        let cfg = cfg_ir.cfgs.iter().find(|cfg| cfg.func_name == "f").unwrap();
+        cfg.iter_inst().map(|i| i.to_string()).collect()
+    };
+
+    // a constant index and an index which is only assigned constants are in bounds
+    let input = r#"
+        fn f(a: [i32; 3]) -> i32 {
+            let mut i = 0;
+            if a[2] > 0 {
+                i = 1;
+            }
+            a[i]
+        }
+    "#;
+    assert!(insts(input).iter().all(|inst| !inst.contains("__rcc_panic")));
+
+    // an argument may be anything, the block is split at the check
+    let input = "fn f(a: [i32; 3], i: usize) -> i32 { let b = 1; a[i] + b }";
+    let mut cfg_ir = get_cfg_ir(input);
+    BoundsChecks.run(&mut cfg_ir).unwrap();
+    let cfg = &cfg_ir.cfgs[0];
+    assert_eq!(3, cfg.basic_blocks.len());
+    assert!(!cfg.is_leaf);
+    assert_eq!(
+        "if i_2:usize < 3usize goto (2)",
+        cfg.basic_blocks[0].instructions.back().unwrap().to_string()
+    );
+    assert_eq!(
+        "call @__rcc_panic(lit .LC0:char)",
+        cfg.basic_blocks[1].instructions.back().unwrap().to_string()
+    );
+    assert_eq!(vec![0, 1], cfg.basic_blocks[2].predecessors);
+
+    // the length of a slice is not known, jumps to the blocks after a check are renumbered
+    let input = r#"
+        fn f(s: &[usize], n: usize) -> usize {
+            let mut i = 0;
+            while i < n {
+                i += s[i];
+            }
+            s[0]
+        }
+    "#;
+    let insts = insts(input);
+    assert_eq!(2, insts.iter().filter(|inst| inst.contains("__rcc_panic")).count());
+    assert!(insts.iter().all(|inst| !inst.starts_with("check")));
+}
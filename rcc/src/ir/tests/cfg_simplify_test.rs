@@ -0,0 +1,115 @@
+use crate::ir::cfg::CFG;
+use crate::ir::tests::{expected_from_file, ir_build_o1};
+
+fn simplified_cfg(input: &str) -> CFG {
+    let mut ir = ir_build_o1(input).unwrap();
+    let mut cfg = CFG::new(ir.funcs.pop().unwrap());
+    cfg.simplify();
+    cfg
+}
+
+#[test]
+fn constant_condition_test() {
+    let cfg = simplified_cfg(
+        r#"
+        fn main() -> i32 {
+            let mut a = 0;
+            if 1 < 2 {
+                a = 3;
+            } else if a == 4 {
+                a = 4;
+            } else {
+                a = 5;
+            }
+            a
+        }
+    "#,
+    );
+    let expected = expected_from_file("test_simplify_const_cond_bb.txt");
+    assert_eq!(expected.trim_end(), format!("{:#?}", cfg.basic_blocks));
+}
+
+#[test]
+fn while_true_test() {
+    let cfg = simplified_cfg(
+        r#"
+        fn main() {
+            let mut a = 0;
+            while true {
+                a += 1;
+                if a == 10 {
+                    break;
+                }
+            }
+        }
+    "#,
+    );
+    let expected = expected_from_file("test_simplify_while_true_bb.txt");
+    assert_eq!(expected.trim_end(), format!("{:#?}", cfg.basic_blocks));
+}
+
+#[test]
+fn jump_threading_test() {
+    let cfg = simplified_cfg(
+        r#"
+        fn foo(a: i32, b: i32) -> i32 {
+            let mut c = 0;
+            if a > 3 {
+                if b > 3 {
+                    c = 1;
+                } else {
+                    c = 2;
+                }
+            } else {
+                c = 3;
+            }
+            c
+        }
+    "#,
+    );
+    let expected = expected_from_file("test_simplify_jump_threading_bb.txt");
+    assert_eq!(expected.trim_end(), format!("{:#?}", cfg.basic_blocks));
+}
+
+#[test]
+fn return_test() {
+    let cfg = simplified_cfg(
+        r#"
+        fn foo(a: i32) -> i32 {
+            if a > 3 {
+                return 1;
+            }
+            return 2;
+            a
+        }
+    "#,
+    );
+    let expected = expected_from_file("test_simplify_return_bb.txt");
+    assert_eq!(expected.trim_end(), format!("{:#?}", cfg.basic_blocks));
+}
+
+#[test]
+fn switch_test() {
+    let cfg = simplified_cfg(
+        r#"
+        fn foo(x: i32) -> i32 {
+            let mut a = 0;
+            if x == 1 {
+            } else if x == 2 {
+                a = 2;
+            } else if x == 3 {
+            } else if x == 4 {
+                a = 4;
+            }
+            a
+        }
+    "#,
+    );
+    assert_eq!(4, cfg.basic_blocks.len());
+    assert_eq!(
+        "switch x_2:i32 [1: goto (3), 2: goto (1), 3: goto (3), 4: goto (2)] else goto (3)",
+        cfg.basic_blocks[0].instructions.back().unwrap().to_string()
+    );
+    assert_eq!(vec![0], cfg.basic_blocks[1].predecessors);
+    assert_eq!(vec![0, 1, 2], cfg.basic_blocks[3].predecessors);
+}
@@ -0,0 +1,254 @@
+use crate::ast::expr::{BinOperator, UnOp};
+use crate::ir::{bin_op_may_constant_fold, un_op_may_constant_fold, Operand, Overflow};
+use crate::rcc::RccError;
+
+const ARITH_OPS: [BinOperator; 5] = [
+    BinOperator::Plus,
+    BinOperator::Minus,
+    BinOperator::Star,
+    BinOperator::Slash,
+    BinOperator::Percent,
+];
+const CMP_OPS: [BinOperator; 6] = [
+    BinOperator::Lt,
+    BinOperator::Le,
+    BinOperator::Gt,
+    BinOperator::Ge,
+    BinOperator::EqEq,
+    BinOperator::Ne,
+];
+
+fn fold(op: &BinOperator, l: &Operand, r: &Operand, overflow: Overflow) -> Option<Operand> {
+    bin_op_may_constant_fold(op, l, r, overflow).unwrap()
+}
+
+fn cmp<T: PartialOrd>(op: &BinOperator, l: T, r: T) -> bool {
+    match op {
+        BinOperator::Lt => l < r,
+        BinOperator::Le => l <= r,
+        BinOperator::Gt => l > r,
+        BinOperator::Ge => l >= r,
+        BinOperator::EqEq => l == r,
+        BinOperator::Ne => l != r,
+        _ => unreachable!(),
+    }
+}
+
+/// Every operator on the boundary values of every integer type folds to what the host computes
+#[test]
+fn int_fold_test() {
+    macro_rules! check_int {
+        ($t:ident, $operand:path) => {
+            let values = [$t::MIN, $t::MIN + 1, 0, 1, 2, 7, $t::MAX - 1, $t::MAX];
+            for &l in values.iter() {
+                for &r in values.iter() {
+                    let (lo, ro) = (&$operand(l), &$operand(r));
+                    let ops = [
+                        (BinOperator::Plus, l.checked_add(r), l.wrapping_add(r)),
+                        (BinOperator::Minus, l.checked_sub(r), l.wrapping_sub(r)),
+                        (BinOperator::Star, l.checked_mul(r), l.wrapping_mul(r)),
+                        (BinOperator::Shl, l.checked_shl(r as u32), l.wrapping_shl(r as u32)),
+                        (BinOperator::Shr, l.checked_shr(r as u32), l.wrapping_shr(r as u32)),
+                        (BinOperator::And, Some(l & r), l & r),
+                        (BinOperator::Or, Some(l | r), l | r),
+                        (BinOperator::Caret, Some(l ^ r), l ^ r),
+                    ];
+                    for (op, checked, wrapping) in ops.iter() {
+                        let actual = bin_op_may_constant_fold(op, lo, ro, Overflow::Error);
+                        match checked {
+                            Some(res) => assert_eq!(Ok(Some($operand(*res))), actual),
+                            None => assert!(actual.is_err(), "{} {} {}", lo, op, ro),
+                        }
+                        let wrapped = Some($operand(*wrapping));
+                        assert_eq!(wrapped, fold(op, lo, ro, Overflow::Wrap));
+                    }
+                    let ops = [
+                        (BinOperator::Slash, l.checked_div(r)),
+                        (BinOperator::Percent, l.checked_rem(r)),
+                    ];
+                    for (op, checked) in ops.iter() {
+                        let actual = bin_op_may_constant_fold(op, lo, ro, Overflow::Error);
+                        match checked {
+                            Some(res) => assert_eq!(Ok(Some($operand(*res))), actual),
+                            None => assert!(actual.is_err(), "{} {} {}", lo, op, ro),
+                        }
+                        let wrapped = bin_op_may_constant_fold(op, lo, ro, Overflow::Wrap);
+                        if r == 0 {
+                            assert!(wrapped.is_err(), "{} {} {}", lo, op, ro);
+                        } else if let BinOperator::Slash = op {
+                            assert_eq!(Ok(Some($operand(l.wrapping_div(r)))), wrapped);
+                        } else {
+                            assert_eq!(Ok(Some($operand(l.wrapping_rem(r)))), wrapped);
+                        }
+                    }
+                    for op in CMP_OPS.iter() {
+                        let expected = Some(Operand::Bool(cmp(op, l, r)));
+                        assert_eq!(expected, fold(op, lo, ro, Overflow::Error));
+                    }
+                    for op in [BinOperator::AndAnd, BinOperator::OrOr].iter() {
+                        assert_eq!(None, fold(op, lo, ro, Overflow::Error));
+                    }
+                }
+                let not = un_op_may_constant_fold(&UnOp::Not, &$operand(l), Overflow::Error);
+                assert_eq!(Ok(Some($operand(!l))), not);
+            }
+        };
+    }
+    check_int!(i8, Operand::I8);
+    check_int!(i16, Operand::I16);
+    check_int!(i32, Operand::I32);
+    check_int!(i64, Operand::I64);
+    check_int!(i128, Operand::I128);
+    check_int!(isize, Operand::Isize);
+    check_int!(u8, Operand::U8);
+    check_int!(u16, Operand::U16);
+    check_int!(u32, Operand::U32);
+    check_int!(u64, Operand::U64);
+    check_int!(u128, Operand::U128);
+    check_int!(usize, Operand::Usize);
+
+    // operands of different types are not folded
+    let (l, r) = (Operand::I32(1), Operand::I64(1));
+    assert_eq!(None, fold(&BinOperator::Plus, &l, &r, Overflow::Error));
+    assert_eq!(
+        Err(RccError::from("div overflow")),
+        bin_op_may_constant_fold(
+            &BinOperator::Slash,
+            &Operand::U8(1),
+            &Operand::U8(0),
+            Overflow::Wrap
+        )
+    );
+}
+
+#[test]
+fn neg_fold_test() {
+    macro_rules! check_neg {
+        ($t:ident, $operand:path) => {
+            for &v in [$t::MIN, $t::MIN + 1, -1, 0, 1, $t::MAX].iter() {
+                let neg = un_op_may_constant_fold(&UnOp::Neg, &$operand(v), Overflow::Error);
+                match v.checked_neg() {
+                    Some(res) => assert_eq!(Ok(Some($operand(res))), neg),
+                    None => assert!(neg.is_err()),
+                }
+                let neg = un_op_may_constant_fold(&UnOp::Neg, &$operand(v), Overflow::Wrap);
+                assert_eq!(Ok(Some($operand(v.wrapping_neg()))), neg);
+            }
+        };
+    }
+    check_neg!(i8, Operand::I8);
+    check_neg!(i16, Operand::I16);
+    check_neg!(i32, Operand::I32);
+    check_neg!(i64, Operand::I64);
+    check_neg!(i128, Operand::I128);
+    check_neg!(isize, Operand::Isize);
+
+    let neg = |v: Operand| un_op_may_constant_fold(&UnOp::Neg, &v, Overflow::Error).unwrap();
+    assert_eq!(Some(Operand::F32(-2.5)), neg(Operand::F32(2.5)));
+    assert_eq!(Some(Operand::F64(0.5)), neg(Operand::F64(-0.5)));
+    assert_eq!(None, neg(Operand::U32(1)));
+    assert_eq!(None, neg(Operand::Bool(true)));
+    assert_eq!(
+        "attempt to negate `-128i8`, which would overflow",
+        match un_op_may_constant_fold(&UnOp::Neg, &Operand::I8(i8::MIN), Overflow::Error) {
+            Err(RccError::Compile(e)) => e.notes[0].clone(),
+            res => panic!("{:?}", res),
+        }
+    );
+}
+
+#[test]
+fn bool_char_fold_test() {
+    for &l in [false, true].iter() {
+        for &r in [false, true].iter() {
+            let (lo, ro) = (&Operand::Bool(l), &Operand::Bool(r));
+            let ops = [
+                (BinOperator::AndAnd, l && r),
+                (BinOperator::OrOr, l || r),
+                (BinOperator::And, l & r),
+                (BinOperator::Or, l | r),
+                (BinOperator::Caret, l ^ r),
+            ];
+            for (op, expected) in ops.iter() {
+                assert_eq!(Some(Operand::Bool(*expected)), fold(op, lo, ro, Overflow::Error));
+            }
+            for op in CMP_OPS.iter() {
+                let expected = Some(Operand::Bool(cmp(op, l, r)));
+                assert_eq!(expected, fold(op, lo, ro, Overflow::Error));
+            }
+            for op in ARITH_OPS.iter() {
+                assert_eq!(None, fold(op, lo, ro, Overflow::Error));
+            }
+        }
+        let not = un_op_may_constant_fold(&UnOp::Not, &Operand::Bool(l), Overflow::Error);
+        assert_eq!(Ok(Some(Operand::Bool(!l))), not);
+    }
+
+    let chars = ['\0', 'a', 'b', 'z', '中', char::MAX];
+    for &l in chars.iter() {
+        for &r in chars.iter() {
+            let (lo, ro) = (&Operand::Char(l), &Operand::Char(r));
+            for op in CMP_OPS.iter() {
+                let expected = Some(Operand::Bool(cmp(op, l, r)));
+                assert_eq!(expected, fold(op, lo, ro, Overflow::Error));
+            }
+            for op in ARITH_OPS.iter() {
+                assert_eq!(None, fold(op, lo, ro, Overflow::Error));
+            }
+        }
+        let not = un_op_may_constant_fold(&UnOp::Not, &Operand::Char(l), Overflow::Error);
+        assert_eq!(Ok(None), not);
+    }
+}
+
+#[test]
+fn float_fold_test() {
+    macro_rules! check_float {
+        ($t:ident, $operand:path) => {
+            let values = [
+                $t::NEG_INFINITY,
+                $t::MIN,
+                -1.5,
+                -0.0,
+                0.0,
+                0.1,
+                $t::MAX,
+                $t::INFINITY,
+                $t::NAN,
+            ];
+            for &l in values.iter() {
+                for &r in values.iter() {
+                    let (lo, ro) = (&$operand(l), &$operand(r));
+                    let ops = [
+                        (BinOperator::Plus, l + r),
+                        (BinOperator::Minus, l - r),
+                        (BinOperator::Star, l * r),
+                        (BinOperator::Slash, l / r),
+                        (BinOperator::Percent, l % r),
+                    ];
+                    for (op, expected) in ops.iter() {
+                        // NaN is not equal to itself, so the bits are compared
+                        match fold(op, lo, ro, Overflow::Error) {
+                            Some($operand(res)) => assert_eq!(expected.to_bits(), res.to_bits()),
+                            res => panic!("{} {} {} = {:?}", lo, op, ro, res),
+                        }
+                    }
+                    // every comparison with NaN is false except `!=`
+                    for op in CMP_OPS.iter() {
+                        let expected = cmp(op, l, r);
+                        if l.is_nan() || r.is_nan() {
+                            assert_eq!(expected, matches!(op, BinOperator::Ne));
+                        }
+                        let actual = fold(op, lo, ro, Overflow::Error);
+                        assert_eq!(Some(Operand::Bool(expected)), actual);
+                    }
+                    for op in [BinOperator::Shl, BinOperator::And, BinOperator::AndAnd].iter() {
+                        assert_eq!(None, fold(op, lo, ro, Overflow::Error));
+                    }
+                }
+            }
+        };
+    }
+    check_float!(f32, Operand::F32);
+    check_float!(f64, Operand::F64);
+}
@@ -0,0 +1,755 @@
+use crate::ir::interp::Interpreter;
+use crate::ir::tests::{ir_build, ir_build_checked};
+use crate::ir::Operand;
+use crate::rcc::RccError;
+
+fn run(input: &str) -> Result<Operand, RccError> {
+    let ir = ir_build(input)?;
+    Interpreter::new(&ir).run()
+}
+
+#[test]
+fn return_test() {
+    assert_eq!(
+        Ok(Operand::I32(10)),
+        run(r#"pub fn main() -> i32{let b = 3 + 4;
+        b + 3
+    }"#)
+    );
+    assert_eq!(
+        Ok(Operand::I32(5)),
+        run(r#"
+        pub fn main() -> i32 {
+            let a = 3;
+            let b = 2;
+            return a + b;
+        }
+    "#)
+    );
+    assert_eq!(Ok(Operand::Unit), run("fn main() {let a = 2;}"));
+}
+
+#[test]
+fn control_flow_test() {
+    let input = r#"
+        fn fib10() -> i32 {
+            let mut f1 = 1;
+            let mut f2 = 1;
+            let mut i = 9;
+            while i > 0 {
+                let temp = f2;
+                f2 += f1;
+                f1 = temp;
+                i -= 1;
+            }
+            f1
+        }
+
+        fn max(a: i32, b: i32) -> i32 {
+            if a > b {
+                a
+            } else {
+                b
+            }
+        }
+
+        pub fn main() -> i32 {
+            let f = fib10();
+            let mut a = loop {
+                break 3;
+            };
+            a = max(a, 100);
+            if f == 55 {
+                a + 133
+            } else {
+                a - 44
+            }
+        }
+    "#;
+    assert_eq!(Ok(Operand::I32(233)), run(input));
+}
+
+#[test]
+fn putchar_test() {
+    let ir = ir_build(
+        r#"
+        extern "C" {
+            fn putchar(c: i32);
+        }
+
+        pub fn main() -> i32 {
+            putchar(103 + 1);
+            putchar(105);
+            let mut i = 0;
+            while i < 3 {
+                putchar(33);
+                i += 1;
+            }
+            0
+        }
+    "#,
+    )
+    .unwrap();
+    let mut interpreter = Interpreter::new(&ir);
+    assert_eq!(Ok(Operand::I32(0)), interpreter.run());
+    assert_eq!(b"hi!!!".to_vec(), interpreter.output);
+}
+
+#[test]
+fn link_name_test() {
+    let ir = ir_build(
+        r#"
+        pub fn main() {
+            put(114);
+            extern "C" {
+                #[link_name = "putchar"]
+                fn write_char(c: i32);
+            }
+            write_char(99);
+        }
+
+        extern "C" {
+            #[link_name = "putchar"]
+            fn put(c: i32);
+        }
+    "#,
+    )
+    .unwrap();
+    let mut interpreter = Interpreter::new(&ir);
+    assert_eq!(Ok(Operand::Unit), interpreter.run());
+    assert_eq!(b"rc".to_vec(), interpreter.output);
+}
+
+#[test]
+fn print_test() {
+    let ir = ir_build(
+        r#"
+        pub fn main() {
+            let s = "rcc";
+            let mut i = 0;
+            while i < 2 {
+                print!("{}{}", s, i);
+                i += 1;
+            }
+            println!();
+            let u = 255u8;
+            println!("{} + {} = {}\t{{{}}}", -3, 5i64, -3 + 5, 'c');
+            print!("{}", u);
+        }
+    "#,
+    )
+    .unwrap();
+    let mut interpreter = Interpreter::new(&ir);
+    assert_eq!(Ok(Operand::Unit), interpreter.run());
+    assert_eq!(
+        "rcc0rcc1\n-3 + 5 = 2\t{c}\n255",
+        String::from_utf8(interpreter.output).unwrap()
+    );
+}
+
+#[test]
+fn assert_test() {
+    let input = r#"
+        fn sq(a: i32) -> i32 {
+            a * a
+        }
+        pub fn main() -> i32 {
+            assert!(sq(2) == 4 && sq(3) > 8);
+            assert_ne!(sq(3), 10, "9 is not 10");
+            assert_eq!(sq(3), 10);
+            0
+        }
+    "#;
+    // assertions are checked without `checks`, the lines are unknown without positions
+    assert_eq!(
+        Err("panicked at 'assertion failed: sq(3) == 10' in `main`".into()),
+        Interpreter::new(&ir_build(input).unwrap()).run()
+    );
+    let input = r#"
+        pub fn main() -> i32 {
+            let a = 1;
+            assert!(a == 2, "a is {{1}}");
+            0
+        }
+    "#;
+    assert_eq!(
+        Err("panicked at 'a is {1}' in `main`".into()),
+        Interpreter::new(&ir_build(input).unwrap()).run()
+    );
+}
+
+#[test]
+fn checks_test() {
+    let input = r#"
+        fn div(a: i32, b: i32) -> i32 {
+            let mut c = a;
+            c /= b;
+            c
+        }
+        pub fn main() -> i32 {
+            let a = div(100, 7);
+            a + div(1, a - 14)
+        }
+    "#;
+    assert_eq!(
+        Err("panicked at 'attempt to divide by zero' in `div`".into()),
+        Interpreter::new(&ir_build_checked(input).unwrap()).run()
+    );
+    // without checks, the interpreter fails like division on the host
+    assert_eq!(
+        Err("div overflow".into()),
+        Interpreter::new(&ir_build(input).unwrap()).run()
+    );
+
+    let input = r#"
+        pub fn main() -> i32 {
+            let a = 0;
+            5 % a
+        }
+    "#;
+    assert_eq!(
+        Err("panicked at 'attempt to calculate the remainder with a divisor of zero' in `main`".into()),
+        Interpreter::new(&ir_build_checked(input).unwrap()).run()
+    );
+}
+
+#[test]
+fn box_test() {
+    let ir = ir_build(
+        r#"
+        fn new_counter(n: i64) -> Box<i64> {
+            let b = Box::new(n);
+            b
+        }
+        fn get(b: Box<i64>) -> i64 {
+            *b
+        }
+        pub fn main() -> i64 {
+            let mut sum = 0i64;
+            let mut i = 0;
+            while i < 3 {
+                let mut c = new_counter(10i64);
+                *c += 1i64;
+                let bb = Box::new(c);
+                sum = sum + **bb;
+                i += 1;
+            }
+            let mut b = Box::new(5i64);
+            *b = *b * 2i64;
+            sum + get(b)
+        }
+    "#,
+    )
+    .unwrap();
+    let mut interpreter = Interpreter::new(&ir);
+    assert_eq!(Ok(Operand::I64(43)), interpreter.run());
+    // `bb` and the box in it are freed in every iteration, and `b` is freed by `get`
+    assert_eq!(0, interpreter.live_allocations());
+
+    // moving out of `*b` does not free the inner box twice
+    let ir = ir_build(
+        r#"
+        fn get(b: Box<i32>) -> i32 {
+            *b
+        }
+        pub fn main() -> i32 {
+            let b = Box::new(Box::new(5));
+            let c = *b;
+            get(c)
+        }
+    "#,
+    )
+    .unwrap();
+    assert_eq!(Ok(Operand::I32(5)), Interpreter::new(&ir).run());
+}
+
+#[test]
+fn dyn_trait_test() {
+    let ir = ir_build(
+        r#"
+        trait Shape {
+            fn area(&self) -> i32;
+            fn scale(&self, k: i32) -> i32;
+        }
+        impl Shape for i32 {
+            fn area(&self) -> i32 {
+                *self * *self
+            }
+            fn scale(&self, k: i32) -> i32 {
+                self.area() * k
+            }
+        }
+        impl Shape for bool {
+            fn area(&self) -> i32 {
+                1
+            }
+            fn scale(&self, k: i32) -> i32 {
+                k
+            }
+        }
+        fn total(s: &dyn Shape) -> i32 {
+            s.area() + s.scale(10)
+        }
+        pub fn main() -> i32 {
+            let b = true;
+            let d: &dyn Shape = &b;
+            let n = 3;
+            total(d) + total(&n)
+        }
+    "#,
+    )
+    .unwrap();
+    let mut interpreter = Interpreter::new(&ir);
+    assert_eq!(Ok(Operand::I32(1 + 10 + 9 + 90)), interpreter.run());
+    assert_eq!(0, interpreter.live_allocations());
+}
+
+#[test]
+fn vec_test() {
+    let ir = ir_build(
+        r#"
+        fn sum(v: Vec<i64>) -> i64 {
+            let mut s = 0i64;
+            let mut i = 0;
+            while i < v.len() {
+                s += v[i];
+                i += 1;
+            }
+            s
+        }
+        pub fn main() -> i64 {
+            let mut v = Vec::new();
+            let mut i = 0i64;
+            while i < 10i64 {
+                v.push(i * i);
+                i += 1i64;
+            }
+            v[0] = 100i64;
+            v[9] -= 1i64;
+            let mut w: Vec<bool> = Vec::new();
+            w.push(true);
+            let mut s = String::from("len: ");
+            s.push('x');
+            s.push_str("y\n");
+            print!("{}{}", s, w.len());
+            sum(v)
+        }
+    "#,
+    )
+    .unwrap();
+    let mut interpreter = Interpreter::new(&ir);
+    assert_eq!(Ok(Operand::I64(384)), interpreter.run());
+    assert_eq!("len: xy\n1", String::from_utf8(interpreter.output.clone()).unwrap());
+    assert_eq!(0, interpreter.live_allocations());
+
+    assert_eq!(
+        Err("index out of bounds: the len is 1 but the index is 1".into()),
+        run(r#"
+        pub fn main() -> i32 {
+            let mut v = Vec::new();
+            v.push(3);
+            v[1]
+        }
+    "#)
+    );
+}
+
+#[test]
+fn array_test() {
+    let ir = ir_build(
+        r#"
+        const N: usize = 3;
+        fn rev(a: [i64; N]) -> [i64; N] {
+            [a[2], a[1], a[0]]
+        }
+        fn bump(a: &mut [i64; N], i: usize) {
+            a[i] += 10i64;
+        }
+        pub fn main() -> i64 {
+            let a = [1i64, 2, 3];
+            let mut b = rev(a);
+            bump(&mut b, 1);
+            b = [b[1], b[2], b[0]];
+            let r = &b;
+            let c = *r;
+            let z = [0u8; N * 2];
+            a[0] * 1000i64 + c[0] * 100i64 + c[1] * 10i64 + c[2] + b[2] - a[2]
+        }
+    "#,
+    )
+    .unwrap();
+    let mut interpreter = Interpreter::new(&ir);
+    assert_eq!(Ok(Operand::I64(1000 + 1200 + 10 + 3 + 3 - 3)), interpreter.run());
+    // the arrays are in the stack frames, not on the heap
+    assert_eq!(0, interpreter.live_allocations());
+
+    assert_eq!(
+        Err("panicked at 'index out of bounds: the len is 2' in `main`".into()),
+        run(r#"
+        pub fn main() -> i32 {
+            let a = [3, 4];
+            let mut i = 0;
+            while i < 3 {
+                i += 1;
+            }
+            a[i]
+        }
+    "#)
+    );
+}
+
+#[test]
+fn slice_test() {
+    let ir = ir_build(
+        r#"
+        fn get(s: &[i64], i: usize) -> i64 {
+            s[i]
+        }
+        fn set(s: &mut [i64], i: usize, v: i64) {
+            s[i] = v;
+        }
+        pub fn main() -> i64 {
+            let mut a = [1i64, 2, 3];
+            set(&mut a, 2, 7i64);
+            let s: &[i64] = &a;
+            let t = s;
+            let n = t.len() * 10 + "hi".len();
+            if n == 32 {
+                get(t, 2) * 10i64 + get(&a, 0)
+            } else {
+                0i64
+            }
+        }
+    "#,
+    )
+    .unwrap();
+    let mut interpreter = Interpreter::new(&ir);
+    assert_eq!(Ok(Operand::I64(70 + 1)), interpreter.run());
+
+    assert_eq!(
+        Err("panicked at 'index out of bounds' in `get`".into()),
+        run(r#"
+        fn get(s: &[i32], i: usize) -> i32 {
+            s[i]
+        }
+        pub fn main() -> i32 {
+            let a = [3, 4];
+            get(&a, 2)
+        }
+    "#)
+    );
+}
+
+#[test]
+fn overflow_checks_test() {
+    /// body of `f(a, b)`, type, a, b, result or the operation which overflows
+    type Case = (
+        &'static str,
+        &'static str,
+        &'static str,
+        &'static str,
+        Result<Operand, &'static str>,
+    );
+    const MIN: &str = "-2147483647 - 1";
+    let cases: &[Case] = &[
+        ("a + b", "i32", "2147483647", "1", Err("add")),
+        ("a + b", "i32", "-2147483647", "-1", Ok(Operand::I32(i32::MIN))),
+        ("a + b", "i32", MIN, "-1", Err("add")),
+        ("a + b", "u8", "200", "55", Ok(Operand::U8(255))),
+        ("a + b", "u8", "200", "56", Err("add")),
+        ("a - b", "i32", "-2147483647", "2", Err("subtract")),
+        ("a - b", "i32", "2147483646", "-1", Ok(Operand::I32(i32::MAX))),
+        ("a - b", "i32", "0", MIN, Err("subtract")),
+        ("a - b", "u32", "3", "4", Err("subtract")),
+        ("a * b", "i32", "65536", "32768", Err("multiply")),
+        ("a * b", "i32", "-65536", "32768", Ok(Operand::I32(i32::MIN))),
+        ("a * b", "i32", "-1", MIN, Err("multiply")),
+        ("a * b", "i32", MIN, "-1", Err("multiply")),
+        ("a * b", "i32", "0", MIN, Ok(Operand::I32(0))),
+        ("a * b", "u16", "256", "256", Err("multiply")),
+        ("a * b", "u16", "255", "257", Ok(Operand::U16(65535))),
+        ("let mut c = a; c += b; c", "i8", "100", "28", Err("add")),
+        ("let mut c = b; c *= a; c", "i8", "-8", "16", Ok(Operand::I8(i8::MIN))),
+    ];
+    for (body, ty, a, b, expected) in cases {
+        let input = format!(
+            "fn f(a: {ty}, b: {ty}) -> {ty} {{ {} }} pub fn main() -> {ty} {{ f({}, {}) }}",
+            body,
+            a,
+            b,
+            ty = ty
+        );
+        let expected = expected.clone().map_err(|op| {
+            RccError::from(format!("panicked at 'attempt to {} with overflow' in `f`", op))
+        });
+        let actual = Interpreter::new(&ir_build_checked(&input).unwrap()).run();
+        assert_eq!(expected, actual, "{}", input);
+    }
+}
+
+#[test]
+fn un_op_test() {
+    let input = r#"
+        fn f(a: i32, b: bool, c: i32) -> i32 {
+            let d = -a;
+            let e = !c;
+            if !b {
+                !d + e
+            } else {
+                -(d - 1)
+            }
+        }
+        pub fn main() -> i32 {
+            f(7, false, 3) * 1000 + f(-2, true, 0)
+        }
+    "#;
+    // !(-7) + !3 = 6 - 4, -(2 - 1) = -1
+    assert_eq!(Ok(Operand::I32(1999)), run(input));
+    let input = "fn not(c: u8) -> u8 { !c } pub fn main() -> u8 { not(3) }";
+    assert_eq!(Ok(Operand::U8(252)), run(input));
+
+    let input = "fn neg(a: i8) -> i8 { -a } pub fn main() -> i8 { neg(-127 - 1) }";
+    assert_eq!(Ok(Operand::I8(i8::MIN)), run(input));
+    assert_eq!(
+        Err("panicked at 'attempt to negate with overflow' in `neg`".into()),
+        Interpreter::new(&ir_build_checked(input).unwrap()).run()
+    );
+}
+
+#[test]
+fn nested_fn_test() {
+    let ir = ir_build(
+        r#"
+        pub fn main() -> i32 {
+            let mut a = 3;
+            fn foo(x: i32) -> i32 {
+                fn bar(y: i32) -> i32 { y * 2 }
+                bar(x) + 1
+            }
+            a = twice(a);
+            fn twice(x: i32) -> i32 { foo(x) * 2 }
+            let b = {
+                fn foo(x: i32) -> i32 { x - 100 }
+                foo(a)
+            };
+            b + a + foo(a)
+        }
+    "#,
+    )
+    .unwrap();
+    let names: Vec<&str> = ir.funcs.iter().map(|f| f.name.as_str()).collect();
+    assert_eq!(
+        vec![
+            "_RCN15_RCN4main3foo2E3bar3E",
+            "_RCN4main3foo2E",
+            "_RCN4main5twice2E",
+            "_RCN4main3foo6E",
+            "main"
+        ],
+        names
+    );
+    // a = 14, b = 14 - 100, foo(14) = 29
+    assert_eq!(Ok(Operand::I32(-43)), Interpreter::new(&ir).run());
+}
+
+#[test]
+fn method_test() {
+    // the receivers are borrowed, dereferenced or reborrowed to match `self`
+    let counter = r#"
+        trait Counter {
+            fn bump(&mut self);
+            fn get(&self) -> i32;
+            fn take(self) -> i32;
+        }
+        impl Counter for i32 {
+            fn bump(&mut self) { *self += 1; }
+            fn get(&self) -> i32 { *self }
+            fn take(self) -> i32 { self * 10 }
+        }
+        pub fn main() -> i32 {
+            let mut n: i32 = 1;
+            n.bump();
+            let r = &mut n;
+            r.bump();
+            r.get() + n.take() + (&n).take()
+        }
+    "#;
+    assert_eq!(Ok(Operand::I32(63)), run(counter));
+    let point = r#"
+        pub fn main() -> i32 {
+            let p = Point { x: 3, y: 4 };
+            p.sum() + Point::scale(p, 2) + p.dist()
+        }
+        impl Point {
+            fn sum(self) -> i32 { self.x + self.y }
+            fn scale(p: Point, k: i32) -> i32 { p.sum() * k }
+            fn dist(self) -> i32 { Point::scale(self, 1) }
+        }
+        struct Point { x: i32, y: i32 }
+    "#;
+    let ir = ir_build(point).unwrap();
+    let names: Vec<&str> = ir.funcs.iter().map(|f| f.name.as_str()).collect();
+    assert_eq!(
+        vec!["main", "_RCI5Point3sum", "_RCI5Point5scale", "_RCI5Point4dist"],
+        names
+    );
+    assert_eq!(Ok(Operand::I32(28)), Interpreter::new(&ir).run());
+}
+
+#[test]
+fn error_test() {
+    // arithmetic wraps around without overflow checks
+    let input = r#"
+        fn add(a: i32, b: i32) -> i32 {
+            a + b
+        }
+        pub fn main() -> i32 {
+            add(2147483647, 1)
+        }
+    "#;
+    assert_eq!(Ok(Operand::I32(i32::MIN)), run(input));
+    assert_eq!(
+        Err("panicked at 'attempt to add with overflow' in `add`".into()),
+        Interpreter::new(&ir_build_checked(input).unwrap()).run()
+    );
+
+    let ir = ir_build("fn main() {loop {}}").unwrap();
+    let mut interpreter = Interpreter::new(&ir);
+    interpreter.set_max_steps(100);
+    assert_eq!(Err("too many steps, infinite loop?".into()), interpreter.run());
+
+    assert_eq!(
+        Err("stack overflow".into()),
+        run("fn main() {main();}")
+    );
+}
+
+#[test]
+fn const_test() {
+    assert_eq!(
+        Ok(Operand::Usize(35)),
+        run(r#"
+        const fn size(n: usize) -> usize {
+            let mut s = 0;
+            let mut i = 0;
+            while i < n {
+                s += 5;
+                i += 1;
+            }
+            s
+        }
+        const N: usize = size(3);
+        const M: usize = N * 2 + 4;
+        pub fn main() -> usize {
+            M + 1
+        }
+    "#)
+    );
+
+    assert_eq!(
+        Err(
+            "evaluation of constant `A` failed: cycle detected when evaluating constant `A_1`"
+                .into()
+        ),
+        ir_build("const A: i32 = B; const B: i32 = A + 1; fn main() {}").map(|_| ())
+    );
+    assert_eq!(
+        Err("evaluation of constant `A` failed: this arithmetic operation will overflow".into()),
+        ir_build("const fn f(n: u8) -> u8 { n * 2 } const A: u8 = f(200); fn main() {}")
+            .map(|_| ())
+    );
+    assert_eq!(
+        Err("evaluation of constant `A` failed: this operation will panic at runtime".into()),
+        ir_build("const fn f(n: i32) -> i32 { 1 / n } const A: i32 = f(0); fn main() {}")
+            .map(|_| ())
+    );
+    assert_eq!(
+        Err("evaluation of constant `A` failed: stack overflow".into()),
+        ir_build("const fn f(n: i32) -> i32 { f(n) } const A: i32 = f(1); fn main() {}")
+            .map(|_| ())
+    );
+    // associated constants are evaluated like the others
+    let point = r#"
+        const fn f(n: i32) -> i32 { 1 / n }
+        struct P { x: i32 }
+        impl P { const A: i32 = Self::B - 1; const B: i32 = 4; const C: i32 = f(P::A - 3); }
+        pub fn main() -> i32 { P::A * P::B }
+    "#;
+    assert_eq!(
+        Err("evaluation of constant `P::C` failed: this operation will panic at runtime".into()),
+        ir_build(point).map(|_| ())
+    );
+    assert_eq!(Ok(Operand::I32(12)), run(&point.replace("f(P::A - 3)", "f(P::A)")));
+}
+
+#[test]
+fn struct_test() {
+    let ir = ir_build(
+        r#"
+        struct Point {
+            x: i32,
+            y: i32,
+        }
+        struct Rect {
+            min: Point,
+            max: Point,
+            id: u8,
+        }
+        fn area(r: Rect) -> i32 {
+            (r.max.x - r.min.x) * (r.max.y - r.min.y)
+        }
+        pub fn main() {
+            let p = Point { x: 1, y: 2 };
+            let mut r = Rect { min: p, max: Point { x: 5, ..p }, id: 7 };
+            r.max.y = 10;
+            r.min = Point { x: r.min.y, y: r.min.x };
+            let q = Rect { id: 3, ..r };
+            println!("{} {} {} {} {}", q.min.x, q.min.y, q.max.x, q.max.y, q.id);
+            let mut s = q.min;
+            s = Point { y: s.x, x: s.y };
+            r.max.x += 1;
+            print!("{} {} {} {}", s.x, s.y, area(q), area(r));
+        }
+    "#,
+    )
+    .unwrap();
+    let mut interpreter = Interpreter::new(&ir);
+    assert_eq!(Ok(Operand::Unit), interpreter.run());
+    assert_eq!(
+        "2 1 5 10 3\n1 2 27 36",
+        String::from_utf8(interpreter.output).unwrap()
+    );
+}
+
+#[test]
+fn switch_test() {
+    let input = r#"
+        fn f(x: i32) -> i32 {
+            if x == 1 {
+                10
+            } else if x == 2 {
+                20
+            } else if 4 == x {
+                40
+            } else if x == 5 {
+                50
+            } else {
+                0
+            }
+        }
+        pub fn main() -> i32 {
+            f(4) + f(3) + f(-7) + f(5)
+        }
+    "#;
+    let ir = ir_build(input).unwrap();
+    assert_eq!(
+        "switch x_2:i32 [1: goto (2), 2: goto (4), 4: goto (6), 5: goto (8)] else goto (10)",
+        ir.funcs[0].insts[0].to_string()
+    );
+    assert_eq!(Ok(Operand::I32(90)), Interpreter::new(&ir).run());
+
+    // too few cases
+    let ir = ir_build("fn f(x: i32) { if x == 1 {} else if x == 2 {} else if x == 3 {} }");
+    let is_switch = |inst: &crate::ir::IRInst| matches!(inst, crate::ir::IRInst::Switch { .. });
+    assert!(!ir.unwrap().funcs[0].insts.iter().any(is_switch));
+}
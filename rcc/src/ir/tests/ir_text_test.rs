@@ -0,0 +1,243 @@
+use crate::ir::cfg::CFG;
+use crate::ir::linear_ir::LinearIR;
+use crate::ir::tests::{expected_from_file, ir_build};
+use std::str::FromStr;
+
+fn round_trip(ir: &LinearIR) {
+    let text = ir.to_string();
+    let parsed = LinearIR::from_str(&text).unwrap();
+    assert_eq!(text, parsed.to_string());
+    assert_eq!(ir.ro_local_strs, parsed.ro_local_strs);
+    assert_eq!(ir.vtables, parsed.vtables);
+    for (func, parsed_func) in ir.funcs.iter().zip(parsed.funcs.iter()) {
+        assert_eq!(func.name, parsed_func.name);
+        assert_eq!(func.is_global, parsed_func.is_global);
+        assert_eq!(func.fn_args, parsed_func.fn_args);
+        assert_eq!(func.block_scope_id, parsed_func.block_scope_id);
+        assert_eq!(func.insts, parsed_func.insts);
+    }
+}
+
+#[test]
+fn print_test() {
+    let ir = ir_build(
+        r#"
+        fn add(a: i32, b: i32) -> i32 {
+            a + b
+        }
+        pub fn main() -> i32 {
+            let s = "hello\n";
+            let mut c = 'c';
+            let mut a = add(3, 4);
+            while a < 10 {
+                a += 1;
+                if a == 5 {
+                    break;
+                }
+            }
+            a
+        }
+    "#,
+    )
+    .unwrap();
+    let expected = expected_from_file("test_print.ir");
+    assert_eq!(expected, ir.to_string());
+    round_trip(&ir);
+}
+
+#[test]
+fn round_trip_test() {
+    for input in [
+        r#"fn main() -> i32{let b = 3 + 4;
+        let mut a = 0;
+        if b == 7 {
+            a = 5;
+        } else if b > 100 {
+            a = -3;
+        } else {
+            a = 333;
+        }
+        a
+    }"#,
+        r#"
+        fn main() {
+            let mut a = 3;
+            loop {
+                a += 1;
+            }
+            let b = loop {
+                let a = 5 + 2;
+                break a;
+            };
+        }
+    "#,
+        r#"
+        fn foo(x: i64, y: bool, z: char) -> f64 {
+            let a = 2.5;
+            let b = 10000000000i64;
+            let c = false;
+            return 1.5e20;
+        }
+    "#,
+        r#"
+        fn main() {
+            let mut b = Box::new(Box::new(1));
+            **b += 2;
+        }
+    "#,
+        r#"
+        trait Show {
+            fn show(&self) -> i32;
+        }
+        impl Show for i32 {
+            fn show(&self) -> i32 {
+                *self
+            }
+        }
+        fn main() {
+            let d: &dyn Show = &3;
+            let a = d.show();
+        }
+    "#,
+        r#"
+        fn main() -> i32 {
+            let x = -2;
+            if x == -2 {
+                1
+            } else if x == 0 {
+                2
+            } else if x == -1 {
+                3
+            } else if x == 1 {
+                4
+            } else {
+                5
+            }
+        }
+    "#,
+        r#"
+        fn main() -> i32 {
+            let mut b = 0;
+            unsafe { asm!("addi {1}, {0}, 1 # {{x}}", "nop", in(reg) 41, out(reg) b); }
+            b
+        }
+    "#,
+        r#"
+        #[link_section = ".fast_ram"]
+        fn f() -> i32 {
+            1
+        }
+        fn main() -> i32 {
+            f()
+        }
+    "#,
+        r#"
+        struct P { x: i64, y: i64 }
+        struct S { a: P, b: P }
+        fn main() -> i64 {
+            let s = S { a: P { x: 1, y: 2 }, b: P { x: 3, y: 4 } };
+            let t = s;
+            t.b.y
+        }
+    "#,
+        r#"
+        fn main() -> i32 {
+            let a = 1;
+            let a = a + 1;
+            let a = a * 3;
+            a
+        }
+    "#,
+        r#"
+        #[cold]
+        #[inline(never)]
+        fn fail() {}
+        fn main() {
+            #[no_mangle]
+            fn inner() {}
+            fail();
+            inner();
+        }
+    "#,
+        r#"
+        fn main() -> i32 {
+            let mut a = 1;
+            let p = &mut a as *mut i32;
+            unsafe {
+                write_volatile(p, 2);
+                read_volatile(p)
+            }
+        }
+    "#,
+        r#"
+        fn get(a: [i32; 3], i: usize) -> i32 {
+            a[i]
+        }
+        fn main() -> i32 {
+            get([1, 2, 3], 2)
+        }
+    "#,
+    ]
+    .iter()
+    {
+        round_trip(&ir_build(input).unwrap());
+    }
+}
+
+#[test]
+fn parse_test() {
+    let ir = LinearIR::from_str(&expected_from_file("test_simplify_cfg.ir")).unwrap();
+    let mut cfg = CFG::new(ir.funcs.into_iter().next().unwrap());
+    assert_eq!(4, cfg.basic_blocks.len());
+    cfg.simplify();
+    assert_eq!(3, cfg.basic_blocks.len());
+}
+
+#[test]
+fn parse_error_test() {
+    for (input, expected) in [
+        (
+            "fn main() scope 1 {\n    (1) ret a_1\n}",
+            "invalid IR at line 2: expect `:` at ``, in `(1) ret a_1`",
+        ),
+        (
+            "fn main() scope 1 {\n    (1) a_1:i32 = 2i32 ** 3i32\n}",
+            "invalid IR at line 2: expect binary operator at ` 3i32`, in `(1) a_1:i32 = 2i32 ** 3i32`",
+        ),
+        (
+            "fn main() scope 1 {\n    (1) volatile *p_1:addr = 1u8\n}",
+            "invalid IR at line 2: expect `store` at `*p_1:addr = 1u8`, in `(1) volatile *p_1:addr = 1u8`",
+        ),
+        ("fn main() scope 1 {", "unexpected end of IR: expect `}`"),
+    ]
+    .iter()
+    {
+        assert_eq!(
+            Err((*expected).into()),
+            LinearIR::from_str(input).map(|_| ())
+        );
+    }
+}
+
+#[test]
+fn cfg_dot_test() {
+    let ir = ir_build(
+        r#"
+        fn main() -> i32 {
+            let mut a = 0;
+            while a < 10 {
+                if a == 5 { break; }
+                a += 1;
+            }
+            a
+        }
+    "#,
+    )
+    .unwrap();
+    let mut dot = Vec::<u8>::new();
+    crate::ir::cfg_dot::write_dot(&crate::ir::cfg::CFGIR::new(ir), &mut dot).unwrap();
+    assert_eq!(
+        expected_from_file("test_loop_cfg.dot"),
+        String::from_utf8(dot).unwrap()
+    );
+}
@@ -0,0 +1,51 @@
+use crate::ir::mangle::{
+    demangle, demangle_text, generic_fn_name, impl_fn_name, inherent_item_name, nested_fn_name,
+    vtable_name,
+};
+
+#[test]
+fn mangle_test() {
+    let args = vec!["i32".to_string(), "f64".to_string()];
+    assert_eq!("_RCG3add3i323f64E", generic_fn_name("add", &args));
+    assert_eq!("_RCM3i323Add3add", impl_fn_name("i32", "Add", "add"));
+    assert_eq!("_RCV3i324Show", vtable_name("i32", "Show"));
+    assert_eq!("_RCI5Point3len", inherent_item_name("Point", "len"));
+    // `$` joined names of both are `a$b$c`
+    assert_ne!(
+        generic_fn_name("a", &["b".to_string(), "c".to_string()]),
+        generic_fn_name("a", &["b$c".to_string()])
+    );
+
+    assert_eq!(Some("add::<i32, f64>".to_string()), demangle(&generic_fn_name("add", &args)));
+    assert_eq!(Some("<i32 as Add>::add".to_string()), demangle("_RCM3i323Add3add"));
+    assert_eq!(Some("<i32 as Show>::{vtable}".to_string()), demangle("_RCV3i324Show"));
+    assert_eq!(Some("Point::len".to_string()), demangle("_RCI5Point3len"));
+    let inner = nested_fn_name("main", "inner", 3);
+    assert_eq!("_RCN4main5inner3E", inner);
+    assert_eq!(Some("main::inner".to_string()), demangle(&inner));
+    assert_eq!(
+        Some("<i32 as Add>::add::f".to_string()),
+        demangle(&nested_fn_name("_RCM3i323Add3add", "f", 12))
+    );
+    assert_eq!(Some("main::inner::f".to_string()), demangle(&nested_fn_name(&inner, "f", 4)));
+    for symbol in [
+        "main",
+        "_RC",
+        "_RCG3addE",
+        "_RCM3i323Add4add",
+        "_RCV3i324Shows",
+        "_RCV03i32",
+        "_RCI5Point",
+        "_RCN4main5inner",
+        "_RCN4main5innerE",
+    ]
+    .iter()
+    {
+        assert_eq!(None, demangle(symbol));
+    }
+
+    assert_eq!(
+        "\tcall\tadd::<i32>\n\tlui\ta1,%hi(<i32 as Show>::{vtable})\n_RCX1a:\n",
+        demangle_text("\tcall\t_RCG3add3i32E\n\tlui\ta1,%hi(_RCV3i324Show)\n_RCX1a:\n")
+    );
+}
@@ -10,6 +10,16 @@ use crate::rcc::{OptimizeLevel, RccError};
 use crate::tests;
 use crate::tests::{assert_fmt_eq, assert_pretty_fmt_eq};
 
+#[cfg(test)]
+mod cfg_simplify_test;
+#[cfg(test)]
+mod const_fold_test;
+#[cfg(test)]
+mod interp_test;
+#[cfg(test)]
+mod ir_text_test;
+#[cfg(test)]
+mod mangle_test;
 mod o1_test;
 
 #[inline]
@@ -18,7 +28,13 @@ fn expected_from_file(file_name: &str) -> String {
 }
 
 fn ir_build_with_optimize(input: &str, opt_level: OptimizeLevel) -> Result<LinearIR, RccError> {
+    ir_build_with(input, opt_level, false)
+}
+
+fn ir_build_with(input: &str, opt_level: OptimizeLevel, checks: bool) -> Result<LinearIR, RccError> {
     let mut ir_builder = IRBuilder::new(opt_level);
+    ir_builder.set_checks(checks);
+    ir_builder.set_overflow_checks(checks);
     let mut lexer = Lexer::new(input);
     let mut cursor = ParseCursor::new(lexer.tokenize());
     let mut ast = AST::parse(&mut cursor)?;
@@ -33,6 +49,11 @@ pub(crate) fn ir_build(input: &str) -> Result<LinearIR, RccError> {
     ir_build_with_optimize(input, OptimizeLevel::Zero)
 }
 
+/// Build IR with runtime checks, including overflow checks
+pub(crate) fn ir_build_checked(input: &str) -> Result<LinearIR, RccError> {
+    ir_build_with(input, OptimizeLevel::Zero, true)
+}
+
 fn ir_build_o1(input: &str) -> Result<LinearIR, RccError> {
     ir_build_with_optimize(input, OptimizeLevel::One)
 }
@@ -65,6 +86,8 @@ fn test_ir_builder() {
 
 #[test]
 fn test_lit_num() {
+    use crate::rcc::{CompileError, ErrorKind};
+
     let ir = ir_build(
         r#"fn main() {let b: i8 = 99999999999999999999999999999;
     }"#,
@@ -72,8 +95,35 @@ fn test_lit_num() {
     .err()
     .unwrap();
     assert_eq!(
-        "ParseInt(ParseIntError { kind: PosOverflow })",
-        format!("{:?}", ir)
+        RccError::from(
+            CompileError::new(ErrorKind::Type, "literal out of range for `i8`").note(
+                "the literal `99999999999999999999999999999` does not fit into the type `i8` \
+                 whose range is `-128..=127`"
+            )
+        ),
+        ir
+    );
+    let ir = ir_build("fn main() { let a = -128i8; let b: i32 = -2147483648; }").unwrap();
+    assert_fmt_eq(
+        "[LoadData { dest: Place { label: \"a_2\", kind: Local, ir_type: I8 }, src: I8(-128) }, \
+        LoadData { dest: Place { label: \"b_2\", kind: Local, ir_type: I32 }, src: I32(-2147483648) }, \
+        Ret(Unit)]",
+        &ir.funcs.first().unwrap().insts,
+    );
+}
+
+#[test]
+fn test_lit_float() {
+    let ir = ir_build(
+        r#"fn main() {let a = 1e3 * 2.5E-3; let b = 1. < 0.5; let c = 1_0.5f32 / 2f32;
+    }"#,
+    )
+    .unwrap();
+    assert_fmt_eq(
+        "[LoadData { dest: Place { label: \"a_2\", kind: Local, ir_type: F64 }, src: F64(2.5) }, \
+        LoadData { dest: Place { label: \"b_2\", kind: Local, ir_type: Bool }, src: Bool(false) }, \
+        LoadData { dest: Place { label: \"c_2\", kind: Local, ir_type: F32 }, src: F32(5.25) }, Ret(Unit)]",
+        &ir.funcs.first().unwrap().insts,
     );
 }
 
@@ -92,6 +142,7 @@ fn test_lit_char() {
 
 #[test]
 fn test_math_overflow() {
+    use crate::rcc::{CompileError, ErrorKind};
     let b = 0x7fffffff;
     println!("{}", b);
     let ir = ir_build(
@@ -101,11 +152,42 @@ fn test_math_overflow() {
     .err()
     .unwrap();
     assert_eq!(
-        "ParseInt(ParseIntError { kind: InvalidDigit })",
-        format!("{:?}", ir)
+        RccError::from(
+            CompileError::new(ErrorKind::Other, "this arithmetic operation will overflow")
+                .note("attempt to add with overflow: `2147483647i32 + 9999i32`")
+        ),
+        ir
     );
 }
 
+#[test]
+fn test_div_by_zero() {
+    use crate::rcc::{CompileError, ErrorKind};
+    let error = |note: &str| -> RccError {
+        CompileError::new(ErrorKind::Other, "this operation will panic at runtime")
+            .note(note)
+            .into()
+    };
+    let cases = [
+        ("let a = 7 / 0;", "attempt to divide `7i32` by zero"),
+        (
+            "let a: u8 = 7 % 0;",
+            "attempt to calculate the remainder of `7u8` with a divisor of zero",
+        ),
+        ("let a = 7; let b = a / 0;", "attempt to divide by zero"),
+        (
+            "let mut a = 7; a %= 0;",
+            "attempt to calculate the remainder with a divisor of zero",
+        ),
+    ];
+    for (body, note) in cases.iter() {
+        let input = format!("fn main() {{ {} }}", body);
+        assert_eq!(Err(error(note)), ir_build(&input).map(|_| ()), "{}", input);
+    }
+    // floats do not panic
+    assert!(ir_build("fn main() { let a = 7.0 / 0.0; }").is_ok());
+}
+
 #[test]
 fn test_return() {
     let ir = ir_build(
@@ -248,6 +330,98 @@ fn test_while() {
     assert_eq!(expected, format!("{:#?}", ir.funcs.last().unwrap().insts));
 }
 
+/// Literal conditions need no test, and `!` inverts the jump
+#[test]
+fn test_cond() {
+    let ir = ir_build(
+        r#"
+        fn cond(flag: bool, a: i32) -> i32 {
+            let mut n = 0;
+            if true {
+                n += 1;
+            }
+            if false {
+                n += 2;
+            } else if !flag {
+                n += 4;
+            }
+            if !(a <= 3) {
+                n += 8;
+            } else if flag {
+                n += 16;
+            }
+            while !flag {
+                n += 32;
+                break;
+            }
+            n
+        }
+    "#,
+    )
+    .unwrap();
+    assert_eq!(expected_from_file("test_cond.ir"), ir.to_string());
+}
+
+#[test]
+fn test_logic_cond() {
+    let ir = ir_build(
+        r#"
+        fn ready(i: i32) -> bool {
+            i < 5
+        }
+        fn logic(a: bool, b: bool, n: i32) -> bool {
+            let mut i = 0;
+            while ready(i) && (i < n || !b) {
+                i += 1;
+            }
+            if a || b && i == 3 {
+                i += 2;
+            }
+            a && !b
+        }
+    "#,
+    )
+    .unwrap();
+    assert_eq!(expected_from_file("test_logic_cond.ir"), ir.to_string());
+}
+
+#[test]
+fn test_param_patterns() {
+    let ir = ir_build(
+        r#"
+        fn f(a: i32, (b, (_, c)): (u8, (bool, i32)), _: i32) -> i32 {
+            a + c
+        }
+        fn g(t: (u8, (bool, i32))) -> i32 {
+            f(1, t, 2) + f(3, (4, (true, 5)), 6)
+        }
+    "#,
+    )
+    .unwrap();
+    assert_eq!(expected_from_file("test_param_patterns.ir"), ir.to_string());
+}
+
+#[test]
+fn test_shadowing() {
+    let ir = ir_build(
+        r#"
+        fn f(a: i32) -> bool {
+            let a = a + 1;
+            let b = {
+                let c = a;
+                let a = c == 2;
+                a
+            };
+            let a = a * 2;
+            let a = if b { a } else { 0 };
+            a > 3
+        }
+    "#,
+    )
+    .unwrap();
+    assert_eq!(expected_from_file("test_shadowing.ir"), ir.to_string());
+}
+
 #[test]
 fn fib10_test() {
     let mut ir = ir_build(
@@ -1,3 +1,4 @@
+use crate::intern::Symbol;
 use crate::ir::tests::ir_build_o1;
 use crate::ir::cfg::CFG;
 use crate::ir::{Operand, IRInst, Place, IRType};
@@ -9,7 +10,7 @@ fn test_ir_builder() {
     let mut ir = ir_build_o1("fn main() {let a = 2 + 3 + 4 * 1;}").unwrap();
 
     let insts = VecDeque::from(vec![
-        IRInst::load_data(Place::local("a_2".into(), IRType::I32), I32(9)),
+        IRInst::load_data(Place::local(Symbol::intern("a_2"), IRType::I32), I32(9)),
         IRInst::Ret(Operand::Unit),
     ]);
 
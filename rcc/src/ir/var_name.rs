@@ -1,12 +1,24 @@
+use crate::intern::Symbol;
+
 pub const RA: &str = "%ra";
 pub const FP: &str = "%fp";
 
-pub fn local_var(ident: &str, scope_id: u64) -> String {
-    format!("{}_{}", ident, scope_id)
+pub fn local_var(ident: &str, scope_id: u64) -> Symbol {
+    Symbol::intern_fmt(format_args!("{}_{}", ident, scope_id))
+}
+
+/// Name of the `version`th variable `ident` declared in a scope, e.g. `a` and `a#1` of
+/// `let a = 1; let a = a + 1;`. The first version keeps its name.
+pub fn versioned_var(ident: &str, version: u32) -> Symbol {
+    if version == 0 {
+        Symbol::intern(ident)
+    } else {
+        Symbol::intern_fmt(format_args!("{}#{}", ident, version))
+    }
 }
 
-pub fn temp_local_var(temp_count: u64, scope_id: u64) -> String {
-    format!("${}_{}", temp_count, scope_id)
+pub fn temp_local_var(temp_count: u64, scope_id: u64) -> Symbol {
+    Symbol::intern_fmt(format_args!("${}_{}", temp_count, scope_id))
 }
 
 pub fn is_temp_var(var_name: &str) -> bool {
@@ -15,4 +27,100 @@ pub fn is_temp_var(var_name: &str) -> bool {
 
 pub fn branch_name(func_scope_id: u64, bb_id: usize) -> String{
     format!(".L{}_{}",  func_scope_id,bb_id)
-}
\ No newline at end of file
+}
+
+/// Label of the `n`th read only string of a module, e.g. `.LC0`
+pub fn ro_str_label(n: usize) -> String {
+    format!(".LC{}", n)
+}
+
+/// Label of the `n`th jump table of a function in `.rodata`, e.g. `.LSW1_0`
+pub fn switch_table_name(func_scope_id: u64, n: usize) -> String {
+    format!(".LSW{}_{}", func_scope_id, n)
+}
+
+/// Label of the `n`th `auipc` of a function in position-independent code, e.g. `.LPC2_0`
+pub fn pcrel_label(func_scope_id: u64, n: usize) -> String {
+    format!(".LPC{}_{}", func_scope_id, n)
+}
+
+/// Variable holding the vtable pointer of the trait object `ident`, e.g. `d$vt`
+pub fn vtable_var(ident: &str) -> Symbol {
+    Symbol::intern_fmt(format_args!("{}$vt", ident))
+}
+
+/// Variable holding the length of the slice `ident`, e.g. `s$len`
+pub fn len_var(ident: &str) -> Symbol {
+    Symbol::intern_fmt(format_args!("{}$len", ident))
+}
+
+/// Variable holding the end of the range `ident`, e.g. `r$end`
+pub fn end_var(ident: &str) -> Symbol {
+    Symbol::intern_fmt(format_args!("{}$end", ident))
+}
+
+/// Variable holding the `n`th param of a function whose pattern is not an identifier, e.g.
+/// `arg$1` of `fn f(a: i32, (b, c): (i32, i32))`. It is bound to the pattern at the start of
+/// the function.
+pub fn param_var(n: usize) -> Symbol {
+    Symbol::intern_fmt(format_args!("arg${}", n))
+}
+
+/// Hidden first param of a function returning a struct or an array, the address which the
+/// value is written to by the function
+pub const SRET: &str = "ret$";
+
+/// Variable of the scalar field at `offset` of the struct variable `ident`, e.g. `p.8`
+pub fn field_var(ident: &str, offset: u32) -> Symbol {
+    Symbol::intern_fmt(format_args!("{}.{}", ident, offset))
+}
+
+/// The struct variable and the offset of the field variable `label`, e.g. `p_1` and 8 of
+/// `p.8_1`, see `field_var`
+pub fn split_field_var(label: &str) -> Option<(Symbol, u32)> {
+    let (name, scope_id) = label.rsplit_once('_')?;
+    let (ident, offset) = name.rsplit_once('.')?;
+    let offset = offset.parse().ok()?;
+    Some((local_var(ident, scope_id.parse().ok()?), offset))
+}
+
+/// Function computing the value of the constant `symbol` at compile time, e.g. `N_1$init`
+pub fn const_init_fn(symbol: &str) -> String {
+    format!("{}$init", symbol)
+}
+
+/// Constant of the `n`th length of an array which is not a literal, evaluated before the
+/// symbols of the file are resolved
+pub fn array_len_const(n: usize) -> String {
+    format!("$len{}", n)
+}
+
+pub fn is_array_len_const(name: &str) -> bool {
+    name.starts_with("$len")
+}
+
+/// Runtime functions called by `print!` and `println!`
+pub const PRINT_STR: &str = "__rcc_print_str";
+pub const PRINT_INT: &str = "__rcc_print_int";
+pub const PRINT_UINT: &str = "__rcc_print_uint";
+pub const PRINT_CHAR: &str = "__rcc_print_char";
+
+/// Runtime function called when a check fails, it writes the message to stderr and exits.
+pub const PANIC: &str = "__rcc_panic";
+
+/// Runtime functions called by `Box::new` and at the end of the scope of a `Box`
+pub const ALLOC: &str = "__rcc_alloc";
+pub const FREE: &str = "__rcc_free";
+
+/// Runtime functions of `Vec<T>` and `String`. A vector is a pointer to `{ data, len, cap }`
+/// on the heap, `__rcc_vec_push` and `__rcc_vec_index` return the address of an element.
+pub const VEC_NEW: &str = "__rcc_vec_new";
+pub const VEC_PUSH: &str = "__rcc_vec_push";
+pub const VEC_INDEX: &str = "__rcc_vec_index";
+pub const VEC_LEN: &str = "__rcc_vec_len";
+pub const VEC_FREE: &str = "__rcc_vec_free";
+pub const STRING_PUSH_STR: &str = "__rcc_string_push_str";
+
+/// Runtime function which copies `a2` bytes from `a1` to `a0`, called for large `Memcpy`s
+pub const MEMCPY: &str = "__rcc_memcpy";
+pub const PRINT_STRING: &str = "__rcc_print_string";
@@ -1,3 +1,4 @@
+use crate::diagnostic::Span;
 use cursor::*;
 use std::str::FromStr;
 
@@ -9,9 +10,15 @@ use std::usize::MAX;
 mod tests;
 pub mod token;
 
+/// Tokens are produced on demand by `Iterator::next`, whitespaces and comments are skipped.
+/// A malformed token is returned as an `Error` token and lexing continues after it.
 pub struct Lexer<'a> {
     cursor: Cursor<'a>,
     input: &'a str,
+    /// line of the last token, starting from 1
+    line: u32,
+    /// offset of the last token, the newlines before it are counted in `line`
+    line_offset: usize,
 }
 
 const INT_SUFFIX: [&str; 12] = ["i8", "i16", "i32", "i64", "i128", "isize", "u8", "u16", "u32", "u64", "u128", "usize"];
@@ -22,45 +29,64 @@ impl<'a: 'b, 'b> Lexer<'a> {
         Lexer {
             cursor: Cursor::new(input),
             input,
+            line: 1,
+            line_offset: 0,
         }
     }
 
     pub fn new_line(&mut self, input: &'a str) {
         self.cursor = Cursor::new(input);
         self.input = input;
+        self.line = 1;
+        self.line_offset = 0;
     }
 
     pub fn tokenize(&'b mut self) -> Vec<Token<'a>> {
-        let mut tokens = vec![];
-        while !self.cursor.is_eof() {
-            let token = self.advance_token();
+        self.collect()
+    }
 
-            match token {
-                Unknown => {
-                    tokens.push(Unknown);
-                    break;
-                }
-                WhiteSpace | Comment => {}
-                _ => tokens.push(token)
-            }
-        }
-        tokens
+    /// Tokens with their lines, starting from 1, and their spans
+    pub fn with_positions(mut self) -> impl Iterator<Item = (Token<'a>, u32, Span)> {
+        std::iter::from_fn(move || {
+            let token = self.next()?;
+            let span = Span::new(self.line_offset, self.cursor.eaten_len());
+            Some((token, self.line, span))
+        })
     }
 
     fn advance_token(&'b mut self) -> Token<'a> {
+        let start = self.cursor.eaten_len();
         match self.cursor.next() {
             c if is_white_space(c) => {
                 self.cursor.eat_whitespace();
                 WhiteSpace
             }
+            'r' if matches!(self.cursor.nth(1), '"' | '#') => {
+                match self.raw_string_literal(start, 1) {
+                    Ok(s) => LitString(s),
+                    Err(e) => Error(e),
+                }
+            }
+            'b' if self.cursor.nth(1) == '"' => self.byte_string_literal(start),
+            'b' if self.cursor.nth(1) == 'r' && matches!(self.cursor.nth(2), '"' | '#') => {
+                match self.raw_string_literal(start, 2) {
+                    Ok(s) if s.is_ascii() => LitByteString(s),
+                    Ok(s) => Error(LexError::new(
+                        "non-ASCII character in raw byte string literal",
+                        start,
+                        start + s.len(),
+                    )),
+                    Err(e) => Error(e),
+                }
+            }
             c if is_id_start(c) => self.identifier_or_keyword(),
             c if ";,@#$?{}[]()".contains(c) => {
                 self.cursor.bump();
                 Token::from_str(&c.to_string()).unwrap()
             }
             '0'..='9' => self.integer_or_float_literal(),
-            '\'' => self.char_literal(self.cursor.eaten_len()),
-            '"' => self.string_literal(self.cursor.eaten_len()),
+            '\'' => self.char_literal(start),
+            '"' => self.string_literal(start),
             c if "+*%^!".contains(c) => {
                 static TABLE: [[Token; 5]; 2] = [
                     [Plus, Star, Percent, Caret, Not],
@@ -106,19 +132,25 @@ impl<'a: 'b, 'b> Lexer<'a> {
                         }
                         '*' => {
                             self.cursor.bump();
-                            let mut comment_count = 1;
-                            while comment_count > 0 {
+                            // block comments nest, `*/` closes the innermost one
+                            let mut depth = 1;
+                            while depth > 0 {
                                 match self.cursor.bump() {
-                                    EOF_CHAR => return Unknown,
-                                    '*' => {
-                                        if self.cursor.bump() == '/' {
-                                            comment_count -= 1;
-                                        }
+                                    '*' if self.cursor.next() == '/' => {
+                                        self.cursor.bump();
+                                        depth -= 1;
+                                    }
+                                    '/' if self.cursor.next() == '*' => {
+                                        self.cursor.bump();
+                                        depth += 1;
                                     }
-                                    '/' => {
-                                        if self.cursor.bump() == '*' {
-                                            comment_count += 1;
-                                        }
+                                    // `\0` may also be in the comment
+                                    EOF_CHAR if self.cursor.is_eof() => {
+                                        return Error(LexError::new(
+                                            "unterminated block comment starting here",
+                                            start,
+                                            start + 2,
+                                        ))
                                     }
                                     _ => {}
                                 }
@@ -128,8 +160,14 @@ impl<'a: 'b, 'b> Lexer<'a> {
                         _ => Slash,
                     }
                 } else {
+                    let start = self.cursor.eaten_len();
                     self.cursor.eat_characters(|c| c != '\n' && c != EOF_CHAR);
-                    Comment
+                    // `////` is not a doc comment
+                    if slash_count == 3 {
+                        DocComment(&self.input[start..self.cursor.eaten_len()])
+                    } else {
+                        Comment
+                    }
                 }
             }
             c if (c == '<' || c == '>') => {
@@ -164,19 +202,22 @@ impl<'a: 'b, 'b> Lexer<'a> {
                     Colon
                 }
             }
-            _ => {
+            c => {
                 self.cursor.bump();
-                Unknown
+                Error(LexError::new(
+                    &format!("unknown start of token: `{}`", c),
+                    start,
+                    self.cursor.eaten_len(),
+                ))
             }
         }
     }
 
     fn identifier_or_keyword(&'b mut self) -> Token<'a> {
-        let len = self.cursor.eat_id();
-        let str = self
-            .input
-            .get(self.cursor.eaten_len() - len..self.cursor.eaten_len())
-            .unwrap();
+        // `eat_id` counts chars, but the offsets are in bytes
+        let start = self.cursor.eaten_len();
+        self.cursor.eat_id();
+        let str = &self.input[start..self.cursor.eaten_len()];
         if let Ok(token) = Token::from_str(str) {
             token
         } else {
@@ -226,19 +267,20 @@ impl<'a: 'b, 'b> Lexer<'a> {
                         } else {
                             16
                         };
-                        self.digits_with_underscore(start, radix, Self::make_integer)
+                        self.digits_with_underscore(
+                            start,
+                            radix,
+                            Self::make_integer,
+                            "no valid digits found for number",
+                        )
                     }
 
-                    // 001 01.23
-                    '0'..='9' => self.decimal_or_float_literal_no_prefix(start),
-                    _ => Literal {
-                        literal_kind: self.make_integer(),
-                        value: "0",
-                    },
+                    // 0 001 01.23 0.5 0e3 0u8
+                    _ => self.decimal_or_float_literal_no_prefix(start),
                 }
             }
             '1'..='9' => self.decimal_or_float_literal_no_prefix(start),
-            _ => Unknown,
+            _ => unreachable!("a number starts with a digit"),
         }
     }
 
@@ -335,7 +377,12 @@ impl<'a: 'b, 'b> Lexer<'a> {
         debug_assert!(self.cursor.next() == 'e' || self.cursor.next() == 'E');
         self.cursor.bump();
         self.cursor.eat_char_if_in("+-");
-        self.digits_with_underscore(start, 10, Self::make_float)
+        self.digits_with_underscore(
+            start,
+            10,
+            Self::make_float,
+            "expected at least one digit in exponent",
+        )
     }
 
     fn make_integer(&'b mut self) -> LiteralKind<'a> {
@@ -367,7 +414,8 @@ impl<'a: 'b, 'b> Lexer<'a> {
     }
 
     fn digits_with_underscore(&'b mut self, start: usize, radix: u32,
-                              func: fn(&'b mut Self) -> LiteralKind<'a>) -> Token<'a> {
+                              func: fn(&'b mut Self) -> LiteralKind<'a>,
+                              message: &str) -> Token<'a> {
         if self.cursor.eat_digits_with_underscore(radix) {
             let end = self.cursor.eaten_len();
             let value = &self.input[start..end];
@@ -377,7 +425,7 @@ impl<'a: 'b, 'b> Lexer<'a> {
                 value,
             }
         } else {
-            Unknown
+            Error(LexError::new(message, start, self.cursor.eaten_len()))
         }
     }
 
@@ -387,26 +435,128 @@ impl<'a: 'b, 'b> Lexer<'a> {
 
         // ''
         if self.cursor.next() == '\'' {
-            Unknown
-        } else if self.cursor.eat_ascii_character() && self.cursor.bump() == '\'' {
-            self.lit(start, self.cursor.eaten_len(), Char)
+            self.cursor.bump();
+            return Error(LexError::new("empty character literal", start, start + 2));
+        }
+        let is_char = self.cursor.eat_ascii_character();
+        // the rest of a malformed literal in the same line
+        let extra_len = self.cursor.eat_characters(|c| c != '\'' && c != '\n' && c != EOF_CHAR);
+        if self.cursor.bump() != '\'' {
+            return Error(LexError::new(
+                "unterminated character literal starting here",
+                start,
+                start + 1,
+            ));
+        }
+        let end = self.cursor.eaten_len();
+        if !is_char || unescape(&self.input[start + 1..end - 1]).is_none() {
+            Error(LexError::new("invalid escape in character literal", start, end))
+        } else if extra_len > 0 {
+            Error(LexError::new(
+                "character literal may only contain one codepoint",
+                start,
+                end,
+            ))
         } else {
-            Unknown
+            self.lit(start, end, Char)
         }
     }
 
     fn string_literal(&'b mut self, start: usize) -> Token<'a> {
         debug_assert!(self.cursor.next() == '"');
         self.cursor.bump();
-        while self.cursor.next() != '"' && self.cursor.next() != EOF_CHAR {
-            if !self.cursor.eat_ascii_character() {
-                return Unknown;
+        loop {
+            match self.cursor.bump() {
+                EOF_CHAR => {
+                    return Error(LexError::new(
+                        "unterminated string literal starting here",
+                        start,
+                        start + 1,
+                    ))
+                }
+                '\\' => {
+                    self.cursor.bump();
+                }
+                '"' => break,
+                _ => {}
+            }
+        }
+        let end = self.cursor.eaten_len();
+        if unescape(&self.input[start + 1..end - 1]).is_none() {
+            Error(LexError::new("invalid escape in string literal", start, end))
+        } else {
+            LitString(&self.input[start..end])
+        }
+    }
+
+    /// RAW_STRING_LITERAL : r RAW_STRING_CONTENT
+    ///
+    /// RAW_STRING_CONTENT :
+    ///       " ( ~ IsolatedCR )* (non-greedy) "
+    ///     | # RAW_STRING_CONTENT #
+    ///
+    /// The prefix is `r` or `br`, escapes are not processed.
+    fn raw_string_literal(
+        &'b mut self,
+        start: usize,
+        prefix_len: usize,
+    ) -> Result<&'a str, LexError> {
+        self.cursor.bump_n(prefix_len - 1);
+        let hash_count = self.cursor.eat_equals('#', usize::MAX);
+        if self.cursor.next() != '"' {
+            return Err(LexError::new(
+                "found invalid character; only `#` is allowed in raw string delimitation",
+                start,
+                self.cursor.eaten_len(),
+            ));
+        }
+        self.cursor.bump();
+        loop {
+            match self.cursor.bump() {
+                EOF_CHAR => {
+                    return Err(LexError::new(
+                        "unterminated raw string literal starting here",
+                        start,
+                        start + prefix_len,
+                    ))
+                }
+                '"' if self.cursor.eat_equals('#', hash_count) == hash_count => {
+                    return Ok(&self.input[start..self.cursor.eaten_len()]);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// BYTE_STRING_LITERAL : b" ( ASCII_FOR_STRING | BYTE_ESCAPE )* "
+    fn byte_string_literal(&'b mut self, start: usize) -> Token<'a> {
+        debug_assert!(self.cursor.next() == 'b');
+        self.cursor.bump_n(1);
+        loop {
+            match self.cursor.bump() {
+                EOF_CHAR => {
+                    return Error(LexError::new(
+                        "unterminated byte string literal starting here",
+                        start,
+                        start + 2,
+                    ))
+                }
+                '\\' => {
+                    self.cursor.bump();
+                }
+                '"' => break,
+                _ => {}
             }
         }
-        if self.cursor.bump() == EOF_CHAR {
-            Unknown
+        let s = &self.input[start..self.cursor.eaten_len()];
+        if byte_string_value(s).is_some() {
+            LitByteString(s)
         } else {
-            LitString(&self.input[start..self.cursor.eaten_len()])
+            Error(LexError::new(
+                "invalid character or escape in byte string literal",
+                start,
+                start + s.len(),
+            ))
         }
     }
 
@@ -421,3 +571,115 @@ impl<'a: 'b, 'b> Lexer<'a> {
         self.cursor.eat_equals('=', 1) == 1
     }
 }
+
+impl<'a> Iterator for Lexer<'a> {
+    type Item = Token<'a>;
+
+    fn next(&mut self) -> Option<Token<'a>> {
+        while !self.cursor.is_eof() {
+            let start = self.cursor.eaten_len();
+            match self.advance_token() {
+                WhiteSpace | Comment => {}
+                token => {
+                    self.line += self.input[self.line_offset..start].matches('\n').count() as u32;
+                    self.line_offset = start;
+                    return Some(token);
+                }
+            }
+        }
+        None
+    }
+}
+
+/// The value of a `LitString` token, `None` if an escape is invalid.
+pub fn string_value(s: &str) -> Option<String> {
+    match s.strip_prefix('r') {
+        Some(raw) => Some(raw_content(raw)?.to_string()),
+        None => unescape(s.strip_prefix('"')?.strip_suffix('"')?),
+    }
+}
+
+/// The value of a `LitByteString` token, `None` if it is not ASCII or an escape is invalid.
+pub fn byte_string_value(s: &str) -> Option<Vec<u8>> {
+    let s = s.strip_prefix('b')?;
+    if let Some(raw) = s.strip_prefix('r') {
+        let content = raw_content(raw)?;
+        return if content.is_ascii() {
+            Some(content.as_bytes().to_vec())
+        } else {
+            None
+        };
+    }
+    let mut res = vec![];
+    let mut chars = s.strip_prefix('"')?.strip_suffix('"')?.chars();
+    while let Some(c) = chars.next() {
+        if !c.is_ascii() {
+            return None;
+        }
+        if c != '\\' {
+            res.push(c as u8);
+            continue;
+        }
+        let b = match chars.next()? {
+            'n' => b'\n',
+            't' => b'\t',
+            'r' => b'\r',
+            '0' => 0,
+            'x' => {
+                let code = chars.as_str().get(..2)?;
+                chars.nth(1);
+                u8::from_str_radix(code, 16).ok()?
+            }
+            c @ ('\\' | '\'' | '"') => c as u8,
+            _ => return None,
+        };
+        res.push(b);
+    }
+    Some(res)
+}
+
+/// `#"hello"#` -> `hello`
+fn raw_content(s: &str) -> Option<&str> {
+    s.trim_matches('#').strip_prefix('"')?.strip_suffix('"')
+}
+
+/// Decode the escapes of a char or string literal without quotes. `None` if an escape is
+/// invalid.
+pub fn unescape(s: &str) -> Option<String> {
+    let mut res = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            res.push(c);
+            continue;
+        }
+        let c = match chars.next()? {
+            'n' => '\n',
+            't' => '\t',
+            'r' => '\r',
+            '0' => '\0',
+            'x' => {
+                let code = chars.as_str().get(..2)?;
+                chars.nth(1);
+                match u8::from_str_radix(code, 16).ok()? {
+                    b if b.is_ascii() => char::from(b),
+                    _ => return None,
+                }
+            }
+            'u' => {
+                let rest = chars.as_str().strip_prefix('{')?;
+                let close = rest.find('}')?;
+                if !(1..=6).contains(&close) {
+                    return None;
+                }
+                let code = u32::from_str_radix(&rest[..close], 16).ok()?;
+                chars.nth(close + 1);
+                char::from_u32(code)?
+            }
+            c @ ('\\' | '\'' | '"') => c,
+            _ => return None,
+        };
+        res.push(c);
+    }
+    Some(res)
+}
@@ -1,8 +1,12 @@
 mod lexer_tests {
     use crate::lexer::token::Token::*;
-    use crate::lexer::token::{LiteralKind, LiteralKind::*, Token};
+    use crate::lexer::token::{LexError, LiteralKind, LiteralKind::*, Token};
     use crate::lexer::Lexer;
 
+    fn error(message: &str, lo: usize, hi: usize) -> Token<'static> {
+        Error(LexError::new(message, lo, hi))
+    }
+
     fn validate_tokenize(inputs: Vec<&str>, excepted_outputs: Vec<Vec<Token>>) {
         for (input, excepted) in inputs.iter().zip(excepted_outputs.iter()) {
             let mut lexer = Lexer::new(input);
@@ -49,6 +53,7 @@ mod lexer_tests {
                     Identifier("i81"),
                 ],
                 vec![
+                    DocComment(" add a to b"),
                     Fn,
                     Identifier("add"),
                     LeftParen,
@@ -101,6 +106,23 @@ mod lexer_tests {
         )
     }
 
+    #[test]
+    fn unicode_identifier_test() {
+        validate_tokenize(
+            vec!["café = 变量 + a\u{0663}", "\u{0663}"],
+            vec![
+                vec![
+                    Identifier("café"),
+                    Eq,
+                    Identifier("变量"),
+                    Plus,
+                    Identifier("a\u{0663}"),
+                ],
+                vec![error("unknown start of token: `\u{0663}`", 0, 2)],
+            ],
+        );
+    }
+
     #[test]
     fn eq_test() {
         validate_tokenize(
@@ -127,14 +149,20 @@ mod lexer_tests {
     #[test]
     fn number_literal_test() {
         validate_tokenize(
-            vec!["3f32", "0o", "0b__", "12.3 1e9 0x37ffhello2  1usize"],
+            vec![
+                "3f32",
+                "0o",
+                "0b__",
+                "12.3 1e9 0x37ffhello2  1usize",
+                "1_000u64 0xFFu8 0x1_i32",
+            ],
             vec![
                 vec![Literal {
                     literal_kind: LiteralKind::f32(),
                     value: "3",
                 }],
-                vec![Unknown],
-                vec![Unknown],
+                vec![error("no valid digits found for number", 0, 2)],
+                vec![error("no valid digits found for number", 0, 4)],
                 vec![
                     Literal {
                         literal_kind: LiteralKind::float_no_suffix(),
@@ -154,6 +182,61 @@ mod lexer_tests {
                         value: "1",
                     },
                 ],
+                vec![
+                    Literal {
+                        literal_kind: Integer { suffix: "u64" },
+                        value: "1_000",
+                    },
+                    Literal {
+                        literal_kind: Integer { suffix: "u8" },
+                        value: "0xFF",
+                    },
+                    Literal {
+                        literal_kind: Integer { suffix: "i32" },
+                        value: "0x1_",
+                    },
+                ],
+            ],
+        );
+    }
+
+    #[test]
+    fn float_literal_test() {
+        validate_tokenize(
+            vec!["0.5 2.5E-3 1e+9f32 0e1 1_0.2_5", "0 0u8"],
+            vec![
+                vec![
+                    Literal {
+                        literal_kind: LiteralKind::float_no_suffix(),
+                        value: "0.5",
+                    },
+                    Literal {
+                        literal_kind: LiteralKind::float_no_suffix(),
+                        value: "2.5E-3",
+                    },
+                    Literal {
+                        literal_kind: LiteralKind::f32(),
+                        value: "1e+9",
+                    },
+                    Literal {
+                        literal_kind: LiteralKind::float_no_suffix(),
+                        value: "0e1",
+                    },
+                    Literal {
+                        literal_kind: LiteralKind::float_no_suffix(),
+                        value: "1_0.2_5",
+                    },
+                ],
+                vec![
+                    Literal {
+                        literal_kind: LiteralKind::integer_no_suffix(),
+                        value: "0",
+                    },
+                    Literal {
+                        literal_kind: Integer { suffix: "u8" },
+                        value: "0",
+                    },
+                ],
             ],
         );
     }
@@ -166,12 +249,47 @@ mod lexer_tests {
                 r#"x = "\n\\\"'\'\0\t\r""#,
                 "\"\"",
                 r#""hello\""#,
+                r#""\x41\u{1F600}é""#,
+                r#""\x80""#,
+                r#""\u{D800}""#,
             ],
             vec![
                 vec![LitString(r#""hello""#)],
                 vec![Identifier("x"), Eq, LitString(r#""\n\\\"'\'\0\t\r""#)],
                 vec![LitString("\"\"")],
-                vec![Unknown],
+                vec![error("unterminated string literal starting here", 0, 1)],
+                vec![LitString(r#""\x41\u{1F600}é""#)],
+                vec![error("invalid escape in string literal", 0, 6)],
+                vec![error("invalid escape in string literal", 0, 10)],
+            ],
+        );
+    }
+
+    #[test]
+    fn raw_and_byte_string_literal_test() {
+        validate_tokenize(
+            vec![
+                r###"r"\n" r#"a"b"# r##"a"#b"##"###,
+                r##"b"a\xff\"" br#"a"b"#"##,
+                r#"r#"a"#,
+                r#"b"é""#,
+                r#"b"\u{41}""#,
+                "r #",
+            ],
+            vec![
+                vec![
+                    LitString(r#"r"\n""#),
+                    LitString(r##"r#"a"b"#"##),
+                    LitString(r###"r##"a"#b"##"###),
+                ],
+                vec![
+                    LitByteString(r#"b"a\xff\"""#),
+                    LitByteString(r##"br#"a"b"#"##),
+                ],
+                vec![error("unterminated raw string literal starting here", 0, 1)],
+                vec![error("invalid character or escape in byte string literal", 0, 5)],
+                vec![error("invalid character or escape in byte string literal", 0, 9)],
+                vec![Identifier("r"), Pound],
             ],
         );
     }
@@ -179,22 +297,30 @@ mod lexer_tests {
     #[test]
     fn char_literal_test() {
         validate_tokenize(
-            vec!["'a' '\''", "'\\", r#"'\''"#, "''", "'''"],
+            vec!["'a' '\''", "'\\", r#"'\''"#, "''", "'''", r#"'\u{41}'"#],
             vec![
                 vec![
                     Literal {
                         literal_kind: Char,
                         value: "'a'",
                     },
-                    Unknown,
+                    error("empty character literal", 4, 6),
+                    error("unterminated character literal starting here", 6, 7),
                 ],
-                vec![Unknown],
+                vec![error("unterminated character literal starting here", 0, 1)],
                 vec![Literal {
                     literal_kind: Char,
                     value: r#"'\''"#,
                 }],
-                vec![Unknown],
-                vec![Unknown],
+                vec![error("empty character literal", 0, 2)],
+                vec![
+                    error("empty character literal", 0, 2),
+                    error("unterminated character literal starting here", 2, 3),
+                ],
+                vec![Literal {
+                    literal_kind: Char,
+                    value: r#"'\u{41}'"#,
+                }],
             ],
         );
     }
@@ -229,6 +355,7 @@ mod lexer_tests {
             vec![
                 "/**",
                 "///  ///",
+                "//// not doc",
                 "/= / //",
                 "//",
                 r#"/*
@@ -239,14 +366,28 @@ mod lexer_tests {
                 r#"/*
                 ///*/*/
                 *// */*/"#,
+                "/* /* nested */ */ a",
+                "/* a **/ b",
+                "/* //* */ */",
+                "a /* /* */",
+                "/* \0 */ ~",
             ],
             vec![
-                vec![Unknown],
+                vec![error("unterminated block comment starting here", 0, 2)],
+                vec![DocComment("  ///")],
                 vec![],
                 vec![SlashEq, Slash],
                 vec![],
-                vec![Unknown],
+                vec![error("unterminated block comment starting here", 0, 2)],
                 vec![],
+                vec![Identifier("a")],
+                vec![Identifier("b")],
+                vec![],
+                vec![
+                    Identifier("a"),
+                    error("unterminated block comment starting here", 2, 4),
+                ],
+                vec![error("unknown start of token: `~`", 8, 9)],
             ],
         );
     }
@@ -325,6 +466,22 @@ mod lexer_tests {
             vec![vec![Lt, Le, Shl, ShlEq, Gt, Ge, Shr, ShrEq], vec![Shl, Lt]],
         );
     }
+
+    #[test]
+    fn line_test() {
+        use crate::diagnostic::Span;
+        let input = "fn a\n// b\n\n/* c\n /* \n */ */ \"d\ne\" f";
+        let positions: Vec<(Token, u32, Span)> = Lexer::new(input).with_positions().collect();
+        assert_eq!(
+            vec![
+                (Fn, 1, Span::new(0, 2)),
+                (Identifier("a"), 1, Span::new(3, 4)),
+                (LitString("\"d\ne\""), 6, Span::new(28, 33)),
+                (Identifier("f"), 7, Span::new(34, 35)),
+            ],
+            positions
+        );
+    }
 }
 
 mod token_tests {
@@ -1,9 +1,13 @@
+use crate::diagnostic::Span;
 use crate::lexer::token::LiteralKind::{Float, Integer};
 use strenum::StrEnum;
 
+/// A token is displayed as its source text, e.g. `->` or `1u8`.
 #[derive(Clone, Debug, PartialEq, StrEnum)]
+#[strenum(display = "fmt_data")]
 pub enum Token<'a> {
     /// Strict keywords
+    #[strenum("as")]
     As,
     Break,
     Const,
@@ -75,11 +79,20 @@ pub enum Token<'a> {
         value: &'a str,
     },
 
-    /// "hello"
+    /// "hello", r#"hello"#
     /// field 0 contains quote
     #[strenum(disabled)]
     LitString(&'a str),
 
+    /// `/// doc`, field 0 is the text after `///`
+    #[strenum(disabled)]
+    DocComment(&'a str),
+
+    /// b"hello", br#"hello"#
+    /// field 0 contains prefix and quote
+    #[strenum(disabled)]
+    LitByteString(&'a str),
+
     /// Symbols
     #[strenum("+")]
     Plus,
@@ -238,14 +251,49 @@ pub enum Token<'a> {
     #[strenum(disabled)]
     Comment,
 
+    /// A malformed token, the lexer continues after it
     #[strenum(disabled)]
-    Unknown,
+    Error(LexError),
 }
 
 impl Token<'_> {
     pub fn is_range_op(&self) -> bool {
         matches!(self, Self::DotDot | Self::DotDotEq)
     }
+
+    /// Source text of the tokens which carry data
+    fn fmt_data(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Identifier(s) | Self::LitString(s) | Self::LitByteString(s) => {
+                write!(f, "{}", s)
+            }
+            Self::Literal {
+                literal_kind: Integer { suffix } | Float { suffix },
+                value,
+            } => write!(f, "{}{}", value, suffix),
+            Self::Literal { value, .. } => write!(f, "{}", value),
+            Self::DocComment(s) => write!(f, "///{}", s),
+            Self::WhiteSpace => write!(f, " "),
+            Self::Comment => write!(f, "//"),
+            Self::Error(e) => write!(f, "{}", e.message),
+            tk => unreachable!("`{:?}` has no data", tk),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct LexError {
+    pub message: String,
+    pub span: Span,
+}
+
+impl LexError {
+    pub fn new(message: &str, lo: usize, hi: usize) -> LexError {
+        LexError {
+            message: message.to_string(),
+            span: Span::new(lo, hi),
+        }
+    }
 }
 
 #[derive(Clone, Debug, PartialEq)]
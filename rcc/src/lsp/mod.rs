@@ -0,0 +1,459 @@
+//! Language server of rcc, started by `rcc --lsp`. It talks JSON-RPC over stdio and supports
+//!
+//! - diagnostics at the spans of the errors, the document is analysed again on every change
+//! - hover, which shows the signatures of functions and the types of paths
+//! - go to definition of the items referred to by paths, found by the symbol resolver
+//!
+//! Positions are counted in chars. A malformed message is logged to the client and skipped.
+use crate::analyser::analyse;
+use crate::analyser::sym_resolver::TypeInfo;
+use crate::ast::expr::PathExpr;
+use crate::ast::file::File;
+use crate::ast::item::{ExternalItemFn, FnParams, Item, ItemFn, ItemImpl};
+use crate::ast::types::TypeAnnotation;
+use crate::ast::visit::Visitor;
+use crate::diagnostic::Span;
+use crate::json::Json;
+use crate::rcc::RccError;
+use std::collections::HashMap;
+use std::io::{BufRead, Write};
+use std::str::FromStr;
+
+#[cfg(test)]
+mod tests;
+
+struct Document {
+    text: String,
+    /// function name -> signature, from the last version which is analysed successfully
+    signatures: HashMap<String, String>,
+    /// paths of the text, empty if it is not analysed successfully
+    paths: Vec<PathInfo>,
+}
+
+/// A path expression of a document
+struct PathInfo {
+    span: Span,
+    type_info: TypeInfo,
+    /// span of the name of the item it refers to, `None` for local variables
+    definition: Option<Span>,
+}
+
+#[derive(Default)]
+pub struct LanguageServer {
+    /// uri -> document
+    documents: HashMap<String, Document>,
+}
+
+impl LanguageServer {
+    pub fn new() -> LanguageServer {
+        LanguageServer::default()
+    }
+
+    /// Serve until the `exit` notification or the end of `input`. Only I/O errors stop the
+    /// server, a malformed message is logged and skipped.
+    pub fn run(&mut self, input: &mut dyn BufRead, output: &mut dyn Write) -> Result<(), RccError> {
+        loop {
+            let message = match read_message(input) {
+                Ok(Some(message)) => message,
+                Ok(None) => break,
+                Err(RccError::IO(e)) => return Err(e.into()),
+                Err(e) => {
+                    write_message(output, &log_message(&e.to_string()))?;
+                    continue;
+                }
+            };
+            if message.get("method").and_then(Json::as_str) == Some("exit") {
+                break;
+            }
+            for reply in self.handle(&message) {
+                write_message(output, &reply)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Responses and notifications to send for `message`
+    pub fn handle(&mut self, message: &Json) -> Vec<Json> {
+        let method = message.get("method").and_then(Json::as_str).unwrap_or("");
+        let params = message.get("params").unwrap_or(&Json::Null);
+        let result = match method {
+            "initialize" => Ok(Json::object(vec![
+                (
+                    "capabilities",
+                    Json::object(vec![
+                        // full text of the document is sent on every change
+                        ("textDocumentSync", Json::from(1)),
+                        ("hoverProvider", Json::Bool(true)),
+                        ("definitionProvider", Json::Bool(true)),
+                    ]),
+                ),
+                ("serverInfo", Json::object(vec![("name", Json::from("rcc"))])),
+            ])),
+            "shutdown" => Ok(Json::Null),
+            "textDocument/didOpen" => {
+                let uri = text_document_uri(params);
+                let text = params
+                    .get("textDocument")
+                    .and_then(|d| d.get("text"))
+                    .and_then(Json::as_str);
+                return match (uri, text) {
+                    (Some(uri), Some(text)) => vec![self.update(uri, text)],
+                    _ => vec![],
+                };
+            }
+            "textDocument/didChange" => {
+                let uri = text_document_uri(params);
+                let text = match params.get("contentChanges") {
+                    Some(Json::Array(changes)) => {
+                        changes.last().and_then(|c| c.get("text")).and_then(Json::as_str)
+                    }
+                    _ => None,
+                };
+                return match (uri, text) {
+                    (Some(uri), Some(text)) => vec![self.update(uri, text)],
+                    _ => vec![],
+                };
+            }
+            "textDocument/didClose" => {
+                return match text_document_uri(params) {
+                    Some(uri) => {
+                        self.documents.remove(uri);
+                        vec![publish_diagnostics(uri, vec![])]
+                    }
+                    None => vec![],
+                };
+            }
+            "textDocument/hover" => Ok(self.hover(params)),
+            "textDocument/definition" => Ok(self.definition(params)),
+            _ => Err(Json::object(vec![
+                // MethodNotFound
+                ("code", Json::Number(-32601.0)),
+                ("message", Json::from(format!("unsupported method `{}`", method).as_str())),
+            ])),
+        };
+        // notifications have no id and need no response
+        let id = match message.get("id") {
+            Some(id) => id.clone(),
+            None => return vec![],
+        };
+        let (key, value) = match result {
+            Ok(result) => ("result", result),
+            Err(error) => ("error", error),
+        };
+        vec![Json::object(vec![
+            ("jsonrpc", Json::from("2.0")),
+            ("id", id),
+            (key, value),
+        ])]
+    }
+
+    /// Analyse the new text of a document and return its diagnostics.
+    fn update(&mut self, uri: &str, text: &str) -> Json {
+        let mut diagnostics = vec![];
+        let (signatures, paths) = match analyse(text) {
+            Ok(ast) => (Some(fn_signatures(&ast.file.items)), path_infos(&ast.file)),
+            Err(errors) => {
+                for e in errors {
+                    diagnostics.push(diagnostic(text, &e));
+                }
+                (None, vec![])
+            }
+        };
+        let document = self
+            .documents
+            .entry(uri.to_string())
+            .or_insert_with(|| Document {
+                text: String::new(),
+                signatures: HashMap::new(),
+                paths: vec![],
+            });
+        document.text = text.to_string();
+        document.paths = paths;
+        if let Some(signatures) = signatures {
+            document.signatures = signatures;
+        }
+        publish_diagnostics(uri, diagnostics)
+    }
+
+    /// The uri, the document and the byte offset of the position of `params`
+    fn position_of(&self, params: &Json) -> Option<(&str, &Document, usize)> {
+        let (uri, document) = self.documents.get_key_value(text_document_uri(params)?)?;
+        let position = params.get("position")?;
+        let line = position.get("line")?.as_u64()? as usize;
+        let character = position.get("character")?.as_u64()? as usize;
+        let offset = offset_of(&document.text, line, character)?;
+        Some((uri.as_str(), document, offset))
+    }
+
+    /// The signature of a function, or the type of a path, under the position of `params`
+    fn hover(&self, params: &Json) -> Json {
+        let (_, document, offset) = match self.position_of(params) {
+            Some(found) => found,
+            None => return Json::Null,
+        };
+        let signature = |name: &str| document.signatures.get(name).cloned();
+        let value = match path_at(document, offset) {
+            Some(path) => {
+                let name = &document.text[path.span.lo..path.span.hi];
+                match &path.type_info {
+                    TypeInfo::Fn { .. } => signature(name),
+                    _ => None,
+                }
+                .unwrap_or_else(|| format!("{}: {}", name, path.type_info))
+            }
+            // the name of a function at its definition
+            None => match word_at(&document.text, offset).and_then(signature) {
+                Some(signature) => signature,
+                None => return Json::Null,
+            },
+        };
+        Json::object(vec![(
+            "contents",
+            Json::object(vec![
+                ("kind", Json::from("markdown")),
+                ("value", Json::from(format!("```rust\n{}\n```", value).as_str())),
+            ]),
+        )])
+    }
+
+    /// Location of the item referred to by the path under the position of `params`
+    fn definition(&self, params: &Json) -> Json {
+        let (uri, document, offset) = match self.position_of(params) {
+            Some(found) => found,
+            None => return Json::Null,
+        };
+        match path_at(document, offset).and_then(|path| path.definition) {
+            Some(span) => Json::object(vec![
+                ("uri", Json::from(uri)),
+                ("range", range(&document.text, span)),
+            ]),
+            None => Json::Null,
+        }
+    }
+}
+
+/// The innermost path which contains `offset`
+fn path_at(document: &Document, offset: usize) -> Option<&PathInfo> {
+    document
+        .paths
+        .iter()
+        .filter(|path| path.span.lo <= offset && offset < path.span.hi)
+        .min_by_key(|path| path.span.hi - path.span.lo)
+}
+
+/// Paths whose types are known, with the items they refer to
+struct PathInfos<'a> {
+    file: &'a File,
+    paths: Vec<PathInfo>,
+}
+
+impl Visitor for PathInfos<'_> {
+    fn visit_path_expr(&mut self, path_expr: &PathExpr) {
+        let span = match self.file.spans.get(&path_expr.node_id) {
+            Some(span) => *span,
+            None => return,
+        };
+        match self.file.type_table.node_type(path_expr.node_id) {
+            Some(type_info) if !type_info.is_unknown() => self.paths.push(PathInfo {
+                span,
+                type_info: type_info.clone(),
+                definition: self.file.references.get(&path_expr.node_id).copied(),
+            }),
+            _ => {}
+        }
+    }
+}
+
+fn path_infos(file: &File) -> Vec<PathInfo> {
+    let mut path_infos = PathInfos { file, paths: vec![] };
+    for item in file.items.iter() {
+        path_infos.visit_item(item);
+    }
+    path_infos.paths
+}
+
+/// An error of `text` at its span, or at the beginning of `text` if the span is unknown
+fn diagnostic(text: &str, e: &RccError) -> Json {
+    let span = match e {
+        RccError::Compile(error) => error.span,
+        _ => None,
+    };
+    let mut fields = vec![
+        ("range", range(text, span.unwrap_or(Span::new(0, 0)))),
+        // Error
+        ("severity", Json::from(1)),
+    ];
+    if let RccError::Compile(error) = e {
+        if let Some(code) = error.code {
+            fields.push(("code", Json::from(code)));
+        }
+    }
+    fields.push(("source", Json::from("rcc")));
+    fields.push(("message", Json::from(e.to_string().as_str())));
+    Json::object(fields)
+}
+
+fn text_document_uri(params: &Json) -> Option<&str> {
+    params.get("textDocument")?.get("uri")?.as_str()
+}
+
+fn publish_diagnostics(uri: &str, diagnostics: Vec<Json>) -> Json {
+    Json::object(vec![
+        ("jsonrpc", Json::from("2.0")),
+        ("method", Json::from("textDocument/publishDiagnostics")),
+        (
+            "params",
+            Json::object(vec![
+                ("uri", Json::from(uri)),
+                ("diagnostics", Json::Array(diagnostics)),
+            ]),
+        ),
+    ])
+}
+
+/// Range of `span` in `text`
+fn range(text: &str, span: Span) -> Json {
+    Json::object(vec![
+        ("start", position(text, span.lo)),
+        ("end", position(text, span.hi)),
+    ])
+}
+
+/// Line and char of the byte `offset` of `text`
+fn position(text: &str, offset: usize) -> Json {
+    let before = &text[..offset.min(text.len())];
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+    Json::object(vec![
+        ("line", Json::from(before.matches('\n').count() as u64)),
+        ("character", Json::from(before[line_start..].chars().count() as u64)),
+    ])
+}
+
+/// Byte offset of the `character`th char of the `line`th line of `text`
+fn offset_of(text: &str, line: usize, character: usize) -> Option<usize> {
+    let line_start = match line {
+        0 => 0,
+        _ => text.match_indices('\n').nth(line - 1)?.0 + 1,
+    };
+    let line_text = text[line_start..].split('\n').next()?;
+    match line_text.char_indices().nth(character) {
+        Some((i, _)) => Some(line_start + i),
+        None => Some(line_start + line_text.len()),
+    }
+}
+
+fn is_ident_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// The identifier which contains the byte `offset` of `text`
+fn word_at(text: &str, offset: usize) -> Option<&str> {
+    if !is_ident_char(text[offset..].chars().next()?) {
+        return None;
+    }
+    let lo = text[..offset]
+        .char_indices()
+        .rev()
+        .take_while(|(_, c)| is_ident_char(*c))
+        .last()
+        .map_or(offset, |(i, _)| i);
+    let hi = text[offset..]
+        .char_indices()
+        .find(|(_, c)| !is_ident_char(*c))
+        .map_or(text.len(), |(i, _)| offset + i);
+    Some(&text[lo..hi])
+}
+
+/// `fn add(a: i32, b: i32) -> i32`
+fn signature(name: &str, fn_params: &FnParams, ret_type: &TypeAnnotation) -> String {
+    let params: Vec<String> = fn_params
+        .params
+        .iter()
+        .map(|param| format!("{}: {:?}", param.pattern, param._type))
+        .collect();
+    let mut s = format!("fn {}({})", name, params.join(", "));
+    if *ret_type != TypeAnnotation::Unit {
+        s.push_str(&format!(" -> {:?}", ret_type));
+    }
+    s
+}
+
+/// Signatures of the fns declared at the top level of a file
+struct FnSignatures(HashMap<String, String>);
+
+impl Visitor for FnSignatures {
+    /// Fns declared in a block are not visible in the file, so the block is not walked.
+    fn visit_item_fn(&mut self, item_fn: &ItemFn) {
+        self.0.insert(
+            item_fn.name.clone(),
+            signature(&item_fn.name, &item_fn.fn_params, &item_fn.ret_type),
+        );
+    }
+
+    fn visit_external_item_fn(&mut self, f: &ExternalItemFn) {
+        self.0.insert(f.name.clone(), signature(&f.name, &f.fn_params, &f.ret_type));
+    }
+
+    /// Methods are not named by an identifier alone
+    fn visit_item_impl(&mut self, _item_impl: &ItemImpl) {}
+}
+
+fn fn_signatures(items: &[Item]) -> HashMap<String, String> {
+    let mut signatures = FnSignatures(HashMap::new());
+    for item in items.iter() {
+        signatures.visit_item(item);
+    }
+    signatures.0
+}
+
+/// `window/logMessage` notification of an error
+fn log_message(message: &str) -> Json {
+    Json::object(vec![
+        ("jsonrpc", Json::from("2.0")),
+        ("method", Json::from("window/logMessage")),
+        (
+            "params",
+            Json::object(vec![
+                // Error
+                ("type", Json::from(1)),
+                ("message", Json::from(message)),
+            ]),
+        ),
+    ])
+}
+
+/// Read a message with the `Content-Length` header, `None` at the end of input.
+fn read_message(input: &mut dyn BufRead) -> Result<Option<Json>, RccError> {
+    let mut content_length = None;
+    loop {
+        let mut line = String::new();
+        if input.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some(len) = line.strip_prefix("Content-Length:") {
+            content_length = Some(len.trim().parse::<usize>()?);
+        }
+    }
+    let len = match content_length {
+        Some(len) => len,
+        None => return Err("missing Content-Length header".into()),
+    };
+    let mut content = vec![0; len];
+    input.read_exact(&mut content)?;
+    let content = match String::from_utf8(content) {
+        Ok(content) => content,
+        Err(_) => return Err("message is not valid UTF-8".into()),
+    };
+    Ok(Some(Json::from_str(&content)?))
+}
+
+fn write_message(output: &mut dyn Write, message: &Json) -> Result<(), RccError> {
+    let content = message.to_string();
+    write!(output, "Content-Length: {}\r\n\r\n{}", content.len(), content)?;
+    output.flush()?;
+    Ok(())
+}
@@ -0,0 +1,126 @@
+use crate::json::Json;
+use crate::lsp::LanguageServer;
+use std::str::FromStr;
+
+fn message(s: &str) -> Json {
+    Json::from_str(s).unwrap()
+}
+
+#[test]
+fn lsp_test() {
+    let mut server = LanguageServer::new();
+    let replies = server.handle(&message(
+        r#"{"jsonrpc":"2.0","id":1,"method":"initialize","params":{}}"#,
+    ));
+    assert_eq!(
+        r#"[{"jsonrpc":"2.0","id":1,"result":{"capabilities":{"textDocumentSync":1,"hoverProvider":true,"definitionProvider":true},"serverInfo":{"name":"rcc"}}}]"#,
+        Json::Array(replies).to_string()
+    );
+
+    let replies = server.handle(&message(
+        r#"{"jsonrpc":"2.0","method":"textDocument/didOpen","params":{"textDocument":{"uri":"file:///a.rc","text":"fn add(a: i32, b: i32) -> i32 { a + b }\nfn main() { add(1, 2); }"}}}"#,
+    ));
+    assert_eq!(
+        r#"[{"jsonrpc":"2.0","method":"textDocument/publishDiagnostics","params":{"uri":"file:///a.rc","diagnostics":[]}}]"#,
+        Json::Array(replies).to_string()
+    );
+
+    // `add` in `main`
+    let replies = server.handle(&message(
+        r#"{"jsonrpc":"2.0","id":2,"method":"textDocument/hover","params":{"textDocument":{"uri":"file:///a.rc"},"position":{"line":1,"character":13}}}"#,
+    ));
+    assert_eq!(
+        r#"[{"jsonrpc":"2.0","id":2,"result":{"contents":{"kind":"markdown","value":"```rust\nfn add(a: i32, b: i32) -> i32\n```"}}}]"#,
+        Json::Array(replies).to_string()
+    );
+    let replies = server.handle(&message(
+        r#"{"jsonrpc":"2.0","id":3,"method":"textDocument/definition","params":{"textDocument":{"uri":"file:///a.rc"},"position":{"line":1,"character":12}}}"#,
+    ));
+    assert_eq!(
+        r#"[{"jsonrpc":"2.0","id":3,"result":{"uri":"file:///a.rc","range":{"start":{"line":0,"character":3},"end":{"line":0,"character":6}}}}]"#,
+        Json::Array(replies).to_string()
+    );
+
+    let replies = server.handle(&message(
+        r#"{"jsonrpc":"2.0","method":"textDocument/didChange","params":{"textDocument":{"uri":"file:///a.rc"},"contentChanges":[{"text":"fn main() { x }"}]}}"#,
+    ));
+    assert_eq!(
+        r#"[{"jsonrpc":"2.0","method":"textDocument/publishDiagnostics","params":{"uri":"file:///a.rc","diagnostics":[{"range":{"start":{"line":0,"character":12},"end":{"line":0,"character":13}},"severity":1,"code":"E0425","source":"rcc","message":"identifier `x` not found"}]}}]"#,
+        Json::Array(replies).to_string()
+    );
+
+    let replies = server.handle(&message(
+        r#"{"jsonrpc":"2.0","method":"textDocument/didChange","params":{"textDocument":{"uri":"file:///a.rc"},"contentChanges":[{"text":"const N: u8 = 1;\nfn main() {\n    let a = N;\n    let b = a;\n}"}]}}"#,
+    ));
+    assert_eq!(
+        r#"[{"jsonrpc":"2.0","method":"textDocument/publishDiagnostics","params":{"uri":"file:///a.rc","diagnostics":[]}}]"#,
+        Json::Array(replies).to_string()
+    );
+    // the types of paths
+    let replies = server.handle(&message(
+        r#"{"jsonrpc":"2.0","id":4,"method":"textDocument/hover","params":{"textDocument":{"uri":"file:///a.rc"},"position":{"line":3,"character":12}}}"#,
+    ));
+    assert_eq!(
+        r#"[{"jsonrpc":"2.0","id":4,"result":{"contents":{"kind":"markdown","value":"```rust\na: u8\n```"}}}]"#,
+        Json::Array(replies).to_string()
+    );
+    // `N` is resolved to the constant, local variables have no definitions yet
+    let replies = server.handle(&message(
+        r#"{"jsonrpc":"2.0","id":5,"method":"textDocument/definition","params":{"textDocument":{"uri":"file:///a.rc"},"position":{"line":2,"character":12}}}"#,
+    ));
+    assert_eq!(
+        r#"[{"jsonrpc":"2.0","id":5,"result":{"uri":"file:///a.rc","range":{"start":{"line":0,"character":6},"end":{"line":0,"character":7}}}}]"#,
+        Json::Array(replies).to_string()
+    );
+    let replies = server.handle(&message(
+        r#"{"jsonrpc":"2.0","id":6,"method":"textDocument/definition","params":{"textDocument":{"uri":"file:///a.rc"},"position":{"line":3,"character":12}}}"#,
+    ));
+    assert_eq!(
+        r#"[{"jsonrpc":"2.0","id":6,"result":null}]"#,
+        Json::Array(replies).to_string()
+    );
+
+    let replies = server.handle(&message(r#"{"jsonrpc":"2.0","id":4,"method":"foo"}"#));
+    assert_eq!(
+        r#"[{"jsonrpc":"2.0","id":4,"error":{"code":-32601,"message":"unsupported method `foo`"}}]"#,
+        Json::Array(replies).to_string()
+    );
+}
+
+#[test]
+fn lsp_run_test() {
+    let mut server = LanguageServer::new();
+    let shutdown = r#"{"jsonrpc":"2.0","id":1,"method":"shutdown"}"#;
+    let exit = r#"{"jsonrpc":"2.0","method":"exit"}"#;
+    let input = format!(
+        "Content-Length: {}\r\n\r\n{}Content-Length: {}\r\n\r\n{}",
+        shutdown.len(),
+        shutdown,
+        exit.len(),
+        exit
+    );
+    let mut output = Vec::<u8>::new();
+    server.run(&mut input.as_bytes(), &mut output).unwrap();
+    let expected = r#"{"jsonrpc":"2.0","id":1,"result":null}"#;
+    assert_eq!(
+        format!("Content-Length: {}\r\n\r\n{}", expected.len(), expected),
+        String::from_utf8(output).unwrap()
+    );
+}
+
+#[test]
+fn lsp_malformed_message_test() {
+    let mut server = LanguageServer::new();
+    let shutdown = r#"{"jsonrpc":"2.0","id":1,"method":"shutdown"}"#;
+    // the server logs the invalid JSON and goes on
+    let input = format!(
+        "Content-Length: 5\r\n\r\n{{abc}}Content-Length: {}\r\n\r\n{}",
+        shutdown.len(),
+        shutdown
+    );
+    let mut output = Vec::<u8>::new();
+    server.run(&mut input.as_bytes(), &mut output).unwrap();
+    let output = String::from_utf8(output).unwrap();
+    assert!(output.contains(r#""method":"window/logMessage","params":{"type":1,"#));
+    assert!(output.ends_with(r#"{"jsonrpc":"2.0","id":1,"result":null}"#));
+}
@@ -1,15 +1,26 @@
 #![feature(map_first_last)]
 
-use crate::rcc::{OptimizeLevel, RcCompiler, RccError};
+use crate::analyser::cfg::CfgOptions;
+use crate::ir::mangle::demangle_text;
+use crate::rcc::{EmitKind, OptimizeLevel, RcCompiler, RccError, TimePassesFormat};
 use clap::Clap;
 use code_gen::TargetPlatform;
+use lsp::LanguageServer;
+use diagnostic::{stderr_supports_color, Diagnostic, Renderer, SourceMap};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
 
 mod analyser;
 mod ast;
+mod cache;
 mod code_gen;
+mod diagnostic;
+mod intern;
 mod ir;
+mod json;
 mod lexer;
+mod lsp;
 mod parser;
 mod rcc;
 mod tests;
@@ -19,34 +30,256 @@ struct Opts {
     /// output asm file
     #[clap(short = 'S')]
     output_asm: bool,
-    /// input file
-    input: String,
-    /// output file
+    /// input files, compiled together as one module
+    #[clap(required_unless_present_any = &["lsp", "demangle"])]
+    input: Vec<String>,
+    /// output file, `-` for stdout. Defaults to the name of the first input file with
+    /// the extension of the emit kind
     #[clap(short = 'o')]
-    output: String,
-    /// target platform
-    #[clap(short = 't', default_value = "riscv32")]
+    output: Option<String>,
+    /// target platform: riscv32 or riscv64
+    #[clap(short = 't', long = "target", default_value = "riscv32")]
     target: String,
+    /// enable `#[cfg(name)]` or `#[cfg(name = "value")]` items by `name` or `name="value"`
+    #[clap(long = "cfg", multiple_occurrences = true, number_of_values = 1)]
+    cfg: Vec<String>,
+    /// optimize level: 0, 1 or 2
+    #[clap(short = 'O', default_value = "0")]
+    opt_level: String,
+    /// dump the textual IR of the basic blocks to stderr after the pass
+    #[clap(long = "print-ir-after")]
+    print_ir_after: Option<String>,
+    /// skip the passes after the pass, used with `--emit cfg-dot`
+    #[clap(long = "stop-after")]
+    stop_after: Option<String>,
+    /// emit `_start` which calls `main` and exits with its return value
+    #[clap(long = "start")]
+    start: bool,
+    /// insert runtime checks which panic on failure, e.g. division by zero
+    #[clap(long = "checks")]
+    checks: bool,
+    /// panic when `+`, `-` or `*` of integers overflows instead of wrapping around
+    #[clap(long = "overflow-checks")]
+    overflow_checks: bool,
+    /// abort with "stack overflow" when sp goes below `__rcc_stack_limit`
+    #[clap(long = "stack-check")]
+    stack_check: bool,
+    /// size of the stack set up by `_start` in bytes
+    #[clap(long = "stack-size")]
+    stack_size: Option<String>,
+    /// emit `.file` and `.loc` directives which map the code to the lines of the input files
+    #[clap(short = 'g')]
+    debug_info: bool,
+    /// the target has no M extension, multiplication and division call runtime routines
+    #[clap(long = "no-m-extension")]
+    no_m_extension: bool,
+    /// emit position-independent code, which computes addresses from the pc
+    #[clap(long = "pic")]
+    pic: bool,
+    /// only report errors of the source code, no output file is written
+    #[clap(long = "check")]
+    check: bool,
+    /// print the time spent in each stage of the compiler to stderr
+    #[clap(long = "time-passes")]
+    time_passes: bool,
+    /// format of `--time-passes`: text or json
+    #[clap(long = "time-passes-format", default_value = "text")]
+    time_passes_format: String,
+    /// number of threads which generate code
+    #[clap(short = 'j', default_value = "1")]
+    jobs: String,
+    /// directory of the incremental compilation cache
+    #[clap(long = "cache-dir")]
+    cache_dir: Option<String>,
+    /// serve the Language Server Protocol over stdio
+    #[clap(long = "lsp")]
+    lsp: bool,
+    /// kind of output: tokens, ast, ast-json, fmt, ir, ir-json, cfg-dot, call-graph, asm, obj
+    /// or c-header
+    #[clap(long = "emit", default_value = "asm")]
+    emit: String,
+    /// format the input files, written to stdout unless `-o` is given
+    #[clap(long = "fmt")]
+    fmt: bool,
+    /// copy the input files, or stdin if there are none, to the output with the mangled
+    /// symbols demangled, e.g. `_RCG3add3i32E` -> `add::<i32>`
+    #[clap(long = "demangle")]
+    demangle: bool,
 }
 
-fn compile(opts: Opts) -> Result<(), RccError> {
+fn demangle(opts: Opts) -> Result<(), RccError> {
+    let mut text = String::new();
+    if opts.input.is_empty() {
+        std::io::stdin().read_to_string(&mut text)?;
+    }
+    for path in opts.input.iter() {
+        match std::fs::read_to_string(path) {
+            Ok(s) => text.push_str(&s),
+            Err(e) => return Err(format!("{}: {}", path, e).into()),
+        }
+    }
+    let mut output: Box<dyn Write> = match opts.output.as_deref() {
+        None | Some("-") => Box::new(std::io::stdout()),
+        Some(path) => Box::new(std::fs::File::create(path)?),
+    };
+    output.write_all(demangle_text(&text).as_bytes())?;
+    Ok(())
+}
+
+fn compile(opts: Opts, source_map: &mut SourceMap) -> Result<(), RccError> {
+    let opt_level = match OptimizeLevel::from_str(&opts.opt_level) {
+        Ok(opt_level) => opt_level,
+        Err(_) => return Err(format!("invalid optimize level {}", opts.opt_level).into()),
+    };
+    let emit = match EmitKind::from_str(&opts.emit) {
+        _ if opts.fmt => EmitKind::Fmt,
+        Ok(emit) => emit,
+        Err(_) => return Err(format!("invalid emit kind {}", opts.emit).into()),
+    };
+    if opts.stop_after.is_some() && emit != EmitKind::CfgDot {
+        return Err("`--stop-after` is only supported with `--emit cfg-dot`".into());
+    }
+    let stack_size = match opts.stack_size.as_deref().map(u32::from_str) {
+        None => None,
+        Some(Ok(size)) => Some(size),
+        Some(Err(_)) => {
+            return Err(format!("invalid stack size {}", opts.stack_size.unwrap()).into())
+        }
+    };
+    let jobs = match usize::from_str(&opts.jobs) {
+        Ok(jobs) if jobs > 0 => jobs,
+        _ => return Err(format!("invalid number of jobs {}", opts.jobs).into()),
+    };
+    let mut cfg_options = CfgOptions::new();
+    for flag in opts.cfg.iter() {
+        cfg_options.insert_flag(flag)?;
+    }
+    let time_passes_format = match TimePassesFormat::from_str(&opts.time_passes_format) {
+        Ok(format) => format,
+        Err(_) => {
+            return Err(format!("invalid time passes format {}", opts.time_passes_format).into())
+        }
+    };
     match TargetPlatform::from_str(&opts.target) {
         Ok(target_platform) => {
-            let input = std::fs::File::open(opts.input)?;
-            let output = std::fs::File::create(opts.output)?;
-            // TODO: set opt level
+            for path in opts.input.iter() {
+                match std::fs::read_to_string(path) {
+                    Ok(s) => source_map.add_file(path, &s),
+                    Err(e) => return Err(format!("{}: {}", path, e).into()),
+                }
+            }
+            let output_path = match opts.output {
+                Some(output) => output,
+                None if emit == EmitKind::Fmt => "-".to_string(),
+                None => default_output(&opts.input[0], emit),
+            };
+            let output: Box<dyn Write> = if opts.check {
+                Box::new(std::io::sink())
+            } else if output_path == "-" {
+                Box::new(std::io::stdout())
+            } else {
+                Box::new(std::fs::File::create(output_path)?)
+            };
             let mut rc_compiler =
-                RcCompiler::new(target_platform, input, output, OptimizeLevel::Zero);
+                RcCompiler::new(target_platform, source_map.text().as_bytes(), output, opt_level);
+            rc_compiler.set_print_ir_after(opts.print_ir_after);
+            rc_compiler.set_stop_after(opts.stop_after);
+            rc_compiler.set_emit_start(opts.start);
+            rc_compiler.set_checks(opts.checks);
+            rc_compiler.set_overflow_checks(opts.overflow_checks);
+            rc_compiler.set_stack_check(opts.stack_check);
+            rc_compiler.set_stack_size(stack_size);
+            if opts.debug_info {
+                rc_compiler.set_debug_files(source_map.file_lines());
+            }
+            rc_compiler.set_m_extension(!opts.no_m_extension);
+            rc_compiler.set_pic(opts.pic);
+            rc_compiler.set_emit(emit);
+            rc_compiler.set_check(opts.check);
+            rc_compiler.set_jobs(jobs);
+            rc_compiler.set_cfg_options(cfg_options);
+            rc_compiler.set_cache_dir(opts.cache_dir.map(PathBuf::from));
+            if opts.time_passes {
+                rc_compiler.set_time_passes(Some(time_passes_format));
+            }
             rc_compiler.compile()?;
             Ok(())
         }
-        Err(_) => Err(format!("invalid target platform {}", opts.input).into()),
+        Err(_) => Err(format!("invalid target platform {}", opts.target).into()),
     }
 }
 
+/// `foo.rc` -> `foo.s`
+fn default_output(input: &str, emit: EmitKind) -> String {
+    let extension = match emit {
+        EmitKind::Tokens => "tokens",
+        EmitKind::Ast => "ast",
+        EmitKind::AstJson => "ast.json",
+        EmitKind::Fmt => "rs",
+        EmitKind::IR => "ir",
+        EmitKind::IRJson => "ir.json",
+        EmitKind::CfgDot => "dot",
+        EmitKind::CallGraph => "calls",
+        EmitKind::Asm => "s",
+        EmitKind::Obj => "o",
+        EmitKind::CHeader => "h",
+    };
+    Path::new(input)
+        .with_extension(extension)
+        .file_name()
+        .unwrap()
+        .to_string_lossy()
+        .to_string()
+}
+
 fn main() {
     let opts = Opts::parse();
-    if let Err(e) = compile(opts) {
-        eprintln!("{:?}", e);
+    if opts.lsp {
+        let stdin = std::io::stdin();
+        if let Err(e) = LanguageServer::new().run(&mut stdin.lock(), &mut std::io::stdout()) {
+            eprintln!("error: {}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
+    if opts.demangle {
+        if let Err(e) = demangle(opts) {
+            eprintln!("error: {}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
+    let mut source_map = SourceMap::new();
+    if let Err(e) = compile(opts, &mut source_map) {
+        let renderer = Renderer::new(&source_map, stderr_supports_color());
+        match e {
+            RccError::Lex(errors) => {
+                for error in errors {
+                    let diagnostic = Diagnostic::error(error.message).primary(error.span, "");
+                    eprint!("{}", renderer.render(&diagnostic));
+                }
+            }
+            RccError::Compile(error) => {
+                let mut diagnostic = Diagnostic::error(error.message);
+                if let Some(code) = error.code {
+                    diagnostic = diagnostic.code(code);
+                }
+                if let Some(span) = error.span {
+                    diagnostic = diagnostic.primary(span, "");
+                }
+                for (span, label) in error.labels.iter() {
+                    diagnostic = diagnostic.secondary(*span, label);
+                }
+                for note in error.notes.iter() {
+                    diagnostic = diagnostic.note(note);
+                }
+                for help in error.helps.iter() {
+                    diagnostic = diagnostic.help(help);
+                }
+                eprint!("{}", renderer.render(&diagnostic));
+            }
+            e => eprint!("{}", renderer.render(&Diagnostic::error(e.to_string()))),
+        }
+        std::process::exit(1);
     }
 }
@@ -17,11 +17,13 @@ pub mod prec {
     use crate::ast::expr::Expr::{ArrayIndex, Assign, Call, FieldAccess, Range, Unary};
     use crate::ast::expr::UnOp::{Borrow, BorrowMut};
     use crate::ast::expr::{
-        ArrayIndexExpr, AssignExpr, BinOpExpr, BinOperator, CallExpr, CallParams, Expr,
+        ArrayIndexExpr, AssignExpr, BinOpExpr, BinOperator, CallExpr, CallParams, CastExpr, Expr,
         FieldAccessExpr, LhsExpr, Precedence, RangeExpr, UnAryExpr, UnOp,
     };
-    use crate::ast::FromToken;
+    use crate::ast::types::TypeAnnotation;
+    use crate::ast::{FromToken, NodeId};
     use crate::ast::TokenStart;
+    use crate::diagnostic::Span;
     use crate::lexer::token::Token;
     use crate::parser::expr::primitive::primitive_expr;
     use crate::parser::{Parse, ParseCursor};
@@ -35,11 +37,21 @@ pub mod prec {
     ///             | RangeExpr(lhs expr) AssignOp AssignExpr
     /// (Associativity: right to left)
     fn assign_expr(cursor: &mut ParseCursor) -> Result<Expr, RccError> {
+        let lo = cursor.span().map(|span| span.lo);
         let mut expr = range_expr(cursor)?;
+        let span = lo.map(|lo| cursor.span_from(lo));
         while let Some(assign_op) = cursor.eat_token_if_from() {
-            let lhs = LhsExpr::from_expr(expr)?;
+            let lhs = LhsExpr::from_expr(expr).map_err(|e| match (e, span) {
+                (RccError::Compile(e), Some(span)) => e.span(span).into(),
+                (e, _) => e,
+            })?;
             let rhs = assign_expr(cursor)?;
-            expr = Assign(AssignExpr::new(lhs, assign_op, rhs));
+            let mut assign_expr = AssignExpr::new(lhs, assign_op, rhs);
+            assign_expr.node_id = cursor.next_node_id();
+            if let Some(span) = span {
+                cursor.spans.insert(assign_expr.node_id, span);
+            }
+            expr = Assign(assign_expr);
         }
         Ok(expr)
     }
@@ -48,6 +60,7 @@ pub mod prec {
     ///            | BinOpExpr? RangeOp BinOpExpr?
     /// (Associativity: require parentheses)
     pub(super) fn range_expr(cursor: &mut ParseCursor) -> Result<Expr, RccError> {
+        let lo = cursor.span().map(|span| span.lo);
         let mut lhs_err = "".into();
         let lhs = if RangeExpr::is_token_start(cursor.next_token()?) {
             None
@@ -63,12 +76,16 @@ pub mod prec {
 
         let expr = if let Some(op) = cursor.eat_token_if_from() {
             let mut range_expr = RangeExpr::new(op);
+            range_expr.node_id = cursor.next_node_id();
             if let Some(expr) = lhs {
                 range_expr.set_lhs(expr);
             }
             if let Ok(rhs) = bin_op_expr(cursor) {
                 range_expr.set_rhs(rhs);
             }
+            if let Some(lo) = lo {
+                cursor.spans.insert(range_expr.node_id, cursor.span_from(lo));
+            }
             Range(range_expr)
         } else {
             match lhs {
@@ -100,20 +117,34 @@ pub mod prec {
     fn bin_op_expr(cursor: &mut ParseCursor) -> Result<Expr, RccError> {
         // 1|2|3&4+4+4+5*6*7+7&8
         fn reduce(
+            cursor: &mut ParseCursor,
             bin_ops: &mut Vec<BinOperator>,
-            exprs: &mut Vec<Expr>,
+            exprs: &mut Vec<(Expr, Option<Span>)>,
             next_prec: Precedence,
         ) -> Result<(), RccError> {
             while !bin_ops.is_empty() && bin_ops.last().unwrap().prec_gt(&next_prec)? {
-                let rhs = exprs.pop().unwrap();
-                let lhs = exprs.pop().unwrap();
+                let (rhs, r_span) = exprs.pop().unwrap();
+                let (lhs, l_span) = exprs.pop().unwrap();
                 let last_op = bin_ops.pop().unwrap();
-                exprs.push(Expr::BinOp(BinOpExpr::new(lhs, last_op, rhs)));
+                let mut bin_op_expr = BinOpExpr::new(lhs, last_op, rhs);
+                bin_op_expr.node_id = cursor.next_node_id();
+                let span = l_span.zip(r_span).map(|(l, r)| Span::new(l.lo, r.hi));
+                if let Some(span) = span {
+                    cursor.spans.insert(bin_op_expr.node_id, span);
+                }
+                exprs.push((Expr::BinOp(bin_op_expr), span));
             }
             Ok(())
         };
 
-        let mut exprs = vec![unary_expr(cursor)?];
+        // every operand is kept with its span, the span of `a + b` is from `a` to `b`
+        fn operand(cursor: &mut ParseCursor) -> Result<(Expr, Option<Span>), RccError> {
+            let lo = cursor.span().map(|span| span.lo);
+            let expr = cast_expr(cursor)?;
+            Ok((expr, lo.map(|lo| cursor.span_from(lo))))
+        }
+
+        let mut exprs = vec![operand(cursor)?];
         let mut bin_ops: Vec<BinOperator> = vec![];
         let mut next_is_op = true;
 
@@ -125,6 +156,7 @@ pub mod prec {
                             // 1 + 2 * 3   <- -
                             if !last_op.prec_lt(&next_op)? {
                                 reduce(
+                                    cursor,
                                     &mut bin_ops,
                                     &mut exprs,
                                     Precedence::from_bin_op(&next_op),
@@ -135,20 +167,34 @@ pub mod prec {
                         bin_ops.push(next_op);
                     }
                     None => {
-                        reduce(&mut bin_ops, &mut exprs, Precedence::Min)?;
+                        reduce(cursor, &mut bin_ops, &mut exprs, Precedence::Min)?;
                         debug_assert!(bin_ops.is_empty());
                         debug_assert_eq!(exprs.len(), 1);
-                        return Ok(exprs.pop().unwrap());
+                        return Ok(exprs.pop().unwrap().0);
                     }
                 }
                 next_is_op = false;
             } else {
-                exprs.push(unary_expr(cursor)?);
+                exprs.push(operand(cursor)?);
                 next_is_op = true;
             }
         }
     }
 
+    /// CastExpr -> UnAryExpr
+    ///           | CastExpr `as` Type
+    fn cast_expr(cursor: &mut ParseCursor) -> Result<Expr, RccError> {
+        let mut expr = unary_expr(cursor)?;
+        // `as` is not expected after every expression, like the binary operators
+        while let Ok(Token::As) = cursor.next_token() {
+            cursor.bump_token()?;
+            let mut cast_expr = CastExpr::new(expr, TypeAnnotation::parse(cursor)?);
+            cast_expr.node_id = cursor.next_node_id();
+            expr = Expr::Cast(cast_expr);
+        }
+        Ok(expr)
+    }
+
     /// UnAryExpr -> CallExpr
     ///            | ( `!` | `*` | `-` | `&` | `& mut` ) UnAryExpr
     fn unary_expr(cursor: &mut ParseCursor) -> Result<Expr, RccError> {
@@ -166,14 +212,19 @@ pub mod prec {
                     } else {
                         Borrow
                     };
-                    let expr = Unary(UnAryExpr::new(op, unary_expr(cursor)?));
-                    Unary(UnAryExpr::new(Borrow, expr))
+                    let mut expr = UnAryExpr::new(op, unary_expr(cursor)?);
+                    expr.node_id = cursor.next_node_id();
+                    let mut borrow_expr = UnAryExpr::new(Borrow, Unary(expr));
+                    borrow_expr.node_id = cursor.next_node_id();
+                    Unary(borrow_expr)
                 } else {
                     let mut op = UnOp::from_token(tk.clone()).unwrap();
                     if op == UnOp::Borrow && cursor.eat_token_if_eq(Token::Mut) {
                         op = UnOp::BorrowMut;
                     }
-                    Unary(UnAryExpr::new(op, unary_expr(cursor)?))
+                    let mut expr = UnAryExpr::new(op, unary_expr(cursor)?);
+                    expr.node_id = cursor.next_node_id();
+                    Unary(expr)
                 }
             } else {
                 call_expr(cursor)?
@@ -192,22 +243,29 @@ pub mod prec {
                 Token::LeftParen => {
                     cursor.bump_token()?;
                     let mut call_expr = CallExpr::new(expr);
+                    call_expr.node_id = cursor.next_node_id();
 
                     if !cursor.eat_token_if_eq(Token::RightParen) {
-                        let call_params = CallParams::parse(cursor)?;
+                        let call = call_expr.node_id;
+                        let call_params =
+                            cursor.with_struct_expr(true, |cursor| call_params(cursor, call))?;
                         cursor.eat_token_eq(Token::RightParen)?;
                         call_expr = call_expr.call_params(call_params);
                     }
                     Call(call_expr)
                 }
                 Token::LeftSquareBrackets => {
-                    let index_expr = ArrayIndexExpr::parse_index(cursor)?;
-                    ArrayIndex(ArrayIndexExpr::new(expr, index_expr))
+                    let index_expr = cursor.with_struct_expr(true, ArrayIndexExpr::parse_index)?;
+                    let mut array_index_expr = ArrayIndexExpr::new(expr, index_expr);
+                    array_index_expr.node_id = cursor.next_node_id();
+                    ArrayIndex(array_index_expr)
                 }
                 Token::Dot => {
                     cursor.bump_token()?;
                     let rhs = primitive_expr(cursor)?;
-                    FieldAccess(FieldAccessExpr::new(expr, rhs))
+                    let mut field_access_expr = FieldAccessExpr::new(expr, rhs);
+                    field_access_expr.node_id = cursor.next_node_id();
+                    FieldAccess(field_access_expr)
                 }
                 _ => return Ok(expr),
             }
@@ -216,16 +274,22 @@ pub mod prec {
     }
 
     /// CallParams -> Expr ( , Expr )* ,?
-    impl Parse for CallParams {
-        fn parse(cursor: &mut ParseCursor) -> Result<Self, RccError> {
-            let mut call_params = vec![];
-            let expr = Expr::parse(cursor)?;
-            call_params.push(expr);
-            while cursor.eat_token_if_eq(Token::Comma) {
-                call_params.push(Expr::parse(cursor)?);
+    /// The spans of the arguments of `call` are kept for the diagnostics of their types.
+    fn call_params(cursor: &mut ParseCursor, call: NodeId) -> Result<CallParams, RccError> {
+        let mut call_params = vec![];
+        let mut spans = vec![];
+        loop {
+            let lo = cursor.span().map(|span| span.lo);
+            call_params.push(Expr::parse(cursor)?);
+            spans.extend(lo.map(|lo| cursor.span_from(lo)));
+            if !cursor.eat_token_if_eq(Token::Comma) {
+                break;
             }
-            Ok(call_params)
         }
+        if spans.len() == call_params.len() {
+            cursor.arg_spans.insert(call, spans);
+        }
+        Ok(call_params)
     }
 
     /// ArrayIndexExpr -> `[` Expr `]`
@@ -243,38 +307,64 @@ pub mod prec {
 pub mod primitive {
     use std::str::FromStr;
 
-    use crate::ast::expr::Expr::{Array, Block, If, LitBool, LitNum, Loop, Path, While};
+    use crate::ast::expr::Expr::{Array, Block, For, If, LitBool, Loop, Path, While};
     use crate::ast::expr::*;
+    use crate::ast::pattern::Pattern;
     use crate::ast::stmt::Stmt;
     use crate::ast::types::TypeLitNum;
     use crate::ast::TokenStart;
     use crate::lexer::token::LiteralKind::*;
     use crate::lexer::token::Token;
+    use crate::lexer::{byte_string_value, string_value, unescape};
     use crate::parser::expr::prec::range_expr;
     use crate::parser::stmt::{parse_stmt_or_expr_without_block, StmtOrExpr};
     use crate::parser::{Parse, ParseCursor};
     use crate::rcc::RccError;
 
-    /// PrimitiveExpr -> PathExpr | LitExpr | LitChar | LitStr | LitBool | BlockExpr
-    ///                | GroupedExpr | TupleExpr | ArrayExpr
-    ///                | ReturnExpr | BreakExpr
+    /// PrimitiveExpr -> PathExpr | LitExpr | LitChar | LitStr | LitByteStr | LitBool | BlockExpr
+    ///                | GroupedExpr | TupleExpr | ArrayExpr | StructExpr
+    ///                | ReturnExpr | BreakExpr | PrintExpr | AssertExpr | AsmExpr
     ///                | RangeExpr(without lhs)
     pub fn primitive_expr(cursor: &mut ParseCursor) -> Result<Expr, RccError> {
         let expr = match cursor.next_token()? {
-            Token::Identifier(_) | Token::PathSep => Path(PathExpr::parse(cursor)?),
+            Token::Identifier(_) | Token::PathSep | Token::SelfValue | Token::SelfType => {
+                let line = cursor.line();
+                let path_expr = PathExpr::parse(cursor)?;
+                match cursor.next_token() {
+                    Ok(Token::Not) if path_expr.segments == ["asm"] => {
+                        Expr::Asm(AsmExpr::parse_after_name(cursor)?)
+                    }
+                    Ok(Token::Not) if AssertExpr::is_assert(&path_expr) => {
+                        Expr::Assert(AssertExpr::parse_from_name(cursor, path_expr, line)?)
+                    }
+                    Ok(Token::Not) => Expr::Print(PrintExpr::parse_from_name(cursor, path_expr)?),
+                    Ok(Token::LeftCurlyBraces) if cursor.struct_expr_allowed() => {
+                        Expr::Struct(StructExpr::parse_from_path(cursor, path_expr)?)
+                    }
+                    _ => Path(path_expr),
+                }
+            }
             Token::Literal { .. } => parse_literal(cursor)?,
             Token::LitString(_) => Expr::LitStr(parse_lit_string(cursor)?),
+            Token::LitByteString(s) => {
+                let bytes = byte_string_value(s).ok_or("invalid escape")?;
+                cursor.bump_token()?;
+                Expr::LitByteStr(bytes)
+            }
             Token::True | Token::False => LitBool(*cursor.bump_token()? == Token::True),
-            Token::LeftCurlyBraces => Block(BlockExpr::parse(cursor)?),
-            Token::LeftParen => parse_grouped_or_tuple_expr(cursor)?,
-            Token::LeftSquareBrackets => Array(ArrayExpr::parse(cursor)?),
+            Token::LeftCurlyBraces | Token::Unsafe => {
+                Block(cursor.with_struct_expr(true, BlockExpr::parse)?)
+            }
+            Token::LeftParen => cursor.with_struct_expr(true, parse_grouped_or_tuple_expr)?,
+            Token::LeftSquareBrackets => Array(cursor.with_struct_expr(true, ArrayExpr::parse)?),
             Token::While => While(WhileExpr::parse(cursor)?),
             Token::Loop => Loop(LoopExpr::parse(cursor)?),
+            Token::For => For(ForExpr::parse(cursor)?),
             Token::If => If(IfExpr::parse(cursor)?),
             Token::Return => Expr::Return(ReturnExpr::parse(cursor)?),
             Token::Break => Expr::Break(BreakExpr::parse(cursor)?),
             Token::DotDot | Token::DotDotEq => range_expr(cursor)?,
-            _ => unreachable!(),
+            tk => return Err(format!("expected expression, found `{}`", tk).into()),
         };
         Ok(expr)
     }
@@ -297,9 +387,10 @@ pub mod primitive {
     impl TupleExpr {
         fn parse_from_second(cursor: &mut ParseCursor, first_expr: Expr) -> Result<Self, RccError> {
             cursor.eat_token_eq(Token::Comma)?;
-            let mut tuple_expr = TupleExpr(vec![first_expr]);
+            let mut tuple_expr = TupleExpr::new(vec![first_expr]);
+            tuple_expr.node_id = cursor.next_node_id();
             while cursor.next_token()? != &Token::RightParen {
-                tuple_expr.0.push(Expr::parse(cursor)?);
+                tuple_expr.elems.push(Expr::parse(cursor)?);
                 if !cursor.eat_token_if_eq(Token::Comma) {
                     break;
                 }
@@ -325,6 +416,8 @@ pub mod primitive {
             }
 
             let mut path_expr = Self::new();
+            path_expr.node_id = cursor.next_node_id();
+            let lo = cursor.span().map(|span| span.lo);
             let mut state = State::Init;
             while let Ok(tk) = cursor.next_token() {
                 match tk {
@@ -341,11 +434,26 @@ pub mod primitive {
                         state = State::Segment;
                         path_expr.segments.push(s.to_string());
                     }
+                    // `Self::N` in an impl
+                    Token::SelfType if state == State::Init => {
+                        state = State::Segment;
+                        path_expr.segments.push("Self".to_string());
+                    }
+                    // `self` is a path of one segment
+                    Token::SelfValue if state == State::Init => {
+                        state = State::Segment;
+                        path_expr.segments.push("self".to_string());
+                        cursor.bump_token()?;
+                        break;
+                    }
                     _ => break,
                 }
                 cursor.bump_token()?;
             }
             if state == State::Segment {
+                if let Some(lo) = lo {
+                    cursor.spans.insert(path_expr.node_id, cursor.span_from(lo));
+                }
                 Ok(path_expr)
             } else {
                 Err("invalid path".into())
@@ -353,34 +461,317 @@ pub mod primitive {
         }
     }
 
+    /// StructExpr -> PathExpr `{` ( StructExprField `,` )* ( StructExprField | `..` Expr )? `}`
+    /// StructExprField -> identifier ( `:` Expr )?
+    impl StructExpr {
+        fn parse_from_path(cursor: &mut ParseCursor, path: PathExpr) -> Result<Self, RccError> {
+            cursor.eat_token_eq(Token::LeftCurlyBraces)?;
+            let mut struct_expr = StructExpr::new(path.segments.join("::"));
+            struct_expr.node_id = cursor.next_node_id();
+            cursor.with_struct_expr(true, |cursor| {
+                let mut struct_expr = struct_expr;
+                while !cursor.eat_token_if_eq(Token::RightCurlyBraces) {
+                    if cursor.eat_token_if_eq(Token::DotDot) {
+                        struct_expr = struct_expr.base(Expr::parse(cursor)?);
+                        cursor.eat_token_eq(Token::RightCurlyBraces)?;
+                        break;
+                    }
+                    let name = cursor.eat_identifier()?;
+                    let expr = if cursor.eat_token_if_eq(Token::Colon) {
+                        Expr::parse(cursor)?
+                    } else {
+                        // `S { x }` is `S { x: x }`
+                        let mut path_expr = PathExpr::from(name);
+                        path_expr.node_id = cursor.next_node_id();
+                        Path(path_expr)
+                    };
+                    struct_expr = struct_expr.field(name, expr);
+                    if !cursor.eat_token_if_eq(Token::Comma) {
+                        cursor.eat_token_eq(Token::RightCurlyBraces)?;
+                        break;
+                    }
+                }
+                Ok(struct_expr)
+            })
+        }
+    }
+
     pub fn parse_lit_string(cursor: &mut ParseCursor) -> Result<String, RccError> {
         if let Token::LitString(s) = cursor.bump_token()? {
-            let s = *s;
-            Ok(s[1..s.len() - 1].to_string())
+            string_value(s).ok_or_else(|| "invalid escape".into())
         } else {
             Err("expected LitString".into())
         }
     }
 
-    fn parse_literal(cursor: &mut ParseCursor) -> Result<Expr, RccError> {
-        let (literal_kind, value) = cursor.eat_literal()?;
-        Ok(match literal_kind {
-            Char => Expr::LitChar(value.chars().nth(1).unwrap()),
-            Integer { suffix } => {
-                Expr::LitNum(LitNumExpr::integer(value).lit_type(if suffix.is_empty() {
-                    TypeLitNum::I
+    /// PrintExpr -> (print | println) `!` `(` ( LitStr ( `,` Expr )* `,`? )? `)`
+    ///
+    /// `println!()` is the only one which can omit the format string.
+    impl PrintExpr {
+        fn parse_from_name(cursor: &mut ParseCursor, name: PathExpr) -> Result<Self, RccError> {
+            cursor.eat_token_eq(Token::Not)?;
+            let newline = match name.segments.as_slice() {
+                [s] if s == "print" => false,
+                [s] if s == "println" => true,
+                _ => {
+                    return Err(
+                        format!("cannot find macro `{}`", name.segments.join("::")).into(),
+                    )
+                }
+            };
+            cursor.eat_token_eq(Token::LeftParen)?;
+            let mut pieces = vec![String::new()];
+            let mut args = vec![];
+            if cursor.eat_token_if_eq(Token::RightParen) {
+                if !newline {
+                    return Err("requires at least a format string argument".into());
+                }
+                return Ok(PrintExpr::new(newline, pieces, args));
+            }
+
+            if !matches!(cursor.next_token()?, Token::LitString(_)) {
+                return Err("format argument must be a string literal".into());
+            }
+            pieces = parse_format_string(&parse_lit_string(cursor)?)?;
+            while cursor.eat_token_if_eq(Token::Comma) {
+                if cursor.next_token()? == &Token::RightParen {
+                    break;
+                }
+                args.push(Expr::parse(cursor)?);
+            }
+            cursor.eat_token_eq(Token::RightParen)?;
+
+            if pieces.len() != args.len() + 1 {
+                return Err(format!(
+                    "{} positional arguments in format string, but there are {} arguments",
+                    pieces.len() - 1,
+                    args.len()
+                )
+                .into());
+            }
+            Ok(PrintExpr::new(newline, pieces, args))
+        }
+    }
+
+    /// AssertExpr -> assert `!` `(` Expr ( `,` LitStr )? `,`? `)`
+    ///             | ( assert_eq | assert_ne ) `!` `(` Expr `,` Expr ( `,` LitStr )? `,`? `)`
+    ///
+    /// `assert_eq!(a, b)` is parsed as `assert!(a == b)` of kind `Eq`. The message has no
+    /// arguments.
+    impl AssertExpr {
+        fn is_assert(name: &PathExpr) -> bool {
+            matches!(name.segments.as_slice(), [s] if s.starts_with("assert"))
+        }
+
+        fn parse_from_name(
+            cursor: &mut ParseCursor,
+            name: PathExpr,
+            line: u32,
+        ) -> Result<Self, RccError> {
+            cursor.eat_token_eq(Token::Not)?;
+            let kind = match name.segments[0].as_str() {
+                "assert" => AssertKind::Assert,
+                "assert_eq" => AssertKind::Eq,
+                "assert_ne" => AssertKind::Ne,
+                s => return Err(format!("cannot find macro `{}`", s).into()),
+            };
+            cursor.eat_token_eq(Token::LeftParen)?;
+            let mut cond = Expr::parse(cursor)?;
+            if kind != AssertKind::Assert {
+                cursor.eat_token_eq(Token::Comma)?;
+                let rhs = Expr::parse(cursor)?;
+                let op = if kind == AssertKind::Eq {
+                    BinOperator::EqEq
                 } else {
-                    TypeLitNum::from_str(suffix).unwrap()
-                }))
+                    BinOperator::Ne
+                };
+                let mut bin_op_expr = BinOpExpr::new(cond, op, rhs);
+                bin_op_expr.node_id = cursor.next_node_id();
+                cond = Expr::BinOp(bin_op_expr);
+            }
+            let mut msg = None;
+            if cursor.eat_token_if_eq(Token::Comma) && cursor.next_token()? != &Token::RightParen {
+                if !matches!(cursor.next_token()?, Token::LitString(_)) {
+                    return Err("assertion message must be a string literal".into());
+                }
+                let mut pieces = parse_format_string(&parse_lit_string(cursor)?)?;
+                if pieces.len() > 1 {
+                    return Err("arguments of assertion messages are not supported".into());
+                }
+                msg = pieces.pop();
+                cursor.eat_token_if_eq(Token::Comma);
             }
-            Float { suffix } => {
-                Expr::LitNum(LitNumExpr::integer(value).lit_type(if suffix.is_empty() {
-                    TypeLitNum::F
+            cursor.eat_token_eq(Token::RightParen)?;
+            Ok(AssertExpr::new(kind, cond, msg, line))
+        }
+    }
+
+    /// AsmExpr -> asm `!` `(` LitStr ( `,` LitStr )* ( `,` AsmOperand )* `,`? `)`
+    /// AsmOperand -> ( in | out ) `(` reg `)` Expr
+    ///
+    /// The strings are lines of the template. An output is a place expr.
+    impl AsmExpr {
+        fn parse_after_name(cursor: &mut ParseCursor) -> Result<Self, RccError> {
+            cursor.eat_token_eq(Token::Not)?;
+            cursor.eat_token_eq(Token::LeftParen)?;
+            if !matches!(cursor.next_token()?, Token::LitString(_)) {
+                return Err("asm template must be a string literal".into());
+            }
+            let mut lines = vec![parse_lit_string(cursor)?];
+            // (is input, expr) in the order of the source
+            let mut operands = vec![];
+            while cursor.eat_token_if_eq(Token::Comma) {
+                let is_input = match cursor.next_token()? {
+                    Token::RightParen => break,
+                    Token::LitString(_) if operands.is_empty() => {
+                        lines.push(parse_lit_string(cursor)?);
+                        continue;
+                    }
+                    Token::In => true,
+                    Token::Identifier("out") => false,
+                    t => return Err(format!("expected asm operand, found `{}`", t).into()),
+                };
+                cursor.bump_token()?;
+                cursor.eat_token_eq(Token::LeftParen)?;
+                match cursor.bump_token()? {
+                    Token::Identifier("reg") => {}
+                    t => {
+                        return Err(format!("register class `{}` is not supported", t).into())
+                    }
+                }
+                cursor.eat_token_eq(Token::RightParen)?;
+                operands.push((is_input, Expr::parse(cursor)?));
+            }
+            cursor.eat_token_eq(Token::RightParen)?;
+
+            // inputs come before outputs
+            let num_inputs = operands.iter().filter(|(is_input, _)| *is_input).count();
+            let (mut inputs, mut outputs) = (vec![], vec![]);
+            let mut numbers = vec![];
+            for (is_input, expr) in operands {
+                if is_input {
+                    numbers.push(inputs.len());
+                    inputs.push(expr);
                 } else {
-                    TypeLitNum::from_str(suffix).unwrap()
-                }))
+                    numbers.push(num_inputs + outputs.len());
+                    outputs.push(LhsExpr::from_expr(expr)?);
+                }
+            }
+            let template = number_asm_template(&lines.join("\n"), &numbers)?;
+            Ok(AsmExpr::new(template, inputs, outputs))
+        }
+    }
+
+    /// Replace `{}` and `{n}` by `{numbers[n]}`, `{}` is the operand after the last `{}`.
+    /// Every operand must be used.
+    fn number_asm_template(template: &str, numbers: &[usize]) -> Result<String, RccError> {
+        let mut out = String::new();
+        let mut used = vec![false; numbers.len()];
+        let mut next = 0;
+        let mut chars = template.chars().peekable();
+        while let Some(c) = chars.next() {
+            match (c, chars.peek()) {
+                ('{', Some('{')) | ('}', Some('}')) => {
+                    chars.next();
+                    out.push(c);
+                    out.push(c);
+                }
+                ('{', _) => {
+                    let mut digits = String::new();
+                    while let Some(d) = chars.next_if(char::is_ascii_digit) {
+                        digits.push(d);
+                    }
+                    if chars.next() != Some('}') {
+                        return Err("invalid asm template: expected `}`".into());
+                    }
+                    let n = if digits.is_empty() {
+                        next += 1;
+                        next - 1
+                    } else {
+                        usize::from_str(&digits).map_err(|e| e.to_string())?
+                    };
+                    let number = numbers.get(n).ok_or_else(|| {
+                        format!("invalid reference to operand {} in asm template", n)
+                    })?;
+                    used[n] = true;
+                    out.push_str(&format!("{{{}}}", number));
+                }
+                ('}', _) => return Err("invalid asm template: unmatched `}` found".into()),
+                _ => out.push(c),
             }
-        })
+        }
+        match used.iter().position(|used| !used) {
+            Some(n) => Err(format!("asm operand {} is never used", n).into()),
+            None => Ok(out),
+        }
+    }
+
+    /// Split the format string at `{}`. `{{` and `}}` are escaped braces.
+    fn parse_format_string(fmt: &str) -> Result<Vec<String>, RccError> {
+        let mut pieces = vec![String::new()];
+        let mut chars = fmt.chars().peekable();
+        while let Some(c) = chars.next() {
+            match (c, chars.peek()) {
+                ('{', Some('{')) | ('}', Some('}')) => {
+                    chars.next();
+                    pieces.last_mut().unwrap().push(c);
+                }
+                ('{', Some('}')) => {
+                    chars.next();
+                    pieces.push(String::new());
+                }
+                ('{', _) => return Err("invalid format string: only `{}` is supported".into()),
+                ('}', _) => return Err("invalid format string: unmatched `}` found".into()),
+                _ => pieces.last_mut().unwrap().push(c),
+            }
+        }
+        Ok(pieces)
+    }
+
+    fn parse_literal(cursor: &mut ParseCursor) -> Result<Expr, RccError> {
+        let span = cursor.span();
+        let (literal_kind, value) = cursor.eat_literal()?;
+        let value = match literal_kind {
+            Char => value,
+            _ => lit_num_value(&value)?,
+        };
+        let mut lit_num_expr = match literal_kind {
+            Char => {
+                let c = unescape(&value[1..value.len() - 1]).and_then(|s| s.chars().next());
+                return c.map(Expr::LitChar).ok_or_else(|| "invalid escape".into());
+            }
+            Integer { suffix } => LitNumExpr::integer(value).lit_type(if suffix.is_empty() {
+                TypeLitNum::I
+            } else {
+                TypeLitNum::from_str(suffix).unwrap()
+            }),
+            Float { suffix } => LitNumExpr::integer(value).lit_type(if suffix.is_empty() {
+                TypeLitNum::F
+            } else {
+                TypeLitNum::from_str(suffix).unwrap()
+            }),
+        };
+        lit_num_expr.node_id = cursor.next_node_id();
+        if let Some(span) = span {
+            cursor.spans.insert(lit_num_expr.node_id, span);
+        }
+        Ok(Expr::LitNum(lit_num_expr))
+    }
+
+    /// Underscores are stripped and binary, octal or hexadecimal literals are converted to
+    /// decimal, so that the value can be parsed by `str::parse` in IR lowering.
+    fn lit_num_value(value: &str) -> Result<String, RccError> {
+        let value = value.replace('_', "");
+        let radix = match value.get(..2) {
+            Some("0b") => 2,
+            Some("0o") => 8,
+            Some("0x") => 16,
+            _ => return Ok(value),
+        };
+        match u128::from_str_radix(&value[2..], radix) {
+            Ok(n) => Ok(n.to_string()),
+            Err(_) => Err(format!("integer literal `{}` is too large", value).into()),
+        }
     }
 
     /// Local type definitions are analysed here.
@@ -388,14 +779,20 @@ pub mod primitive {
     /// BlockExpr -> `{` Stmt* Expr(without block)? `}`
     impl Parse for BlockExpr {
         fn parse(cursor: &mut ParseCursor) -> Result<Self, RccError> {
+            let lo = cursor.span().map(|span| span.lo);
+            let is_unsafe = cursor.eat_token_if_eq(Token::Unsafe);
             cursor.eat_token_eq(Token::LeftCurlyBraces)?;
-            let mut block_expr = BlockExpr::new(cursor.scope_count);
-            cursor.scope_count += 1;
+            let mut block_expr = BlockExpr::new(cursor.scopes.new_scope(None));
+            block_expr.node_id = cursor.next_node_id();
+            block_expr.is_unsafe = is_unsafe;
+            let mut lines = vec![];
             while cursor.next_token()? != &Token::RightCurlyBraces {
+                lines.push(cursor.line());
+                let start = cursor.token_idx();
                 match parse_stmt_or_expr_without_block(cursor)? {
                     StmtOrExpr::Stmt(stmt) => {
                         if let crate::ast::stmt::Stmt::Item(item) = &stmt {
-                            block_expr.scope.add_typedef(item);
+                            cursor.add_typedef(block_expr.scope_id, item, start)?;
                         }
                         block_expr.stmts.push(stmt)
                     }
@@ -417,7 +814,11 @@ pub mod primitive {
                 }
             }
 
+            cursor.stmt_lines.insert(block_expr.node_id, lines);
             cursor.eat_token_eq(Token::RightCurlyBraces)?;
+            if let Some(lo) = lo {
+                cursor.spans.insert(block_expr.node_id, cursor.span_from(lo));
+            }
             Ok(block_expr)
         }
     }
@@ -427,38 +828,27 @@ pub mod primitive {
     impl Parse for ArrayExpr {
         fn parse(cursor: &mut ParseCursor) -> Result<Self, RccError> {
             cursor.eat_token_eq(Token::LeftSquareBrackets)?;
+            if cursor.next_token()? == &Token::RightSquareBrackets {
+                return Err("empty arrays are not supported yet".into());
+            }
             let mut elems = vec![Expr::parse(cursor)?];
-            let mut last_is_comma = false;
-            loop {
-                match cursor.next_token()? {
-                    Token::RightCurlyBraces => return Ok(ArrayExpr::elems(elems)),
-                    Token::Semi => {
-                        cursor.bump_token()?;
-                        return if elems.len() == 1 {
-                            let len = Expr::parse(cursor)?;
-                            let len = ConstantExpr::<usize>::expr(len);
-                            cursor.eat_token_eq(Token::RightSquareBrackets)?;
-                            Ok(ArrayExpr::new(elems, len))
-                        } else {
-                            Err("length of elems should be 1".into())
-                        };
-                    }
-                    Token::Comma => {
-                        if last_is_comma {
-                            return Err("expected expr, found `,`".into());
-                        }
-                        last_is_comma = true;
-                        cursor.bump_token()?;
-                    }
-                    _ => {
-                        if !last_is_comma {
-                            return Err("expected `,`".into());
-                        }
-                        last_is_comma = false;
-                        elems.push(Expr::parse(cursor)?);
+            let mut array_expr = if cursor.eat_token_if_eq(Token::Semi) {
+                let len = Expr::parse(cursor)?;
+                cursor.eat_token_eq(Token::RightSquareBrackets)?;
+                ArrayExpr::new(elems.pop().unwrap(), len)
+            } else {
+                while cursor.eat_token_in(&[Token::Comma, Token::RightSquareBrackets])?
+                    == &Token::Comma
+                {
+                    if cursor.eat_token_if_eq(Token::RightSquareBrackets) {
+                        break;
                     }
+                    elems.push(Expr::parse(cursor)?);
                 }
-            }
+                ArrayExpr::elems(elems)
+            };
+            array_expr.node_id = cursor.next_node_id();
+            Ok(array_expr)
         }
     }
 
@@ -466,16 +856,32 @@ pub mod primitive {
         fn parse(cursor: &mut ParseCursor) -> Result<Self, RccError> {
             cursor.eat_token_eq(Token::While)?;
             Ok(WhileExpr(
-                Box::new(Expr::parse(cursor)?),
+                Box::new(cursor.with_struct_expr(false, Expr::parse)?),
                 Box::new(BlockExpr::parse(cursor)?),
             ))
         }
     }
 
+    /// ForExpr -> `for` Pattern `in` Expr BlockExpr
+    impl Parse for ForExpr {
+        fn parse(cursor: &mut ParseCursor) -> Result<Self, RccError> {
+            cursor.eat_token_eq(Token::For)?;
+            let pattern = Pattern::parse(cursor)?;
+            cursor.eat_token_eq(Token::In)?;
+            Ok(ForExpr {
+                pattern,
+                expr: Box::new(cursor.with_struct_expr(false, Expr::parse)?),
+                block: Box::new(BlockExpr::parse(cursor)?),
+            })
+        }
+    }
+
     impl Parse for LoopExpr {
         fn parse(cursor: &mut ParseCursor) -> Result<Self, RccError> {
             cursor.eat_token_eq(Token::Loop)?;
-            Ok(LoopExpr::new(BlockExpr::parse(cursor)?))
+            let mut loop_expr = LoopExpr::new(BlockExpr::parse(cursor)?);
+            loop_expr.node_id = cursor.next_node_id();
+            Ok(loop_expr)
         }
     }
 
@@ -484,11 +890,12 @@ pub mod primitive {
         fn parse(cursor: &mut ParseCursor) -> Result<Self, RccError> {
             cursor.eat_token_eq(Token::If)?;
             let mut if_expr = IfExpr::new();
-            if_expr.add_cond(Expr::parse(cursor)?);
+            if_expr.node_id = cursor.next_node_id();
+            if_expr.add_cond(cursor.with_struct_expr(false, Expr::parse)?);
             if_expr.add_block(BlockExpr::parse(cursor)?);
             while cursor.eat_token_if_eq(Token::Else) {
                 if cursor.eat_token_if_eq(Token::If) {
-                    if_expr.add_cond(Expr::parse(cursor)?);
+                    if_expr.add_cond(cursor.with_struct_expr(false, Expr::parse)?);
                 }
                 if_expr.add_block(BlockExpr::parse(cursor)?);
             }
@@ -1,4 +1,5 @@
 //! File -> Item*
+use crate::analyser::scope::BUILTIN_SCOPE_ID;
 use crate::ast::file::File;
 use crate::ast::item::Item;
 use crate::parser::Parse;
@@ -7,13 +8,21 @@ use crate::rcc::RccError;
 
 impl Parse for File {
     fn parse(cursor: &mut ParseCursor) -> Result<Self, RccError> {
-        let mut file = File::new(cursor.scope_count);
-        cursor.scope_count += 1;
+        let mut file = File::new(cursor.scopes.new_scope(Some(BUILTIN_SCOPE_ID)));
         while !cursor.is_eof() {
+            let start = cursor.token_idx();
             let item = Item::parse(cursor)?;
-            file.scope.add_typedef(&item);
+            cursor.add_typedef(file.scope_id, &item, start)?;
             file.items.push(item);
+            file.item_tokens.push(start..cursor.token_idx());
         }
+        file.scopes = std::mem::take(&mut cursor.scopes);
+        file.node_count = cursor.node_count;
+        file.stmt_lines = std::mem::take(&mut cursor.stmt_lines);
+        file.spans = std::mem::take(&mut cursor.spans);
+        file.sig_spans = std::mem::take(&mut cursor.sig_spans);
+        file.arg_spans = std::mem::take(&mut cursor.arg_spans);
+        file.def_spans = cursor.def_spans();
         Ok(file)
     }
 }
@@ -1,10 +1,13 @@
-use crate::ast::expr::BlockExpr;
+use crate::ast::expr::{BlockExpr, Expr, PathExpr, StructExpr};
 use crate::ast::item::{
-    ExternalItem, ExternalItemFn, FnParam, FnParams, Item, ItemExternalBlock, ItemFn, ItemStruct,
-    StructField, TupleField, TypeEnum, ABI,
+    Attribute, ExternalItem, ExternalItemFn, FnParam, FnParams, GenericParam, InlineHint, Item,
+    ItemCfg, ItemConst, ItemExternalBlock, ItemFn, ItemImpl, ItemStruct, ItemTrait,
+    ItemTypeAlias, StructField, TraitItemFn, TupleField, TypeEnum, ABI,
 };
-use crate::ast::pattern::Pattern;
-use crate::ast::types::TypeAnnotation;
+use crate::ast::pattern::{IdentPattern, Pattern};
+use crate::ast::printer::type_anno;
+use crate::ast::types::{PtrKind, TypeAnnotation, TypePtr};
+use crate::ast::visit::VisitorMut;
 use crate::ast::{TokenStart, Visibility};
 use crate::lexer::token::Token;
 use crate::parser::expr::primitive::parse_lit_string;
@@ -12,21 +15,134 @@ use crate::parser::{Parse, ParseCursor};
 use crate::rcc::RccError;
 use std::string::ToString;
 
+/// Item -> OuterAttribute* vis? ( ItemFn | ItemStruct | ... )
+///
+/// Any item may have `#[cfg(...)]`, which makes it an `ItemCfg`. Only functions may have
+/// the other attributes: `#[link_section = "..."]`, `#[no_mangle]`, `#[inline]`,
+/// `#[inline(always)]`, `#[inline(never)]` and `#[cold]`.
 impl Parse for Item {
     fn parse(cursor: &mut ParseCursor) -> Result<Self, RccError> {
-        let vis = Visibility::parse(cursor)?;
+        let mut docs = cursor.doc_comments();
+        let mut cfgs = vec![];
+        let mut attrs = vec![];
+        while Attribute::is_token_start(cursor.next_token()?) {
+            let attr = Attribute::parse(cursor)?;
+            if attr.name != "cfg" {
+                attrs.push(attr);
+            } else if attr.value.is_none() && attr.args.len() == 1 {
+                cfgs.push(attr);
+            } else {
+                return Err("expected `#[cfg(predicate)]`".into());
+            }
+            docs.extend(cursor.doc_comments());
+        }
+        // `#[cfg(a)] #[cfg(b)] item` is compiled if both `a` and `b` hold
+        let item = parse_item(cursor, docs, attrs)?;
+        Ok(cfgs
+            .into_iter()
+            .rev()
+            .fold(item, |item, cfg| Item::Cfg(ItemCfg::new(cfg, item))))
+    }
+}
 
-        match cursor.next_token()? {
-            Token::Fn => Ok(Self::Fn(ItemFn::parse_with_attr(cursor, vis)?)),
-            Token::Struct => Ok(Self::Struct(ItemStruct::parse_with_attr(cursor, vis)?)),
-            Token::Enum => Ok(Self::Enum(TypeEnum::parse_with_attr(cursor, vis)?)),
-            Token::Static => unimplemented!(),
-            Token::Const => unimplemented!(),
-            Token::Impl => unimplemented!(),
-            Token::Extern => Ok(Self::ExternalBlock(ItemExternalBlock::parse(cursor)?)),
-            _ => unreachable!("inner item must be fn, struct, enum, static, const or impl"),
+fn parse_item(
+    cursor: &mut ParseCursor,
+    docs: Vec<String>,
+    attrs: Vec<Attribute>,
+) -> Result<Item, RccError> {
+    let vis = Visibility::parse(cursor)?;
+    if !attrs.is_empty() && !is_fn_start(cursor)? {
+        return Err(format!("unsupported attribute `{}`", attrs[0].name).into());
+    }
+
+    match cursor.next_token()? {
+        Token::Fn | Token::Unsafe => Ok(Item::Fn(parse_item_fn(cursor, vis, attrs)?.docs(docs))),
+        Token::Struct => Ok(Item::Struct(ItemStruct::parse_with_attr(cursor, vis)?.docs(docs))),
+        Token::Enum => Ok(Item::Enum(TypeEnum::parse_with_attr(cursor, vis)?)),
+        Token::Type => Ok(Item::Type(ItemTypeAlias::parse_with_attr(cursor, vis)?)),
+        Token::Static => Err("static items are not supported yet".into()),
+        Token::Const => match cursor.nth_token(1)? {
+            Token::Fn | Token::Unsafe | Token::Extern => {
+                Ok(Item::Fn(parse_item_fn(cursor, vis, attrs)?.docs(docs)))
+            }
+            _ => Ok(Item::Const(ItemConst::parse_with_attr(cursor, vis)?)),
+        },
+        Token::Impl => Ok(Item::Impl(ItemImpl::parse(cursor)?)),
+        Token::Trait => Ok(Item::Trait(ItemTrait::parse_with_attr(cursor, vis)?)),
+        // `extern "C" fn foo() {}` or `extern "C" { ... }`
+        Token::Extern => match (cursor.nth_token(1)?, cursor.nth_token(2)) {
+            (Token::Fn, _) | (Token::LitString(_), Ok(Token::Fn)) => {
+                Ok(Item::Fn(parse_item_fn(cursor, vis, attrs)?.docs(docs)))
+            }
+            _ => Ok(Item::ExternalBlock(ItemExternalBlock::parse(cursor)?)),
+        },
+        tk => Err(format!("expected item, found `{}`", tk).into()),
+    }
+}
+
+/// `fn`, `const fn`, `unsafe fn`, `extern "C" fn` or `const unsafe extern "C" fn`
+fn is_fn_start(cursor: &ParseCursor) -> Result<bool, RccError> {
+    let mut n = 0;
+    if cursor.nth_token(n)? == &Token::Const {
+        n += 1;
+    }
+    if cursor.nth_token(n)? == &Token::Unsafe {
+        n += 1;
+    }
+    if cursor.nth_token(n)? == &Token::Extern {
+        n += 1;
+        if let Token::LitString(_) = cursor.nth_token(n)? {
+            n += 1;
+        }
+    }
+    Ok(cursor.nth_token(n)? == &Token::Fn)
+}
+
+fn parse_item_fn(
+    cursor: &mut ParseCursor,
+    vis: Visibility,
+    attrs: Vec<Attribute>,
+) -> Result<ItemFn, RccError> {
+    let mut item_fn = ItemFn::parse_with_attr(cursor, vis)?;
+    for attr in attrs {
+        let name = attr.name.as_str();
+        match name {
+            "link_section" => {
+                let section = match attr.value {
+                    Some(section) if attr.args.is_empty() => section,
+                    _ => return Err("expected `#[link_section = \"...\"]`".into()),
+                };
+                let is_symbol_char = |c: char| c.is_ascii_alphanumeric() || "._$".contains(c);
+                if section.is_empty() || !section.chars().all(is_symbol_char) {
+                    return Err(format!("invalid section name `{}`", section).into());
+                }
+                item_fn = item_fn.link_section(section);
+            }
+            "no_mangle" | "cold" if !attr.is_word() => {
+                return Err(format!("expected `#[{}]`", name).into())
+            }
+            "no_mangle" => item_fn = item_fn.no_mangle(),
+            "cold" => item_fn = item_fn.cold(),
+            "inline" => {
+                let hint = match (&attr.value, attr.word_args().as_deref()) {
+                    (None, Some([])) => InlineHint::Hint,
+                    (None, Some(["always"])) => InlineHint::Always,
+                    (None, Some(["never"])) => InlineHint::Never,
+                    _ => {
+                        return Err("expected `#[inline]`, `#[inline(always)]` or \
+                                    `#[inline(never)]`"
+                            .into())
+                    }
+                };
+                item_fn = item_fn.inline(hint);
+            }
+            _ => return Err(format!("unsupported attribute `{}`", name).into()),
         }
     }
+    if item_fn.no_mangle && item_fn.is_generic() {
+        return Err("functions generic over types must be mangled".into());
+    }
+    Ok(item_fn)
 }
 
 /// Parse struct definition
@@ -66,16 +182,21 @@ impl ItemStruct {
 }
 
 impl TypeEnum {
-    fn parse_with_attr(cursor: &mut ParseCursor, vis: Visibility) -> Result<Self, RccError> {
-        unimplemented!()
+    fn parse_with_attr(_cursor: &mut ParseCursor, _vis: Visibility) -> Result<Self, RccError> {
+        Err("enums are not supported yet".into())
     }
 }
 
 fn parse_fn_signature(
     cursor: &mut ParseCursor,
-) -> Result<(String, FnParams, TypeAnnotation), RccError> {
+) -> Result<(String, Vec<GenericParam>, FnParams, TypeAnnotation), RccError> {
     cursor.eat_token_eq(Token::Fn)?;
     let fn_name = cursor.eat_identifier()?.to_string();
+    let generics = if cursor.eat_token_if_eq(Token::Lt) {
+        parse_generic_params(cursor)?
+    } else {
+        vec![]
+    };
 
     cursor.eat_token_eq(Token::LeftParen)?;
     let fn_params = if cursor.eat_token_if_eq(Token::RightParen) {
@@ -86,23 +207,106 @@ fn parse_fn_signature(
         fn_params
     };
 
-    let ret_type = match cursor.next_token()? {
-        Token::RArrow => {
-            cursor.bump_token()?;
-            TypeAnnotation::parse(cursor)?
+    let ret_type = if cursor.eat_token_if_eq(Token::RArrow) {
+        TypeAnnotation::parse(cursor)?
+    } else {
+        match cursor.next_token()? {
+            Token::Semi | Token::LeftCurlyBraces => TypeAnnotation::Unit,
+            _ => return Err(cursor.err_expected(&[Token::Semi, Token::LeftCurlyBraces])),
         }
-        Token::Semi | Token::LeftCurlyBraces => TypeAnnotation::Unit,
-        _ => return Err("except '->' or '{'".into()),
     };
-    Ok((fn_name, fn_params, ret_type))
+    Ok((fn_name, generics, fn_params, ret_type))
+}
+
+/// GenericParams -> `<` GenericParam (`,` GenericParam)* `,`? `>`, the `<` has been eaten.
+///
+/// GenericParam -> identifier ( `:` identifier ( `+` identifier )* )?
+fn parse_generic_params(cursor: &mut ParseCursor) -> Result<Vec<GenericParam>, RccError> {
+    let mut generics = vec![];
+    while !matches!(cursor.next_token()?, Token::Gt | Token::Shr) {
+        let name = cursor.eat_identifier()?.to_string();
+        let mut bounds = vec![];
+        if cursor.eat_token_if_eq(Token::Colon) {
+            bounds.push(cursor.eat_identifier()?.to_string());
+            while cursor.eat_token_if_eq(Token::Plus) {
+                bounds.push(cursor.eat_identifier()?.to_string());
+            }
+        }
+        generics.push(GenericParam::new(name, bounds));
+        if !cursor.eat_token_if_eq(Token::Comma) {
+            break;
+        }
+    }
+    cursor.eat_gt()?;
+    Ok(generics)
 }
 
-/// ItemFn -> vis? `fn` identifier `(` FnParams? `)` ( `->` Type )? BlockExpr
+/// ItemFn -> vis? `const`? `unsafe`? ( `extern` ABI? )? `fn` identifier GenericParams?
+///           `(` FnParams? `)` ( `->` Type )? BlockExpr
 impl ItemFn {
     fn parse_with_attr(cursor: &mut ParseCursor, vis: Visibility) -> Result<Self, RccError> {
-        let (fn_name, fn_params, ret_type) = parse_fn_signature(cursor)?;
+        let lo = cursor.span().map(|span| span.lo);
+        let is_const = cursor.eat_token_if_eq(Token::Const);
+        let is_unsafe = cursor.eat_token_if_eq(Token::Unsafe);
+        let abi = if cursor.eat_token_if_eq(Token::Extern) {
+            // `extern fn` is `extern "C" fn`
+            if matches!(cursor.next_token()?, Token::LitString(_)) {
+                Some(ABI::parse(cursor)?)
+            } else {
+                Some(ABI::C)
+            }
+        } else {
+            None
+        };
+        let (fn_name, generics, fn_params, ret_type) = parse_fn_signature(cursor)?;
+        if abi.is_some() && !generics.is_empty() {
+            return Err("`extern` function can not be generic".into());
+        }
+        let sig_span = lo.map(|lo| cursor.span_from(lo));
         let fn_block = BlockExpr::parse(cursor)?;
-        Ok(ItemFn::new(vis, fn_name, fn_params, ret_type, fn_block))
+        if let Some(sig_span) = sig_span {
+            cursor.sig_spans.insert(fn_block.node_id, sig_span);
+        }
+        let mut item_fn =
+            ItemFn::new(vis, fn_name, fn_params, ret_type, fn_block).generics(generics);
+        if is_const {
+            item_fn = item_fn.const_fn();
+        }
+        if is_unsafe {
+            item_fn = item_fn.unsafe_fn();
+        }
+        Ok(match abi {
+            Some(abi) => item_fn.abi(abi),
+            None => item_fn,
+        })
+    }
+}
+
+/// ItemConst -> `const` Identifier `:` Type `=` Expr `;`
+impl ItemConst {
+    fn parse_with_attr(cursor: &mut ParseCursor, vis: Visibility) -> Result<Self, RccError> {
+        cursor.eat_token_eq(Token::Const)?;
+        let name = cursor.eat_identifier()?.to_string();
+        if !cursor.eat_token_if_eq(Token::Colon) {
+            return Err(format!("missing type for `const` item `{}`", name).into());
+        }
+        let type_anno = TypeAnnotation::parse(cursor)?;
+        cursor.eat_token_eq(Token::Eq)?;
+        let expr = Expr::parse(cursor)?;
+        cursor.eat_token_eq(Token::Semi)?;
+        Ok(ItemConst::new(vis, name, type_anno, expr))
+    }
+}
+
+/// ItemTypeAlias -> `type` Identifier `=` Type `;`
+impl ItemTypeAlias {
+    fn parse_with_attr(cursor: &mut ParseCursor, vis: Visibility) -> Result<Self, RccError> {
+        cursor.eat_token_eq(Token::Type)?;
+        let name = cursor.eat_identifier()?.to_string();
+        cursor.eat_token_eq(Token::Eq)?;
+        let type_anno = TypeAnnotation::parse(cursor)?;
+        cursor.eat_token_eq(Token::Semi)?;
+        Ok(ItemTypeAlias::new(vis, name, type_anno))
     }
 }
 
@@ -121,9 +325,32 @@ impl Parse for FnParams {
     }
 }
 
-/// FnParam -> Pattern `:` Type
+/// FnParam -> `&`? `mut`? `self` | Pattern `:` Type
 impl Parse for FnParam {
     fn parse(cursor: &mut ParseCursor) -> Result<Self, RccError> {
+        let self_type = TypeAnnotation::Identifier("Self".to_string());
+        if cursor.next_token()? == &Token::And {
+            let ptr_kind = match (cursor.nth_token(1)?, cursor.nth_token(2)?) {
+                (Token::SelfValue, _) => Some(PtrKind::Ref),
+                (Token::Mut, Token::SelfValue) => Some(PtrKind::MutRef),
+                _ => None,
+            };
+            if let Some(ptr_kind) = ptr_kind {
+                cursor.bump_token()?;
+                cursor.eat_token_if_eq(Token::Mut);
+                cursor.eat_token_eq(Token::SelfValue)?;
+                return Ok(FnParam::new(
+                    Pattern::Identifier(IdentPattern::new_const("self".to_string())),
+                    TypeAnnotation::Ptr(TypePtr::new(ptr_kind, self_type)),
+                ));
+            }
+        }
+        if cursor.eat_token_if_eq(Token::SelfValue) {
+            return Ok(FnParam::new(
+                Pattern::Identifier(IdentPattern::new_const("self".to_string())),
+                self_type,
+            ));
+        }
         let ptn = Pattern::parse(cursor)?;
         cursor.eat_token_eq(Token::Colon)?;
         let _type = TypeAnnotation::parse(cursor)?;
@@ -152,18 +379,66 @@ impl Parse for ABI {
         ABI::from_string(&s)
     }
 }
-/// ExternalItem -> ExternalItemFn
+/// OuterAttribute -> `#` `[` Meta `]`
+impl Parse for Attribute {
+    fn parse(cursor: &mut ParseCursor) -> Result<Self, RccError> {
+        cursor.eat_token_eq(Token::Pound)?;
+        cursor.eat_token_eq(Token::LeftSquareBrackets)?;
+        let attr = parse_meta(cursor)?;
+        cursor.eat_token_eq(Token::RightSquareBrackets)?;
+        Ok(attr)
+    }
+}
+
+/// Meta -> identifier ( `=` LitStr | `(` ( Meta `,` )* Meta? `)` )?
+fn parse_meta(cursor: &mut ParseCursor) -> Result<Attribute, RccError> {
+    let name = cursor.eat_identifier()?.to_string();
+    if cursor.eat_token_if_eq(Token::Eq) {
+        if !matches!(cursor.next_token()?, Token::LitString(_)) {
+            return Err("expected string literal in attribute".into());
+        }
+        return Ok(Attribute::new(name, Some(parse_lit_string(cursor)?)));
+    }
+    let mut args = vec![];
+    if cursor.eat_token_if_eq(Token::LeftParen) {
+        while !cursor.eat_token_if_eq(Token::RightParen) {
+            args.push(parse_meta(cursor)?);
+            if !cursor.eat_token_if_eq(Token::Comma) {
+                cursor.eat_token_eq(Token::RightParen)?;
+                break;
+            }
+        }
+    }
+    Ok(Attribute::new(name, None).args(args))
+}
+
+/// ExternalItem -> OuterAttribute* ExternalItemFn
 impl Parse for ExternalItem {
     fn parse(cursor: &mut ParseCursor) -> Result<Self, RccError> {
+        let mut attrs = vec![];
+        while Attribute::is_token_start(cursor.next_token()?) {
+            attrs.push(Attribute::parse(cursor)?);
+        }
         let vis = Visibility::parse(cursor)?;
         match cursor.next_token()? {
             Token::Fn => {
-                let external_fn = ExternalItemFn::parse_after_vis(cursor, vis)?;
+                let mut external_fn = ExternalItemFn::parse_after_vis(cursor, vis)?;
+                for attr in attrs {
+                    match (attr.name.as_str(), attr.value) {
+                        ("link_name", Some(link_name)) => {
+                            external_fn = external_fn.link_name(link_name);
+                        }
+                        ("link_name", None) => {
+                            return Err("expected `#[link_name = \"...\"]`".into())
+                        }
+                        (name, _) => {
+                            return Err(format!("unsupported attribute `{}`", name).into())
+                        }
+                    }
+                }
                 Ok(ExternalItem::Fn(external_fn))
             }
-            _ => {
-                todo!()
-            }
+            tk => Err(format!("expected `fn` in extern block, found `{}`", tk).into()),
         }
     }
 }
@@ -174,8 +449,114 @@ impl ExternalItemFn {
         cursor: &mut ParseCursor,
         vis: Visibility,
     ) -> Result<ExternalItemFn, RccError> {
-        let (fn_name, fn_params, ret_type) = parse_fn_signature(cursor)?;
+        let (fn_name, generics, fn_params, ret_type) = parse_fn_signature(cursor)?;
+        if !generics.is_empty() {
+            return Err("external function can not be generic".into());
+        }
         cursor.eat_token_eq(Token::Semi)?;
         Ok(ExternalItemFn::new(vis, fn_name, fn_params, ret_type))
     }
 }
+
+/// ItemTrait -> vis? `trait` identifier `{` TraitItemFn* `}`
+///
+/// TraitItemFn -> `fn` identifier `(` FnParams? `)` ( `->` Type )? `;`
+impl ItemTrait {
+    fn parse_with_attr(cursor: &mut ParseCursor, vis: Visibility) -> Result<Self, RccError> {
+        cursor.eat_token_eq(Token::Trait)?;
+        let name = cursor.eat_identifier()?.to_string();
+        cursor.eat_token_eq(Token::LeftCurlyBraces)?;
+        let mut fns = vec![];
+        while !cursor.eat_token_if_eq(Token::RightCurlyBraces) {
+            let (fn_name, generics, fn_params, ret_type) = parse_fn_signature(cursor)?;
+            if !generics.is_empty() {
+                return Err("method of trait can not be generic".into());
+            }
+            if cursor.next_token()? == &Token::LeftCurlyBraces {
+                return Err(format!("default body of `{}` is not supported", fn_name).into());
+            }
+            cursor.eat_token_eq(Token::Semi)?;
+            fns.push(TraitItemFn::new(fn_name, fn_params, ret_type));
+        }
+        Ok(ItemTrait::new(vis, name, fns))
+    }
+}
+
+/// ItemImpl -> `impl` (identifier `for`)? Type `{` (ItemFn | ItemConst)* `}`
+impl Parse for ItemImpl {
+    fn parse(cursor: &mut ParseCursor) -> Result<Self, RccError> {
+        cursor.eat_token_eq(Token::Impl)?;
+        let (trait_name, self_type) = if cursor.nth_token(1)? == &Token::For {
+            let trait_name = cursor.eat_identifier()?.to_string();
+            cursor.bump_token()?;
+            (Some(trait_name), TypeAnnotation::parse(cursor)?)
+        } else {
+            (None, TypeAnnotation::parse(cursor)?)
+        };
+        if !matches!(self_type, TypeAnnotation::Identifier(_)) {
+            return Err(format!("impl for `{}` is not supported", type_anno(&self_type)).into());
+        }
+        cursor.eat_token_eq(Token::LeftCurlyBraces)?;
+        let mut fns = vec![];
+        let mut consts = vec![];
+        let mut replacer = SelfReplacer(&self_type);
+        while !cursor.eat_token_if_eq(Token::RightCurlyBraces) {
+            let docs = cursor.doc_comments();
+            let vis = Visibility::parse(cursor)?;
+            if cursor.next_token()? == &Token::Const
+                && !matches!(cursor.nth_token(1)?, Token::Fn | Token::Unsafe | Token::Extern)
+            {
+                let mut item_const = ItemConst::parse_with_attr(cursor, vis)?;
+                replacer.visit_item_const(&mut item_const);
+                consts.push(item_const);
+                continue;
+            }
+            let mut item_fn = ItemFn::parse_with_attr(cursor, vis)?.docs(docs);
+            if item_fn.is_generic() {
+                return Err("method of impl can not be generic".into());
+            }
+            replacer.visit_item_fn(&mut item_fn);
+            fns.push(item_fn);
+        }
+        Ok(ItemImpl::new(trait_name, self_type, fns).consts(consts))
+    }
+}
+
+/// Replaces `Self` in the types, paths and struct expressions of an impl with its self type
+struct SelfReplacer<'a>(&'a TypeAnnotation);
+
+impl VisitorMut for SelfReplacer<'_> {
+    fn visit_type(&mut self, type_anno: &mut TypeAnnotation) {
+        replace_self(type_anno, self.0);
+    }
+
+    fn visit_path_expr(&mut self, path_expr: &mut PathExpr) {
+        if let (Some(segment), TypeAnnotation::Identifier(name)) =
+            (path_expr.segments.first_mut(), self.0)
+        {
+            if segment == "Self" {
+                *segment = name.clone();
+            }
+        }
+    }
+
+    fn visit_struct_expr(&mut self, struct_expr: &mut StructExpr) {
+        if let TypeAnnotation::Identifier(name) = self.0 {
+            if struct_expr.name == "Self" {
+                struct_expr.name = name.clone();
+            }
+        }
+        self.walk_struct_expr(struct_expr);
+    }
+}
+
+/// `Self`, `&Self`, ... -> `self_type`, `&self_type`, ...
+fn replace_self(type_anno: &mut TypeAnnotation, self_type: &TypeAnnotation) {
+    match type_anno {
+        TypeAnnotation::Identifier(name) if name == "Self" => *type_anno = self_type.clone(),
+        TypeAnnotation::Ptr(TypePtr { type_anno, .. }) | TypeAnnotation::Vec(type_anno) => {
+            replace_self(type_anno, self_type)
+        }
+        _ => {}
+    }
+}
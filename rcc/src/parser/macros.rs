@@ -0,0 +1,384 @@
+//! Expansion of declarative macros, between lexing and parsing:
+//!
+//! ```text
+//! macro_rules! square {
+//!     ($x:expr) => { $x * $x };
+//! }
+//! let a = square!(b + 1);        ->    let a = (b + 1) * (b + 1);
+//! ```
+//!
+//! A macro has rules `(matcher) => { transcriber }`, and an invocation is replaced by the
+//! transcriber of the first rule whose matcher matches its tokens. A matcher is made of
+//! tokens and fragments `$name:kind`:
+//!
+//! - `expr` and `ty` match the tokens until the token after the fragment in the matcher,
+//!   `,` or `;` outside of brackets, or until the end. An `expr` is put in parentheses, so
+//!   that `$x * $x` keeps the precedence of the operators;
+//! - `ident` and `literal` match an identifier and a literal;
+//! - `tt` matches a token, or tokens in brackets.
+//!
+//! Macros are used after their definitions, and may use the macros defined before them but
+//! not themselves. Repetitions `$(...)*` are not supported. The tokens of the transcriber
+//! have the position of the invocation, and the fragments keep their positions.
+use crate::diagnostic::Span;
+use crate::lexer::token::Token;
+use crate::rcc::{CompileError, ErrorKind};
+use std::collections::HashMap;
+
+/// A token with its line and span
+type Tok<'a> = (Token<'a>, u32, Span);
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum FragmentKind {
+    Expr,
+    Ty,
+    Ident,
+    Literal,
+    Tt,
+}
+
+enum Matcher<'a> {
+    Token(Token<'a>),
+    Fragment(&'a str, FragmentKind),
+}
+
+struct Rule<'a> {
+    matchers: Vec<Matcher<'a>>,
+    /// `$name` are kept as `$` and the identifier
+    transcriber: Vec<Token<'a>>,
+}
+
+/// Expand the invocations of the `macro_rules!` macros in `tokens`, and remove the
+/// definitions.
+pub fn expand_macros<'a>(tokens: Vec<Tok<'a>>) -> Result<Vec<Tok<'a>>, Box<CompileError>> {
+    let mut expander = MacroExpander {
+        macros: HashMap::new(),
+        expanding: vec![],
+    };
+    expander.expand(tokens, true)
+}
+
+struct MacroExpander<'a> {
+    macros: HashMap<&'a str, Vec<Rule<'a>>>,
+    /// the macros whose transcribers are being expanded
+    expanding: Vec<&'a str>,
+}
+
+impl<'a> MacroExpander<'a> {
+    /// `items` is whether `tokens` are items of the file, where the `;` after an invocation
+    /// is a part of it.
+    fn expand(
+        &mut self,
+        tokens: Vec<Tok<'a>>,
+        items: bool,
+    ) -> Result<Vec<Tok<'a>>, Box<CompileError>> {
+        let mut output = Vec::with_capacity(tokens.len());
+        let mut depth = 0;
+        let mut i = 0;
+        while i < tokens.len() {
+            let name = match (&tokens[i].0, tokens.get(i + 1).map(|t| &t.0)) {
+                (Token::Identifier(name), Some(Token::Not)) => *name,
+                (token, _) => {
+                    depth += delimiter_depth(token);
+                    output.push(tokens[i].clone());
+                    i += 1;
+                    continue;
+                }
+            };
+            if name == "macro_rules" {
+                i = self.define(&tokens, i + 2)?;
+            } else if self.macros.contains_key(name) {
+                let end = group_end(&tokens, i + 2).ok_or_else(|| {
+                    error(format!("expected `(`, `[` or `{{` after `{}!`", name), tokens[i].2)
+                })?;
+                let is_item = items && depth == 0;
+                output.extend(self.invoke(name, &tokens[i..end], is_item)?);
+                i = end;
+                if is_item && tokens.get(i).map(|t| &t.0) == Some(&Token::Semi) {
+                    i += 1;
+                }
+            } else {
+                output.push(tokens[i].clone());
+                i += 1;
+            }
+        }
+        Ok(output)
+    }
+
+    /// MacroRulesDefinition -> `macro_rules` `!` identifier
+    ///                         ( `{` Rules `}` | `(` Rules `)` `;` | `[` Rules `]` `;` )
+    ///
+    /// Rules -> Rule ( `;` Rule )* `;`?
+    ///
+    /// Rule -> DelimTokenTree `=>` DelimTokenTree
+    ///
+    /// `start` is the index of the name, return the index after the definition.
+    fn define(&mut self, tokens: &[Tok<'a>], start: usize) -> Result<usize, Box<CompileError>> {
+        let span = tokens[start - 2].2;
+        let name = match tokens.get(start) {
+            Some((Token::Identifier(name), ..)) => *name,
+            _ => return Err(error("expected the name of the macro", span)),
+        };
+        let end = group_end(tokens, start + 1)
+            .ok_or_else(|| error(format!("expected the rules of `{}!`", name), span))?;
+        let mut i = start + 2;
+        let mut rules = vec![];
+        while i < end - 1 {
+            let matcher_end = group_end(tokens, i)
+                .ok_or_else(|| error("expected the matcher of a rule in brackets", span))?;
+            if tokens.get(matcher_end).map(|t| &t.0) != Some(&Token::FatArrow) {
+                return Err(error("expected `=>` after the matcher", span));
+            }
+            let transcriber_end = match group_end(tokens, matcher_end + 1) {
+                Some(e) if e < end => e,
+                _ => return Err(error("expected the transcriber of a rule in brackets", span)),
+            };
+            let matchers = parse_matchers(&tokens[i + 1..matcher_end - 1], span)?;
+            let transcriber: Vec<Token<'a>> = tokens[matcher_end + 2..transcriber_end - 1]
+                .iter()
+                .map(|t| t.0.clone())
+                .collect();
+            check_transcriber(&matchers, &transcriber, span)?;
+            rules.push(Rule {
+                matchers,
+                transcriber,
+            });
+            i = transcriber_end;
+            if i < end - 1 && tokens[i].0 == Token::Semi {
+                i += 1;
+            } else if i < end - 1 {
+                return Err(error("expected `;` between the rules", span));
+            }
+        }
+        if rules.is_empty() {
+            return Err(error(format!("macro `{}!` has no rules", name), span));
+        }
+        self.macros.insert(name, rules);
+        match tokens[start + 1].0 {
+            Token::LeftCurlyBraces => Ok(end),
+            _ if tokens.get(end).map(|t| &t.0) == Some(&Token::Semi) => Ok(end + 1),
+            _ => Err(error(format!("expected `;` after the definition of `{}!`", name), span)),
+        }
+    }
+
+    /// `invocation` is `name ! ( ... )`
+    fn invoke(
+        &mut self,
+        name: &'a str,
+        invocation: &[Tok<'a>],
+        is_item: bool,
+    ) -> Result<Vec<Tok<'a>>, Box<CompileError>> {
+        let (_, line, lo) = invocation[0];
+        let span = Span::new(lo.lo, invocation[invocation.len() - 1].2.hi.max(lo.lo));
+        if self.expanding.contains(&name) {
+            let msg = format!("recursive macro `{}!` is not supported", name);
+            return Err(error(msg, span));
+        }
+        let args = &invocation[3..invocation.len() - 1];
+        let rules = &self.macros[name];
+        let (rule, bindings) = match rules
+            .iter()
+            .find_map(|rule| match_rule(&rule.matchers, args).map(|b| (rule, b)))
+        {
+            Some(found) => found,
+            None => {
+                let msg = format!("no rules of macro `{}!` match the arguments", name);
+                return Err(error(msg, span));
+            }
+        };
+        let mut tokens = vec![];
+        let mut i = 0;
+        while i < rule.transcriber.len() {
+            match (&rule.transcriber[i], rule.transcriber.get(i + 1)) {
+                (Token::Dollar, Some(Token::Identifier(var))) => {
+                    let (kind, fragment) = &bindings[var];
+                    if *kind == FragmentKind::Expr {
+                        tokens.push((Token::LeftParen, line, span));
+                        tokens.extend(fragment.iter().cloned());
+                        tokens.push((Token::RightParen, line, span));
+                    } else {
+                        tokens.extend(fragment.iter().cloned());
+                    }
+                    i += 2;
+                }
+                (token, _) => {
+                    tokens.push((token.clone(), line, span));
+                    i += 1;
+                }
+            }
+        }
+        self.expanding.push(name);
+        let expanded = self.expand(tokens, is_item);
+        self.expanding.pop();
+        expanded
+    }
+}
+
+/// Index after the tokens in brackets which start at `start`
+fn group_end(tokens: &[Tok], start: usize) -> Option<usize> {
+    if delimiter_depth(&tokens.get(start)?.0) != 1 {
+        return None;
+    }
+    let mut depth = 0;
+    for (i, (token, ..)) in tokens.iter().enumerate().skip(start) {
+        depth += delimiter_depth(token);
+        if depth == 0 {
+            return Some(i + 1);
+        }
+    }
+    None
+}
+
+/// 1 for an opening bracket, -1 for a closing one
+fn delimiter_depth(token: &Token) -> i32 {
+    match token {
+        Token::LeftParen | Token::LeftSquareBrackets | Token::LeftCurlyBraces => 1,
+        Token::RightParen | Token::RightSquareBrackets | Token::RightCurlyBraces => -1,
+        _ => 0,
+    }
+}
+
+fn parse_matchers<'a>(
+    tokens: &[Tok<'a>],
+    span: Span,
+) -> Result<Vec<Matcher<'a>>, Box<CompileError>> {
+    let mut matchers = vec![];
+    let mut i = 0;
+    while i < tokens.len() {
+        if tokens[i].0 != Token::Dollar {
+            matchers.push(Matcher::Token(tokens[i].0.clone()));
+            i += 1;
+            continue;
+        }
+        let (name, kind) = match (tokens.get(i + 1), tokens.get(i + 2), tokens.get(i + 3)) {
+            (Some((Token::LeftParen, ..)), ..) => {
+                return Err(error("repetitions in macros are not supported yet", span));
+            }
+            (
+                Some((Token::Identifier(name), ..)),
+                Some((Token::Colon, ..)),
+                Some((Token::Identifier(kind), ..)),
+            ) => (*name, *kind),
+            _ => return Err(error("expected a fragment `$name:kind`", span)),
+        };
+        let kind = match kind {
+            "expr" => FragmentKind::Expr,
+            "ty" => FragmentKind::Ty,
+            "ident" => FragmentKind::Ident,
+            "literal" => FragmentKind::Literal,
+            "tt" => FragmentKind::Tt,
+            _ => {
+                let msg = format!(
+                    "invalid fragment specifier `{}`, expected `expr`, `ty`, `ident`, \
+                     `literal` or `tt`",
+                    kind
+                );
+                return Err(error(msg, span));
+            }
+        };
+        if matchers.iter().any(|m| matches!(m, Matcher::Fragment(n, _) if *n == name)) {
+            return Err(error(format!("duplicate matcher binding `${}`", name), span));
+        }
+        if let Some(Matcher::Fragment(prev, FragmentKind::Expr | FragmentKind::Ty)) =
+            matchers.last()
+        {
+            let msg = format!("`${}` must be followed by a token, not `${}`", prev, name);
+            return Err(error(msg, span));
+        }
+        matchers.push(Matcher::Fragment(name, kind));
+        i += 4;
+    }
+    Ok(matchers)
+}
+
+/// Each `$` of the transcriber is followed by a fragment of the matcher
+fn check_transcriber(
+    matchers: &[Matcher],
+    transcriber: &[Token],
+    span: Span,
+) -> Result<(), Box<CompileError>> {
+    for (i, token) in transcriber.iter().enumerate() {
+        if token != &Token::Dollar {
+            continue;
+        }
+        match transcriber.get(i + 1) {
+            Some(Token::Identifier(name))
+                if matchers.iter().any(|m| matches!(m, Matcher::Fragment(n, _) if n == name)) => {}
+            Some(Token::Identifier(name)) => {
+                return Err(error(format!("unknown macro variable `${}`", name), span));
+            }
+            _ => return Err(error("expected a macro variable after `$`", span)),
+        }
+    }
+    Ok(())
+}
+
+/// The tokens of the fragments if `matchers` match all of `args`
+fn match_rule<'a>(
+    matchers: &[Matcher<'a>],
+    args: &[Tok<'a>],
+) -> Option<HashMap<&'a str, (FragmentKind, Vec<Tok<'a>>)>> {
+    let mut bindings = HashMap::new();
+    let mut i = 0;
+    for (m, matcher) in matchers.iter().enumerate() {
+        let (name, kind) = match matcher {
+            Matcher::Token(token) => {
+                if &args.get(i)?.0 != token {
+                    return None;
+                }
+                i += 1;
+                continue;
+            }
+            Matcher::Fragment(name, kind) => (*name, *kind),
+        };
+        let end = match kind {
+            FragmentKind::Ident => match args.get(i)?.0 {
+                Token::Identifier(_) => i + 1,
+                _ => return None,
+            },
+            FragmentKind::Literal => match args.get(i)?.0 {
+                Token::Literal { .. }
+                | Token::LitString(_)
+                | Token::LitByteString(_)
+                | Token::True
+                | Token::False => i + 1,
+                _ => return None,
+            },
+            FragmentKind::Tt => match delimiter_depth(&args.get(i)?.0) {
+                0 => i + 1,
+                1 => group_end(args, i)?,
+                _ => return None,
+            },
+            FragmentKind::Expr | FragmentKind::Ty => {
+                let next = match matchers.get(m + 1) {
+                    Some(Matcher::Token(token)) => Some(token),
+                    _ => None,
+                };
+                // `,` and `;` end an expr or a type
+                let ends = |token: &Token| {
+                    Some(token) == next || matches!(token, Token::Comma | Token::Semi)
+                };
+                let mut depth = 0;
+                let mut end = i;
+                while end < args.len() && (depth > 0 || !ends(&args[end].0)) {
+                    depth += delimiter_depth(&args[end].0);
+                    if depth < 0 {
+                        return None;
+                    }
+                    end += 1;
+                }
+                if end == i || depth != 0 {
+                    return None;
+                }
+                end
+            }
+        };
+        bindings.insert(name, (kind, args[i..end].to_vec()));
+        i = end;
+    }
+    (i == args.len()).then_some(bindings)
+}
+
+fn error(msg: impl Into<String>, span: Span) -> Box<CompileError> {
+    Box::new(CompileError::new(ErrorKind::Parse, msg).span(span))
+}
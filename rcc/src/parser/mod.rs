@@ -26,15 +26,23 @@
 //!
 //! Static -> static ident TypeAnnotation eq semi
 
+use crate::analyser::scope::{ScopeArena, ScopeID};
+use crate::ast::item::Item;
 use crate::ast::FromToken;
-use crate::ast::{Visibility, AST};
-use crate::lexer::token::{LiteralKind, Token};
-use crate::rcc::RccError;
+use crate::ast::{NodeId, Visibility, AST};
+use crate::diagnostic::Span;
+use crate::lexer::token::{LexError, LiteralKind, Token};
+use crate::lexer::Lexer;
+use crate::parser::macros::expand_macros;
+use crate::rcc::{CompileError, ErrorKind, RccError};
+use std::collections::HashMap;
 use std::fmt::Debug;
+use std::rc::Rc;
 
 pub mod expr;
 pub mod file;
 pub mod item;
+pub mod macros;
 
 mod pattern;
 mod stmt;
@@ -46,81 +54,302 @@ pub trait Parse: Sized + Debug + PartialEq {
     fn parse(cursor: &mut ParseCursor) -> Result<Self, RccError>;
 }
 
+/// The tokens are shared by the clones of a cursor.
 #[derive(Clone)]
 pub struct ParseCursor<'a> {
-    token_stream: Vec<Token<'a>>,
+    token_stream: Rc<[Token<'a>]>,
+    /// line of each token, 0 if unknown
+    token_lines: Rc<[u32]>,
+    /// span of each token, empty if the spans are unknown
+    token_spans: Rc<[Span]>,
     token_idx: usize,
-    scope_count: u64,
+    /// the `>>` at `token_idx` is split and its first `>` has been eaten
+    split_shr: bool,
+    /// scopes of the file and blocks, moved to `File` at the end of parsing
+    scopes: ScopeArena,
+    node_count: u32,
+    /// `S {` starts a struct expr, except in the conditions of `if` and `while` where `{`
+    /// starts the block
+    struct_expr_allowed: bool,
+    /// doc comments are removed from the token stream, and kept by the index of the token
+    /// after them
+    doc_comments: Rc<HashMap<usize, Vec<String>>>,
+    /// error tokens are removed from the token stream, so that parsing goes on and all of
+    /// them are reported
+    lex_errors: Rc<[LexError]>,
+    /// block -> lines of its statements and last expr, moved to `File` at the end of parsing
+    stmt_lines: HashMap<NodeId, Vec<u32>>,
+    /// node -> its span in the source, moved to `File` at the end of parsing
+    spans: HashMap<NodeId, Span>,
+    /// body of a function -> span of its signature, moved to `File` at the end of parsing
+    sig_spans: HashMap<NodeId, Span>,
+    /// call -> spans of its arguments, moved to `File` at the end of parsing
+    arg_spans: HashMap<NodeId, Vec<Span>>,
+    /// (scope, name) of the functions, structs and constants -> span of the name
+    definitions: HashMap<(ScopeID, String), Option<Span>>,
+    /// tokens tried and not found at `expected_idx`, reported by parse errors there
+    expected: Vec<String>,
+    expected_idx: usize,
+    /// the tokens are not expanded if a macro is malformed, the error is reported by
+    /// `AST::parse`
+    macro_error: Option<Box<CompileError>>,
+    /// whether any `macro_rules!` is defined
+    has_macros: bool,
 }
 
 impl<'a> ParseCursor<'a> {
-    pub fn new(token_stream: Vec<Token<'a>>) -> Self {
+    pub fn new(token_stream: impl IntoIterator<Item = Token<'a>>) -> Self {
+        let unknown = Span::new(0, 0);
+        let mut cursor =
+            Self::with_positions(token_stream.into_iter().map(|token| (token, 0, unknown)));
+        cursor.token_spans = Rc::new([]);
+        cursor
+    }
+
+    /// Tokens with their lines and spans, e.g. from `Lexer::with_positions`. The lines of
+    /// statements are kept for debug info, and the spans of some nodes for diagnostics.
+    /// Macros are expanded first, see `macros`.
+    pub fn with_positions(
+        token_stream: impl IntoIterator<Item = (Token<'a>, u32, Span)>,
+    ) -> Self {
+        let token_stream: Vec<_> = token_stream.into_iter().collect();
+        let has_macros = token_stream
+            .iter()
+            .any(|(token, ..)| token == &Token::Identifier("macro_rules"));
+        let (token_stream, macro_error) = if has_macros {
+            match expand_macros(token_stream.clone()) {
+                Ok(expanded) => (expanded, None),
+                Err(e) => (token_stream, Some(e)),
+            }
+        } else {
+            (token_stream, None)
+        };
+        let mut tokens = vec![];
+        let mut token_lines = vec![];
+        let mut token_spans = vec![];
+        let mut doc_comments: HashMap<usize, Vec<String>> = HashMap::new();
+        let mut lex_errors = vec![];
+        for (token, line, span) in token_stream {
+            match token {
+                Token::DocComment(doc) => doc_comments
+                    .entry(tokens.len())
+                    .or_default()
+                    .push(doc.to_string()),
+                Token::Error(e) => lex_errors.push(e),
+                token => {
+                    tokens.push(token);
+                    token_lines.push(line);
+                    token_spans.push(span);
+                }
+            }
+        }
         ParseCursor {
-            token_stream,
+            token_stream: tokens.into(),
+            token_lines: token_lines.into(),
+            token_spans: token_spans.into(),
             token_idx: 0,
-            scope_count: 1,
+            split_shr: false,
+            scopes: ScopeArena::new(),
+            node_count: 0,
+            struct_expr_allowed: true,
+            doc_comments: Rc::new(doc_comments),
+            lex_errors: lex_errors.into(),
+            stmt_lines: HashMap::new(),
+            spans: HashMap::new(),
+            sig_spans: HashMap::new(),
+            arg_spans: HashMap::new(),
+            definitions: HashMap::new(),
+            expected: vec![],
+            expected_idx: 0,
+            macro_error,
+            has_macros,
         }
     }
 
+    pub fn lex_errors(&self) -> &[LexError] {
+        &self.lex_errors
+    }
+
+    /// Whether the source defines macros, which are expanded in the tokens
+    pub fn has_macros(&self) -> bool {
+        self.has_macros
+    }
+
+    /// All the tokens except doc comments
+    pub fn tokens(&self) -> Rc<[Token<'a>]> {
+        self.token_stream.clone()
+    }
+
+    /// Lines of the doc comments before the next token
+    pub fn doc_comments(&self) -> Vec<String> {
+        self.doc_comments
+            .get(&self.token_idx)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// Line of the next token, 0 if unknown
+    pub fn line(&self) -> u32 {
+        self.token_lines.get(self.token_idx).copied().unwrap_or(0)
+    }
+
+    /// Span of the next token, or the empty span after the last one, `None` if the spans
+    /// are unknown
+    pub fn span(&self) -> Option<Span> {
+        match self.token_spans.get(self.token_idx) {
+            Some(span) => Some(*span),
+            None => self.token_spans.last().map(|s| Span::new(s.hi, s.hi)),
+        }
+    }
+
+    /// Span from `lo` to the end of the last eaten token
+    pub fn span_from(&self, lo: usize) -> Span {
+        let hi = match self.token_idx.checked_sub(1) {
+            Some(i) => self.token_spans.get(i).map_or(lo, |s| s.hi),
+            None => lo,
+        };
+        Span::new(lo, hi.max(lo))
+    }
+
+    /// Add the definitions of `item`, whose tokens start at `start`, to the scope. Variables
+    /// may shadow each other, but an item name can only be defined once in a scope.
+    pub fn add_typedef(
+        &mut self,
+        scope_id: ScopeID,
+        item: &Item,
+        start: usize,
+    ) -> Result<(), RccError> {
+        for name in item.defined_names() {
+            let span = self.name_span(start, name);
+            if let Some(first) = self.definitions.get(&(scope_id, name.to_string())) {
+                let msg = format!("the name `{}` is defined multiple times", name);
+                let mut error = CompileError::new(ErrorKind::Resolve, msg)
+                    .code("E0428")
+                    .note(format!("`{}` must be defined only once in this scope", name));
+                if let (Some(span), Some(first)) = (span, first) {
+                    error = error
+                        .span(span)
+                        .label(*first, format!("previous definition of `{}` here", name));
+                }
+                return Err(error.into());
+            }
+            self.definitions.insert((scope_id, name.to_string()), span);
+        }
+        self.scopes.get_mut(scope_id).add_typedef(item);
+        Ok(())
+    }
+
+    /// Span of `name` after `fn`, `struct` or `const` in the tokens since `start`
+    fn name_span(&self, start: usize, name: &str) -> Option<Span> {
+        let tokens = self.token_stream.get(start..self.token_idx)?;
+        let i = tokens.windows(2).position(|tks| {
+            matches!(tks[0], Token::Fn | Token::Struct | Token::Const)
+                && tks[1] == Token::Identifier(name)
+        })?;
+        self.token_spans.get(start + i + 1).copied()
+    }
+
+    /// Spans of the names of the definitions which are known, for go to definition
+    pub fn def_spans(&self) -> HashMap<(ScopeID, String), Span> {
+        self.definitions
+            .iter()
+            .filter_map(|(key, span)| Some((key.clone(), (*span)?)))
+            .collect()
+    }
+
+    /// Id of the next expr node whose type is kept in `TypeTable`
+    pub fn next_node_id(&mut self) -> NodeId {
+        self.node_count += 1;
+        NodeId(self.node_count - 1)
+    }
+
     pub fn next_token(&self) -> Result<&Token<'a>, RccError> {
-        match self.token_stream.get(self.token_idx) {
+        self.nth_token(0)
+    }
+
+    /// Look ahead `n` tokens without consuming them, `nth_token(0)` is `next_token()`.
+    pub fn nth_token(&self, n: usize) -> Result<&Token<'a>, RccError> {
+        match self.token_stream.get(self.token_idx + n) {
+            Some(_) if n == 0 && self.split_shr => Ok(&Token::Gt),
             Some(tk) => Ok(tk),
-            None => Err("EOF token".into()),
+            None if n == 0 => Err(self.unexpected()),
+            None => Err(CompileError::new(ErrorKind::Parse, "unexpected end of file").into()),
+        }
+    }
+
+    /// Eat `>` which closes generic arguments, `>>` is split into two `>`s.
+    pub fn eat_gt(&mut self) -> Result<(), RccError> {
+        match self.next_token()? {
+            Token::Shr => {
+                self.split_shr = true;
+                Ok(())
+            }
+            _ => self.eat_token_eq(Token::Gt),
         }
     }
 
     pub fn bump_token(&mut self) -> Result<&Token<'a>, RccError> {
+        let split_shr = std::mem::take(&mut self.split_shr);
         match self.token_stream.get(self.token_idx) {
+            Some(_) if split_shr => {
+                self.token_idx += 1;
+                Ok(&Token::Gt)
+            }
             Some(tk) => {
                 self.token_idx += 1;
                 Ok(tk)
             }
-            None => Err("EOF token".into()),
+            None => Err(self.unexpected()),
         }
     }
 
     pub fn eat_identifier(&mut self) -> Result<&'a str, RccError> {
-        match self.bump_token()? {
-            Token::Identifier(s) => Ok(s),
-            _ => Err(self.err("identifier".to_string()).into()),
+        match self.next_token()? {
+            Token::Identifier(s) => {
+                let s = *s;
+                self.bump_token()?;
+                Ok(s)
+            }
+            _ => Err(self.err("identifier".to_string())),
         }
     }
 
     pub fn eat_literal(&mut self) -> Result<(LiteralKind, String), RccError> {
-        match self.bump_token()? {
+        match self.next_token()? {
             Token::Literal {
                 literal_kind,
                 value,
-            } => Ok((literal_kind.clone(), value.to_string())),
-            _ => Err(self.err("literal".to_string()).into()),
+            } => {
+                let literal = (literal_kind.clone(), value.to_string());
+                self.bump_token()?;
+                Ok(literal)
+            }
+            _ => Err(self.err("literal".to_string())),
         }
     }
 
     pub fn eat_token_eq(&mut self, tk: Token) -> Result<(), RccError> {
-        if self.bump_token()? != &tk {
-            Err(self.err(tk.to_string()).into())
-        } else {
+        if self.eat_token_if_eq(tk) {
             Ok(())
+        } else {
+            Err(self.unexpected())
         }
     }
 
     pub fn eat_token_in(&mut self, tks: &[Token]) -> Result<&Token, RccError> {
-        let next_token = self.next_token()?;
-        for tk in tks {
-            if next_token == tk {
-                return Ok(self.bump_token()?);
-            }
+        match self.eat_token_if_in(tks) {
+            Some(_) => Ok(&self.token_stream[self.token_idx - 1]),
+            None => Err(self.unexpected()),
         }
-        Err(self.err(format!("{:?}", tks)).into())
     }
 
     pub fn eat_token_if_eq(&mut self, tk: Token) -> bool {
         if let Ok(next_tk) = self.next_token() {
             if next_tk == &tk {
-                self.bump_token();
-                return true;
+                return self.bump_token().is_ok();
             }
         }
+        self.expect(describe(&tk));
         false
     }
 
@@ -132,6 +361,9 @@ impl<'a> ParseCursor<'a> {
                 }
             }
         }
+        for tk in tks {
+            self.expect(describe(tk));
+        }
         None
     }
 
@@ -147,8 +379,70 @@ impl<'a> ParseCursor<'a> {
         }
     }
 
-    fn err(&self, expect: String) -> String {
-        format!("error in parsing: except {}", expect)
+    /// Add `expected` to the tokens which are acceptable at the next token.
+    fn expect(&mut self, expected: String) {
+        if self.expected_idx != self.token_idx {
+            self.expected_idx = self.token_idx;
+            self.expected.clear();
+        }
+        if !self.expected.contains(&expected) {
+            self.expected.push(expected);
+        }
+    }
+
+    /// "expected `x`, found `y`" or "expected one of `x`, `z`; found `y`", with all the
+    /// tokens tried at the next token, at the span of the next token if it is known.
+    fn unexpected(&self) -> RccError {
+        let found = match self.token_stream.get(self.token_idx) {
+            Some(_) if self.split_shr => describe(&Token::Gt),
+            Some(tk) => describe(tk),
+            None => "end of file".to_string(),
+        };
+        let msg = match &self.expected[..] {
+            _ if self.expected_idx != self.token_idx => format!("unexpected {}", found),
+            [expected] => format!("expected {}, found {}", expected, found),
+            expected => format!("expected one of {}; found {}", expected.join(", "), found),
+        };
+        let error = CompileError::new(ErrorKind::Parse, msg);
+        match self.span() {
+            Some(span) => error.span(span).into(),
+            None => error.into(),
+        }
+    }
+
+    fn err(&mut self, expected: String) -> RccError {
+        self.expect(expected);
+        self.unexpected()
+    }
+
+    /// Error at the next token, which is none of `tks`
+    pub fn err_expected(&mut self, tks: &[Token]) -> RccError {
+        for tk in tks {
+            self.expect(describe(tk));
+        }
+        self.unexpected()
+    }
+
+    /// Parse by `f` with struct exprs allowed or not. They are allowed again inside
+    /// parentheses and blocks, e.g. `if a == (S { x: 1 }) {}`.
+    pub fn with_struct_expr<T>(
+        &mut self,
+        allowed: bool,
+        f: impl FnOnce(&mut Self) -> Result<T, RccError>,
+    ) -> Result<T, RccError> {
+        let outer = std::mem::replace(&mut self.struct_expr_allowed, allowed);
+        let result = f(self);
+        self.struct_expr_allowed = outer;
+        result
+    }
+
+    pub fn struct_expr_allowed(&self) -> bool {
+        self.struct_expr_allowed
+    }
+
+    /// Index of the next token
+    pub fn token_idx(&self) -> usize {
+        self.token_idx
     }
 
     pub fn is_eof(&self) -> bool {
@@ -168,9 +462,36 @@ impl Parse for Visibility {
     }
 }
 
+/// `tk` as it is written in the source, e.g. `` `;` ``
+fn describe(tk: &Token) -> String {
+    format!("`{}`", tk)
+}
+
+/// Lex and parse a whole source file. It never panics whatever `src` is, so that the
+/// language server and fuzzers can feed it any text. Each malformed token is an error,
+/// otherwise the error is the first syntax error.
+pub fn parse_source(src: &str) -> Result<AST, Vec<RccError>> {
+    let mut cursor = ParseCursor::with_positions(Lexer::new(src).with_positions());
+    match AST::parse(&mut cursor) {
+        Ok(ast) => Ok(ast),
+        Err(RccError::Lex(errors)) => Err(errors
+            .into_iter()
+            .map(|e| CompileError::new(ErrorKind::Lex, e.message).span(e.span).into())
+            .collect()),
+        Err(e) => Err(vec![e]),
+    }
+}
+
 impl Parse for AST {
     fn parse(cursor: &mut ParseCursor) -> Result<Self, RccError> {
-        let file = crate::ast::file::File::parse(cursor)?;
-        Ok(AST { file })
+        let file = match cursor.macro_error.take() {
+            Some(e) => Err(RccError::Compile(e)),
+            None => crate::ast::file::File::parse(cursor),
+        };
+        // a parse error is likely caused by the malformed tokens
+        if !cursor.lex_errors().is_empty() {
+            return Err(RccError::Lex(cursor.lex_errors().to_vec()));
+        }
+        Ok(AST { file: file? })
     }
 }
@@ -3,9 +3,13 @@ use crate::lexer::token::Token;
 use crate::parser::{Parse, ParseCursor};
 use crate::rcc::RccError;
 
-/// Pattern -> IdentifierPattern
+/// Pattern -> IdentifierPattern | WildcardPattern | TuplePattern
 ///
 /// IdentifierPattern -> `mut`? identifier
+///
+/// WildcardPattern -> `_`
+///
+/// TuplePattern -> `(` ( Pattern , )+ Pattern? `)`
 impl Parse for Pattern {
     fn parse(cursor: &mut ParseCursor) -> Result<Self, RccError> {
         match cursor.next_token()? {
@@ -19,11 +23,35 @@ impl Parse for Pattern {
                     Err("expect identifier".into())
                 }
             }
+            Token::Identifier("_") => {
+                cursor.bump_token()?;
+                Ok(Self::Wild)
+            }
             Token::Identifier(s) => {
                 let s = s.to_string();
                 cursor.bump_token()?;
                 Ok(Self::Identifier(IdentPattern::new_const(s)))
             }
+            Token::LeftParen => {
+                cursor.bump_token()?;
+                let mut patterns = vec![];
+                while cursor.next_token()? != &Token::RightParen {
+                    patterns.push(Pattern::parse(cursor)?);
+                    if !cursor.eat_token_if_eq(Token::Comma) {
+                        // `(a)` is `a`
+                        if patterns.len() == 1 {
+                            cursor.eat_token_eq(Token::RightParen)?;
+                            return Ok(patterns.pop().unwrap());
+                        }
+                        break;
+                    }
+                }
+                cursor.eat_token_eq(Token::RightParen)?;
+                if patterns.is_empty() {
+                    return Err("unit patterns are not supported".into());
+                }
+                Ok(Self::Tuple(patterns))
+            }
             _ => Err("invalid pattern".into()),
         }
     }
@@ -1,5 +1,5 @@
 use crate::ast::expr::Expr::{Block, For, If, Loop, While};
-use crate::ast::expr::{BlockExpr, Expr, IfExpr, LoopExpr, WhileExpr};
+use crate::ast::expr::{BlockExpr, Expr, ForExpr, IfExpr, LoopExpr, WhileExpr};
 use crate::ast::item::Item;
 use crate::ast::pattern::Pattern;
 use crate::ast::stmt::{LetStmt, Stmt};
@@ -19,13 +19,13 @@ impl Expr {
     pub fn parse_with_block(cursor: &mut ParseCursor) -> Result<Self, RccError> {
         debug_assert!(Self::is_with_block_token_start(cursor.next_token()?));
         match cursor.next_token()? {
-            Token::LeftCurlyBraces => Ok(Block(BlockExpr::parse(cursor)?)),
+            Token::LeftCurlyBraces | Token::Unsafe => Ok(Block(BlockExpr::parse(cursor)?)),
             Token::While => Ok(While(WhileExpr::parse(cursor)?)),
             Token::Loop => Ok(Loop(LoopExpr::parse(cursor)?)),
-            Token::For => todo!("parse for expr"),
+            Token::For => Ok(For(ForExpr::parse(cursor)?)),
             Token::If => Ok(If(IfExpr::parse(cursor)?)),
-            Token::Match => todo!("parse match expr"),
-            _ => unreachable!(),
+            Token::Match => Err("`match` expressions are not supported yet".into()),
+            tk => Err(format!("expected block expression, found `{}`", tk).into()),
         }
     }
 }
@@ -43,6 +43,10 @@ pub(super) fn parse_stmt_or_expr_without_block(
             Stmt::Semi
         }
         Token::Let => Stmt::Let(LetStmt::parse(cursor)?),
+        // `unsafe { ... }` rather than `unsafe fn`
+        Token::Unsafe if cursor.nth_token(1)? == &Token::LeftCurlyBraces => {
+            Stmt::ExprStmt(Expr::parse_with_block(cursor)?)
+        }
         tk if Item::is_token_start(tk) => Stmt::Item(Item::parse(cursor)?),
         tk if Expr::is_with_block_token_start(tk) => {
             Stmt::ExprStmt(Expr::parse_with_block(cursor)?)
@@ -55,7 +59,7 @@ pub(super) fn parse_stmt_or_expr_without_block(
             }
             Stmt::ExprStmt(expr)
         }
-        tk => unimplemented!("{}", tk),
+        tk => return Err(format!("expected statement, found `{}`", tk).into()),
     }))
 }
 
@@ -6,4 +6,16 @@ fn cursor_test() {
     let mut cursor = get_parser("a - b");
     assert_eq!(Ok("a"), cursor.eat_identifier()) ;
     assert!(cursor.eat_token_in(&[Token::Minus, Token::Le]).is_ok());
-}
\ No newline at end of file
+}
+#[test]
+fn cursor_clone_test() {
+    let mut cursor = get_parser("a >> b");
+    assert_eq!(Ok("a"), cursor.eat_identifier());
+    let mut lookahead = cursor.clone();
+    assert!(lookahead.eat_gt().is_ok());
+    assert_eq!(Ok(&Token::Gt), lookahead.next_token());
+    assert!(lookahead.eat_gt().is_ok());
+    assert_eq!(Ok("b"), lookahead.eat_identifier());
+    assert_eq!(Ok(&Token::Shr), cursor.next_token());
+    assert!(std::rc::Rc::ptr_eq(&cursor.tokens(), &lookahead.tokens()));
+}
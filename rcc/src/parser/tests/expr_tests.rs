@@ -2,14 +2,15 @@ use crate::ast::expr::Expr::*;
 use crate::ast::expr::RangeOp::{DotDot, DotDotEq};
 use crate::ast::expr::UnOp::{Borrow, BorrowMut};
 use crate::ast::expr::{
-    AssignExpr, AssignOp, BinOpExpr, BinOperator, BlockExpr, CallExpr, Expr, FieldAccessExpr,
-    GroupedExpr, IfExpr, LhsExpr, PathExpr, RangeExpr, ReturnExpr, TupleExpr,
+    AsmExpr, AssertExpr, AssertKind, AssignExpr, AssignOp, BinOpExpr, BinOperator, BlockExpr,
+    CallExpr, CastExpr, Expr, FieldAccessExpr, GroupedExpr, IfExpr, LhsExpr, PathExpr, PrintExpr,
+    RangeExpr, ReturnExpr, StructExpr, TupleExpr,
 };
 use crate::ast::expr::{LitNumExpr, UnAryExpr, UnOp};
 use crate::ast::stmt::Stmt;
-use crate::ast::types::TypeLitNum;
+use crate::ast::types::{PtrKind, TypeAnnotation, TypeLitNum, TypePtr};
 use crate::parser::tests::{parse_validate, parse_input, expected_from_file};
-use crate::rcc::RccError;
+use crate::rcc::{CompileError, ErrorKind, RccError};
 use crate::tests::assert_pretty_fmt_eq;
 
 #[test]
@@ -30,7 +31,20 @@ fn path_expr_test() {
 #[test]
 fn lit_expr_test() {
     parse_validate::<Expr>(
-        vec!["2f32", "123", "'c'", r#""hello""#],
+        vec![
+            "2f32",
+            "123",
+            "'c'",
+            r#""hello""#,
+            "1_000_000u64",
+            "0xFFu8",
+            "0b1010_1010",
+            "0x1_0000_0000_0000_0000_0000_0000_0000_0000",
+            r#""a\tb\x41\u{e9}\\""#,
+            r#"'\n'"#,
+            r##"r#"a\n"b"#"##,
+            r#"b"a\xff""#,
+        ],
         vec![
             Ok(Expr::LitNum(LitNumExpr::new(
                 "2".to_string(),
@@ -42,6 +56,23 @@ fn lit_expr_test() {
             ))),
             Ok(Expr::LitChar('c')),
             Ok(Expr::LitStr("hello".to_string())),
+            Ok(Expr::LitNum(LitNumExpr::new(
+                "1000000".to_string(),
+                TypeLitNum::U64,
+            ))),
+            Ok(Expr::LitNum(LitNumExpr::new(
+                "255".to_string(),
+                TypeLitNum::U8,
+            ))),
+            Ok(Expr::LitNum(LitNumExpr::new(
+                "170".to_string(),
+                TypeLitNum::I,
+            ))),
+            Err("integer literal `0x100000000000000000000000000000000` is too large".into()),
+            Ok(Expr::LitStr("a\tbAé\\".to_string())),
+            Ok(Expr::LitChar('\n')),
+            Ok(Expr::LitStr(r#"a\n"b"#.to_string())),
+            Ok(Expr::LitByteStr(vec![b'a', 0xff])),
         ],
     );
 }
@@ -128,10 +159,10 @@ fn left_paren_test() {
     parse_validate(
         vec!["('1',)", "(1)", "(1,2)", "(1,22,)"],
         vec![
-            Ok(Tuple(TupleExpr(vec![LitChar('1')]))),
+            Ok(Tuple(TupleExpr::new(vec![LitChar('1')]))),
             Ok(Grouped(GroupedExpr::new(LitNum(1.into())))),
-            Ok(Tuple(TupleExpr(vec![LitNum(1.into()), LitNum(2.into())]))),
-            Ok(Tuple(TupleExpr(vec![LitNum(1.into()), LitNum(22.into())]))),
+            Ok(Tuple(TupleExpr::new(vec![LitNum(1.into()), LitNum(2.into())]))),
+            Ok(Tuple(TupleExpr::new(vec![LitNum(1.into()), LitNum(22.into())]))),
         ],
     );
 }
@@ -186,6 +217,32 @@ fn bin_op_test() {
     );
 }
 
+#[test]
+fn cast_expr_test() {
+    let mut_ptr = TypeAnnotation::Ptr(TypePtr::new(PtrKind::MutRawPtr, "i32".into()));
+    parse_validate(
+        vec!["-a as u8 + b", "&mut x as *mut i32 as usize", "a as"],
+        vec![
+            Ok(BinOp(BinOpExpr::new(
+                Cast(CastExpr::new(
+                    Unary(UnAryExpr::new(UnOp::Neg, "a".into())),
+                    "u8".into(),
+                )),
+                BinOperator::Plus,
+                "b".into(),
+            ))),
+            Ok(Cast(CastExpr::new(
+                Cast(CastExpr::new(
+                    Unary(UnAryExpr::new(BorrowMut, "x".into())),
+                    mut_ptr,
+                )),
+                "usize".into(),
+            ))),
+            Err(CompileError::new(ErrorKind::Parse, "unexpected end of file").into()),
+        ],
+    );
+}
+
 #[test]
 fn if_expr_test() {
     parse_validate(
@@ -213,17 +270,169 @@ fn call_expr_test() {
     );
 }
 
+#[test]
+fn struct_expr_test() {
+    parse_validate(
+        vec!["S { x: 1, y, ..a.b }", "a.b.c = S {}", "if a == S {}", "(S { x })"],
+        vec![
+            Ok(Struct(
+                StructExpr::new("S".into())
+                    .field("x", LitNum(1.into()))
+                    .field("y", "y".into())
+                    .base(FieldAccess(FieldAccessExpr::new("a".into(), "b".into()))),
+            )),
+            Ok(Assign(AssignExpr::new(
+                LhsExpr::FieldAccess(FieldAccessExpr::new(
+                    FieldAccess(FieldAccessExpr::new("a".into(), "b".into())),
+                    "c".into(),
+                )),
+                AssignOp::Eq,
+                Struct(StructExpr::new("S".into())),
+            ))),
+            Ok(If(IfExpr::from_exprs(
+                vec![BinOp(BinOpExpr::new("a".into(), BinOperator::EqEq, "S".into()))],
+                vec![BlockExpr::new(0)],
+            ))),
+            Ok(Grouped(GroupedExpr::new(Struct(
+                StructExpr::new("S".into()).field("x", "x".into()),
+            )))),
+        ],
+    );
+}
+
+#[test]
+fn print_expr_test() {
+    parse_validate(
+        vec![
+            r#"println!("a = {}, {{b}}", a + 1,)"#,
+            "println!()",
+            r#"print!("{}{}", 'c', "s")"#,
+            "print!()",
+            r#"println!("{}")"#,
+            r#"println!("{:x}", a)"#,
+            "println!(a)",
+            "format!()",
+        ],
+        vec![
+            Ok(Print(PrintExpr::new(
+                true,
+                vec!["a = ".to_string(), ", {b}".to_string()],
+                vec![BinOp(BinOpExpr::new(
+                    "a".into(),
+                    BinOperator::Plus,
+                    LitNum(1.into()),
+                ))],
+            ))),
+            Ok(Print(PrintExpr::new(true, vec!["".to_string()], vec![]))),
+            Ok(Print(PrintExpr::new(
+                false,
+                vec!["".to_string(), "".to_string(), "".to_string()],
+                vec![LitChar('c'), LitStr("s".to_string())],
+            ))),
+            Err("requires at least a format string argument".into()),
+            Err("1 positional arguments in format string, but there are 0 arguments".into()),
+            Err("invalid format string: only `{}` is supported".into()),
+            Err("format argument must be a string literal".into()),
+            Err("cannot find macro `format`".into()),
+        ],
+    );
+}
+
+#[test]
+fn assert_expr_test() {
+    parse_validate(
+        vec![
+            "assert!(a)",
+            r#"assert_eq!(a + 1, 2, "a is {{1}}",)"#,
+            "assert_ne!(a, b,)",
+            "assert!(a, b)",
+            r#"assert!(a, "{}", a)"#,
+            "assert_eq!(a)",
+            "assert_gt!(a, b)",
+        ],
+        vec![
+            Ok(Assert(AssertExpr::new(AssertKind::Assert, "a".into(), None, 0))),
+            Ok(Assert(AssertExpr::new(
+                AssertKind::Eq,
+                BinOp(BinOpExpr::new(
+                    BinOp(BinOpExpr::new("a".into(), BinOperator::Plus, LitNum(1.into()))),
+                    BinOperator::EqEq,
+                    LitNum(2.into()),
+                )),
+                Some("a is {1}".to_string()),
+                0,
+            ))),
+            Ok(Assert(AssertExpr::new(
+                AssertKind::Ne,
+                BinOp(BinOpExpr::new("a".into(), BinOperator::Ne, "b".into())),
+                None,
+                0,
+            ))),
+            Err("assertion message must be a string literal".into()),
+            Err("arguments of assertion messages are not supported".into()),
+            Err(CompileError::new(ErrorKind::Parse, "expected `,`, found `)`").into()),
+            Err("cannot find macro `assert_gt`".into()),
+        ],
+    );
+}
+
+#[test]
+fn asm_expr_test() {
+    parse_validate(
+        vec![
+            r#"asm!("csrr {}, mscratch", "nop", out(reg) x)"#,
+            r#"asm!("addi {1}, {}, {{1}}", out(reg) *p, in(reg) a + 1,)"#,
+            r#"asm!("nop", in(reg) a)"#,
+            r#"asm!("mv {}, {2}", in(reg) a, out(reg) b)"#,
+            r#"asm!("nop }", )"#,
+            r#"asm!("nop", inout(reg) a)"#,
+        ],
+        vec![
+            Ok(Asm(AsmExpr::new(
+                "csrr {0}, mscratch\nnop".to_string(),
+                vec![],
+                vec![LhsExpr::Path("x".into())],
+            ))),
+            Ok(Asm(AsmExpr::new(
+                "addi {0}, {1}, {{1}}".to_string(),
+                vec![BinOp(BinOpExpr::new(
+                    "a".into(),
+                    BinOperator::Plus,
+                    LitNum(1.into()),
+                ))],
+                vec![LhsExpr::Deref(Box::new("p".into()))],
+            ))),
+            Err("asm operand 0 is never used".into()),
+            Err("invalid reference to operand 2 in asm template".into()),
+            Err("invalid asm template: unmatched `}` found".into()),
+            Err("expected asm operand, found `inout`".into()),
+        ],
+    );
+}
+
 #[test]
 fn place_expr_test() {
+    let not_place = |msg: &str| -> RccError {
+        CompileError::new(ErrorKind::Type, msg)
+            .code("E0070")
+            .note("only variables, fields, indexing and dereferences can be assigned to")
+            .into()
+    };
     let expecteds: Vec<Result<Expr, RccError>> = vec![
-        Err("invalid lhs expr".into()),
+        Err(not_place("cannot assign to this expression")),
         Ok(Expr::Assign(AssignExpr::new(
             LhsExpr::Deref(Box::new("a".into())),
             AssignOp::Eq,
             Expr::LitNum(4.into()),
         ))),
+        Err(not_place("cannot assign to the result of this function call")),
+        Err(not_place("cannot assign to a literal")),
+        Err(not_place("cannot assign to this expression")),
     ];
-    parse_validate(vec!["if true {1} else {3} = 3", "*a = 4"], expecteds);
+    parse_validate(
+        vec!["if true {1} else {3} = 3", "*a = 4", "f(a) += 1", "3 = a", "(a + 1) = 2"],
+        expecteds,
+    );
 }
 
 #[test]
@@ -11,12 +11,40 @@ use super::parse_input;
 #[test]
 fn file_test() {
     let result = parse_input::<File>("fn pi() -> f64 {3.14f64}");
-    let excepted = Ok(File::new(0).items(vec![Item::Fn(ItemFn::new(
+    let mut excepted = File::new(0);
+    excepted.items = vec![Item::Fn(ItemFn::new(
         Priv,
         "pi".into(),
         FnParams::new(),
         TypeAnnotation::Identifier("f64".into()),
         BlockExpr::new(0).expr_without_block(LitNum(LitNumExpr::new("3.14".into(), TypeLitNum::F64))),
-    ))]));
-    assert_eq!(excepted, result);
+    ))];
+    assert_eq!(Ok(excepted), result);
+}
+
+#[test]
+fn duplicate_definition_test() {
+    use crate::rcc::{CompileError, ErrorKind};
+    let duplicate = |name: &str| {
+        Err(CompileError::new(
+            ErrorKind::Resolve,
+            format!("the name `{}` is defined multiple times", name),
+        )
+        .code("E0428")
+        .note(format!("`{}` must be defined only once in this scope", name))
+        .into())
+    };
+    assert_eq!(duplicate("f"), parse_input::<File>("fn f() {} fn f() {}"));
+    assert_eq!(duplicate("A"), parse_input::<File>("struct A {} const A: i32 = 1;"));
+    assert_eq!(
+        duplicate("g"),
+        parse_input::<File>("fn g() {} extern \"C\" { fn g(); }")
+    );
+    assert_eq!(
+        duplicate("h"),
+        parse_input::<File>("fn f() { fn h() {} let a = 1; fn h() {} }")
+    );
+    // items of a block shadow the outer ones, and variables shadow each other
+    assert!(parse_input::<File>("fn f() {} fn g() { fn f() {} }").is_ok());
+    assert!(parse_input::<File>("fn f() { let a = 1; let a = 2; { fn f() {} } }").is_ok());
 }
@@ -0,0 +1,115 @@
+//! Fuzz target of `parse_source`: inputs are mutations of the test programs, and parsing
+//! any of them must return instead of panicking. The number of inputs is
+//! `$RCC_FUZZ_ITERATIONS` (default 2000) and `$RCC_FUZZ_SEED` changes them, e.g.
+//!
+//! ```text
+//! RCC_FUZZ_ITERATIONS=1000000 cargo test fuzz_parse_source
+//! ```
+use crate::parser::parse_source;
+use crate::tests::{env_or, Rng};
+
+/// Pieces of source code spliced into the inputs
+const FRAGMENTS: &[&str] = &[
+    "fn", "pub", "let", "mut", "if", "else", "while", "loop", "for", "in", "match", "return",
+    "break", "continue", "struct", "enum", "impl", "trait", "static", "const", "type", "extern",
+    "as", "dyn", "self", "Self", "&", "&&", "*", "->", "=>", "::", ":", ";", ",", ".", "..",
+    "..=", "=", "==", "+", "-", "<", ">", ">>", "<<", "!", "?", "#", "(", ")", "[", "]", "{",
+    "}", "a", "T", "i32", "1", "1u8", "2.5", "'c'", "b'c'", "\"s\"", "b\"s\"", "r#\"s\"#",
+    "true", "_", "'a", "println!", "asm!", "#[test]", "//", "/*", "\n", "macro_rules!", "$",
+    "$x:expr", "=> {", "assert!", "assert_eq!",
+];
+
+fn corpus() -> Vec<String> {
+    let mut corpus = vec![];
+    for dir in ["./src/tests", "./src/tests/fixtures"] {
+        for entry in std::fs::read_dir(dir).unwrap() {
+            let path = entry.unwrap().path();
+            let name = path.file_name().unwrap().to_string_lossy();
+            if name.ends_with(".rs") || name.starts_with("in") && name.ends_with(".txt") {
+                corpus.push(std::fs::read_to_string(&path).unwrap());
+            }
+        }
+    }
+    corpus.sort();
+    corpus
+}
+
+/// `input` split between identifiers, numbers and punctuations
+fn split_tokens(input: &str) -> Vec<String> {
+    let is_word = |c: char| c.is_alphanumeric() || c == '_';
+    let mut pieces = vec![];
+    let mut start = 0;
+    for (i, c) in input.char_indices().skip(1) {
+        if !is_word(c) || !input[..i].ends_with(is_word) {
+            pieces.push(input[start..i].to_string());
+            start = i;
+        }
+    }
+    pieces.push(input[start..].to_string());
+    pieces
+}
+
+/// A program of the corpus with some pieces removed, replaced or copied from another one
+fn mutate(rng: &mut Rng, corpus: &[String]) -> String {
+    let mut pieces = split_tokens(&corpus[rng.below(corpus.len())]);
+    for _ in 0..1 + rng.below(8) {
+        let i = rng.below(pieces.len() + 1);
+        let fragment = FRAGMENTS[rng.below(FRAGMENTS.len())].to_string();
+        match rng.below(4) {
+            0 if i < pieces.len() => {
+                let len = 1 + rng.below(16).min(pieces.len() - i - 1);
+                pieces.drain(i..i + len);
+            }
+            1 => pieces.insert(i, fragment),
+            2 => {
+                let other = split_tokens(&corpus[rng.below(corpus.len())]);
+                let start = rng.below(other.len());
+                let len = 1 + rng.below(32).min(other.len() - start - 1);
+                pieces.insert(i, other[start..start + len].concat());
+            }
+            _ if i < pieces.len() => pieces[i] = fragment,
+            _ => pieces.push(fragment),
+        }
+    }
+    pieces.concat()
+}
+
+#[test]
+fn fuzz_parse_source() {
+    let corpus = corpus();
+    let mut rng = Rng::new(env_or("RCC_FUZZ_SEED", 0x5eed));
+    for _ in 0..env_or("RCC_FUZZ_ITERATIONS", 2000) {
+        let input = mutate(&mut rng, &corpus);
+        if std::panic::catch_unwind(|| parse_source(&input).is_ok()).is_err() {
+            panic!("parse_source panicked on:\n{}", input);
+        }
+    }
+}
+
+
+#[test]
+fn parse_source_errors() {
+    let error = |input: &str| -> Vec<String> {
+        let errors = parse_source(input).unwrap_err();
+        errors.iter().map(|e| e.to_string()).collect()
+    };
+    assert_eq!(vec!["`match` expressions are not supported yet"], error("fn f() { match a {} }"));
+    assert_eq!(vec!["unit patterns are not supported"], error("fn f((): ()) {}"));
+    assert_eq!(vec!["expected item, found `->`"], error("pub ->"));
+    assert_eq!(vec!["expected statement, found `->`"], error("fn f() { -> }"));
+    assert_eq!(
+        vec!["expected one of `,`, `)`; found `;`"],
+        error("fn f(a: i32; b: i32) {}")
+    );
+    assert_eq!(vec!["expected identifier, found `1`"], error("fn 1() {}"));
+    assert_eq!(vec!["expected `}`, found end of file"], error("struct S { a: i32,"));
+    // each malformed token is an error
+    assert_eq!(
+        vec![
+            "character literal may only contain one codepoint",
+            "invalid escape in string literal",
+        ],
+        error(r#"fn f() { let a = 'ab'; let b = "\q"; }"#)
+    );
+    assert!(parse_source("pub fn main() -> i32 { 0 }").is_ok());
+}
@@ -1,10 +1,15 @@
 use crate::ast::expr::Expr::{BinOp, LitNum};
-use crate::ast::expr::{BinOpExpr, BinOperator, BlockExpr};
-use crate::ast::item::{FnParam, FnParams, Item, ItemExternalBlock, ItemFn};
+use crate::ast::expr::{BinOpExpr, BinOperator, BlockExpr, Expr};
+use crate::ast::item::{
+    Attribute, FnParam, FnParams, GenericParam, InlineHint, Item, ItemCfg, ItemConst,
+    ItemExternalBlock, ItemFn, ItemImpl, ItemStruct, ItemTrait, ItemTypeAlias, StructField,
+    TraitItemFn, ABI,
+};
 use crate::ast::pattern::{IdentPattern, Pattern};
-use crate::ast::types::TypeAnnotation;
-use crate::ast::Visibility::Priv;
+use crate::ast::types::{PtrKind, TypeAnnotation, TypeArray, TypePtr};
+use crate::ast::Visibility::{Priv, Pub};
 use crate::parser::tests::{expected_from_file, parse_input, parse_validate};
+use crate::rcc::{CompileError, ErrorKind};
 use crate::tests::assert_pretty_fmt_eq;
 
 #[test]
@@ -59,16 +64,420 @@ fn item_fn_test() {
     );
 }
 
+#[test]
+fn item_extern_fn_test() {
+    parse_validate(
+        vec![
+            r#"pub extern "C" fn foo() {}"#,
+            "extern fn bar() {}",
+            r#"extern "Rust" fn baz() {}"#,
+        ],
+        vec![
+            Ok(Item::Fn(
+                ItemFn::new(
+                    Pub,
+                    "foo".into(),
+                    FnParams::new(),
+                    TypeAnnotation::Unit,
+                    BlockExpr::new(0),
+                )
+                .abi(ABI::C),
+            )),
+            Ok(Item::Fn(
+                ItemFn::new(
+                    Priv,
+                    "bar".into(),
+                    FnParams::new(),
+                    TypeAnnotation::Unit,
+                    BlockExpr::new(0),
+                )
+                .abi(ABI::C),
+            )),
+            Err("invalid abi Rust".into()),
+        ],
+    );
+}
+
 #[test]
 fn item_external_block_test() {
     let result = parse_input::<ItemExternalBlock>(
         r#"
         extern "C" {
             pub fn foo();
+            #[link_name = "c_bar"]
             fn bar(a: i32, b: i32);
         }
     "#,
     );
     let expected = expected_from_file("item_external_block.txt");
     assert_pretty_fmt_eq(&expected, &result.unwrap());
+
+    for (input, err) in [
+        (r#"extern "C" { #[inline] fn foo(); }"#, "unsupported attribute `inline`"),
+        (r#"extern "C" { #[link_name] fn foo(); }"#, "expected `#[link_name = \"...\"]`"),
+        (r#"extern "C" { #[link_name = 1] fn foo(); }"#, "expected string literal in attribute"),
+    ] {
+        assert_eq!(Err(err.into()), parse_input::<ItemExternalBlock>(input));
+    }
+}
+
+#[test]
+fn item_attribute_test() {
+    parse_validate(
+        vec![
+            r#"
+                #[link_section = ".fast_ram"]
+                #[no_mangle]
+                pub extern "C" fn isr() {}
+            "#,
+        ],
+        vec![Ok(Item::Fn(
+            ItemFn::new(
+                Pub,
+                "isr".into(),
+                FnParams::new(),
+                TypeAnnotation::Unit,
+                BlockExpr::new(0),
+            )
+            .abi(ABI::C)
+            .link_section(".fast_ram".into())
+            .no_mangle(),
+        ))],
+    );
+
+    parse_validate(
+        vec![
+            "#[inline] fn foo() {}",
+            "#[inline(never)] #[cold] fn foo() {}",
+            "#[inline(always,)] fn foo() {}",
+        ],
+        vec![
+            Ok(Item::Fn(foo().inline(InlineHint::Hint))),
+            Ok(Item::Fn(foo().inline(InlineHint::Never).cold())),
+            Ok(Item::Fn(foo().inline(InlineHint::Always))),
+        ],
+    );
+
+    let inline = "expected `#[inline]`, `#[inline(always)]` or `#[inline(never)]`";
+    for (input, err) in [
+        ("#[must_use] fn foo() {}", "unsupported attribute `must_use`"),
+        ("#[inline(sometimes)] fn foo() {}", inline),
+        ("#[inline(always, never)] fn foo() {}", inline),
+        (r#"#[inline = "always"] fn foo() {}"#, inline),
+        ("#[cold(always)] fn foo() {}", "expected `#[cold]`"),
+        ("#[no_mangle] fn foo<T>() {}", "functions generic over types must be mangled"),
+        ("#[no_mangle] struct Foo;", "unsupported attribute `no_mangle`"),
+        ("#[link_section] fn foo() {}", "expected `#[link_section = \"...\"]`"),
+        (r#"#[link_section = "a b"] fn foo() {}"#, "invalid section name `a b`"),
+        (r#"#[no_mangle = "foo"] fn foo() {}"#, "expected `#[no_mangle]`"),
+    ] {
+        assert_eq!(Err(err.into()), parse_input::<Item>(input));
+    }
+}
+
+#[test]
+fn item_cfg_test() {
+    let cfg = |predicate| Attribute::new("cfg".into(), None).args(vec![predicate]);
+    let feature = Attribute::new("feature".into(), Some("big".into()));
+    let not_debug =
+        Attribute::new("not".into(), None).args(vec![Attribute::new("debug".into(), None)]);
+    parse_validate(
+        vec![
+            r#"#[cfg(feature = "big")] const N: usize = 3;"#,
+            r#"#[cfg(not(debug))] #[cfg(feature = "big")] #[cold] fn foo() {}"#,
+        ],
+        vec![
+            Ok(Item::Cfg(ItemCfg::new(
+                cfg(feature.clone()),
+                Item::Const(ItemConst::new(Priv, "N".into(), "usize".into(), LitNum(3.into()))),
+            ))),
+            Ok(Item::Cfg(ItemCfg::new(
+                cfg(not_debug),
+                Item::Cfg(ItemCfg::new(cfg(feature), Item::Fn(foo().cold()))),
+            ))),
+        ],
+    );
+
+    for input in [
+        "#[cfg] fn foo() {}",
+        r#"#[cfg = "big"] fn foo() {}"#,
+        "#[cfg(debug, test)] fn foo() {}",
+    ] {
+        assert_eq!(Err("expected `#[cfg(predicate)]`".into()), parse_input::<Item>(input));
+    }
+    assert_eq!(
+        Err("unsupported attribute `cold`".into()),
+        parse_input::<Item>("#[cfg(debug)] #[cold] struct Foo;")
+    );
+}
+
+fn foo() -> ItemFn {
+    ItemFn::new(Priv, "foo".into(), FnParams::new(), TypeAnnotation::Unit, BlockExpr::new(0))
+}
+
+fn self_params(self_type: &str) -> FnParams {
+    vec![
+        FnParam::new(
+            Pattern::Identifier(IdentPattern::new_const("self".into())),
+            self_type.into(),
+        ),
+        FnParam::new(
+            Pattern::Identifier(IdentPattern::new_const("rhs".into())),
+            self_type.into(),
+        ),
+    ]
+    .into()
+}
+
+#[test]
+fn item_trait_test() {
+    parse_validate(
+        vec![
+            "trait Add { fn add(self, rhs: Self) -> Self; }",
+            "pub trait Empty {}",
+            "trait Add { fn add(self, rhs: Self) -> Self { self } }",
+            "trait Show { fn show(&mut self, other: &dyn Show); }",
+            "trait Show { fn show(a: &dyn &Show); }",
+        ],
+        vec![
+            Ok(Item::Trait(ItemTrait::new(
+                Priv,
+                "Add".into(),
+                vec![TraitItemFn::new("add".into(), self_params("Self"), "Self".into())],
+            ))),
+            Ok(Item::Trait(ItemTrait::new(Pub, "Empty".into(), vec![]))),
+            Err("default body of `add` is not supported".into()),
+            Ok(Item::Trait(ItemTrait::new(
+                Priv,
+                "Show".into(),
+                vec![TraitItemFn::new(
+                    "show".into(),
+                    vec![
+                        FnParam::new(
+                            Pattern::Identifier(IdentPattern::new_const("self".into())),
+                            TypeAnnotation::Ptr(TypePtr::new(PtrKind::MutRef, "Self".into())),
+                        ),
+                        FnParam::new(
+                            Pattern::Identifier(IdentPattern::new_const("other".into())),
+                            TypeAnnotation::Ptr(TypePtr::new(
+                                PtrKind::Ref,
+                                TypeAnnotation::Dyn("Show".into()),
+                            )),
+                        ),
+                    ]
+                    .into(),
+                    TypeAnnotation::Unit,
+                )],
+            ))),
+            Err("expected trait name after `dyn`, found `&`".into()),
+        ],
+    );
+}
+
+#[test]
+fn item_impl_test() {
+    parse_validate(
+        vec![
+            "impl Add for i32 { fn add(self, rhs: Self) -> Self { self } }",
+            "impl Add for &i32 {}",
+            "impl Add for i32 { fn add<T>(self, rhs: T) -> i32 { self } }",
+            "impl Point { fn get(&mut self) -> Self { 0 } }",
+            "impl Point { const N: Self = Self::M; }",
+        ],
+        vec![
+            Ok(Item::Impl(ItemImpl::new(
+                Some("Add".into()),
+                "i32".into(),
+                vec![ItemFn::new(
+                    Priv,
+                    "add".into(),
+                    self_params("i32"),
+                    "i32".into(),
+                    BlockExpr::new(0).expr_without_block("self".into()),
+                )],
+            ))),
+            Err("impl for `&i32` is not supported".into()),
+            Err("method of impl can not be generic".into()),
+            Ok(Item::Impl(ItemImpl::new(
+                None,
+                "Point".into(),
+                vec![ItemFn::new(
+                    Priv,
+                    "get".into(),
+                    vec![FnParam::new(
+                        Pattern::Identifier(IdentPattern::new_const("self".into())),
+                        TypeAnnotation::Ptr(TypePtr::new(PtrKind::MutRef, "Point".into())),
+                    )]
+                    .into(),
+                    "Point".into(),
+                    BlockExpr::new(0).expr_without_block(LitNum(0.into())),
+                )],
+            ))),
+            Ok(Item::Impl(
+                ItemImpl::new(None, "Point".into(), vec![]).consts(vec![ItemConst::new(
+                    Priv,
+                    "N".into(),
+                    "Point".into(),
+                    Expr::Path(vec!["Point", "M"].into()),
+                )]),
+            )),
+        ],
+    );
+}
+
+#[test]
+fn item_generic_fn_test() {
+    parse_validate(
+        vec![
+            "fn sum<T: Add + Copy, U>(a: T) -> T { a }",
+            "extern \"C\" fn id<T>(a: T) -> T { a }",
+        ],
+        vec![
+            Ok(Item::Fn(
+                ItemFn::new(
+                    Priv,
+                    "sum".into(),
+                    vec![FnParam::new(
+                        Pattern::Identifier(IdentPattern::new_const("a".into())),
+                        "T".into(),
+                    )]
+                    .into(),
+                    "T".into(),
+                    BlockExpr::new(0).expr_without_block("a".into()),
+                )
+                .generics(vec![
+                    GenericParam::new("T".into(), vec!["Add".into(), "Copy".into()]),
+                    GenericParam::new("U".into(), vec![]),
+                ]),
+            )),
+            Err("`extern` function can not be generic".into()),
+        ],
+    );
+}
+
+#[test]
+fn item_const_test() {
+    parse_validate(
+        vec!["pub const N: usize = 3;", "const fn f() {}", "const A = 3;"],
+        vec![
+            Ok(Item::Const(ItemConst::new(
+                Pub,
+                "N".into(),
+                "usize".into(),
+                LitNum(3.into()),
+            ))),
+            Ok(Item::Fn(
+                ItemFn::new(
+                    Priv,
+                    "f".into(),
+                    FnParams::new(),
+                    TypeAnnotation::Unit,
+                    BlockExpr::new(0),
+                )
+                .const_fn(),
+            )),
+            Err("missing type for `const` item `A`".into()),
+        ],
+    );
+}
+
+#[test]
+fn item_type_alias_test() {
+    parse_validate(
+        vec![
+            "pub type Meters = i32;",
+            "type P = &mut Point;",
+            "type A = [u8; N + 1];",
+            "type S = [u8];",
+            "type A;",
+            "type = i32;",
+        ],
+        vec![
+            Ok(Item::Type(ItemTypeAlias::new(Pub, "Meters".into(), "i32".into()))),
+            Ok(Item::Type(ItemTypeAlias::new(
+                Priv,
+                "P".into(),
+                TypeAnnotation::Ptr(TypePtr::new(PtrKind::MutRef, "Point".into())),
+            ))),
+            Ok(Item::Type(ItemTypeAlias::new(
+                Priv,
+                "A".into(),
+                TypeAnnotation::Array(TypeArray::new(
+                    "u8".into(),
+                    BinOp(BinOpExpr::new("N".into(), BinOperator::Plus, LitNum(1.into()))),
+                )),
+            ))),
+            Ok(Item::Type(ItemTypeAlias::new(
+                Priv,
+                "S".into(),
+                TypeAnnotation::Slice(Box::new("u8".into())),
+            ))),
+            Err(CompileError::new(ErrorKind::Parse, "expected `=`, found `;`").into()),
+            Err(CompileError::new(ErrorKind::Parse, "expected identifier, found `=`").into()),
+        ],
+    );
+}
+
+#[test]
+fn item_struct_test() {
+    parse_validate(
+        vec![
+            "struct Point { x: i32, pub y: i32, }",
+            "pub struct Empty {}",
+            "struct P { x: i32 y: i32 }",
+        ],
+        vec![
+            Ok(Item::Struct(ItemStruct::new(Priv, "Point".into()).struct_fields(vec![
+                StructField {
+                    vis: Priv,
+                    name: "x".into(),
+                    _type: "i32".into(),
+                },
+                StructField {
+                    vis: Pub,
+                    name: "y".into(),
+                    _type: "i32".into(),
+                },
+            ]))),
+            Ok(Item::Struct(
+                ItemStruct::new(Pub, "Empty".into()).struct_fields(vec![]),
+            )),
+            Err(CompileError::new(ErrorKind::Parse, "expected one of `,`, `}`; found `y`").into()),
+        ],
+    );
+}
+
+#[test]
+fn item_docs_test() {
+    parse_validate(
+        vec![
+            "/// A point\n///\n/// in 2D\nstruct Point {}",
+            "/// Does nothing\npub fn oops() {\n    /// not attached\n}",
+            "//// not a doc\nfn oops() {}",
+        ],
+        vec![
+            Ok(Item::Struct(
+                ItemStruct::new(Priv, "Point".into())
+                    .struct_fields(vec![])
+                    .docs(vec![" A point".into(), "".into(), " in 2D".into()]),
+            )),
+            Ok(Item::Fn(
+                ItemFn::new(
+                    Pub,
+                    "oops".into(),
+                    FnParams::new(),
+                    TypeAnnotation::Unit,
+                    BlockExpr::new(0),
+                )
+                .docs(vec![" Does nothing".into()]),
+            )),
+            Ok(Item::Fn(ItemFn::new(
+                Priv,
+                "oops".into(),
+                FnParams::new(),
+                TypeAnnotation::Unit,
+                BlockExpr::new(0),
+            ))),
+        ],
+    );
 }
@@ -0,0 +1,109 @@
+use crate::diagnostic::Span;
+use crate::lexer::token::Token;
+use crate::lexer::Lexer;
+use crate::parser::macros::expand_macros;
+
+fn expand(input: &str) -> Result<Vec<Token<'_>>, String> {
+    let tokens = Lexer::new(input).with_positions().collect();
+    match expand_macros(tokens) {
+        Ok(tokens) => Ok(tokens.into_iter().map(|(token, ..)| token).collect()),
+        Err(e) => Err(e.message),
+    }
+}
+
+fn tokens(input: &str) -> Result<Vec<Token<'_>>, String> {
+    Ok(Lexer::new(input).tokenize())
+}
+
+#[test]
+fn expand_test() {
+    let inputs = [
+        (
+            "macro_rules! square { ($x:expr) => { $x * $x }; } { square!(b + 1); }",
+            "{ (b + 1) * (b + 1); }",
+        ),
+        (
+            // the first matching rule is used
+            r#"
+            macro_rules! max {
+                ($a:expr, $b:expr) => { if $a > $b { $a } else { $b } };
+                ($a:expr) => { $a }
+            }
+            max!(f(1, 2), [3, 4][0]) + max![x]
+            "#,
+            "if (f(1, 2)) > ([3, 4][0]) { (f(1, 2)) } else { ([3, 4][0]) } + (x)",
+        ),
+        (
+            // `;` after an invocation in the file is a part of it
+            r#"
+            macro_rules! getter (
+                ($name:ident -> $t:ty = $v:literal) => { fn $name() -> $t { $v } }
+            );
+            getter!(seven -> Box<i32> = 7);
+            fn main() { getter!{eight -> i32 = 8}; }
+            "#,
+            "fn seven() -> Box<i32> { 7 } fn main() { fn eight() -> i32 { 8 }; }",
+        ),
+        (
+            // a macro can use the macros defined before it
+            r#"
+            macro_rules! twice { ($s:tt) => { $s; $s; } }
+            macro_rules! inc_twice { ($n:ident) => { twice!({ $n += 1 }) } }
+            inc_twice!(n)
+            "#,
+            "{ n += 1 }; { n += 1 };",
+        ),
+        ("a!(1); fn f() { println!(\"{}\", x) }", "a!(1); fn f() { println!(\"{}\", x) }"),
+    ];
+    for (input, expected) in inputs.iter() {
+        assert_eq!(tokens(expected), expand(input), "{}", input);
+    }
+}
+
+#[test]
+fn expand_error_test() {
+    let inputs = [
+        (
+            "macro_rules! a { ($x:expr) => { a!($x) }; } a!(1)",
+            "recursive macro `a!` is not supported",
+        ),
+        (
+            "macro_rules! a { ($x:ident) => {} } a!(1)",
+            "no rules of macro `a!` match the arguments",
+        ),
+        ("macro_rules! a { ($x:expr) => {} } a!(1,)", "no rules of macro `a!` match the arguments"),
+        ("macro_rules! a { () => {} } a!", "expected `(`, `[` or `{` after `a!`"),
+        ("macro_rules! a {}", "macro `a!` has no rules"),
+        ("macro_rules! a { () => {} () => {} }", "expected `;` between the rules"),
+        ("macro_rules! a { () {} }", "expected `=>` after the matcher"),
+        ("macro_rules! a ( () => {} )", "expected `;` after the definition of `a!`"),
+        ("macro_rules! a { ($x:expr) => { $y } }", "unknown macro variable `$y`"),
+        ("macro_rules! a { ($x:expr, $x:expr) => {} }", "duplicate matcher binding `$x`"),
+        ("macro_rules! a { ($x:expr $y:tt) => {} }", "`$x` must be followed by a token, not `$y`"),
+        ("macro_rules! a { ($($x:expr),*) => {} }", "repetitions in macros are not supported yet"),
+        (
+            "macro_rules! a { ($x:block) => {} }",
+            "invalid fragment specifier `block`, expected `expr`, `ty`, `ident`, `literal` or `tt`",
+        ),
+    ];
+    for (input, expected) in inputs.iter() {
+        assert_eq!(Err(expected.to_string()), expand(input), "{}", input);
+    }
+}
+
+#[test]
+fn expand_span_test() {
+    let input = "macro_rules! m { ($x:expr) => { $x + 1 } }\nm!(a)";
+    let tokens = expand_macros(Lexer::new(input).with_positions().collect()).unwrap();
+    let spans: Vec<(Token, u32, Span)> = tokens;
+    let invocation = Span::new(43, 48);
+    assert_eq!(
+        vec![
+            (Token::LeftParen, 2, invocation),
+            (Token::Identifier("a"), 2, Span::new(46, 47)),
+            (Token::RightParen, 2, invocation),
+            (Token::Plus, 2, invocation),
+        ],
+        spans[..4].to_vec()
+    );
+}
@@ -6,7 +6,11 @@ use crate::tests::read_from_file;
 mod cursor_test;
 mod expr_tests;
 mod item_tests;
+mod macro_tests;
+mod printer_tests;
+mod visit_tests;
 mod file_tests;
+mod fuzz_tests;
 mod stmt_tests;
 
 fn get_parser(input: &str) -> ParseCursor {
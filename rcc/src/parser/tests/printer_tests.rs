@@ -0,0 +1,89 @@
+use crate::ast::file::File;
+use crate::ast::printer::print_file;
+use crate::parser::tests::{expected_from_file, parse_input};
+
+/// parse -> print -> parse gives the same AST, and printing is idempotent
+fn round_trip(input: &str) -> String {
+    let file = parse_input::<File>(input).unwrap();
+    let printed = print_file(&file);
+    let reparsed = parse_input::<File>(&printed).unwrap();
+    assert_eq!(file, reparsed);
+    assert_eq!(printed, print_file(&reparsed));
+    printed
+}
+
+#[test]
+fn printer_test() {
+    let printed = round_trip(
+        r#"
+        /// a point
+        pub struct Point {
+            pub x: i32,
+            y: i64,
+        }
+        struct Pair(pub i32, u8);
+        const N: usize = 3 + 4;
+        trait Shape {
+            fn area(&self) -> i32;
+        }
+        impl Shape for Point {
+            fn area(&self) -> i32 { self.x * 2 }
+        }
+        extern "C" {
+            #[link_name = "abs"]
+            fn my_abs(a: i32) -> i32;
+        }
+        #[link_section = ".fast_ram"]
+        #[no_mangle]
+        fn isr() {}
+        /// add
+        /// two numbers
+        pub fn add<T: Add + Sub, U>(a: T, b: &mut U) -> T { a }
+        fn main() -> i32 {
+            let mut a: i32 = 3u8;
+            let p = Point { x: 1, y: 2 };
+            let q = Point { x: a, ..p };
+            let arr = [0; 4];
+            let s = "a\n\"b";
+            let c = '\'';
+            let b = b"hi\n";
+            let t = (1,);
+            *(&mut a) += -(1 + 2) * 3;
+            ;
+            if a > 2 && !false { a = 1; } else if a == 0 { return 2; } else { a = arr[1]; }
+            while a < 10 { a += 1; }
+            let r = loop { break 3; };
+            for_each(0..=3, ..a);
+            fn inner() {}
+            println!("{} {{}}", a);
+            println!();
+            print!("x");
+            {}
+            a
+        }
+        "#,
+    );
+    assert_eq!(expected_from_file("printer_test.txt"), printed);
+}
+
+#[test]
+fn printer_expr_test() {
+    let inputs = [
+        "fn f() { a = -(b + c) * !d; }",
+        "fn f() -> i32 { let x = if a { 1 } else { 2 }; x }",
+        "fn f(p: *mut u8, q: &mut i32, r: &str) {}",
+        "fn f() { let a = 1..b; let b = ..=c; let c = x.y.z(1, 2)[3]; }",
+        r#"fn f() { print!("{{{}}}\n", 0xffu32); }"#,
+        "fn f(s: &str) { for mut b in s.bytes() { b += 1; } }",
+        "fn f((a, mut b): (i32, (u8,)), _: ()) { g((1, (2,)), (a)); for _ in 0..2 {} }",
+        "const unsafe fn f(p: *mut u8) { unsafe { *p = 1; } let a = unsafe { *p }; }",
+        "fn f(x: &mut u8) { let p = x as *mut u8 as usize; let q = -a as u8 + b * c as u16; }",
+        "#[inline] fn f() {} #[inline(always)] fn g() {} #[inline(never)] #[cold] fn h() {}",
+        r#"#[cfg(all(feature = "big", not(any(debug, test))))] #[cfg(x)] fn i() {}"#,
+        "fn f() { #[cfg(not(x))] fn g() {} #[cfg(x)] const A: i32 = 1; }",
+        r#"fn f() { assert!(a > 1 || b); assert_eq!(f(1), 2, "f {{}}"); assert_ne!(a, b,); }"#,
+    ];
+    for input in inputs.iter() {
+        round_trip(input);
+    }
+}
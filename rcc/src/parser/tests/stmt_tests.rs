@@ -8,7 +8,7 @@ use crate::ast::stmt::{LetStmt, Stmt};
 use crate::parser::stmt::{parse_stmt_or_expr_without_block, StmtOrExpr};
 use crate::parser::tests::{get_parser, parse_validate};
 use crate::parser::Parse;
-use crate::rcc::RccError;
+use crate::rcc::{CompileError, ErrorKind, RccError};
 
 #[test]
 #[should_panic]
@@ -16,6 +16,10 @@ fn not_expr() {
     parse_validate(vec![";"], vec![Ok(Expr::LitNum(0.into()))]);
 }
 
+fn parse_error(message: &str) -> RccError {
+    CompileError::new(ErrorKind::Parse, message).into()
+}
+
 fn validate(inputs: Vec<&str>, outputs: Vec<Result<StmtOrExpr, RccError>>) {
     for (input, output) in inputs.iter().zip(outputs) {
         let mut cursor = get_parser(input);
@@ -50,9 +54,9 @@ fn not_end_with_semicolon() {
         vec![";", "let a=1", "let a: i32 = 4", "let mut bbb"],
         vec![
             Ok(StmtOrExpr::Stmt(Stmt::Semi)),
-            Err("EOF token".into()),
-            Err("EOF token".into()),
-            Err("EOF token".into()),
+            Err(parse_error("expected `;`, found end of file")),
+            Err(parse_error("expected `;`, found end of file")),
+            Err(parse_error("expected one of `:`, `=`, `;`; found end of file")),
         ],
     );
 }
@@ -0,0 +1,64 @@
+use crate::ast::expr::{CallExpr, PathExpr};
+use crate::ast::file::File;
+use crate::ast::printer::print_file;
+use crate::ast::visit::{Visitor, VisitorMut};
+use crate::parser::tests::parse_input;
+
+/// Counts calls, the args of a call are not walked into
+struct CallCounter {
+    calls: usize,
+    paths: usize,
+}
+
+impl Visitor for CallCounter {
+    fn visit_call_expr(&mut self, _call_expr: &CallExpr) {
+        self.calls += 1;
+    }
+
+    fn visit_path_expr(&mut self, _path_expr: &PathExpr) {
+        self.paths += 1;
+    }
+}
+
+struct Renamer<'a>(&'a str, &'a str);
+
+impl VisitorMut for Renamer<'_> {
+    fn visit_path_expr(&mut self, path_expr: &mut PathExpr) {
+        if path_expr.segments == [self.0] {
+            path_expr.segments = vec![self.1.to_string()];
+        }
+    }
+}
+
+#[test]
+fn visitor_test() {
+    let file = parse_input::<File>(
+        r#"
+        fn f(a: i32) -> i32 { g(a, h(a)) }
+        fn main() {
+            let mut a = 1;
+            while a < 10 { a += f(a); }
+            println!("{}", -a);
+        }
+        "#,
+    )
+    .unwrap();
+    let mut counter = CallCounter { calls: 0, paths: 0 };
+    for item in file.items.iter() {
+        counter.visit_item(item);
+    }
+    assert_eq!(2, counter.calls);
+    assert_eq!(3, counter.paths);
+}
+
+#[test]
+fn visitor_mut_test() {
+    let mut file = parse_input::<File>("fn main() { let a = 1; *b = a + f(a)[a]; }").unwrap();
+    for item in file.items.iter_mut() {
+        Renamer("a", "x").visit_item(item);
+    }
+    assert_eq!(
+        "fn main() {\n    let a = 1;\n    *b = x + f(x)[x];\n}\n",
+        print_file(&file)
+    );
+}
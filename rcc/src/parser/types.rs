@@ -1,3 +1,4 @@
+use crate::ast::expr::Expr;
 use crate::ast::item::{StructField, TupleField, TypeEnum};
 use crate::ast::types::TypeAnnotation::Ptr;
 use crate::ast::types::{
@@ -16,17 +17,40 @@ enum TypeArrayOrSlice {
     Slice(TypeSlice),
 }
 
+/// TypeArrayOrSlice -> Type `;` Expr `]` | Type `]`, after the `[`
 impl Parse for TypeArrayOrSlice {
     fn parse(cursor: &mut ParseCursor) -> Result<Self, RccError> {
-        unimplemented!()
+        let elem = TypeAnnotation::parse(cursor)?;
+        if cursor.eat_token_in(&[Semi, Token::RightSquareBrackets])? != &Semi {
+            return Ok(Self::Slice(Box::new(elem)));
+        }
+        let len = Expr::parse(cursor)?;
+        cursor.eat_token_eq(Token::RightSquareBrackets)?;
+        Ok(Self::Array(TypeArray::new(elem, len)))
     }
 }
 
 impl Parse for TypeAnnotation {
     fn parse(cursor: &mut ParseCursor) -> Result<Self, RccError> {
+        // Box<T>, Vec<T>
+        if let Token::Identifier(name @ ("Box" | "Vec")) = cursor.next_token()? {
+            let name = *name;
+            if cursor.nth_token(1)? == &Token::Lt {
+                cursor.bump_token()?;
+                cursor.bump_token()?;
+                let type_anno = TypeAnnotation::parse(cursor)?;
+                cursor.eat_gt()?;
+                return Ok(if name == "Box" {
+                    Self::Ptr(TypePtr::new(PtrKind::Box, type_anno))
+                } else {
+                    Self::Vec(Box::new(type_anno))
+                });
+            }
+        }
         match cursor.bump_token()? {
             Token::Identifier(s) => Ok(Self::Identifier(s.to_string())),
-            Token::LeftParen => Ok(Self::Tuple(TypeTuple::parse(cursor)?)),
+            Token::SelfType => Ok(Self::Identifier("Self".to_string())),
+            Token::LeftParen => Self::parse_paren(cursor),
             Token::LeftSquareBrackets => {
                 let arr_or_slice = TypeArrayOrSlice::parse(cursor)?;
                 match arr_or_slice {
@@ -36,30 +60,47 @@ impl Parse for TypeAnnotation {
             }
             Token::Fn => Ok(Self::FnPtr(TypeFnPtr::parse(cursor)?)),
             Token::Not => Ok(Self::Never),
+            Token::Dyn => match cursor.bump_token()? {
+                Token::Identifier(s) => Ok(Self::Dyn(s.to_string())),
+                tk => Err(format!("expected trait name after `dyn`, found `{}`", tk).into()),
+            },
             tk if matches!(tk, Token::And | Token::AndAnd | Token::Star) => {
                 let tk = tk.clone();
                 Ok(Self::Ptr(TypePtr::parse_from_first(cursor, tk)?))
             }
-            tk => Err(format!("invalid token `{:?}` for type annotation", tk).into()),
+            tk => Err(format!("invalid token `{}` for type annotation", tk).into()),
         }
     }
 }
 
-impl Parse for TypeTuple {
-    fn parse(cursor: &mut ParseCursor) -> Result<Self, RccError> {
-        unimplemented!()
-    }
-}
-
-impl Parse for TypeArray {
-    fn parse(cursor: &mut ParseCursor) -> Result<Self, RccError> {
-        unimplemented!()
+impl TypeAnnotation {
+    /// TupleType -> `(` ( Type , )+ Type? `)`
+    ///
+    /// `()` is the unit type and `(T)` is `T`, the `(` is eaten by the caller.
+    fn parse_paren(cursor: &mut ParseCursor) -> Result<Self, RccError> {
+        let mut types: TypeTuple = vec![];
+        while cursor.next_token()? != &RightParen {
+            types.push(TypeAnnotation::parse(cursor)?);
+            if !cursor.eat_token_if_eq(Comma) {
+                if types.len() == 1 {
+                    cursor.eat_token_eq(RightParen)?;
+                    return Ok(types.pop().unwrap());
+                }
+                break;
+            }
+        }
+        cursor.eat_token_eq(RightParen)?;
+        Ok(if types.is_empty() {
+            Self::Unit
+        } else {
+            Self::Tuple(types)
+        })
     }
 }
 
 impl Parse for TypeFnPtr {
-    fn parse(cursor: &mut ParseCursor) -> Result<Self, RccError> {
-        unimplemented!()
+    fn parse(_cursor: &mut ParseCursor) -> Result<Self, RccError> {
+        Err("function pointer types are not supported yet".into())
     }
 }
 
@@ -98,17 +139,29 @@ impl TypePtr {
     }
 }
 
+/// StructFields -> `{` ( StructField `,` )* StructField? `}`
+/// StructField -> pub? identifier `:` Type
 impl Parse for Vec<StructField> {
     fn parse(cursor: &mut ParseCursor) -> Result<Self, RccError> {
-        unimplemented!()
+        cursor.eat_token_eq(Token::LeftCurlyBraces)?;
+        let mut struct_fields = vec![];
+        while !cursor.eat_token_if_eq(Token::RightCurlyBraces) {
+            let vis = Visibility::parse(cursor)?;
+            let name = cursor.eat_identifier()?.to_string();
+            cursor.eat_token_eq(Token::Colon)?;
+            let _type = TypeAnnotation::parse(cursor)?;
+            struct_fields.push(StructField { vis, name, _type });
+            if cursor.eat_token_in(&[Comma, Token::RightCurlyBraces])? != &Comma {
+                break;
+            }
+        }
+        Ok(struct_fields)
     }
 }
 
 impl Parse for Vec<TupleField> {
     fn parse(cursor: &mut ParseCursor) -> Result<Self, RccError> {
-        if cursor.bump_token()? != &LeftParen {
-            return Err("invalid tuple field: except '('".into());
-        }
+        cursor.eat_token_eq(LeftParen)?;
 
         let mut tuple_fields = vec![];
         if cursor.next_token()? == &RightParen {
@@ -120,10 +173,8 @@ impl Parse for Vec<TupleField> {
             let vis = Visibility::parse(cursor)?;
             let _type = TypeAnnotation::parse(cursor)?;
             tuple_fields.push(TupleField { vis, _type });
-            match cursor.bump_token()? {
-                Comma => {}
-                RightParen => break,
-                _ => return Err("invalid tuple field: except ','".into()),
+            if cursor.eat_token_in(&[Comma, RightParen])? != &Comma {
+                break;
             }
         }
         Ok(tuple_fields)
@@ -1,23 +1,105 @@
+use crate::analyser::callgraph::CallGraph;
+use crate::analyser::cfg::CfgOptions;
 use crate::analyser::sym_resolver::SymbolResolver;
+use crate::ast::printer::print_file;
 use crate::ast::AST;
-use crate::code_gen::riscv32::Riscv32CodeGen;
-use crate::code_gen::TargetPlatform;
+use crate::cache::FnCache;
+use crate::code_gen::assembler::assemble;
+use crate::code_gen::c_header::gen_c_header;
+use crate::code_gen::{gen_code, CodeGenOptions, TargetPlatform};
+use crate::diagnostic::Span;
 use crate::ir::cfg::CFGIR;
+use crate::ir::cfg_dot::write_dot;
 use crate::ir::ir_build::IRBuilder;
+use crate::ir::passes::PassManager;
+use crate::json::Json;
+use crate::lexer::token::LexError;
 use crate::lexer::Lexer;
 use crate::parser::{Parse, ParseCursor};
+use std::collections::HashMap;
 use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+use strenum::StrEnum;
 
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Debug, PartialEq, StrEnum)]
 pub enum OptimizeLevel {
+    #[strenum("0")]
     Zero,
+    #[strenum("1")]
     One,
+    #[strenum("2")]
+    Two,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, StrEnum)]
+pub enum EmitKind {
+    /// one token per line
+    #[strenum("tokens")]
+    Tokens,
+    /// AST before symbol resolution
+    #[strenum("ast")]
+    Ast,
+    /// AST before symbol resolution in JSON
+    #[strenum("ast-json")]
+    AstJson,
+    /// source text printed from the AST
+    #[strenum("fmt")]
+    Fmt,
+    /// textual linear IR before optimization passes
+    #[strenum("ir")]
+    IR,
+    /// linear IR before optimization passes in JSON
+    #[strenum("ir-json")]
+    IRJson,
+    /// basic blocks of each function in Graphviz DOT, after the passes
+    #[strenum("cfg-dot")]
+    CfgDot,
+    /// callees of each function, recursion cycles and unreachable functions
+    #[strenum("call-graph")]
+    CallGraph,
+    #[strenum("asm")]
+    Asm,
+    /// relocatable ELF object, assembled by `code_gen::assembler`
+    #[strenum("obj")]
+    Obj,
+    /// C prototypes of `pub extern "C"` functions
+    #[strenum("c-header")]
+    CHeader,
+}
+
+/// Format of the report of `--time-passes`
+#[derive(Copy, Clone, Debug, PartialEq, StrEnum)]
+pub enum TimePassesFormat {
+    #[strenum("text")]
+    Text,
+    #[strenum("json")]
+    Json,
 }
 
 pub struct RcCompiler<R: Read, W: Write> {
     input: BufReader<R>,
     pub output: BufWriter<W>,
+    target_platform: TargetPlatform,
     opt_level: OptimizeLevel,
+    print_ir_after: Option<String>,
+    stop_after: Option<String>,
+    emit_start: bool,
+    checks: bool,
+    overflow_checks: bool,
+    stack_check: bool,
+    stack_size: Option<u32>,
+    debug_files: Vec<(String, u32)>,
+    m_extension: bool,
+    pic: bool,
+    emit: EmitKind,
+    check: bool,
+    time_passes: Option<TimePassesFormat>,
+    cache_dir: Option<PathBuf>,
+    jobs: usize,
+    cfg_options: CfgOptions,
+    /// Time spent in each stage of the last compilation, in order
+    pub timings: Vec<(String, Duration)>,
 }
 
 impl<R: Read, W: Write> RcCompiler<R, W> {
@@ -30,44 +112,401 @@ impl<R: Read, W: Write> RcCompiler<R, W> {
         RcCompiler {
             input: BufReader::new(input),
             output: BufWriter::new(output),
+            target_platform,
             opt_level,
+            print_ir_after: None,
+            stop_after: None,
+            emit_start: false,
+            checks: false,
+            overflow_checks: false,
+            stack_check: false,
+            stack_size: None,
+            debug_files: vec![],
+            m_extension: true,
+            pic: false,
+            emit: EmitKind::Asm,
+            check: false,
+            time_passes: None,
+            cache_dir: None,
+            jobs: 1,
+            cfg_options: CfgOptions::new(),
+            timings: vec![],
         }
     }
 
+    /// Dump IR to stderr after the pass named `pass_name`.
+    pub fn set_print_ir_after(&mut self, pass_name: Option<String>) {
+        self.print_ir_after = pass_name;
+    }
+
+    /// Skip the passes after the pass named `pass_name`, for `EmitKind::CfgDot`.
+    pub fn set_stop_after(&mut self, pass_name: Option<String>) {
+        self.stop_after = pass_name;
+    }
+
+    /// Emit `_start` which calls `main` and exits with its return value.
+    pub fn set_emit_start(&mut self, emit_start: bool) {
+        self.emit_start = emit_start;
+    }
+
+    /// Insert runtime checks, e.g. division by zero, which call `__rcc_panic` on failure.
+    pub fn set_checks(&mut self, checks: bool) {
+        self.checks = checks;
+    }
+
+    /// Panic when `+`, `-` or `*` of integers overflows, instead of wrapping around.
+    pub fn set_overflow_checks(&mut self, overflow_checks: bool) {
+        self.overflow_checks = overflow_checks;
+    }
+
+    /// Check for stack overflow in every function prologue.
+    pub fn set_stack_check(&mut self, stack_check: bool) {
+        self.stack_check = stack_check;
+    }
+
+    /// Size in bytes of the stack set up by `_start`.
+    pub fn set_stack_size(&mut self, stack_size: Option<u32>) {
+        self.stack_size = stack_size;
+    }
+
+    /// Map the instructions to the lines of the source by `.file` and `.loc` directives.
+    /// `files` are the input files with the lines of the text where they start, see
+    /// `CodeGenOptions::debug_files`. The incremental cache is not used with debug info, since
+    /// the lines of an unchanged function may move.
+    pub fn set_debug_files(&mut self, files: Vec<(String, u32)>) {
+        self.debug_files = files;
+    }
+
+    /// Whether the target has the M extension, see `CodeGenOptions::m_extension`. The
+    /// incremental cache is not used without it, since the runtime routines called by a
+    /// cached function are not known.
+    pub fn set_m_extension(&mut self, m_extension: bool) {
+        self.m_extension = m_extension;
+    }
+
+    /// Emit position-independent code, see `CodeGenOptions::pic`.
+    pub fn set_pic(&mut self, pic: bool) {
+        self.pic = pic;
+    }
+
+    pub fn set_emit(&mut self, emit: EmitKind) {
+        self.emit = emit;
+    }
+
+    /// Only run lexing, parsing and symbol resolution to report errors, nothing is written
+    /// to the output.
+    pub fn set_check(&mut self, check: bool) {
+        self.check = check;
+    }
+
+    /// Report the time of each stage to stderr after compilation.
+    pub fn set_time_passes(&mut self, format: Option<TimePassesFormat>) {
+        self.time_passes = format;
+    }
+
+    /// Reuse the assembly of unchanged functions saved in `cache_dir`, see `crate::cache`.
+    pub fn set_cache_dir(&mut self, cache_dir: Option<PathBuf>) {
+        self.cache_dir = cache_dir;
+    }
+
+    /// Number of threads which generate code of functions. The output is the same for any
+    /// number. IR is still built on one thread: the IR builder adds the types of temporaries
+    /// to the type table and the temporaries to the scopes of the file, and numbers the
+    /// read-only strings of the whole module, so the functions are built in order.
+    pub fn set_jobs(&mut self, jobs: usize) {
+        self.jobs = jobs.max(1);
+    }
+
+    /// Options of `#[cfg(...)]` set by `--cfg`. `target` is always set to the name of the
+    /// target platform.
+    pub fn set_cfg_options(&mut self, cfg_options: CfgOptions) {
+        self.cfg_options = cfg_options;
+    }
+
     pub fn compile(&mut self) -> Result<(), RccError> {
+        self.timings.clear();
+        let result = self.compile_stages();
+        if let Some(format) = self.time_passes {
+            write_timings(&self.timings, format, &mut std::io::stderr())?;
+        }
+        result
+    }
+
+    fn compile_stages(&mut self) -> Result<(), RccError> {
         let mut input = String::new();
         self.input.read_to_string(&mut input)?;
 
         // lex
-        let mut lexer = Lexer::new(input.as_str());
-        let token_stream = lexer.tokenize();
+        let start = Instant::now();
+        let lexer = Lexer::new(input.as_str());
+        if self.emit == EmitKind::Tokens {
+            for token in lexer {
+                writeln!(self.output, "{:?}", token)?;
+            }
+            return Ok(());
+        }
+        // tokens are produced while they are collected by the cursor
+        let mut cursor = ParseCursor::with_positions(lexer.with_positions());
+        self.timings.push(("lex".to_string(), start.elapsed()));
 
         // parse
-        let mut cursor = ParseCursor::new(token_stream);
+        let start = Instant::now();
+        let tokens = cursor.tokens();
         let mut ast = AST::parse(&mut cursor)?;
+        self.timings.push(("parse".to_string(), start.elapsed()));
+        if self.emit == EmitKind::Ast {
+            writeln!(self.output, "{:#?}", ast.file)?;
+            return Ok(());
+        }
+        if self.emit == EmitKind::AstJson {
+            writeln!(self.output, "{}", Json::from(&ast.file))?;
+            return Ok(());
+        }
+        if self.emit == EmitKind::Fmt {
+            if cursor.has_macros() {
+                return Err("formatting files with `macro_rules!` is not supported yet".into());
+            }
+            write!(self.output, "{}", print_file(&ast.file))?;
+            return Ok(());
+        }
 
+        let start = Instant::now();
         let mut sym_resolver = SymbolResolver::new();
+        sym_resolver.set_entry(self.emit_start);
+        let mut cfg_options = self.cfg_options.clone();
+        cfg_options.insert("target", Some(self.target_platform.desc().name));
+        sym_resolver.set_cfg_options(cfg_options);
+        sym_resolver.set_pointer_width(self.target_platform.desc().pointer_width);
+        sym_resolver.set_codegen(matches!(self.emit, EmitKind::Asm | EmitKind::Obj));
         sym_resolver.visit_file(&mut ast.file)?;
+        self.timings.push(("resolve".to_string(), start.elapsed()));
+        if self.check {
+            return Ok(());
+        }
+        if self.emit == EmitKind::CHeader {
+            return gen_c_header(&ast.file, &mut self.output);
+        }
 
+        let fingerprint = format!(
+            "{:?} {:?} {} {} {} {}",
+            self.target_platform,
+            self.opt_level,
+            self.checks,
+            self.overflow_checks,
+            self.stack_check,
+            self.pic
+        );
+        let cache = match &self.cache_dir {
+            Some(dir)
+                if self.emit == EmitKind::Asm
+                    && self.debug_files.is_empty()
+                    && self.m_extension =>
+            {
+                Some(FnCache::new(dir, &tokens, &ast.file, &fingerprint)?)
+            }
+            _ => None,
+        };
+        let no_reused = HashMap::new();
+        let reused = cache.as_ref().map_or(&no_reused, |cache| cache.reused());
+
+        let start = Instant::now();
         let mut ir_builder = IRBuilder::new(self.opt_level);
+        ir_builder.set_checks(self.checks);
+        ir_builder.set_overflow_checks(self.overflow_checks);
+        ir_builder.set_debug_info(!self.debug_files.is_empty());
+        ir_builder.set_reused_fns(
+            reused
+                .iter()
+                .map(|(name, cached_fn)| (name.clone(), cached_fn.ro_strs.clone()))
+                .collect(),
+        );
         let linear_ir = ir_builder.generate_ir(&mut ast)?;
+        self.timings.push(("build-ir".to_string(), start.elapsed()));
+        if self.emit == EmitKind::IR {
+            write!(self.output, "{}", linear_ir)?;
+            return Ok(());
+        }
+        if self.emit == EmitKind::IRJson {
+            writeln!(self.output, "{}", Json::from(&linear_ir))?;
+            return Ok(());
+        }
 
-        let cfg_ir = CFGIR::new(linear_ir);
-        cfg_ir.reaching_definitions_analysis()?;
+        let mut cfg_ir = CFGIR::new(linear_ir);
+        cfg_ir.precompiled_fns = reused.values().map(|f| f.precompiled.clone()).collect();
+        let mut pass_manager = PassManager::with_opt_level(self.opt_level, self.checks);
+        pass_manager.set_print_ir_after(self.print_ir_after.clone());
+        pass_manager.set_stop_after(self.stop_after.clone());
+        let result = pass_manager.run(&mut cfg_ir, &mut std::io::stderr());
+        for (name, duration) in pass_manager.timings() {
+            self.timings.push((name.to_string(), *duration));
+        }
+        result?;
+        if self.emit == EmitKind::CfgDot {
+            return write_dot(&cfg_ir, &mut self.output);
+        }
+        if self.emit == EmitKind::CallGraph {
+            write!(self.output, "{}", CallGraph::new(&cfg_ir))?;
+            return Ok(());
+        }
 
-        match self.opt_level {
-            OptimizeLevel::Zero => {
-                let mut code_gen = Riscv32CodeGen::new(cfg_ir, &mut self.output, self.opt_level);
-                code_gen.run()?;
-            }
-            OptimizeLevel::One => {
-                todo!()
-            }
+        let start = Instant::now();
+        let mut options = CodeGenOptions::new(self.opt_level);
+        options.emit_start = self.emit_start;
+        options.stack_check = self.stack_check;
+        options.stack_size = self.stack_size;
+        options.debug_files = self.debug_files.clone();
+        options.m_extension = self.m_extension;
+        options.pic = self.pic;
+        let mut asm = vec![];
+        let out: &mut dyn Write = match self.emit {
+            EmitKind::Obj => &mut asm,
+            _ => &mut self.output,
+        };
+        match &cache {
+            Some(cache) => cache.gen_code(
+                self.target_platform,
+                options,
+                &cfg_ir,
+                self.jobs,
+                ir_builder.fn_ro_strs(),
+                out,
+            )?,
+            None => gen_code(self.target_platform, options, &cfg_ir, self.jobs, out)?,
+        }
+        self.timings.push(("codegen".to_string(), start.elapsed()));
+        if self.emit == EmitKind::Obj {
+            let start = Instant::now();
+            let xlen = self.target_platform.desc().pointer_width;
+            let obj = assemble(&String::from_utf8_lossy(&asm), xlen)?;
+            obj.write(&mut self.output)?;
+            self.timings.push(("assemble".to_string(), start.elapsed()));
         }
         Ok(())
     }
 }
 
+/// ```text
+/// stage                       time(ms)       %
+/// lex                            0.012     4.1
+/// ...
+/// total                          0.290   100.0
+/// ```
+pub fn write_timings(
+    timings: &[(String, Duration)],
+    format: TimePassesFormat,
+    out: &mut dyn Write,
+) -> Result<(), RccError> {
+    let total: Duration = timings.iter().map(|(_, d)| *d).sum();
+    match format {
+        TimePassesFormat::Text => {
+            writeln!(out, "{:<24}{:>12}{:>8}", "stage", "time(ms)", "%")?;
+            for (name, duration) in timings.iter() {
+                let percent = if total.is_zero() {
+                    0.0
+                } else {
+                    duration.as_secs_f64() / total.as_secs_f64() * 100.0
+                };
+                writeln!(
+                    out,
+                    "{:<24}{:>12.3}{:>8.1}",
+                    name,
+                    duration.as_secs_f64() * 1000.0,
+                    percent
+                )?;
+            }
+            writeln!(
+                out,
+                "{:<24}{:>12.3}{:>8.1}",
+                "total",
+                total.as_secs_f64() * 1000.0,
+                100.0
+            )?;
+        }
+        TimePassesFormat::Json => {
+            let stages: Vec<String> = timings
+                .iter()
+                .map(|(name, duration)| {
+                    format!("{{\"name\":\"{}\",\"micros\":{}}}", name, duration.as_micros())
+                })
+                .collect();
+            writeln!(
+                out,
+                "{{\"stages\":[{}],\"total_micros\":{}}}",
+                stages.join(","),
+                total.as_micros()
+            )?;
+        }
+    }
+    Ok(())
+}
+
+/// Stage of the compiler which rejects the program
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ErrorKind {
+    /// malformed tokens
+    Lex,
+    Parse,
+    /// names and paths
+    Resolve,
+    Type,
+    /// errors which are not classified yet, e.g. made from strings
+    Other,
+}
+
+/// An error in the compiled program. `code` is the number of the same error in rustc, e.g.
+/// `E0425` for an unresolved name.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CompileError {
+    pub kind: ErrorKind,
+    pub message: String,
+    pub code: Option<&'static str>,
+    pub span: Option<Span>,
+    pub notes: Vec<String>,
+    /// e.g. "did you mean `count`?"
+    pub helps: Vec<String>,
+    /// secondary spans with their messages, e.g. "previous definition of `foo` here"
+    pub labels: Vec<(Span, String)>,
+}
+
+impl CompileError {
+    pub fn new(kind: ErrorKind, message: impl Into<String>) -> CompileError {
+        CompileError {
+            kind,
+            message: message.into(),
+            code: None,
+            span: None,
+            notes: vec![],
+            helps: vec![],
+            labels: vec![],
+        }
+    }
+
+    pub fn code(mut self, code: &'static str) -> CompileError {
+        self.code = Some(code);
+        self
+    }
+
+    pub fn span(mut self, span: Span) -> CompileError {
+        self.span = Some(span);
+        self
+    }
+
+    pub fn note(mut self, note: impl Into<String>) -> CompileError {
+        self.notes.push(note.into());
+        self
+    }
+
+    pub fn help(mut self, help: impl Into<String>) -> CompileError {
+        self.helps.push(help.into());
+        self
+    }
+
+    pub fn label(mut self, span: Span, message: impl Into<String>) -> CompileError {
+        self.labels.push((span, message.into()));
+        self
+    }
+}
+
 #[derive(thiserror::Error, Debug)]
 pub enum RccError {
     #[error("{0}")]
@@ -76,19 +515,31 @@ pub enum RccError {
     ParseInt(#[from] std::num::ParseIntError),
     #[error("{0}")]
     ParseFloat(#[from] std::num::ParseFloatError),
-    #[error("{0}")]
-    Parse(String),
+    /// boxed to keep `Result<T, RccError>` small
+    #[error("{}", .0.message)]
+    Compile(Box<CompileError>),
+    /// All the malformed tokens of the input
+    #[error("{}", .0.iter().map(|e| e.message.as_str()).collect::<Vec<_>>().join("\n"))]
+    Lex(Vec<LexError>),
 }
 
+impl From<CompileError> for RccError {
+    fn from(e: CompileError) -> Self {
+        RccError::Compile(Box::new(e))
+    }
+}
+
+/// Errors made from strings are not classified, so that the code which reports them can be
+/// migrated one by one.
 impl From<String> for RccError {
     fn from(s: String) -> Self {
-        RccError::Parse(s)
+        RccError::from(CompileError::new(ErrorKind::Other, s))
     }
 }
 
 impl From<&str> for RccError {
     fn from(s: &str) -> Self {
-        RccError::Parse(s.to_string())
+        RccError::from(s.to_string())
     }
 }
 
@@ -101,9 +552,9 @@ impl PartialEq for RccError {
                 }
                 false
             }
-            RccError::Parse(s) => {
-                if let RccError::Parse(o) = other {
-                    return s == o;
+            RccError::Compile(e) => {
+                if let RccError::Compile(o) = other {
+                    return e == o;
                 }
                 false
             }
@@ -119,6 +570,12 @@ impl PartialEq for RccError {
                 }
                 false
             }
+            RccError::Lex(errors) => {
+                if let RccError::Lex(o) = other {
+                    return errors == o;
+                }
+                false
+            }
         }
     }
 }
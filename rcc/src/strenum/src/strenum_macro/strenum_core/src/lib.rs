@@ -5,6 +5,26 @@ use syn::{DataEnum, DeriveInput};
 
 const STR_ENUM: &str = "strenum";
 const DISABLED: &str = "disabled";
+const DISPLAY: &str = "display";
+
+/// The method of `#[strenum(display = "method")]` on the enum, which formats the disabled
+/// variants. Without it, formatting them is an error.
+fn display_method(attrs: &[syn::Attribute]) -> Option<Ident> {
+    for attr in attrs.iter().filter(|attr| attr.path.is_ident(STR_ENUM)) {
+        let expr = match syn::parse2::<syn::ExprParen>(attr.tokens.clone()) {
+            Ok(paren) => *paren.expr,
+            Err(_) => continue,
+        };
+        if let syn::Expr::Assign(syn::ExprAssign { left, right, .. }) = expr {
+            if let (syn::Expr::Path(path), syn::Expr::Lit(lit)) = (*left, *right) {
+                if let (true, syn::Lit::Str(method)) = (path.path.is_ident(DISPLAY), lit.lit) {
+                    return Some(format_ident!("{}", method.value()));
+                }
+            }
+        }
+    }
+    None
+}
 
 /// add `from_str` and `fmt` method for deriving enum
 pub fn add_impl_items(ast: syn::DeriveInput) -> proc_macro2::TokenStream {
@@ -15,8 +35,12 @@ pub fn add_impl_items(ast: syn::DeriveInput) -> proc_macro2::TokenStream {
         data,
         attrs,
         ident,
-        vis,
+        ..
     } = ast;
+    let display_fallback = match display_method(&attrs) {
+        Some(method) => quote!(self.#method(f)),
+        None => quote!(Err(std::fmt::Error {})),
+    };
 
     match data {
         syn::Data::Enum(DataEnum { variants, .. }) => {
@@ -77,7 +101,7 @@ pub fn add_impl_items(ast: syn::DeriveInput) -> proc_macro2::TokenStream {
             fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
                 match self {
                     #( Self::#enums => write!(f, "{}", #strs), )*
-                    _ => Err(std::fmt::Error {})
+                    _ => #display_fallback
                 }
             }
         }
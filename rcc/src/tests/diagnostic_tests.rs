@@ -0,0 +1,87 @@
+use crate::diagnostic::{Diagnostic, Renderer, SourceMap, Span};
+
+fn source_map() -> SourceMap {
+    let mut source_map = SourceMap::new();
+    source_map.add_file("a.rc", "fn foo() {}");
+    source_map.add_file("b.rc", "fn main() {\n    let a = 1;\n    a = 2;\n}");
+    source_map
+}
+
+#[test]
+fn lookup_test() {
+    let source_map = source_map();
+    assert_eq!(("a.rc", 1, 4), source_map.lookup(3));
+    // "a = 2;" in b.rc
+    assert_eq!(("b.rc", 3, 5), source_map.lookup(43));
+}
+
+#[test]
+fn render_test() {
+    let source_map = source_map();
+    let diagnostic = Diagnostic::error("cannot assign twice to immutable variable `a`".into())
+        .primary(Span::new(43, 48), "cannot assign twice")
+        .secondary(Span::new(32, 33), "first assignment");
+    assert_eq!(
+        "\
+error: cannot assign twice to immutable variable `a`
+ --> b.rc:3:5
+  |
+2 |     let a = 1;
+  |         - first assignment
+3 |     a = 2;
+  |     ^^^^^ cannot assign twice
+",
+        Renderer::new(&source_map, false).render(&diagnostic)
+    );
+
+    let diagnostic = Diagnostic::error("the name `foo` is defined multiple times".into())
+        .primary(Span::new(3, 6), "");
+    assert_eq!(
+        "\
+\x1b[1;31merror\x1b[0m\x1b[1m: the name `foo` is defined multiple times\x1b[0m
+ \x1b[1;34m-->\x1b[0m a.rc:1:4
+\x1b[1;34m  |\x1b[0m
+\x1b[1;34m1 |\x1b[0m fn foo() {}
+\x1b[1;34m  |\x1b[0m    \x1b[1;31m^^^\x1b[0m
+",
+        Renderer::new(&source_map, true).render(&diagnostic)
+    );
+
+    // no span
+    assert_eq!(
+        "error: `main` function not found\n",
+        Renderer::new(&source_map, false).render(&Diagnostic::error("`main` function not found".into()))
+    );
+}
+
+#[test]
+fn render_code_and_notes_test() {
+    let source_map = source_map();
+    let diagnostic = Diagnostic::error("lhs is not mutable".into())
+        .code("E0384")
+        .primary(Span::new(43, 48), "")
+        .note("a variable is mutable only if it is declared by `let mut`");
+    assert_eq!(
+        "\
+error[E0384]: lhs is not mutable
+ --> b.rc:3:5
+  |
+3 |     a = 2;
+  |     ^^^^^
+  |
+  = note: a variable is mutable only if it is declared by `let mut`
+",
+        Renderer::new(&source_map, false).render(&diagnostic)
+    );
+
+    // no span
+    let diagnostic = Diagnostic::error("identifier `b` not found".into())
+        .code("E0425")
+        .note("b is not declared")
+        .help("did you mean `a`?");
+    assert_eq!(
+        "error[E0425]: identifier `b` not found\n = note: b is not declared\n \
+         = help: did you mean `a`?\n",
+        Renderer::new(&source_map, false).render(&diagnostic)
+    );
+}
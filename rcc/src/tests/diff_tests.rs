@@ -0,0 +1,238 @@
+//! Differential tests: random programs of the subset rcc supports are run by the IR
+//! interpreter and compiled by rustc, and what they print must be the same. The functions
+//! mix `if` statements and expressions, `while` and `loop` with `break`, and integer
+//! arithmetic which never overflows.
+//!
+//! `$RCC_DIFF_FUNCTIONS` (default 50) is the number of functions in the program, and
+//! `$RCC_DIFF_SEED` changes them. rustc is `$RUSTC` or `rustc`, the test is skipped if it can
+//! not be found.
+use crate::ir::interp::Interpreter;
+use crate::ir::tests::ir_build_checked;
+use crate::tests::{env_or, Rng};
+use std::io::ErrorKind;
+use std::process::Command;
+
+/// Variables are kept in `-BOUND..BOUND` so that expressions never overflow
+const BOUND: i32 = 1000;
+const VARS: usize = 4;
+const MAX_DEPTH: usize = 3;
+
+struct ProgramGen {
+    rng: Rng,
+    /// number of the loop counters
+    counters: usize,
+}
+
+impl ProgramGen {
+    fn var(&mut self) -> String {
+        format!("v{}", self.rng.below(VARS))
+    }
+
+    fn lit(&mut self) -> String {
+        (self.rng.below(200) as i32 - 100).to_string()
+    }
+
+    /// Terms of `+` and `-` are at most `BOUND * BOUND`
+    fn expr(&mut self, depth: usize) -> String {
+        let leaf = |gen: &mut Self| {
+            if gen.rng.below(3) == 0 {
+                gen.lit()
+            } else {
+                gen.var()
+            }
+        };
+        if depth == 0 || self.rng.below(3) == 0 {
+            return leaf(self);
+        }
+        match self.rng.below(5) {
+            0 => format!("({} * {})", leaf(self), leaf(self)),
+            1 => format!("({} / {})", self.expr(depth - 1), 1 + self.rng.below(9)),
+            2 => format!("({} % {})", self.expr(depth - 1), 1 + self.rng.below(9)),
+            3 => format!("({} - {})", self.expr(depth - 1), self.expr(depth - 1)),
+            _ => format!("({} + {})", self.expr(depth - 1), self.expr(depth - 1)),
+        }
+    }
+
+    fn bounded_expr(&mut self) -> String {
+        format!("{} % {}", self.expr(2), BOUND)
+    }
+
+    fn cond(&mut self) -> String {
+        let cmp = ["<", "<=", ">", ">=", "==", "!="][self.rng.below(6)];
+        format!("{} {} {}", self.expr(1), cmp, self.expr(1))
+    }
+
+    fn block(&mut self, depth: usize, indent: usize, out: &mut String) {
+        for _ in 0..1 + self.rng.below(3) {
+            self.stmt(depth, indent, out);
+        }
+    }
+
+    fn stmt(&mut self, depth: usize, indent: usize, out: &mut String) {
+        let pad = "    ".repeat(indent);
+        let kind = if depth == 0 { self.rng.below(2) } else { self.rng.below(6) };
+        match kind {
+            0 => {
+                let (var, e) = (self.var(), self.bounded_expr());
+                out.push_str(&format!("{}{} = {};\n", pad, var, e));
+            }
+            1 => out.push_str(&format!("{}println!(\"{{}}\", {});\n", pad, self.var())),
+            2 => {
+                out.push_str(&format!("{}if {} {{\n", pad, self.cond()));
+                self.block(depth - 1, indent + 1, out);
+                if self.rng.below(2) == 0 {
+                    out.push_str(&format!("{}}} else {{\n", pad));
+                    self.block(depth - 1, indent + 1, out);
+                }
+                out.push_str(&format!("{}}}\n", pad));
+            }
+            3 => {
+                let (var, cond) = (self.var(), self.cond());
+                let (then, els) = (self.bounded_expr(), self.bounded_expr());
+                out.push_str(&format!(
+                    "{}{} = if {} {{ {} }} else {{ {} }};\n",
+                    pad, var, cond, then, els
+                ));
+            }
+            4 => {
+                let i = self.counter();
+                let n = 1 + self.rng.below(5);
+                out.push_str(&format!("{}let mut {} = 0;\n", pad, i));
+                out.push_str(&format!("{}while {} < {} {{\n", pad, i, n));
+                out.push_str(&format!("{}    {} += 1;\n", pad, i));
+                self.block(depth - 1, indent + 1, out);
+                if self.rng.below(2) == 0 {
+                    let cond = self.cond();
+                    out.push_str(&format!("{}    if {} {{\n{}        break;\n", pad, cond, pad));
+                    out.push_str(&format!("{}    }}\n", pad));
+                }
+                out.push_str(&format!("{}}}\n", pad));
+            }
+            _ => {
+                let i = self.counter();
+                let n = 1 + self.rng.below(5);
+                let var = self.var();
+                out.push_str(&format!("{}let mut {} = 0;\n", pad, i));
+                out.push_str(&format!("{}{} = loop {{\n", pad, var));
+                out.push_str(&format!("{}    {} += 1;\n", pad, i));
+                self.block(depth - 1, indent + 1, out);
+                // the loop may also end early
+                let (cond, e) = (self.cond(), self.bounded_expr());
+                out.push_str(&format!("{}    if {} {{\n", pad, cond));
+                out.push_str(&format!("{}        break {};\n{}    }}\n", pad, e, pad));
+                let e = self.bounded_expr();
+                out.push_str(&format!("{}    if {} >= {} {{\n", pad, i, n));
+                out.push_str(&format!("{}        break {};\n{}    }}\n", pad, e, pad));
+                out.push_str(&format!("{}}};\n", pad));
+            }
+        }
+    }
+
+    fn counter(&mut self) -> String {
+        self.counters += 1;
+        format!("i{}", self.counters)
+    }
+
+    fn function(&mut self, name: &str) -> String {
+        let mut out = format!("fn {}() {{\n", name);
+        for v in 0..VARS {
+            out.push_str(&format!("    let mut v{} = {};\n", v, self.lit()));
+        }
+        for _ in 0..2 + self.rng.below(3) {
+            self.stmt(MAX_DEPTH, 1, &mut out);
+        }
+        for v in 0..VARS {
+            out.push_str(&format!("    println!(\"{{}}\", v{});\n", v));
+        }
+        out.push_str("}\n");
+        out
+    }
+}
+
+/// Output of the program compiled by rustc, `None` if rustc is not found
+fn run_rustc(program: &str, dir: &std::path::Path) -> Option<String> {
+    let (src, exe) = (dir.join("diff.rs"), dir.join("diff"));
+    std::fs::write(&src, program).unwrap();
+    let rustc = std::env::var("RUSTC").unwrap_or_else(|_| "rustc".to_string());
+    let compiled = match Command::new(rustc)
+        .args(["--edition", "2018", "-A", "warnings", "-C", "overflow-checks=on", "-o"])
+        .arg(&exe)
+        .arg(&src)
+        .output()
+    {
+        Ok(output) => output,
+        Err(e) if e.kind() == ErrorKind::NotFound => return None,
+        Err(e) => panic!("failed to run rustc: {}", e),
+    };
+    assert!(
+        compiled.status.success(),
+        "rustc failed:\n{}",
+        String::from_utf8_lossy(&compiled.stderr)
+    );
+    let output = Command::new(&exe).output().unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    Some(String::from_utf8(output.stdout).unwrap())
+}
+
+/// Lines printed by each function, after the line of its name
+fn split_by_function(output: &str, count: usize) -> Vec<&str> {
+    let mut outputs = vec![];
+    let mut rest = output;
+    for i in 0..count {
+        let marker = format!("f{}\n", i);
+        rest = rest.strip_prefix(marker.as_str()).unwrap_or(rest);
+        let end = rest.find(&format!("\nf{}\n", i + 1)).map_or(rest.len(), |end| end + 1);
+        outputs.push(&rest[..end]);
+        rest = &rest[end..];
+    }
+    outputs
+}
+
+#[test]
+fn diff_test() {
+    let count = env_or("RCC_DIFF_FUNCTIONS", 50) as usize;
+    let mut gen = ProgramGen {
+        rng: Rng::new(env_or("RCC_DIFF_SEED", 0xd1ff)),
+        counters: 0,
+    };
+    let functions: Vec<String> = (0..count).map(|i| gen.function(&format!("f{}", i))).collect();
+    let mut program = functions.concat();
+    program.push_str("\npub fn main() {\n");
+    for i in 0..count {
+        program.push_str(&format!("    println!(\"f{}\");\n    f{}();\n", i, i));
+    }
+    program.push_str("}\n");
+
+    let dir = std::env::temp_dir().join(format!("rcc-diff-tests-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let expected = run_rustc(&program, &dir);
+    std::fs::remove_dir_all(&dir).unwrap();
+    let expected = match expected {
+        Some(expected) => expected,
+        None => {
+            eprintln!("rustc is not found, skipping the differential tests");
+            return;
+        }
+    };
+
+    let ir = ir_build_checked(&program).unwrap();
+    let mut interpreter = Interpreter::new(&ir);
+    interpreter.set_max_steps(count * 100_000);
+    if let Err(e) = interpreter.run() {
+        panic!("{}\n{}", e, program);
+    }
+    let actual = String::from_utf8(interpreter.output).unwrap();
+
+    let mut report = String::new();
+    let expected = split_by_function(&expected, count);
+    let actual = split_by_function(&actual, count);
+    for (i, function) in functions.iter().enumerate() {
+        if expected[i] != actual[i] {
+            report.push_str(&format!(
+                "{}rustc:\n{}rcc:\n{}\n",
+                function, expected[i], actual[i]
+            ));
+        }
+    }
+    assert!(report.is_empty(), "differential test mismatches:\n{}", report);
+}
@@ -0,0 +1,104 @@
+//! Runs the `// EXITCODE:` fixtures of the golden tests as RISC-V executables, built by
+//! `--emit obj --start` and a linker, under an emulator. Enabled by the `exec-tests` feature:
+//!
+//! ```text
+//! cargo test --features exec-tests exec_test
+//! ```
+//!
+//! The linker is `$RCC_TEST_LD` (default `ld.lld`) and the emulator is `$RCC_TEST_RUNNER`
+//! (default `qemu-riscv32`, e.g. `spike pk` also works). The test is skipped if either of them
+//! can not be found.
+use crate::code_gen::TargetPlatform;
+use crate::rcc::{EmitKind, OptimizeLevel, RcCompiler, RccError};
+use crate::tests::golden_tests::{check_run, diff, fixtures, parse_expectation, Expectation};
+use std::io::ErrorKind;
+use std::path::Path;
+use std::process::{Command, Output};
+
+fn tool(var: &str, default: &str) -> Vec<String> {
+    let cmd = std::env::var(var).unwrap_or_else(|_| default.to_string());
+    cmd.split_whitespace().map(str::to_string).collect()
+}
+
+/// `None` if the program is not found
+fn exec(cmd: &[String], args: &[&Path]) -> Option<Output> {
+    match Command::new(&cmd[0]).args(&cmd[1..]).args(args).output() {
+        Ok(output) => Some(output),
+        Err(e) if e.kind() == ErrorKind::NotFound => None,
+        Err(e) => panic!("failed to run `{}`: {}", cmd[0], e),
+    }
+}
+
+fn compile_obj(source: &str, opt_level: OptimizeLevel) -> Result<Vec<u8>, RccError> {
+    let mut rcc = RcCompiler::new(
+        TargetPlatform::Riscv32,
+        source.as_bytes(),
+        Vec::<u8>::new(),
+        opt_level,
+    );
+    rcc.set_emit(EmitKind::Obj);
+    rcc.set_emit_start(true);
+    rcc.set_checks(true);
+    rcc.set_overflow_checks(true);
+    rcc.compile()?;
+    Ok(rcc.output.buffer().to_vec())
+}
+
+#[test]
+fn exec_test() {
+    let (ld, runner) = (tool("RCC_TEST_LD", "ld.lld"), tool("RCC_TEST_RUNNER", "qemu-riscv32"));
+    let dir = std::env::temp_dir().join(format!("rcc-exec-tests-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+
+    let mut report = String::new();
+    let mut missing = None;
+    'fixtures: for path in fixtures().iter() {
+        let source = std::fs::read_to_string(path).unwrap();
+        let code = match parse_expectation(&source) {
+            Ok(Expectation::ExitCode(code)) => code,
+            _ => continue,
+        };
+        for opt_level in [OptimizeLevel::Zero, OptimizeLevel::One] {
+            let stem = path.file_stem().unwrap().to_string_lossy();
+            let exe = dir.join(format!("{}-O{}", stem, opt_level));
+            let obj = exe.with_extension("o");
+            std::fs::write(&obj, compile_obj(&source, opt_level).unwrap()).unwrap();
+
+            let linked = match exec(&ld, &[&obj, Path::new("-o"), &exe]) {
+                Some(output) => output,
+                None => {
+                    missing = Some(&ld[0]);
+                    break 'fixtures;
+                }
+            };
+            if !linked.status.success() {
+                let stderr = String::from_utf8_lossy(&linked.stderr);
+                report.push_str(&format!("{}:\n{}", path.display(), diff("linked", &stderr)));
+                continue;
+            }
+            let output = match exec(&runner, &[&exe]) {
+                Some(output) => output,
+                None => {
+                    missing = Some(&runner[0]);
+                    break 'fixtures;
+                }
+            };
+            let exit_code = match output.status.code() {
+                Some(exit_code) => exit_code as i64,
+                None => {
+                    report.push_str(&format!("{}: killed by a signal\n", exe.display()));
+                    continue;
+                }
+            };
+            let stdout = String::from_utf8_lossy(&output.stdout).into_owned();
+            if let Some(mismatch) = check_run(&source, code, (exit_code, stdout)) {
+                report.push_str(&format!("{}:\n{}", exe.display(), mismatch));
+            }
+        }
+    }
+    std::fs::remove_dir_all(&dir).unwrap();
+    if let Some(program) = missing {
+        eprintln!("`{}` is not found, skipping the execution tests", program);
+    }
+    assert!(report.is_empty(), "execution test mismatches:\n{}", report);
+}
@@ -0,0 +1,40 @@
+// arrays in the stack frames, returned through the address passed by the caller, copied to
+// the heap by `Box::new`, and too far from fp for the immediates of `addi`
+// EXITCODE: 12
+// STDOUT: 3 9 8
+// STDOUT: 7 10
+fn make(x: i32) -> [i32; 3] {
+    if x > 100 {
+        return [0; 3];
+    }
+    [x, x + 1, x + 2]
+}
+
+fn set(r: &mut [i32; 3]) {
+    *r = [7, 8, 9];
+    r[0] = 9;
+}
+
+fn large(n: usize) -> u8 {
+    let mut a = [0u8; 4096];
+    a[n] = 5;
+    let b = a;
+    a[4095] = 1;
+    b[n] + a[4095]
+}
+
+pub fn main() -> i32 {
+    let mut a = make(1);
+    let b = Box::new(a);
+    set(&mut a);
+    println!("{} {} {}", (*b)[2], a[0], a[1]);
+    let s: &[i32] = &a;
+    let mut c = [0; 3];
+    let mut i = 0;
+    while i < 2 {
+        c = make(i + 5);
+        i += 1;
+    }
+    println!("{} {}", c[0] + s.len() as i32 - 2, make(200)[0] + 10);
+    (large(4095) + large(3)) as i32
+}
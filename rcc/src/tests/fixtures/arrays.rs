@@ -0,0 +1,55 @@
+// EXITCODE: 4
+// STDOUT: 10
+// STDOUT: 19
+// STDOUT: 7
+// STDOUT: 5
+// STDOUT: 200
+const N: usize = 2;
+
+struct Rgb {}
+
+impl Rgb {
+    const LEN: usize = size(1) + 1;
+}
+
+const fn size(n: usize) -> usize {
+    n * N
+}
+
+fn sum(a: [i32; size(N)]) -> i32 {
+    let mut s = 0;
+    let mut i = 0;
+    while i < 4 {
+        s += a[i];
+        i += 1;
+    }
+    s
+}
+
+fn fill(a: &mut [i32; 4], v: i32) {
+    let mut i = 0;
+    while i < 4 {
+        a[i] = v;
+        i += 1;
+    }
+}
+
+fn first(a: &[u8; Rgb::LEN]) -> u8 {
+    a[0]
+}
+
+pub fn main() -> i32 {
+    let a: [i32; 4] = [1, 2, 3, 4];
+    let mut b = a;
+    b[0] = 10;
+    println!("{}", sum(a));
+    println!("{}", sum(b));
+    fill(&mut b, 7);
+    println!("{}", b[3]);
+    let c = [5u8; 3];
+    println!("{}", first(&c));
+    let d: [u8; 3] = [200, 1, 2];
+    b = [0; 4];
+    println!("{}", d[0]);
+    b[1] + sum([1; 4])
+}
@@ -0,0 +1,19 @@
+// a failed assertion panics with its line, also without runtime checks
+// EXITCODE: 101
+// STDOUT: 6
+fn gcd(a: u32, b: u32) -> u32 {
+    if b == 0 {
+        a
+    } else {
+        gcd(b, a % b)
+    }
+}
+
+fn main() -> i32 {
+    assert!(gcd(12, 18) == 6 && gcd(7, 5) == 1);
+    assert_eq!(gcd(0, 9), 9, "gcd(0, n) is n");
+    assert_ne!(gcd(4, 8), 8);
+    println!("{}", gcd(12, 18));
+    assert_eq!(gcd(10, 4), 4);
+    0
+}
@@ -0,0 +1,6 @@
+// ERROR[E0384]: cannot assign twice to immutable variable `a`
+pub fn main() -> i32 {
+    let a = 1;
+    a = 2;
+    a
+}
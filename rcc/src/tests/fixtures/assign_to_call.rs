@@ -0,0 +1,9 @@
+// ERROR[E0070]: cannot assign to the result of this function call
+fn one() -> i32 {
+    1
+}
+
+pub fn main() -> i32 {
+    one() = 2;
+    0
+}
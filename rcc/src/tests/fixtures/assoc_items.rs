@@ -0,0 +1,33 @@
+// constants and functions of an impl are used by their paths, `Self` is the struct
+// EXITCODE: 39
+// STDOUT: 30
+struct Point {
+    x: i32,
+    y: i32,
+}
+
+impl Point {
+    const ORIGIN_X: i32 = 0;
+    const MAX: i32 = Self::SCALE * 10 + Point::ORIGIN_X;
+    const SCALE: i32 = 3;
+
+    fn clamp(self) -> i32 {
+        let p: Self = Self { x: self.x, ..self };
+        if p.x + p.y > Self::MAX {
+            Self::MAX
+        } else {
+            p.x + p.y
+        }
+    }
+
+    fn scaled(p: Point) -> i32 {
+        Point::clamp(p) * Self::SCALE
+    }
+}
+
+fn main() -> i32 {
+    let p = Point { x: 20, y: 15 };
+    let q = Point { x: 1, y: 2 };
+    println!("{}", Point::MAX);
+    p.clamp() + Point::scaled(q)
+}
@@ -0,0 +1,8 @@
+// ERROR[E0571]: `break` with value from a `while` loop
+fn main() {
+    let mut a = 0;
+    while a < 3 {
+        a += 1;
+        break a;
+    }
+}
@@ -0,0 +1,9 @@
+// ERROR[E0308]: invalid type for break expr
+fn main() {
+    let a = loop {
+        if true {
+            break 1;
+        }
+        break 'c';
+    };
+}
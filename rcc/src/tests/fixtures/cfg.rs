@@ -0,0 +1,29 @@
+// EXITCODE: 12
+// STDOUT: small
+#[cfg(feature = "big")]
+const SCALE: i32 = 100;
+#[cfg(not(feature = "big"))]
+const SCALE: i32 = 3;
+
+#[cfg(feature = "big")]
+fn report() {
+    println!("big");
+}
+#[cfg(not(feature = "big"))]
+fn report() {
+    println!("small");
+}
+
+fn main() -> i32 {
+    // not compiled, so it may refer to anything
+    #[cfg(any(feature = "big", all(test, not(test))))]
+    fn extra() -> i32 {
+        undefined()
+    }
+    #[cfg(not(any(feature = "big", all(test, not(test)))))]
+    fn extra() -> i32 {
+        4
+    }
+    report();
+    extra() * SCALE
+}
@@ -0,0 +1,50 @@
+// comparisons used as values, like the body of `eq`, are computed with `slt`, `sltu` and
+// `xor`
+// EXITCODE: 0
+// STDOUT: 1 0
+// STDOUT: 1 1 0 1 0
+// STDOUT: 1 0 1
+trait PartialEq {
+    fn eq(&self, other: &Self) -> bool;
+}
+
+struct P {
+    x: i32,
+}
+
+impl PartialEq for P {
+    fn eq(&self, o: &P) -> bool {
+        self.x == o.x
+    }
+}
+
+fn lt(a: i32, b: i32) -> bool {
+    a < b
+}
+
+fn ltu(a: u32, b: u32) -> bool {
+    a < b
+}
+
+fn to_i32(b: bool) -> i32 {
+    if b { 1 } else { 0 }
+}
+
+fn main() -> i32 {
+    let p = P { x: 1 };
+    let q = P { x: 1 };
+    let r = P { x: 2 };
+    let eq = p == q;
+    let ne = p == r;
+    println!("{} {}", to_i32(eq), to_i32(ne));
+    let a = -1;
+    let b = 2;
+    let le = a <= b;
+    let ge = b >= a;
+    let gt = a > b;
+    let not_eq = a != b;
+    let eq_ = a == b;
+    println!("{} {} {} {} {}", to_i32(le), to_i32(ge), to_i32(gt), to_i32(not_eq), to_i32(eq_));
+    println!("{} {} {}", to_i32(lt(-1, 2)), to_i32(ltu(4294967295, 2)), to_i32(ltu(1, 2)));
+    0
+}
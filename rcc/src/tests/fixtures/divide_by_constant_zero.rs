@@ -0,0 +1,6 @@
+// a division by the constant zero always panics, so it is rejected at compile time
+// ERROR: this operation will panic at runtime
+fn main() -> i32 {
+    let a = 7;
+    a / 0
+}
@@ -0,0 +1,9 @@
+// a panic exits with 101
+// EXITCODE: 101
+fn div(a: i32, b: i32) -> i32 {
+    a / b
+}
+
+pub fn main() -> i32 {
+    div(1, 0)
+}
@@ -0,0 +1,16 @@
+// ERROR[E0428]: the name `area` is defined multiple times
+struct Rect {
+    w: i32,
+    h: i32,
+}
+
+fn area(r: &Rect) -> i32 {
+    r.w * r.h
+}
+
+const area: i32 = 0;
+
+fn main() -> i32 {
+    let r = Rect { w: 2, h: 3 };
+    area(&r)
+}
@@ -0,0 +1,46 @@
+// the implementors of `Show` dispatch through the same slots of their vtables
+// EXITCODE: 40
+// STDOUT: 3
+// STDOUT: 12
+trait Show {
+    fn show(&self) -> i32;
+    fn twice(&self) -> i32;
+}
+
+impl Show for i32 {
+    fn show(&self) -> i32 { *self }
+    fn twice(&self) -> i32 { *self * 2 }
+}
+
+struct Meters {
+    m: i32,
+}
+
+struct Rect {
+    w: i32,
+    h: i32,
+}
+
+impl Show for Meters {
+    fn show(&self) -> i32 { self.m }
+    fn twice(&self) -> i32 { self.show() + self.m }
+}
+
+impl Show for Rect {
+    fn show(&self) -> i32 { self.w * self.h }
+    fn twice(&self) -> i32 { self.show() * 2 }
+}
+
+fn call(s: &dyn Show) -> i32 {
+    s.twice()
+}
+
+pub fn main() -> i32 {
+    let n = 5;
+    let m = Meters { m: 3 };
+    let r = Rect { w: 2, h: 3 };
+    let d = &m as &dyn Show;
+    println!("{}", d.show());
+    println!("{}", call(&r));
+    call(&n) + call(d) + (&r as &dyn Show).show() + call(&Rect { w: 3, h: 3 })
+}
@@ -0,0 +1,5 @@
+// the exit status of a process is the low byte of the value returned by `main`
+// EXITCODE: 44
+pub fn main() -> i32 {
+    300
+}
@@ -0,0 +1,12 @@
+// EXITCODE: 55
+fn fib(n: i32) -> i32 {
+    if n < 2 {
+        n
+    } else {
+        fib(n - 1) + fib(n - 2)
+    }
+}
+
+pub fn main() -> i32 {
+    fib(10)
+}
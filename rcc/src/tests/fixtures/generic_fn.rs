@@ -0,0 +1,16 @@
+// EXITCODE: 3
+trait Add {
+    fn add(self, rhs: Self) -> Self;
+}
+
+impl Add for i32 {
+    fn add(self, rhs: Self) -> Self { self + rhs }
+}
+
+fn sum<T: Add>(a: T, b: T) -> T {
+    a.add(b)
+}
+
+pub fn main() -> i32 {
+    sum(1, 2)
+}
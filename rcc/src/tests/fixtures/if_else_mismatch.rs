@@ -0,0 +1,9 @@
+// ERROR[E0308]: `if` and `else` have incompatible types
+fn main() {
+    let a = 1;
+    let b = if a > 0 {
+        1
+    } else {
+        'c'
+    };
+}
@@ -0,0 +1,7 @@
+// ERROR[E0317]: `if` may be missing an `else` clause
+fn main() -> i32 {
+    let a = 1;
+    if a > 0 {
+        2
+    }
+}
@@ -0,0 +1,6 @@
+// ERROR[E0277]: the type `str` cannot be indexed by `usize`
+fn main() -> i32 {
+    let s = "abc";
+    let b = s[0];
+    0
+}
@@ -0,0 +1,13 @@
+// EXITCODE: 42
+// STDOUT: 44 -2 254 254 255
+fn main() -> i32 {
+    let a: i32 = 300;
+    let b = a as u8;
+    let c: i8 = -2;
+    let d = c as i32;
+    let e = c as u8;
+    let f = e as u16;
+    let g = -1i32 as u8;
+    println!("{} {} {} {} {}", b, d, e, f, g);
+    b as i32 + d
+}
@@ -0,0 +1,41 @@
+// EXITCODE: 23
+fn three() -> i32 {
+    loop {
+        break 3;
+    }
+}
+
+fn double_until(n: i32) -> i32 {
+    let mut i = 0;
+    loop {
+        i += 1;
+        // `break` of the inner `while` leaves it only
+        while i < n {
+            break;
+        }
+        if i == n {
+            break i * 2;
+        }
+    }
+}
+
+fn main() -> i32 {
+    let unit = loop {
+        break;
+    };
+    let a: u8 = loop {
+        break 4;
+    };
+    let mut b = 0;
+    b = loop {
+        let c = loop {
+            break three();
+        };
+        break c + 6;
+    };
+    if a == 4 {
+        b + double_until(7)
+    } else {
+        0
+    }
+}
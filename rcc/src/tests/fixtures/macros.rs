@@ -0,0 +1,41 @@
+// EXITCODE: 44
+// STDOUT: 3
+// STDOUT: 9
+macro_rules! square {
+    ($x:expr) => {
+        $x * $x
+    };
+}
+
+macro_rules! max {
+    ($a:expr, $b:expr) => {
+        if $a > $b { $a } else { $b }
+    };
+    ($a:expr) => {
+        $a
+    };
+}
+
+macro_rules! constant {
+    ($name:ident: $t:ty = $v:literal) => {
+        fn $name() -> $t {
+            $v
+        }
+    };
+}
+
+constant!(seven: i32 = 7);
+
+macro_rules! show {
+    ($e:expr) => {
+        println!("{}", $e)
+    };
+}
+
+fn main() -> i32 {
+    let b = 2;
+    show!(b + 1);
+    show!(square!(b + 1));
+    // 9 + 7 + 28
+    square!(b + 1) + max!(seven(), b) + max!(b * 14)
+}
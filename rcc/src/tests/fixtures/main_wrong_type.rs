@@ -0,0 +1,4 @@
+// ERROR[E0580]: `main` function has wrong type: expected `fn()`, found `fn(i32)`
+fn main(argc: i32) -> i32 {
+    argc
+}
@@ -0,0 +1,91 @@
+// methods take `self`, `&self` or `&mut self`, and the receivers are adjusted to match
+// EXITCODE: 42
+// STDOUT: 3
+// STDOUT: 7
+// STDOUT: 34
+// STDOUT: 9
+// STDOUT: 23
+trait Counter {
+    fn bump(&mut self);
+    fn get(&self) -> i32;
+    fn take(self) -> i32;
+}
+
+impl Counter for i32 {
+    fn bump(&mut self) {
+        *self += 1;
+    }
+
+    fn get(&self) -> i32 {
+        *self
+    }
+
+    fn take(self) -> i32 {
+        self * 2
+    }
+}
+
+struct Point {
+    x: i32,
+    y: i32,
+}
+
+impl Point {
+    fn sum(self) -> i32 {
+        self.x + self.y
+    }
+
+    fn scale(p: Point, k: i32) -> i32 {
+        p.sum() * k
+    }
+
+    fn dot(&self, other: &Point) -> i32 {
+        self.x * other.x + self.y * other.y
+    }
+
+    fn shift(&mut self, d: i32) {
+        self.x += d;
+        self.y = self.y + d;
+    }
+}
+
+struct Line {
+    from: Point,
+    to: Point,
+}
+
+impl Line {
+    fn len2(&self) -> i32 {
+        let d = Point { x: self.to.x - self.from.x, y: self.to.y - self.from.y };
+        d.dot(&d)
+    }
+
+    fn flip(&mut self) {
+        let from = Point { ..self.from };
+        self.from = Point { ..self.to };
+        self.to = from;
+        self.from.shift(0);
+    }
+}
+
+fn main() -> i32 {
+    let mut n: i32 = 1;
+    n.bump();
+    let r = &mut n;
+    r.bump();
+    println!("{}", r.get());
+    let p = Point { x: 3, y: 4 };
+    println!("{}", p.sum());
+    let mut q = Point { x: 1, y: 2 };
+    q.shift(1);
+    let r = &mut q;
+    r.shift(1);
+    r.x = r.x * 2;
+    let s = r.sum();
+    println!("{}", p.dot(&q));
+    let mut line = Line { from: Point { ..p }, to: Point { ..q } };
+    line.flip();
+    println!("{}", line.len2());
+    println!("{}", line.from.dot(&Point { x: 1, y: 0 }) + (&line).to.sum() + s);
+    n.take() + (&n).take() + Point::scale(p, 3) + 9
+}
@@ -0,0 +1,5 @@
+// ERROR[E0308]: invalid type in let stmt: expected `Bool`, found `LitNum(#i)`
+pub fn main() -> i32 {
+    let a: bool = 1;
+    0
+}
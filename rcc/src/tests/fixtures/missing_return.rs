@@ -0,0 +1,12 @@
+// ERROR[E0308]: missing return value in function `sign`
+fn sign(a: i32) -> i32 {
+    if a > 0 {
+        return 1;
+    } else if a < 0 {
+        return -1;
+    }
+}
+
+fn main() -> i32 {
+    sign(3)
+}
@@ -0,0 +1,15 @@
+// ERROR[E0308]: mismatched types: expected `u8`, found `u32`
+fn checksum(data: &[u8]) -> u32 {
+    let mut sum: u32 = 0;
+    let mut i = 0;
+    while i < data.len() {
+        sum = data[i] + sum;
+        i += 1;
+    }
+    sum
+}
+
+fn main() -> i32 {
+    checksum(&[1, 2]);
+    0
+}
@@ -0,0 +1,55 @@
+// items may be used before they are declared, and functions may call each other
+// EXITCODE: 14
+// STDOUT: 1
+// STDOUT: 0
+fn main() -> i32 {
+    let p = Point { x: N, y: M };
+    println!("{}", is_even(LIMIT));
+    println!("{}", is_odd(LIMIT));
+    twice(p.x) + countdown(p.y - 1)
+}
+
+fn is_even(n: i32) -> i32 {
+    if n == 0 {
+        1
+    } else {
+        is_odd(n - 1)
+    }
+}
+
+fn is_odd(n: i32) -> i32 {
+    if n == 0 {
+        0
+    } else {
+        is_even(n - 1)
+    }
+}
+
+fn countdown(n: i32) -> i32 {
+    if n <= 0 {
+        0
+    } else {
+        step(n - 1) + 1
+    }
+}
+
+fn step(n: i32) -> i32 {
+    if n <= 0 {
+        0
+    } else {
+        countdown(n - 1) + 2
+    }
+}
+
+fn twice(x: i32) -> i32 {
+    x * 2
+}
+
+const LIMIT: i32 = N * 2;
+const N: i32 = M + 1;
+const M: i32 = 4;
+
+struct Point {
+    x: i32,
+    y: i32,
+}
@@ -0,0 +1,8 @@
+// EXITCODE: 0
+// STDOUT: -128 -2147483648
+fn main() -> i32 {
+    let a = -128i8;
+    let b: i32 = -2147483648;
+    println!("{} {}", a, b);
+    if b == -2147483648 { 0 } else { 1 }
+}
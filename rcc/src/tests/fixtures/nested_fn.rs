@@ -0,0 +1,20 @@
+// functions declared in blocks are visible in their blocks only
+// EXITCODE: 23
+// STDOUT: 7
+fn main() -> i32 {
+    let a = 3;
+    println!("{}", inner(a));
+    fn inner(x: i32) -> i32 {
+        fn twice(y: i32) -> i32 {
+            y * 2
+        }
+        twice(x) + 1
+    }
+    let b = {
+        fn inner(x: i32) -> i32 {
+            x * 5
+        }
+        inner(a)
+    };
+    b + inner(1) * 3 - 1
+}
@@ -0,0 +1,8 @@
+// ERROR[E0434]: can't capture dynamic environment in a fn item
+fn main() -> i32 {
+    let a = 3;
+    fn inner() -> i32 {
+        a
+    }
+    inner()
+}
@@ -0,0 +1,55 @@
+// the operators of a user struct call the methods of its impls, `==` and `!=` borrow the
+// operands
+// EXITCODE: 17
+// STDOUT: 7
+// STDOUT: 1
+// STDOUT: 0
+trait Add {
+    fn add(self, rhs: Self) -> Self;
+}
+
+trait Sub {
+    fn sub(self, rhs: Self) -> i32;
+}
+
+trait PartialEq {
+    fn eq(&self, other: &Self) -> bool;
+}
+
+struct V {
+    x: i32,
+    y: i32,
+}
+
+impl Add for V {
+    fn add(self, rhs: V) -> V {
+        V { x: self.x + rhs.x, y: self.y + rhs.y }
+    }
+}
+
+impl Sub for V {
+    fn sub(self, rhs: V) -> i32 {
+        self.x - rhs.x
+    }
+}
+
+impl PartialEq for V {
+    fn eq(&self, other: &V) -> bool {
+        self.x == other.x && self.y == other.y
+    }
+}
+
+fn main() -> i32 {
+    let a = V { x: 1, y: 2 };
+    let b = V { x: 3, y: 4 };
+    let c = a + b;
+    let d = V { x: 10, y: 0 } - V { x: 3, y: 0 };
+    println!("{}", d);
+    let e = V { x: 4, y: 6 };
+    let eq: i32 = if c == e { 1 } else { 0 };
+    let ne: i32 = if c != e { 1 } else { 0 };
+    println!("{}", eq);
+    println!("{}", ne);
+    V { x: 0, y: 0 } + V { x: 1, y: 1 };
+    c.x + c.y + d
+}
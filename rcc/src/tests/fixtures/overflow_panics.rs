@@ -0,0 +1,17 @@
+// programs are checked like `-C overflow-checks=on`, an overflow panics
+// EXITCODE: 101
+// STDOUT: 479001600
+fn factorial(n: i32) -> i32 {
+    let mut f = 1;
+    let mut i = 1;
+    while i <= n {
+        f *= i;
+        i += 1;
+    }
+    f
+}
+
+fn main() -> i32 {
+    println!("{}", factorial(12));
+    factorial(13)
+}
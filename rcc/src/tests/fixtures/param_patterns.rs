@@ -0,0 +1,32 @@
+// EXITCODE: 12
+// STDOUT: 121
+// STDOUT: 121
+struct P {
+    x: i32,
+    y: i32,
+}
+
+fn f((x, y): (i32, i32), _: i32) -> i32 {
+    x * 10 + y
+}
+
+fn g(a: i32, (b, (c, _), p): (u8, (i32, bool), P), _: bool) -> i32 {
+    a + c + p.y
+}
+
+fn h(t: (i32, i32)) -> i32 {
+    f(t, 0)
+}
+
+pub fn main() -> i32 {
+    let n = f((1, 2), 3);
+    let m = g(1, (2, (3, true), P { x: 1, y: 4 }), false);
+    for _ in 0..2 {
+        println!("{}", h((n, 1)));
+    }
+    if m == 8 {
+        n
+    } else {
+        0
+    }
+}
@@ -0,0 +1,7 @@
+// EXITCODE: 0
+// STDOUT: hello
+// STDOUT: 42
+pub fn main() {
+    println!("hello");
+    println!("{}", 42);
+}
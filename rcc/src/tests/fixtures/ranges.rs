@@ -0,0 +1,29 @@
+// EXITCODE: 40
+pub fn main() -> i32 {
+    let a = [1, 2, 3, 4];
+    let mut sum = 0;
+    for i in 0..4 {
+        sum += a[i];
+    }
+    for i in 1..=3 {
+        sum += i;
+    }
+    let n: u8 = 255;
+    let mut count = 0;
+    for b in 250..=n {
+        count += 1;
+    }
+    let mut r = 2..5;
+    for mut j in r {
+        j *= 2;
+        sum += j;
+    }
+    r = 0..0;
+    for j in r {
+        sum += 100;
+    }
+    for j in 5..=4 {
+        sum += 1000;
+    }
+    sum + count
+}
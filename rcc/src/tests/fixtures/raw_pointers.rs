@@ -0,0 +1,29 @@
+// EXITCODE: 92
+// STDOUT: 20 30
+fn sum(p: *const i32, n: usize) -> i32 {
+    let mut s = 0;
+    for i in 0..n {
+        s = s + unsafe { *p.add(i) };
+    }
+    s
+}
+
+fn main() -> i32 {
+    let mut a = [1, 2, 3, 4];
+    let p = a.as_mut_ptr();
+    unsafe {
+        *p.add(1) = 20;
+        let q = p.add(3);
+        *q.sub(1) = *q + 26;
+    }
+    let r = &a;
+    let t = r.as_ptr().wrapping_add(2);
+    let mut x = 5;
+    let px = &mut x as *mut i32;
+    let addr = px as usize;
+    unsafe {
+        *(addr as *mut i32) = 7;
+    }
+    println!("{} {}", a[1], a[2]);
+    sum(a.as_ptr(), 4) + unsafe { *t } + x
+}
@@ -0,0 +1,38 @@
+// EXITCODE: 48
+// STDOUT: 8
+// STDOUT: 108
+struct P {
+    x: i32,
+    y: i32,
+}
+
+fn f(a: i32, s: &[i32]) -> i32 {
+    let a = a * 2;
+    println!("{}", a);
+    let s = s[0] + s[1];
+    let p = P { x: a, y: s };
+    let p = P { x: p.y, y: p.x };
+    let mut t = 0;
+    for i in 0..3 {
+        let i = i * 10;
+        t = t + i;
+    }
+    let a = {
+        let b = a;
+        let a = 100;
+        a + b
+    };
+    println!("{}", a);
+    a + p.x + t
+}
+
+fn main() -> i32 {
+    let v = [1, 2, 3];
+    let x = f(4, &v);
+    let x = x - 100;
+    let b = Box::new(5);
+    let b = *b + 1;
+    let big = if x > 40 { true } else { false };
+    let big = if big { 1 } else { 0 };
+    x + b + big
+}
@@ -0,0 +1,32 @@
+// EXITCODE: 6
+// STDOUT: 1
+// STDOUT: 2
+// STDOUT: 3
+// STDOUT: 4
+// STDOUT: 5
+// STDOUT: 2
+fn check(n: i32, limit: i32) -> bool {
+    println!("{}", n);
+    if n < limit {
+        true
+    } else {
+        false
+    }
+}
+
+pub fn main() -> i32 {
+    let mut i = 1;
+    while check(i, 5) && i < 10 || i == 4 {
+        i += 1;
+    }
+    // `check` is not called once the result is known
+    let mut count = 0;
+    if false && check(100, 0) || (true || check(200, 0)) {
+        count += 1;
+    }
+    let both = check(2, 0) && check(300, 0);
+    if !both {
+        count += 5;
+    }
+    count
+}
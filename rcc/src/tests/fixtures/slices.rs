@@ -0,0 +1,49 @@
+// EXITCODE: 42
+// STDOUT: 10
+// STDOUT: 3
+// STDOUT: hello
+// STDOUT: 5
+// STDOUT: 6
+// STDOUT: 2
+fn sum(s: &[i32]) -> i32 {
+    let mut total = 0;
+    let mut i = 0;
+    while i < s.len() {
+        total += s[i];
+        i += 1;
+    }
+    total
+}
+
+fn double(s: &mut [u8]) {
+    let mut i = 0;
+    while i < s.len() {
+        s[i] *= 2;
+        i += 1;
+    }
+}
+
+fn last(s: &[i32]) -> i32 {
+    s[s.len() - 1]
+}
+
+fn show(s: &str) -> usize {
+    println!("{}", s);
+    s.len()
+}
+
+pub fn main() -> i32 {
+    let a = [1, 2, 3, 4];
+    println!("{}", sum(&a));
+    let mut s: &[i32] = &a;
+    let b = [3, 0];
+    s = &b;
+    println!("{}", sum(s));
+    let mut c = [1, 2];
+    double(&mut c);
+    let t = "hello";
+    println!("{}", show(t));
+    println!("{}", c[0] + c[1]);
+    println!("{}", s.len());
+    last(&a) * 10 + last(s) + sum(&b) - 1
+}
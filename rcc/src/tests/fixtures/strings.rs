@@ -0,0 +1,43 @@
+// EXITCODE: 3
+// STDOUT: 2
+// STDOUT: 22
+// STDOUT: 122
+// STDOUT: 65
+fn count(s: &str, c: u8) -> i32 {
+    let mut n = 0;
+    for b in s.bytes() {
+        if b == c {
+            n += 1;
+        }
+    }
+    n
+}
+
+fn first(s: &str) -> u8 {
+    let bytes = s.as_bytes();
+    bytes[0]
+}
+
+pub fn main() -> i32 {
+    let s = "hello world";
+    let mut vowels = 0;
+    let mut i = 0;
+    while i < s.len() {
+        if s.as_bytes()[i] == 111 {
+            vowels += 1;
+        }
+        i += 1;
+    }
+    println!("{}", vowels);
+    let mut sum = 0;
+    for b in "abc".bytes() {
+        if b == 99 {
+            break;
+        }
+        sum += 10;
+    }
+    println!("{}", sum + vowels);
+    println!("{}", "xyz".as_bytes()[2]);
+    println!("{}", first("A"));
+    count(s, 108)
+}
@@ -0,0 +1,35 @@
+// constants of struct types, whose fields are evaluated at compile time
+// EXITCODE: 17
+// STDOUT: 3 4 10 -2
+struct Point {
+    x: i32,
+    y: i32,
+}
+
+struct Rect {
+    min: Point,
+    max: Point,
+}
+
+impl Point {
+    const ORIGIN: Point = Point { x: 0, y: 0 };
+    const UNIT: Self = Self { x: 1, ..Self::ORIGIN };
+
+    fn len(self) -> i32 {
+        self.x + self.y
+    }
+}
+
+const fn point(x: i32, y: i32) -> Point {
+    Point { x, y }
+}
+
+const P: Point = point(3, 4);
+const BOX: Rect = Rect { min: Point { x: -2, y: 0 }, max: P };
+
+fn main() -> i32 {
+    let o = Point::ORIGIN;
+    let r = BOX;
+    println!("{} {} {} {}", P.x, P.y, BOX.max.x + BOX.max.y + Point::UNIT.x * 3, r.min.x);
+    o.len() + Point::UNIT.len() + P.len() + r.max.x + r.min.y + Point::ORIGIN.y + 6
+}
@@ -0,0 +1,53 @@
+// a struct is returned through the address passed before the arguments
+// EXITCODE: 12
+// STDOUT: 13
+struct P {
+    x: i32,
+    y: i32,
+}
+
+struct R {
+    a: P,
+    b: P,
+    c: i32,
+}
+
+trait Make {
+    fn make(&self, k: i32) -> P;
+}
+
+impl Make for P {
+    fn make(&self, k: i32) -> P {
+        P { x: self.x * k, y: self.y * k }
+    }
+}
+
+fn mk(x: i32, y: i32) -> P {
+    P { x, y }
+}
+
+fn early(x: i32) -> P {
+    if x > 0 {
+        return P { x, y: 0 };
+    }
+    let p = mk(x, 1);
+    p
+}
+
+fn big(n: i32) -> R {
+    R { a: mk(n, n), b: early(n), c: 7 }
+}
+
+fn call(m: &dyn Make) -> i32 {
+    let p = m.make(3);
+    p.x + p.y
+}
+
+fn main() -> i32 {
+    let p = mk(1, 2);
+    mk(5, 5);
+    let r = big(-3);
+    let q = early(4);
+    println!("{}", call(&p) + p.make(2).y);
+    p.x + p.y + r.a.x + r.b.y + q.x + r.c
+}
@@ -0,0 +1,8 @@
+// ERROR[E0308]: found a tuple with 2 elements
+fn f((a, b): i32) -> i32 {
+    a + b
+}
+
+pub fn main() -> i32 {
+    f(1)
+}
@@ -0,0 +1,35 @@
+// EXITCODE: 30
+// STDOUT: 7
+// STDOUT: 12
+type Meters = i32;
+type Pos = Point;
+
+struct Point {
+    x: Meters,
+    y: Meters,
+}
+
+impl Pos {
+    fn sum(self) -> Meters {
+        self.x + self.y
+    }
+}
+
+fn bump(c: &mut Meters, by: Meters) {
+    *c += by;
+}
+
+fn area(p: Pos) -> Meters {
+    type Side = Meters;
+    let w: Side = p.x;
+    w * p.y
+}
+
+pub fn main() -> Meters {
+    let p = Pos { x: 3, y: 4 };
+    println!("{}", p.sum());
+    let mut total: Meters = area(Point { x: 2, y: 6 });
+    println!("{}", total);
+    bump(&mut total, 18);
+    total
+}
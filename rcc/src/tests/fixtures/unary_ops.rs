@@ -0,0 +1,16 @@
+// EXITCODE: 9
+// STDOUT: -5
+// STDOUT: -6
+fn neg(a: i32) -> i32 {
+    -a
+}
+
+fn not(a: i32) -> i32 {
+    !a
+}
+
+fn main() -> i32 {
+    println!("{}", neg(5));
+    println!("{}", not(5));
+    not(neg(10))
+}
@@ -0,0 +1,5 @@
+// ERROR[E0425]: identifier `b` not found
+pub fn main() -> i32 {
+    let a = 1;
+    a + b
+}
@@ -0,0 +1,8 @@
+// ERROR[E0133]: dereference of raw pointer is unsafe and requires unsafe function or block
+fn read(p: *const i32) -> i32 {
+    *p
+}
+
+pub fn main() -> i32 {
+    0
+}
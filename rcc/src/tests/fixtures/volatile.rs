@@ -0,0 +1,21 @@
+// EXITCODE: 51
+// STDOUT: 3 41
+fn poll(status: *const u8) -> u8 {
+    unsafe {
+        read_volatile(status);
+        core::ptr::read_volatile(status)
+    }
+}
+
+fn main() -> i32 {
+    let mut reg = 0;
+    let p = &mut reg as *mut i32;
+    let status: u8 = 3;
+    let s = &status as *const u8;
+    unsafe {
+        write_volatile(p, 40);
+        std::ptr::write_volatile(p, read_volatile(p) + 1);
+    }
+    println!("{} {}", poll(s), reg);
+    reg + 10
+}
@@ -0,0 +1,10 @@
+// EXITCODE: 45
+pub fn main() -> i32 {
+    let mut sum = 0;
+    let mut i = 0;
+    while i < 10 {
+        sum += i;
+        i += 1;
+    }
+    sum
+}
@@ -0,0 +1,181 @@
+//! Golden tests of the programs in `src/tests/fixtures`, whose expectations are written in
+//! their comments:
+//!
+//! ```text
+//! // ERROR: <message>   compiling fails with an error which contains <message>
+//! // ERROR[E0425]: ...  the error also has the code E0425
+//! // EXITCODE: <n>      compiling succeeds and the program exits with <n> (modulo 256)
+//! // STDOUT: <line>     with EXITCODE, the next line the program prints is <line>
+//! ```
+//!
+//! A program without annotations must compile as an executable, with `main` as its entry.
+//! Programs are run by the IR interpreter with runtime checks, and a panic exits with 101 like
+//! rustc's binaries. All the mismatches are reported at once.
+use crate::code_gen::TargetPlatform;
+use crate::ir::interp::Interpreter;
+use crate::ir::tests::ir_build_checked;
+use crate::ir::Operand;
+use crate::rcc::{OptimizeLevel, RcCompiler, RccError};
+
+const FIXTURES_DIR: &str = "./src/tests/fixtures";
+const PANIC_EXIT_CODE: i64 = 101;
+
+pub(super) enum Expectation {
+    Error { code: Option<String>, message: String },
+    ExitCode(i64),
+    Compiles,
+}
+
+pub(super) fn parse_expectation(source: &str) -> Result<Expectation, String> {
+    for line in source.lines() {
+        let line = line.trim_start();
+        if let Some(rest) = line.strip_prefix("// ERROR") {
+            let (code, message) = match rest.strip_prefix('[').and_then(|s| s.split_once("]:")) {
+                Some((code, message)) => (Some(code.to_string()), message),
+                None => match rest.strip_prefix(':') {
+                    Some(message) => (None, message),
+                    None => return Err(format!("invalid annotation `{}`", line)),
+                },
+            };
+            let message = message.trim().to_string();
+            return Ok(Expectation::Error { code, message });
+        }
+        if let Some(code) = line.strip_prefix("// EXITCODE:") {
+            return code
+                .trim()
+                .parse()
+                .map(Expectation::ExitCode)
+                .map_err(|_| format!("invalid exit code `{}`", code.trim()));
+        }
+    }
+    Ok(Expectation::Compiles)
+}
+
+/// Lines of the `// STDOUT:` annotations, each ends with `\n`
+pub(super) fn expected_stdout(source: &str) -> String {
+    let mut stdout = String::new();
+    for line in source.lines() {
+        if let Some(line) = line.trim_start().strip_prefix("// STDOUT:") {
+            stdout.push_str(line.strip_prefix(' ').unwrap_or(line));
+            stdout.push('\n');
+        }
+    }
+    stdout
+}
+
+/// Paths of the fixtures in order
+pub(super) fn fixtures() -> Vec<std::path::PathBuf> {
+    let mut paths: Vec<_> = std::fs::read_dir(FIXTURES_DIR)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "rs"))
+        .collect();
+    paths.sort();
+    assert!(!paths.is_empty());
+    paths
+}
+
+fn compile(source: &str) -> Result<(), RccError> {
+    let mut rcc = RcCompiler::new(
+        TargetPlatform::Riscv32,
+        source.as_bytes(),
+        Vec::<u8>::new(),
+        OptimizeLevel::Zero,
+    );
+    rcc.set_emit_start(true);
+    rcc.compile()
+}
+
+/// Exit code and stdout of the program
+fn run(source: &str) -> Result<(i64, String), RccError> {
+    let ir = ir_build_checked(source)?;
+    let mut interpreter = Interpreter::new(&ir);
+    let exit_code = match interpreter.run() {
+        Ok(Operand::Unit) => 0,
+        Ok(value) => match value.int_value() {
+            Some(n) => n & 0xff,
+            None => return Err(format!("`main` returns {:?}", value).into()),
+        },
+        Err(e) if e.to_string().starts_with("panicked at") => PANIC_EXIT_CODE,
+        Err(e) => return Err(e),
+    };
+    Ok((exit_code, String::from_utf8_lossy(&interpreter.output).into_owned()))
+}
+
+/// `error[E0425]: ...` like rustc
+fn error_header(code: Option<&str>, message: &str) -> String {
+    match code {
+        Some(code) => format!("error[{}]: {}", code, message),
+        None => format!("error: {}", message),
+    }
+}
+
+pub(super) fn diff(expected: &str, actual: &str) -> String {
+    format!("- {}\n+ {}\n", expected, actual)
+}
+
+/// Mismatch between the expected and actual exit code and stdout, if any
+pub(super) fn check_run(source: &str, code: i64, actual: (i64, String)) -> Option<String> {
+    let (exit_code, stdout) = actual;
+    let mut mismatch = String::new();
+    if exit_code != code & 0xff {
+        mismatch.push_str(&diff(
+            &format!("exit code {}", code),
+            &format!("exit code {}", exit_code),
+        ));
+    }
+    let expected = expected_stdout(source);
+    if stdout != expected {
+        mismatch.push_str(&diff(
+            &format!("stdout {:?}", expected),
+            &format!("stdout {:?}", stdout),
+        ));
+    }
+    Some(mismatch).filter(|m| !m.is_empty())
+}
+
+/// Mismatch between the expectation of `source` and what the compiler does, if any
+fn check(source: &str) -> Option<String> {
+    let expectation = match parse_expectation(source) {
+        Ok(expectation) => expectation,
+        Err(e) => return Some(format!("{}\n", e)),
+    };
+    match (expectation, compile(source)) {
+        (Expectation::Error { code, message }, Ok(())) => {
+            Some(diff(&error_header(code.as_deref(), &message), "ok"))
+        }
+        (Expectation::Error { code, message }, Err(e)) => {
+            let actual_code = match &e {
+                RccError::Compile(error) => error.code,
+                _ => None,
+            };
+            let actual = e.to_string();
+            if actual.contains(&message) && (code.is_none() || code.as_deref() == actual_code) {
+                None
+            } else {
+                Some(diff(
+                    &error_header(code.as_deref(), &message),
+                    &error_header(actual_code, &actual),
+                ))
+            }
+        }
+        (_, Err(e)) => Some(diff("ok", &format!("error: {}", e))),
+        (Expectation::ExitCode(code), Ok(())) => match run(source) {
+            Ok(actual) => check_run(source, code, actual),
+            Err(e) => Some(diff(&format!("exit code {}", code), &format!("error: {}", e))),
+        },
+        (Expectation::Compiles, Ok(())) => None,
+    }
+}
+
+#[test]
+fn golden_test() {
+    let mut report = String::new();
+    for path in fixtures().iter() {
+        let source = std::fs::read_to_string(path).unwrap();
+        if let Some(mismatch) = check(&source) {
+            report.push_str(&format!("{}:\n{}", path.display(), mismatch));
+        }
+    }
+    assert!(report.is_empty(), "golden test mismatches:\n{}", report);
+}
@@ -0,0 +1,25 @@
+use crate::intern::Symbol;
+
+#[test]
+fn intern_test() {
+    let a = Symbol::intern("intern_test_a");
+    assert_eq!(a, Symbol::intern("intern_test_a"));
+    assert_ne!(a, Symbol::intern("intern_test_b"));
+    assert_eq!("intern_test_a", a.as_str());
+    assert_eq!(Some(a), Symbol::find("intern_test_a"));
+    assert_eq!(None, Symbol::find("intern_test_c"));
+    assert_eq!("\"intern_test_a\"", format!("{:?}", a));
+}
+
+#[test]
+fn intern_threads_test() {
+    let a = Symbol::intern("intern_threads_test_a");
+    let b = std::thread::spawn(move || {
+        assert_eq!("intern_threads_test_a", a.as_str());
+        Symbol::intern_fmt(format_args!("intern_threads_test_{}", 'b'))
+    })
+    .join()
+    .unwrap();
+    assert_eq!(b, Symbol::intern("intern_threads_test_b"));
+    assert_ne!(a, b);
+}
@@ -0,0 +1,15 @@
+use crate::json::Json;
+use std::str::FromStr;
+
+#[test]
+fn json_test() {
+    let s = r#"{"a":[1,-2.5,true,null],"b":"x\"\nA","c":{}}"#;
+    let json = Json::from_str(s).unwrap();
+    assert_eq!(Some("x\"\nA"), json.get("b").and_then(Json::as_str));
+    assert_eq!(
+        r#"{"a":[1,-2.5,true,null],"b":"x\"\nA","c":{}}"#,
+        json.to_string()
+    );
+    assert!(Json::from_str("[1,").is_err());
+    assert!(Json::from_str("{} x").is_err());
+}
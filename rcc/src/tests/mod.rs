@@ -2,6 +2,18 @@ use std::fmt::Debug;
 use std::fs::File;
 use std::io::Read;
 
+#[cfg(test)]
+mod diagnostic_tests;
+#[cfg(all(test, feature = "exec-tests"))]
+mod exec_tests;
+#[cfg(test)]
+mod diff_tests;
+#[cfg(test)]
+mod golden_tests;
+#[cfg(test)]
+mod intern_tests;
+#[cfg(test)]
+mod json_tests;
 #[cfg(test)]
 mod rcc_tests;
 
@@ -12,6 +24,31 @@ pub fn read_from_file(file_name: &str, path: &str) -> String {
     expected
 }
 
+/// xorshift64* generator of the randomized tests
+#[cfg(test)]
+pub struct Rng(u64);
+
+#[cfg(test)]
+impl Rng {
+    pub fn new(seed: u64) -> Rng {
+        Rng(seed | 1)
+    }
+
+    /// A number in `0..n`
+    pub fn below(&mut self, n: usize) -> usize {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        (self.0.wrapping_mul(0x2545_f491_4f6c_dd1d) >> 32) as usize % n
+    }
+}
+
+/// Value of the environment variable `var` as a number, `default` if it is not set
+#[cfg(test)]
+pub fn env_or(var: &str, default: u64) -> u64 {
+    std::env::var(var).ok().and_then(|s| s.parse().ok()).unwrap_or(default)
+}
+
 #[inline]
 pub fn assert_pretty_fmt_eq<T: Debug + PartialEq>(expected: &str, actual: &T) {
     assert_eq!(expected, format!("{:#?}", actual));
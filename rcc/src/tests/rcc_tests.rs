@@ -1,15 +1,32 @@
+use crate::analyser::cfg::CfgOptions;
 use crate::code_gen::TargetPlatform;
-use crate::rcc::{OptimizeLevel, RcCompiler, RccError};
+use crate::diagnostic::Span;
+use crate::json::Json;
+use crate::lexer::token::LexError;
+use crate::rcc::{
+    write_timings, CompileError, EmitKind, ErrorKind, OptimizeLevel, RcCompiler, RccError,
+    TimePassesFormat,
+};
 use std::io::Read;
+use std::str::FromStr;
+use std::time::Duration;
 
 fn file_path(file_name: &str) -> String {
     format!("./src/tests/{}", file_name)
 }
 
 fn test_compile(input: &str, expected_output: &str) -> Result<(), RccError> {
+    test_compile_on(TargetPlatform::Riscv32, input, expected_output)
+}
+
+fn test_compile_on(
+    target_platform: TargetPlatform,
+    input: &str,
+    expected_output: &str,
+) -> Result<(), RccError> {
     let input = std::fs::File::open(file_path(input))?;
     let output = Vec::<u8>::new();
-    let mut rcc = RcCompiler::new(TargetPlatform::Riscv32, input, output, OptimizeLevel::Zero);
+    let mut rcc = RcCompiler::new(target_platform, input, output, OptimizeLevel::Zero);
 
     rcc.compile()?;
 
@@ -23,11 +40,1483 @@ fn test_compile(input: &str, expected_output: &str) -> Result<(), RccError> {
 
 #[test]
 fn rcc_test_ok() {
-    for i in 1..=5 {
+    for i in [1, 2, 3, 4, 5, 8, 9] {
         test_compile(&format!("in{}.txt", i), &format!("out{}.txt", i)).unwrap();
     }
 }
 
+#[test]
+fn rcc_test_riscv64() {
+    for i in [1, 2, 3, 4, 5, 7, 8, 9] {
+        test_compile_on(
+            TargetPlatform::Riscv64,
+            &format!("in{}.txt", i),
+            &format!("out64_{}.txt", i),
+        )
+        .unwrap();
+    }
+}
+
+#[test]
+fn rcc_test_jobs() {
+    let input = std::fs::read_to_string(file_path("in3.txt")).unwrap();
+    let compile = |jobs: usize| {
+        let mut rcc = RcCompiler::new(
+            TargetPlatform::Riscv64,
+            input.as_bytes(),
+            Vec::<u8>::new(),
+            OptimizeLevel::Zero,
+        );
+        rcc.set_jobs(jobs);
+        rcc.compile().unwrap();
+        std::str::from_utf8(rcc.output.buffer()).unwrap().to_string()
+    };
+    let expected = compile(1);
+    for jobs in [2, 3, 8] {
+        assert_eq!(expected, compile(jobs));
+    }
+}
+
+#[test]
+fn rcc_test_deterministic() {
+    let mut input = String::from("fn main() {\n");
+    for i in 0..12 {
+        input.push_str(&format!("    let a: i32 = {};\n    print!(\"s{}\");\n", i, i));
+    }
+    input.push_str("}\n");
+    let compile = |emit: EmitKind| {
+        let mut rcc = RcCompiler::new(
+            TargetPlatform::Riscv64,
+            input.as_bytes(),
+            Vec::<u8>::new(),
+            OptimizeLevel::One,
+        );
+        rcc.set_emit(emit);
+        rcc.compile().unwrap();
+        std::str::from_utf8(rcc.output.buffer()).unwrap().to_string()
+    };
+
+    for emit in [EmitKind::IR, EmitKind::Asm] {
+        let expected = compile(emit);
+        for _ in 0..8 {
+            assert_eq!(expected, compile(emit));
+        }
+    }
+    // strings are emitted in the order of labels
+    let asm = compile(EmitKind::Asm);
+    let labels: Vec<&str> = asm.lines().filter(|l| l.starts_with(".LC")).collect();
+    let expected: Vec<String> = (0..12).map(|i| format!(".LC{}:", i)).collect();
+    assert_eq!(expected, labels);
+}
+
+#[test]
+fn rcc_test_dead_store() {
+    let compile = |opt_level: OptimizeLevel| {
+        let mut rcc = RcCompiler::new(
+            TargetPlatform::Riscv32,
+            "fn main() -> i32 { let mut a = 1; a = 2; let b = a + 1; a }".as_bytes(),
+            Vec::<u8>::new(),
+            opt_level,
+        );
+        rcc.compile().unwrap();
+        let asm = std::str::from_utf8(rcc.output.buffer()).unwrap().to_string();
+        asm.lines().filter(|l| l.trim_start().starts_with("sw\t")).count()
+    };
+    // `a = 1` and `b` are not stored, nor is `a = 2` since `2` is returned
+    assert_eq!(compile(OptimizeLevel::Zero) - 3, compile(OptimizeLevel::One));
+}
+
+#[test]
+fn rcc_test_switch() {
+    let input = r#"
+        fn f(x: i32) -> i32 {
+            if x == 3 {
+                30
+            } else if x == 1 {
+                10
+            } else if x == 4 {
+                40
+            } else if x == 6 {
+                60
+            } else {
+                0
+            }
+        }
+    "#;
+    let mut rcc = RcCompiler::new(
+        TargetPlatform::Riscv64,
+        input.as_bytes(),
+        Vec::<u8>::new(),
+        OptimizeLevel::Zero,
+    );
+    rcc.compile().unwrap();
+    let asm = std::str::from_utf8(rcc.output.buffer()).unwrap();
+    let expected = "\
+\tli\ta4,1
+\tsub\ta5,a5,a4
+\tli\ta4,6
+\tbgeu\ta5,a4,.L2_5
+\tslli\ta5,a5,3
+\tlui\ta4,%hi(.LSW2_0)
+\taddi\ta4,a4,%lo(.LSW2_0)
+\tadd\ta5,a5,a4
+\tld\ta5,0(a5)
+\tjr\ta5
+\t.section\t.rodata
+\t.align\t3
+.LSW2_0:
+\t.dword\t.L2_2
+\t.dword\t.L2_5
+\t.dword\t.L2_1
+\t.dword\t.L2_3
+\t.dword\t.L2_5
+\t.dword\t.L2_4
+\t.text
+";
+    assert!(asm.contains(expected), "{}", asm);
+}
+
+#[test]
+fn backend_test() {
+    for (name, pointer_width) in [("riscv32", 32), ("riscv64", 64)] {
+        let target = TargetPlatform::from_str(name).unwrap().desc();
+        assert_eq!(name, target.name);
+        assert_eq!(pointer_width, target.pointer_width);
+        assert_eq!("a0", target.ret_reg);
+    }
+}
+
+fn compile_with_start(input: &str) -> Result<String, RccError> {
+    let output = Vec::<u8>::new();
+    let mut rcc = RcCompiler::new(
+        TargetPlatform::Riscv32,
+        input.as_bytes(),
+        output,
+        OptimizeLevel::Zero,
+    );
+    rcc.set_emit_start(true);
+    rcc.compile()?;
+    Ok(std::str::from_utf8(rcc.output.buffer()).unwrap().to_string())
+}
+
+#[test]
+fn rcc_test_start() {
+    let asm = compile_with_start("pub fn main() -> i32 { 42 }").unwrap();
+    assert!(asm.ends_with(
+        "\t.globl  _start\n_start:\n\tcall\tmain\n\tli\ta7,93\n\tecall\n"
+    ));
+
+    // exit code of `fn main()` is 0
+    let asm = compile_with_start("fn main() {}").unwrap();
+    assert!(asm.ends_with("\tcall\tmain\n\tli\ta0,0\n\tli\ta7,93\n\tecall\n"));
+
+    assert_eq!(
+        Err(CompileError::new(ErrorKind::Resolve, "`main` function not found")
+            .code("E0601")
+            .help("consider adding a `main` function")
+            .into()),
+        compile_with_start("fn foo() {}")
+    );
+    let msg = "`main` function has wrong type: expected `fn()`, found `fn(i32, bool)`";
+    assert_eq!(
+        Err(CompileError::new(ErrorKind::Type, msg)
+            .code("E0580")
+            .span(Span::new(0, 24))
+            .into()),
+        compile_with_start("fn main(a: i32, b: bool) {}")
+    );
+    assert_eq!(
+        Err(CompileError::new(ErrorKind::Type, "`main` has invalid return type `bool`")
+            .code("E0277")
+            .span(Span::new(4, 21))
+            .note("the return value of `main` is the exit status of the program")
+            .help("consider using `()` or `i32` as the return type of `main`")
+            .into()),
+        compile_with_start("pub fn main() -> bool { true }")
+    );
+    assert!(compile_with_start("fn main() -> ! { loop {} }").is_ok());
+}
+
+#[test]
+fn rcc_test_checks() {
+    let compile = |checks: bool| {
+        let mut rcc = RcCompiler::new(
+            TargetPlatform::Riscv32,
+            "fn div(a: i32, b: i32) -> i32 { a / b }".as_bytes(),
+            Vec::<u8>::new(),
+            OptimizeLevel::Zero,
+        );
+        rcc.set_checks(checks);
+        rcc.compile().unwrap();
+        std::str::from_utf8(rcc.output.buffer()).unwrap().to_string()
+    };
+
+    let asm = compile(true);
+    assert!(asm.contains(
+        "\tlw\ta4,-16(s0)\n\tli\ta5,0\n\tbne\ta5,a4,.L2_2\n\
+         .L2_1:\n\tlui\ta0,%hi(.LC0)\n\taddi\ta0,a0,%lo(.LC0)\n\tcall\t__rcc_panic\n\
+         .L2_2:\n"
+    ));
+    let msg = "panicked at 'attempt to divide by zero', line 1 in `div`";
+    assert!(asm.contains(&format!("\t.string \"{}\\n\"", msg)));
+    assert!(asm.ends_with("\tli\ta0,101\n\tli\ta7,93\n\tecall\n"));
+
+    let asm = compile(false);
+    assert!(!asm.contains("__rcc_panic"));
+}
+
+#[test]
+fn rcc_test_overflow_checks() {
+    let compile = |overflow_checks: bool| {
+        let mut rcc = RcCompiler::new(
+            TargetPlatform::Riscv32,
+            "fn sub(a: u32, b: u32) -> u32 { a - b }".as_bytes(),
+            Vec::<u8>::new(),
+            OptimizeLevel::Zero,
+        );
+        rcc.set_overflow_checks(overflow_checks);
+        rcc.compile().unwrap();
+        std::str::from_utf8(rcc.output.buffer()).unwrap().to_string()
+    };
+
+    // unsigned operands are compared by `bleu`
+    let asm = compile(true);
+    assert!(asm.contains(
+        "\tlw\ta4,-12(s0)\n\tlw\ta5,-16(s0)\n\tbleu\ta5,a4,.L2_2\n\
+         .L2_1:\n\tlui\ta0,%hi(.LC0)\n\taddi\ta0,a0,%lo(.LC0)\n\tcall\t__rcc_panic\n\
+         .L2_2:\n"
+    ));
+    let msg = "panicked at 'attempt to subtract with overflow', line 1 in `sub`";
+    assert!(asm.contains(&format!("\t.string \"{}\\n\"", msg)));
+
+    let asm = compile(false);
+    assert!(!asm.contains("__rcc_panic"));
+}
+
+#[test]
+fn rcc_test_assert() {
+    let mut rcc = RcCompiler::new(
+        TargetPlatform::Riscv32,
+        "fn f(a: i32) {\n    assert_eq!(a, 3);\n}".as_bytes(),
+        Vec::<u8>::new(),
+        OptimizeLevel::Zero,
+    );
+    // assertions are kept without runtime checks
+    rcc.set_checks(false);
+    rcc.compile().unwrap();
+    let asm = std::str::from_utf8(rcc.output.buffer()).unwrap();
+    assert!(asm.contains(
+        "\tlw\ta4,-12(s0)\n\tli\ta5,3\n\tbeq\ta5,a4,.L2_2\n\
+         .L2_1:\n\tlui\ta0,%hi(.LC0)\n\taddi\ta0,a0,%lo(.LC0)\n\tcall\t__rcc_panic\n\
+         .L2_2:\n"
+    ));
+    let msg = "panicked at 'assertion failed: a == 3', line 2 in `f`";
+    assert!(asm.contains(&format!("\t.string \"{}\\n\"", msg)));
+}
+
+#[test]
+fn rcc_test_un_op() {
+    let input = "fn neg(a: i32) -> i32 { -a } fn not(a: u8) -> u8 { !a } \
+                 fn not_bool(a: bool) -> bool { !a }";
+    let mut rcc = RcCompiler::new(
+        TargetPlatform::Riscv32,
+        input.as_bytes(),
+        Vec::<u8>::new(),
+        OptimizeLevel::Zero,
+    );
+    rcc.set_overflow_checks(true);
+    rcc.compile().unwrap();
+    let asm = std::str::from_utf8(rcc.output.buffer()).unwrap();
+    assert!(asm.contains("\tli\ta5,-2147483648\n\tbne\ta5,a4,.L2_2\n"));
+    assert!(asm.contains("\tlw\ta5,-12(s0)\n\tneg\ta5,a5\n"));
+    assert!(asm.contains("\tlbu\ta5,-5(s0)\n\tnot\ta5,a5\n\tsb\ta5,-6(s0)\n"));
+    assert!(asm.contains("\tlbu\ta5,-5(s0)\n\tseqz\ta5,a5\n\tsb\ta5,-6(s0)\n"));
+    let msg = "panicked at 'attempt to negate with overflow', line 1 in `neg`";
+    assert!(asm.contains(&format!("\t.string \"{}\\n\"", msg)));
+}
+
+#[test]
+fn rcc_test_cmp_value() {
+    let input = "fn lt(a: i32, b: i32) -> bool { a < b } fn ge(a: u32, b: u32) -> bool { a >= b } \
+                 fn eq(a: u8, b: u8) -> bool { a == b }";
+    let mut rcc = RcCompiler::new(
+        TargetPlatform::Riscv32,
+        input.as_bytes(),
+        Vec::<u8>::new(),
+        OptimizeLevel::Zero,
+    );
+    rcc.compile().unwrap();
+    let asm = std::str::from_utf8(rcc.output.buffer()).unwrap();
+    assert!(asm.contains("\tlw\ta4,-8(s0)\n\tlw\ta5,-12(s0)\n\tslt\ta5,a4,a5\n\tsb\ta5,-13(s0)\n"));
+    assert!(asm.contains("\tsltu\ta5,a4,a5\n\txori\ta5,a5,1\n\tsb\ta5,-13(s0)\n"));
+    assert!(asm.contains("\tlbu\ta5,-6(s0)\n\txor\ta5,a4,a5\n\tseqz\ta5,a5\n\tsb\ta5,-7(s0)\n"));
+}
+
+#[test]
+fn rcc_test_load_store_width() {
+    let input = "fn f(p: *mut u8, q: *mut i16) -> i16 { unsafe { *p = 1; *q } }";
+    let mut rcc = RcCompiler::new(
+        TargetPlatform::Riscv32,
+        input.as_bytes(),
+        Vec::<u8>::new(),
+        OptimizeLevel::Zero,
+    );
+    rcc.compile().unwrap();
+    let asm = std::str::from_utf8(rcc.output.buffer()).unwrap();
+    // the widths are the types of the store and the load
+    assert!(asm.contains("\tli\ta5,1\n\tlw\ta4,-8(s0)\n\tsb\ta5,0(a4)\n"));
+    assert!(asm.contains("\tlw\ta4,-12(s0)\n\tlh\ta5,0(a4)\n"));
+}
+
+#[test]
+fn rcc_test_assign_infers_u64() {
+    let input = "fn returns_u64() -> u64 { 1_000_000_000_000 } \
+                 fn main() { let mut v = 0; v = returns_u64(); }";
+    let compile = |target_platform: TargetPlatform| -> Result<String, RccError> {
+        let mut rcc = RcCompiler::new(
+            target_platform,
+            input.as_bytes(),
+            Vec::<u8>::new(),
+            OptimizeLevel::Zero,
+        );
+        rcc.compile()?;
+        Ok(String::from_utf8(rcc.output.buffer().to_vec()).unwrap())
+    };
+    // `v` is a `u64` stored with `sd`, after its initial value `0`
+    let asm = compile(TargetPlatform::Riscv64).unwrap();
+    assert!(asm.contains(
+        "\tli\ta5,0\n\tsd\ta5,-24(s0)\n\tcall\treturns_u64\n\tmv\ta5,a0\n\tsd\ta5,-24(s0)\n"
+    ));
+    // a `u64` does not fit in a register of riscv32
+    let error = compile(TargetPlatform::Riscv32).unwrap_err();
+    assert!(error.to_string().contains("`u64` is not supported by the 32-bit target"));
+}
+
+#[test]
+fn rcc_test_debug_info() {
+    // two files compiled as one text, `b.rs` starts at line 4
+    let input = "fn add(a: i32, b: i32) -> i32 {\n    a + b\n}\n\
+                 fn main() -> i32 {\n    let x = 1;\n\n    add(x, 2)\n}\n";
+    let compile = |emit: EmitKind, debug_files: Vec<(String, u32)>| {
+        let mut rcc = RcCompiler::new(
+            TargetPlatform::Riscv32,
+            input.as_bytes(),
+            Vec::<u8>::new(),
+            OptimizeLevel::Zero,
+        );
+        rcc.set_emit(emit);
+        rcc.set_debug_files(debug_files);
+        rcc.compile().unwrap();
+        std::str::from_utf8(rcc.output.buffer()).unwrap().to_string()
+    };
+    let files = vec![("a.rs".to_string(), 1), ("b.rs".to_string(), 4)];
+
+    let ir = compile(EmitKind::IR, files.clone());
+    assert!(ir.contains("    (1) loc 5\n    (2) x_3:i32 = 1i32\n    (3) loc 7\n"));
+    let asm = compile(EmitKind::Asm, files);
+    assert!(asm.starts_with("\t.file\t1 \"a.rs\"\n\t.file\t2 \"b.rs\"\n"));
+    assert!(asm.contains("\t.loc\t1 2\n\tlw\ta4,-8(s0)\n"));
+    assert!(asm.contains("\t.loc\t2 2\n\tli\ta5,1\n"));
+    assert!(asm.contains("\t.loc\t2 4\n\tlw\ta0,-12(s0)\n"));
+    // the code is the same without debug info
+    let without_loc: String = asm
+        .lines()
+        .filter(|l| !l.starts_with("\t.loc") && !l.starts_with("\t.file"))
+        .map(|l| format!("{}\n", l))
+        .collect();
+    assert_eq!(compile(EmitKind::Asm, vec![]), without_loc);
+    assert!(!compile(EmitKind::IR, vec![]).contains("loc"));
+}
+
+#[test]
+fn rcc_test_asm() {
+    let compile = |input: &str| -> Result<String, RccError> {
+        let mut rcc = RcCompiler::new(
+            TargetPlatform::Riscv32,
+            input.as_bytes(),
+            Vec::<u8>::new(),
+            OptimizeLevel::Zero,
+        );
+        rcc.compile()?;
+        Ok(std::str::from_utf8(rcc.output.buffer()).unwrap().to_string())
+    };
+    let asm = compile(
+        r#"
+        fn main() -> i32 {
+            let a: usize = 7;
+            unsafe { asm!("csrw mscratch, {}", "nop", in(reg) a); }
+            let mut b: i32 = 0;
+            unsafe { asm!("addi {1}, {0}, 1", in(reg) 41, out(reg) b); }
+            b
+        }
+    "#,
+    )
+    .unwrap();
+    assert!(asm.contains("\tlw\tt0,-8(s0)\n\tcsrw mscratch, t0\n\tnop\n"));
+    assert!(asm.contains("\tli\tt0,41\n\taddi t1, t0, 1\n\tsw\tt1,-12(s0)\n"));
+
+    // callee-saved registers named by the template are saved below old fp
+    let asm = compile(
+        r#"
+        fn main() {
+            unsafe { asm!("mv s1, {0}", "addi s10, s1, 1", "li s1, 0", in(reg) 1); }
+        }
+    "#,
+    )
+    .unwrap();
+    assert!(asm.contains("\tsw\ts0,12(sp)\n\tsw\ts1,8(sp)\n\tsw\ts10,4(sp)\n\taddi\ts0,sp,16\n"));
+    assert!(asm.contains("\tlw\ts1,8(sp)\n\tlw\ts10,4(sp)\n\tlw\ts0,12(sp)\n"));
+
+    for (input, expected) in [
+        (
+            r#"unsafe fn f() { asm!("{}{}{}{}{}{}{}{}", in(reg) 0, in(reg) 1, in(reg) 2,
+                in(reg) 3, in(reg) 4, in(reg) 5, in(reg) 6, in(reg) 7); }"#,
+            "`asm!` has more than 7 operands",
+        ),
+        (
+            r#"fn main() { let b = 0; unsafe { asm!("li {}, 1", out(reg) b) } }"#,
+            "output operand of `asm!` is not a mutable place",
+        ),
+    ] {
+        assert_eq!(Err(expected.into()), compile(input));
+    }
+}
+
+#[test]
+fn rcc_test_no_m_extension() {
+    let input = r#"
+        fn f(a: i32, b: i32) -> i32 {
+            a * b + a / b
+        }
+        fn g(a: u32, b: u32) -> u32 {
+            a % b * b
+        }
+        fn main() -> i32 {
+            let c = g(7, 3);
+            f(7, -2)
+        }
+    "#;
+    let compile = |target: TargetPlatform, m_extension: bool| {
+        let mut rcc =
+            RcCompiler::new(target, input.as_bytes(), Vec::<u8>::new(), OptimizeLevel::Zero);
+        rcc.set_m_extension(m_extension);
+        rcc.compile().unwrap();
+        std::str::from_utf8(rcc.output.buffer()).unwrap().to_string()
+    };
+    let asm = compile(TargetPlatform::Riscv32, true);
+    assert!(asm.contains("\tmul\ta5,a4,a5\n") && asm.contains("\tremu\ta5,a4,a5\n"));
+    assert!(!asm.contains("__mulsi3"));
+
+    let asm = compile(TargetPlatform::Riscv32, false);
+    for inst in ["\tmul", "\tdiv", "\trem"] {
+        assert!(!asm.contains(inst), "{}", inst);
+    }
+    // `f` calls the routines, so ra is saved
+    assert!(asm.starts_with("\t.text\nf:\n\taddi\tsp,sp,-32\n\tsw\tra,28(sp)\n"));
+    assert!(asm.contains("\tmv\ta0,a4\n\tmv\ta1,a5\n\tcall\t__mulsi3\n\tmv\ta5,a0\n"));
+    // each routine is emitted once, in order of first use
+    let routines: Vec<&str> = asm.lines().filter(|l| l.starts_with("__")).collect();
+    assert_eq!(
+        vec!["__mulsi3:", "__divsi3:", "__umodsi3:", "__rcc_mul:", "__rcc_udivmod:"],
+        routines
+    );
+
+    let asm = compile(TargetPlatform::Riscv64, false);
+    assert!(asm.contains("\tcall\t__muldi3\n") && asm.contains("\n__umoddi3:\n"));
+}
+
+/// a0 returned by the RV32 runtime routine `routine` in `asm` called with a0 and a1, by
+/// interpreting the few instructions which the routines use
+fn call_routine(asm: &str, routine: &str, a0: i32, a1: i32) -> i32 {
+    let lines: Vec<&str> = asm.lines().map(str::trim).collect();
+    let find = |label: &str| lines.iter().position(|l| *l == label).unwrap();
+    // `1b` and `1f` are the nearest local labels `1:` before and after the line `pc`
+    let target = |label: &str, pc: usize| -> usize {
+        let local = format!("{}:", &label[..label.len() - 1]);
+        match label.chars().last() {
+            Some('b') if label.len() > 1 && label[..label.len() - 1].parse::<u32>().is_ok() => {
+                (0..pc).rev().find(|i| lines[*i] == local).unwrap()
+            }
+            Some('f') if label.len() > 1 && label[..label.len() - 1].parse::<u32>().is_ok() => {
+                (pc..lines.len()).find(|i| lines[*i] == local).unwrap()
+            }
+            _ => find(&format!("{}:", label)),
+        }
+    };
+    let mut regs: std::collections::HashMap<&str, u32> = std::collections::HashMap::new();
+    regs.insert("a0", a0 as u32);
+    regs.insert("a1", a1 as u32);
+    regs.insert("ra", u32::MAX);
+    let mut pc = find(&format!("{}:", routine));
+    while pc != u32::MAX as usize {
+        let (op, args) = lines[pc].split_once('\t').unwrap_or((lines[pc], ""));
+        let args: Vec<&str> = args.split(',').collect();
+        let reg = |regs: &std::collections::HashMap<&str, u32>, i: usize| -> u32 {
+            regs.get(args[i]).copied().unwrap_or(0)
+        };
+        let imm = |i: usize| -> u32 { args[i].parse::<i32>().unwrap() as u32 };
+        pc += 1;
+        let value = match op {
+            _ if op.ends_with(':') => continue,
+            "mv" => reg(&regs, 1),
+            "li" => imm(1),
+            "neg" => reg(&regs, 1).wrapping_neg(),
+            "add" => reg(&regs, 1).wrapping_add(reg(&regs, 2)),
+            "sub" => reg(&regs, 1).wrapping_sub(reg(&regs, 2)),
+            "or" => reg(&regs, 1) | reg(&regs, 2),
+            "xor" => reg(&regs, 1) ^ reg(&regs, 2),
+            "andi" => reg(&regs, 1) & imm(2),
+            "slli" => reg(&regs, 1) << imm(2),
+            "srli" => reg(&regs, 1) >> imm(2),
+            "jal" => {
+                let ret = pc as u32;
+                pc = target(args[1], pc);
+                ret
+            }
+            _ => {
+                let (taken, label) = match op {
+                    "j" => (true, args[0]),
+                    "beqz" => (reg(&regs, 0) == 0, args[1]),
+                    "bnez" => (reg(&regs, 0) != 0, args[1]),
+                    "bgez" => ((reg(&regs, 0) as i32) >= 0, args[1]),
+                    "bltz" => ((reg(&regs, 0) as i32) < 0, args[1]),
+                    "bgeu" => (reg(&regs, 0) >= reg(&regs, 1), args[2]),
+                    "bltu" => (reg(&regs, 0) < reg(&regs, 1), args[2]),
+                    "jr" => {
+                        pc = reg(&regs, 0) as usize;
+                        continue;
+                    }
+                    "ret" => {
+                        pc = regs["ra"] as usize;
+                        continue;
+                    }
+                    _ => panic!("unexpected instruction `{}`", lines[pc - 1]),
+                };
+                if taken {
+                    pc = target(label, pc);
+                }
+                continue;
+            }
+        };
+        regs.insert(args[0], value);
+    }
+    regs["a0"] as i32
+}
+
+#[test]
+fn rcc_test_soft_routines() {
+    let input = "fn f(a: i32, b: i32) -> i32 { a * b + a / b + a % b } \
+                 fn g(a: u32, b: u32) -> u32 { a / b + a % b }";
+    let mut rcc = RcCompiler::new(
+        TargetPlatform::Riscv32,
+        input.as_bytes(),
+        Vec::<u8>::new(),
+        OptimizeLevel::Zero,
+    );
+    rcc.set_m_extension(false);
+    rcc.compile().unwrap();
+    let asm = std::str::from_utf8(rcc.output.buffer()).unwrap();
+    let call = |routine: &str, a: i32, b: i32| call_routine(asm, routine, a, b);
+    assert_eq!(-14, call("__mulsi3", 7, -2));
+    assert_eq!(42, call("__mulsi3", -6, -7));
+    // signed division truncates toward zero, the remainder has the sign of the dividend
+    assert_eq!((-3, -1), (call("__divsi3", -7, 2), call("__modsi3", -7, 2)));
+    assert_eq!((-3, 1), (call("__divsi3", 7, -2), call("__modsi3", 7, -2)));
+    assert_eq!((3, -1), (call("__divsi3", -7, -2), call("__modsi3", -7, -2)));
+    // unsigned operands are never negated
+    assert_eq!((3, 1), (call("__udivsi3", 7, 2), call("__umodsi3", 7, 2)));
+    assert_eq!(i32::MAX, call("__udivsi3", -1, 2));
+    assert_eq!((0x7fff_fffe, 3), (call("__udivsi3", -4, 2), call("__umodsi3", -1, 4)));
+    // division by zero gives the results of `div`, `divu`, `rem` and `remu`
+    assert_eq!((-1, 7), (call("__divsi3", 7, 0), call("__modsi3", 7, 0)));
+    assert_eq!((-1, 7), (call("__udivsi3", 7, 0), call("__umodsi3", 7, 0)));
+}
+
+#[test]
+fn rcc_test_memcpy() {
+    let compile = |input: &str| {
+        let mut rcc = RcCompiler::new(
+            TargetPlatform::Riscv64,
+            input.as_bytes(),
+            Vec::<u8>::new(),
+            OptimizeLevel::Zero,
+        );
+        rcc.compile().unwrap();
+        std::str::from_utf8(rcc.output.buffer()).unwrap().to_string()
+    };
+    // the fields of `s` and `t` are laid out like `S`, 40 bytes are copied by double words
+    let asm = compile(
+        r#"
+        struct P { x: i64, y: i64 }
+        struct S { a: P, b: P, c: i32 }
+        fn main() -> i32 {
+            let s = S { a: P { x: 1, y: 2 }, b: P { x: 3, y: 4 }, c: 5 };
+            let t = s;
+            t.c
+        }
+    "#,
+    );
+    assert!(asm.contains("\tsd\ta5,-48(s0)\n\tli\ta5,2\n\tsd\ta5,-40(s0)\n"));
+    assert!(asm.contains(
+        "\taddi\ta5,s0,-48\n\taddi\ta4,s0,-88\n\tli\ta3,5\n1:\n\tld\ta2,0(a5)\n\tsd\ta2,0(a4)\n"
+    ));
+    assert!(!asm.contains("__rcc_memcpy"));
+
+    // a large copy calls the runtime, so ra is saved
+    let asm = compile(
+        r#"
+        struct B { a: i64, b: i64, c: i64, d: i64, e: i64, f: i64, g: i64, h: i64, i: i32 }
+        fn main() -> i32 {
+            let x = B { a: 1, b: 2, c: 3, d: 4, e: 5, f: 6, g: 7, h: 8, i: 9 };
+            let mut y = B { a: 0, b: 0, c: 0, d: 0, e: 0, f: 0, g: 0, h: 0, i: 0 };
+            y = x;
+            y.i
+        }
+    "#,
+    );
+    assert!(asm.contains("\tsd\tra,"));
+    assert!(asm.contains("\tli\ta2,72\n\tcall\t__rcc_memcpy\n"));
+    assert_eq!(1, asm.matches("\n__rcc_memcpy:\n").count());
+}
+
+#[test]
+fn rcc_test_stack_arrays() {
+    let input = r#"
+        fn main() -> i32 {
+            let a = [1, 2, 3];
+            let b = [7u8; 4096];
+            b[1] as i32 + a[2]
+        }
+    "#;
+    let mut rcc = RcCompiler::new(
+        TargetPlatform::Riscv32,
+        input.as_bytes(),
+        Vec::<u8>::new(),
+        OptimizeLevel::Zero,
+    );
+    rcc.compile().unwrap();
+    let asm = std::str::from_utf8(rcc.output.buffer()).unwrap();
+    assert!(!asm.contains("__rcc_alloc"));
+    // the frame is too large for `addi`
+    assert!(asm.contains("main:\n\tli\tt0,4168\n\tsub\tsp,sp,t0\n"));
+    assert!(asm.contains("\tli\tt0,4168\n\tadd\tt0,sp,t0\n\tsw\ts0,-4(t0)\n\tmv\ts0,t0\n"));
+    // a = &$0, b = &$1 after the scalars
+    assert!(asm.contains("\taddi\ta5,s0,-72\n\tsw\ta5,-8(s0)\n"));
+    assert!(asm.contains("\tli\tt0,4168\n\tsub\ta5,s0,t0\n\tsw\ta5,-20(s0)\n"));
+    assert!(asm.contains("\tmv\tt0,s0\n\tlw\ts0,-4(t0)\n\tmv\tsp,t0\n\tret\n"));
+}
+
+#[test]
+fn rcc_test_pic() {
+    let input = r#"
+        fn main() -> i32 {
+            let x = 2;
+            println!("hi");
+            if x == 0 {
+                1
+            } else if x == 1 {
+                2
+            } else if x == 2 {
+                3
+            } else if x == 3 {
+                4
+            } else {
+                5
+            }
+        }
+    "#;
+    let compile = |pic: bool| {
+        let mut rcc = RcCompiler::new(
+            TargetPlatform::Riscv64,
+            input.as_bytes(),
+            Vec::<u8>::new(),
+            OptimizeLevel::Zero,
+        );
+        rcc.set_emit_start(true);
+        rcc.set_stack_size(Some(4096));
+        rcc.set_pic(pic);
+        rcc.compile().unwrap();
+        std::str::from_utf8(rcc.output.buffer()).unwrap().to_string()
+    };
+    let asm = compile(false);
+    assert!(asm.contains("\tlui\ta0,%hi(.LC0)\n") && asm.contains("\t.dword\t.L2_"));
+
+    let asm = compile(true);
+    assert!(!asm.contains("%hi(") && !asm.contains(".dword"));
+    assert!(asm.contains(
+        "\n.LPC2_0:\n\tauipc\ta0,%pcrel_hi(.LC0)\n\taddi\ta0,a0,%pcrel_lo(.LPC2_0)\n"
+    ));
+    // the entries of the jump table are offsets from the table
+    assert!(asm.contains("\tlw\ta5,0(a5)\n\tadd\ta5,a5,a4\n\tjr\ta5\n"));
+    assert!(asm.contains("\t.word\t.L2_2-.LSW2_0\n"));
+    // labels of the runtime are in scope 0
+    assert!(asm.contains("\tauipc\tsp,%pcrel_hi(__rcc_stack_top)\n"));
+}
+
+#[test]
+fn rcc_test_link_section() {
+    let input = r#"
+        #[link_section = ".fast_ram"]
+        #[no_mangle]
+        fn handler(x: i32) -> i32 {
+            if x == 0 {
+                1
+            } else if x == 1 {
+                2
+            } else if x == 2 {
+                3
+            } else if x == 3 {
+                4
+            } else {
+                5
+            }
+        }
+        fn main() -> i32 {
+            handler(2)
+        }
+    "#;
+    let mut rcc = RcCompiler::new(
+        TargetPlatform::Riscv32,
+        input.as_bytes(),
+        Vec::<u8>::new(),
+        OptimizeLevel::Zero,
+    );
+    rcc.compile().unwrap();
+    let asm = std::str::from_utf8(rcc.output.buffer()).unwrap();
+    assert!(asm.starts_with(
+        "\t.text\n\t.section\t.fast_ram,\"ax\",@progbits\n\t.globl  handler\nhandler:\n"
+    ));
+    // the code after the jump table is in the section of the function
+    assert!(asm.contains("\t.word\t.L2_4\n\t.section\t.fast_ram,\"ax\",@progbits\n.L2_1:\n"));
+    assert!(asm.contains("\tret\n\t.text\nmain:\n"));
+}
+
+#[test]
+fn rcc_test_cold() {
+    let input = r#"
+        #[cold]
+        fn fail(a: i32) -> i32 {
+            #[no_mangle]
+            fn report() {}
+            report();
+            a
+        }
+        fn main() -> i32 {
+            let mut a = 3;
+            if a > 2 {
+                a = fail(a);
+            }
+            a + 1
+        }
+    "#;
+    let mut rcc = RcCompiler::new(
+        TargetPlatform::Riscv32,
+        input.as_bytes(),
+        Vec::<u8>::new(),
+        OptimizeLevel::One,
+    );
+    rcc.compile().unwrap();
+    let asm = std::str::from_utf8(rcc.output.buffer()).unwrap();
+    // a function declared in a block keeps its name with `#[no_mangle]`
+    assert!(asm.contains("\t.globl  report\nreport:\n") && asm.contains("\tcall\treport\n"));
+    // the call of the cold function is after `ret` of `main`, which jumps to the exit
+    let main = &asm[asm.find("\nmain:\n").unwrap()..];
+    let exit = main.find("\tj\t.L4_3\n.L4_2:\n").unwrap();
+    assert!(main.find("\tcall\tfail\n").unwrap() > exit);
+    assert!(main.contains("\tj\t.L4_1\n.L4_3:\n"));
+}
+
+#[test]
+fn rcc_test_cfg() {
+    let input = r#"
+        #[cfg(target = "riscv64")]
+        fn word() -> usize { 8 }
+        #[cfg(not(target = "riscv64"))]
+        fn word() -> usize { 4 }
+        #[cfg(feature = "double")]
+        fn scale() -> usize { 2 }
+        #[cfg(not(feature = "double"))]
+        fn scale() -> usize { 1 }
+    "#;
+    let compile = |target_platform: TargetPlatform, flags: &[&str]| {
+        let mut rcc = RcCompiler::new(
+            target_platform,
+            input.as_bytes(),
+            Vec::<u8>::new(),
+            OptimizeLevel::Zero,
+        );
+        let mut cfg_options = CfgOptions::new();
+        for flag in flags {
+            cfg_options.insert_flag(flag).unwrap();
+        }
+        rcc.set_cfg_options(cfg_options);
+        rcc.set_emit(EmitKind::IR);
+        rcc.compile().unwrap();
+        std::str::from_utf8(rcc.output.buffer()).unwrap().to_string()
+    };
+    let ir = compile(TargetPlatform::Riscv32, &[]);
+    assert!(ir.contains("ret 4usize") && ir.contains("ret 1usize"));
+    let ir = compile(TargetPlatform::Riscv64, &["feature=\"double\""]);
+    assert!(ir.contains("ret 8usize") && ir.contains("ret 2usize"));
+}
+
+#[test]
+fn rcc_test_stack_check() {
+    let compile = |emit_start: bool, stack_size: Option<u32>| -> Result<String, RccError> {
+        let mut rcc = RcCompiler::new(
+            TargetPlatform::Riscv32,
+            "fn main() -> i32 { 42 }".as_bytes(),
+            Vec::<u8>::new(),
+            OptimizeLevel::Zero,
+        );
+        rcc.set_emit_start(emit_start);
+        rcc.set_stack_check(true);
+        rcc.set_stack_size(stack_size);
+        rcc.compile()?;
+        Ok(std::str::from_utf8(rcc.output.buffer()).unwrap().to_string())
+    };
+
+    let asm = compile(true, Some(4096)).unwrap();
+    assert!(asm.contains(
+        "main:\n\taddi\tsp,sp,-8\n\
+         \tlui\tt0,%hi(__rcc_stack_limit)\n\taddi\tt0,t0,%lo(__rcc_stack_limit)\n\
+         \tbgeu\tsp,t0,1f\n\tj\t__rcc_stack_overflow\n1:\n"
+    ));
+    assert!(asm.contains("__rcc_stack_overflow:\n"));
+    assert!(asm.contains("\t.string \"stack overflow\\n\""));
+    assert!(asm.contains(
+        "_start:\n\tlui\tsp,%hi(__rcc_stack_top)\n\taddi\tsp,sp,%lo(__rcc_stack_top)\n\tcall\tmain\n"
+    ));
+    assert!(asm.ends_with("__rcc_stack_limit:\n\t.zero\t4096\n__rcc_stack_top:\n\t.text\n"));
+
+    // `__rcc_stack_limit` is defined by the linker script
+    let asm = compile(false, None).unwrap();
+    assert!(asm.contains("\tbgeu\tsp,t0,1f\n"));
+    assert!(!asm.contains("__rcc_stack_limit:"));
+
+    assert_eq!(
+        Err("the stack can only be set up by `_start`".into()),
+        compile(false, Some(4096))
+    );
+    assert_eq!(
+        Err("stack size 100 is not a positive multiple of 8".into()),
+        compile(true, Some(100))
+    );
+}
+
+#[test]
+fn rcc_test_extern_c() {
+    let input = r#"
+        extern "C" {
+            #[link_name = "labs"]
+            fn abs64(n: i64) -> i64;
+            fn sleep(seconds: u32) -> u32;
+        }
+
+        pub fn main() -> u32 {
+            let a = abs64(-3);
+            let s = 4000000000u32;
+            sleep(s);
+            sleep(4000000000u32)
+        }
+    "#;
+    let mut rcc = RcCompiler::new(
+        TargetPlatform::Riscv64,
+        input.as_bytes(),
+        Vec::<u8>::new(),
+        OptimizeLevel::Zero,
+    );
+    rcc.compile().unwrap();
+    let asm = std::str::from_utf8(rcc.output.buffer()).unwrap();
+    assert!(asm.contains("\tli\ta0,-3\n\tcall\tlabs\n"));
+    // `u32` arguments are sign-extended
+    assert!(asm.contains("\tlw\ta0,-28(s0)\n\tcall\tsleep\n"));
+    assert!(asm.contains("\tli\ta0,-294967296\n\tcall\tsleep\n"));
+    assert!(asm.contains("\tlw\ta0,-32(s0)\n\tld\tra,24(sp)\n"));
+}
+
+#[test]
+fn rcc_test_box() {
+    let input = r#"
+        pub fn main() -> i32 {
+            let mut b = Box::new(7);
+            *b += 1;
+            *b
+        }
+    "#;
+    let mut rcc = RcCompiler::new(
+        TargetPlatform::Riscv32,
+        input.as_bytes(),
+        Vec::<u8>::new(),
+        OptimizeLevel::Zero,
+    );
+    rcc.compile().unwrap();
+    let asm = std::str::from_utf8(rcc.output.buffer()).unwrap();
+    assert!(asm.contains("\tli\ta0,4\n\tcall\t__rcc_alloc\n"));
+    // *b = 7
+    assert!(asm.contains("\tli\ta5,7\n\tlw\ta4,-12(s0)\n\tsw\ta5,0(a4)\n"));
+    // `b` is freed after `*b` is loaded as the return value
+    assert!(asm.contains("\tlw\ta0,-12(s0)\n\tcall\t__rcc_free\n"));
+    assert!(asm.contains("__rcc_alloc:\n"));
+    assert!(asm.contains("\tlw\ta2,0(a1)\n"));
+    assert!(asm.contains("__rcc_free:\n\tret\n"));
+}
+
+#[test]
+fn rcc_test_escape() {
+    let input = r#"
+        pub fn main() {
+            print!("\"\t\\\u{e9}\n");
+        }
+    "#;
+    let mut rcc = RcCompiler::new(
+        TargetPlatform::Riscv32,
+        input.as_bytes(),
+        Vec::<u8>::new(),
+        OptimizeLevel::Zero,
+    );
+    rcc.compile().unwrap();
+    let asm = std::str::from_utf8(rcc.output.buffer()).unwrap();
+    assert!(asm.contains(r#".LC0:
+	.string "\"\t\\\303\251\n""#));
+}
+
+#[test]
+fn rcc_test_ro_strs() {
+    let input = r#"
+        fn foo() {
+            print!("hi");
+        }
+        pub fn main() {
+            print!("hi");
+            foo();
+            print!("bye");
+        }
+    "#;
+    let mut rcc = RcCompiler::new(
+        TargetPlatform::Riscv32,
+        input.as_bytes(),
+        Vec::<u8>::new(),
+        OptimizeLevel::Zero,
+    );
+    rcc.compile().unwrap();
+    let asm = std::str::from_utf8(rcc.output.buffer()).unwrap();
+    // identical strings share a label, one `.rodata` section is emitted for the module
+    let labels: Vec<&str> = asm.lines().filter(|l| l.starts_with(".LC")).collect();
+    assert_eq!(vec![".LC0:", ".LC1:"], labels);
+    assert_eq!(1, asm.matches(".section\t.rodata").count());
+    assert_eq!(3, asm.matches("%hi(.LC0)").count() + asm.matches("%hi(.LC1)").count());
+}
+
+#[test]
+fn rcc_test_vec() {
+    let input = r#"
+        pub fn main() -> i32 {
+            let mut v: Vec<i32> = Vec::new();
+            v.push(5);
+            v[0]
+        }
+    "#;
+    let mut rcc = RcCompiler::new(
+        TargetPlatform::Riscv64,
+        input.as_bytes(),
+        Vec::<u8>::new(),
+        OptimizeLevel::Zero,
+    );
+    rcc.compile().unwrap();
+    let asm = std::str::from_utf8(rcc.output.buffer()).unwrap();
+    // v.push(5)
+    assert!(asm.contains("\tli\ta1,4\n\tcall\t__rcc_vec_push\n"));
+    // v[0]
+    assert!(asm.contains("\tli\ta1,0\n\tli\ta2,4\n\tcall\t__rcc_vec_index\n"));
+    assert!(asm.contains("\tcall\t__rcc_vec_free\n"));
+    // { data, len, cap } on RV64
+    assert!(asm.contains("__rcc_vec_len:\n\tld\ta0,8(a0)\n\tret\n"));
+    assert!(asm.contains("__rcc_alloc:\n"));
+    assert!(asm.contains("__rcc_panic:\n"));
+}
+
+#[test]
+fn rcc_test_dyn_trait() {
+    let input = r#"
+        trait Show {
+            fn show(&self) -> i32;
+            fn twice(&self) -> i32;
+        }
+        impl Show for i32 {
+            fn show(&self) -> i32 { *self }
+            fn twice(&self) -> i32 { *self * 2 }
+        }
+        fn call(s: &dyn Show) -> i32 { s.twice() }
+        pub fn main() -> i32 {
+            let n = 5;
+            call(&n)
+        }
+    "#;
+    let mut rcc = RcCompiler::new(
+        TargetPlatform::Riscv64,
+        input.as_bytes(),
+        Vec::<u8>::new(),
+        OptimizeLevel::Zero,
+    );
+    rcc.compile().unwrap();
+    let asm = std::str::from_utf8(rcc.output.buffer()).unwrap();
+    assert!(asm.contains(
+        "\t.align\t3\n_RCV3i324Show:\n\t.dword\t_RCM3i324Show4show\n\t.dword\t_RCM3i324Show5twice\n"
+    ));
+    // &n
+    assert!(asm.contains("\taddi\ta5,s0,-28\n"));
+    assert!(asm.contains("\tlui\ta1,%hi(_RCV3i324Show)\n\taddi\ta1,a1,%lo(_RCV3i324Show)\n"));
+    // the second method of the vtable
+    assert!(asm.contains("\taddi\ta5,a5,8\n"));
+    assert!(asm.contains("\tld\tt1,-48(s0)\n\tjalr\tt1\n"));
+}
+
+#[test]
+fn rcc_test_trait() {
+    let input = r#"
+        trait Add { fn add(self, rhs: Self) -> Self; }
+        impl Add for i32 { fn add(self, rhs: Self) -> Self { self + rhs } }
+        fn sum<T: Add>(a: T, b: T) -> T { a.add(b) }
+        pub fn main() -> i32 { sum(1, 2) }
+    "#;
+    let mut rcc = RcCompiler::new(
+        TargetPlatform::Riscv32,
+        input.as_bytes(),
+        Vec::<u8>::new(),
+        OptimizeLevel::Zero,
+    );
+    rcc.compile().unwrap();
+    let asm = std::str::from_utf8(rcc.output.buffer()).unwrap();
+    assert!(asm.contains("\tcall\t_RCG3sum3i32E\n"));
+    // `a.add(b)` is dispatched statically
+    assert!(asm.contains("_RCG3sum3i32E:\n"));
+    assert!(asm.contains("\tcall\t_RCM3i323Add3add\n"));
+    assert!(asm.contains("_RCM3i323Add3add:\n"));
+    assert!(!asm.contains("sum:\n"));
+}
+
+#[test]
+fn rcc_test_operator_overloading() {
+    let input = r#"
+        trait Add { fn add(self, rhs: Self) -> Self; }
+        impl Add for String { fn add(self, rhs: String) -> String { self } }
+        impl Add for i32 { fn add(self, rhs: i32) -> i32 { self - rhs } }
+        pub fn main() -> i32 {
+            let s = String::new() + String::new();
+            let a = 1;
+            a + 2
+        }
+    "#;
+    let mut rcc = RcCompiler::new(
+        TargetPlatform::Riscv32,
+        input.as_bytes(),
+        Vec::<u8>::new(),
+        OptimizeLevel::Zero,
+    );
+    rcc.set_emit(EmitKind::IR);
+    rcc.compile().unwrap();
+    let ir = std::str::from_utf8(rcc.output.buffer()).unwrap();
+    assert!(ir.contains("call @_RCM6String3Add3add("));
+    // `+` of the primitive types is never overloaded
+    assert!(!ir.contains("call @_RCM3i323Add3add("));
+}
+
+#[test]
+fn rcc_test_emit() {
+    let compile = |emit: EmitKind| {
+        let mut rcc = RcCompiler::new(
+            TargetPlatform::Riscv32,
+            "fn main() -> i32 { let a = 1; a + 2 }".as_bytes(),
+            Vec::<u8>::new(),
+            OptimizeLevel::One,
+        );
+        rcc.set_emit(emit);
+        rcc.compile().unwrap();
+        std::str::from_utf8(rcc.output.buffer()).unwrap().to_string()
+    };
+
+    let tokens = compile(EmitKind::Tokens);
+    assert!(tokens.starts_with("Fn\nIdentifier(\"main\")\nLeftParen\nRightParen\n"));
+    assert!(tokens.ends_with("Plus\nLiteral { literal_kind: Integer { suffix: \"\" }, value: \"2\" }\nRightCurlyBraces\n"));
+
+    let ast = compile(EmitKind::Ast);
+    assert!(ast.starts_with("[\n    Fn(\n        ItemFn {\n            vis: Priv,\n            name: \"main\",\n"));
+    assert!(ast.contains("            fn_block: BlockExpr {\n                stmts: [\n"));
+
+    // passes of `-O1` are not run
+    assert_eq!(
+        "fn main() scope 2 {\n    (1) a_2:i32 = 1i32\n    (2) $0_1:i32 = a_2:i32 + 2i32\n    (3) ret $0_1:i32\n}\n",
+        compile(EmitKind::IR)
+    );
+}
+
+#[test]
+fn rcc_test_emit_obj() {
+    let compile = |target: TargetPlatform| {
+        let mut rcc = RcCompiler::new(
+            target,
+            "pub fn main() -> i32 { println!(\"hi\"); 0 }".as_bytes(),
+            Vec::<u8>::new(),
+            OptimizeLevel::Zero,
+        );
+        rcc.set_emit(EmitKind::Obj);
+        rcc.set_emit_start(true);
+        rcc.compile().unwrap();
+        rcc.output.buffer().to_vec()
+    };
+    // ELF32 and ELF64, relocatable, RISC-V
+    for (target, class) in [(TargetPlatform::Riscv32, 1), (TargetPlatform::Riscv64, 2)] {
+        let obj = compile(target);
+        assert_eq!(b"\x7fELF", &obj[..4]);
+        assert_eq!(class, obj[4]);
+        assert_eq!([1, 0, 243, 0], obj[16..20]);
+        let has = |s: &[u8]| obj.windows(s.len()).any(|w| w == s);
+        assert!(has(b"hi\n\0"));
+        assert!(has(b"__rcc_print_str\0") && has(b".rela.text\0"));
+    }
+}
+
+#[test]
+fn rcc_test_check() {
+    let check = |input: &str| -> Result<usize, RccError> {
+        let mut rcc = RcCompiler::new(
+            TargetPlatform::Riscv32,
+            input.as_bytes(),
+            Vec::<u8>::new(),
+            OptimizeLevel::Zero,
+        );
+        rcc.set_check(true);
+        rcc.compile()?;
+        Ok(rcc.output.buffer().len())
+    };
+    assert_eq!(Ok(0), check("fn main() -> i32 { let a = 1; a + 2 }"));
+    assert_eq!(
+        Err(CompileError::new(ErrorKind::Resolve, "identifier `b` not found")
+            .code("E0425")
+            .span(Span::new(30, 31))
+            .help("did you mean `a`?")
+            .into()),
+        check("fn main() -> i32 { let a = 1; b }")
+    );
+    assert_eq!(
+        Err(RccError::Lex(vec![
+            LexError::new("unknown start of token: `~`", 20, 21),
+            LexError::new("unterminated string literal starting here", 31, 32),
+        ])),
+        check(r#"fn main() { let a = ~; let s = "a; }"#)
+    );
+    // the spans point at the mismatching blocks
+    let msg = "`if` and `else` have incompatible types: expected `{integer}`, found `char`";
+    assert_eq!(
+        Err(CompileError::new(ErrorKind::Type, msg)
+            .code("E0308")
+            .span(Span::new(39, 46))
+            .into()),
+        check("fn main() { let a = if true { 1 } else { 'c' }; }")
+    );
+    let msg = "`if` may be missing an `else` clause: expected `()`, found `{integer}`";
+    assert_eq!(
+        Err(CompileError::new(ErrorKind::Type, msg)
+            .code("E0317")
+            .span(Span::new(32, 37))
+            .into()),
+        check("fn main() { let a = 1; if a > 0 { 2 } }")
+    );
+    // the span points at the signature
+    let msg = "missing return value in function `f`: expected `i32`, found `()`";
+    assert_eq!(
+        Err(CompileError::new(ErrorKind::Type, msg)
+            .code("E0308")
+            .span(Span::new(0, 19))
+            .note("the body has no tail expression, and not every path of it returns")
+            .into()),
+        check("fn f(a: i32) -> i32 { if a > 0 { return 1; } } fn main() {}")
+    );
+    // the spans point at both names
+    assert_eq!(
+        Err(CompileError::new(ErrorKind::Resolve, "the name `f` is defined multiple times")
+            .code("E0428")
+            .span(Span::new(17, 18))
+            .note("`f` must be defined only once in this scope")
+            .label(Span::new(3, 4), "previous definition of `f` here")
+            .into()),
+        check("fn f() {} pub fn f() {} fn main() {}")
+    );
+    // a parse error points at the unexpected token
+    assert_eq!(
+        Err(CompileError::new(ErrorKind::Parse, "expected `;`, found `let`")
+            .span(Span::new(22, 25))
+            .into()),
+        check("fn main() { let a = 1 let b = 2; }")
+    );
+    // integer literals are checked against their types
+    assert_eq!(
+        Err(CompileError::new(ErrorKind::Type, "literal out of range for `u8`")
+            .span(Span::new(24, 27))
+            .note("the literal `256` does not fit into the type `u8` whose range is `0..=255`")
+            .into()),
+        check("fn main() { let a: u8 = 256; }")
+    );
+    // the backend keeps every number in one register
+    assert_eq!(
+        Err(CompileError::new(ErrorKind::Type, "`u64` is not supported by the 32-bit target")
+            .span(Span::new(20, 32))
+            .note("numbers wider than a register of the target are not supported yet")
+            .into()),
+        check("fn main() { let a = 1_000_000u64; }")
+    );
+    assert_eq!(Ok(0), check("fn main() { let a = 0x1_i32; }"));
+    assert_eq!(
+        Err(CompileError::new(ErrorKind::Type, "`f64` is not supported by the backend")
+            .span(Span::new(25, 28))
+            .note("floating-point numbers are only evaluated by the IR interpreter yet")
+            .into()),
+        check("fn main() { let a: f64 = 2.0; }")
+    );
+    // the span points at the binary expression
+    let msg = "mismatched types: expected `i32`, found `i64`";
+    assert_eq!(
+        Err(CompileError::new(ErrorKind::Type, msg)
+            .code("E0308")
+            .span(Span::new(34, 42))
+            .note("both operands of `+` must have the same type")
+            .help("numbers are not converted implicitly, consider casting with `as`")
+            .into()),
+        check("fn main() { let a = 1i64; let b = 2i32 + a; }")
+    );
+    // the span points at the argument
+    let msg = "mismatched types in argument 2 of function `f`: expected `&str`, found `bool`";
+    assert_eq!(
+        Err(CompileError::new(ErrorKind::Type, msg)
+            .code("E0308")
+            .span(Span::new(42, 46))
+            .into()),
+        check("fn f(a: i32, s: &str) {} fn main() { f(1, true); }")
+    );
+}
+
+#[test]
+fn rcc_test_time_passes() {
+    let mut rcc = RcCompiler::new(
+        TargetPlatform::Riscv32,
+        "fn main() -> i32 { 42 }".as_bytes(),
+        Vec::<u8>::new(),
+        OptimizeLevel::One,
+    );
+    rcc.compile().unwrap();
+    let stages: Vec<&str> = rcc.timings.iter().map(|(name, _)| name.as_str()).collect();
+    assert_eq!(
+        vec![
+            "lex",
+            "parse",
+            "resolve",
+            "build-ir",
+            "constant-propagation",
+            "simplify-cfg",
+            "cold-block-layout",
+            "reaching-definitions",
+            "dead-store-elimination",
+            "codegen"
+        ],
+        stages
+    );
+
+    let timings = vec![
+        ("lex".to_string(), Duration::from_micros(250)),
+        ("codegen".to_string(), Duration::from_micros(750)),
+    ];
+    let mut out = Vec::<u8>::new();
+    write_timings(&timings, TimePassesFormat::Text, &mut out).unwrap();
+    assert_eq!(
+        "\
+stage                       time(ms)       %
+lex                            0.250    25.0
+codegen                        0.750    75.0
+total                          1.000   100.0
+",
+        std::str::from_utf8(&out).unwrap()
+    );
+    let mut out = Vec::<u8>::new();
+    write_timings(&timings, TimePassesFormat::Json, &mut out).unwrap();
+    assert_eq!(
+        "{\"stages\":[{\"name\":\"lex\",\"micros\":250},{\"name\":\"codegen\",\"micros\":750}],\"total_micros\":1000}\n",
+        std::str::from_utf8(&out).unwrap()
+    );
+}
+
+#[test]
+fn rcc_test_cache() {
+    let dir = std::env::temp_dir().join(format!("rcc_cache_test_{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let compile_at = |opt_level, input: &str, cache_dir: Option<&std::path::Path>| {
+        let mut rcc = RcCompiler::new(
+            TargetPlatform::Riscv32,
+            input.as_bytes(),
+            Vec::<u8>::new(),
+            opt_level,
+        );
+        rcc.set_cache_dir(cache_dir.map(|d| d.to_path_buf()));
+        rcc.compile().unwrap();
+        std::str::from_utf8(rcc.output.buffer()).unwrap().to_string()
+    };
+    let compile = |input: &str, cache_dir| compile_at(OptimizeLevel::Zero, input, cache_dir);
+    let entries = || std::fs::read_dir(&dir).unwrap().count();
+
+    let v1 = r#"
+        fn main() { println!("main {}", foo(1)); }
+        fn foo(a: i32) -> i32 { println!("foo"); a }
+    "#;
+    let expected = compile(v1, None);
+    assert_eq!(expected, compile(v1, Some(&dir)));
+    assert_eq!(2, entries());
+    assert_eq!(expected, compile(v1, Some(&dir)));
+    assert_eq!(2, entries());
+
+    // `main` is reused, the strings of `foo` are numbered after the ones of `main`
+    for entry in std::fs::read_dir(&dir).unwrap() {
+        let path = entry.unwrap().path();
+        let content = std::fs::read_to_string(&path).unwrap();
+        if content.contains("main:") {
+            std::fs::write(&path, content.replace("main:", "main:\\t# cached")).unwrap();
+        }
+    }
+    let v2 = r#"
+        fn main() { println!("main {}", foo(1)); }
+        fn foo(a: i32) -> i32 { println!("foo {}", a); a }
+    "#;
+    let asm = compile(v2, Some(&dir));
+    assert_eq!(3, entries());
+    assert_eq!(compile(v2, None), asm.replace("main:\t# cached", "main:"));
+    assert!(asm.contains("main:\t# cached\n"));
+
+    // `main` declares `bar`, so they are not cached, and the reused `foo` is still written
+    // after them
+    let v3 = r#"
+        fn main() { println!("main {}", foo(bar())); fn bar() -> i32 { 2 } }
+        fn foo(a: i32) -> i32 { println!("foo {}", a); a }
+    "#;
+    let asm = compile(v3, Some(&dir));
+    assert_eq!(asm, compile(v3, Some(&dir)));
+    // only the new entry of `foo`
+    assert_eq!(4, entries());
+    assert_eq!(compile(v3, None), asm);
+    assert!(asm.find("_RCN4main3bar2E:").unwrap() < asm.find("foo:").unwrap());
+
+    // the block of `main` calling `fail` is laid out last once `fail` is cold, so `main`
+    // is not reused
+    let v4 = r#"
+        fn main() { let a = 3; if a > 2 { fail(); } println!("main"); }
+        fn fail() { println!("fail"); }
+    "#;
+    let v5 = v4.replace("fn fail", "#[cold] fn fail");
+    for input in [v4, v5.as_str()] {
+        let expected = compile_at(OptimizeLevel::One, input, None);
+        assert_eq!(expected, compile_at(OptimizeLevel::One, input, Some(&dir)));
+    }
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn rcc_test_c_header() {
+    let compile = |input: &str| -> Result<String, RccError> {
+        let mut rcc = RcCompiler::new(
+            TargetPlatform::Riscv32,
+            input.as_bytes(),
+            Vec::<u8>::new(),
+            OptimizeLevel::Zero,
+        );
+        rcc.set_emit(EmitKind::CHeader);
+        rcc.compile()?;
+        Ok(std::str::from_utf8(rcc.output.buffer()).unwrap().to_string())
+    };
+    let input = r#"
+        pub extern "C" fn add(a: i32, b: i64) -> i64 { b }
+        pub extern "C" fn greet(name: &str, ok: bool) {}
+        pub extern "C" fn sum(a: &[i32], b: &mut [u8]) -> i32 { 0 }
+        pub extern fn len(p: *const u8, n: *mut usize) -> u8 { 0 }
+        extern "C" fn private_fn() {}
+        pub fn rust_fn() {}
+        extern "C" {
+            fn putchar(c: i32) -> i32;
+        }
+        fn main() {}
+    "#;
+    let expected = "\
+/* Generated by rcc. */
+#pragma once
+
+#include <stdbool.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern \"C\" {
+#endif
+
+int64_t add(int32_t a, int64_t b);
+void greet(const char *name, uintptr_t name_len, bool ok);
+int32_t sum(const int32_t *a, uintptr_t a_len, uint8_t *b, uintptr_t b_len);
+uint8_t len(const uint8_t *p, uintptr_t *n);
+
+#ifdef __cplusplus
+}
+#endif
+";
+    assert_eq!(Ok(expected.to_string()), compile(input));
+    assert_eq!(
+        Err("`i128` in `f` can not be represented in C".into()),
+        compile("pub extern \"C\" fn f(n: i128) {}")
+    );
+}
+
+#[test]
+fn rcc_test_json() {
+    let compile = |emit: EmitKind| -> Json {
+        let mut rcc = RcCompiler::new(
+            TargetPlatform::Riscv32,
+            "pub fn main() -> i32 { let s = \"hi\"; 1 + 2 }".as_bytes(),
+            Vec::<u8>::new(),
+            OptimizeLevel::Zero,
+        );
+        rcc.set_emit(emit);
+        rcc.compile().unwrap();
+        Json::from_str(std::str::from_utf8(rcc.output.buffer()).unwrap()).unwrap()
+    };
+
+    let ast = compile(EmitKind::AstJson);
+    let main = match ast.get("items") {
+        Some(Json::Array(items)) => &items[0],
+        items => panic!("{:?}", items),
+    };
+    assert_eq!(Some("Fn"), main.get("kind").and_then(Json::as_str));
+    assert_eq!(Some(&Json::Bool(true)), main.get("pub"));
+    assert_eq!(Some("i32"), main.get("ret_type").and_then(Json::as_str));
+    let expr = main.get("block").and_then(|b| b.get("expr")).unwrap();
+    assert_eq!(Some("BinOp"), expr.get("kind").and_then(Json::as_str));
+    assert_eq!(Some("+"), expr.get("op").and_then(Json::as_str));
+
+    let ir = compile(EmitKind::IRJson);
+    assert_eq!(
+        Ok(ir.get("strs").unwrap().clone()),
+        Json::from_str(r#"[{"label":".LC0","value":"hi"}]"#)
+    );
+    let insts = match ir.get("funcs") {
+        Some(Json::Array(funcs)) => funcs[0].get("insts").unwrap(),
+        funcs => panic!("{:?}", funcs),
+    };
+    assert_eq!(
+        Ok(insts.clone()),
+        Json::from_str(
+            r#"[{"op":"load_data","dest":{"kind":"place","label":"s_2","var_kind":"local","type":"addr"},
+                 "src":{"kind":"place","label":".LC0","var_kind":"lit","type":"char"}},
+                {"op":"load_data","dest":{"kind":"place","label":"s$len_2","var_kind":"local","type":"usize"},
+                 "src":{"kind":"imm","type":"usize","value":"2"}},
+                {"op":"ret","src":{"kind":"imm","type":"i32","value":"3"}}]"#
+        )
+    );
+}
+
 #[test]
 fn rcc_test_error() {
     let errors: [Result<(), RccError>; 1] = [Err("`a_5` may not have definition".into())];